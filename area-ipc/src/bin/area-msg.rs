@@ -0,0 +1,660 @@
+//! area-msg - send commands to a running Area window manager
+//!
+//! Usage: area-msg [--json] <command> [args...]
+
+use area_ipc::{
+    AccessibilityFeature, Client, DpmsState, OsdKind, Direction, PanelEdge, Rect, Reply, SessionAction, ShellCommand, TimingSummary, WallpaperMode, WindowInfo,
+    WindowMenuAction,
+};
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: area-msg [--json] <command> [args...]
+
+Commands:
+  debug dump              Print the in-memory log buffer
+  dump-state [--redact]   Print the window manager's internal state as JSON (--redact: hide window titles)
+  debug level <filter>    Set the log filter (RUST_LOG syntax, e.g. \"area::wm::focus=trace,info\")
+  metrics                 Print frame time, event latency and IPC round-trip statistics
+  self-check [on|off]     Print the compositor's OpenGL setup; on/off shows or hides its test patterns
+  version                 Print the protocol version and capabilities of the window manager
+  windows                 List managed windows
+  info <id>               Print a window's properties (class, role, type, PID, workspace, ...)
+  find [--class <class>] [--title <regex>] [--workspace <n>]
+                          List matching windows, most recently focused first
+  run-or-raise <class> <command>
+                          Focus a window of <class> (the next one if focused), else run <command>
+  focus <id>              Focus and raise a window (id in decimal or 0x-hex)
+  close <id>              Ask a window to close
+  move <id> <x> <y>       Move a window (root coordinates)
+  workspace <n>           Switch to a workspace (0-based)
+  move-to-workspace <id> <n>
+                          Move a window to a workspace
+  minimize <id>           Iconify a window
+  restore <id>            De-iconify a window
+  minimize-group <leader> Iconify every window of a group (leader as shown by windows)
+  restore-group <leader>  De-iconify every window of a group
+  maximize <id> on|off    Maximize or unmaximize a window
+  fullscreen <id> on|off  Enter or leave fullscreen
+  inhibit-shortcuts <id> on|off
+                          Pass all shortcuts to a window while it has focus
+  kill <id> [--force]     Kill a window's client (--force: also signal its process)
+  move-to-monitor <id> left|right|up|down
+                          Move a window to the adjacent monitor
+  menu-action <id> <action>
+                          Run a window menu entry (move|resize|minimize|maximize|above|close|workspace <n>)
+  root-menu <index>       Run a root menu entry (0-based, as listed in show_root_menu events)
+  end-session logout|reboot|shutdown [force]
+                          Close all windows, then end the session (force kills what is left)
+  cancel-end-session      Abort a pending end-session
+  strut <edge> <size> [<start> <end>] [--monitor <n>]
+                          Reserve a panel strut (edge: left|right|top|bottom, size 0 removes it)
+  edge-trigger <edge> on|off [--monitor <n>]
+                          Report pointer pressure against an edge (auto-hidden panel)
+  accessibility <feature> on|off
+                          Toggle sticky-keys|slow-keys|bounce-keys|mouse-keys
+  osd <kind> [<percent>] [<label>]
+                          Show the on-screen display (kind: volume|brightness|keyboard-layout|workspace)
+  dnd on|off              Turn Do Not Disturb on or off (notifications are only recorded)
+  notifications [<limit>] List recorded notifications, newest first
+  mute-audio <id> on|off  Mute or unmute the sound a window plays
+  expo on|off             Show or hide the overview of all workspaces
+  dpms on|standby|suspend|off
+                          Switch the monitors' power state now (input wakes them)
+  activation-token        Print a token letting an application started with DESKTOP_STARTUP_ID=<token> take focus
+  input-region <id> [<W>x<H>+<X>+<Y>...]
+                          Set a window's input region (no rectangles: click-through)
+  preview <id> <W>x<H> [<fps>]
+                          Stream a live miniature of a window; prints the shared-memory file
+  wallpaper <path> [--mode <mode>] [--monitor <n>] [--workspace <n>]
+                          Set the wallpaper (mode: fill|fit|stretch|center|tile)
+  taskbar-order <id>...    Save the taskbar button order
+  pin <desktop-id> [<position>]
+                          Pin an application to the dock (e.g. firefox.desktop)
+  unpin <desktop-id>      Remove an application from the dock
+  pinned                  List pinned applications and their windows
+  components              List supervised session components and their state
+  subscribe               Print window manager events as they happen (one JSON object per line)
+  subscribe pointer       Same, including pointer position updates
+  urgency clear <id>      Clear a window's attention request
+
+Options:
+  --json                  Print the raw JSON reply";
+
+/// Translate command-line arguments into a `ShellCommand`
+fn parse_command(args: &[String]) -> Result<ShellCommand, String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["debug", "dump"] => Ok(ShellCommand::DebugDump),
+        ["dump-state"] => Ok(ShellCommand::DumpState { redact_titles: false }),
+        ["dump-state", "--redact"] => Ok(ShellCommand::DumpState { redact_titles: true }),
+        ["debug", "level", filter] => Ok(ShellCommand::SetLogLevel {
+            filter: (*filter).to_string(),
+        }),
+        ["metrics"] => Ok(ShellCommand::GetMetrics),
+        ["self-check"] => Ok(ShellCommand::SelfCheck { pattern: None }),
+        ["self-check", state] => Ok(ShellCommand::SelfCheck { pattern: Some(parse_on_off(state)?) }),
+        ["version"] => Ok(ShellCommand::Hello {
+            version: area_ipc::PROTOCOL_VERSION.to_string(),
+            capabilities: area_ipc::CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+            token: std::env::var(area_ipc::TOKEN_ENV).ok(),
+        }),
+        ["windows"] => Ok(ShellCommand::GetWindows),
+        ["info", id] => Ok(ShellCommand::GetWindowInfo {
+            id: parse_window_id(id)?,
+        }),
+        ["find", options @ ..] => parse_find(options),
+        ["run-or-raise", class, command @ ..] if !command.is_empty() => Ok(ShellCommand::ActivateOrLaunch {
+            class: (*class).to_string(),
+            exec: command.join(" "),
+        }),
+        ["focus", id] => Ok(ShellCommand::FocusWindow {
+            id: parse_window_id(id)?,
+        }),
+        ["close", id] => Ok(ShellCommand::CloseWindow {
+            id: parse_window_id(id)?,
+        }),
+        ["move", id, x, y] => Ok(ShellCommand::MoveWindow {
+            id: parse_window_id(id)?,
+            x: parse_number(x)?,
+            y: parse_number(y)?,
+        }),
+        ["workspace", workspace] => Ok(ShellCommand::SwitchWorkspace {
+            workspace: parse_number(workspace)?,
+        }),
+        ["move-to-workspace", id, workspace] => Ok(ShellCommand::MoveWindowToWorkspace {
+            id: parse_window_id(id)?,
+            workspace: parse_number(workspace)?,
+        }),
+        ["minimize", id] => Ok(ShellCommand::MinimizeWindow {
+            id: parse_window_id(id)?,
+        }),
+        ["restore", id] => Ok(ShellCommand::RestoreWindow {
+            id: parse_window_id(id)?,
+        }),
+        ["minimize-group", leader] => Ok(ShellCommand::MinimizeGroup {
+            leader: parse_window_id(leader)?,
+        }),
+        ["restore-group", leader] => Ok(ShellCommand::RestoreGroup {
+            leader: parse_window_id(leader)?,
+        }),
+        ["maximize", id, state] => Ok(ShellCommand::MaximizeWindow {
+            id: parse_window_id(id)?,
+            maximized: parse_on_off(state)?,
+        }),
+        ["fullscreen", id, state] => Ok(ShellCommand::SetFullscreen {
+            id: parse_window_id(id)?,
+            fullscreen: parse_on_off(state)?,
+        }),
+        ["inhibit-shortcuts", id, state] => Ok(ShellCommand::InhibitShortcuts {
+            id: parse_window_id(id)?,
+            inhibit: parse_on_off(state)?,
+        }),
+        ["kill", id, force @ ..] => Ok(ShellCommand::KillWindow {
+            id: parse_window_id(id)?,
+            force: match force {
+                [] => false,
+                ["--force"] => true,
+                _ => return Err(format!("unexpected arguments: {}", force.join(" "))),
+            },
+        }),
+        ["move-to-monitor", id, direction] => Ok(ShellCommand::MoveWindowToMonitor {
+            id: parse_window_id(id)?,
+            direction: match *direction {
+                "left" => Direction::Left,
+                "right" => Direction::Right,
+                "up" => Direction::Up,
+                "down" => Direction::Down,
+                _ => return Err(format!("invalid direction: {}", direction)),
+            },
+        }),
+        ["menu-action", id, action @ ..] => Ok(ShellCommand::RunWindowMenuAction {
+            id: parse_window_id(id)?,
+            action: match action {
+                ["move"] => WindowMenuAction::Move,
+                ["resize"] => WindowMenuAction::Resize,
+                ["minimize"] => WindowMenuAction::Minimize,
+                ["maximize"] => WindowMenuAction::Maximize,
+                ["above"] => WindowMenuAction::AlwaysOnTop,
+                ["close"] => WindowMenuAction::Close,
+                ["workspace", n] => {
+                    WindowMenuAction::MoveToWorkspace(n.parse().map_err(|_| format!("invalid workspace: {}", n))?)
+                }
+                _ => return Err(format!("invalid menu action: {}", action.join(" "))),
+            },
+        }),
+        ["root-menu", index] => Ok(ShellCommand::RunRootMenuEntry {
+            index: index.parse().map_err(|_| format!("invalid index: {}", index))?,
+        }),
+        ["end-session", action, force @ ..] => Ok(ShellCommand::EndSession {
+            action: match *action {
+                "logout" => SessionAction::Logout,
+                "reboot" => SessionAction::Reboot,
+                "shutdown" => SessionAction::Shutdown,
+                _ => return Err(format!("invalid session action: {}", action)),
+            },
+            force: match force {
+                [] => false,
+                ["force"] => true,
+                _ => return Err(format!("unexpected arguments: {}", force.join(" "))),
+            },
+        }),
+        ["cancel-end-session"] => Ok(ShellCommand::CancelEndSession),
+        ["taskbar-order", ids @ ..] => Ok(ShellCommand::SetTaskbarOrder {
+            ids: ids.iter().map(|id| parse_window_id(id)).collect::<Result<_, _>>()?,
+            slots: Vec::new(),
+        }),
+        ["pin", id] => Ok(ShellCommand::PinApp { id: id.to_string(), position: None }),
+        ["pin", id, position] => Ok(ShellCommand::PinApp { id: id.to_string(), position: Some(parse_number(position)?) }),
+        ["unpin", id] => Ok(ShellCommand::UnpinApp { id: id.to_string() }),
+        ["pinned"] => Ok(ShellCommand::ListPinned),
+        ["components"] => Ok(ShellCommand::ListComponents),
+        ["strut", rest @ ..] => parse_strut(rest),
+        ["edge-trigger", edge, enabled] => Ok(ShellCommand::SetEdgeTrigger {
+            edge: parse_edge(edge)?,
+            enabled: parse_on_off(enabled)?,
+            monitor: 0,
+        }),
+        ["edge-trigger", edge, enabled, "--monitor", n] => Ok(ShellCommand::SetEdgeTrigger {
+            edge: parse_edge(edge)?,
+            enabled: parse_on_off(enabled)?,
+            monitor: parse_number(n)?,
+        }),
+        ["accessibility", feature, enabled] => Ok(ShellCommand::SetAccessibility {
+            feature: parse_accessibility_feature(feature)?,
+            enabled: parse_on_off(enabled)?,
+        }),
+        ["osd", kind, rest @ ..] => parse_osd(kind, rest),
+        ["dnd", enabled] => Ok(ShellCommand::SetDoNotDisturb { enabled: parse_on_off(enabled)? }),
+        ["notifications"] => Ok(ShellCommand::GetNotificationHistory { limit: None }),
+        ["notifications", limit] => Ok(ShellCommand::GetNotificationHistory { limit: Some(parse_number(limit)?) }),
+        ["expo", enabled] => Ok(ShellCommand::SetExpo { enabled: parse_on_off(enabled)? }),
+        ["dpms", state] => Ok(ShellCommand::SetDpms { state: parse_dpms_state(state)? }),
+        ["activation-token"] => Ok(ShellCommand::RequestActivationToken),
+        ["mute-audio", id, muted] => Ok(ShellCommand::MuteWindowAudio { id: parse_window_id(id)?, muted: parse_on_off(muted)? }),
+        ["input-region", id, rects @ ..] => Ok(ShellCommand::SetInputRegion {
+            window: parse_window_id(id)?,
+            rects: rects.iter().map(|rect| parse_rect(rect)).collect::<Result<_, _>>()?,
+        }),
+        ["preview", id, size, fps @ ..] => {
+            let invalid = || format!("invalid size (expected WxH): {}", size);
+            let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+            let fps = match fps {
+                [] => 10,
+                [fps] => fps.parse().map_err(|_| format!("invalid fps: {}", fps))?,
+                _ => return Err("usage: preview <id> <W>x<H> [<fps>]".to_string()),
+            };
+            Ok(ShellCommand::RequestLivePreview {
+                id: parse_window_id(id)?,
+                width: width.parse().map_err(|_| invalid())?,
+                height: height.parse().map_err(|_| invalid())?,
+                fps,
+            })
+        }
+        ["wallpaper", path, rest @ ..] => parse_wallpaper(path, rest),
+        ["subscribe"] => Ok(ShellCommand::Subscribe),
+        ["subscribe", "pointer"] => Ok(ShellCommand::SubscribePointer),
+        ["urgency", "clear", id] => Ok(ShellCommand::ClearUrgency {
+            id: parse_window_id(id)?,
+        }),
+        [] => Err("missing command".to_string()),
+        _ => Err(format!("unknown command: {}", args.join(" "))),
+    }
+}
+
+/// Parse a window id given in decimal or `0x` hexadecimal
+fn parse_window_id(text: &str) -> Result<u32, String> {
+    let parsed = match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| format!("invalid window id: {}", text))
+}
+
+/// Parse a decimal number (coordinate, workspace)
+fn parse_number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.parse().map_err(|_| format!("invalid number: {}", text))
+}
+
+/// Parse the options of `find`
+fn parse_find(mut options: &[&str]) -> Result<ShellCommand, String> {
+    let (mut class, mut title_regex, mut workspace) = (None, None, None);
+    while let [option, value, rest @ ..] = options {
+        match *option {
+            "--class" => class = Some((*value).to_string()),
+            "--title" => title_regex = Some((*value).to_string()),
+            "--workspace" => workspace = Some(parse_number(value)?),
+            _ => return Err(format!("unknown option: {}", option)),
+        }
+        options = rest;
+    }
+    if !options.is_empty() {
+        return Err("usage: find [--class <class>] [--title <regex>] [--workspace <n>]".to_string());
+    }
+    Ok(ShellCommand::FindWindows { class, title_regex, workspace })
+}
+
+/// Parse the arguments of `strut`
+fn parse_strut(args: &[&str]) -> Result<ShellCommand, String> {
+    let (args, monitor) = match args {
+        [rest @ .., "--monitor", n] => (rest, n.parse().map_err(|_| format!("invalid monitor: {}", n))?),
+        _ => (args, 0),
+    };
+    let number = |text: &str| text.parse::<u32>().map_err(|_| format!("invalid number: {}", text));
+    let (edge, size, start, end) = match args {
+        [edge, size] => (*edge, number(size)?, 0, 0),
+        [edge, size, start, end] => (*edge, number(size)?, number(start)?, number(end)?),
+        _ => return Err("usage: strut <edge> <size> [<start> <end>] [--monitor <n>]".to_string()),
+    };
+    Ok(ShellCommand::SetPanelStrut { edge: parse_edge(edge)?, size, start, end, monitor })
+}
+
+/// Parse a monitor edge
+fn parse_edge(edge: &str) -> Result<PanelEdge, String> {
+    match edge {
+        "left" => Ok(PanelEdge::Left),
+        "right" => Ok(PanelEdge::Right),
+        "top" => Ok(PanelEdge::Top),
+        "bottom" => Ok(PanelEdge::Bottom),
+        _ => Err(format!("invalid edge: {}", edge)),
+    }
+}
+
+/// Parse the arguments of `wallpaper`
+fn parse_wallpaper(path: &str, mut options: &[&str]) -> Result<ShellCommand, String> {
+    // The window manager doesn't share our working directory
+    let path = std::fs::canonicalize(path).map_err(|e| format!("{}: {}", path, e))?;
+    let number = |text: &str| text.parse::<u32>().map_err(|_| format!("invalid number: {}", text));
+    let (mut mode, mut monitor, mut workspace) = (None, None, None);
+    while let [option, value, rest @ ..] = options {
+        match *option {
+            "--mode" => {
+                mode = Some(match *value {
+                    "fill" => WallpaperMode::Fill,
+                    "fit" => WallpaperMode::Fit,
+                    "stretch" => WallpaperMode::Stretch,
+                    "center" => WallpaperMode::Center,
+                    "tile" => WallpaperMode::Tile,
+                    _ => return Err(format!("invalid mode: {}", value)),
+                })
+            }
+            "--monitor" => monitor = Some(number(value)?),
+            "--workspace" => workspace = Some(number(value)?),
+            _ => return Err(format!("unknown option: {}", option)),
+        }
+        options = rest;
+    }
+    if !options.is_empty() {
+        return Err("usage: wallpaper <path> [--mode <mode>] [--monitor <n>] [--workspace <n>]".to_string());
+    }
+    Ok(ShellCommand::SetWallpaper {
+        path: path.to_string_lossy().into_owned(),
+        mode,
+        monitor,
+        workspace,
+    })
+}
+
+/// Parse an X geometry string (`WxH+X+Y`)
+fn parse_rect(text: &str) -> Result<Rect, String> {
+    let invalid = || format!("invalid rectangle (expected WxH+X+Y): {}", text);
+    let (size, position) = text.split_once('+').ok_or_else(invalid)?;
+    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+    let (x, y) = position.split_once('+').ok_or_else(invalid)?;
+    Ok(Rect {
+        x: x.parse().map_err(|_| invalid())?,
+        y: y.parse().map_err(|_| invalid())?,
+        width: width.parse().map_err(|_| invalid())?,
+        height: height.parse().map_err(|_| invalid())?,
+    })
+}
+
+/// Parse the arguments of `osd`: a percentage and/or a label
+fn parse_osd(kind: &str, args: &[&str]) -> Result<ShellCommand, String> {
+    let kind = match kind {
+        "volume" => OsdKind::Volume,
+        "brightness" => OsdKind::Brightness,
+        "keyboard-layout" => OsdKind::KeyboardLayout,
+        "workspace" => OsdKind::Workspace,
+        _ => return Err(format!("invalid osd kind: {}", kind)),
+    };
+    let (value, label) = match args {
+        [] => (None, None),
+        [single] => match single.parse::<f64>() {
+            Ok(percent) => (Some(percent), None),
+            Err(_) => (None, Some(single.to_string())),
+        },
+        [percent, label] => (Some(percent.parse::<f64>().map_err(|_| format!("invalid percentage: {}", percent))?), Some(label.to_string())),
+        _ => return Err("usage: osd <kind> [<percent>] [<label>]".to_string()),
+    };
+    Ok(ShellCommand::ShowOsd { kind, label, value: value.map(|percent| percent / 100.0) })
+}
+
+/// Parse a keyboard accessibility feature
+fn parse_accessibility_feature(feature: &str) -> Result<AccessibilityFeature, String> {
+    match feature {
+        "sticky-keys" => Ok(AccessibilityFeature::StickyKeys),
+        "slow-keys" => Ok(AccessibilityFeature::SlowKeys),
+        "bounce-keys" => Ok(AccessibilityFeature::BounceKeys),
+        "mouse-keys" => Ok(AccessibilityFeature::MouseKeys),
+        _ => Err(format!("invalid accessibility feature: {}", feature)),
+    }
+}
+
+/// Parse a monitor power state
+fn parse_dpms_state(state: &str) -> Result<DpmsState, String> {
+    match state {
+        "on" => Ok(DpmsState::On),
+        "standby" => Ok(DpmsState::Standby),
+        "suspend" => Ok(DpmsState::Suspend),
+        "off" => Ok(DpmsState::Off),
+        _ => Err(format!("invalid DPMS state: {}", state)),
+    }
+}
+
+/// Parse an `on`/`off` argument
+fn parse_on_off(text: &str) -> Result<bool, String> {
+    match text {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("expected on or off, got: {}", text)),
+    }
+}
+
+/// Print a reply in human-readable form; returns false for error replies
+fn print_reply(reply: &Reply) -> bool {
+    match reply {
+        Reply::Ok => true,
+        Reply::Error { message } => {
+            eprintln!("area-msg: {}", message);
+            false
+        }
+        Reply::DebugDump { filter, lines } => {
+            println!("# filter: {}", filter);
+            for line in lines {
+                println!("{}", line);
+            }
+            true
+        }
+        Reply::State { state } => {
+            println!("{}", serde_json::to_string_pretty(state).unwrap_or_default());
+            true
+        }
+        Reply::Metrics { metrics } => {
+            println!("uptime: {:.1}s", metrics.uptime_ms as f64 / 1000.0);
+            println!("damage events: {}", metrics.damage_events);
+            println!("framebuffer: {} bits per channel", metrics.framebuffer_bits);
+            print_timing("frame time", &metrics.frame_time);
+            print_timing("event latency", &metrics.event_latency);
+            print_timing("ipc round trip", &metrics.ipc_round_trip);
+            true
+        }
+        Reply::SelfCheck { gl: None } => {
+            println!("compositor: no OpenGL (not composited)");
+            true
+        }
+        Reply::SelfCheck { gl: Some(gl) } => {
+            println!("vendor: {}", gl.vendor);
+            println!("renderer: {}", gl.renderer);
+            println!("version: {}", gl.version);
+            println!("shading language: {}", gl.shading_language_version);
+            println!("glx: {}", gl.glx_version);
+            println!("direct rendering: {}", if gl.direct_rendering { "yes" } else { "no" });
+            println!("vsync: {}", if gl.vsync { "on" } else { "off" });
+            println!("framebuffer: {} bits per channel", gl.color_bits);
+            println!("max texture size: {}", gl.max_texture_size);
+            println!("extensions: {}", gl.extensions.join(" "));
+            true
+        }
+        Reply::Welcome { version, capabilities, trusted } => {
+            println!("protocol {}", version);
+            println!("capabilities: {}", capabilities.join(" "));
+            if !trusted {
+                println!("restricted: set {} to run every command", area_ipc::TOKEN_ENV);
+            }
+            true
+        }
+        Reply::LivePreview { path } => {
+            println!("{}", path);
+            true
+        }
+        Reply::ActivationToken { token } => {
+            println!("{}", token);
+            true
+        }
+        Reply::Pinned { apps } => {
+            for app in apps {
+                let windows: Vec<String> = app.windows.iter().map(|id| format!("0x{:08x}", id)).collect();
+                println!("{} {} [{}]", app.id, app.name.as_deref().unwrap_or("-"), windows.join(","));
+            }
+            true
+        }
+        Reply::Components { components } => {
+            for component in components {
+                let pid = component.pid.map_or_else(|| "-".to_string(), |pid| pid.to_string());
+                println!("{} {:?} pid {} restarts {}", component.name, component.state, pid, component.restarts);
+            }
+            true
+        }
+        Reply::NotificationHistory { do_not_disturb, notifications } => {
+            println!("do not disturb: {}", if *do_not_disturb { "on" } else { "off" });
+            for notification in notifications {
+                println!("{} {} [{}] {}: {}", notification.id, notification.timestamp, notification.app_name, notification.summary, notification.body);
+            }
+            true
+        }
+        Reply::WindowDetails { window } => {
+            let unset = || "-".to_string();
+            let info = &window.info;
+            let mut flags = window_flags(info);
+            for (set, name) in [
+                (window.sticky, "sticky"),
+                (window.modal, "modal"),
+                (window.skip_taskbar, "skip-taskbar"),
+                (window.skip_pager, "skip-pager"),
+                (window.has_icon, "icon"),
+            ] {
+                if set {
+                    flags.push(name);
+                }
+            }
+            println!("id: 0x{:08x}", info.id);
+            println!("title: {}", info.title);
+            println!("class: {}", window.class.clone().unwrap_or_else(unset));
+            println!("instance: {}", window.instance.clone().unwrap_or_else(unset));
+            println!("role: {}", window.role.clone().unwrap_or_else(unset));
+            println!("type: {}", window.window_type);
+            println!("pid: {}", window.pid.map(|pid| pid.to_string()).unwrap_or_else(unset));
+            println!("geometry: {}x{}+{}+{}", info.width, info.height, info.x, info.y);
+            println!("workspace: {}", window.workspace.map(|ws| ws.to_string()).unwrap_or_else(|| "all".to_string()));
+            println!("monitor: {}", window.monitor.map(|m| m.to_string()).unwrap_or_else(unset));
+            println!("transient for: {}", window.transient_for.map(|w| format!("0x{:08x}", w)).unwrap_or_else(unset));
+            println!("group: {}", info.group.map(|w| format!("0x{:08x}", w)).unwrap_or_else(unset));
+            println!("state: {}", flags.join(","));
+            true
+        }
+        Reply::Windows { windows } => {
+            for window in windows {
+                let flags = window_flags(window);
+                let group = window.group.map(|leader| format!(" group=0x{:08x}", leader)).unwrap_or_default();
+                println!(
+                    "0x{:08x} {}x{}+{}+{} [{}]{} {}",
+                    window.id,
+                    window.width,
+                    window.height,
+                    window.x,
+                    window.y,
+                    flags.join(","),
+                    group,
+                    window.title
+                );
+            }
+            true
+        }
+    }
+}
+
+/// State flags of a window, as listed by `windows`
+fn window_flags(window: &WindowInfo) -> Vec<&'static str> {
+    [
+        (window.focused, "focused"),
+        (window.iconified, "iconified"),
+        (window.urgent, "urgent"),
+        (window.maximized, "maximized"),
+        (window.fullscreen, "fullscreen"),
+        (window.shaded, "shaded"),
+        (window.above, "above"),
+        (window.shortcuts_inhibited, "inhibit"),
+    ]
+    .into_iter()
+    .filter_map(|(set, name)| set.then_some(name))
+    .collect()
+}
+
+/// Print one timing summary line
+fn print_timing(name: &str, timing: &TimingSummary) {
+    println!(
+        "{:<15} n={:<8} avg={:.2}ms p50={:.2}ms p95={:.2}ms p99={:.2}ms max={:.2}ms",
+        format!("{}:", name),
+        timing.count,
+        timing.avg_ms,
+        timing.p50_ms,
+        timing.p95_ms,
+        timing.p99_ms,
+        timing.max_ms
+    );
+}
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let json = args.iter().any(|arg| arg == "--json");
+    args.retain(|arg| arg != "--json");
+
+    let command = match parse_command(&args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("area-msg: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let subscribe = matches!(command, ShellCommand::Subscribe | ShellCommand::SubscribePointer);
+    let mut client = match Client::connect() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("area-msg: failed to talk to Area at {:?}: {}", area_ipc::socket_path(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if !matches!(command, ShellCommand::Hello { .. })
+        && let Err(e) = client.hello()
+    {
+        eprintln!("area-msg: failed to talk to Area at {:?}: {}", area_ipc::socket_path(), e);
+        return ExitCode::FAILURE;
+    }
+    let reply = match client.request(&command) {
+        Ok(reply) => reply,
+        Err(e) => {
+            eprintln!("area-msg: failed to talk to Area at {:?}: {}", area_ipc::socket_path(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if subscribe && !matches!(reply, Reply::Error { .. }) {
+        return print_events(&mut client);
+    }
+
+    if json {
+        match serde_json::to_string_pretty(&reply) {
+            Ok(text) => println!("{}", text),
+            Err(e) => eprintln!("area-msg: {}", e),
+        }
+        return if matches!(reply, Reply::Error { .. }) { ExitCode::FAILURE } else { ExitCode::SUCCESS };
+    }
+
+    if print_reply(&reply) { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+/// Print events until the connection closes
+fn print_events(client: &mut Client) -> ExitCode {
+    loop {
+        let event = match client.next_event() {
+            Ok(event) => event,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("area-msg: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        match serde_json::to_string(&event) {
+            Ok(text) => println!("{}", text),
+            Err(e) => eprintln!("area-msg: {}", e),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Blocking IPC client (used by `area-msg` and scripts)
+
+use std::collections::VecDeque;
+use std::io;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+use crate::codec::FramedMessage;
+use crate::protocol::{Reply, ShellCommand, WmEvent, WmMessage};
+use crate::version::{CAPABILITIES, PROTOCOL_VERSION, Version};
+
+/// Connection to a running window manager
+pub struct Client {
+    stream: UnixStream,
+    /// Events that arrived while waiting for a reply
+    pending_events: VecDeque<WmEvent>,
+    /// Window manager capabilities, known after `hello`
+    capabilities: Vec<String>,
+}
+
+impl Client {
+    /// Connect to the window manager of the current display
+    pub fn connect() -> io::Result<Self> {
+        Self::connect_to(&crate::socket_path())
+    }
+
+    /// Connect to a specific socket path
+    pub fn connect_to(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            stream: UnixStream::connect(path)?,
+            pending_events: VecDeque::new(),
+            capabilities: Vec::new(),
+        })
+    }
+
+    /// Exchange protocol versions and capabilities with the window manager
+    ///
+    /// Presents the token from `$AREA_IPC_TOKEN`, if set. Fails with
+    /// `InvalidData` if the versions are incompatible. A window
+    /// manager predating the handshake rejects `Hello` as an unknown command;
+    /// that is not an error, it just supports no optional capabilities.
+    pub fn hello(&mut self) -> io::Result<()> {
+        let hello = ShellCommand::Hello {
+            version: PROTOCOL_VERSION.to_string(),
+            capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+            token: std::env::var(crate::TOKEN_ENV).ok(),
+        };
+        match self.request(&hello)? {
+            Reply::Welcome { version, capabilities, .. } => {
+                let compatible = Version::parse(&version).is_some_and(|v| v.is_compatible(&PROTOCOL_VERSION));
+                if !compatible {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("window manager speaks protocol {}, this client {}", version, PROTOCOL_VERSION),
+                    ));
+                }
+                self.capabilities = capabilities;
+            }
+            _ => self.capabilities.clear(),
+        }
+        Ok(())
+    }
+
+    /// Whether the window manager announced a capability in its `Welcome`
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+
+    /// Send a command and wait for its reply
+    pub fn request(&mut self, command: &ShellCommand) -> io::Result<Reply> {
+        FramedMessage::write(&mut self.stream, command)?;
+        loop {
+            match self.read_message()? {
+                Some(WmMessage::Reply(reply)) => return Ok(reply),
+                Some(WmMessage::Event(event)) => self.pending_events.push_back(event),
+                None => continue,
+            }
+        }
+    }
+
+    /// Wait for the next event (requires a prior `ShellCommand::Subscribe`)
+    pub fn next_event(&mut self) -> io::Result<WmEvent> {
+        if let Some(event) = self.pending_events.pop_front() {
+            return Ok(event);
+        }
+        loop {
+            if let Some(WmMessage::Event(event)) = self.read_message()? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Read the next message; None for an event this client cannot decode
+    /// (sent by a newer window manager), which is skipped
+    fn read_message(&mut self) -> io::Result<Option<WmMessage>> {
+        let value: serde_json::Value = FramedMessage::read(&mut self.stream)?;
+        let is_event = value.get("event").is_some();
+        match serde_json::from_value(value) {
+            Ok(message) => Ok(Some(message)),
+            Err(_) if is_event => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
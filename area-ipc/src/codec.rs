@@ -0,0 +1,400 @@
+//! Message framing
+//!
+//! Every message is a JSON document preceded by its length as a 4-byte
+//! big-endian integer. Blocking helpers are used by `area-msg`, async ones by
+//! the window manager's socket server, and `FrameDecoder` by callers that
+//! receive bytes in arbitrary chunks.
+//!
+//! Lengths of zero or above `MAX_FRAME_LEN` are framing errors: the stream
+//! cannot be resynchronized after them, so readers should drop the
+//! connection (see `FramedMessage::is_framing_error`). A payload that is not
+//! valid JSON for the expected type was still consumed whole, so reading can
+//! go on after it. Payload buffers grow as data arrives rather than being
+//! allocated from the announced length, so a client announcing a large
+//! frame and sending nothing holds no memory.
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Largest accepted payload in bytes
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Time the rest of a frame may take to arrive once its length was read
+/// (async reads only; an idle connection may wait forever between frames)
+pub const PAYLOAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A length prefix no valid frame has
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    /// Zero-length payload (an empty document is never valid JSON)
+    Empty,
+    /// Announced payload larger than `MAX_FRAME_LEN`
+    TooLarge(u32),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty frame"),
+            Self::TooLarge(len) => write!(f, "frame of {} bytes exceeds the {} byte limit", len, MAX_FRAME_LEN),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl From<FrameError> for io::Error {
+    fn from(error: FrameError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+/// Length-prefixed JSON framing
+pub struct FramedMessage;
+
+impl FramedMessage {
+    /// Size of the length prefix in bytes
+    pub const HEADER_LEN: usize = 4;
+
+    /// Encode a message into a complete frame (prefix + payload)
+    pub fn encode<T: Serialize>(message: &T) -> io::Result<Vec<u8>> {
+        let payload = serde_json::to_vec(message)?;
+        if payload.len() > MAX_FRAME_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "message too large"));
+        }
+        let len = payload.len() as u32;
+
+        let mut frame = Vec::with_capacity(Self::HEADER_LEN + payload.len());
+        frame.extend_from_slice(&len.to_be_bytes());
+        frame.extend_from_slice(&payload);
+        Ok(frame)
+    }
+
+    /// Decode a frame payload (without the length prefix)
+    pub fn decode<T: DeserializeOwned>(payload: &[u8]) -> io::Result<T> {
+        Ok(serde_json::from_slice(payload)?)
+    }
+
+    /// Payload length announced by a length prefix
+    pub fn payload_len(header: [u8; Self::HEADER_LEN]) -> Result<usize, FrameError> {
+        let len = u32::from_be_bytes(header);
+        match len as usize {
+            0 => Err(FrameError::Empty),
+            n if n > MAX_FRAME_LEN => Err(FrameError::TooLarge(len)),
+            n => Ok(n),
+        }
+    }
+
+    /// Whether a read error left the stream out of sync (bad length prefix),
+    /// as opposed to a payload that merely failed to decode
+    pub fn is_framing_error(error: &io::Error) -> bool {
+        error.get_ref().is_some_and(|inner| inner.is::<FrameError>())
+    }
+
+    /// Write one message to a blocking stream
+    pub fn write<T: Serialize, W: Write>(writer: &mut W, message: &T) -> io::Result<()> {
+        writer.write_all(&Self::encode(message)?)?;
+        writer.flush()
+    }
+
+    /// Read one message from a blocking stream
+    pub fn read<T: DeserializeOwned, R: Read>(reader: &mut R) -> io::Result<T> {
+        let mut header = [0u8; Self::HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        let len = Self::payload_len(header)?;
+        let mut payload = Vec::new();
+        reader.take(len as u64).read_to_end(&mut payload)?;
+        if payload.len() < len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Self::decode(&payload)
+    }
+
+    /// Write one message to an async stream
+    pub async fn write_async<T: Serialize, W: AsyncWrite + Unpin>(
+        writer: &mut W,
+        message: &T,
+    ) -> io::Result<()> {
+        writer.write_all(&Self::encode(message)?).await?;
+        writer.flush().await
+    }
+
+    /// Read one message from an async stream
+    ///
+    /// Fails with `TimedOut` if a started frame does not complete within
+    /// `PAYLOAD_TIMEOUT`, so a stalled client cannot hold a reader forever.
+    pub async fn read_async<T: DeserializeOwned, R: AsyncRead + Unpin>(
+        reader: &mut R,
+    ) -> io::Result<T> {
+        let mut header = [0u8; Self::HEADER_LEN];
+        reader.read_exact(&mut header[..1]).await?;
+        let payload = tokio::time::timeout(PAYLOAD_TIMEOUT, async {
+            reader.read_exact(&mut header[1..]).await?;
+            let len = Self::payload_len(header)?;
+            let mut payload = Vec::new();
+            (&mut *reader).take(len as u64).read_to_end(&mut payload).await?;
+            if payload.len() < len {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+            Ok(payload)
+        })
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "incomplete frame"))??;
+        Self::decode(&payload)
+    }
+}
+
+/// Incremental frame decoder for data arriving in arbitrary chunks
+///
+/// After a framing error the decoder stays failed: the rest of the stream
+/// cannot be interpreted.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+    failed: Option<FrameError>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append received bytes
+    pub fn extend(&mut self, data: &[u8]) {
+        if self.failed.is_none() {
+            self.buffer.extend_from_slice(data);
+        }
+    }
+
+    /// Bytes received but not yet returned as a frame
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The next complete payload, if one has arrived
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        if let Some(error) = self.failed {
+            return Err(error);
+        }
+        let Some(header) = self.buffer.first_chunk::<{ FramedMessage::HEADER_LEN }>() else {
+            return Ok(None);
+        };
+        let len = match FramedMessage::payload_len(*header) {
+            Ok(len) => len,
+            Err(error) => {
+                self.failed = Some(error);
+                self.buffer = Vec::new();
+                return Err(error);
+            }
+        };
+        let end = FramedMessage::HEADER_LEN + len;
+        if self.buffer.len() < end {
+            return Ok(None);
+        }
+        let payload = self.buffer[FramedMessage::HEADER_LEN..end].to_vec();
+        self.buffer.drain(..end);
+        Ok(Some(payload))
+    }
+
+    /// The next complete message, if one has arrived; a payload that does not
+    /// decode is consumed and reported as `InvalidData`
+    pub fn next_message<T: DeserializeOwned>(&mut self) -> io::Result<Option<T>> {
+        match self.next_frame()? {
+            Some(payload) => FramedMessage::decode(&payload).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{Direction, Rect, ShellCommand};
+
+    /// Deterministic xorshift generator, so failures reproduce
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn bytes(&mut self, len: usize) -> Vec<u8> {
+            (0..len).map(|_| self.next() as u8).collect()
+        }
+    }
+
+    fn commands() -> Vec<ShellCommand> {
+        vec![
+            ShellCommand::GetWindows,
+            ShellCommand::FocusWindow { id: 0x0120_0004 },
+            ShellCommand::SetLogLevel { filter: "area=trace,\u{1F600}\"\\".to_string() },
+            ShellCommand::MoveWindowToMonitor { id: 7, direction: Direction::Left },
+            ShellCommand::SetInputRegion { window: 9, rects: vec![Rect { x: -5, y: 3, width: 10, height: 20 }; 100] },
+            ShellCommand::DumpState { redact_titles: true },
+        ]
+    }
+
+    fn same(a: &ShellCommand, b: &ShellCommand) -> bool {
+        serde_json::to_value(a).unwrap() == serde_json::to_value(b).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        for command in commands() {
+            let frame = FramedMessage::encode(&command).unwrap();
+            let decoded: ShellCommand = FramedMessage::read(&mut frame.as_slice()).unwrap();
+            assert!(same(&command, &decoded));
+        }
+    }
+
+    #[test]
+    fn rejects_bad_length_prefixes() {
+        assert_eq!(FramedMessage::payload_len([0; 4]), Err(FrameError::Empty));
+        assert_eq!(FramedMessage::payload_len([0xff; 4]), Err(FrameError::TooLarge(u32::MAX)));
+        let too_large = (MAX_FRAME_LEN as u32 + 1).to_be_bytes();
+        assert!(FramedMessage::payload_len(too_large).is_err());
+        assert_eq!(FramedMessage::payload_len((MAX_FRAME_LEN as u32).to_be_bytes()), Ok(MAX_FRAME_LEN));
+
+        let error = FramedMessage::read::<ShellCommand, _>(&mut [0xffu8, 0xff, 0xff, 0xff, b'{'].as_slice()).unwrap_err();
+        assert!(FramedMessage::is_framing_error(&error));
+        let error = FramedMessage::read::<ShellCommand, _>(&mut [0u8, 0, 0, 0].as_slice()).unwrap_err();
+        assert!(FramedMessage::is_framing_error(&error));
+    }
+
+    #[test]
+    fn invalid_payload_is_not_a_framing_error() {
+        let mut stream = Vec::new();
+        stream.extend_from_slice(&5u32.to_be_bytes());
+        stream.extend_from_slice(b"{oops");
+        stream.extend(FramedMessage::encode(&ShellCommand::GetWindows).unwrap());
+        let mut reader = stream.as_slice();
+        let error = FramedMessage::read::<ShellCommand, _>(&mut reader).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(!FramedMessage::is_framing_error(&error));
+        // The bad payload was consumed; the next frame still reads
+        assert!(matches!(FramedMessage::read(&mut reader).unwrap(), ShellCommand::GetWindows));
+    }
+
+    #[test]
+    fn truncated_frame_is_eof() {
+        let frame = FramedMessage::encode(&ShellCommand::GetWindows).unwrap();
+        for cut in 0..frame.len() {
+            let error = FramedMessage::read::<ShellCommand, _>(&mut &frame[..cut]).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof, "cut at {}", cut);
+        }
+    }
+
+    #[test]
+    fn decoder_handles_any_chunking() {
+        let commands = commands();
+        let stream: Vec<u8> = commands.iter().flat_map(|c| FramedMessage::encode(c).unwrap()).collect();
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..200 {
+            let mut decoder = FrameDecoder::new();
+            let mut decoded = Vec::new();
+            let mut rest = stream.as_slice();
+            while !rest.is_empty() {
+                let (chunk, tail) = rest.split_at(1 + rng.below(rest.len().min(64)));
+                decoder.extend(chunk);
+                rest = tail;
+                while let Some(command) = decoder.next_message::<ShellCommand>().unwrap() {
+                    decoded.push(command);
+                }
+            }
+            assert_eq!(decoder.buffered(), 0);
+            assert_eq!(decoded.len(), commands.len());
+            assert!(commands.iter().zip(&decoded).all(|(a, b)| same(a, b)));
+        }
+    }
+
+    #[test]
+    fn decoder_stays_failed_after_bad_prefix() {
+        let mut decoder = FrameDecoder::new();
+        decoder.extend(&[0x7f, 0, 0, 0]);
+        assert!(matches!(decoder.next_frame(), Err(FrameError::TooLarge(_))));
+        decoder.extend(&FramedMessage::encode(&ShellCommand::GetWindows).unwrap());
+        assert!(decoder.next_frame().is_err());
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn random_input_never_panics() {
+        let mut rng = Rng(0xDEAD_BEEF_CAFE_F00D);
+        let valid: Vec<Vec<u8>> = commands().iter().map(|c| FramedMessage::encode(c).unwrap()).collect();
+        for round in 0..2000 {
+            let mut input = if round % 2 == 0 {
+                let len = rng.below(256);
+                rng.bytes(len)
+            } else {
+                // A valid frame with a few bytes flipped, or cut short
+                let mut frame = valid[rng.below(valid.len())].clone();
+                for _ in 0..1 + rng.below(4) {
+                    let index = rng.below(frame.len());
+                    frame[index] ^= 1 << rng.below(8);
+                }
+                frame
+            };
+            if round % 3 == 0 {
+                input.truncate(rng.below(input.len() + 1));
+            }
+
+            let mut reader = input.as_slice();
+            while !reader.is_empty() {
+                match FramedMessage::read::<ShellCommand, _>(&mut reader) {
+                    Ok(_) => {}
+                    Err(e) if e.kind() == io::ErrorKind::InvalidData && !FramedMessage::is_framing_error(&e) => {}
+                    Err(_) => break,
+                }
+            }
+
+            let mut decoder = FrameDecoder::new();
+            decoder.extend(&input);
+            loop {
+                match decoder.next_message::<ShellCommand>() {
+                    Ok(Some(_)) => {}
+                    Err(e) if !FramedMessage::is_framing_error(&e) => {}
+                    Ok(None) | Err(_) => break,
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn async_read_across_partial_writes() {
+        let (mut client, mut server) = tokio::io::duplex(8);
+        let commands = commands();
+        let stream: Vec<u8> = commands.iter().flat_map(|c| FramedMessage::encode(c).unwrap()).collect();
+        let writer = tokio::spawn(async move {
+            for chunk in stream.chunks(3) {
+                client.write_all(chunk).await.unwrap();
+            }
+        });
+        for command in &commands {
+            let decoded: ShellCommand = FramedMessage::read_async(&mut server).await.unwrap();
+            assert!(same(command, &decoded));
+        }
+        writer.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn async_read_times_out_on_stalled_frame() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(&100u32.to_be_bytes()).await.unwrap();
+        client.write_all(b"{\"command\"").await.unwrap();
+        let error = FramedMessage::read_async::<ShellCommand, _>(&mut server).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        drop(client);
+    }
+}
@@ -0,0 +1,78 @@
+//! Area IPC
+//!
+//! Protocol shared by the window manager (`area`), the shell, and the
+//! `area-msg` command-line client. Messages are JSON documents sent over a
+//! Unix socket, each prefixed with its length (see `FramedMessage`).
+
+pub mod client;
+pub mod codec;
+pub mod protocol;
+pub mod version;
+
+pub use client::Client;
+pub use codec::{FrameDecoder, FrameError, FramedMessage, MAX_FRAME_LEN};
+pub use protocol::{
+    AccessibilityFeature, ComponentState, ComponentStatus, CycleEntry, Direction, DpmsState, ExpoWorkspace, GlCapabilities, MetricsSnapshot, Notification, OsdKind, PagerWindow, PanelEdge, PinnedApp, Rect, Reply, RootMenuItem, SessionAction, ShellCommand, TaskbarSlot, TimingSummary,
+    WallpaperMode, WindowDetails, WindowInfo, WindowMenuAction, WindowMenuEntry, WmEvent, WmMessage, WorkspaceInfo,
+};
+pub use version::{CAPABILITIES, PROTOCOL_VERSION, Version};
+
+use std::path::PathBuf;
+
+/// Environment variable overriding the socket path
+pub const SOCKET_ENV: &str = "AREA_IPC_SOCKET";
+
+/// Environment variable holding the token `Client::hello` presents (see `ShellCommand::Hello`)
+pub const TOKEN_ENV: &str = "AREA_IPC_TOKEN";
+
+/// Root pixels per unit of `WmEvent::WorkspaceLayout` coordinates
+pub const PAGER_SCALE: u32 = 10;
+
+/// Path of the IPC socket for the current display
+///
+/// `$AREA_IPC_SOCKET` if set, otherwise `$XDG_RUNTIME_DIR/area-ipc-<display>.sock`
+/// (falling back to `/tmp`). The display is part of the name so a nested
+/// session (e.g. Xephyr on `:99`) doesn't collide with the host session.
+pub fn socket_path() -> PathBuf {
+    if let Ok(path) = std::env::var(SOCKET_ENV) {
+        return PathBuf::from(path);
+    }
+
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+    let display = std::env::var("DISPLAY").unwrap_or_else(|_| ":0".to_string());
+    let display: String = display
+        .trim_start_matches(':')
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '_' })
+        .collect();
+
+    PathBuf::from(runtime_dir).join(format!("area-ipc-{}.sock", display))
+}
+
+/// Size of the header at the start of a live preview file
+pub const LIVE_PREVIEW_HEADER: usize = 16;
+
+/// Shared-memory file a window's live preview is streamed to
+///
+/// Lives next to the socket (on `$XDG_RUNTIME_DIR`, normally a tmpfs) and is
+/// meant to be mmapped. Layout, all integers little-endian `u32`:
+/// `sequence`, `width`, `height`, reserved, then `width * height` RGBA
+/// pixels, top row first. `sequence` is 0 until the first frame, odd while
+/// a frame is being written and even once it's complete; re-read if it
+/// changed during the copy. The file is removed when the preview ends.
+pub fn live_preview_path(id: u32) -> PathBuf {
+    let socket = socket_path();
+    let stem = socket.file_stem().and_then(|s| s.to_str()).unwrap_or("area-ipc");
+    socket.with_file_name(format!("{}-preview-{}.shm", stem, id))
+}
+
+/// Shared-memory file holding the last frame of a closed window
+///
+/// Same layout as `live_preview_path` with a single frame (the sequence is
+/// even once it is written). The file is removed after the compositor's
+/// `close_snapshot_ms`.
+pub fn closed_snapshot_path(id: u32) -> PathBuf {
+    let socket = socket_path();
+    let stem = socket.file_stem().and_then(|s| s.to_str()).unwrap_or("area-ipc");
+    socket.with_file_name(format!("{}-closed-{}.shm", stem, id))
+}
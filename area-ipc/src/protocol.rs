@@ -0,0 +1,829 @@
+//! IPC message types
+
+use serde::{Deserialize, Serialize};
+
+/// Commands sent from a client (shell, `area-msg`) to the window manager
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ShellCommand {
+    /// Open the connection: announce the client's protocol version and
+    /// the capabilities it understands (see `version`)
+    ///
+    /// Answered with `Welcome`. Without it a client only receives events of
+    /// the base protocol. `token` unlocks the full command set when the
+    /// window manager is configured with an IPC token.
+    Hello {
+        version: String,
+        #[serde(default)]
+        capabilities: Vec<String>,
+        #[serde(default)]
+        token: Option<String>,
+    },
+    /// Replace the active log filter (same syntax as `RUST_LOG`)
+    SetLogLevel { filter: String },
+    /// Fetch the in-memory log ring buffer
+    DebugDump,
+    /// Fetch the window manager's internal state (clients and their flags,
+    /// geometry, workspaces, focus history, stacking, struts, frame windows)
+    DumpState {
+        /// Replace window titles, for dumps attached to bug reports
+        #[serde(default)]
+        redact_titles: bool,
+    },
+    /// Fetch performance metrics (frame times, event/IPC latency)
+    GetMetrics,
+    /// Fetch the compositor's OpenGL setup, and show or hide its test
+    /// patterns (gradients, a tear test bar, repainted windows flashing)
+    SelfCheck {
+        /// None leaves the patterns as they are
+        #[serde(default)]
+        pattern: Option<bool>,
+    },
+    /// Start receiving `WmEvent`s on this connection
+    Subscribe,
+    /// Like `Subscribe`, and also receive the rate-limited `PointerMoved` stream
+    SubscribePointer,
+    /// Drop a window's attention request (urgency hint / DEMANDS_ATTENTION)
+    ClearUrgency { id: u32 },
+    /// List managed windows
+    GetWindows,
+    /// Extended properties of one window (answered with `WindowDetails`)
+    GetWindowInfo { id: u32 },
+    /// Windows matching every given criterion, most recently focused first
+    /// (answered with `Windows`)
+    FindWindows {
+        /// `WM_CLASS` class or instance, case-insensitive
+        #[serde(default)]
+        class: Option<String>,
+        /// Regular expression searched in the title
+        #[serde(default)]
+        title_regex: Option<String>,
+        /// Workspace number (0-based); sticky windows are on every workspace
+        #[serde(default)]
+        workspace: Option<u32>,
+    },
+    /// Run-or-raise: focus the most recent window of `class` (the next one
+    /// if it already has focus), or run `exec` (`sh -c`) if there is none
+    ActivateOrLaunch { class: String, exec: String },
+    /// Give a window the input focus and raise it
+    FocusWindow { id: u32 },
+    /// Ask a window to close (`WM_DELETE_WINDOW`); unresponsive windows get
+    /// a `ForceQuitRequested` instead
+    CloseWindow { id: u32 },
+    /// Move a window's client area to `x`,`y` in root coordinates
+    MoveWindow { id: u32, x: i32, y: i32 },
+    /// Switch to a workspace (0-based)
+    SwitchWorkspace { workspace: u32 },
+    /// Move a window to a workspace (0-based)
+    MoveWindowToWorkspace { id: u32, workspace: u32 },
+    /// Iconify a window
+    MinimizeWindow { id: u32 },
+    /// De-iconify a window (maps and raises it)
+    RestoreWindow { id: u32 },
+    /// Iconify every window of a group (`WM_HINTS` window_group, see `WindowInfo::group`)
+    MinimizeGroup { leader: u32 },
+    /// De-iconify every window of a group
+    RestoreGroup { leader: u32 },
+    /// Maximize or unmaximize a window
+    MaximizeWindow { id: u32, maximized: bool },
+    /// Enter or leave fullscreen
+    SetFullscreen { id: u32, fullscreen: bool },
+    /// Pass all shortcuts to a window while it has focus (VMs, remote
+    /// desktops): the window manager releases its key grabs except the
+    /// Super+Escape toggle
+    InhibitShortcuts { id: u32, inhibit: bool },
+    /// Kill a window's client (XKillClient)
+    ///
+    /// With `force` the local process behind it (`_NET_WM_PID`) also gets
+    /// SIGTERM, then SIGKILL if it does not exit. Shells send this after the
+    /// user confirms a `ForceQuitRequested` prompt.
+    KillWindow {
+        id: u32,
+        #[serde(default)]
+        force: bool,
+    },
+    /// Move a window to the adjacent monitor, keeping its relative position
+    /// (a maximized window is re-maximized there)
+    MoveWindowToMonitor { id: u32, direction: Direction },
+    /// Restrict which parts of a window accept pointer input (XShape input region)
+    ///
+    /// Meant for the shell's full-screen overlay: clicks outside `rects`
+    /// (window-relative) pass through to the windows below. An empty list
+    /// makes the whole window click-through; one rectangle covering the
+    /// window restores normal input.
+    SetInputRegion { window: u32, rects: Vec<Rect> },
+    /// Change the wallpaper (crossfades to the new image)
+    ///
+    /// Without `monitor`/`workspace` this replaces the default wallpaper;
+    /// otherwise it sets an override for that monitor (index) or workspace.
+    /// `mode` defaults to the configured mode.
+    SetWallpaper {
+        path: String,
+        #[serde(default)]
+        mode: Option<WallpaperMode>,
+        #[serde(default)]
+        monitor: Option<u32>,
+        #[serde(default)]
+        workspace: Option<u32>,
+    },
+    /// Stream downscaled frames of a window to a shared-memory file
+    ///
+    /// Replies `LivePreview` with the file path (see `live_preview_path` for
+    /// the layout). Frames are written at up to `fps` while the window
+    /// changes, for a few seconds; send the command again to keep the
+    /// preview alive (e.g. while a taskbar button stays hovered). The reply
+    /// comes before the compositor has (re)created the file: wait until it
+    /// exists with the requested size and a non-zero `sequence` (it never
+    /// does if the compositor failed to create it, so give up eventually).
+    RequestLivePreview { id: u32, width: u32, height: u32, fps: u32 },
+    /// Reserve space for a shell panel along a monitor edge, like an X dock strut
+    ///
+    /// `size` is the thickness from the monitor edge and `start`..`end` the
+    /// covered range along it in monitor coordinates (`end` 0 for the whole
+    /// edge). One panel per edge and monitor; size 0 removes it.
+    SetPanelStrut {
+        edge: PanelEdge,
+        size: u32,
+        start: u32,
+        end: u32,
+        monitor: u32,
+    },
+    /// Watch for the pointer pushing against a monitor edge, e.g. where the
+    /// shell has an auto-hidden panel; pressure is reported with
+    /// `EdgeTriggered` (thresholds are in the window manager's config)
+    SetEdgeTrigger { edge: PanelEdge, monitor: u32, enabled: bool },
+    /// Turn an XKB AccessX keyboard accessibility feature on or off;
+    /// the new state is reported with `AccessibilityChanged`
+    SetAccessibility { feature: AccessibilityFeature, enabled: bool },
+    /// Show the on-screen display, e.g. from the daemon handling the volume
+    /// keys; `label` defaults to the kind's name, `value` (0.0-1.0) adds a bar
+    ShowOsd { kind: OsdKind, label: Option<String>, value: Option<f64> },
+    /// Turn Do Not Disturb on or off: while on, notifications are only
+    /// recorded in the history (critical ones are still shown)
+    SetDoNotDisturb { enabled: bool },
+    /// Recorded notifications, newest first (at most `limit`), for a
+    /// notification center
+    GetNotificationHistory {
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    /// Show or hide the overview of all workspaces (see `WmEvent::ExpoChanged`)
+    SetExpo { enabled: bool },
+    /// Switch the monitors to a DPMS power state now (any input wakes them)
+    SetDpms { state: DpmsState },
+    /// Get a token that lets the next window of an application take focus
+    ///
+    /// Replies `ActivationToken`. A launcher starts the application with
+    /// the token in `DESKTOP_STARTUP_ID` (and `XDG_ACTIVATION_TOKEN`); a new
+    /// window showing it in `_NET_STARTUP_ID` before it expires is focused,
+    /// other windows only demand attention. Tokens are valid once.
+    RequestActivationToken,
+    /// Mute or unmute the sound a window plays (every stream of its
+    /// process); fails if it plays none
+    MuteWindowAudio { id: u32, muted: bool },
+    /// Run an entry the user picked from a menu shown for `ShowWindowMenu`
+    RunWindowMenuAction { id: u32, action: WindowMenuAction },
+    /// Run the entry at `index` of the menu shown for `ShowRootMenu`
+    RunRootMenuEntry { index: u32 },
+    /// End the session: save window state, ask every client to close
+    /// (`WM_DELETE_WINDOW`), then log out, reboot or power off
+    ///
+    /// Windows still open after the timeout are reported with
+    /// `EndSessionBlocked`; sending the command again with `force` kills them
+    /// and proceeds.
+    EndSession {
+        action: SessionAction,
+        #[serde(default)]
+        force: bool,
+    },
+    /// Abort a pending `EndSession` (windows already closed stay closed)
+    CancelEndSession,
+    /// Remember the user's arrangement of taskbar buttons (client ids, first
+    /// button first)
+    ///
+    /// Saved across window manager restarts and reported back in
+    /// `WindowInfo::taskbar_position`; windows missing from `ids` have no
+    /// position and go wherever the shell puts new buttons.
+    ///
+    /// `slots` are the buttons as shown, pinned launchers included, for
+    /// Super+number (`WmEvent::TaskbarSlotActivated`); without them the
+    /// nth window of `ids` is the nth button.
+    SetTaskbarOrder {
+        ids: Vec<u32>,
+        #[serde(default)]
+        slots: Vec<TaskbarSlot>,
+    },
+    /// Pin an application to the dock by desktop-file ID (e.g.
+    /// "org.gnome.Terminal.desktop"), at `position` or last; pinning a
+    /// pinned application moves it (see `WmEvent::PinnedAppsChanged`)
+    PinApp {
+        id: String,
+        #[serde(default)]
+        position: Option<u32>,
+    },
+    /// Remove an application from the dock
+    UnpinApp { id: String },
+    /// The pinned applications and their windows, in dock order
+    ListPinned,
+    /// State of the supervised session components (`[[components]]`)
+    ListComponents,
+}
+
+/// Replies sent from the window manager in answer to a `ShellCommand`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "reply", rename_all = "snake_case")]
+pub enum Reply {
+    /// Command succeeded with nothing to report
+    Ok,
+    /// Answer to `Hello`: the window manager's protocol version and capabilities
+    ///
+    /// `trusted` is false while the client may only run the restricted
+    /// command set (no or wrong token).
+    Welcome {
+        version: String,
+        capabilities: Vec<String>,
+        #[serde(default = "default_trusted")]
+        trusted: bool,
+    },
+    /// Command failed
+    Error { message: String },
+    /// Recent log lines, oldest first
+    DebugDump { filter: String, lines: Vec<String> },
+    /// Answer to `DumpState`; the layout may change between releases
+    State { state: serde_json::Value },
+    /// Performance metrics snapshot
+    Metrics { metrics: MetricsSnapshot },
+    /// Answer to `SelfCheck`; None when the compositor has no OpenGL
+    SelfCheck { gl: Option<GlCapabilities> },
+    /// Managed windows, in no particular order
+    Windows { windows: Vec<WindowInfo> },
+    /// Answer to `GetWindowInfo`
+    WindowDetails { window: WindowDetails },
+    /// Shared-memory file a live preview is streamed to
+    LivePreview { path: String },
+    /// Answer to `GetNotificationHistory`
+    NotificationHistory { do_not_disturb: bool, notifications: Vec<Notification> },
+    /// Answer to `RequestActivationToken`
+    ActivationToken { token: String },
+    /// Answer to `ListPinned`
+    Pinned { apps: Vec<PinnedApp> },
+    /// Answer to `ListComponents`, in config order
+    Components { components: Vec<ComponentStatus> },
+}
+
+/// Unsolicited notifications sent to subscribed clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WmEvent {
+    /// A window started or stopped requesting attention
+    WindowUrgencyChanged { id: u32, urgent: bool },
+    /// A window was iconified
+    WindowMinimized { id: u32 },
+    /// An iconified window was mapped again
+    WindowRestored { id: u32 },
+    /// A window's maximized, fullscreen, shaded or above state changed
+    WindowStateChanged {
+        id: u32,
+        maximized: bool,
+        fullscreen: bool,
+        shaded: bool,
+        above: bool,
+    },
+    /// Pointer position in root coordinates, only sent after `SubscribePointer`
+    ///
+    /// `monitor` is the index of the output under the pointer. Sampled at a
+    /// low rate (10 Hz) and only sent when the position changed, so it suits
+    /// reveal zones and hot corners, not cursor tracking.
+    PointerMoved { x: i32, y: i32, monitor: u32 },
+    /// The user asked for a window's menu (titlebar right-click, menu button
+    /// or `GTK_SHOW_WINDOW_MENU`)
+    ///
+    /// The shell pops up `entries` at `x`,`y` (root coordinates) and answers
+    /// with `RunWindowMenuAction`; the window manager draws no menu itself.
+    ShowWindowMenu {
+        id: u32,
+        x: i32,
+        y: i32,
+        entries: Vec<WindowMenuEntry>,
+    },
+    /// The desktop was right-clicked; the shell pops up the configured root
+    /// menu at `x`,`y` and answers with `RunRootMenuEntry`
+    ShowRootMenu { x: i32, y: i32, entries: Vec<RootMenuItem> },
+    /// `EndSession` timed out with these windows still open (e.g. waiting for
+    /// an unsaved-changes prompt); the shell asks the user to force or cancel
+    EndSessionBlocked { action: SessionAction, windows: Vec<u32> },
+    /// The user tried to close a window that stopped answering pings
+    ///
+    /// The shell asks whether to force quit and answers with `KillWindow`
+    /// (`force` set); `pid` is the local process, if known.
+    ForceQuitRequested { id: u32, title: String, pid: Option<u32> },
+    /// The current workspace changed and with it the active settings profile
+    ///
+    /// `profile` is the profile's name (its workspace number if unnamed), or
+    /// None when the workspace has no profile and the global settings apply.
+    WorkspaceProfileChanged { workspace: u32, profile: Option<String> },
+    /// Window rectangles of every workspace, for a pager mini-map
+    ///
+    /// Coordinates are root coordinates divided by `PAGER_SCALE`; `width` and
+    /// `height` are the screen size in the same units. Sent when the layout
+    /// changed (at most a few times per second) and after a client subscribes.
+    WorkspaceLayout { width: u32, height: u32, workspaces: Vec<WorkspaceInfo> },
+    /// Managed windows bottom to top, mirroring `_NET_CLIENT_LIST_STACKING`;
+    /// sent whenever the stacking order changes
+    ClientListStacking { ids: Vec<u32> },
+    /// A window that resizes in steps (e.g. a terminal's character cells)
+    /// is being resized interactively
+    ///
+    /// The shell shows a size popup such as "80x24" centred on `x`,`y` (root
+    /// coordinates); sent whenever the size changes.
+    ResizeFeedback { id: u32, columns: u32, rows: u32, x: i32, y: i32 },
+    /// The resize behind `ResizeFeedback` ended; hide the popup
+    ResizeFeedbackEnded { id: u32 },
+    /// The pointer pushed against an edge enabled with `SetEdgeTrigger`;
+    /// the shell reveals its panel there. Sent once until the pointer
+    /// leaves the edge.
+    EdgeTriggered { edge: PanelEdge, monitor: u32 },
+    /// Keyboard accessibility features changed, over IPC, in the config or
+    /// from the keyboard (e.g. Shift pressed five times); the shell shows
+    /// indicators for the enabled ones
+    AccessibilityChanged { sticky_keys: bool, slow_keys: bool, bounce_keys: bool, mouse_keys: bool },
+    /// Show the on-screen display (sent instead of drawing it when the
+    /// window manager's `osd.render` is `"shell"`)
+    Osd { kind: OsdKind, label: String, value: Option<f64> },
+    /// An application sent a desktop notification (not sent for ordinary
+    /// ones while Do Not Disturb is on)
+    Notification { notification: Notification },
+    /// The sender withdrew a notification
+    NotificationClosed { id: u32 },
+    DoNotDisturbChanged { enabled: bool },
+    /// A fullscreen window now covers a monitor, or no longer does (it was
+    /// restored, minimized, lost focus or left the workspace); the shell
+    /// hides its panels on the monitor while `active`
+    FullscreenChanged { monitor: u32, active: bool },
+    /// A window started or stopped playing sound, or was muted or unmuted
+    ///
+    /// `playing` is true while any of its streams plays, `muted` while all
+    /// of them are muted. Both are false once it has no streams left.
+    WindowAudioState { id: u32, playing: bool, muted: bool },
+    /// The overview of all workspaces opened, changed or closed
+    ///
+    /// The compositor draws the workspaces and their windows; the shell may
+    /// label the tiles (root coordinates). `workspaces` is empty when
+    /// `active` is false.
+    ExpoChanged { active: bool, workspaces: Vec<ExpoWorkspace> },
+    /// A window closed; its last frame is in the shared-memory file `shm`
+    /// (see `closed_snapshot_path` for the layout) for a short while, so
+    /// the shell can animate the close or show a placeholder. Sent once
+    /// the file is written, so not at all when the compositor's
+    /// `close_snapshot_ms` is unset or it had no frame of the window.
+    WindowClosedSnapshot { id: u32, shm: String },
+    /// The user has been idle (no input) for one of the window manager's
+    /// `idle.thresholds`, or is active again after that (`idle` false,
+    /// `idle_ms` how long the idle period lasted), e.g. to dim the panel,
+    /// pause animations or show a screensaver
+    UserIdleStateChanged { idle: bool, idle_ms: u64 },
+    /// Taskbar button `slot` (0-based, see `SetTaskbarOrder`) was activated
+    /// from the keyboard: `window` was focused, or the pinned application
+    /// launched when `None`, so the shell can highlight the button
+    TaskbarSlotActivated { slot: u32, window: Option<u32> },
+    /// Applications were pinned, unpinned or reordered (`PinApp`, `UnpinApp`)
+    PinnedAppsChanged { apps: Vec<PinnedApp> },
+    /// Alt+Tab started, moved to another window or ended (`active` false,
+    /// no `windows`), for the shell's switcher
+    CycleChanged { active: bool, windows: Vec<CycleEntry>, selected: Option<u32> },
+    /// A supervised session component (e.g. area-shell) started, crashed
+    /// and is about to restart, gave up or was stopped
+    ComponentStatusChanged { component: ComponentStatus },
+}
+
+/// Anything the window manager sends to a client
+///
+/// Replies and events carry distinct tag fields (`reply` / `event`), so the
+/// envelope itself adds nothing to the wire format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WmMessage {
+    Reply(Reply),
+    Event(WmEvent),
+}
+
+fn default_trusted() -> bool {
+    true
+}
+
+impl WmEvent {
+    /// Capability a client must announce in `Hello` to receive this event
+    /// (None: part of the base protocol)
+    pub fn capability(&self) -> Option<&'static str> {
+        match self {
+            Self::WorkspaceLayout { .. } => Some(crate::version::capability::WORKSPACE_LAYOUT),
+            Self::ClientListStacking { .. } => Some(crate::version::capability::CLIENT_LIST_STACKING),
+            Self::ResizeFeedback { .. } | Self::ResizeFeedbackEnded { .. } => {
+                Some(crate::version::capability::RESIZE_FEEDBACK)
+            }
+            Self::EdgeTriggered { .. } => Some(crate::version::capability::EDGE_TRIGGER),
+            Self::AccessibilityChanged { .. } => Some(crate::version::capability::ACCESSIBILITY),
+            Self::Osd { .. } => Some(crate::version::capability::OSD),
+            Self::Notification { .. } | Self::NotificationClosed { .. } | Self::DoNotDisturbChanged { .. } => {
+                Some(crate::version::capability::NOTIFICATIONS)
+            }
+            Self::FullscreenChanged { .. } => Some(crate::version::capability::FULLSCREEN),
+            Self::WindowAudioState { .. } => Some(crate::version::capability::WINDOW_AUDIO),
+            Self::ExpoChanged { .. } => Some(crate::version::capability::EXPO),
+            Self::WindowClosedSnapshot { .. } => Some(crate::version::capability::CLOSED_SNAPSHOT),
+            Self::UserIdleStateChanged { .. } => Some(crate::version::capability::USER_IDLE),
+            Self::TaskbarSlotActivated { .. } => Some(crate::version::capability::TASKBAR_SLOTS),
+            Self::PinnedAppsChanged { .. } => Some(crate::version::capability::PINNED_APPS),
+            Self::CycleChanged { .. } => Some(crate::version::capability::CYCLE),
+            Self::ComponentStatusChanged { .. } => Some(crate::version::capability::COMPONENTS),
+            _ => None,
+        }
+    }
+}
+
+impl Reply {
+    /// Build an error reply
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::Error { message: message.into() }
+    }
+}
+
+/// A rectangle in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// How a wallpaper image is scaled to its monitor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WallpaperMode {
+    /// Scale to cover the monitor, cropping the overflow
+    #[default]
+    Fill,
+    /// Scale to fit inside the monitor, keeping the aspect ratio
+    Fit,
+    /// Scale to the monitor size, ignoring the aspect ratio
+    Stretch,
+    /// Natural size, centered
+    Center,
+    /// Natural size, repeated
+    Tile,
+}
+
+/// Monitor edge a shell panel is attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PanelEdge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// What an on-screen display is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OsdKind {
+    Volume,
+    Brightness,
+    KeyboardLayout,
+    Workspace,
+}
+
+impl OsdKind {
+    /// Label used when none is given
+    pub fn default_label(self) -> &'static str {
+        match self {
+            Self::Volume => "Volume",
+            Self::Brightness => "Brightness",
+            Self::KeyboardLayout => "Keyboard layout",
+            Self::Workspace => "Workspace",
+        }
+    }
+}
+
+/// Monitor power state (DPMS)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DpmsState {
+    On,
+    Standby,
+    Suspend,
+    Off,
+}
+
+/// Keyboard accessibility feature (XKB AccessX)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessibilityFeature {
+    /// Modifiers stay pressed until the next key
+    StickyKeys,
+    /// Keys must be held for a while before they count
+    SlowKeys,
+    /// Repeated presses of a key in quick succession are ignored
+    BounceKeys,
+    /// The numeric keypad moves the pointer
+    MouseKeys,
+}
+
+/// Direction on screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Action of a window menu entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowMenuAction {
+    /// Drag the window with the pointer until a button is released
+    Move,
+    /// Resize the window by its bottom-right corner with the pointer
+    Resize,
+    Minimize,
+    /// Toggle maximization
+    Maximize,
+    /// Toggle `_NET_WM_STATE_ABOVE`
+    AlwaysOnTop,
+    /// Move the window to a workspace (0-based)
+    MoveToWorkspace(u32),
+    Close,
+}
+
+/// What happens once `EndSession` has closed all windows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionAction {
+    /// Exit the window manager, ending the X session
+    Logout,
+    Reboot,
+    Shutdown,
+}
+
+/// An entry of a window menu, in display order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowMenuEntry {
+    pub action: WindowMenuAction,
+    pub label: String,
+    /// Shown with a check mark (maximized, always on top, current workspace)
+    pub checked: bool,
+}
+
+/// An entry of the root menu, in display order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootMenuItem {
+    pub label: String,
+    /// A divider line, not selectable
+    pub separator: bool,
+}
+
+/// A managed window as seen by clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowInfo {
+    /// Client window id (not the frame)
+    pub id: u32,
+    pub title: String,
+    /// Client area geometry in root coordinates
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub focused: bool,
+    /// Minimized (hidden, shown only in the taskbar)
+    pub iconified: bool,
+    /// Requesting attention
+    pub urgent: bool,
+    pub maximized: bool,
+    pub fullscreen: bool,
+    pub shaded: bool,
+    /// Kept above normal windows (`_NET_WM_STATE_ABOVE`)
+    pub above: bool,
+    /// Leader of the window's group (`WM_HINTS` window_group), for `MinimizeGroup`
+    #[serde(default)]
+    pub group: Option<u32>,
+    /// Receives all shortcuts while focused (`InhibitShortcuts`)
+    #[serde(default)]
+    pub shortcuts_inhibited: bool,
+    /// Button position set with `SetTaskbarOrder`
+    #[serde(default)]
+    pub taskbar_position: Option<u32>,
+    /// Pinned application the window belongs to (desktop-file ID)
+    #[serde(default)]
+    pub pinned: Option<String>,
+}
+
+/// A managed window with the properties needed for window menus and scripts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowDetails {
+    #[serde(flatten)]
+    pub info: WindowInfo,
+    /// `_NET_WM_PID`, if set
+    pub pid: Option<u32>,
+    /// `WM_CLASS` class part (e.g. `Firefox`)
+    pub class: Option<String>,
+    /// `WM_CLASS` instance part (e.g. `Navigator`)
+    pub instance: Option<String>,
+    /// `WM_WINDOW_ROLE`
+    pub role: Option<String>,
+    /// `_NET_WM_WINDOW_TYPE` without prefix, lowercase (e.g. `dialog`)
+    pub window_type: String,
+    /// Workspace number (0-based), `None` when sticky
+    pub workspace: Option<u32>,
+    /// Monitor containing the window's center
+    pub monitor: Option<u32>,
+    /// Parent window (`WM_TRANSIENT_FOR`)
+    pub transient_for: Option<u32>,
+    pub sticky: bool,
+    pub modal: bool,
+    pub skip_taskbar: bool,
+    pub skip_pager: bool,
+    /// The window has an icon (`_NET_WM_ICON` or `WM_HINTS` icon pixmap)
+    pub has_icon: bool,
+}
+
+/// A workspace as drawn by a pager
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceInfo {
+    /// Workspace number (0-based)
+    pub index: u32,
+    pub name: String,
+    pub current: bool,
+    /// Windows shown on the workspace, bottom to top (sticky windows appear
+    /// on every workspace; minimized and skip-pager windows are left out)
+    pub windows: Vec<PagerWindow>,
+}
+
+/// A taskbar button, for activation by number
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskbarSlot {
+    /// Windows on the button, in the order repeated presses cycle them
+    #[serde(default)]
+    pub windows: Vec<u32>,
+    /// Command of the pinned application, run when none of `windows` exists
+    #[serde(default)]
+    pub launch: Option<String>,
+    /// Pinned application of the button (see `PinApp`), whose windows and
+    /// desktop file stand in for empty `windows` and `launch`
+    #[serde(default)]
+    pub pinned: Option<String>,
+}
+
+/// An application pinned to the dock
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinnedApp {
+    /// Desktop-file ID
+    pub id: String,
+    /// Name and icon from the desktop file (None if it was not found)
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Open windows of the application (matched by StartupWMClass or the
+    /// ID against WM_CLASS), most recently focused first
+    #[serde(default)]
+    pub windows: Vec<u32>,
+}
+
+/// A session component run by the window manager's supervisor
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComponentStatus {
+    /// `name` of its `[[components]]` table
+    pub name: String,
+    pub state: ComponentState,
+    /// Process ID while running
+    #[serde(default)]
+    pub pid: Option<u32>,
+    /// Restarts after crashes so far
+    #[serde(default)]
+    pub restarts: u32,
+    /// Exit status of the last run (None while running or when killed by a signal)
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+}
+
+/// Lifecycle state of a session component
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentState {
+    Running,
+    /// Crashed; restarting after a backoff delay
+    Restarting,
+    /// Exited cleanly or removed from the config
+    Stopped,
+    /// Crashed too often (or has `restart = false`); not restarted
+    Failed,
+}
+
+/// A workspace tile of the overview
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpoWorkspace {
+    /// Workspace number (0-based)
+    pub index: u32,
+    pub name: String,
+    pub rect: Rect,
+    pub current: bool,
+}
+
+/// A desktop notification (org.freedesktop.Notifications `Notify`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: u32,
+    pub app_name: String,
+    /// Icon name or `file://` URI
+    pub app_icon: String,
+    pub summary: String,
+    /// May contain simple markup
+    pub body: String,
+    /// 0 low, 1 normal, 2 critical
+    pub urgency: u8,
+    /// Action keys and labels, alternating
+    pub actions: Vec<String>,
+    /// Milliseconds until it expires (-1: server default, 0: never)
+    pub expire_timeout: i32,
+    /// When it arrived (seconds since the Unix epoch)
+    pub timestamp: u64,
+}
+
+/// A window of the Alt+Tab switcher, in cycle order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CycleEntry {
+    pub id: u32,
+    pub title: String,
+    /// Live thumbnail, laid out like a live preview (`live_preview_path`)
+    /// and streamed until the cycle ends; None when thumbnails are off.
+    /// Minimized windows get no frames.
+    #[serde(default)]
+    pub preview: Option<String>,
+}
+
+/// A window on a pager mini-map
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PagerWindow {
+    /// Client window id (not the frame)
+    pub id: u32,
+    /// Frame geometry, scaled down by `PAGER_SCALE`
+    pub rect: Rect,
+    pub focused: bool,
+    pub urgent: bool,
+}
+
+/// Distribution of a timing measurement (milliseconds)
+///
+/// Percentiles and max cover the most recent samples only; `count` and
+/// `total_ms` cover the whole session.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimingSummary {
+    pub count: u64,
+    pub total_ms: f64,
+    pub avg_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Performance counters collected by the window manager
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    /// Time since the window manager started
+    pub uptime_ms: u64,
+    /// Compositor frame render times
+    pub frame_time: TimingSummary,
+    /// Damage notifications received by the compositor
+    pub damage_events: u64,
+    /// Time from reading an X event off the socket to finishing its handling
+    pub event_latency: TimingSummary,
+    /// Time from receiving an IPC command to sending its reply
+    pub ipc_round_trip: TimingSummary,
+    /// Bits per color channel of the compositor's framebuffer (8, or 10
+    /// with deep color; 0 when not composited)
+    #[serde(default)]
+    pub framebuffer_bits: u32,
+}
+
+/// The compositor's OpenGL setup, for driver bug reports
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GlCapabilities {
+    pub vendor: String,
+    pub renderer: String,
+    pub version: String,
+    pub shading_language_version: String,
+    pub glx_version: String,
+    /// Rendering goes to the GPU directly (not through the X server)
+    pub direct_rendering: bool,
+    /// Buffer swaps wait for vertical blank
+    pub vsync: bool,
+    /// Bits per color channel of the framebuffer
+    pub color_bits: u32,
+    pub max_texture_size: u32,
+    /// OpenGL and GLX extensions
+    pub extensions: Vec<String>,
+}
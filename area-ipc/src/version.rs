@@ -0,0 +1,103 @@
+//! Protocol versioning
+//!
+//! Clients open with `ShellCommand::Hello`, the window manager answers with
+//! `Reply::Welcome`. Both sides send a semver-style version and the optional
+//! features they understand. Events behind a capability are only sent to
+//! clients that announced it, so an older shell never has to decode a
+//! variant it does not know. Clients that skip the handshake, and window
+//! managers that predate it, fall back to the base protocol.
+
+use std::fmt;
+
+/// Version of the protocol implemented by this crate
+pub const PROTOCOL_VERSION: Version = Version { major: 1, minor: 0, patch: 0 };
+
+/// Optional features implemented by this crate (see `WmEvent::capability`)
+pub const CAPABILITIES: &[&str] = &[
+    capability::WORKSPACE_LAYOUT,
+    capability::CLIENT_LIST_STACKING,
+    capability::RESIZE_FEEDBACK,
+    capability::EDGE_TRIGGER,
+    capability::ACCESSIBILITY,
+    capability::OSD,
+    capability::NOTIFICATIONS,
+    capability::FULLSCREEN,
+    capability::WINDOW_AUDIO,
+    capability::EXPO,
+    capability::CLOSED_SNAPSHOT,
+    capability::USER_IDLE,
+    capability::TASKBAR_SLOTS,
+    capability::PINNED_APPS,
+    capability::CYCLE,
+    capability::COMPONENTS,
+];
+
+/// Capability names
+pub mod capability {
+    /// `WmEvent::WorkspaceLayout`
+    pub const WORKSPACE_LAYOUT: &str = "workspace_layout";
+    /// `WmEvent::ClientListStacking`
+    pub const CLIENT_LIST_STACKING: &str = "client_list_stacking";
+    /// `WmEvent::ResizeFeedback` and `WmEvent::ResizeFeedbackEnded`
+    pub const RESIZE_FEEDBACK: &str = "resize_feedback";
+    /// `WmEvent::EdgeTriggered`
+    pub const EDGE_TRIGGER: &str = "edge_trigger";
+    /// `WmEvent::AccessibilityChanged`
+    pub const ACCESSIBILITY: &str = "accessibility";
+    /// `WmEvent::Osd`
+    pub const OSD: &str = "osd";
+    /// `WmEvent::Notification`, `WmEvent::NotificationClosed` and
+    /// `WmEvent::DoNotDisturbChanged`
+    pub const NOTIFICATIONS: &str = "notifications";
+    /// `WmEvent::FullscreenChanged`
+    pub const FULLSCREEN: &str = "fullscreen";
+    /// `WmEvent::WindowAudioState`
+    pub const WINDOW_AUDIO: &str = "window_audio";
+    /// `WmEvent::ExpoChanged`
+    pub const EXPO: &str = "expo";
+    /// `WmEvent::WindowClosedSnapshot`
+    pub const CLOSED_SNAPSHOT: &str = "closed_snapshot";
+    /// `WmEvent::UserIdleStateChanged`
+    pub const USER_IDLE: &str = "user_idle";
+    /// `ShellCommand::SetTaskbarOrder` takes `slots`, and
+    /// `WmEvent::TaskbarSlotActivated`
+    pub const TASKBAR_SLOTS: &str = "taskbar_slots";
+    /// `PinApp`, `UnpinApp`, `ListPinned` and `WmEvent::PinnedAppsChanged`
+    pub const PINNED_APPS: &str = "pinned_apps";
+    /// `WmEvent::CycleChanged`
+    pub const CYCLE: &str = "cycle";
+    /// `ListComponents` and `WmEvent::ComponentStatusChanged`
+    pub const COMPONENTS: &str = "components";
+}
+
+/// A `major.minor.patch` protocol version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    /// Parse `major.minor.patch` (missing minor/patch count as 0)
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.trim().splitn(3, '.').map(str::parse::<u32>);
+        let major = parts.next()?.ok()?;
+        let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+        let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+        Some(Self { major, minor, patch })
+    }
+
+    /// Whether peers speaking `self` and `other` understand each other
+    ///
+    /// Same major version; before 1.0 the minor version must match too.
+    pub fn is_compatible(&self, other: &Version) -> bool {
+        self.major == other.major && (self.major != 0 || self.minor == other.minor)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
@@ -0,0 +1,53 @@
+//! Compositor throughput under synthetic load
+//!
+//! Runs `area-loadgen` with growing numbers of damage-spamming windows
+//! against `$DISPLAY` (a nested Xephyr session from scripts/xephyr-dev.sh
+//! works well) and prints one line of JSON results per scenario, for
+//! comparing renderer and damage-pipeline changes:
+//!
+//! ```sh
+//! DISPLAY=:99 cargo bench --bench compositor
+//! AREA_BENCH_DURATION=30 AREA_BENCH_WINDOWS=4,64 cargo bench --bench compositor
+//! ```
+//!
+//! Without a display the scenarios are skipped.
+
+use std::process::Command;
+
+/// Window counts run when `AREA_BENCH_WINDOWS` is unset
+const DEFAULT_WINDOWS: &[u32] = &[1, 8, 32];
+/// Seconds per scenario when `AREA_BENCH_DURATION` is unset
+const DEFAULT_DURATION: u32 = 10;
+
+fn main() {
+    if std::env::var_os("DISPLAY").is_none() {
+        eprintln!("compositor bench: DISPLAY is not set, skipping");
+        return;
+    }
+    let windows: Vec<u32> = match std::env::var("AREA_BENCH_WINDOWS") {
+        Ok(list) => list.split(',').filter_map(|n| n.trim().parse().ok()).collect(),
+        Err(_) => DEFAULT_WINDOWS.to_vec(),
+    };
+    let duration = std::env::var("AREA_BENCH_DURATION").ok().and_then(|s| s.parse().ok()).unwrap_or(DEFAULT_DURATION);
+
+    let mut failed = false;
+    for count in windows {
+        let output = Command::new(env!("CARGO_BIN_EXE_area-loadgen"))
+            .args(["--windows", &count.to_string(), "--duration", &duration.to_string(), "--json"])
+            .output();
+        match output {
+            Ok(output) if output.status.success() => print!("{}", String::from_utf8_lossy(&output.stdout)),
+            Ok(output) => {
+                eprintln!("compositor bench: {} windows failed: {}", count, String::from_utf8_lossy(&output.stderr).trim());
+                failed = true;
+            }
+            Err(e) => {
+                eprintln!("compositor bench: failed to run area-loadgen: {}", e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -349,6 +349,12 @@ else
     fi
 fi
 
+if [ -f "$PROJECT_ROOT/session/area.portal" ]; then
+    run_cmd "Creating portal directory" mkdir -p "$INSTALL_PREFIX/share/xdg-desktop-portal/portals"
+    run_cmd "Installing Screenshot portal backend" install -m 644 "$PROJECT_ROOT/session/area.portal" "$INSTALL_PREFIX/share/xdg-desktop-portal/portals/area.portal"
+    run_cmd "Installing portal preferences" install -m 644 "$PROJECT_ROOT/session/area-portals.conf" "$INSTALL_PREFIX/share/xdg-desktop-portal/area-portals.conf"
+fi
+
 echo ""
 
 # ============================================================================
@@ -0,0 +1,196 @@
+//! Per-window audio state
+//!
+//! Watches the sound server's playback streams (PulseAudio "sink inputs",
+//! also served by PipeWire's pipewire-pulse) with `pactl`, so the shell can
+//! show which windows are playing sound and mute them. A stream belongs to
+//! the windows of the nearest process in its ancestry that has any, its own
+//! process first (browsers play audio from child processes), see
+//! `AudioStream::processes`. A terminal or launcher doesn't get the streams
+//! of the programs it started once they have windows of their own.
+//!
+//! `pactl subscribe` reports stream changes; after each burst the streams
+//! are listed again (`pactl --format=json list sink-inputs`, pactl 16 and
+//! later) and the whole set is handed to the main loop.
+
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// Quiet time after a change before listing the streams again
+const SETTLE: Duration = Duration::from_millis(100);
+/// Wait before restarting `pactl subscribe` after it exits (sound server restart)
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+/// Parent processes searched for a window's PID
+const MAX_ANCESTORS: usize = 8;
+
+/// A playback stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioStream {
+    /// Sink input index, for `pactl set-sink-input-mute`
+    pub index: u32,
+    /// The playing process and its ancestors, nearest first
+    pub processes: Vec<u32>,
+    /// Not paused ("corked")
+    pub playing: bool,
+    pub muted: bool,
+}
+
+/// Sink input as printed by `pactl --format=json`
+#[derive(Debug, Deserialize)]
+struct SinkInput {
+    index: u32,
+    #[serde(default)]
+    corked: bool,
+    #[serde(default)]
+    mute: bool,
+    #[serde(default)]
+    properties: HashMap<String, serde_json::Value>,
+}
+
+/// Start watching the streams; every change sends the full list
+pub fn watch() -> mpsc::UnboundedReceiver<Vec<AudioStream>> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        loop {
+            match follow(&tx).await {
+                Ok(()) => {}
+                Err(e) if e.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) => {
+                    warn!("pactl not found, window audio indicators disabled");
+                    return;
+                }
+                Err(e) => warn!("Audio stream monitor failed: {:#}", e),
+            }
+            if tx.is_closed() {
+                return;
+            }
+            tokio::time::sleep(RESTART_DELAY).await;
+        }
+    });
+    rx
+}
+
+/// Send the streams now and after every change, until `pactl subscribe` exits
+async fn follow(tx: &mpsc::UnboundedSender<Vec<AudioStream>>) -> Result<()> {
+    let mut child = tokio::process::Command::new("pactl")
+        .arg("subscribe")
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("failed to run pactl")?;
+    let stdout = child.stdout.take().context("no pactl output")?;
+    let mut lines = BufReader::new(stdout).lines();
+    if tx.send(list().await?).is_err() {
+        return Ok(());
+    }
+    // Lines look like "Event 'change' on sink-input #42"
+    while let Some(line) = lines.next_line().await? {
+        if !line.contains("sink-input") {
+            continue;
+        }
+        // Skip the rest of the burst (a new stream reports several changes)
+        while let Ok(Ok(Some(_))) = tokio::time::timeout(SETTLE, lines.next_line()).await {}
+        if tx.send(list().await?).is_err() {
+            return Ok(());
+        }
+    }
+    let status = child.wait().await?;
+    debug!("pactl subscribe exited with {}", status);
+    Ok(())
+}
+
+/// Current playback streams of processes that tell their PID
+async fn list() -> Result<Vec<AudioStream>> {
+    let output = tokio::process::Command::new("pactl")
+        .args(["--format=json", "list", "sink-inputs"])
+        .output()
+        .await
+        .context("failed to run pactl")?;
+    if !output.status.success() {
+        anyhow::bail!("pactl list sink-inputs failed ({})", output.status);
+    }
+    let inputs: Vec<SinkInput> = serde_json::from_slice(&output.stdout).context("unexpected pactl output (pactl 16 or later needed)")?;
+    Ok(inputs
+        .into_iter()
+        .filter_map(|input| {
+            // A string property ("1234"), a number in some versions
+            let pid = match input.properties.get("application.process.id")? {
+                serde_json::Value::String(pid) => pid.parse().ok()?,
+                value => u32::try_from(value.as_u64()?).ok()?,
+            };
+            Some(AudioStream { index: input.index, processes: ancestors(pid), playing: !input.corked, muted: input.mute })
+        })
+        .collect())
+}
+
+/// A process and its ancestors (up to `MAX_ANCESTORS`, without init)
+fn ancestors(pid: u32) -> Vec<u32> {
+    std::iter::successors(Some(pid), |&pid| parent(pid)).take(MAX_ANCESTORS + 1).collect()
+}
+
+/// Parent process, from /proc/<pid>/stat
+fn parent(pid: u32) -> Option<u32> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // "pid (comm) state ppid ...", comm may contain spaces and parentheses
+    let ppid = stat.rsplit_once(')')?.1.split_whitespace().nth(1)?.parse().ok()?;
+    (ppid > 1).then_some(ppid)
+}
+
+/// Indices into `streams` of the streams each window plays; `windows` are
+/// (window, `_NET_WM_PID`) pairs
+pub fn streams_by_window(streams: &[AudioStream], windows: &[(u32, u32)]) -> HashMap<u32, Vec<usize>> {
+    let mut by_window: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (index, stream) in streams.iter().enumerate() {
+        let Some(&owner) = stream.processes.iter().find(|&&pid| windows.iter().any(|&(_, p)| p == pid)) else {
+            continue;
+        };
+        for &(window, _) in windows.iter().filter(|&&(_, pid)| pid == owner) {
+            by_window.entry(window).or_default().push(index);
+        }
+    }
+    by_window
+}
+
+/// Mute or unmute streams in the background
+pub fn set_muted(indices: Vec<u32>, muted: bool) {
+    tokio::spawn(async move {
+        for index in indices {
+            let status = tokio::process::Command::new("pactl")
+                .args(["set-sink-input-mute", &index.to_string(), if muted { "1" } else { "0" }])
+                .status()
+                .await;
+            match status {
+                Ok(status) if status.success() => {}
+                Ok(status) => warn!("Muting sink input {} failed ({})", index, status),
+                Err(e) => warn!("Muting sink input {} failed: {}", index, e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(index: u32, processes: &[u32]) -> AudioStream {
+        AudioStream { index, processes: processes.to_vec(), playing: true, muted: false }
+    }
+
+    #[test]
+    fn streams_go_to_the_nearest_process_with_a_window() {
+        // A terminal (pid 100, windows 1 and 2) started a player (pid 200,
+        // window 3) and a windowless one (pid 300)
+        let windows = [(1, 100), (2, 100), (3, 200)];
+        let streams = [stream(10, &[201, 200, 100]), stream(11, &[300, 100]), stream(12, &[400, 1])];
+        let by_window = streams_by_window(&streams, &windows);
+        assert_eq!(by_window.get(&3), Some(&vec![0]));
+        assert_eq!(by_window.get(&1), Some(&vec![1]));
+        assert_eq!(by_window.get(&2), Some(&vec![1]));
+        assert_eq!(by_window.len(), 3);
+    }
+}
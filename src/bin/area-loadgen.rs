@@ -0,0 +1,353 @@
+//! area-loadgen: synthetic compositor load
+//!
+//! Opens N test windows that repaint themselves with a new color at a fixed
+//! rate (each repaint is a full-window damage event), and meanwhile
+//! measures end-to-end latency with a small probe window: it is filled with
+//! a fresh color, and the time until that color can be read back from the
+//! screen is one sample. A sample not seen within `--deadline` counts as a
+//! dropped frame. With Area running, its compositor metrics (`GetMetrics`)
+//! are reported alongside, so renderer and damage-pipeline regressions show
+//! up as numbers. Used by `cargo bench` (benches/compositor.rs).
+
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use area_ipc::{Client, MetricsSnapshot, Reply, ShellCommand};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+
+const USAGE: &str = "\
+Usage: area-loadgen [options]
+
+Options:
+  --windows <n>          Damage-spamming windows (default 16)
+  --size <W>x<H>         Size of each window (default 256x256)
+  --rate <hz>            Repaints per second of each window (default 60)
+  --duration <secs>      Measurement time (default 10)
+  --deadline <ms>        A probe frame not on screen by then is dropped (default 100)
+  --json                 Print the results as JSON";
+
+/// Side of the probe window
+const PROBE_SIZE: u16 = 32;
+/// Pause between probe frames, so probes do not add load of their own
+const PROBE_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Command-line options
+#[derive(Debug, Clone)]
+struct Options {
+    windows: u32,
+    width: u16,
+    height: u16,
+    rate: u32,
+    duration: Duration,
+    deadline: Duration,
+    json: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            windows: 16,
+            width: 256,
+            height: 256,
+            rate: 60,
+            duration: Duration::from_secs(10),
+            deadline: Duration::from_millis(100),
+            json: false,
+        }
+    }
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            if arg == "--json" {
+                options.json = true;
+                continue;
+            }
+            let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+            let number = |value: &str| value.parse::<u32>().map_err(|_| format!("invalid number: {}", value));
+            match arg {
+                "--windows" => options.windows = number(value)?,
+                "--rate" => options.rate = number(value)?.max(1),
+                "--duration" => options.duration = Duration::from_secs(number(value)?.into()),
+                "--deadline" => options.deadline = Duration::from_millis(number(value)?.into()),
+                "--size" => {
+                    let (width, height) = value.split_once('x').ok_or_else(|| format!("invalid size: {}", value))?;
+                    let size = |value: &str| value.parse::<u16>().ok().filter(|&v| v > 0).ok_or_else(|| format!("invalid size: {}", value));
+                    options.width = size(width)?;
+                    options.height = size(height)?;
+                }
+                _ => return Err(format!("unknown option: {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Probe latencies and repaint counts of one run
+#[derive(Debug, Default)]
+struct Results {
+    /// End-to-end latency of each probe frame that made it to the screen (ms)
+    latencies: Vec<f64>,
+    dropped: u32,
+    /// Repaints of the load windows
+    repaints: u64,
+    elapsed: Duration,
+}
+
+impl Results {
+    fn percentile(&self, p: f64) -> f64 {
+        let mut sorted = self.latencies.clone();
+        sorted.sort_by(f64::total_cmp);
+        match sorted.len() {
+            0 => 0.0,
+            n => sorted[((n - 1) as f64 * p).round() as usize],
+        }
+    }
+}
+
+/// A window filled with solid colors
+struct TestWindow {
+    window: Window,
+    gc: Gcontext,
+    width: u16,
+    height: u16,
+}
+
+impl TestWindow {
+    fn create(conn: &RustConnection, screen: &Screen, x: i16, y: i16, width: u16, height: u16) -> Result<Self> {
+        let window = conn.generate_id()?;
+        // Override-redirect, so windows stay where the measurement expects them
+        conn.create_window(
+            screen.root_depth,
+            window,
+            screen.root,
+            x,
+            y,
+            width,
+            height,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            screen.root_visual,
+            &CreateWindowAux::new().override_redirect(1).background_pixel(screen.black_pixel),
+        )?;
+        let gc = conn.generate_id()?;
+        conn.create_gc(gc, window, &CreateGCAux::new())?;
+        conn.map_window(window)?;
+        Ok(Self { window, gc, width, height })
+    }
+
+    fn fill(&self, conn: &RustConnection, color: u32) -> Result<()> {
+        conn.change_gc(self.gc, &ChangeGCAux::new().foreground(color))?;
+        conn.poly_fill_rectangle(self.window, self.gc, &[Rectangle { x: 0, y: 0, width: self.width, height: self.height }])?;
+        Ok(())
+    }
+}
+
+/// Distinct 24-bit color for frame `n`
+fn color(n: u64) -> u32 {
+    (n.wrapping_mul(0x9E37_79B1) as u32 & 0x00FF_FFFF) | 0x0010_1010
+}
+
+/// The color on screen at the probe's centre
+fn screen_color(conn: &RustConnection, root: Window) -> Result<u32> {
+    let centre = (PROBE_SIZE / 2) as i16;
+    let image = conn.get_image(ImageFormat::Z_PIXMAP, root, centre, centre, 1, 1, !0)?.reply()?;
+    let bytes = image.data.get(..4).context("short image")?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) & 0x00FF_FFFF)
+}
+
+/// Repaint the load windows at `rate` until `stop` is set
+fn spam(conn: RustConnection, windows: Vec<TestWindow>, rate: u32, stop: Arc<AtomicBool>, repaints: Arc<AtomicU64>) -> Result<()> {
+    let interval = Duration::from_secs(1) / rate;
+    let mut next = Instant::now();
+    let mut frame = 0u64;
+    while !stop.load(Ordering::Relaxed) {
+        for (index, window) in windows.iter().enumerate() {
+            window.fill(&conn, color(frame + index as u64))?;
+        }
+        conn.flush()?;
+        // Keep the request queue from growing when the server falls behind
+        conn.get_input_focus()?.reply()?;
+        repaints.fetch_add(windows.len() as u64, Ordering::Relaxed);
+        frame += 1;
+        next += interval;
+        match next.checked_duration_since(Instant::now()) {
+            Some(wait) => std::thread::sleep(wait),
+            None => next = Instant::now(),
+        }
+    }
+    Ok(())
+}
+
+/// Compositor metrics from a running Area, if there is one
+fn area_metrics() -> Option<MetricsSnapshot> {
+    let mut client = Client::connect().ok()?;
+    client.hello().ok()?;
+    match client.request(&ShellCommand::GetMetrics).ok()? {
+        Reply::Metrics { metrics } => Some(metrics),
+        _ => None,
+    }
+}
+
+fn run(options: &Options) -> Result<Results> {
+    let (conn, screen_num) = RustConnection::connect(None).context("failed to connect to the X server")?;
+    let screen = conn.setup().roots[screen_num].clone();
+    if screen.root_depth != 24 && screen.root_depth != 32 {
+        bail!("unsupported root depth {}", screen.root_depth);
+    }
+
+    // Load windows in a grid right of the probe
+    let (load_conn, _) = RustConnection::connect(None)?;
+    let columns = ((screen.width_in_pixels - PROBE_SIZE) / options.width).max(1);
+    let mut windows = Vec::new();
+    for index in 0..options.windows {
+        let (column, row) = (index as u16 % columns, index as u16 / columns);
+        let x = (PROBE_SIZE + column * options.width) as i16;
+        let y = ((row * options.height) % screen.height_in_pixels.max(1)) as i16;
+        windows.push(TestWindow::create(&load_conn, &screen, x, y, options.width, options.height)?);
+    }
+    let probe = TestWindow::create(&conn, &screen, 0, 0, PROBE_SIZE, PROBE_SIZE)?;
+    conn.configure_window(probe.window, &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE))?;
+    load_conn.flush()?;
+    conn.flush()?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let repaints = Arc::new(AtomicU64::new(0));
+    let spammer = {
+        let (stop, repaints, rate) = (stop.clone(), repaints.clone(), options.rate);
+        std::thread::spawn(move || spam(load_conn, windows, rate, stop, repaints))
+    };
+
+    // Warm up: wait until the probe is on screen at all
+    let warm_up = Instant::now();
+    probe.fill(&conn, color(0))?;
+    conn.flush()?;
+    while screen_color(&conn, screen.root)? != color(0) {
+        if warm_up.elapsed() > Duration::from_secs(5) {
+            stop.store(true, Ordering::Relaxed);
+            bail!("the probe window never appeared on screen");
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    let mut results = Results::default();
+    let started = Instant::now();
+    let repaints_before = repaints.load(Ordering::Relaxed);
+    let mut frame = 1u64;
+    while started.elapsed() < options.duration {
+        let expected = color(frame);
+        probe.fill(&conn, expected)?;
+        conn.flush()?;
+        let sent = Instant::now();
+        loop {
+            if screen_color(&conn, screen.root)? == expected {
+                results.latencies.push(sent.elapsed().as_secs_f64() * 1000.0);
+                break;
+            }
+            if sent.elapsed() > options.deadline {
+                results.dropped += 1;
+                break;
+            }
+            std::thread::sleep(Duration::from_micros(500));
+        }
+        frame += 1;
+        std::thread::sleep(PROBE_INTERVAL);
+    }
+    results.elapsed = started.elapsed();
+    results.repaints = repaints.load(Ordering::Relaxed) - repaints_before;
+
+    stop.store(true, Ordering::Relaxed);
+    spammer.join().map_err(|_| anyhow::anyhow!("load thread panicked"))??;
+    Ok(results)
+}
+
+fn report(options: &Options, results: &Results, before: Option<&MetricsSnapshot>, after: Option<&MetricsSnapshot>) {
+    let seconds = results.elapsed.as_secs_f64().max(f64::EPSILON);
+    let probes = results.latencies.len() as u32 + results.dropped;
+    let damage_rate = before.zip(after).map(|(before, after)| (after.damage_events - before.damage_events) as f64 / seconds);
+    if options.json {
+        let summary = serde_json::json!({
+            "windows": options.windows,
+            "width": options.width,
+            "height": options.height,
+            "rate": options.rate,
+            "seconds": seconds,
+            "repaints_per_second": results.repaints as f64 / seconds,
+            "probes": probes,
+            "dropped": results.dropped,
+            "latency_ms": {
+                "p50": results.percentile(0.5),
+                "p95": results.percentile(0.95),
+                "p99": results.percentile(0.99),
+                "max": results.percentile(1.0),
+            },
+            "compositor": after.map(|metrics| serde_json::json!({
+                "frame_time": metrics.frame_time,
+                "damage_events_per_second": damage_rate,
+            })),
+        });
+        println!("{}", summary);
+        return;
+    }
+    println!(
+        "{} windows {}x{} at {} Hz for {:.1}s: {:.0} repaints/s",
+        options.windows,
+        options.width,
+        options.height,
+        options.rate,
+        seconds,
+        results.repaints as f64 / seconds
+    );
+    println!(
+        "latency: p50 {:.2} ms  p95 {:.2} ms  p99 {:.2} ms  max {:.2} ms",
+        results.percentile(0.5),
+        results.percentile(0.95),
+        results.percentile(0.99),
+        results.percentile(1.0)
+    );
+    println!("dropped: {} of {} probe frames", results.dropped, probes);
+    if let Some(metrics) = after {
+        let timing = &metrics.frame_time;
+        println!(
+            "compositor frame time: avg {:.2} ms  p95 {:.2} ms  p99 {:.2} ms  max {:.2} ms",
+            timing.avg_ms, timing.p95_ms, timing.p99_ms, timing.max_ms
+        );
+    }
+    if let Some(rate) = damage_rate {
+        println!("damage events: {:.0}/s", rate);
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("area-loadgen: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let before = area_metrics();
+    match run(&options) {
+        Ok(results) => {
+            report(&options, &results, before.as_ref(), area_metrics().as_ref());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("area-loadgen: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -34,6 +34,21 @@ pub struct CWindow {
     /// Window opacity (0.0 - 1.0)
     pub opacity: f32,
     
+    /// Drawn desaturated: the client stopped answering _NET_WM_PING
+    pub unresponsive: bool,
+    
+    /// Color multiplier, below 1.0 while dimmed as inactive
+    pub brightness: f32,
+    
+    /// Effects not drawn for this window (`[[compositor.rules]]`)
+    pub excluded_effects: super::effects::EffectKind,
+    
+    /// Depth of the window contents (0 until the pixmap is bound)
+    pub depth: u8,
+    
+    /// Fully covered by opaque windows above, not drawn
+    pub occluded: bool,
+    
     /// Is the window damaged and needs redraw?
     pub damaged: bool,
     
@@ -43,6 +58,9 @@ pub struct CWindow {
     /// Has pixmap binding failed?
     pub bind_failed: bool,
     
+    /// A pixmap worker is preparing this window's pixmap
+    pub pixmap_pending: bool,
+    
     /// Have we logged the bind failure? (prevents log spam)
     pub bind_failure_logged: bool,
     
@@ -51,6 +69,16 @@ pub struct CWindow {
     
     /// Is the window unredirected (bypassing compositor)?
     pub unredirected: bool,
+    
+    /// Titlebar and buttons painted over a frame (`DecorationRender::Compositor`)
+    pub decoration: Option<super::decorations::Decoration>,
+    
+    /// XShape bounding region in window coordinates (None: rectangular)
+    pub shape: Option<Vec<Geometry>>,
+    
+    /// Parts of an ARGB window that are fully opaque (_NET_WM_OPAQUE_REGION),
+    /// in window coordinates
+    pub opaque_region: Option<Vec<Geometry>>,
 }
 
 impl CWindow {
@@ -64,12 +92,21 @@ impl CWindow {
             pixmap: None,
             damage: None,
             opacity: 1.0,
+            unresponsive: false,
+            brightness: 1.0,
+            excluded_effects: super::effects::EffectKind::empty(),
+            depth: 0,
+            occluded: false,
             damaged: false,
             frames_since_pixmap: 0,
             bind_failed: false,
+            pixmap_pending: false,
             bind_failure_logged: false,
             redirected: false,
             unredirected: false,
+            decoration: None,
+            shape: None,
+            opaque_region: None,
         }
     }
 
@@ -83,6 +120,23 @@ impl CWindow {
         }
     }
 
+    /// Opaque parts of a translucent-capable window in screen coordinates,
+    /// if it declares any and is drawn unchanged (fully opaque, unshaped)
+    pub fn opaque_rects(&self) -> Option<Vec<Geometry>> {
+        let region = self.opaque_region.as_ref().filter(|r| !r.is_empty())?;
+        if self.opacity < 1.0 || self.shape.is_some() {
+            return None;
+        }
+        let g = self.geometry;
+        let clipped = region.iter().filter_map(|r| {
+            let (x0, y0) = (r.x.max(0), r.y.max(0));
+            let x1 = (r.x + r.width as i32).min(g.width as i32);
+            let y1 = (r.y + r.height as i32).min(g.height as i32);
+            (x1 > x0 && y1 > y0).then(|| Geometry::new(g.x + x0, g.y + y0, (x1 - x0) as u32, (y1 - y0) as u32))
+        });
+        Some(clipped.collect())
+    }
+    
     /// Check if the window is currently covering the entire screen
     pub fn is_fullscreen(&self, screen_width: u16, screen_height: u16) -> bool {
         let outer = self.outer_geometry();
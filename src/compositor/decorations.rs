@@ -0,0 +1,86 @@
+//! Decorations drawn by the compositor
+//!
+//! With `decorations.render = "compositor"` the titlebar and its buttons are
+//! input-only X windows: they still receive clicks, but the compositor paints
+//! them over the frame window, so X never draws subwindows and buttons are
+//! anti-aliased.
+
+use anyhow::Result;
+
+use super::effects::WindowDraw;
+use super::renderer::Renderer;
+
+/// Decorations of one frame window
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoration {
+    pub titlebar_height: f32,
+    pub titlebar_color: [f32; 3],
+    pub buttons: Vec<DecorationButton>,
+}
+
+/// A titlebar button
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecorationButton {
+    /// Left edge, from the titlebar's right edge when `from_right`
+    pub offset: f32,
+    pub from_right: bool,
+    pub y: f32,
+    pub size: f32,
+    pub color: [f32; 3],
+}
+
+/// Convert a 0xRRGGBB config color
+pub fn rgb(color: u32) -> [f32; 3] {
+    [(color >> 16 & 0xff) as f32 / 255.0, (color >> 8 & 0xff) as f32 / 255.0, (color & 0xff) as f32 / 255.0]
+}
+
+const BUTTON_SHADER: &str = r#"
+    #version 330 core
+    out vec4 FragColor;
+
+    in vec2 TexCoord;
+
+    uniform float uDiameter;
+    uniform vec4 uColor;
+
+    void main() {
+        // One pixel of coverage falloff at the circle's edge
+        float dist = length(TexCoord - vec2(0.5)) * uDiameter;
+        float coverage = clamp(uDiameter * 0.5 - dist + 0.5, 0.0, 1.0);
+        FragColor = vec4(uColor.rgb, uColor.a * coverage);
+    }
+"#;
+
+/// GL resources for drawing decorations
+pub struct DecorationRenderer {
+    button_program: u32,
+}
+
+impl DecorationRenderer {
+    pub fn new(renderer: &Renderer) -> Result<Self> {
+        Ok(Self { button_program: renderer.create_effect_program(BUTTON_SHADER)? })
+    }
+
+    /// Draw a frame's decorations over the frame as drawn in `window`
+    pub fn draw(&self, renderer: &Renderer, decoration: &Decoration, window: &WindowDraw, screen: (f32, f32)) {
+        let (x, y, width, opacity) = (window.x, window.y, window.width, window.opacity);
+        let [r, g, b] = decoration.titlebar_color;
+        renderer.render_rectangle(x, y, width, decoration.titlebar_height, screen.0, screen.1, r, g, b, opacity);
+        for button in &decoration.buttons {
+            let left = if button.from_right { width - button.offset } else { button.offset };
+            let [r, g, b] = button.color;
+            unsafe {
+                gl::UseProgram(self.button_program);
+                gl::Uniform1f(gl::GetUniformLocation(self.button_program, c"uDiameter".as_ptr()), button.size);
+                gl::Uniform4f(gl::GetUniformLocation(self.button_program, c"uColor".as_ptr()), r, g, b, opacity);
+            }
+            renderer.draw_quad(self.button_program, x + left, y + button.y, button.size, button.size, screen);
+        }
+    }
+}
+
+impl Drop for DecorationRenderer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteProgram(self.button_program) };
+    }
+}
@@ -0,0 +1,427 @@
+//! Compositor effects
+//!
+//! Drawing effects hook into the render loop through `CompositorEffect`
+//! instead of living in the renderer: each one gets the frame before
+//! windows are drawn, every window just before it is drawn (to draw under
+//! it or change its opacity and geometry) and the frame once windows are
+//! done. The chain is built from `[[compositor.effects]]` and rebuilt when
+//! the configuration is reloaded.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use tracing::{info, warn};
+
+use super::renderer::Renderer;
+use crate::config::{EffectConfig, FlashStyle};
+
+bitflags::bitflags! {
+    /// What an effect does, for per-window exclusion (`[[compositor.rules]]`)
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct EffectKind: u8 {
+        const SHADOW = 1 << 0;
+        /// Fades and other transitions
+        const ANIMATION = 1 << 1;
+        const BLUR = 1 << 2;
+    }
+}
+
+/// The frame being rendered
+pub struct FrameInfo {
+    pub now: Instant,
+    pub screen_width: f32,
+    pub screen_height: f32,
+}
+
+/// A window about to be drawn; effects may adjust how
+pub struct WindowDraw {
+    /// Compositor window (frame or client)
+    pub id: u32,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub opacity: f32,
+    /// Color multiplier (below 1.0 dims, above brightens)
+    pub brightness: f32,
+    /// Drawn on top, covering the screen
+    pub fullscreen: bool,
+    /// Effects skipped for this window
+    pub excluded: EffectKind,
+}
+
+/// A drawing effect
+///
+/// Hooks run on the render thread with the GL context current.
+pub trait CompositorEffect: Send {
+    /// Name as used in the configuration
+    fn name(&self) -> &'static str;
+
+    /// What the effect does (windows may exclude it)
+    fn kind(&self) -> EffectKind;
+
+    /// Compile shaders and look up uniforms (`Renderer::create_effect_program`)
+    fn init(&mut self, _renderer: &Renderer) -> Result<()> {
+        Ok(())
+    }
+
+    /// Before any window is drawn
+    fn pre_frame(&mut self, _frame: &FrameInfo) {}
+
+    /// Just before a window is drawn
+    fn draw_window(&mut self, _renderer: &Renderer, _frame: &FrameInfo, _window: &mut WindowDraw) {}
+
+    /// After all windows are drawn (before the cursor)
+    fn post_frame(&mut self, _renderer: &Renderer, _frame: &FrameInfo) {}
+
+    /// A window was unmapped or destroyed
+    fn window_hidden(&mut self, _id: u32) {}
+
+    /// A window should be highlighted (focused from Alt+Tab or run-or-raise)
+    fn window_flashed(&mut self, _id: u32, _now: Instant) {}
+
+    /// Whether the effect is animating, which keeps frames coming without damage
+    fn is_animating(&self, _now: Instant) -> bool {
+        false
+    }
+}
+
+/// The configured effects, in drawing order
+#[derive(Default)]
+pub struct EffectChain {
+    effects: Vec<Box<dyn CompositorEffect>>,
+    /// Configuration the chain was built from
+    configs: Vec<EffectConfig>,
+    /// Effects not initialized yet (no GL context when configured)
+    pending_init: bool,
+}
+
+impl EffectChain {
+    /// Build the effects named in the configuration
+    pub fn from_config(configs: &[EffectConfig]) -> Self {
+        let effects: Vec<Box<dyn CompositorEffect>> = configs
+            .iter()
+            .map(|config| -> Box<dyn CompositorEffect> {
+                match *config {
+                    EffectConfig::Shadow { radius, offset_x, offset_y, opacity } => {
+                        Box::new(Shadow::new(radius, offset_x, offset_y, opacity))
+                    }
+                    EffectConfig::Fade { duration_ms } => Box::new(Fade::new(Duration::from_millis(duration_ms))),
+                    EffectConfig::FocusFlash { duration_ms, style, color, radius } => {
+                        Box::new(FocusFlash::new(Duration::from_millis(duration_ms), style, color, radius))
+                    }
+                }
+            })
+            .collect();
+        if !effects.is_empty() {
+            info!("Compositor effects: {}", effects.iter().map(|e| e.name()).collect::<Vec<_>>().join(", "));
+        }
+        Self { pending_init: !effects.is_empty(), effects, configs: configs.to_vec() }
+    }
+
+    /// Whether the chain was built from `configs` (a reload left it unchanged)
+    pub fn is_built_from(&self, configs: &[EffectConfig]) -> bool {
+        self.configs == configs
+    }
+
+    /// Initialize new effects, dropping those that fail
+    pub fn init(&mut self, renderer: &Renderer) {
+        if !std::mem::take(&mut self.pending_init) {
+            return;
+        }
+        self.effects.retain_mut(|effect| match effect.init(renderer) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Disabling compositor effect {}: {:#}", effect.name(), e);
+                false
+            }
+        });
+    }
+
+    /// Whether an effect is animating (the compositor keeps rendering)
+    pub fn is_animating(&self, now: Instant) -> bool {
+        self.effects.iter().any(|effect| effect.is_animating(now))
+    }
+
+    pub fn pre_frame(&mut self, frame: &FrameInfo) {
+        for effect in &mut self.effects {
+            effect.pre_frame(frame);
+        }
+    }
+
+    pub fn draw_window(&mut self, renderer: &Renderer, frame: &FrameInfo, window: &mut WindowDraw) {
+        for effect in &mut self.effects {
+            if !window.excluded.intersects(effect.kind()) {
+                effect.draw_window(renderer, frame, window);
+            }
+        }
+    }
+
+    pub fn post_frame(&mut self, renderer: &Renderer, frame: &FrameInfo) {
+        for effect in &mut self.effects {
+            effect.post_frame(renderer, frame);
+        }
+    }
+
+    pub fn window_hidden(&mut self, id: u32) {
+        for effect in &mut self.effects {
+            effect.window_hidden(id);
+        }
+    }
+
+    pub fn window_flashed(&mut self, id: u32, now: Instant) {
+        for effect in &mut self.effects {
+            effect.window_flashed(id, now);
+        }
+    }
+}
+
+/// Soft drop shadow drawn under each window
+struct Shadow {
+    radius: f32,
+    offset: (f32, f32),
+    opacity: f32,
+    program: u32,
+}
+
+const SHADOW_SHADER: &str = r#"
+    #version 330 core
+    out vec4 FragColor;
+
+    in vec2 TexCoord;
+
+    uniform vec2 uQuadSize;
+    uniform float uRadius;
+    uniform float uOpacity;
+
+    void main() {
+        // Distance outside the window rectangle, which is the quad inset by uRadius
+        vec2 half_size = uQuadSize * 0.5;
+        vec2 d = abs(TexCoord * uQuadSize - half_size) - (half_size - vec2(uRadius));
+        float falloff = 1.0 - smoothstep(0.0, uRadius, length(max(d, 0.0)));
+        FragColor = vec4(0.0, 0.0, 0.0, uOpacity * falloff * falloff);
+    }
+"#;
+
+impl Shadow {
+    fn new(radius: f32, offset_x: f32, offset_y: f32, opacity: f32) -> Self {
+        Self { radius: radius.max(1.0), offset: (offset_x, offset_y), opacity: opacity.clamp(0.0, 1.0), program: 0 }
+    }
+}
+
+impl CompositorEffect for Shadow {
+    fn name(&self) -> &'static str {
+        "shadow"
+    }
+
+    fn kind(&self) -> EffectKind {
+        EffectKind::SHADOW
+    }
+
+    fn init(&mut self, renderer: &Renderer) -> Result<()> {
+        self.program = renderer.create_effect_program(SHADOW_SHADER)?;
+        Ok(())
+    }
+
+    fn draw_window(&mut self, renderer: &Renderer, frame: &FrameInfo, window: &mut WindowDraw) {
+        if window.fullscreen || window.opacity <= 0.0 {
+            return;
+        }
+        let (width, height) = (window.width + 2.0 * self.radius, window.height + 2.0 * self.radius);
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::Uniform2f(gl::GetUniformLocation(self.program, c"uQuadSize".as_ptr()), width, height);
+            gl::Uniform1f(gl::GetUniformLocation(self.program, c"uRadius".as_ptr()), self.radius);
+            gl::Uniform1f(gl::GetUniformLocation(self.program, c"uOpacity".as_ptr()), self.opacity * window.opacity);
+        }
+        renderer.draw_quad(
+            self.program,
+            window.x - self.radius + self.offset.0,
+            window.y - self.radius + self.offset.1,
+            width,
+            height,
+            (frame.screen_width, frame.screen_height),
+        );
+    }
+}
+
+impl Drop for Shadow {
+    fn drop(&mut self) {
+        if self.program != 0 {
+            unsafe { gl::DeleteProgram(self.program) };
+        }
+    }
+}
+
+/// Fade windows in when they are shown
+struct Fade {
+    duration: Duration,
+    /// When each visible window was first drawn
+    shown: HashMap<u32, Instant>,
+}
+
+impl Fade {
+    fn new(duration: Duration) -> Self {
+        Self { duration, shown: HashMap::new() }
+    }
+
+    fn progress(&self, since: Instant, now: Instant) -> f32 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+        (now.duration_since(since).as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+    }
+}
+
+impl CompositorEffect for Fade {
+    fn name(&self) -> &'static str {
+        "fade"
+    }
+
+    fn kind(&self) -> EffectKind {
+        EffectKind::ANIMATION
+    }
+
+    fn draw_window(&mut self, _renderer: &Renderer, frame: &FrameInfo, window: &mut WindowDraw) {
+        let since = *self.shown.entry(window.id).or_insert(frame.now);
+        window.opacity *= self.progress(since, frame.now);
+    }
+
+    fn window_hidden(&mut self, id: u32) {
+        self.shown.remove(&id);
+    }
+
+    fn is_animating(&self, now: Instant) -> bool {
+        self.shown.values().any(|&since| self.progress(since, now) < 1.0)
+    }
+}
+
+/// Highlight a window for a moment after it got focus
+struct FocusFlash {
+    duration: Duration,
+    style: FlashStyle,
+    color: (f32, f32, f32),
+    radius: f32,
+    program: u32,
+    /// Flashing window and when its flash started
+    flash: Option<(u32, Instant)>,
+}
+
+const GLOW_SHADER: &str = r#"
+    #version 330 core
+    out vec4 FragColor;
+
+    in vec2 TexCoord;
+
+    uniform vec2 uQuadSize;
+    uniform float uRadius;
+    uniform vec3 uColor;
+    uniform float uOpacity;
+
+    void main() {
+        // Distance outside the window rectangle, which is the quad inset by uRadius
+        vec2 half_size = uQuadSize * 0.5;
+        vec2 d = abs(TexCoord * uQuadSize - half_size) - (half_size - vec2(uRadius));
+        float falloff = 1.0 - smoothstep(0.0, uRadius, length(max(d, 0.0)));
+        FragColor = vec4(uColor, uOpacity * falloff);
+    }
+"#;
+
+/// How much brighter a pulsing window gets at first
+const PULSE_BRIGHTNESS: f32 = 0.4;
+
+impl FocusFlash {
+    fn new(duration: Duration, style: FlashStyle, color: u32, radius: f32) -> Self {
+        let channel = |shift: u32| ((color >> shift) & 0xff) as f32 / 255.0;
+        Self {
+            duration,
+            style,
+            color: (channel(16), channel(8), channel(0)),
+            radius: radius.max(1.0),
+            program: 0,
+            flash: None,
+        }
+    }
+
+    /// Strength of the flash, from 1.0 when it starts down to 0.0
+    fn intensity(&self, now: Instant) -> f32 {
+        let Some((_, since)) = self.flash else {
+            return 0.0;
+        };
+        if self.duration.is_zero() {
+            return 0.0;
+        }
+        1.0 - (now.duration_since(since).as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+    }
+}
+
+impl CompositorEffect for FocusFlash {
+    fn name(&self) -> &'static str {
+        "focus_flash"
+    }
+
+    fn kind(&self) -> EffectKind {
+        EffectKind::ANIMATION
+    }
+
+    fn init(&mut self, renderer: &Renderer) -> Result<()> {
+        if self.style == FlashStyle::Glow {
+            self.program = renderer.create_effect_program(GLOW_SHADER)?;
+        }
+        Ok(())
+    }
+
+    fn draw_window(&mut self, renderer: &Renderer, frame: &FrameInfo, window: &mut WindowDraw) {
+        if self.flash.is_none_or(|(id, _)| id != window.id) {
+            return;
+        }
+        let intensity = self.intensity(frame.now);
+        if intensity <= 0.0 {
+            self.flash = None;
+            return;
+        }
+        match self.style {
+            FlashStyle::Pulse => window.brightness *= 1.0 + PULSE_BRIGHTNESS * intensity,
+            FlashStyle::Glow if !window.fullscreen => {
+                let (width, height) = (window.width + 2.0 * self.radius, window.height + 2.0 * self.radius);
+                unsafe {
+                    gl::UseProgram(self.program);
+                    gl::Uniform2f(gl::GetUniformLocation(self.program, c"uQuadSize".as_ptr()), width, height);
+                    gl::Uniform1f(gl::GetUniformLocation(self.program, c"uRadius".as_ptr()), self.radius);
+                    gl::Uniform3f(gl::GetUniformLocation(self.program, c"uColor".as_ptr()), self.color.0, self.color.1, self.color.2);
+                    gl::Uniform1f(gl::GetUniformLocation(self.program, c"uOpacity".as_ptr()), intensity * window.opacity);
+                }
+                renderer.draw_quad(
+                    self.program,
+                    window.x - self.radius,
+                    window.y - self.radius,
+                    width,
+                    height,
+                    (frame.screen_width, frame.screen_height),
+                );
+            }
+            FlashStyle::Glow => {}
+        }
+    }
+
+    fn window_hidden(&mut self, id: u32) {
+        self.flash.take_if(|(flashing, _)| *flashing == id);
+    }
+
+    fn window_flashed(&mut self, id: u32, now: Instant) {
+        self.flash = Some((id, now));
+    }
+
+    fn is_animating(&self, now: Instant) -> bool {
+        self.intensity(now) > 0.0
+    }
+}
+
+impl Drop for FocusFlash {
+    fn drop(&mut self) {
+        if self.program != 0 {
+            unsafe { gl::DeleteProgram(self.program) };
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Workspace overview drawing (see `wm::expo`)
+//!
+//! The WM sends the layout; this draws it over the monitor: a backdrop, one
+//! tile per workspace and the windows scaled into their tiles. Windows on
+//! the current workspace are drawn live; the others have no pixmap while
+//! hidden, so a small snapshot of each is kept from the moment it was
+//! hidden. Windows never shown yet are drawn as plain rectangles.
+
+use std::collections::HashMap;
+
+use super::gl_context::GlContext;
+use super::renderer::Renderer;
+use crate::shared::Geometry;
+
+/// Longest side of a snapshot (px)
+const SNAPSHOT_SIZE: f64 = 512.0;
+const BACKDROP: [f32; 4] = [0.06, 0.06, 0.07, 1.0];
+const TILE: [f32; 3] = [0.16, 0.16, 0.18];
+const TILE_SELECTED: [f32; 3] = [0.24, 0.26, 0.3];
+const PLACEHOLDER: [f32; 3] = [0.35, 0.35, 0.38];
+const CURRENT_OUTLINE: [f32; 3] = [0.25, 0.55, 0.95];
+const OUTLINE_WIDTH: f32 = 3.0;
+
+/// A workspace tile
+#[derive(Debug, Clone, Copy)]
+pub struct ExpoCell {
+    pub rect: Geometry,
+    pub current: bool,
+    /// Highlighted by the pointer or the arrow keys
+    pub selected: bool,
+}
+
+/// The overview as laid out by the WM
+#[derive(Debug, Clone)]
+pub struct ExpoScene {
+    /// Monitor covered
+    pub area: Geometry,
+    pub cells: Vec<ExpoCell>,
+    /// Windows to draw in order (composited window, rectangle)
+    pub windows: Vec<(u32, Geometry)>,
+}
+
+#[derive(Debug, Default)]
+pub struct ExpoView {
+    scene: Option<ExpoScene>,
+    /// Snapshot texture of each hidden window
+    snapshots: HashMap<u32, u32>,
+}
+
+impl ExpoView {
+    /// Show the overview, or hide it (None)
+    pub fn set(&mut self, scene: Option<ExpoScene>) {
+        self.scene = scene;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.scene.is_some()
+    }
+
+    /// Keep a copy of a window about to lose its pixmap
+    pub fn snapshot(&mut self, renderer: &Renderer, ctx: &GlContext, id: u32, geometry: Geometry) {
+        let scale = (SNAPSHOT_SIZE / geometry.width.max(geometry.height).max(1) as f64).min(1.0);
+        let (width, height) = (((geometry.width as f64 * scale) as u32).max(1), ((geometry.height as f64 * scale) as u32).max(1));
+        self.forget(renderer, id);
+        if let Some(texture) = renderer.snapshot_window(ctx, id, width, height) {
+            self.snapshots.insert(id, texture);
+        }
+    }
+
+    /// Drop a window's snapshot (shown again or destroyed)
+    pub fn forget(&mut self, renderer: &Renderer, id: u32) {
+        if let Some(texture) = self.snapshots.remove(&id) {
+            renderer.delete_texture(texture);
+        }
+    }
+
+    /// Draw the overview; `live` tells windows drawn from their pixmap
+    pub fn render(&self, renderer: &Renderer, ctx: &GlContext, live: impl Fn(u32) -> bool, screen: (f32, f32)) {
+        let Some(scene) = &self.scene else { return };
+        let rect = |g: &Geometry| (g.x as f32, g.y as f32, g.width as f32, g.height as f32);
+        renderer.set_clip(&[], screen.1);
+
+        let (x, y, w, h) = rect(&scene.area);
+        let [r, g, b, a] = BACKDROP;
+        renderer.render_rectangle(x, y, w, h, screen.0, screen.1, r, g, b, a);
+        for cell in &scene.cells {
+            let (x, y, w, h) = rect(&cell.rect);
+            let [r, g, b] = if cell.selected { TILE_SELECTED } else { TILE };
+            renderer.render_rectangle(x, y, w, h, screen.0, screen.1, r, g, b, 1.0);
+            if cell.current {
+                let [r, g, b] = CURRENT_OUTLINE;
+                let t = OUTLINE_WIDTH;
+                for (x, y, w, h) in [(x - t, y - t, w + 2.0 * t, t), (x - t, y + h, w + 2.0 * t, t), (x - t, y, t, h), (x + w, y, t, h)] {
+                    renderer.render_rectangle(x, y, w, h, screen.0, screen.1, r, g, b, 1.0);
+                }
+            }
+        }
+
+        for (id, geometry) in &scene.windows {
+            let (x, y, w, h) = rect(geometry);
+            if live(*id) && renderer.has_texture(*id) {
+                renderer.render_window(ctx, *id, x, y, w, h, screen.0, screen.1, 1.0, false, u32::MAX);
+            } else if let Some(&texture) = self.snapshots.get(id) {
+                renderer.render_texture(texture, (x, y, w, h), [0.0, 1.0, 1.0, 0.0], screen, 1.0);
+            } else {
+                let [r, g, b] = PLACEHOLDER;
+                renderer.render_rectangle(x, y, w, h, screen.0, screen.1, r, g, b, 1.0);
+            }
+        }
+    }
+}
@@ -84,6 +84,13 @@ pub struct GlContext {
     pub screen_num: i32,
     
     pub config: glx::GLXFBConfig, // Default config (for overlay window)
+    /// Bits per color channel of the framebuffer (10 with `deep_color`)
+    pub color_bits: i32,
+    /// GLX version and extensions of the screen (for the self-check report)
+    pub glx_version: (i32, i32),
+    pub glx_extensions: String,
+    /// Swap interval 1 was set
+    pub vsync: bool,
     
     // Per-depth FBConfig cache (like compiz's glxPixmapFBConfigs[depth])
     // Indexed by depth: 8, 15, 16, 24, 32
@@ -98,7 +105,10 @@ pub struct GlContext {
 
 impl GlContext {
     /// Create a new OpenGL context using GLX directly (like Compiz)
-    pub fn new(_conn: &RustConnection, screen_num: usize, root: u32) -> Result<Self> {
+    ///
+    /// With `deep_color`, a 30-bit (10 bits per channel) FBConfig is
+    /// preferred when the server has one, falling back to 8 bits.
+    pub fn new(_conn: &RustConnection, screen_num: usize, root: u32, deep_color: bool) -> Result<Self> {
         // Load X11 and GLX libraries
         let xlib = Xlib::open().context("Failed to load libX11")?;
         let glx = Glx::open().context("Failed to load libGLX")?;
@@ -161,21 +171,35 @@ impl GlContext {
 
         // First, try to find FBConfig matching overlay visual ID (like xfwm4 does)
         // Start with basic attributes (don't require TFP yet - we'll check it later)
-        let basic_attribs = [
-            glx::GLX_DRAWABLE_TYPE as i32, glx::GLX_WINDOW_BIT as i32 | glx::GLX_PIXMAP_BIT as i32,
-            glx::GLX_RENDER_TYPE as i32, glx::GLX_RGBA_BIT as i32,
-            glx::GLX_DOUBLEBUFFER as i32, 1,
-            glx::GLX_RED_SIZE as i32, 8,
-            glx::GLX_GREEN_SIZE as i32, 8,
-            glx::GLX_BLUE_SIZE as i32, 8,
-            0
-        ];
-
-        let mut num_configs = 0;
-        let configs_ptr = unsafe {
-            (glx.glXChooseFBConfig)(display, screen_num_i32, basic_attribs.as_ptr(), &mut num_configs)
+        let choose_configs = |bits: i32| {
+            let basic_attribs = [
+                glx::GLX_DRAWABLE_TYPE as i32, glx::GLX_WINDOW_BIT as i32 | glx::GLX_PIXMAP_BIT as i32,
+                glx::GLX_RENDER_TYPE as i32, glx::GLX_RGBA_BIT as i32,
+                glx::GLX_DOUBLEBUFFER as i32, 1,
+                glx::GLX_RED_SIZE as i32, bits,
+                glx::GLX_GREEN_SIZE as i32, bits,
+                glx::GLX_BLUE_SIZE as i32, bits,
+                0
+            ];
+            let mut num_configs = 0;
+            let configs_ptr = unsafe {
+                (glx.glXChooseFBConfig)(display, screen_num_i32, basic_attribs.as_ptr(), &mut num_configs)
+            };
+            (configs_ptr, num_configs)
         };
 
+        // 30-bit output needs the X server running at depth 30 (DefaultDepth 30)
+        if deep_color && overlay_depth != 30 {
+            warn!("Deep color requested but the screen has depth {}; a 10-bit framebuffer may not be displayable", overlay_depth);
+        }
+        let mut deep_bits = if deep_color { 10 } else { 0 };
+        let (mut configs_ptr, mut num_configs) = choose_configs(deep_bits.max(8));
+        if deep_color && (configs_ptr.is_null() || num_configs == 0) {
+            warn!("No 10-bit GLX FBConfig, falling back to 8 bits per channel");
+            deep_bits = 0;
+            (configs_ptr, num_configs) = choose_configs(8);
+        }
+
         if configs_ptr.is_null() || num_configs == 0 {
              unsafe { (xlib.XCloseDisplay)(display) };
              return Err(anyhow::anyhow!("No suitable GLX FBConfig found"));
@@ -256,6 +280,10 @@ impl GlContext {
                 glx::GLX_DRAWABLE_TYPE as i32, glx::GLX_WINDOW_BIT as i32 | glx::GLX_PIXMAP_BIT as i32,
                 glx::GLX_RENDER_TYPE as i32, glx::GLX_RGBA_BIT as i32,
                 glx::GLX_DOUBLEBUFFER as i32, 1,
+                // Don't care (0) unless deep color was found
+                glx::GLX_RED_SIZE, deep_bits,
+                glx::GLX_GREEN_SIZE, deep_bits,
+                glx::GLX_BLUE_SIZE, deep_bits,
                 GLX_BIND_TO_TEXTURE_RGBA_EXT, 1, 
                 GLX_BIND_TO_TEXTURE_TARGETS_EXT, GLX_TEXTURE_2D_BIT_EXT,
                 0
@@ -307,13 +335,16 @@ impl GlContext {
         let config = config.unwrap();
         
         // Debug buffer depth
+        let mut color_bits = 0;
         unsafe {
             let mut alpha = 0;
             let mut depth = 0;
+            (glx.glXGetFBConfigAttrib)(display, config, glx::GLX_RED_SIZE, &mut color_bits);
             (glx.glXGetFBConfigAttrib)(display, config, glx::GLX_ALPHA_SIZE as i32, &mut alpha);
             (glx.glXGetFBConfigAttrib)(display, config, glx::GLX_BUFFER_SIZE as i32, &mut depth);
-            info!("Selected FBConfig: Alpha={}, Depth={}", alpha, depth);
+            info!("Selected FBConfig: {} bits per channel, Alpha={}, Depth={}", color_bits, alpha, depth);
         }
+        crate::metrics::global().set_framebuffer_bits(color_bits.max(0) as u32);
         
         // Retrieve visual from config
         let vinfo = unsafe {
@@ -474,8 +505,10 @@ impl GlContext {
         // CRITICAL: glXSwapIntervalEXT doesn't work with GLX windows created via glXCreateWindow
         // We must use the overlay window for swap interval control, even though we render to the GLX window
         // This is because swap interval is tied to the window's swap chain, not the rendering context
+        let mut vsync = false;
         if std::env::var("AREA_BENCHMARK").is_err() {
             if let Some(swap_func) = swap_fn {
+                vsync = true;
                 unsafe {
                     // Use overlay window for swap interval (this is what controls VSync)
                     // Even though we render to the GLX window, swap interval must be set on the overlay
@@ -505,6 +538,10 @@ impl GlContext {
             root, // In main.rs we pass COW here effectively
             screen_num: screen_num_i32,
             config,
+            color_bits,
+            glx_version: (major, minor),
+            glx_extensions: extensions_str.to_string(),
+            vsync,
             depth_configs: [None; 33], // Initialize all to None
             glXBindTexImageEXT: bind_fn,
             glXReleaseTexImageEXT: release_fn,
@@ -558,8 +595,8 @@ impl GlContext {
             return Ok(()); // Non-fatal, will fall back to default config
         }
         
-        // Process each depth (8, 15, 16, 24, 32 are common)
-        for depth in [8u8, 15, 16, 24, 32] {
+        // Process each depth (8, 15, 16, 24, 32 are common; 30 for deep color clients)
+        for depth in [8u8, 15, 16, 24, 30, 32] {
             let mut best_config: Option<DepthFBConfig> = None;
             let mut best_mipmap = 0i32;
             let mut best_db = i32::MAX;
@@ -0,0 +1,71 @@
+//! On-screen key hints (keyboard window management mode)
+//!
+//! Labels are rasterized with the built-in font into one texture each when
+//! the WM sends a new set, so drawing a frame is one quad per label.
+
+use super::font;
+use super::renderer::Renderer;
+use crate::shared::Geometry;
+
+/// Pixels per font dot
+const SCALE: u32 = 3;
+/// Label background (RGBA)
+const BACKGROUND: [u8; 4] = [24, 24, 24, 230];
+/// Outline of the window keys apply to
+const OUTLINE_COLOR: [f32; 3] = [0.25, 0.55, 1.0];
+const OUTLINE_WIDTH: f32 = 4.0;
+
+/// A key label at a root position (its top-left corner)
+#[derive(Debug, Clone)]
+pub struct Hint {
+    pub x: i32,
+    pub y: i32,
+    pub label: String,
+}
+
+/// Hints and the window outline currently shown
+#[derive(Debug, Default)]
+pub struct HintOverlay {
+    /// New hints not rasterized yet
+    pending: Option<Vec<Hint>>,
+    /// Texture and screen rectangle of each label
+    labels: Vec<(u32, (f32, f32, f32, f32))>,
+    outline: Option<Geometry>,
+}
+
+impl HintOverlay {
+    /// Replace the hints (empty to hide them) and the outlined window
+    pub fn set(&mut self, hints: Vec<Hint>, outline: Option<Geometry>) {
+        self.pending = Some(hints);
+        self.outline = outline;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        !self.labels.is_empty() || self.outline.is_some() || self.pending.as_ref().is_some_and(|h| !h.is_empty())
+    }
+
+    /// Draw the outline and labels, rasterizing new labels first
+    pub fn render(&mut self, renderer: &Renderer, screen: (f32, f32)) {
+        if let Some(hints) = self.pending.take() {
+            for (texture, _) in self.labels.drain(..) {
+                renderer.delete_texture(texture);
+            }
+            for hint in hints {
+                let image = font::rasterize(&hint.label, SCALE, BACKGROUND);
+                let rect = (hint.x as f32, hint.y as f32, image.width as f32, image.height as f32);
+                self.labels.push((renderer.upload_texture(&image), rect));
+            }
+        }
+        if let Some(g) = self.outline {
+            let [r, gr, b] = OUTLINE_COLOR;
+            let (x, y, w, h) = (g.x as f32, g.y as f32, g.width as f32, g.height as f32);
+            let t = OUTLINE_WIDTH;
+            for (x, y, w, h) in [(x, y, w, t), (x, y + h - t, w, t), (x, y, t, h), (x + w - t, y, t, h)] {
+                renderer.render_rectangle(x, y, w, h, screen.0, screen.1, r, gr, b, 1.0);
+            }
+        }
+        for &(texture, rect) in &self.labels {
+            renderer.render_texture(texture, rect, [0.0, 0.0, 1.0, 1.0], screen, 1.0);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Profiling HUD (Super+F12)
+//!
+//! A panel in the top-right corner with the frame rate, a graph of recent
+//! frame times, damage events per second, an estimate of the memory held by
+//! window textures and the windows redrawn most often. Counters cover the
+//! last second and the text is rebuilt once per second; the graph is drawn
+//! every frame. Only frames the compositor renders are counted, so an idle
+//! desktop shows the last busy second.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use super::font;
+use super::renderer::Renderer;
+
+/// Pixels per font dot
+const SCALE: u32 = 2;
+/// Distance from the screen's top-right corner and space inside the panel (px)
+const MARGIN: f32 = 12.0;
+const WIDTH: f32 = 360.0;
+const GRAPH_HEIGHT: f32 = 60.0;
+/// Frame time at the top of the graph (ms)
+const GRAPH_MAX_MS: f32 = 33.3;
+/// Frame time of 60 Hz, marked on the graph (ms)
+const TARGET_MS: f32 = 16.7;
+/// Frames in the graph, one bar each
+const GRAPH_FRAMES: usize = 120;
+/// Windows listed by redraw frequency
+const TOP_WINDOWS: usize = 5;
+const BACKGROUND: [f32; 4] = [0.05, 0.05, 0.05, 0.8];
+const FAST: [f32; 3] = [0.3, 0.8, 0.3];
+const SLOW: [f32; 3] = [0.9, 0.8, 0.2];
+const DROPPED: [f32; 3] = [0.9, 0.3, 0.3];
+const TARGET_LINE: [f32; 3] = [0.6, 0.6, 0.6];
+
+/// Figures the HUD cannot count itself
+#[derive(Debug, Clone, Copy)]
+pub struct HudStats {
+    pub fps: f64,
+    /// Windows with a texture
+    pub textures: usize,
+    /// Their size at 4 bytes per pixel
+    pub texture_bytes: u64,
+}
+
+#[derive(Debug)]
+pub struct Hud {
+    enabled: bool,
+    /// Recent frame times (ms), oldest first
+    frame_times: VecDeque<f32>,
+    /// Counters of the current second
+    damage: u32,
+    redraws: HashMap<u32, u32>,
+    second_start: Instant,
+    /// Text lines with their texture and size, rebuilt every second
+    lines: Vec<(u32, f32, f32)>,
+    /// The text needs rebuilding
+    stale: bool,
+    /// Figures of the last full second
+    damage_rate: u32,
+    redraw_rates: Vec<(u32, u32)>,
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frame_times: VecDeque::with_capacity(GRAPH_FRAMES),
+            damage: 0,
+            redraws: HashMap::new(),
+            second_start: Instant::now(),
+            lines: Vec::new(),
+            stale: true,
+            damage_rate: 0,
+            redraw_rates: Vec::new(),
+        }
+    }
+}
+
+impl Hud {
+    /// Show or hide the HUD, starting over with empty counters
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.frame_times.clear();
+        self.redraws.clear();
+        self.damage = 0;
+        self.second_start = Instant::now();
+        self.stale = true;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.enabled
+    }
+
+    /// Count a damage event
+    pub fn record_damage(&mut self) {
+        if self.enabled {
+            self.damage += 1;
+        }
+    }
+
+    /// Count a window drawn with new contents
+    pub fn record_redraw(&mut self, client_id: u32) {
+        if self.enabled {
+            *self.redraws.entry(client_id).or_default() += 1;
+        }
+    }
+
+    /// Add a frame to the graph
+    pub fn record_frame(&mut self, elapsed: Duration) {
+        if !self.enabled {
+            return;
+        }
+        if self.frame_times.len() == GRAPH_FRAMES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(elapsed.as_secs_f32() * 1000.0);
+    }
+
+    /// Draw the panel, rebuilding the text if a second has passed
+    pub fn render(&mut self, renderer: &Renderer, screen: (f32, f32), stats: HudStats) {
+        if !self.enabled {
+            // Hidden since the last frame
+            for (texture, _, _) in self.lines.drain(..) {
+                renderer.delete_texture(texture);
+            }
+            return;
+        }
+        let elapsed = self.second_start.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            let seconds = elapsed.as_secs_f32();
+            self.damage_rate = (self.damage as f32 / seconds).round() as u32;
+            let mut rates: Vec<(u32, u32)> = self.redraws.drain()
+                .map(|(id, count)| (id, (count as f32 / seconds).round() as u32))
+                .collect();
+            rates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            rates.truncate(TOP_WINDOWS);
+            self.redraw_rates = rates;
+            self.damage = 0;
+            self.second_start = Instant::now();
+            self.stale = true;
+        }
+        if self.stale {
+            self.stale = false;
+            for (texture, _, _) in self.lines.drain(..) {
+                renderer.delete_texture(texture);
+            }
+            for text in self.text(stats) {
+                let image = font::rasterize(&text, SCALE, [0, 0, 0, 0]);
+                self.lines.push((renderer.upload_texture(&image), image.width as f32, image.height as f32));
+            }
+        }
+
+        let text_height: f32 = self.lines.iter().map(|&(_, _, h)| h).sum();
+        let height = MARGIN + GRAPH_HEIGHT + MARGIN + text_height + MARGIN;
+        let (x, y) = (screen.0 - WIDTH - MARGIN, MARGIN);
+        let [r, g, b, a] = BACKGROUND;
+        renderer.render_rectangle(x, y, WIDTH, height, screen.0, screen.1, r, g, b, a);
+
+        // Frame time graph, newest on the right
+        let (graph_x, graph_y, graph_width) = (x + MARGIN, y + MARGIN, WIDTH - 2.0 * MARGIN);
+        let bar = graph_width / GRAPH_FRAMES as f32;
+        let offset = GRAPH_FRAMES - self.frame_times.len();
+        for (i, &ms) in self.frame_times.iter().enumerate() {
+            let h = (ms / GRAPH_MAX_MS).min(1.0) * GRAPH_HEIGHT;
+            let [r, g, b] = if ms <= TARGET_MS { FAST } else if ms <= GRAPH_MAX_MS { SLOW } else { DROPPED };
+            let bx = graph_x + (offset + i) as f32 * bar;
+            renderer.render_rectangle(bx, graph_y + GRAPH_HEIGHT - h, bar.max(1.0), h, screen.0, screen.1, r, g, b, 1.0);
+        }
+        let target_y = graph_y + GRAPH_HEIGHT - TARGET_MS / GRAPH_MAX_MS * GRAPH_HEIGHT;
+        let [r, g, b] = TARGET_LINE;
+        renderer.render_rectangle(graph_x, target_y, graph_width, 1.0, screen.0, screen.1, r, g, b, 0.8);
+
+        let mut line_y = graph_y + GRAPH_HEIGHT + MARGIN;
+        for &(texture, w, h) in &self.lines {
+            renderer.render_texture(texture, (x + MARGIN, line_y, w, h), [0.0, 0.0, 1.0, 1.0], screen, 1.0);
+            line_y += h;
+        }
+    }
+
+    /// Lines of the panel
+    fn text(&self, stats: HudStats) -> Vec<String> {
+        let (average, worst) = match self.frame_times.len() {
+            0 => (0.0, 0.0),
+            n => (self.frame_times.iter().sum::<f32>() / n as f32, self.frame_times.iter().copied().fold(0.0, f32::max)),
+        };
+        let mut lines = vec![
+            format!("FPS {:.1}", stats.fps),
+            format!("FRAME {:.1} MS AVG  {:.1} MAX", average, worst),
+            format!("DAMAGE {}/S", self.damage_rate),
+            format!("TEXTURES {}  {:.1} MB", stats.textures, stats.texture_bytes as f64 / (1024.0 * 1024.0)),
+        ];
+        if !self.redraw_rates.is_empty() {
+            lines.push("REDRAWS/S".to_string());
+            lines.extend(self.redraw_rates.iter().map(|&(id, rate)| format!("  0X{:X} {}", id, rate)));
+        }
+        lines
+    }
+}
@@ -0,0 +1,101 @@
+//! Image Decoding
+//!
+//! Wallpaper images are decoded with the `image` crate (PNG, JPEG, WebP and
+//! binary PPM, the format detected from the contents) and converted to 8-bit
+//! RGBA. Images above `MAX_PIXELS` are refused before anything is allocated
+//! for them.
+
+use anyhow::{bail, Context, Result};
+use std::io::Cursor;
+use std::path::Path;
+
+use ::image::{ImageEncoder, ImageReader, Limits};
+
+/// Largest image accepted, in pixels (a 8192x8192 wallpaper)
+const MAX_PIXELS: u32 = 8192 * 8192;
+
+/// A decoded image, 8-bit RGBA, rows top to bottom
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Decode an image file, detecting the format from its contents
+pub fn load(path: &Path) -> Result<Image> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+    decode(&data).with_context(|| format!("Failed to decode {:?}", path))
+}
+
+/// Decode an in-memory image
+pub fn decode(data: &[u8]) -> Result<Image> {
+    let reader = || ImageReader::new(Cursor::new(data)).with_guessed_format();
+    // Only the header is read here
+    let (width, height) = reader()?.into_dimensions()?;
+    if width.checked_mul(height).is_none_or(|pixels| pixels > MAX_PIXELS) {
+        bail!("Image too large ({}x{})", width, height);
+    }
+    let mut reader = reader()?;
+    let mut limits = Limits::default();
+    // Room for 16-bit RGBA at the largest size
+    limits.max_alloc = Some(MAX_PIXELS as u64 * 8);
+    reader.limits(limits);
+    let image = reader.decode()?.into_rgba8();
+    Ok(Image { width: image.width(), height: image.height(), pixels: image.into_raw() })
+}
+
+/// Encode 8-bit RGBA as a PNG
+pub fn encode_png(image: &Image) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    ::image::codecs::png::PngEncoder::new(&mut png)
+        .write_image(&image.pixels, image.width, image.height, ::image::ExtendedColorType::Rgba8)
+        .context("Failed to encode PNG")?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_round_trip() {
+        let image = Image { width: 3, height: 2, pixels: (0..24).collect() };
+        let decoded = decode(&encode_png(&image).unwrap()).unwrap();
+        assert_eq!((decoded.width, decoded.height), (3, 2));
+        assert_eq!(decoded.pixels, image.pixels);
+    }
+
+    #[test]
+    fn ppm_is_converted_to_rgba() {
+        let image = decode(b"P6 2 1 255\n\x01\x02\x03\x04\x05\x06").unwrap();
+        assert_eq!(image.pixels, [1, 2, 3, 255, 4, 5, 6, 255]);
+    }
+
+    #[test]
+    fn jpeg_is_decoded() {
+        let mut jpeg = Vec::new();
+        ::image::codecs::jpeg::JpegEncoder::new(&mut jpeg)
+            .write_image(&[128; 8 * 8 * 3], 8, 8, ::image::ExtendedColorType::Rgb8)
+            .unwrap();
+        let image = decode(&jpeg).unwrap();
+        assert_eq!((image.width, image.height), (8, 8));
+        assert!(image.pixels.chunks_exact(4).all(|p| p[0].abs_diff(128) <= 2 && p[3] == 255));
+    }
+
+    #[test]
+    fn truncated_input() {
+        let png = encode_png(&Image { width: 4, height: 4, pixels: vec![7; 64] }).unwrap();
+        for len in [0, 8, 20, png.len() / 2] {
+            assert!(decode(&png[..len]).is_err(), "PNG truncated to {} bytes", len);
+        }
+        assert!(decode(b"P6 2 2 255\n\x01\x02\x03").is_err());
+        assert!(decode(b"not an image").is_err());
+    }
+
+    #[test]
+    fn oversized_images() {
+        assert!(decode(b"P6 4294967295 4294967295 255\n").is_err());
+        assert!(decode(b"P6 100000 100000 255\n").is_err());
+        assert!(decode(b"P6 9000 9000 255\n").is_err());
+    }
+}
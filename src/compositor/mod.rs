@@ -9,6 +9,22 @@ pub mod dri3;
 pub mod fps;
 pub mod c_window;
 pub mod cursor;
+pub mod image;
+pub mod preview;
+pub mod occlusion;
+pub mod scheduler;
+pub mod pixmap_worker;
+pub mod wallpaper;
+pub mod effects;
+pub mod decorations;
+pub mod popups;
+pub mod font;
+pub mod hints;
+pub mod osd;
+pub mod hud;
+pub mod snap_preview;
+pub mod expo;
+pub mod selfcheck;
 
 use anyhow::{Context, Result};
 use std::collections::HashMap;
@@ -25,6 +41,9 @@ use cursor::CursorManager;
 use tokio::sync::mpsc;
 use crate::shared::Geometry;
 
+/// Saturation of windows that stopped answering _NET_WM_PING
+const UNRESPONSIVE_SATURATION: f32 = 0.2;
+
 /// Commands sent from the WM logic to the Compositor thread
 pub enum CompositorCommand {
     /// Add a new window for compositing
@@ -37,6 +56,22 @@ pub enum CompositorCommand {
     UpdateWindowDamage(u32),
     /// Update window state (for fullscreen detection)
     UpdateWindowState(u32),
+    /// A window was mapped or unmapped by the WM (workspace switch)
+    SetWindowViewable(u32, bool),
+    /// Set a window's opacity (0.0 - 1.0)
+    SetWindowOpacity(u32, f32),
+    /// Gray out a window that stopped answering _NET_WM_PING (or restore it)
+    SetWindowUnresponsive(u32, bool),
+    /// Dim an inactive window (1.0: normal brightness)
+    SetWindowBrightness(u32, f32),
+    /// Highlight a window that was just focused (`focus_flash` effect)
+    FlashWindow(u32),
+    /// Effects not to draw for a window
+    SetWindowExcludedEffects(u32, effects::EffectKind),
+    /// Clip a window to its XShape bounding region (None: rectangular)
+    SetWindowShape(u32, Option<Vec<Geometry>>),
+    /// Opaque parts of an ARGB window in window coordinates (_NET_WM_OPAQUE_REGION)
+    SetWindowOpaqueRegion(u32, Option<Vec<Geometry>>),
     /// Unredirect a window (bypass compositor for performance)
     UnredirectWindow(u32),
     /// Redirect a window (re-enable compositing)
@@ -45,6 +80,45 @@ pub enum CompositorCommand {
     UpdateCursor(i16, i16, bool),
     /// Update cursor image (shape change detected)
     UpdateCursorImage,
+    /// Replace the wallpaper, crossfading over the given duration
+    SetWallpaper(Vec<wallpaper::WallpaperLayer>, std::time::Duration),
+    /// Stream downscaled frames of a client window to a shared-memory file
+    StartLivePreview { client_id: u32, path: std::path::PathBuf, width: u32, height: u32, fps: u32 },
+    /// End a live preview now, removing its file
+    StopLivePreview(u32),
+    /// Write the last frame of a closing window to a shared-memory file,
+    /// removed after `keep` (sent before `RemoveWindow`); `written` is
+    /// answered once the file is there, dropped if there is none
+    SnapshotClosedWindow {
+        window_id: u32,
+        path: std::path::PathBuf,
+        keep: std::time::Duration,
+        written: tokio::sync::oneshot::Sender<()>,
+    },
+    /// Read back the next frame (without the cursor); None without OpenGL
+    CaptureScreen(tokio::sync::oneshot::Sender<Option<image::Image>>),
+    /// Show key hints and outline a window (window management mode); empty hides them
+    SetKeyHints(Vec<hints::Hint>, Option<Geometry>),
+    /// Show the on-screen display, or hide it (None)
+    SetOsd(Option<osd::OsdContent>),
+    /// Show or hide the profiling HUD
+    ToggleHud,
+    /// Show or hide the self-check test patterns
+    SetSelfCheck(bool),
+    /// Show where a window dragged onto another monitor lands, or hide it (None)
+    SetSnapPreview(Option<Geometry>),
+    /// Show the workspace overview, or hide it (None)
+    SetExpo(Option<expo::ExpoScene>),
+    /// The root window was resized (RandR resize or rotation)
+    SetScreenSize(u16, u16),
+    /// Windows in stacking order, bottom to top (for occlusion culling)
+    SetStacking(Vec<u32>),
+    /// Frame rate limits from the configuration
+    SetFrameRate(crate::config::FrameRateConfig),
+    /// Rebuild the effect chain from the configuration
+    SetEffects(Vec<crate::config::EffectConfig>),
+    /// The system switched between battery and AC power
+    SetOnBattery(bool),
     /// Signal that a render frame is needed
     TriggerRender,
     /// Shutdown the compositor thread
@@ -56,6 +130,9 @@ pub enum CompositorCommand {
 pub struct Compositor {
     pub overlay_window: u32,
     tx: mpsc::UnboundedSender<CompositorCommand>,
+    /// OpenGL setup, set once the compositor thread has tried to create its
+    /// context (None inside: no OpenGL)
+    gl: std::sync::Arc<std::sync::OnceLock<Option<area_ipc::GlCapabilities>>>,
 }
 
 /// The actual compositor implementation (internal to the compositor thread)
@@ -77,6 +154,41 @@ struct CompositorInner {
     unredirected_count: u32,
     /// Whether to unredirect fullscreen windows (from config)
     unredirect_fullscreen: bool,
+    /// Wallpaper textures (bottom layer)
+    wallpaper: wallpaper::WallpaperState,
+    /// Live previews being streamed, by client window
+    previews: HashMap<u32, preview::LivePreview>,
+    /// Last frames of closed windows kept for the shell
+    closed_snapshots: Vec<preview::ClosedSnapshot>,
+    /// Current root size (the setup's size goes stale after RandR changes)
+    screen_size: (u16, u16),
+    /// Window ids bottom to top, as last sent by the WM
+    stacking: Vec<u32>,
+    /// Frame rate caps and idle throttling
+    scheduler: scheduler::FrameScheduler,
+    /// Threads naming window pixmaps off the render thread
+    pixmap_workers: pixmap_worker::PixmapWorkers,
+    /// Screenshots waiting for the next frame
+    captures: Vec<tokio::sync::oneshot::Sender<Option<image::Image>>>,
+    /// Workspace overview over the windows and panels, with snapshots of
+    /// hidden windows
+    expo: expo::ExpoView,
+    /// Drop target of a window dragged between monitors, over the windows
+    snap_preview: snap_preview::SnapPreview,
+    /// Key hints drawn over everything
+    key_hints: hints::HintOverlay,
+    /// On-screen display, over the key hints
+    osd: osd::Osd,
+    /// Profiling HUD, over everything but the cursor
+    hud: hud::Hud,
+    /// Test patterns, under the HUD
+    selfcheck: selfcheck::SelfCheck,
+    /// OpenGL setup (None: no OpenGL)
+    gl_capabilities: Option<area_ipc::GlCapabilities>,
+    /// Configured drawing effects (shadows, fades)
+    effects: effects::EffectChain,
+    /// Shaders for compositor-drawn decorations (created on first use)
+    decoration_renderer: Option<decorations::DecorationRenderer>,
 }
 
 impl Compositor {
@@ -85,6 +197,7 @@ impl Compositor {
         conn: std::sync::Arc<x11rb::rust_connection::RustConnection>,
         screen_num: usize,
         root: u32,
+        deep_color: bool,
     ) -> Result<Self> {
         use x11rb::connection::Connection;
         info!("Spinning up compositor thread");
@@ -113,10 +226,14 @@ impl Compositor {
 
         let (tx, rx) = mpsc::unbounded_channel();
         let conn_clone = conn.clone();
+        let wake = tx.clone();
+        let gl = std::sync::Arc::new(std::sync::OnceLock::new());
+        let gl_clone = gl.clone();
         
         // 2. Spawn the compositor thread
         std::thread::spawn(move || {
-            let mut inner = CompositorInner::new(conn_clone, screen_num, overlay_window, rx);
+            let mut inner = CompositorInner::new(conn_clone, screen_num, overlay_window, deep_color, rx, wake);
+            let _ = gl_clone.set(inner.gl_capabilities.clone());
             if let Err(e) = inner.run() {
                 error!("Compositor thread crashed: {}", e);
             }
@@ -125,9 +242,18 @@ impl Compositor {
         Ok(Self {
             overlay_window,
             tx,
+            gl,
         })
     }
 
+    /// A handle without a compositor thread (event replay): commands are
+    /// dropped and there is no overlay window
+    pub fn detached() -> Self {
+        let (tx, _) = mpsc::unbounded_channel();
+        let gl = std::sync::Arc::new(std::sync::OnceLock::from(None));
+        Self { overlay_window: 0, tx, gl }
+    }
+
     pub fn add_window(&self, window: CWindow) {
         let _ = self.tx.send(CompositorCommand::AddWindow(window));
     }
@@ -148,6 +274,94 @@ impl Compositor {
         let _ = self.tx.send(CompositorCommand::UpdateWindowState(window_id));
     }
 
+    pub fn set_window_viewable(&self, window_id: u32, viewable: bool) {
+        let _ = self.tx.send(CompositorCommand::SetWindowViewable(window_id, viewable));
+    }
+
+    pub fn set_window_opacity(&self, window_id: u32, opacity: f32) {
+        let _ = self.tx.send(CompositorCommand::SetWindowOpacity(window_id, opacity));
+    }
+
+    pub fn set_window_unresponsive(&self, window_id: u32, unresponsive: bool) {
+        let _ = self.tx.send(CompositorCommand::SetWindowUnresponsive(window_id, unresponsive));
+    }
+
+    pub fn set_window_brightness(&self, window_id: u32, brightness: f32) {
+        let _ = self.tx.send(CompositorCommand::SetWindowBrightness(window_id, brightness));
+    }
+
+    pub fn set_window_excluded_effects(&self, window_id: u32, excluded: effects::EffectKind) {
+        let _ = self.tx.send(CompositorCommand::SetWindowExcludedEffects(window_id, excluded));
+    }
+
+    pub fn flash_window(&self, window_id: u32) {
+        let _ = self.tx.send(CompositorCommand::FlashWindow(window_id));
+    }
+
+    pub fn set_window_shape(&self, window_id: u32, shape: Option<Vec<Geometry>>) {
+        let _ = self.tx.send(CompositorCommand::SetWindowShape(window_id, shape));
+    }
+
+    pub fn set_window_opaque_region(&self, window_id: u32, region: Option<Vec<Geometry>>) {
+        let _ = self.tx.send(CompositorCommand::SetWindowOpaqueRegion(window_id, region));
+    }
+
+    pub fn set_key_hints(&self, hints: Vec<hints::Hint>, outline: Option<Geometry>) {
+        let _ = self.tx.send(CompositorCommand::SetKeyHints(hints, outline));
+    }
+
+    pub fn set_osd(&self, content: Option<osd::OsdContent>) {
+        let _ = self.tx.send(CompositorCommand::SetOsd(content));
+    }
+
+    pub fn toggle_hud(&self) {
+        let _ = self.tx.send(CompositorCommand::ToggleHud);
+    }
+
+    pub fn set_self_check(&self, enabled: bool) {
+        let _ = self.tx.send(CompositorCommand::SetSelfCheck(enabled));
+    }
+
+    /// The OpenGL setup; None without OpenGL or before the compositor
+    /// thread is up
+    pub fn gl_capabilities(&self) -> Option<area_ipc::GlCapabilities> {
+        self.gl.get().cloned().flatten()
+    }
+
+    /// The OpenGL setup, once the compositor thread has set up rendering
+    pub async fn wait_gl_capabilities(&self) -> Option<area_ipc::GlCapabilities> {
+        loop {
+            if let Some(gl) = self.gl.get() {
+                return gl.clone();
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    }
+
+    pub fn set_snap_preview(&self, area: Option<Geometry>) {
+        let _ = self.tx.send(CompositorCommand::SetSnapPreview(area));
+    }
+
+    pub fn set_expo(&self, scene: Option<expo::ExpoScene>) {
+        let _ = self.tx.send(CompositorCommand::SetExpo(scene));
+    }
+
+    pub fn set_stacking(&self, stacking: Vec<u32>) {
+        let _ = self.tx.send(CompositorCommand::SetStacking(stacking));
+    }
+
+    pub fn set_frame_rate(&self, config: crate::config::FrameRateConfig) {
+        let _ = self.tx.send(CompositorCommand::SetFrameRate(config));
+    }
+
+    pub fn set_effects(&self, effects: Vec<crate::config::EffectConfig>) {
+        let _ = self.tx.send(CompositorCommand::SetEffects(effects));
+    }
+
+    pub fn set_on_battery(&self, on_battery: bool) {
+        let _ = self.tx.send(CompositorCommand::SetOnBattery(on_battery));
+    }
+
     pub fn unredirect_window(&self, window_id: u32) {
         let _ = self.tx.send(CompositorCommand::UnredirectWindow(window_id));
     }
@@ -164,6 +378,42 @@ impl Compositor {
         let _ = self.tx.send(CompositorCommand::UpdateCursorImage);
     }
 
+    pub fn set_wallpaper(&self, layers: Vec<wallpaper::WallpaperLayer>, crossfade: std::time::Duration) {
+        let _ = self.tx.send(CompositorCommand::SetWallpaper(layers, crossfade));
+    }
+
+    pub fn start_live_preview(&self, client_id: u32, path: std::path::PathBuf, width: u32, height: u32, fps: u32) {
+        let _ = self.tx.send(CompositorCommand::StartLivePreview { client_id, path, width, height, fps });
+    }
+
+    pub fn stop_live_preview(&self, client_id: u32) {
+        let _ = self.tx.send(CompositorCommand::StopLivePreview(client_id));
+    }
+
+    /// Keep the last frame of a closing window; the receiver completes once
+    /// the file is written and fails if no snapshot could be taken
+    pub fn snapshot_closed_window(
+        &self,
+        window_id: u32,
+        path: std::path::PathBuf,
+        keep: std::time::Duration,
+    ) -> tokio::sync::oneshot::Receiver<()> {
+        let (written, rx) = tokio::sync::oneshot::channel();
+        let _ = self.tx.send(CompositorCommand::SnapshotClosedWindow { window_id, path, keep, written });
+        rx
+    }
+
+    /// Capture the screen as composited, on the next frame
+    pub fn capture_screen(&self) -> tokio::sync::oneshot::Receiver<Option<image::Image>> {
+        let (reply, rx) = tokio::sync::oneshot::channel();
+        let _ = self.tx.send(CompositorCommand::CaptureScreen(reply));
+        rx
+    }
+
+    pub fn set_screen_size(&self, width: u16, height: u16) {
+        let _ = self.tx.send(CompositorCommand::SetScreenSize(width, height));
+    }
+
     pub fn trigger_render(&self) {
         let _ = self.tx.send(CompositorCommand::TriggerRender);
     }
@@ -179,9 +429,11 @@ impl CompositorInner {
         conn: std::sync::Arc<x11rb::rust_connection::RustConnection>,
         screen_num: usize,
         overlay_window: u32,
+        deep_color: bool,
         rx: mpsc::UnboundedReceiver<CompositorCommand>,
+        wake: mpsc::UnboundedSender<CompositorCommand>,
     ) -> Self {
-        let gl_context = match GlContext::new(&conn, screen_num, overlay_window) {
+        let gl_context = match GlContext::new(&conn, screen_num, overlay_window, deep_color) {
             Ok(ctx) => Some(ctx),
             Err(e) => {
                 error!("Failed to initialize GL context: {}", e);
@@ -190,7 +442,11 @@ impl CompositorInner {
         };
 
         use x11rb::connection::Connection;
-        let renderer = gl_context.as_ref().and_then(|_| Renderer::new().ok());
+        let renderer = gl_context.as_ref().and_then(|ctx| Renderer::new(ctx.color_bits).ok());
+        let gl_capabilities = gl_context.as_ref().filter(|_| renderer.is_some()).map(selfcheck::capabilities);
+        if let Some(ref gl) = gl_capabilities {
+            info!("OpenGL {} ({}, {})", gl.version, gl.vendor, gl.renderer);
+        }
         let mut cursor_manager = CursorManager::new(&conn, conn.as_ref().setup().roots[screen_num].root).ok();
         
         // Load initial cursor image and position immediately (don't wait for events)
@@ -217,8 +473,14 @@ impl CompositorInner {
             default_panel_config,
         );
         
+        let screen_size = (
+            conn.as_ref().setup().roots[screen_num].width_in_pixels,
+            conn.as_ref().setup().roots[screen_num].height_in_pixels,
+        );
+
         // Try to initialize EWMH atoms (may fail if WM hasn't initialized them yet)
         let ewmh_atoms = crate::wm::ewmh::Atoms::new(conn.as_ref()).ok();
+        let pixmap_workers = pixmap_worker::PixmapWorkers::new(conn.clone(), wake);
 
         Self {
             conn,
@@ -234,6 +496,23 @@ impl CompositorInner {
             ewmh_atoms,
             unredirected_count: 0,
             unredirect_fullscreen: false, // TODO: Pass from config
+            wallpaper: wallpaper::WallpaperState::default(),
+            previews: HashMap::new(),
+            closed_snapshots: Vec::new(),
+            screen_size,
+            stacking: Vec::new(),
+            scheduler: scheduler::FrameScheduler::new(crate::config::FrameRateConfig::default()),
+            pixmap_workers,
+            captures: Vec::new(),
+            expo: expo::ExpoView::default(),
+            snap_preview: snap_preview::SnapPreview::default(),
+            key_hints: hints::HintOverlay::default(),
+            osd: osd::Osd::default(),
+            hud: hud::Hud::default(),
+            selfcheck: selfcheck::SelfCheck::default(),
+            gl_capabilities,
+            effects: effects::EffectChain::default(),
+            decoration_renderer: None,
         }
     }
 
@@ -261,7 +540,12 @@ impl CompositorInner {
                 }
             }
 
+            let now = std::time::Instant::now();
+            self.closed_snapshots.retain(|snapshot| !snapshot.expired(now));
+            
             // Check damage after processing commands
+            self.process_pixmap_results();
+            self.update_occlusion();
             needs_render = self.any_damaged();
             
             // Only render cursor if it moved or is dirty (changed shape/image)
@@ -283,14 +567,30 @@ impl CompositorInner {
                 }
             }
 
+            // Pace frames: cap the rate, throttle redraws while idle
+            if needs_render {
+                match self.scheduler.next_frame(std::time::Instant::now(), self.has_activity()) {
+                    scheduler::FrameDecision::Render => {}
+                    scheduler::FrameDecision::Wait(delay) => {
+                        self.wait_for_command(delay);
+                        continue;
+                    }
+                    scheduler::FrameDecision::Skip => {
+                        self.force_render = false;
+                        needs_render = false;
+                    }
+                }
+            }
+
             // Perform rendering
             if needs_render {
-                use x11rb::connection::Connection;
-                let (w, h) = {
-                    let screen = &self.conn.as_ref().setup().roots[0];
-                    (screen.width_in_pixels as f32, screen.height_in_pixels as f32)
-                };
+                let (w, h) = (self.screen_size.0 as f32, self.screen_size.1 as f32);
+                let frame_start = std::time::Instant::now();
+                self.scheduler.frame_rendered(frame_start);
                 self.render(w, h)?;
+                self.update_previews();
+                crate::metrics::global().record_frame(frame_start.elapsed());
+                self.hud.record_frame(frame_start.elapsed());
                 self.clear_damage();
                 needs_render = false;
                 
@@ -309,24 +609,7 @@ impl CompositorInner {
     fn handle_command(&mut self, cmd: CompositorCommand) {
         match cmd {
             CompositorCommand::AddWindow(w) => {
-                // #region agent log
-                {
-                    use std::fs::OpenOptions;
-                    use std::io::Write;
-                    let log_entry = serde_json::json!({
-                        "sessionId": "debug-session",
-                        "runId": "run1",
-                        "hypothesisId": "D",
-                        "location": "compositor/mod.rs:311",
-                        "message": "Compositor AddWindow command",
-                        "data": {"window_id": w.id, "client_id": w.client_id, "viewable": w.viewable, "geometry": {"x": w.geometry.x, "y": w.geometry.y, "width": w.geometry.width, "height": w.geometry.height}},
-                        "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()
-                    });
-                    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("/home/bizkit/GitHub/area/.cursor/debug.log") {
-                        let _ = writeln!(file, "{}", log_entry);
-                    }
-                }
-                // #endregion
+                debug!("AddWindow {} (client {}) {:?} viewable={}", w.id, w.client_id, w.geometry, w.viewable);
                 
                 use x11rb::connection::Connection;
                 let id = w.id;
@@ -342,26 +625,10 @@ impl CompositorInner {
                 // Check if window is already fullscreen when added
                 self.handle_window_state_update(id);
                 
-                // #region agent log
-                {
-                    use std::fs::OpenOptions;
-                    use std::io::Write;
-                    let log_entry = serde_json::json!({
-                        "sessionId": "debug-session",
-                        "runId": "run1",
-                        "hypothesisId": "D",
-                        "location": "compositor/mod.rs:325",
-                        "message": "Window added to compositor windows map",
-                        "data": {"window_id": id, "total_windows": self.windows.len()},
-                        "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()
-                    });
-                    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("/home/bizkit/GitHub/area/.cursor/debug.log") {
-                        let _ = writeln!(file, "{}", log_entry);
-                    }
-                }
-                // #endregion
+                debug!("Window {} added ({} windows)", id, self.windows.len());
             }
             CompositorCommand::RemoveWindow(id) => {
+                self.effects.window_hidden(id);
                 if let Some(w) = self.windows.remove(&id) {
                     // If window was unredirected, decrement count
                     if w.unredirected && self.unredirected_count > 0 {
@@ -380,6 +647,7 @@ impl CompositorInner {
                     
                     // Remove texture from renderer (clean up GLX pixmap and OpenGL texture)
                     if let (Some(gl_ctx), Some(renderer)) = (&mut self.gl_context, &mut self.renderer) {
+                        self.expo.forget(renderer, id);
                         renderer.remove_texture(gl_ctx, id);
                     }
                     
@@ -418,13 +686,86 @@ impl CompositorInner {
                 }
             }
             CompositorCommand::UpdateWindowDamage(id) => {
+                crate::metrics::global().record_damage();
+                self.hud.record_damage();
                 if let Some(w) = self.windows.get_mut(&id) {
+                    // Hidden windows keep their damage until they show again
+                    // (unless a live preview still needs their frames)
+                    if w.occluded && !self.previews.contains_key(&w.client_id) {
+                        return;
+                    }
                     w.damaged = true;
+                    if let Some(preview) = self.previews.get_mut(&w.client_id) {
+                        preview.mark_stale();
+                    }
                 }
             }
             CompositorCommand::UpdateWindowState(id) => {
                 self.handle_window_state_update(id);
             }
+            CompositorCommand::SetWindowViewable(id, viewable) => {
+                if let Some(w) = self.windows.get_mut(&id) {
+                    if !viewable {
+                        self.effects.window_hidden(id);
+                        // The named pixmap dies with the mapping, bind a fresh one when shown again
+                        if let Some(pixmap) = w.pixmap.take() {
+                            let _ = self.conn.as_ref().free_pixmap(pixmap);
+                        }
+                        if let (Some(gl_ctx), Some(renderer)) = (&self.gl_context, &mut self.renderer) {
+                            // Kept for the workspace overview
+                            self.expo.snapshot(renderer, gl_ctx, id, w.outer_geometry());
+                            renderer.remove_texture(gl_ctx, id);
+                        }
+                        w.bind_failed = false;
+                    } else if let Some(renderer) = &self.renderer {
+                        self.expo.forget(renderer, id);
+                    }
+                    w.viewable = viewable;
+                    w.damaged = true;
+                }
+            }
+            CompositorCommand::SetWindowOpacity(id, opacity) => {
+                if let Some(w) = self.windows.get_mut(&id) {
+                    w.opacity = opacity;
+                    w.damaged = true;
+                }
+            }
+            CompositorCommand::SetWindowUnresponsive(id, unresponsive) => {
+                if let Some(w) = self.windows.get_mut(&id) {
+                    w.unresponsive = unresponsive;
+                    w.damaged = true;
+                }
+            }
+            CompositorCommand::SetWindowBrightness(id, brightness) => {
+                if let Some(w) = self.windows.get_mut(&id) {
+                    w.brightness = brightness;
+                    w.damaged = true;
+                }
+            }
+            CompositorCommand::SetWindowExcludedEffects(id, excluded) => {
+                if let Some(w) = self.windows.get_mut(&id) {
+                    w.excluded_effects = excluded;
+                    w.damaged = true;
+                }
+            }
+            CompositorCommand::FlashWindow(id) => {
+                self.effects.window_flashed(id, std::time::Instant::now());
+                if let Some(w) = self.windows.get_mut(&id) {
+                    w.damaged = true;
+                }
+            }
+            CompositorCommand::SetWindowShape(id, shape) => {
+                if let Some(w) = self.windows.get_mut(&id) {
+                    w.shape = shape;
+                    w.damaged = true;
+                }
+            }
+            CompositorCommand::SetWindowOpaqueRegion(id, region) => {
+                if let Some(w) = self.windows.get_mut(&id) {
+                    w.opaque_region = region;
+                    w.damaged = true;
+                }
+            }
             CompositorCommand::UnredirectWindow(id) => {
                 self.unredirect_window(id);
             }
@@ -444,6 +785,114 @@ impl CompositorInner {
                     }
                 }
             }
+            CompositorCommand::SetWallpaper(layers, crossfade) => {
+                if let (Some(gl_ctx), Some(renderer)) = (&self.gl_context, &self.renderer) {
+                    if let Err(e) = gl_ctx.make_current() {
+                        warn!("Failed to make GL context current for wallpaper upload: {}", e);
+                        return;
+                    }
+                    self.wallpaper.set(renderer, layers, crossfade);
+                    self.force_render = true;
+                }
+            }
+            CompositorCommand::SnapshotClosedWindow { window_id, path, keep, written } => {
+                let (Some(gl_context), Some(renderer), Some(window)) = (&self.gl_context, &self.renderer, self.windows.get(&window_id)) else {
+                    return;
+                };
+                let geometry = window.outer_geometry();
+                let scale = (preview::MAX_PREVIEW_SIZE as f64 / geometry.width.max(geometry.height).max(1) as f64).min(1.0);
+                let (width, height) = (((geometry.width as f64 * scale) as u32).max(1), ((geometry.height as f64 * scale) as u32).max(1));
+                let Some(pixels) = renderer.capture_window(gl_context, window_id, width, height) else {
+                    debug!("No texture to keep for closed window {}", window_id);
+                    return;
+                };
+                // An earlier snapshot in the same file would remove it when dropped
+                self.closed_snapshots.retain(|snapshot| !snapshot.is_at(&path));
+                match preview::ClosedSnapshot::create(path, width, height, &pixels, keep) {
+                    Ok(snapshot) => {
+                        self.closed_snapshots.push(snapshot);
+                        let _ = written.send(());
+                    }
+                    Err(e) => warn!("Failed to keep the last frame of window {}: {:#}", window_id, e),
+                }
+            }
+            CompositorCommand::StartLivePreview { client_id, path, width, height, fps } => {
+                match self.previews.get_mut(&client_id) {
+                    Some(preview) if preview.width == width && preview.height == height => preview.extend(fps),
+                    _ => {
+                        // Drop any preview of another size first, it removes the file
+                        self.previews.remove(&client_id);
+                        match preview::LivePreview::create(path, width, height, fps) {
+                            Ok(preview) => {
+                                debug!("Live preview of window {} started ({}x{} at {} fps)", client_id, width, height, fps);
+                                self.previews.insert(client_id, preview);
+                                self.force_render = true;
+                            }
+                            Err(e) => warn!("Failed to start live preview of window {}: {:#}", client_id, e),
+                        }
+                    }
+                }
+            }
+            CompositorCommand::StopLivePreview(client_id) => {
+                self.previews.remove(&client_id);
+            }
+            CompositorCommand::CaptureScreen(reply) => {
+                if self.gl_context.is_none() {
+                    let _ = reply.send(None);
+                } else {
+                    self.captures.push(reply);
+                    self.force_render = true;
+                }
+            }
+            CompositorCommand::SetKeyHints(hints, outline) => {
+                self.key_hints.set(hints, outline);
+                self.force_render = true;
+            }
+            CompositorCommand::SetOsd(content) => {
+                self.osd.set(content);
+                self.force_render = true;
+            }
+            CompositorCommand::ToggleHud => {
+                self.hud.toggle();
+                self.force_render = true;
+            }
+            CompositorCommand::SetSelfCheck(enabled) => {
+                self.selfcheck.set(enabled, self.gl_capabilities.as_ref());
+                self.force_render = true;
+            }
+            CompositorCommand::SetSnapPreview(area) => {
+                self.snap_preview.set(area);
+                self.force_render = true;
+            }
+            CompositorCommand::SetExpo(scene) => {
+                self.expo.set(scene);
+                self.force_render = true;
+            }
+            CompositorCommand::SetScreenSize(width, height) => {
+                debug!("Screen size now {}x{}", width, height);
+                self.screen_size = (width, height);
+                self.shell.set_screen_size(width, height);
+                self.force_render = true;
+            }
+            CompositorCommand::SetStacking(stacking) => {
+                self.stacking = stacking;
+            }
+            CompositorCommand::SetFrameRate(config) => {
+                self.scheduler.set_config(config);
+            }
+            CompositorCommand::SetEffects(configs) if self.effects.is_built_from(&configs) => {}
+            CompositorCommand::SetEffects(configs) => {
+                // Old effects free their GL objects on drop
+                if let Some(ref gl_context) = self.gl_context {
+                    let _ = gl_context.make_current();
+                }
+                self.effects = effects::EffectChain::from_config(&configs);
+                self.force_render = true;
+            }
+            CompositorCommand::SetOnBattery(on_battery) => {
+                info!("Running on {} power", if on_battery { "battery" } else { "AC" });
+                self.scheduler.set_on_battery(on_battery);
+            }
             CompositorCommand::TriggerRender => {
                 self.force_render = true;
             }
@@ -497,8 +946,6 @@ impl CompositorInner {
     
     /// Unredirect a window (allow it to render directly, bypassing compositor)
     fn unredirect_window(&mut self, window_id: u32) {
-        use x11rb::connection::Connection;
-        
         // Check if window exists and get client_id BEFORE mutable borrow
         let client_id = if let Some(window) = self.windows.get(&window_id) {
             if window.unredirected {
@@ -516,12 +963,8 @@ impl CompositorInner {
                                  self.check_ewmh_fullscreen(client_id);
             
             // Check geometry-based fullscreen
-            let screen = &self.conn.as_ref().setup().roots[0];
             let geometry_fullscreen = if let Some(window) = self.windows.get(&window_id) {
-                window.is_fullscreen(
-                    screen.width_in_pixels,
-                    screen.height_in_pixels,
-                )
+                window.is_fullscreen(self.screen_size.0, self.screen_size.1)
             } else {
                 false
             };
@@ -606,7 +1049,7 @@ impl CompositorInner {
     /// Render all managed windows and shell components.
     /// This is called internal to the Compositor thread.
     fn render(&mut self, screen_width: f32, screen_height: f32) -> Result<()> {
-        use x11rb::connection::Connection;
+        let _span = tracing::trace_span!(target: "area::compositor::render", "render", windows = self.windows.len()).entered();
         // Update shell state (animations, clock, etc.)
         self.shell.update();
         
@@ -637,235 +1080,35 @@ impl CompositorInner {
             gl_context.make_current()?;
             
             unsafe {
+                // Follows the overlay window, which the server resizes with the root
+                gl::Viewport(0, 0, screen_width as i32, screen_height as i32);
                 gl::ClearColor(0.15, 0.15, 0.15, 1.0);
                 gl::Clear(gl::COLOR_BUFFER_BIT);
                 gl::Enable(gl::BLEND);
                 gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
             }
+
+            self.wallpaper.render(renderer, screen_width, screen_height);
+            
+            self.effects.init(renderer);
+            let frame = effects::FrameInfo { now: std::time::Instant::now(), screen_width, screen_height };
+            self.effects.pre_frame(&frame);
             
             // Panel removed - no height adjustment needed
             
             // First pass: lazy pixmap binding
             // Skip unmapped/unviewable windows (performance optimization)
             // CRITICAL: Don't check failed windows every frame - this causes performance issues
-            let windows_to_bind: Vec<u32> = self.windows.values()
-                .filter(|w| {
-                    // Only attempt binding if window is viewable, has no texture, and hasn't failed
-                    w.viewable && !renderer.has_texture(w.id) && !w.bind_failed
-                })
-                .map(|w| w.id)
-                .collect();
-            
-            if !windows_to_bind.is_empty() {
-                debug!("Attempting to create pixmaps for {} window(s): {:?}", windows_to_bind.len(), windows_to_bind);
-            }
-            
-            for window_id in windows_to_bind {
-                // Get window reference and perform initial checks
-                let (window_id_copy, needs_redirect) = {
-                    let window = match self.windows.get(&window_id) {
-                        Some(w) => w,
-                        None => continue,
-                    };
-                    
-                    if window.bind_failed {
-                        continue;
-                    }
-                    
-                    match conn.get_window_attributes(window.id) {
-                        Ok(cookie) => {
-                            if let Ok(window_attrs) = cookie.reply() {
-                                use x11rb::protocol::xproto::MapState;
-                                if window_attrs.map_state == MapState::UNMAPPED || window_attrs.map_state == MapState::UNVIEWABLE {
-                                    continue;
-                                }
-                            }
-                        }
-                        Err(_) => continue,
-                    }
-                    
-                    (window.id, !window.redirected)
-                };
-                
-                // CRITICAL: Redirect window BEFORE creating pixmap (required by Composite extension)
-                if needs_redirect {
-                    let composite_id_for_redirect = {
-                        let window = match self.windows.get(&window_id) {
-                            Some(w) => w.client_id,  // Use client_id for redirect
-                            None => continue,
-                        };
-                        window
-                    };
-                    
-                    // Redirect the window using Composite extension
-                    if let Err(e) = conn.composite_redirect_window(
-                        composite_id_for_redirect,
-                        composite::Redirect::MANUAL,
-                    ) {
-                        warn!("Failed to redirect window {} before pixmap creation: {}", composite_id_for_redirect, e);
-                        if let Some(w) = self.windows.get_mut(&window_id) {
-                            w.bind_failed = true;
-                            if !w.bind_failure_logged {
-                                warn!("Window {} marked as bind_failed - will skip future pixmap creation attempts", window_id);
-                                w.bind_failure_logged = true;
-                            }
-                        }
-                        continue;
-                    }
-                    // Flush to ensure redirect request is sent to X server
-                    // X11 requests are generally synchronous, so flush() should be sufficient
-                    // The subsequent get_window_attributes check will verify the redirect took effect
-                    conn.flush().ok();
-                    
-                    // Mark as redirected
-                    if let Some(window) = self.windows.get_mut(&window_id) {
-                        window.redirected = true;
-                    }
-                }
-                
-                if !self.windows.contains_key(&window_id) {
-                    continue;
-                }
-                
-                if let Ok(cookie) = conn.get_window_attributes(window_id_copy) {
-                    if cookie.reply().is_err() {
-                        if let Some(w) = self.windows.get_mut(&window_id) {
-                            w.bind_failed = true;
-                            if !w.bind_failure_logged {
-                                warn!("Window {} marked as bind_failed (get_window_attributes failed) - will skip future attempts", window_id);
-                                w.bind_failure_logged = true;
-                            }
-                        }
-                        continue;
-                    }
-                } else {
-                    if let Some(w) = self.windows.get_mut(&window_id) {
-                        w.bind_failed = true;
-                        if !w.bind_failure_logged {
-                            warn!("Window {} marked as bind_failed (get_window_attributes cookie failed) - will skip future attempts", window_id);
-                            w.bind_failure_logged = true;
-                        }
-                    }
-                    continue;
-                }
-
-                let window = match self.windows.get_mut(&window_id) {
-                    Some(w) => w,
-                    None => continue,
-                };
-
-                // Use client_id for pixmap creation - the actual window content is in the client window
-                // window.id might be a frame window (decoration), but we need the client window content
-                let composite_id = window.client_id;
-
-                if let Ok(pixmap) = conn.generate_id() {
-                    debug!("Attempting to create pixmap {} for window {}", pixmap, window_id);
-                    match conn.composite_name_window_pixmap(composite_id, pixmap) {
-                        Ok(cookie) => {
-                            if cookie.check().is_err() {
-                                warn!("composite_name_window_pixmap failed for window {} (pixmap {})", window_id, pixmap);
-                                if let Some(w) = self.windows.get_mut(&window_id) {
-                                    w.bind_failed = true;
-                                    if !w.bind_failure_logged {
-                                        warn!("Window {} marked as bind_failed - will skip future pixmap creation attempts", window_id);
-                                        w.bind_failure_logged = true;
-                                    }
-                                }
-                                let _ = conn.free_pixmap(pixmap);
-                                continue;
-                            }
-                            // Flush to ensure pixmap creation request is sent to X server
-                            // X11 requests are generally synchronous, so flush() should be sufficient
-                            // The subsequent get_geometry check will verify the pixmap is ready
-                            conn.flush().ok();
-                            
-                            match conn.get_geometry(pixmap) {
-                                Ok(cookie) => match cookie.reply() {
-                                    Ok(pixmap_geom) => {
-                                    if pixmap_geom.width == 0 || pixmap_geom.height == 0 {
-                                        debug!("Pixmap {} for window {} has invalid dimensions: {}x{}", pixmap, window_id, pixmap_geom.width, pixmap_geom.height);
-                                        let _ = conn.free_pixmap(pixmap);
-                                        continue;
-                                    }
-                                    
-                                    // Compare pixmap size with client window size (not frame window size)
-                                    // Get client window geometry directly
-                                    let client_geom = match conn.get_geometry(composite_id) {
-                                        Ok(cookie) => match cookie.reply() {
-                                            Ok(geom) => (geom.width, geom.height),
-                                            Err(_) => {
-                                                let _ = conn.free_pixmap(pixmap);
-                                                continue;
-                                            }
-                                        }
-                                        Err(_) => {
-                                            let _ = conn.free_pixmap(pixmap);
-                                            continue;
-                                        }
-                                    };
-                                    
-                                    if pixmap_geom.width != client_geom.0 || pixmap_geom.height != client_geom.1 {
-                                        debug!("Pixmap {} size mismatch for window {} (client {}): pixmap={}x{}, client={}x{}", 
-                                            pixmap, window_id, composite_id, pixmap_geom.width, pixmap_geom.height, client_geom.0, client_geom.1);
-                                        let _ = conn.free_pixmap(pixmap);
-                                        continue;
-                                    }
-                                    
-                                    let depth = match conn.get_geometry(composite_id) {
-                                        Ok(cookie) => match cookie.reply() {
-                                            Ok(geom) => geom.depth,
-                                            Err(e) => {
-                                                warn!("Failed to get geometry for window {}: {}", composite_id, e);
-                                                let _ = conn.free_pixmap(pixmap);
-                                                continue;
-                                            }
-                                        }
-                                        Err(e) => {
-                                            warn!("Failed to get geometry cookie for window {}: {}", composite_id, e);
-                                            let _ = conn.free_pixmap(pixmap);
-                                            continue;
-                                        }
-                                    };
-
-                                    debug!("Created pixmap {} for window {} ({}x{}, depth {})", pixmap, window_id, pixmap_geom.width, pixmap_geom.height, depth);
-                                    window.pixmap = Some(pixmap);
-                                    match renderer.update_window_pixmap(gl_context, window.id, pixmap, depth) {
-                                        Ok(_) => {
-                                            debug!("Successfully created texture for window {}", window_id);
-                                            // Mark window as damaged so texture gets bound on next render
-                                            // This ensures initial content is displayed even if damage events are delayed
-                                            window.damaged = true;
-                                            window.frames_since_pixmap = 0; // Reset counter
-                                        }
-                                        Err(e) => {
-                                            warn!("Failed to create texture for window {} (pixmap {}, depth {}): {}", window_id, pixmap, depth, e);
-                                            window.pixmap = None;
-                                            window.bind_failed = true;
-                                            if !window.bind_failure_logged {
-                                                warn!("Window {} marked as bind_failed - will skip future pixmap creation attempts", window_id);
-                                                window.bind_failure_logged = true;
-                                            }
-                                            let _ = conn.free_pixmap(pixmap);
-                                        }
-                                    }
-                                }
-                                    Err(e) => {
-                                        warn!("Failed to get pixmap geometry for window {} (pixmap {}): {}", window_id, pixmap, e);
-                                        let _ = conn.free_pixmap(pixmap);
-                                    }
-                                }
-                                Err(e) => {
-                                    warn!("Failed to get pixmap geometry cookie for window {} (pixmap {}): {}", window_id, pixmap, e);
-                                    let _ = conn.free_pixmap(pixmap);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            warn!("composite_name_window_pixmap error for window {}: {}", window_id, e);
-                        }
-                    }
-                } else {
-                    warn!("Failed to generate pixmap ID for window {}", window_id);
+            // The X round trips run on the pixmap workers; finished pixmaps
+            // become textures in `process_pixmap_results`
+            for window in self.windows.values_mut() {
+                if window.viewable && !window.unredirected && !renderer.has_texture(window.id) && !window.bind_failed && !window.pixmap_pending {
+                    window.pixmap_pending = true;
+                    self.pixmap_workers.submit(pixmap_worker::PixmapJob {
+                        window_id: window.id,
+                        client_id: window.client_id,
+                        redirect: !window.redirected,
+                    });
                 }
             }
             
@@ -951,23 +1194,86 @@ impl CompositorInner {
             for (window_id, render_id) in normal_windows {
                 // Get window from HashMap now (after collecting info)
                 if let Some(window) = self.windows.get(&window_id) {
+                    if window.occluded {
+                        continue;
+                    }
                     let has_texture = renderer.has_texture(render_id);
+                    if has_texture && window.damaged {
+                        self.hud.record_redraw(window.client_id);
+                        self.selfcheck.record_damage(window.outer_geometry());
+                    }
                     
                     if has_texture {
                         // Normal windows: render at their position
-                        renderer.render_window(
-                            gl_context,
-                            render_id,
-                            window.geometry.x as f32,
-                            window.geometry.y as f32,
-                            window.geometry.width as f32,
-                            window.geometry.height as f32,
-                            screen_width,
-                            screen_height,
-                            window.opacity,
-                            window.damaged,
-                            window.frames_since_pixmap,
-                        );
+                        let mut draw = effects::WindowDraw {
+                            id: render_id,
+                            x: window.geometry.x as f32,
+                            y: window.geometry.y as f32,
+                            width: window.geometry.width as f32,
+                            height: window.geometry.height as f32,
+                            opacity: window.opacity,
+                            brightness: window.brightness,
+                            fullscreen: false,
+                            excluded: window.excluded_effects,
+                        };
+                        self.effects.draw_window(renderer, &frame, &mut draw);
+                        if window.unresponsive {
+                            renderer.set_saturation(UNRESPONSIVE_SATURATION);
+                        }
+                        if draw.brightness != 1.0 {
+                            renderer.set_brightness(draw.brightness);
+                        }
+                        // Shaped windows only show their bounding region
+                        let mut clip: Vec<_> = window.shape.iter().flatten()
+                            .map(|r| (draw.x + r.x as f32, draw.y + r.y as f32, r.width as f32, r.height as f32))
+                            .collect();
+                        // Opaque parts of ARGB windows skip blending, the rest is clipped
+                        // to what remains (only while drawn at their own size and place)
+                        let rect = |g: &Geometry| (g.x as f32, g.y as f32, g.width as f32, g.height as f32);
+                        let unmoved = (draw.x, draw.y, draw.width, draw.height) == rect(&window.geometry);
+                        if window.depth == 32
+                            && draw.opacity >= 1.0
+                            && unmoved
+                            && let Some(opaque) = window.opaque_rects().filter(|rects| !rects.is_empty())
+                        {
+                            let rest = opaque.iter().fold(vec![window.geometry], occlusion::subtract);
+                            clip = rest.iter().map(rect).collect();
+                            renderer.set_opaque(&opaque.iter().map(rect).collect::<Vec<_>>(), screen_height);
+                        }
+                        renderer.set_clip(&clip, screen_height);
+                        if window.shape.as_ref().is_none_or(|shape| !shape.is_empty()) {
+                            renderer.render_window(
+                                gl_context,
+                                render_id,
+                                draw.x,
+                                draw.y,
+                                draw.width,
+                                draw.height,
+                                screen_width,
+                                screen_height,
+                                draw.opacity,
+                                window.damaged,
+                                window.frames_since_pixmap,
+                            );
+                        }
+                        renderer.set_clip(&[], screen_height);
+                        renderer.set_opaque(&[], screen_height);
+                        if window.unresponsive {
+                            renderer.set_saturation(1.0);
+                        }
+                        if draw.brightness != 1.0 {
+                            renderer.set_brightness(1.0);
+                        }
+                        if let Some(ref decoration) = window.decoration {
+                            if self.decoration_renderer.is_none() {
+                                self.decoration_renderer = decorations::DecorationRenderer::new(renderer)
+                                    .inspect_err(|e| warn!("Compositor decorations unavailable: {:#}", e))
+                                    .ok();
+                            }
+                            if let Some(ref decoration_renderer) = self.decoration_renderer {
+                                decoration_renderer.draw(renderer, decoration, &draw, (screen_width, screen_height));
+                            }
+                        }
                     } else {
                         // Fallback rendering
                         renderer.render_window_fallback(
@@ -1006,22 +1312,50 @@ impl CompositorInner {
                 // Get window from HashMap now (after collecting info)
                 if let Some(window) = self.windows.get(&window_id) {
                     let has_texture = renderer.has_texture(render_id);
+                    if has_texture && window.damaged {
+                        self.hud.record_redraw(window.client_id);
+                        self.selfcheck.record_damage(window.outer_geometry());
+                    }
                     
                     if has_texture {
                         // Fullscreen windows: render covering entire screen (0,0 to screen_width, screen_height)
+                        let mut draw = effects::WindowDraw {
+                            id: render_id,  // Use client window if fullscreen with frame
+                            x: 0.0,
+                            y: 0.0,
+                            width: screen_width,
+                            height: screen_height,
+                            opacity: window.opacity,
+                            brightness: window.brightness,
+                            fullscreen: true,
+                            excluded: window.excluded_effects,
+                        };
+                        self.effects.draw_window(renderer, &frame, &mut draw);
+                        if window.unresponsive {
+                            renderer.set_saturation(UNRESPONSIVE_SATURATION);
+                        }
+                        if draw.brightness != 1.0 {
+                            renderer.set_brightness(draw.brightness);
+                        }
                         renderer.render_window(
                             gl_context,
-                            render_id,  // Use client window if fullscreen with frame
-                            0.0,  // x = 0
-                            0.0,  // y = 0
-                            screen_width,  // width = full screen
-                            screen_height, // height = full screen
+                            render_id,
+                            draw.x,
+                            draw.y,
+                            draw.width,
+                            draw.height,
                             screen_width,
                             screen_height,
-                            window.opacity,
+                            draw.opacity,
                             window.damaged,
                             window.frames_since_pixmap,
                         );
+                        if window.unresponsive {
+                            renderer.set_saturation(1.0);
+                        }
+                        if draw.brightness != 1.0 {
+                            renderer.set_brightness(1.0);
+                        }
                     } else {
                         // Fallback rendering for fullscreen
                         renderer.render_window_fallback(
@@ -1038,6 +1372,43 @@ impl CompositorInner {
                 }
             }
             
+            self.effects.post_frame(renderer, &frame);
+            
+            // Screenshots show everything but the cursor
+            if !self.captures.is_empty() {
+                let (width, height) = (screen_width as u32, screen_height as u32);
+                let pixels = renderer.read_screen(width, height);
+                for reply in self.captures.drain(..) {
+                    let _ = reply.send(Some(image::Image { width, height, pixels: pixels.clone() }));
+                }
+            }
+            
+            if self.expo.is_visible() {
+                let windows = &self.windows;
+                self.expo.render(renderer, gl_context, |id| windows.get(&id).is_some_and(|w| w.viewable), (screen_width, screen_height));
+            }
+            if self.snap_preview.is_visible() {
+                self.snap_preview.render(renderer, (screen_width, screen_height));
+            }
+            if self.key_hints.is_visible() {
+                self.key_hints.render(renderer, (screen_width, screen_height));
+            }
+            if self.osd.is_visible() {
+                self.osd.render(renderer, (screen_width, screen_height));
+            }
+            self.selfcheck.render(renderer, (screen_width, screen_height));
+            let textured: Vec<&CWindow> = if self.hud.is_visible() {
+                self.windows.values().filter(|w| renderer.has_texture(w.id)).collect()
+            } else {
+                Vec::new()
+            };
+            let stats = hud::HudStats {
+                fps: self.fps_counter.fps(),
+                textures: textured.len(),
+                texture_bytes: textured.iter().map(|w| w.outer_geometry()).map(|g| g.width as u64 * g.height as u64 * 4).sum(),
+            };
+            self.hud.render(renderer, (screen_width, screen_height), stats);
+            
             if let Some(ref mut cursor) = self.cursor_manager {
                 // Load cursor image if not loaded yet (fallback if XfixesCursorNotify didn't fire)
                 if cursor.width == 0 || cursor.height == 0 || cursor.pixels.is_empty() {
@@ -1082,9 +1453,136 @@ impl CompositorInner {
         Ok(())
     }
 
+    /// Capture a frame for every live preview whose window changed, and end expired previews
+    fn update_previews(&mut self) {
+        if self.previews.is_empty() {
+            return;
+        }
+        let now = std::time::Instant::now();
+        let windows = &self.windows;
+        self.previews.retain(|client_id, preview| {
+            !preview.expired(now) && windows.values().any(|w| w.client_id == *client_id)
+        });
+
+        let (Some(gl_context), Some(renderer)) = (&self.gl_context, &self.renderer) else {
+            return;
+        };
+        for (client_id, preview) in self.previews.iter_mut().filter(|(_, p)| p.due(now)) {
+            // The frame if the client is decorated, else the client itself
+            let Some(window) = self.windows.values()
+                .filter(|w| w.client_id == *client_id && w.viewable)
+                .max_by_key(|w| w.id != w.client_id)
+            else {
+                continue;
+            };
+            if let Some(pixels) = renderer.capture_window(gl_context, window.id, preview.width, preview.height)
+                && let Err(e) = preview.write_frame(&pixels)
+            {
+                warn!("Failed to write live preview of window {}: {:#}", client_id, e);
+            }
+        }
+    }
+
+    /// Turn pixmaps prepared by the workers into textures
+    fn process_pixmap_results(&mut self) {
+        use pixmap_worker::PixmapResult;
+        
+        let results: Vec<PixmapResult> = self.pixmap_workers.finished().collect();
+        for result in results {
+            let (window_id, redirected) = match result {
+                PixmapResult::Ready { window_id, redirected, .. }
+                | PixmapResult::Retry { window_id, redirected }
+                | PixmapResult::Failed { window_id, redirected } => (window_id, redirected),
+            };
+            let Some(window) = self.windows.get_mut(&window_id).filter(|w| w.pixmap_pending) else {
+                // Removed (or re-added) meanwhile
+                if let PixmapResult::Ready { pixmap, .. } = result {
+                    let _ = self.conn.free_pixmap(pixmap);
+                }
+                continue;
+            };
+            window.pixmap_pending = false;
+            window.redirected |= redirected;
+            match result {
+                PixmapResult::Ready { pixmap, depth, .. } => {
+                    let (Some(gl_context), Some(renderer)) = (&self.gl_context, &mut self.renderer) else {
+                        let _ = self.conn.free_pixmap(pixmap);
+                        continue;
+                    };
+                    window.pixmap = Some(pixmap);
+                    window.depth = depth;
+                    match renderer.update_window_pixmap(gl_context, window.id, pixmap, depth) {
+                        Ok(_) => {
+                            debug!("Successfully created texture for window {}", window_id);
+                            // Mark window as damaged so texture gets bound on next render
+                            // This ensures initial content is displayed even if damage events are delayed
+                            window.damaged = true;
+                            window.frames_since_pixmap = 0; // Reset counter
+                        }
+                        Err(e) => {
+                            warn!("Failed to create texture for window {} (pixmap {}, depth {}): {}", window_id, pixmap, depth, e);
+                            window.pixmap = None;
+                            window.bind_failed = true;
+                            let _ = self.conn.free_pixmap(pixmap);
+                        }
+                    }
+                }
+                PixmapResult::Retry { .. } => {}
+                PixmapResult::Failed { .. } => window.bind_failed = true,
+            }
+            if window.bind_failed && !window.bind_failure_logged {
+                warn!("Window {} marked as bind_failed - will skip future pixmap creation attempts", window_id);
+                window.bind_failure_logged = true;
+            }
+        }
+    }
+
+    /// Recompute which windows are hidden under opaque windows; a window
+    /// coming back into view is repainted with fresh contents
+    fn update_occlusion(&mut self) {
+        let occluded = occlusion::occluded_windows(&self.windows, &self.stacking, self.screen_size.0, self.screen_size.1);
+        for window in self.windows.values_mut() {
+            let now = occluded.contains(&window.id);
+            if window.occluded && !now {
+                window.damaged = true;
+            }
+            window.occluded = now;
+        }
+    }
+
+    /// Sleep for `delay`, waking early when a command (e.g. damage) arrives
+    fn wait_for_command(&self, delay: std::time::Duration) {
+        let deadline = std::time::Instant::now() + delay;
+        while self.rx.is_empty() {
+            let now = std::time::Instant::now();
+            if now >= deadline {
+                break;
+            }
+            std::thread::sleep((deadline - now).min(std::time::Duration::from_millis(4)));
+        }
+    }
+
+    /// Whether anything on screen changed: window damage, cursor motion or
+    /// an animation (as opposed to a bare render request)
+    fn has_activity(&self) -> bool {
+        let cursor_active = self.cursor_manager.as_ref()
+            .is_some_and(|c| c.visible && (c.width == 0 || c.height == 0 || c.has_moved() || c.dirty));
+        cursor_active
+            || self.wallpaper.is_fading()
+            || self.effects.is_animating(std::time::Instant::now())
+            || self.previews.values().any(|p| p.is_stale())
+            || self.selfcheck.is_visible()
+            || self.windows.values().any(|w| w.damaged)
+    }
+
     /// Check if any window is damaged or cursor moved
     pub fn any_damaged(&self) -> bool {
-        if self.force_render {
+        if self.force_render
+            || self.wallpaper.is_fading()
+            || self.effects.is_animating(std::time::Instant::now())
+            || self.previews.values().any(|p| p.is_stale())
+            || self.selfcheck.is_visible()
+        {
             return true;
         }
         let window_damaged = self.windows.values().any(|w| w.damaged || w.damage.is_some());
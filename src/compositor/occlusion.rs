@@ -0,0 +1,94 @@
+//! Occlusion culling
+//!
+//! Windows entirely covered by opaque windows stacked above them are not
+//! drawn: their textures are not rebound every frame and their damage is
+//! left pending (so the server stops reporting it) until they show again.
+//! A maximized window over everything else reduces a frame to one window.
+//! ARGB windows only cover what their _NET_WM_OPAQUE_REGION declares.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::compositor::c_window::CWindow;
+use crate::shared::Geometry;
+
+/// Whether a window hides everything below its rectangle
+fn is_opaque(window: &CWindow) -> bool {
+    window.viewable
+        && !window.unredirected
+        && window.pixmap.is_some()
+        && window.opacity >= 1.0
+        && window.shape.is_none()
+        // 32-bit (ARGB) windows may be translucent anywhere
+        && (1..=24).contains(&window.depth)
+}
+
+/// Parts of `rects` not covered by `cut`
+pub fn subtract(rects: Vec<Geometry>, cut: &Geometry) -> Vec<Geometry> {
+    let (cx0, cy0) = (cut.x as i64, cut.y as i64);
+    let (cx1, cy1) = (cx0 + cut.width as i64, cy0 + cut.height as i64);
+    let mut out = Vec::with_capacity(rects.len());
+    for r in rects {
+        let (x0, y0) = (r.x as i64, r.y as i64);
+        let (x1, y1) = (x0 + r.width as i64, y0 + r.height as i64);
+        if cx1 <= x0 || cx0 >= x1 || cy1 <= y0 || cy0 >= y1 {
+            out.push(r);
+            continue;
+        }
+        let mut push = |x0: i64, y0: i64, x1: i64, y1: i64| {
+            if x1 > x0 && y1 > y0 {
+                out.push(Geometry::new(x0 as i32, y0 as i32, (x1 - x0) as u32, (y1 - y0) as u32));
+            }
+        };
+        // Bands above and below the cut, then left and right of it
+        push(x0, y0, x1, cy0.min(y1));
+        push(x0, cy1.max(y0), x1, y1);
+        let (my0, my1) = (cy0.max(y0), cy1.min(y1));
+        push(x0, my0, cx0.min(x1), my1);
+        push(cx1.max(x0), my0, x1, my1);
+    }
+    out
+}
+
+/// Whether `rect` lies entirely under the union of `covers`
+fn is_covered(rect: &Geometry, covers: &[Geometry]) -> bool {
+    let mut remaining = vec![*rect];
+    for cover in covers {
+        remaining = subtract(remaining, cover);
+        if remaining.is_empty() {
+            return true;
+        }
+    }
+    false
+}
+
+/// Windows fully covered by opaque windows above them
+///
+/// `stacking` lists window ids bottom to top; windows missing from it
+/// (override-redirect menus, tooltips) are never culled and never occlude.
+/// Fullscreen windows are drawn last, so they are never culled either.
+pub fn occluded_windows(
+    windows: &HashMap<u32, CWindow>,
+    stacking: &[u32],
+    screen_width: u16,
+    screen_height: u16,
+) -> HashSet<u32> {
+    let mut occluded = HashSet::new();
+    let mut covers: Vec<Geometry> = Vec::new();
+    for id in stacking.iter().rev() {
+        let Some(window) = windows.get(id) else { continue };
+        if !window.viewable {
+            continue;
+        }
+        if !window.is_fullscreen(screen_width, screen_height) && is_covered(&window.geometry, &covers) {
+            occluded.insert(*id);
+        } else if is_opaque(window) {
+            covers.push(window.geometry);
+        } else if window.viewable && !window.unredirected && window.pixmap.is_some()
+            && let Some(rects) = window.opaque_rects()
+        {
+            // ARGB windows hide what is under their declared opaque region
+            covers.extend(rects);
+        }
+    }
+    occluded
+}
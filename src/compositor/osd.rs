@@ -0,0 +1,102 @@
+//! On-screen display (volume, brightness, keyboard layout, workspace)
+//!
+//! A box with a line of text and, for levels, a bar under it. The WM picks
+//! the monitor and hides the OSD again when its timeout passes.
+
+use super::font;
+use super::renderer::Renderer;
+use crate::config::OsdPosition;
+use crate::shared::Geometry;
+
+/// Pixels per font dot
+const SCALE: u32 = 4;
+/// Space inside the box and between text and bar (px)
+const MARGIN: f32 = 16.0;
+/// Narrowest box, so short labels still get a usable bar (px)
+const MIN_WIDTH: f32 = 280.0;
+const BAR_HEIGHT: f32 = 8.0;
+/// Distance from the monitor edge at the top and bottom positions (px)
+const EDGE_OFFSET: f32 = 96.0;
+const BACKGROUND: [f32; 4] = [0.1, 0.1, 0.1, 0.85];
+const BAR_TRACK: [f32; 3] = [0.35, 0.35, 0.35];
+const BAR_FILL: [f32; 3] = [1.0, 1.0, 1.0];
+
+/// What to show and where
+#[derive(Debug, Clone)]
+pub struct OsdContent {
+    pub text: String,
+    /// Level shown as a bar (0.0-1.0)
+    pub value: Option<f32>,
+    /// Monitor to show it on
+    pub monitor: Geometry,
+    pub position: OsdPosition,
+}
+
+/// The OSD currently shown
+#[derive(Debug)]
+struct Shown {
+    texture: u32,
+    text: (f32, f32, f32, f32),
+    background: (f32, f32, f32, f32),
+    bar: Option<((f32, f32, f32, f32), f32)>,
+}
+
+#[derive(Debug, Default)]
+pub struct Osd {
+    /// Content to lay out on the next frame (`Some(None)`: hide)
+    pending: Option<Option<OsdContent>>,
+    shown: Option<Shown>,
+}
+
+impl Osd {
+    /// Show new content, replacing what is shown, or hide the OSD
+    pub fn set(&mut self, content: Option<OsdContent>) {
+        self.pending = Some(content);
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.shown.is_some() || self.pending.as_ref().is_some_and(Option::is_some)
+    }
+
+    pub fn render(&mut self, renderer: &Renderer, screen: (f32, f32)) {
+        if let Some(content) = self.pending.take() {
+            if let Some(shown) = self.shown.take() {
+                renderer.delete_texture(shown.texture);
+            }
+            self.shown = content.map(|content| layout(renderer, &content));
+        }
+        let Some(ref shown) = self.shown else { return };
+        let (x, y, w, h) = shown.background;
+        let [r, g, b, a] = BACKGROUND;
+        renderer.render_rectangle(x, y, w, h, screen.0, screen.1, r, g, b, a);
+        renderer.render_texture(shown.texture, shown.text, [0.0, 0.0, 1.0, 1.0], screen, 1.0);
+        if let Some(((x, y, w, h), value)) = shown.bar {
+            let [r, g, b] = BAR_TRACK;
+            renderer.render_rectangle(x, y, w, h, screen.0, screen.1, r, g, b, 1.0);
+            let [r, g, b] = BAR_FILL;
+            renderer.render_rectangle(x, y, w * value, h, screen.0, screen.1, r, g, b, 1.0);
+        }
+    }
+}
+
+/// Rasterize the text and place the box on its monitor
+fn layout(renderer: &Renderer, content: &OsdContent) -> Shown {
+    let image = font::rasterize(&content.text, SCALE, [0, 0, 0, 0]);
+    let (text_width, text_height) = (image.width as f32, image.height as f32);
+    let width = text_width.max(MIN_WIDTH) + 2.0 * MARGIN;
+    let bar_space = if content.value.is_some() { MARGIN + BAR_HEIGHT } else { 0.0 };
+    let height = text_height + bar_space + 2.0 * MARGIN;
+    let m = content.monitor;
+    let x = m.x as f32 + (m.width as f32 - width) / 2.0;
+    let y = match content.position {
+        OsdPosition::Top => m.y as f32 + EDGE_OFFSET,
+        OsdPosition::Center => m.y as f32 + (m.height as f32 - height) / 2.0,
+        OsdPosition::Bottom => m.y as f32 + m.height as f32 - EDGE_OFFSET - height,
+    };
+    let text = (x + (width - text_width) / 2.0, y + MARGIN, text_width, text_height);
+    let bar = content.value.map(|value| {
+        let rect = (x + MARGIN, y + MARGIN + text_height + MARGIN, width - 2.0 * MARGIN, BAR_HEIGHT);
+        (rect, value.clamp(0.0, 1.0))
+    });
+    Shown { texture: renderer.upload_texture(&image), text, background: (x, y, width, height), bar }
+}
@@ -0,0 +1,152 @@
+//! Off-thread pixmap preparation
+//!
+//! Naming a window's pixmap takes several X round trips (redirect, map
+//! state, pixmap and window geometry). Doing them on the render thread
+//! stalled every frame behind large windows being (re)created, e.g. a 4K
+//! video resizing. A small pool of worker threads does them instead and
+//! hands finished pixmaps back over a channel; the render thread only turns
+//! them into textures (GLX calls must stay on the thread owning the context).
+
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{debug, warn};
+use x11rb::connection::Connection;
+use x11rb::protocol::composite::{self, ConnectionExt as CompositeExt};
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+
+use super::CompositorCommand;
+
+/// Number of worker threads
+const WORKERS: usize = 2;
+
+/// A window needing a pixmap
+pub struct PixmapJob {
+    /// Compositor window (frame or client)
+    pub window_id: u32,
+    /// Window whose contents are named (always the client)
+    pub client_id: u32,
+    /// The client must be redirected first
+    pub redirect: bool,
+}
+
+/// Outcome of a `PixmapJob`
+pub enum PixmapResult {
+    /// Pixmap named and checked against the client size
+    Ready { window_id: u32, pixmap: u32, depth: u8, redirected: bool },
+    /// Not possible yet (unmapped, being resized); retried on a later frame
+    Retry { window_id: u32, redirected: bool },
+    /// The window cannot be composited; not retried
+    Failed { window_id: u32, redirected: bool },
+}
+
+/// Worker threads preparing pixmaps
+pub struct PixmapWorkers {
+    jobs: Vec<Sender<PixmapJob>>,
+    results: Receiver<PixmapResult>,
+    next: usize,
+}
+
+impl PixmapWorkers {
+    /// Start the workers; each ready pixmap wakes the compositor loop through `wake`
+    pub fn new(conn: Arc<RustConnection>, wake: UnboundedSender<CompositorCommand>) -> Self {
+        let (result_tx, results) = mpsc::channel();
+        let jobs = (0..WORKERS)
+            .map(|i| {
+                let (job_tx, job_rx) = mpsc::channel::<PixmapJob>();
+                let conn = conn.clone();
+                let result_tx = result_tx.clone();
+                let wake = wake.clone();
+                let spawned = std::thread::Builder::new()
+                    .name(format!("area-pixmap-{}", i))
+                    .spawn(move || {
+                        for job in job_rx {
+                            let result = prepare(&conn, &job);
+                            // Retries wait for the next frame, waking for them would spin
+                            let ready = matches!(result, PixmapResult::Ready { .. });
+                            if result_tx.send(result).is_err() {
+                                break;
+                            }
+                            if ready {
+                                let _ = wake.send(CompositorCommand::TriggerRender);
+                            }
+                        }
+                    });
+                if let Err(e) = spawned {
+                    warn!("Failed to start pixmap worker {}: {}", i, e);
+                }
+                job_tx
+            })
+            .collect();
+        Self { jobs, results, next: 0 }
+    }
+
+    /// Queue a window (round-robin over the workers)
+    pub fn submit(&mut self, job: PixmapJob) {
+        self.next = (self.next + 1) % self.jobs.len();
+        let _ = self.jobs[self.next].send(job);
+    }
+
+    /// Finished jobs, without blocking
+    pub fn finished(&self) -> impl Iterator<Item = PixmapResult> + '_ {
+        self.results.try_iter()
+    }
+}
+
+/// Redirect (if needed) and name the pixmap of a window
+fn prepare(conn: &RustConnection, job: &PixmapJob) -> PixmapResult {
+    let (window_id, client_id) = (job.window_id, job.client_id);
+    let mut redirected = false;
+    if job.redirect {
+        // CRITICAL: Redirect window BEFORE creating pixmap (required by Composite extension)
+        let done = conn
+            .composite_redirect_window(client_id, composite::Redirect::MANUAL)
+            .map(|cookie| cookie.check());
+        if !matches!(done, Ok(Ok(()))) {
+            warn!("Failed to redirect window {} before pixmap creation", client_id);
+            return PixmapResult::Failed { window_id, redirected };
+        }
+        redirected = true;
+    }
+
+    match conn.get_window_attributes(window_id).map(|cookie| cookie.reply()) {
+        Ok(Ok(attrs)) if attrs.map_state != MapState::VIEWABLE => {
+            return PixmapResult::Retry { window_id, redirected };
+        }
+        Ok(Ok(_)) => {}
+        _ => return PixmapResult::Failed { window_id, redirected },
+    }
+
+    let Ok(pixmap) = conn.generate_id() else {
+        warn!("Failed to generate pixmap ID for window {}", window_id);
+        return PixmapResult::Retry { window_id, redirected };
+    };
+    debug!("Attempting to create pixmap {} for window {}", pixmap, window_id);
+    if !matches!(conn.composite_name_window_pixmap(client_id, pixmap).map(|cookie| cookie.check()), Ok(Ok(()))) {
+        warn!("composite_name_window_pixmap failed for window {} (pixmap {})", window_id, pixmap);
+        let _ = conn.free_pixmap(pixmap);
+        return PixmapResult::Failed { window_id, redirected };
+    }
+
+    // The pixmap must match the client's current size, else it is being resized
+    let pixmap_geom = conn.get_geometry(pixmap).map(|cookie| cookie.reply());
+    let client_geom = conn.get_geometry(client_id).map(|cookie| cookie.reply());
+    match (pixmap_geom, client_geom) {
+        (Ok(Ok(p)), Ok(Ok(c))) if p.width != 0 && p.height != 0 && (p.width, p.height) == (c.width, c.height) => {
+            debug!("Created pixmap {} for window {} ({}x{}, depth {})", pixmap, window_id, p.width, p.height, c.depth);
+            PixmapResult::Ready { window_id, pixmap, depth: c.depth, redirected }
+        }
+        (Ok(Ok(p)), Ok(Ok(c))) => {
+            debug!("Pixmap {} size mismatch for window {} (client {}): pixmap={}x{}, client={}x{}",
+                pixmap, window_id, client_id, p.width, p.height, c.width, c.height);
+            let _ = conn.free_pixmap(pixmap);
+            PixmapResult::Retry { window_id, redirected }
+        }
+        _ => {
+            let _ = conn.free_pixmap(pixmap);
+            PixmapResult::Retry { window_id, redirected }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Override-redirect windows (menus, tooltips)
+//!
+//! The WM doesn't manage popups but the compositor still has to draw them.
+//! With `compositor.popups.direct`, a new popup is left unredirected so X
+//! draws it right away instead of waiting for a texture; one still open
+//! after `promote_after_ms` is composited like any other window.
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Popups known to the compositor
+#[derive(Debug, Default)]
+pub struct Popups {
+    windows: HashSet<u32>,
+    /// Popups drawn directly, and when they are due for compositing
+    direct: HashMap<u32, Instant>,
+}
+
+impl Popups {
+    /// A popup was mapped; true if it should be drawn directly
+    pub fn mapped(&mut self, window: u32, config: &crate::config::PopupConfig) -> bool {
+        self.windows.insert(window);
+        if !config.direct {
+            return false;
+        }
+        self.direct.insert(window, Instant::now() + Duration::from_millis(config.promote_after_ms));
+        true
+    }
+
+    /// Whether the compositor already has this popup
+    pub fn is_tracked(&self, window: u32) -> bool {
+        self.windows.contains(&window)
+    }
+
+    /// Forget a popup (unmapped or destroyed); false if it wasn't one
+    pub fn remove(&mut self, window: u32) -> bool {
+        self.direct.remove(&window);
+        self.windows.remove(&window)
+    }
+
+    /// When the next directly drawn popup is due for compositing
+    pub fn next_promotion(&self) -> Option<Instant> {
+        self.direct.values().min().copied()
+    }
+
+    /// Take the directly drawn popups that lived long enough to be composited
+    pub fn promote(&mut self, now: Instant) -> Vec<u32> {
+        self.direct.extract_if(|_, due| *due <= now).map(|(window, _)| window).collect()
+    }
+}
@@ -0,0 +1,154 @@
+//! Live window previews
+//!
+//! Downscaled frames of a single window written to a shared-memory file (see
+//! `area_ipc::live_preview_path` for the layout) so the shell can show a
+//! miniature while a taskbar button is hovered. Frames are captured on the
+//! compositor thread from the window's texture, only when the window changed.
+
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// How long a preview keeps streaming after the last request
+pub const LIVE_PREVIEW_DURATION: Duration = Duration::from_secs(5);
+
+/// Largest preview width/height accepted
+pub const MAX_PREVIEW_SIZE: u32 = 1024;
+
+/// Highest preview frame rate accepted
+pub const MAX_PREVIEW_FPS: u32 = 30;
+
+/// A preview being streamed
+pub struct LivePreview {
+    file: File,
+    path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    interval: Duration,
+    until: Instant,
+    last_frame: Option<Instant>,
+    /// The window changed since the last frame
+    stale: bool,
+    sequence: u32,
+}
+
+impl LivePreview {
+    /// Create the shared-memory file
+    ///
+    /// Whatever is at `path` is removed first and the file is created
+    /// exclusively, so a symlink planted there (the directory may be a
+    /// world-writable `/tmp`) is never followed.
+    pub fn create(path: PathBuf, width: u32, height: u32, fps: u32) -> Result<Self> {
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(e).with_context(|| format!("Failed to remove old live preview file {:?}", path));
+            }
+            _ => {}
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .with_context(|| format!("Failed to create live preview file {:?}", path))?;
+        file.set_len((area_ipc::LIVE_PREVIEW_HEADER + (width * height * 4) as usize) as u64)?;
+
+        let mut preview = Self {
+            file,
+            path,
+            width,
+            height,
+            interval: Duration::from_secs(1) / fps.max(1),
+            until: Instant::now(),
+            last_frame: None,
+            stale: true,
+            sequence: 0,
+        };
+        preview.extend(fps);
+        preview.write_header()?;
+        Ok(preview)
+    }
+
+    /// Keep streaming for another `LIVE_PREVIEW_DURATION`
+    pub fn extend(&mut self, fps: u32) {
+        self.interval = Duration::from_secs(1) / fps.max(1);
+        self.until = Instant::now() + LIVE_PREVIEW_DURATION;
+    }
+
+    pub fn expired(&self, now: Instant) -> bool {
+        now >= self.until
+    }
+
+    /// Note that the window changed
+    pub fn mark_stale(&mut self) {
+        self.stale = true;
+    }
+
+    /// A changed window is waiting for its next frame
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Whether a frame should be captured now
+    pub fn due(&self, now: Instant) -> bool {
+        self.stale && self.last_frame.is_none_or(|last| now.duration_since(last) >= self.interval)
+    }
+
+    /// Publish a frame (`width * height` RGBA pixels, top row first)
+    pub fn write_frame(&mut self, rgba: &[u8]) -> Result<()> {
+        // Odd sequence while the pixels are inconsistent
+        self.sequence = self.sequence.wrapping_add(1);
+        self.write_header()?;
+        self.file.write_all_at(rgba, area_ipc::LIVE_PREVIEW_HEADER as u64)?;
+        self.sequence = self.sequence.wrapping_add(1);
+        self.write_header()?;
+
+        self.last_frame = Some(Instant::now());
+        self.stale = false;
+        Ok(())
+    }
+
+    fn write_header(&self) -> Result<()> {
+        let mut header = [0u8; area_ipc::LIVE_PREVIEW_HEADER];
+        for (field, value) in header.chunks_exact_mut(4).zip([self.sequence, self.width, self.height, 0]) {
+            field.copy_from_slice(&value.to_le_bytes());
+        }
+        self.file.write_all_at(&header, 0)?;
+        Ok(())
+    }
+}
+
+impl Drop for LivePreview {
+    fn drop(&mut self) {
+        debug!("Live preview {:?} ended", self.path);
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// The last frame of a closed window, kept for the shell for a while
+pub struct ClosedSnapshot {
+    preview: LivePreview,
+    until: Instant,
+}
+
+impl ClosedSnapshot {
+    /// Write the frame (`width * height` RGBA pixels, top row first)
+    pub fn create(path: PathBuf, width: u32, height: u32, rgba: &[u8], keep: Duration) -> Result<Self> {
+        let mut preview = LivePreview::create(path, width, height, 1)?;
+        preview.write_frame(rgba)?;
+        Ok(Self { preview, until: Instant::now() + keep })
+    }
+
+    pub fn expired(&self, now: Instant) -> bool {
+        now >= self.until
+    }
+
+    /// Whether the snapshot is kept in the file at `path`
+    pub fn is_at(&self, path: &std::path::Path) -> bool {
+        self.preview.path == path
+    }
+}
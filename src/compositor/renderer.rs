@@ -10,8 +10,29 @@ struct WindowTexture {
     texture: u32,
     glx_pixmap: Option<u32>, // None if using XGetImage fallback
     x11_pixmap: Option<u32>, // None if using XGetImage fallback
+    /// ARGB (depth 32) pixmap: its alpha is real and its colors premultiplied
+    has_alpha: bool,
 }
 
+/// Quad vertex shader shared by every program; passes `TexCoord` (0..1,
+/// top-left origin) to the fragment shader
+const VERTEX_SHADER: &str = r#"
+    #version 330 core
+    layout (location = 0) in vec2 aPos;
+    layout (location = 1) in vec2 aTexCoord;
+    
+    uniform vec2 uPosition;
+    uniform vec2 uSize;
+    
+    out vec2 TexCoord;
+    
+    void main() {
+        vec2 pos = aPos * uSize + uPosition;
+        gl_Position = vec4(pos.x, pos.y, 0.0, 1.0);
+        TexCoord = aTexCoord;
+    }
+"#;
+
 /// OpenGL renderer for compositing windows
 pub struct Renderer {
     program: u32,
@@ -19,11 +40,19 @@ pub struct Renderer {
     vbo: u32,
     textures: std::collections::HashMap<u32, WindowTexture>, // window_id -> WindowTexture
     white_texture: u32, // Cached 1x1 white texture for solid color rendering
+    /// Scissor boxes `render_window` draws through (empty: unclipped)
+    clip: std::cell::RefCell<Vec<[i32; 4]>>,
+    /// Scissor boxes `render_window` draws without blending (see `set_opaque`)
+    opaque: std::cell::RefCell<Vec<[i32; 4]>>,
+    /// Internal format of offscreen render targets (16 bits per channel
+    /// on a deep color framebuffer, so nothing is lost on the way; alpha
+    /// keeps its full depth for translucent windows in previews and expo)
+    target_format: u32,
 }
 
 impl Renderer {
-    /// Initialize the OpenGL renderer
-    pub fn new() -> Result<Self> {
+    /// Initialize the OpenGL renderer for a framebuffer with `color_bits` per channel
+    pub fn new(color_bits: i32) -> Result<Self> {
         unsafe {
             // Enable blending for transparency
             gl::Enable(gl::BLEND);
@@ -31,6 +60,9 @@ impl Renderer {
 
             // Create shader program
             let program = Self::create_shader_program()?;
+            gl::UseProgram(program);
+            gl::Uniform1f(gl::GetUniformLocation(program, c"uSaturation".as_ptr()), 1.0);
+            gl::Uniform1f(gl::GetUniformLocation(program, c"uBrightness".as_ptr()), 1.0);
 
             // Create VAO and VBO for window quads
             let mut vao = 0;
@@ -77,28 +109,16 @@ impl Renderer {
                 vbo,
                 textures: std::collections::HashMap::new(),
                 white_texture,
+                clip: std::cell::RefCell::new(Vec::new()),
+                opaque: std::cell::RefCell::new(Vec::new()),
+                target_format: if color_bits > 8 { gl::RGBA16 } else { gl::RGBA },
             })
         }
     }
 
     /// Create shader program
     fn create_shader_program() -> Result<u32> {
-        let vertex_shader = r#"
-            #version 330 core
-            layout (location = 0) in vec2 aPos;
-            layout (location = 1) in vec2 aTexCoord;
-            
-            uniform vec2 uPosition;
-            uniform vec2 uSize;
-            
-            out vec2 TexCoord;
-            
-            void main() {
-                vec2 pos = aPos * uSize + uPosition;
-                gl_Position = vec4(pos.x, pos.y, 0.0, 1.0);
-                TexCoord = aTexCoord;
-            }
-        "#;
+        let vertex_shader = VERTEX_SHADER;
 
         let fragment_shader = r#"
             #version 330 core
@@ -108,10 +128,27 @@ impl Renderer {
             
             uniform sampler2D uTexture;
             uniform float uOpacity;
+            uniform float uSaturation;
+            // Color multiplier (windows dimmed as inactive)
+            uniform float uBrightness;
+            // Window pixmaps: output premultiplied color (blended with
+            // ONE, ONE_MINUS_SRC_ALPHA), and ignore alpha unless uAlpha
+            uniform bool uPremultiplied;
+            uniform bool uAlpha;
             
             void main() {
                 vec4 texColor = texture(uTexture, TexCoord);
-                FragColor = vec4(texColor.rgb, texColor.a * uOpacity);
+                if (uPremultiplied && !uAlpha) {
+                    texColor.a = 1.0;
+                }
+                // Desaturate and dim (unresponsive windows); 1.0 leaves the color untouched
+                float luma = dot(texColor.rgb, vec3(0.299, 0.587, 0.114));
+                vec3 rgb = mix(vec3(luma), texColor.rgb, uSaturation) * mix(0.7, 1.0, uSaturation) * uBrightness;
+                if (uPremultiplied) {
+                    FragColor = vec4(rgb, texColor.a) * uOpacity;
+                } else {
+                    FragColor = vec4(rgb, texColor.a * uOpacity);
+                }
             }
         "#;
 
@@ -127,6 +164,58 @@ impl Renderer {
         }
     }
 
+    /// Compile a program from an effect's fragment shader and the quad
+    /// vertex shader (see `draw_quad`)
+    pub fn create_effect_program(&self, fragment_shader: &str) -> Result<u32> {
+        unsafe {
+            let vs = Self::compile_shader(VERTEX_SHADER, gl::VERTEX_SHADER)?;
+            let fs = match Self::compile_shader(fragment_shader, gl::FRAGMENT_SHADER) {
+                Ok(fs) => fs,
+                Err(e) => {
+                    gl::DeleteShader(vs);
+                    return Err(e);
+                }
+            };
+            let program = Self::link_program(vs, fs);
+            gl::DeleteShader(vs);
+            gl::DeleteShader(fs);
+            program
+        }
+    }
+
+    /// Draw a screen rectangle with an effect program
+    ///
+    /// The effect sets its own uniforms (after `glUseProgram`) beforehand;
+    /// this sets `uPosition` and `uSize` and draws the quad.
+    pub fn draw_quad(&self, program: u32, x: f32, y: f32, width: f32, height: f32, screen: (f32, f32)) {
+        let (screen_width, screen_height) = screen;
+        unsafe {
+            gl::UseProgram(program);
+            let x_gl = (x / screen_width) * 2.0 - 1.0;
+            let y_gl = 1.0 - ((y + height) / screen_height) * 2.0;
+            gl::Uniform2f(gl::GetUniformLocation(program, c"uPosition".as_ptr()), x_gl, y_gl);
+            gl::Uniform2f(gl::GetUniformLocation(program, c"uSize".as_ptr()), (width / screen_width) * 2.0, (height / screen_height) * 2.0);
+
+            let vertices: [f32; 16] = [
+                0.0, 0.0, 0.0, 1.0,
+                1.0, 0.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 0.0,
+                0.0, 1.0, 0.0, 0.0,
+            ];
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (vertices.len() * std::mem::size_of::<f32>()) as isize,
+                vertices.as_ptr() as *const _,
+                gl::DYNAMIC_DRAW,
+            );
+            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+            gl::BindVertexArray(0);
+            gl::UseProgram(self.program);
+        }
+    }
+
     fn compile_shader(source: &str, shader_type: u32) -> Result<u32> {
         unsafe {
             let shader = gl::CreateShader(shader_type);
@@ -218,6 +307,7 @@ impl Renderer {
 
                 win_tex.glx_pixmap = Some(new_glx_pixmap);
                 win_tex.x11_pixmap = Some(x11_pixmap);
+                win_tex.has_alpha = depth == 32;
                 trace!("Updated texture for window {} - glx_pixmap={:?}, texture={}", window_id, win_tex.glx_pixmap, win_tex.texture);
                 
                 Ok(old_x11)
@@ -245,6 +335,7 @@ impl Renderer {
                     texture,
                     glx_pixmap: Some(new_glx_pixmap),
                     x11_pixmap: Some(x11_pixmap),
+                    has_alpha: depth == 32,
                 });
                 
                 trace!("Inserted texture for window {} into HashMap - has_texture now returns: {}", window_id, self.has_texture(window_id));
@@ -254,6 +345,42 @@ impl Renderer {
         }
     }
 
+    /// Color saturation of subsequent draws (1.0: normal, 0.0: grayscale and dimmed)
+    pub fn set_saturation(&self, saturation: f32) {
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::Uniform1f(gl::GetUniformLocation(self.program, c"uSaturation".as_ptr()), saturation);
+        }
+    }
+
+    /// Color multiplier of subsequent draws (1.0: normal)
+    pub fn set_brightness(&self, brightness: f32) {
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::Uniform1f(gl::GetUniformLocation(self.program, c"uBrightness".as_ptr()), brightness);
+        }
+    }
+
+    /// Limit subsequent `render_window` calls to rectangles in screen
+    /// coordinates (top-left origin); an empty list removes the limit
+    pub fn set_clip(&self, rects: &[(f32, f32, f32, f32)], screen_height: f32) {
+        *self.clip.borrow_mut() = rects
+            .iter()
+            .map(|&(x, y, width, height)| [x as i32, (screen_height - y - height) as i32, width as i32, height as i32])
+            .collect();
+    }
+
+    /// Draw subsequent `render_window` calls through these rectangles with
+    /// blending off and alpha ignored (a window's opaque region); the clip
+    /// then lists the remaining, blended parts, and an empty clip draws
+    /// nothing blended. An empty list turns this off.
+    pub fn set_opaque(&self, rects: &[(f32, f32, f32, f32)], screen_height: f32) {
+        *self.opaque.borrow_mut() = rects
+            .iter()
+            .map(|&(x, y, width, height)| [x as i32, (screen_height - y - height) as i32, width as i32, height as i32])
+            .collect();
+    }
+
     /// Render a window with per-frame texture binding (like Compiz's strictBinding mode)
     pub fn render_window(
         &self,
@@ -300,6 +427,11 @@ impl Renderer {
             gl::Uniform1f(opacity_loc, opacity);
             gl::Uniform1i(tex_loc, 0);
 
+            // X pixmaps of ARGB windows hold premultiplied colors
+            gl::Uniform1i(gl::GetUniformLocation(self.program, c"uPremultiplied".as_ptr()), 1);
+            gl::Uniform1i(gl::GetUniformLocation(self.program, c"uAlpha".as_ptr()), win_tex.has_alpha as i32);
+            gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+
             // Bind texture with per-frame TFP binding (strictBinding mode)
             gl::ActiveTexture(gl::TEXTURE0);
             gl::BindTexture(gl::TEXTURE_2D, win_tex.texture);
@@ -347,7 +479,33 @@ impl Renderer {
                 gl::DYNAMIC_DRAW,
             );
 
-            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+            // Opaque parts first: no blending, alpha ignored
+            let opaque = self.opaque.borrow();
+            if !opaque.is_empty() {
+                gl::Disable(gl::BLEND);
+                gl::Uniform1i(gl::GetUniformLocation(self.program, c"uAlpha".as_ptr()), 0);
+                gl::Enable(gl::SCISSOR_TEST);
+                for &[x, y, width, height] in opaque.iter() {
+                    gl::Scissor(x, y, width, height);
+                    gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+                }
+                gl::Disable(gl::SCISSOR_TEST);
+                gl::Enable(gl::BLEND);
+                gl::Uniform1i(gl::GetUniformLocation(self.program, c"uAlpha".as_ptr()), win_tex.has_alpha as i32);
+            }
+
+            // One draw per clip rectangle, binding the pixmap only once
+            let clip = self.clip.borrow();
+            if clip.is_empty() && opaque.is_empty() {
+                gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+            } else {
+                gl::Enable(gl::SCISSOR_TEST);
+                for &[x, y, width, height] in clip.iter() {
+                    gl::Scissor(x, y, width, height);
+                    gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+                }
+                gl::Disable(gl::SCISSOR_TEST);
+            }
             
             // CRITICAL: Release the pixmap image AFTER drawing (like Compiz strictBinding)
             if let Some(glx_pixmap) = win_tex.glx_pixmap {
@@ -356,6 +514,8 @@ impl Renderer {
             
             gl::BindVertexArray(0);
             gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::Uniform1i(gl::GetUniformLocation(self.program, c"uPremultiplied".as_ptr()), 0);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
             
             // Check for OpenGL errors
             let err = gl::GetError();
@@ -366,6 +526,172 @@ impl Renderer {
     }
 
 
+    /// Read back what was drawn this frame (before the swap) as RGBA, top row first
+    pub fn read_screen(&self, width: u32, height: u32) -> Vec<u8> {
+        let stride = width as usize * 4;
+        let mut pixels = vec![0u8; stride * height as usize];
+        unsafe {
+            gl::ReadBuffer(gl::BACK);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, width as i32, height as i32, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut _);
+        }
+        // GL rows are bottom to top
+        let mut flipped = Vec::with_capacity(pixels.len());
+        for row in pixels.chunks_exact(stride).rev() {
+            flipped.extend_from_slice(row);
+        }
+        flipped
+    }
+
+    /// Draw a window's texture scaled to `width` x `height` and read it back
+    ///
+    /// Returns RGBA pixels, top row first, or None if the window has no texture.
+    pub fn capture_window(
+        &self,
+        ctx: &super::gl_context::GlContext,
+        window_id: u32,
+        width: u32,
+        height: u32,
+    ) -> Option<Vec<u8>> {
+        unsafe {
+            let (target, fbo, viewport) = self.draw_window_offscreen(ctx, window_id, width, height)?;
+            let stride = width as usize * 4;
+            let mut pixels = vec![0u8; stride * height as usize];
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, width as i32, height as i32, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut _);
+            Self::end_offscreen(fbo, viewport);
+            gl::DeleteTextures(1, &target);
+
+            let err = gl::GetError();
+            if err != gl::NO_ERROR {
+                warn!("OpenGL error after capturing window {}: 0x{:x}", window_id, err);
+                return None;
+            }
+
+            // GL rows are bottom-up
+            let flipped = pixels.chunks_exact(stride).rev().flatten().copied().collect();
+            Some(flipped)
+        }
+    }
+
+    /// Copy a window's current contents, scaled to `width` x `height`, into a
+    /// texture of its own that outlives the window's pixmap
+    ///
+    /// Rows are bottom-up: draw it with `render_texture` and `uv` `[0, 1, 1, 0]`.
+    /// Free it with `delete_texture`.
+    pub fn snapshot_window(
+        &self,
+        ctx: &super::gl_context::GlContext,
+        window_id: u32,
+        width: u32,
+        height: u32,
+    ) -> Option<u32> {
+        unsafe {
+            let (target, fbo, viewport) = self.draw_window_offscreen(ctx, window_id, width, height)?;
+            Self::end_offscreen(fbo, viewport);
+            let err = gl::GetError();
+            if err != gl::NO_ERROR {
+                warn!("OpenGL error after taking a snapshot of window {}: 0x{:x}", window_id, err);
+                gl::DeleteTextures(1, &target);
+                return None;
+            }
+            Some(target)
+        }
+    }
+
+    /// Draw a window's texture into a new `width` x `height` texture attached
+    /// to a new, bound framebuffer; returns both and the viewport to restore
+    /// with `end_offscreen`
+    unsafe fn draw_window_offscreen(
+        &self,
+        ctx: &super::gl_context::GlContext,
+        window_id: u32,
+        width: u32,
+        height: u32,
+    ) -> Option<(u32, u32, [i32; 4])> {
+        let win_tex = self.textures.get(&window_id)?;
+
+        unsafe {
+            // Offscreen target of the requested size
+            let mut viewport = [0i32; 4];
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            let mut target = 0;
+            gl::GenTextures(1, &mut target);
+            gl::BindTexture(gl::TEXTURE_2D, target);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                self.target_format as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                ptr::null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            let mut fbo = 0;
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, target, 0);
+            gl::Viewport(0, 0, width as i32, height as i32);
+            gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+            gl::Disable(gl::BLEND);
+
+            // Full-target quad, same orientation as render_window; opaque
+            // windows get an opaque copy
+            gl::UseProgram(self.program);
+            gl::Uniform2f(gl::GetUniformLocation(self.program, c"uPosition".as_ptr()), -1.0, -1.0);
+            gl::Uniform2f(gl::GetUniformLocation(self.program, c"uSize".as_ptr()), 2.0, 2.0);
+            gl::Uniform1f(gl::GetUniformLocation(self.program, c"uOpacity".as_ptr()), 1.0);
+            gl::Uniform1i(gl::GetUniformLocation(self.program, c"uTexture".as_ptr()), 0);
+            gl::Uniform1i(gl::GetUniformLocation(self.program, c"uPremultiplied".as_ptr()), 1);
+            gl::Uniform1i(gl::GetUniformLocation(self.program, c"uAlpha".as_ptr()), win_tex.has_alpha as i32);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, win_tex.texture);
+            if let Some(glx_pixmap) = win_tex.glx_pixmap {
+                ctx.bind_tex_image(glx_pixmap);
+            }
+
+            gl::BindVertexArray(self.vao);
+            let vertices: [f32; 16] = [
+                0.0, 0.0, 0.0, 1.0,
+                1.0, 0.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 0.0,
+                0.0, 1.0, 0.0, 0.0,
+            ];
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (vertices.len() * std::mem::size_of::<f32>()) as isize,
+                vertices.as_ptr() as *const _,
+                gl::DYNAMIC_DRAW,
+            );
+            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+            if let Some(glx_pixmap) = win_tex.glx_pixmap {
+                ctx.release_tex_image(glx_pixmap);
+            }
+            gl::Uniform1i(gl::GetUniformLocation(self.program, c"uPremultiplied".as_ptr()), 0);
+            Some((target, fbo, viewport))
+        }
+    }
+
+    /// Restore the on-screen state after `draw_window_offscreen`
+    unsafe fn end_offscreen(fbo: u32, viewport: [i32; 4]) {
+        unsafe {
+            gl::BindVertexArray(0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::DeleteFramebuffers(1, &fbo);
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+            gl::Enable(gl::BLEND);
+        }
+    }
+
     /// Check if texture exists for window
     pub fn has_texture(&self, window_id: u32) -> bool {
         self.textures.contains_key(&window_id)
@@ -644,6 +970,102 @@ impl Renderer {
             gl::BindTexture(gl::TEXTURE_2D, 0);
         }
     }
+
+    /// Upload a decoded RGBA image as a (repeating) texture
+    pub fn upload_texture(&self, image: &super::image::Image) -> u32 {
+        unsafe {
+            let mut tex_id = 0;
+            gl::GenTextures(1, &mut tex_id);
+            gl::BindTexture(gl::TEXTURE_2D, tex_id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as i32,
+                image.width as i32,
+                image.height as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                image.pixels.as_ptr() as *const _,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            let err = gl::GetError();
+            if err != gl::NO_ERROR {
+                warn!("OpenGL error after uploading {}x{} image: 0x{:x}", image.width, image.height, err);
+            }
+            tex_id
+        }
+    }
+
+    /// Delete a texture created by `upload_texture`
+    pub fn delete_texture(&self, texture_id: u32) {
+        unsafe {
+            gl::DeleteTextures(1, &texture_id);
+        }
+    }
+
+    /// Render part of a texture at `(x, y, width, height)` (`uv` = `[u0, v0, u1, v1]`, v0 at the top)
+    pub fn render_texture(
+        &self,
+        texture_id: u32,
+        (x, y, width, height): (f32, f32, f32, f32),
+        uv: [f32; 4],
+        (screen_width, screen_height): (f32, f32),
+        opacity: f32,
+    ) {
+        unsafe {
+            gl::UseProgram(self.program);
+
+            // Convert screen coordinates to OpenGL normalized coordinates
+            let x_gl = (x / screen_width) * 2.0 - 1.0;
+            let y_gl = 1.0 - ((y + height) / screen_height) * 2.0;
+            let width_gl = (width / screen_width) * 2.0;
+            let height_gl = (height / screen_height) * 2.0;
+
+            // Set uniforms
+            let pos_loc = gl::GetUniformLocation(self.program, c"uPosition".as_ptr());
+            let size_loc = gl::GetUniformLocation(self.program, c"uSize".as_ptr());
+            let opacity_loc = gl::GetUniformLocation(self.program, c"uOpacity".as_ptr());
+            let tex_loc = gl::GetUniformLocation(self.program, c"uTexture".as_ptr());
+
+            gl::Uniform2f(pos_loc, x_gl, y_gl);
+            gl::Uniform2f(size_loc, width_gl, height_gl);
+            gl::Uniform1f(opacity_loc, opacity);
+
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, texture_id);
+            gl::Uniform1i(tex_loc, 0);
+
+            // Render quad
+            gl::BindVertexArray(self.vao);
+
+            let [u0, v0, u1, v1] = uv;
+            let vertices: [f32; 16] = [
+                0.0, 0.0, u0, v1,
+                1.0, 0.0, u1, v1,
+                1.0, 1.0, u1, v0,
+                0.0, 1.0, u0, v0,
+            ];
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (vertices.len() * std::mem::size_of::<f32>()) as isize,
+                vertices.as_ptr() as *const _,
+                gl::DYNAMIC_DRAW,
+            );
+
+            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+            gl::BindVertexArray(0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
 }
 
 impl Drop for Renderer {
@@ -0,0 +1,96 @@
+//! Frame pacing
+//!
+//! Caps the compositor's frame rate (lower on battery) and, after a while
+//! without window damage, cursor motion or animations, throttles the
+//! remaining redraw requests to an idle rate or drops them entirely.
+
+use std::time::{Duration, Instant};
+
+use crate::config::FrameRateConfig;
+
+/// What the render loop should do with a pending frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDecision {
+    /// Render now
+    Render,
+    /// Too early for the current rate, render after this delay
+    Wait(Duration),
+    /// Idle and nothing changed on screen, drop the request
+    Skip,
+}
+
+/// Decides when frames are rendered
+pub struct FrameScheduler {
+    config: FrameRateConfig,
+    /// UPower reports the system is discharging
+    on_battery: bool,
+    last_frame: Option<Instant>,
+    last_activity: Instant,
+}
+
+impl FrameScheduler {
+    pub fn new(config: FrameRateConfig) -> Self {
+        Self {
+            config,
+            on_battery: false,
+            last_frame: None,
+            last_activity: Instant::now(),
+        }
+    }
+
+    pub fn set_config(&mut self, config: FrameRateConfig) {
+        self.config = config;
+    }
+
+    pub fn set_on_battery(&mut self, on_battery: bool) {
+        self.on_battery = on_battery;
+    }
+
+    /// Whether no activity was seen for `idle_after_ms`
+    pub fn is_idle(&self, now: Instant) -> bool {
+        now.duration_since(self.last_activity) >= Duration::from_millis(self.config.idle_after_ms)
+    }
+
+    /// Frame rate cap in the current state (0: uncapped)
+    fn max_fps(&self, idle: bool) -> u32 {
+        let cap = |a: u32, b: u32| match (a, b) {
+            (0, b) => b,
+            (a, 0) => a,
+            (a, b) => a.min(b),
+        };
+        let mut fps = self.config.max_fps;
+        if self.on_battery {
+            fps = cap(fps, self.config.battery_max_fps);
+        }
+        if idle {
+            fps = cap(fps, self.config.idle_fps);
+        }
+        fps
+    }
+
+    /// Decide about a pending frame; `active` is true when something on
+    /// screen changed (damage, cursor motion, animation)
+    pub fn next_frame(&mut self, now: Instant, active: bool) -> FrameDecision {
+        if active {
+            self.last_activity = now;
+        }
+        let idle = self.is_idle(now);
+        if idle && self.config.idle_fps == 0 {
+            return FrameDecision::Skip;
+        }
+        let fps = self.max_fps(idle);
+        if fps == 0 {
+            return FrameDecision::Render;
+        }
+        let interval = Duration::from_secs(1) / fps;
+        match self.last_frame {
+            Some(last) if now.duration_since(last) < interval => FrameDecision::Wait(interval - now.duration_since(last)),
+            _ => FrameDecision::Render,
+        }
+    }
+
+    /// A frame was rendered
+    pub fn frame_rendered(&mut self, now: Instant) {
+        self.last_frame = Some(now);
+    }
+}
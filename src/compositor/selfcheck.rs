@@ -0,0 +1,166 @@
+//! Self-check test patterns (`area --selfcheck`, `area-msg self-check on`)
+//!
+//! Patterns for triaging driver problems, drawn over everything but the
+//! cursor: gray, red, green and blue ramps (banding or wrong colors point
+//! at the framebuffer format), a bar sweeping across the screen every frame
+//! (a break in it is tearing, so vsync is not working) and a red flash over
+//! each window the compositor repaints (windows flashing while idle waste
+//! power). The top-left corner names the OpenGL driver. While shown, the
+//! compositor renders every frame.
+
+use std::ffi::CStr;
+use std::time::{Duration, Instant};
+
+use area_ipc::GlCapabilities;
+
+use crate::shared::Geometry;
+
+use super::font;
+use super::gl_context::GlContext;
+use super::image::Image;
+use super::renderer::Renderer;
+
+/// Pixels per font dot
+const SCALE: u32 = 2;
+/// Distance from the screen edges (px)
+const MARGIN: f32 = 12.0;
+/// Rows of each color ramp in the gradient texture
+const RAMP_ROWS: u32 = 16;
+/// Size the gradients are drawn at (px)
+const GRADIENT_SIZE: (f32, f32) = (768.0, 192.0);
+const BAR_WIDTH: f32 = 24.0;
+/// Distance the tear test bar moves per frame (px)
+const BAR_STEP: f32 = 16.0;
+const DAMAGE_COLOR: [f32; 4] = [0.9, 0.1, 0.1, 0.35];
+/// How long a repaint flash fades
+const DAMAGE_FADE: Duration = Duration::from_millis(400);
+const BACKGROUND: [u8; 4] = [13, 13, 13, 204];
+
+#[derive(Debug, Default)]
+pub struct SelfCheck {
+    enabled: bool,
+    /// Frames drawn since shown, moving the bar
+    frame: u64,
+    /// Repainted areas and when
+    flashes: Vec<(Geometry, Instant)>,
+    /// Gradient and text textures with their sizes (created on first draw)
+    gradient: Option<u32>,
+    lines: Vec<(u32, f32, f32)>,
+    /// Text of the label, set with the patterns
+    label: Vec<String>,
+}
+
+impl SelfCheck {
+    /// Show or hide the patterns; the label names the driver
+    pub fn set(&mut self, enabled: bool, gl: Option<&GlCapabilities>) {
+        self.enabled = enabled;
+        self.frame = 0;
+        self.flashes.clear();
+        self.label = match gl {
+            Some(gl) => vec![
+                "SELF-CHECK: SMOOTH RAMPS - STRAIGHT BAR - RED: REPAINTED".to_string(),
+                format!("{} - {}", gl.vendor, gl.renderer),
+                format!("GL {}  VSYNC {}  {} BIT", gl.version, if gl.vsync { "ON" } else { "OFF" }, gl.color_bits),
+            ],
+            None => vec!["SELF-CHECK".to_string()],
+        };
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.enabled
+    }
+
+    /// Flash a window drawn with new contents
+    pub fn record_damage(&mut self, area: Geometry) {
+        if self.enabled {
+            self.flashes.push((area, Instant::now()));
+        }
+    }
+
+    pub fn render(&mut self, renderer: &Renderer, screen: (f32, f32)) {
+        if !self.enabled {
+            // Hidden since the last frame
+            for texture in self.gradient.take().into_iter().chain(self.lines.drain(..).map(|(texture, _, _)| texture)) {
+                renderer.delete_texture(texture);
+            }
+            return;
+        }
+        if self.lines.is_empty() {
+            for text in &self.label {
+                let image = font::rasterize(text, SCALE, BACKGROUND);
+                self.lines.push((renderer.upload_texture(&image), image.width as f32, image.height as f32));
+            }
+        }
+        let gradient = *self.gradient.get_or_insert_with(|| renderer.upload_texture(&gradient_image()));
+
+        // Repaint flashes, under the patterns
+        let now = Instant::now();
+        self.flashes.retain(|&(_, at)| now.duration_since(at) < DAMAGE_FADE);
+        let [r, g, b, a] = DAMAGE_COLOR;
+        for &(area, at) in &self.flashes {
+            let fade = 1.0 - now.duration_since(at).as_secs_f32() / DAMAGE_FADE.as_secs_f32();
+            renderer.render_rectangle(area.x as f32, area.y as f32, area.width as f32, area.height as f32, screen.0, screen.1, r, g, b, a * fade);
+        }
+
+        let mut y = MARGIN;
+        for &(texture, w, h) in &self.lines {
+            renderer.render_texture(texture, (MARGIN, y, w, h), [0.0, 0.0, 1.0, 1.0], screen, 1.0);
+            y += h;
+        }
+        let (width, height) = (GRADIENT_SIZE.0.min(screen.0 - 2.0 * MARGIN), GRADIENT_SIZE.1);
+        renderer.render_texture(gradient, (MARGIN, y + MARGIN, width, height), [0.0, 0.0, 1.0, 1.0], screen, 1.0);
+
+        // Tear test: one full-height bar further right every frame
+        let span = (screen.0 - BAR_WIDTH).max(1.0);
+        let x = (self.frame as f32 * BAR_STEP) % span;
+        renderer.render_rectangle(x, 0.0, BAR_WIDTH, screen.1, screen.0, screen.1, 1.0, 1.0, 1.0, 1.0);
+        self.frame += 1;
+    }
+}
+
+/// Gray, red, green and blue ramps from black to full intensity
+fn gradient_image() -> Image {
+    let (width, height) = (256, 4 * RAMP_ROWS);
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for channel in [[1, 1, 1], [1, 0, 0], [0, 1, 0], [0, 0, 1]] {
+        for _ in 0..RAMP_ROWS {
+            for level in 0..width {
+                let level = level as u8;
+                pixels.extend(channel.map(|on| on * level));
+                pixels.push(255);
+            }
+        }
+    }
+    Image { width, height, pixels }
+}
+
+/// Describe the OpenGL setup (the context must be current)
+pub fn capabilities(ctx: &GlContext) -> GlCapabilities {
+    let string = |name: u32| unsafe {
+        let ptr = gl::GetString(name);
+        if ptr.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(ptr as *const _).to_string_lossy().into_owned()
+        }
+    };
+    let mut max_texture_size = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max_texture_size);
+    }
+    let mut extensions: Vec<String> = string(gl::EXTENSIONS).split_whitespace().map(str::to_string).collect();
+    extensions.extend(ctx.glx_extensions.split_whitespace().map(str::to_string));
+    let direct = unsafe { (ctx.glx.glXIsDirect)(ctx.display, ctx.context) != 0 };
+    GlCapabilities {
+        vendor: string(gl::VENDOR),
+        renderer: string(gl::RENDERER),
+        version: string(gl::VERSION),
+        shading_language_version: string(gl::SHADING_LANGUAGE_VERSION),
+        glx_version: format!("{}.{}", ctx.glx_version.0, ctx.glx_version.1),
+        direct_rendering: direct,
+        vsync: ctx.vsync,
+        color_bits: ctx.color_bits.max(0) as u32,
+        max_texture_size: max_texture_size.max(0) as u32,
+        extensions,
+    }
+}
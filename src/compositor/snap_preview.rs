@@ -0,0 +1,40 @@
+//! Placement preview for a window dragged onto another monitor
+//!
+//! A translucent rectangle with an outline where the window will land when
+//! it is dropped (its new work area if it is maximized).
+
+use crate::shared::Geometry;
+
+use super::renderer::Renderer;
+
+const FILL: [f32; 4] = [0.25, 0.55, 0.95, 0.2];
+const OUTLINE_COLOR: [f32; 3] = [0.25, 0.55, 0.95];
+const OUTLINE_WIDTH: f32 = 2.0;
+
+#[derive(Debug, Default)]
+pub struct SnapPreview {
+    area: Option<Geometry>,
+}
+
+impl SnapPreview {
+    /// Show the preview at a frame's outer geometry, or hide it (None)
+    pub fn set(&mut self, area: Option<Geometry>) {
+        self.area = area;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.area.is_some()
+    }
+
+    pub fn render(&self, renderer: &Renderer, screen: (f32, f32)) {
+        let Some(g) = self.area else { return };
+        let (x, y, w, h) = (g.x as f32, g.y as f32, g.width as f32, g.height as f32);
+        let [r, gr, b, a] = FILL;
+        renderer.render_rectangle(x, y, w, h, screen.0, screen.1, r, gr, b, a);
+        let [r, gr, b] = OUTLINE_COLOR;
+        let t = OUTLINE_WIDTH;
+        for (x, y, w, h) in [(x, y, w, t), (x, y + h - t, w, t), (x, y, t, h), (x + w - t, y, t, h)] {
+            renderer.render_rectangle(x, y, w, h, screen.0, screen.1, r, gr, b, 1.0);
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! Wallpaper
+//!
+//! The desktop background, drawn by the compositor as the bottom layer.
+//!
+//! The main thread resolves which image each monitor shows (`resolve`),
+//! decodes the images off the main loop (`load_layers`) and hands the result
+//! to the compositor thread, which uploads the textures and crossfades from
+//! the previous wallpaper.
+//!
+//! For legacy pseudo-transparent clients (urxvt, conky) the same wallpaper is
+//! also rendered on the CPU into a pixmap published as `_XROOTPMAP_ID` /
+//! `ESETROOT_PMAP_ID` (see `RootPixmap`).
+
+use anyhow::{Context, Result};
+use area_ipc::WallpaperMode;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::debug;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+use super::image::Image;
+use super::renderer::Renderer;
+use crate::config::WallpaperConfig;
+use crate::shared::Geometry;
+use crate::wm::screen::Monitor;
+
+/// Which image a monitor should show
+#[derive(Debug, Clone, PartialEq)]
+pub struct WallpaperChoice {
+    pub monitor: Geometry,
+    pub path: PathBuf,
+    pub mode: WallpaperMode,
+}
+
+/// A decoded wallpaper ready for upload
+pub struct WallpaperLayer {
+    pub monitor: Geometry,
+    pub image: Arc<Image>,
+    pub mode: WallpaperMode,
+}
+
+/// Pick the wallpaper of every monitor (workspace override, then monitor override, then default)
+pub fn resolve(config: &WallpaperConfig, monitors: &[Monitor], workspace: u32) -> Vec<WallpaperChoice> {
+    let workspace_spec = config.workspaces.get(&workspace.to_string());
+    monitors
+        .iter()
+        .filter_map(|monitor| {
+            let (path, mode) = match workspace_spec.or_else(|| config.monitors.get(&monitor.name)) {
+                Some(spec) => (spec.path.as_str(), spec.mode.unwrap_or(config.mode)),
+                None => (config.path.as_deref()?, config.mode),
+            };
+            Some(WallpaperChoice {
+                monitor: Geometry::new(monitor.x, monitor.y, monitor.width, monitor.height),
+                path: expand_home(path),
+                mode,
+            })
+        })
+        .collect()
+}
+
+/// Expand a leading `~/` to the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Decode the images of a set of choices (each file once)
+///
+/// Blocking; call from a worker thread.
+pub fn load_layers(choices: &[WallpaperChoice]) -> Result<Vec<WallpaperLayer>> {
+    let mut images: HashMap<&PathBuf, Arc<Image>> = HashMap::new();
+    let mut layers = Vec::with_capacity(choices.len());
+    for choice in choices {
+        let image = match images.get(&choice.path) {
+            Some(image) => image.clone(),
+            None => {
+                let image = Arc::new(super::image::load(&choice.path)?);
+                debug!("Loaded wallpaper {:?} ({}x{})", choice.path, image.width, image.height);
+                images.insert(&choice.path, image.clone());
+                image
+            }
+        };
+        layers.push(WallpaperLayer { monitor: choice.monitor, image, mode: choice.mode });
+    }
+    Ok(layers)
+}
+
+/// Where to draw an image on its monitor and which part of it (texture coordinates)
+///
+/// Returns the destination rectangle and `[u0, v0, u1, v1]`; coordinates
+/// beyond 1.0 repeat the texture (tile mode).
+pub fn place(monitor: Geometry, image_width: u32, image_height: u32, mode: WallpaperMode) -> (Geometry, [f32; 4]) {
+    let (mw, mh) = (monitor.width as f32, monitor.height as f32);
+    let (iw, ih) = (image_width.max(1) as f32, image_height.max(1) as f32);
+
+    match mode {
+        WallpaperMode::Stretch => (monitor, [0.0, 0.0, 1.0, 1.0]),
+        WallpaperMode::Tile => (monitor, [0.0, 0.0, mw / iw, mh / ih]),
+        WallpaperMode::Fill => {
+            // Crop the overflowing dimension symmetrically
+            let scale = (mw / iw).max(mh / ih);
+            let (uw, vh) = (mw / (iw * scale), mh / (ih * scale));
+            let (u0, v0) = ((1.0 - uw) / 2.0, (1.0 - vh) / 2.0);
+            (monitor, [u0, v0, u0 + uw, v0 + vh])
+        }
+        WallpaperMode::Fit => {
+            let scale = (mw / iw).min(mh / ih);
+            let (w, h) = (iw * scale, ih * scale);
+            let dest = Geometry::new(
+                monitor.x + ((mw - w) / 2.0) as i32,
+                monitor.y + ((mh - h) / 2.0) as i32,
+                w as u32,
+                h as u32,
+            );
+            (dest, [0.0, 0.0, 1.0, 1.0])
+        }
+        WallpaperMode::Center => {
+            // Natural size; clip to the monitor if the image is larger
+            let (w, h) = (iw.min(mw), ih.min(mh));
+            let (u0, v0) = ((iw - w) / 2.0 / iw, (ih - h) / 2.0 / ih);
+            let dest = Geometry::new(
+                monitor.x + ((mw - w) / 2.0) as i32,
+                monitor.y + ((mh - h) / 2.0) as i32,
+                w as u32,
+                h as u32,
+            );
+            (dest, [u0, v0, u0 + w / iw, v0 + h / ih])
+        }
+    }
+}
+
+/// An uploaded wallpaper layer
+struct PlacedWallpaper {
+    texture: u32,
+    dest: Geometry,
+    uv: [f32; 4],
+}
+
+/// Wallpaper textures owned by the compositor thread
+#[derive(Default)]
+pub struct WallpaperState {
+    current: Vec<PlacedWallpaper>,
+    /// Wallpaper being faded out
+    previous: Vec<PlacedWallpaper>,
+    fade_start: Option<Instant>,
+    fade: Duration,
+}
+
+impl WallpaperState {
+    /// Upload a new wallpaper and start crossfading to it
+    pub fn set(&mut self, renderer: &Renderer, layers: Vec<WallpaperLayer>, fade: Duration) {
+        // A fade still in progress is cut short
+        Self::release(renderer, std::mem::take(&mut self.previous));
+
+        let mut textures: HashMap<*const Image, u32> = HashMap::new();
+        let current = layers
+            .iter()
+            .map(|layer| {
+                let texture = *textures
+                    .entry(Arc::as_ptr(&layer.image))
+                    .or_insert_with(|| renderer.upload_texture(&layer.image));
+                let (dest, uv) = place(layer.monitor, layer.image.width, layer.image.height, layer.mode);
+                PlacedWallpaper { texture, dest, uv }
+            })
+            .collect();
+
+        self.previous = std::mem::replace(&mut self.current, current);
+        self.fade = fade;
+        self.fade_start = Some(Instant::now());
+    }
+
+    /// Whether a crossfade is running (keeps the compositor rendering)
+    pub fn is_fading(&self) -> bool {
+        self.fade_start.is_some()
+    }
+
+    /// Draw the wallpaper; finishes the crossfade once it has run its course
+    pub fn render(&mut self, renderer: &Renderer, screen_width: f32, screen_height: f32) {
+        let progress = match self.fade_start {
+            Some(start) if !self.fade.is_zero() => (start.elapsed().as_secs_f32() / self.fade.as_secs_f32()).min(1.0),
+            _ => 1.0,
+        };
+
+        if progress < 1.0 {
+            for layer in &self.previous {
+                Self::draw(renderer, layer, 1.0, screen_width, screen_height);
+            }
+        } else if self.fade_start.take().is_some() {
+            Self::release(renderer, std::mem::take(&mut self.previous));
+        }
+        for layer in &self.current {
+            Self::draw(renderer, layer, progress, screen_width, screen_height);
+        }
+    }
+
+    fn draw(renderer: &Renderer, layer: &PlacedWallpaper, opacity: f32, screen_width: f32, screen_height: f32) {
+        renderer.render_texture(
+            layer.texture,
+            (layer.dest.x as f32, layer.dest.y as f32, layer.dest.width as f32, layer.dest.height as f32),
+            layer.uv,
+            (screen_width, screen_height),
+            opacity,
+        );
+    }
+
+    /// Delete the textures of a set of layers (shared textures once)
+    fn release(renderer: &Renderer, layers: Vec<PlacedWallpaper>) {
+        let mut textures: Vec<u32> = layers.iter().map(|l| l.texture).collect();
+        textures.sort_unstable();
+        textures.dedup();
+        for texture in textures {
+            renderer.delete_texture(texture);
+        }
+    }
+}
+
+/// Background color behind (and around) the wallpaper, as in the compositor
+const BACKGROUND: [u8; 3] = [38, 38, 38];
+
+/// The wallpaper as a root window pixmap, for pseudo-transparent clients
+///
+/// Follows the Esetroot convention: each pixmap is created on a connection
+/// of its own that is closed with `RetainPermanent`, and the next wallpaper
+/// kills that connection's resources (`ESETROOT_PMAP_ID`). A client that read
+/// the previous ID just before the switch can still use it until then, and
+/// the wallpaper outlives the WM.
+pub struct RootPixmap {
+    conn: Arc<RustConnection>,
+    root: Window,
+    depth: u8,
+    /// `_XROOTPMAP_ID` and `ESETROOT_PMAP_ID`
+    atoms: [Atom; 2],
+}
+
+impl RootPixmap {
+    pub fn new(conn: Arc<RustConnection>, screen_num: usize) -> Result<Self> {
+        let screen = &conn.setup().roots[screen_num];
+        let (root, depth) = (screen.root, screen.root_depth);
+        let atoms = [
+            crate::wm::atom_cache::intern(conn.as_ref(), "_XROOTPMAP_ID")?,
+            crate::wm::atom_cache::intern(conn.as_ref(), "ESETROOT_PMAP_ID")?,
+        ];
+        Ok(Self { conn, root, depth, atoms })
+    }
+
+    /// Render `layers` into a new root pixmap and publish it, releasing the previous one
+    ///
+    /// Blocking (CPU scaling of the whole screen); call from a worker thread.
+    pub fn publish(&self, layers: &[WallpaperLayer], width: u16, height: u16) -> Result<()> {
+        let data = self.encode(&compose(layers, width as u32, height as u32))?;
+
+        let (owner, _) = x11rb::connect(None).context("Failed to open a connection for the root pixmap")?;
+        let conn = &owner;
+        let pixmap = conn.generate_id()?;
+        conn.create_pixmap(self.depth, pixmap, self.root, width, height)?;
+        let gc = conn.generate_id()?;
+        conn.create_gc(gc, pixmap, &CreateGCAux::new())?;
+
+        // Upload in strips that fit in a single request
+        let stride = width as usize * 4;
+        let rows_per_request = ((conn.maximum_request_bytes() - 64) / stride.max(1)).max(1);
+        for (i, strip) in data.chunks(stride * rows_per_request).enumerate() {
+            conn.put_image(
+                ImageFormat::Z_PIXMAP,
+                pixmap,
+                gc,
+                width,
+                (strip.len() / stride) as u16,
+                0,
+                (i * rows_per_request) as i16,
+                0,
+                self.depth,
+                strip,
+            )?;
+        }
+        conn.free_gc(gc)?;
+        conn.set_close_down_mode(CloseDown::RETAIN_PERMANENT)?;
+        // Round trip so everything is processed before the connection closes
+        conn.get_input_focus()?.reply()?;
+        drop(owner);
+
+        let conn = self.conn.as_ref();
+        let [xrootpmap, esetroot] = self.atoms;
+        // Only a pixmap still shown was set by someone following the convention
+        let shown = self.pixmap_property(xrootpmap)?;
+        let previous = self.pixmap_property(esetroot)?.filter(|&previous| Some(previous) == shown);
+        for atom in self.atoms {
+            conn.change_property32(PropMode::REPLACE, self.root, atom, AtomEnum::PIXMAP, &[pixmap])?;
+        }
+        if let Some(previous) = previous {
+            debug!("Releasing previous root pixmap 0x{:x}", previous);
+            conn.kill_client(previous)?;
+        }
+        conn.flush()?;
+        debug!("Published root pixmap 0x{:x} ({}x{})", pixmap, width, height);
+        Ok(())
+    }
+
+    /// Pixmap named by a root window property, before it is replaced
+    fn pixmap_property(&self, atom: Atom) -> Result<Option<Pixmap>> {
+        let reply = self.conn.get_property(false, self.root, atom, AtomEnum::PIXMAP, 0, 1)?.reply()?;
+        Ok(reply.value32().and_then(|mut values| values.next()).filter(|&pixmap| pixmap != 0))
+    }
+
+    /// Convert RGB pixels to the root visual's 32 bits-per-pixel Z format
+    fn encode(&self, rgb: &[u8]) -> Result<Vec<u8>> {
+        let setup = self.conn.setup();
+        let bits_per_pixel = setup
+            .pixmap_formats
+            .iter()
+            .find(|f| f.depth == self.depth)
+            .map(|f| f.bits_per_pixel);
+        if bits_per_pixel != Some(32) {
+            anyhow::bail!("unsupported root depth {} ({:?} bits per pixel)", self.depth, bits_per_pixel);
+        }
+        let visual = setup
+            .roots
+            .iter()
+            .find(|s| s.root == self.root)
+            .and_then(|s| s.allowed_depths.iter().find(|d| d.depth == self.depth))
+            .and_then(|d| d.visuals.first())
+            .context("no visual for the root depth")?;
+
+        let channel = |value: u8, mask: u32| ((value as u32) << mask.trailing_zeros()) & mask;
+        let big_endian = setup.image_byte_order == ImageOrder::MSB_FIRST;
+        Ok(rgb
+            .chunks_exact(3)
+            .flat_map(|p| {
+                let pixel = channel(p[0], visual.red_mask) | channel(p[1], visual.green_mask) | channel(p[2], visual.blue_mask);
+                if big_endian { pixel.to_be_bytes() } else { pixel.to_le_bytes() }
+            })
+            .collect())
+    }
+}
+
+/// Render layers into a `width` x `height` RGB buffer (nearest-neighbour sampling)
+fn compose(layers: &[WallpaperLayer], width: u32, height: u32) -> Vec<u8> {
+    let mut rgb = BACKGROUND.repeat((width * height) as usize);
+    for layer in layers {
+        let image = &layer.image;
+        let (dest, [u0, v0, u1, v1]) = place(layer.monitor, image.width, image.height, layer.mode);
+        let x_range = dest.x.max(0)..(dest.x + dest.width as i32).min(width as i32);
+        let y_range = dest.y.max(0)..(dest.y + dest.height as i32).min(height as i32);
+
+        for y in y_range {
+            let v = v0 + (y - dest.y) as f32 / dest.height as f32 * (v1 - v0);
+            let ty = (v * image.height as f32) as u32 % image.height.max(1);
+            for x in x_range.clone() {
+                let u = u0 + (x - dest.x) as f32 / dest.width as f32 * (u1 - u0);
+                let tx = (u * image.width as f32) as u32 % image.width.max(1);
+                let texel = &image.pixels[((ty * image.width + tx) * 4) as usize..][..4];
+                let out = &mut rgb[((y as u32 * width + x as u32) * 3) as usize..][..3];
+                let alpha = texel[3] as u32;
+                for (out, &value) in out.iter_mut().zip(&texel[..3]) {
+                    *out = ((value as u32 * alpha + *out as u32 * (255 - alpha)) / 255) as u8;
+                }
+            }
+        }
+    }
+    rgb
+}
@@ -6,6 +6,7 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use tracing::{debug, info, warn};
 
@@ -17,6 +18,46 @@ pub struct Config {
     pub panel: PanelConfig,
     pub keybindings: KeybindingsConfig,
     pub compositor: CompositorConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub ipc: IpcConfig,
+    #[serde(default)]
+    pub wallpaper: WallpaperConfig,
+    #[serde(default)]
+    pub root_menu: RootMenuConfig,
+    /// On-screen display for volume, brightness, layout and workspace changes
+    #[serde(default)]
+    pub osd: OsdConfig,
+    /// Desktop notification server, Do Not Disturb and history
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+    /// Per-window audio indicators
+    #[serde(default)]
+    pub audio: AudioConfig,
+    /// Screen blanking and monitor power saving
+    #[serde(default)]
+    pub dpms: DpmsConfig,
+    /// Selection handling
+    #[serde(default)]
+    pub clipboard: ClipboardConfig,
+    /// User idle reporting to the shell
+    #[serde(default)]
+    pub idle: IdleConfig,
+    /// Extra environment variables for launched applications (over the
+    /// session's DISPLAY, XDG_CURRENT_DESKTOP, locale, ...)
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+    /// Per-workspace settings, keyed by workspace number ("0", "1", ...)
+    #[serde(default)]
+    pub workspace_profiles: HashMap<String, WorkspaceProfile>,
+    /// Lua script run on window manager events
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    /// Session components started and restarted by the window manager
+    /// (`[[components]]` tables, see the `supervisor` module)
+    #[serde(default)]
+    pub components: Vec<ComponentConfig>,
 }
 
 impl Default for Config {
@@ -27,6 +68,20 @@ impl Default for Config {
             panel: PanelConfig::default(),
             keybindings: KeybindingsConfig::default(),
             compositor: CompositorConfig::default(),
+            metrics: MetricsConfig::default(),
+            ipc: IpcConfig::default(),
+            wallpaper: WallpaperConfig::default(),
+            root_menu: RootMenuConfig::default(),
+            osd: OsdConfig::default(),
+            notifications: NotificationsConfig::default(),
+            audio: AudioConfig::default(),
+            dpms: DpmsConfig::default(),
+            clipboard: ClipboardConfig::default(),
+            idle: IdleConfig::default(),
+            environment: HashMap::new(),
+            workspace_profiles: HashMap::new(),
+            hooks: HooksConfig::default(),
+            components: Vec::new(),
         }
     }
 }
@@ -90,16 +145,51 @@ impl Config {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InputConfig {
     pub mouse: MouseConfig,
+    /// Keyboard accessibility (XKB AccessX)
+    #[serde(default)]
+    pub accessibility: AccessibilityConfig,
+    /// XKB options and key remapping
+    #[serde(default)]
+    pub keyboard: KeyboardConfig,
 }
 
 impl Default for InputConfig {
     fn default() -> Self {
         Self {
             mouse: MouseConfig::default(),
+            accessibility: AccessibilityConfig::default(),
+            keyboard: KeyboardConfig::default(),
         }
     }
 }
 
+/// Keyboard layout tweaks, applied at startup and when a keyboard is plugged in
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyboardConfig {
+    /// XKB options, e.g. "ctrl:nocaps" or "compose:ralt" (empty: keep the server's)
+    pub options: Vec<String>,
+    /// Keys to remap, e.g. `caps_lock = "escape"` (key names like "control_l",
+    /// "super_r", "backspace" or a single character)
+    pub remap: HashMap<String, String>,
+}
+
+/// Keyboard accessibility features; unset ones keep the X server's state
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilityConfig {
+    /// Modifiers stay pressed until the next key
+    pub sticky_keys: Option<bool>,
+    /// Keys must be held for `slow_keys_delay_ms` before they count
+    pub slow_keys: Option<bool>,
+    pub slow_keys_delay_ms: Option<u16>,
+    /// A key pressed again within `bounce_keys_delay_ms` of its release is ignored
+    pub bounce_keys: Option<bool>,
+    pub bounce_keys_delay_ms: Option<u16>,
+    /// The numeric keypad moves the pointer
+    pub mouse_keys: Option<bool>,
+}
+
 /// Mouse configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MouseConfig {
@@ -158,6 +248,192 @@ pub struct WindowDecorationConfig {
     pub button_size: u16,
     /// Button padding in pixels
     pub button_padding: u16,
+    /// Titlebar buttons left|right of the title, e.g. "menu,sticky|minimize,maximize,close"
+    ///
+    /// Buttons: menu, sticky, above, shade, minimize, maximize, close.
+    #[serde(default = "default_button_layout")]
+    pub buttons: String,
+    /// Who draws titlebars and buttons (borders stay X borders)
+    #[serde(default)]
+    pub render: DecorationRender,
+}
+
+/// On-screen display
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OsdConfig {
+    pub enabled: bool,
+    /// Who draws it
+    pub render: OsdRender,
+    /// Where on the focused monitor
+    pub position: OsdPosition,
+    /// How long it stays up after the last change (ms)
+    pub timeout_ms: u64,
+    /// Show the workspace on workspace switches
+    pub workspace: bool,
+    /// Show the layout name when the keyboard layout changes
+    pub keyboard_layout: bool,
+}
+
+impl Default for OsdConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            render: OsdRender::default(),
+            position: OsdPosition::default(),
+            timeout_ms: 1500,
+            workspace: true,
+            keyboard_layout: true,
+        }
+    }
+}
+
+/// Desktop notifications
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    /// Serve org.freedesktop.Notifications and hand notifications to the
+    /// shell (off: leave them to another notification daemon)
+    pub server: bool,
+    /// Notifications kept in the history
+    pub history_size: usize,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self { server: true, history_size: 200 }
+    }
+}
+
+/// Per-window audio state
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Watch the sound server's streams with `pactl` and report which
+    /// windows play sound (`WindowAudioState`)
+    pub window_indicators: bool,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self { window_indicators: true }
+    }
+}
+
+/// Idle timeouts of the screen (seconds, 0: never); the X server applies
+/// them and wakes the monitors on input
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DpmsConfig {
+    /// Set the timeouts (off: leave them to xset or another tool)
+    pub enabled: bool,
+    /// Blank the screen
+    pub blank_timeout: u32,
+    /// Put the monitors into standby, suspend and off
+    pub standby_timeout: u32,
+    pub suspend_timeout: u32,
+    pub off_timeout: u32,
+    /// Serve org.freedesktop.ScreenSaver so applications (video players)
+    /// can keep the screen on
+    pub inhibit_service: bool,
+}
+
+impl Default for DpmsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            blank_timeout: 600,
+            standby_timeout: 0,
+            suspend_timeout: 0,
+            off_timeout: 900,
+            inhibit_service: true,
+        }
+    }
+}
+
+/// Selection handling
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipboardConfig {
+    /// Copy text between PRIMARY (middle-click paste) and CLIPBOARD (Ctrl+V)
+    pub sync: SelectionSync,
+    /// Keep the clipboard of exiting applications (CLIPBOARD_MANAGER),
+    /// unless another clipboard manager runs
+    pub manager: bool,
+}
+
+impl Default for ClipboardConfig {
+    fn default() -> Self {
+        Self {
+            sync: SelectionSync::default(),
+            manager: true,
+        }
+    }
+}
+
+/// User idle reporting (`UserIdleStateChanged` events)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdleConfig {
+    /// Seconds without input after which the shell is told the user is
+    /// idle, one event per threshold (empty: never)
+    pub thresholds: Vec<u32>,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self { thresholds: vec![60, 300] }
+    }
+}
+
+/// Which way selected/copied text is synchronized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionSync {
+    #[default]
+    Off,
+    /// Selecting text also copies it
+    PrimaryToClipboard,
+    /// Copying text also selects it for middle-click paste
+    ClipboardToPrimary,
+    Both,
+}
+
+/// Who draws the on-screen display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OsdRender {
+    /// The compositor, with its built-in font
+    #[default]
+    Compositor,
+    /// The shell, from `WmEvent::Osd`
+    Shell,
+}
+
+/// Vertical position of the on-screen display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OsdPosition {
+    Top,
+    Center,
+    #[default]
+    Bottom,
+}
+
+/// Who draws window decorations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecorationRender {
+    /// Colored X subwindows
+    #[default]
+    X11,
+    /// The GL compositor, over input-only titlebar and button windows
+    /// (anti-aliased buttons, no subwindow painting)
+    Compositor,
+}
+
+fn default_button_layout() -> String {
+    crate::wm::decorations::DEFAULT_BUTTON_LAYOUT.to_string()
 }
 
 impl Default for WindowDecorationConfig {
@@ -167,6 +443,8 @@ impl Default for WindowDecorationConfig {
             border_width: 2,
             button_size: 16,
             button_padding: 8,
+            buttons: default_button_layout(),
+            render: DecorationRender::default(),
         }
     }
 }
@@ -204,6 +482,13 @@ pub struct WindowColors {
     pub maximize_button: u32,
     /// Minimize button color (hex: 0xRRGGBB)
     pub minimize_button: u32,
+    /// Menu, sticky, above and shade button color (hex: 0xRRGGBB)
+    #[serde(default = "default_button_color")]
+    pub button: u32,
+}
+
+fn default_button_color() -> u32 {
+    0x81a1c1 // Frost Light Blue
 }
 
 impl Default for WindowColors {
@@ -216,6 +501,7 @@ impl Default for WindowColors {
             close_button: 0xbf616a,    // Aurora Red
             maximize_button: 0xa3be8c, // Aurora Green
             minimize_button: 0xebcb8b, // Aurora Yellow
+            button: default_button_color(),
         }
     }
 }
@@ -225,10 +511,180 @@ impl Default for WindowColors {
 pub struct WindowBehaviorConfig {
     /// Focus mode: "click_to_focus", "focus_follows_mouse", "sloppy_focus"
     pub focus_mode: String,
-    /// Raise window when focused
+    /// Raise a window focused by the pointer (focus follows mouse)
     pub raise_on_focus: bool,
+    /// How long the pointer has to stay in a window before it is raised (ms, 0: at once)
+    #[serde(default)]
+    pub raise_delay_ms: u64,
+    /// Clicks that raise a window: "anywhere" or "titlebar"
+    #[serde(default)]
+    pub raise_on_click: crate::wm::focus::RaiseOnClick,
     /// Window gaps (for tiling, in pixels)
     pub window_gaps: u32,
+    /// Margin between maximized or tiled windows and the work area edges (pixels)
+    #[serde(default)]
+    pub outer_gaps: u32,
+    /// Alt+Tab order: "most_recently_used", "stacking" or "workspace"
+    #[serde(default)]
+    pub cycle_order: crate::wm::cycle::CycleOrder,
+    /// Include minimized windows when cycling
+    #[serde(default)]
+    pub cycle_include_minimized: bool,
+    /// Show one Alt+Tab entry per application window group, raising the whole group
+    #[serde(default)]
+    pub cycle_group_windows: bool,
+    /// Refresh rate of the live Alt+Tab thumbnails (0: none, the shell shows icons)
+    #[serde(default = "default_cycle_preview_fps")]
+    pub cycle_preview_fps: u32,
+    /// Longest side of an Alt+Tab thumbnail (pixels)
+    #[serde(default = "default_cycle_preview_size")]
+    pub cycle_preview_size: u32,
+    /// Minimize and restore all windows of an application group together
+    #[serde(default)]
+    pub minimize_groups: bool,
+    /// Number of workspaces
+    #[serde(default = "default_workspace_count")]
+    pub workspace_count: u32,
+    /// Window focused after a workspace switch: "last_focused", "topmost",
+    /// "under_pointer" or "none"
+    #[serde(default)]
+    pub workspace_focus: crate::wm::workspace::WorkspaceFocus,
+    /// Flip to the adjacent workspace when a dragged window is held at the left/right screen edge
+    #[serde(default)]
+    pub edge_flip: bool,
+    /// How long the window has to be held at the edge (milliseconds)
+    #[serde(default = "default_edge_flip_delay_ms")]
+    pub edge_flip_delay_ms: u64,
+    /// Mouse wheel over a titlebar: "shade", "opacity" or "none"
+    #[serde(default)]
+    pub titlebar_wheel: TitlebarWheelAction,
+    /// Modifiers the titlebar wheel action needs, e.g. "alt" or "ctrl+shift" (empty: none)
+    #[serde(default)]
+    pub titlebar_wheel_modifiers: String,
+    /// Switch workspaces with the mouse wheel over the desktop
+    #[serde(default = "default_desktop_wheel")]
+    pub desktop_wheel: bool,
+    /// Modifiers desktop wheel switching needs (empty: none)
+    #[serde(default)]
+    pub desktop_wheel_modifiers: String,
+    /// Modifiers for moving (left button) and resizing (right button) a
+    /// window by dragging anywhere inside it (empty: disabled)
+    #[serde(default = "default_drag_modifiers")]
+    pub drag_modifiers: String,
+    /// WM_CLASS names (instance or class, case-insensitive) that keep their
+    /// modifier clicks, e.g. games
+    #[serde(default)]
+    pub drag_exclude: Vec<String>,
+    /// Keep the pointer on the monitor of a focused fullscreen window that
+    /// asks for it (`_AREA_CONFINE_POINTER` or `_NET_WM_BYPASS_COMPOSITOR`)
+    #[serde(default = "default_confine_pointer")]
+    pub confine_pointer: bool,
+    /// WM_CLASS names (instance or class, case-insensitive) whose fullscreen
+    /// windows always keep the pointer on their monitor
+    #[serde(default)]
+    pub confine_pointer_classes: Vec<String>,
+    /// Where new windows without a position of their own go: "smart" (where
+    /// they overlap other windows least), "center" or "cascade" (down and
+    /// right of the previous one, per workspace and monitor)
+    #[serde(default)]
+    pub placement: crate::wm::placement::PlacementPolicy,
+    /// Largest share of the work area (percent) a window placed by "smart"
+    /// may cover; bigger windows are centered on the monitor
+    #[serde(default = "default_smart_placement_max_size")]
+    pub smart_placement_max_size: u32,
+    /// WM_CLASS names (instance or class, case-insensitive) whose maximized
+    /// or tiled windows may still move and resize themselves
+    #[serde(default)]
+    pub configure_exempt: Vec<String>,
+    /// Titlebar width (pixels) that always stays on a monitor, outside panels
+    #[serde(default = "default_titlebar_min_visible")]
+    pub titlebar_min_visible: u32,
+    /// How long ending the session waits for windows to close before asking the user (ms)
+    #[serde(default = "default_end_session_timeout_ms")]
+    pub end_session_timeout_ms: u64,
+    /// How long a window may leave a _NET_WM_PING unanswered before it is
+    /// grayed out as unresponsive (ms, 0: never ping)
+    #[serde(default = "default_ping_timeout_ms")]
+    pub ping_timeout_ms: u64,
+    /// Windows moved off an unplugged monitor go back if it returns within
+    /// this time (ms, 0: they stay)
+    #[serde(default = "default_monitor_return_grace_ms")]
+    pub monitor_return_grace_ms: u64,
+    /// Only windows started with an activation token (or belonging to the
+    /// focused application) may take focus; others demand attention instead
+    #[serde(default = "default_focus_stealing_prevention")]
+    pub focus_stealing_prevention: bool,
+    /// How long an activation token stays valid (ms)
+    #[serde(default = "default_activation_token_timeout_ms")]
+    pub activation_token_timeout_ms: u64,
+}
+
+/// What the mouse wheel does over a titlebar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TitlebarWheelAction {
+    /// Up unshades, down shades
+    #[default]
+    Shade,
+    /// Up makes the window more opaque, down more transparent
+    Opacity,
+    None,
+}
+
+fn default_workspace_count() -> u32 {
+    4
+}
+
+fn default_cycle_preview_fps() -> u32 {
+    5
+}
+
+fn default_cycle_preview_size() -> u32 {
+    256
+}
+
+fn default_confine_pointer() -> bool {
+    true
+}
+
+fn default_edge_flip_delay_ms() -> u64 {
+    500
+}
+
+fn default_desktop_wheel() -> bool {
+    true
+}
+
+fn default_smart_placement_max_size() -> u32 {
+    crate::wm::placement::DEFAULT_SMART_MAX_SIZE
+}
+
+fn default_titlebar_min_visible() -> u32 {
+    crate::wm::placement::DEFAULT_MIN_VISIBLE
+}
+
+fn default_end_session_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_ping_timeout_ms() -> u64 {
+    3000
+}
+
+fn default_monitor_return_grace_ms() -> u64 {
+    60_000
+}
+
+fn default_focus_stealing_prevention() -> bool {
+    true
+}
+
+fn default_activation_token_timeout_ms() -> u64 {
+    30_000
+}
+
+fn default_drag_modifiers() -> String {
+    "alt".to_string()
 }
 
 impl Default for WindowBehaviorConfig {
@@ -236,7 +692,37 @@ impl Default for WindowBehaviorConfig {
         Self {
             focus_mode: "click_to_focus".to_string(),
             raise_on_focus: true,
+            raise_delay_ms: 0,
+            raise_on_click: crate::wm::focus::RaiseOnClick::default(),
             window_gaps: 0,
+            outer_gaps: 0,
+            cycle_order: crate::wm::cycle::CycleOrder::MostRecentlyUsed,
+            cycle_include_minimized: false,
+            cycle_group_windows: false,
+            cycle_preview_fps: default_cycle_preview_fps(),
+            cycle_preview_size: default_cycle_preview_size(),
+            minimize_groups: false,
+            workspace_count: default_workspace_count(),
+            workspace_focus: crate::wm::workspace::WorkspaceFocus::default(),
+            edge_flip: false,
+            edge_flip_delay_ms: default_edge_flip_delay_ms(),
+            titlebar_wheel: TitlebarWheelAction::default(),
+            placement: crate::wm::placement::PlacementPolicy::default(),
+            smart_placement_max_size: default_smart_placement_max_size(),
+            titlebar_wheel_modifiers: String::new(),
+            desktop_wheel: default_desktop_wheel(),
+            desktop_wheel_modifiers: String::new(),
+            drag_modifiers: default_drag_modifiers(),
+            drag_exclude: Vec::new(),
+            confine_pointer: default_confine_pointer(),
+            confine_pointer_classes: Vec::new(),
+            configure_exempt: Vec::new(),
+            titlebar_min_visible: default_titlebar_min_visible(),
+            end_session_timeout_ms: default_end_session_timeout_ms(),
+            ping_timeout_ms: default_ping_timeout_ms(),
+            monitor_return_grace_ms: default_monitor_return_grace_ms(),
+            focus_stealing_prevention: default_focus_stealing_prevention(),
+            activation_token_timeout_ms: default_activation_token_timeout_ms(),
         }
     }
 }
@@ -252,6 +738,41 @@ pub struct PanelConfig {
     pub opacity: f32,
     /// Panel background color: RGB values 0.0-1.0
     pub color: [f32; 3],
+    /// Pushing the pointer against an edge where the shell has an
+    /// auto-hidden panel reveals it (`[panel.edge_pressure.bottom]` etc.)
+    #[serde(default)]
+    pub edge_pressure: EdgePressureConfig,
+}
+
+/// Edge pressure settings of each monitor edge
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EdgePressureConfig {
+    pub left: EdgePressure,
+    pub right: EdgePressure,
+    pub top: EdgePressure,
+    pub bottom: EdgePressure,
+}
+
+/// When pushing against one edge reveals its panel
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EdgePressure {
+    pub enabled: bool,
+    /// Pointer motion into the edge needed (px)...
+    pub threshold: u32,
+    /// ...within this time (ms)
+    pub timeout_ms: u64,
+}
+
+impl Default for EdgePressure {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 100,
+            timeout_ms: 1000,
+        }
+    }
 }
 
 impl Default for PanelConfig {
@@ -261,6 +782,7 @@ impl Default for PanelConfig {
             position: "top".to_string(),
             opacity: 0.9,
             color: [0.2, 0.2, 0.2], // Dark gray
+            edge_pressure: EdgePressureConfig::default(),
         }
     }
 }
@@ -272,6 +794,38 @@ pub struct KeybindingsConfig {
     pub launcher_key: String,
     /// Command to run when launcher key is pressed
     pub launcher_command: String,
+    /// Keyboard window management mode (Super+W)
+    #[serde(default)]
+    pub window_mode: WindowModeConfig,
+    /// Multi-key bindings, in addition to the built-in Super+G/Super+S, then 1-9
+    #[serde(default)]
+    pub chords: Vec<ChordConfig>,
+    /// How long the next key of a chord is waited for (ms)
+    #[serde(default = "default_chord_timeout_ms")]
+    pub chord_timeout_ms: u64,
+    /// Show the ways a pending chord can be completed
+    #[serde(default = "default_chord_hints")]
+    pub chord_hints: bool,
+}
+
+fn default_chord_timeout_ms() -> u64 {
+    1500
+}
+
+fn default_chord_hints() -> bool {
+    true
+}
+
+/// A multi-key binding (`[[keybindings.chords]]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChordConfig {
+    /// Keys pressed in a row, e.g. "super+a, 1"
+    pub keys: String,
+    /// "switch_workspace <n>", "move_to_workspace <n>" (0-based),
+    /// "move_to_monitor left|right|up|down", "tile_left", "tile_right",
+    /// "window_menu", "window_mode", "toggle_shortcut_inhibit",
+    /// "toggle_pointer_confinement" or "toggle_do_not_disturb"
+    pub action: String,
 }
 
 impl Default for KeybindingsConfig {
@@ -279,6 +833,29 @@ impl Default for KeybindingsConfig {
         Self {
             launcher_key: "Super".to_string(),
             launcher_command: "navigator".to_string(),
+            window_mode: WindowModeConfig::default(),
+            chords: Vec::new(),
+            chord_timeout_ms: default_chord_timeout_ms(),
+            chord_hints: default_chord_hints(),
+        }
+    }
+}
+
+/// Keyboard window management mode
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowModeConfig {
+    /// Pixels a window moves per key press
+    pub move_step: u32,
+    /// Pixels a window grows or shrinks per key press
+    pub resize_step: u32,
+}
+
+impl Default for WindowModeConfig {
+    fn default() -> Self {
+        Self {
+            move_step: 32,
+            resize_step: 32,
         }
     }
 }
@@ -293,6 +870,34 @@ pub struct CompositorConfig {
     /// Unredirect fullscreen windows for performance
     pub unredirect_fullscreen: bool,
     pub transparency: TransparencyConfig,
+    /// Experimental fractional scaling per RandR output name (e.g. `"eDP-1" = 1.5`)
+    ///
+    /// Windows render at their native size and the output's CRTC upscales them;
+    /// the previous layout comes back once this is emptied or area exits.
+    #[serde(default)]
+    pub output_scale: HashMap<String, f64>,
+    /// Frame rate limits (idle and battery throttling)
+    #[serde(default)]
+    pub frame_rate: FrameRateConfig,
+    /// Drawing effects, applied in order (`[[compositor.effects]]` tables)
+    #[serde(default)]
+    pub effects: Vec<EffectConfig>,
+    /// Override-redirect menus and tooltips
+    #[serde(default)]
+    pub popups: PopupConfig,
+    /// Dimming of windows without focus
+    #[serde(default)]
+    pub dim_inactive: DimInactiveConfig,
+    /// Per-application effect exclusions and bypass (`[[compositor.rules]]`)
+    #[serde(default)]
+    pub rules: Vec<CompositorRule>,
+    /// Keep the last frame of closed windows for the shell this long (ms, 0: off)
+    #[serde(default)]
+    pub close_snapshot_ms: u64,
+    /// Render to a 30-bit (10 bits per channel) framebuffer when the
+    /// hardware has one; needs the X server at depth 30 and a restart
+    #[serde(default)]
+    pub deep_color: bool,
 }
 
 impl Default for CompositorConfig {
@@ -302,6 +907,180 @@ impl Default for CompositorConfig {
             tear_free: true,
             unredirect_fullscreen: false,
             transparency: TransparencyConfig::default(),
+            output_scale: HashMap::new(),
+            frame_rate: FrameRateConfig::default(),
+            effects: Vec::new(),
+            popups: PopupConfig::default(),
+            dim_inactive: DimInactiveConfig::default(),
+            rules: Vec::new(),
+            close_snapshot_ms: 0,
+            deep_color: false,
+        }
+    }
+}
+
+/// A compositor effect and its settings
+///
+/// In TOML: `[[compositor.effects]]` with `name = "shadow"` or `name = "fade"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum EffectConfig {
+    /// Soft drop shadow under non-fullscreen windows
+    Shadow {
+        /// Blur radius (px)
+        #[serde(default = "default_shadow_radius")]
+        radius: f32,
+        /// Offset from the window (px)
+        #[serde(default)]
+        offset_x: f32,
+        #[serde(default = "default_shadow_offset_y")]
+        offset_y: f32,
+        /// Darkness at the window edge (0.0-1.0)
+        #[serde(default = "default_shadow_opacity")]
+        opacity: f32,
+    },
+    /// Fade windows in when they are shown
+    Fade {
+        #[serde(default = "default_fade_ms")]
+        duration_ms: u64,
+    },
+    /// Briefly highlight a window focused with Alt+Tab or run-or-raise
+    FocusFlash {
+        #[serde(default = "default_flash_ms")]
+        duration_ms: u64,
+        #[serde(default)]
+        style: FlashStyle,
+        /// Glow color (hex: 0xRRGGBB)
+        #[serde(default = "default_flash_color")]
+        color: u32,
+        /// Glow width (px)
+        #[serde(default = "default_flash_radius")]
+        radius: f32,
+    },
+}
+
+/// How `EffectConfig::FocusFlash` highlights a window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlashStyle {
+    /// A colored glow around the window that fades out
+    #[default]
+    Glow,
+    /// The window brightens and returns to normal
+    Pulse,
+}
+
+fn default_shadow_radius() -> f32 {
+    16.0
+}
+
+fn default_shadow_offset_y() -> f32 {
+    4.0
+}
+
+fn default_shadow_opacity() -> f32 {
+    0.4
+}
+
+fn default_fade_ms() -> u64 {
+    150
+}
+
+fn default_flash_ms() -> u64 {
+    400
+}
+
+fn default_flash_color() -> u32 {
+    0x88c0d0 // Frost Light Cyan
+}
+
+fn default_flash_radius() -> f32 {
+    12.0
+}
+
+/// Compositor frame pacing
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrameRateConfig {
+    /// Frame rate cap (0: render as fast as damage arrives)
+    pub max_fps: u32,
+    /// Cap while UPower reports the system running on battery (0: no extra cap)
+    pub battery_max_fps: u32,
+    /// Time without damage, cursor motion or animations before going idle (ms)
+    pub idle_after_ms: u64,
+    /// Redraw rate while idle (0: only redraw when something is damaged)
+    pub idle_fps: u32,
+}
+
+impl Default for FrameRateConfig {
+    fn default() -> Self {
+        Self {
+            max_fps: 0,
+            battery_max_fps: 30,
+            idle_after_ms: 2000,
+            idle_fps: 1,
+        }
+    }
+}
+
+/// Override-redirect popups (menus, tooltips)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PopupConfig {
+    /// Let X draw new popups directly, skipping texture creation (faster menus on slow GPUs)
+    pub direct: bool,
+    /// Directly drawn popups still open after this are composited (ms)
+    pub promote_after_ms: u64,
+}
+
+impl Default for PopupConfig {
+    fn default() -> Self {
+        Self {
+            direct: false,
+            promote_after_ms: 500,
+        }
+    }
+}
+
+/// Compositor treatment of the windows of an application
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompositorRule {
+    /// WM_CLASS instance or class, case-insensitive; `*` matches any text (e.g. "*.exe")
+    pub class: String,
+    /// Draw no shadow under the window
+    pub no_shadow: bool,
+    /// Skip fades and focus flashes
+    pub no_animations: bool,
+    /// Draw no blur behind the window
+    pub no_blur: bool,
+    /// Let X draw the window directly, as with _NET_WM_BYPASS_COMPOSITOR
+    pub unredirect: bool,
+}
+
+/// Dimming of windows without focus
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DimInactiveConfig {
+    pub enabled: bool,
+    /// How much darker inactive windows get (0.0-1.0)
+    pub amount: f32,
+    /// Never dim fullscreen windows
+    pub exclude_fullscreen: bool,
+    /// Never dim always-on-top windows
+    pub exclude_above: bool,
+    /// WM_CLASS names (instance or class, case-insensitive) never dimmed
+    pub exclude_classes: Vec<String>,
+}
+
+impl Default for DimInactiveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            amount: 0.2,
+            exclude_fullscreen: true,
+            exclude_above: true,
+            exclude_classes: Vec::new(),
         }
     }
 }
@@ -324,7 +1103,183 @@ impl Default for TransparencyConfig {
     }
 }
 
+/// Performance metrics configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricsConfig {
+    /// Address for a Prometheus text endpoint (e.g. "127.0.0.1:9464"); disabled if unset
+    pub prometheus_listen: Option<String>,
+}
+
+/// IPC socket access control
+///
+/// Only the session user (and `allowed_uids`) may connect. With a `token`,
+/// clients are untrusted until they present it in their `Hello` and may
+/// only run `untrusted_commands` until then.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IpcConfig {
+    /// Other users allowed to connect (by uid)
+    pub allowed_uids: Vec<u32>,
+    /// Secret a client must present to run every command (`AREA_IPC_TOKEN` for area-msg); unset trusts all clients
+    pub token: Option<String>,
+    /// Commands untrusted clients, and D-Bus callers while a token is set, may run (snake_case names, e.g. "get_windows")
+    pub untrusted_commands: Vec<String>,
+}
+
+impl Default for IpcConfig {
+    fn default() -> Self {
+        Self {
+            allowed_uids: Vec::new(),
+            token: None,
+            untrusted_commands: ["hello", "subscribe", "subscribe_pointer", "get_windows", "get_window_info", "find_windows", "get_metrics"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+/// Desktop wallpaper configuration
+///
+/// Overrides are looked up workspace first, then monitor, then the default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WallpaperConfig {
+    /// Default image (PNG, JPEG, WebP or binary PPM); plain background color if unset
+    pub path: Option<String>,
+    /// Default scaling mode: "fill", "fit", "stretch", "center" or "tile"
+    pub mode: area_ipc::WallpaperMode,
+    /// Crossfade duration when the wallpaper changes (0 to switch instantly)
+    pub crossfade_ms: u64,
+    /// Per-monitor overrides, keyed by RandR output name (e.g. "HDMI-1")
+    pub monitors: HashMap<String, WallpaperSpec>,
+    /// Per-workspace overrides, keyed by workspace number ("0", "1", ...)
+    pub workspaces: HashMap<String, WallpaperSpec>,
+}
+
+impl Default for WallpaperConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            mode: area_ipc::WallpaperMode::Fill,
+            crossfade_ms: 300,
+            monitors: HashMap::new(),
+            workspaces: HashMap::new(),
+        }
+    }
+}
+
+/// A wallpaper override
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WallpaperSpec {
+    pub path: String,
+    /// Scaling mode; the default mode if unset
+    #[serde(default)]
+    pub mode: Option<area_ipc::WallpaperMode>,
+}
+
+/// Settings that apply while a workspace is current; unset fields use the global value
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkspaceProfile {
+    /// Name reported to the shell (`WorkspaceProfileChanged`)
+    pub name: Option<String>,
+    /// Wallpaper of every monitor (an override set with `SetWallpaper` wins)
+    pub wallpaper: Option<WallpaperSpec>,
+    /// Overrides `window_manager.behavior.window_gaps`
+    pub window_gaps: Option<u32>,
+    /// Overrides `window_manager.behavior.outer_gaps`
+    pub outer_gaps: Option<u32>,
+    /// Overrides `window_manager.behavior.focus_mode`
+    pub focus_mode: Option<String>,
+    /// Overrides `window_manager.behavior.workspace_focus`
+    pub workspace_focus: Option<crate::wm::workspace::WorkspaceFocus>,
+}
+
+/// Desktop right-click menu, shown by the shell
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RootMenuConfig {
+    /// Show the menu on a right-click on the root window
+    pub enabled: bool,
+    /// Entries in display order
+    pub entries: Vec<RootMenuEntry>,
+}
+
+impl Default for RootMenuConfig {
+    fn default() -> Self {
+        let entry = |label: &str, action| RootMenuEntry { label: label.to_string(), action };
+        Self {
+            enabled: true,
+            entries: vec![
+                entry("Terminal", RootMenuAction::Exec("xfce4-terminal".to_string())),
+                entry("", RootMenuAction::Separator),
+                entry("Reload Configuration", RootMenuAction::Reload),
+                entry("Exit", RootMenuAction::Exit),
+            ],
+        }
+    }
+}
+
+/// Lua hook script (see the `hooks` module)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HooksConfig {
+    /// Path of the script (`~/` is expanded); none runs no hooks
+    #[serde(default)]
+    pub script: Option<String>,
+}
 
+/// A program kept running for the session (see the `supervisor` module)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComponentConfig {
+    /// Name reported in `ComponentStatusChanged`
+    pub name: String,
+    /// Command line run through `sh -c`
+    pub exec: String,
+    /// Restart it when it crashes
+    #[serde(default = "default_component_restart")]
+    pub restart: bool,
+}
 
+fn default_component_restart() -> bool {
+    true
+}
+
+/// Events the hook script can handle (`hooks.<name>` in Lua)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    /// A window was managed; gets `window` (as `GetWindowInfo`)
+    WindowOpen,
+    /// The focused window changed; gets `window` (or null) and `previous`
+    FocusChange,
+    /// The current workspace changed; gets `workspace` and `previous`
+    WorkspaceSwitch,
+}
 
+/// A root menu entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootMenuEntry {
+    #[serde(default)]
+    pub label: String,
+    pub action: RootMenuAction,
+}
 
+/// What a root menu entry does
+///
+/// In TOML: `action = { exec = "firefox" }`, `action = { workspace = 1 }`,
+/// `action = "reload"`, `action = "exit"` or `action = "separator"`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RootMenuAction {
+    /// Run a command line through `sh -c`
+    Exec(String),
+    /// Switch to a workspace (0-based)
+    Workspace(u32),
+    /// Re-read the configuration file
+    Reload,
+    /// Quit the window manager
+    Exit,
+    /// A divider line, not selectable
+    Separator,
+}
@@ -0,0 +1,70 @@
+//! Runtime diagnostics over D-Bus (org.area.Diagnostics)
+//!
+//! Mirrors the `SetLogLevel` / `DebugDump` IPC commands for tooling that
+//! speaks D-Bus rather than the area-ipc socket. As on the socket, both
+//! need the `[ipc] token` unless listed in `untrusted_commands`.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use area_ipc::ShellCommand;
+use tracing::warn;
+use zbus::{Connection, fdo, interface};
+
+use crate::config::IpcConfig;
+use crate::diagnostics;
+
+/// Well-known bus name owned by the window manager
+pub const BUS_NAME: &str = "org.area.WindowManager";
+
+/// Object path of the diagnostics interface
+pub const OBJECT_PATH: &str = "/org/area/Diagnostics";
+
+pub struct DiagnosticsInterface {
+    access: Arc<Mutex<IpcConfig>>,
+}
+
+impl DiagnosticsInterface {
+    /// Refuse `command` unless an untrusted IPC client could run it
+    fn check(&self, command: &ShellCommand) -> fdo::Result<()> {
+        if crate::ipc::untrusted_allowed(&self.access.lock().unwrap(), command) {
+            return Ok(());
+        }
+        warn!("D-Bus client is not allowed to run {:?}", command);
+        Err(fdo::Error::AccessDenied("command requires the IPC token, list it in [ipc] untrusted_commands to allow it over D-Bus".into()))
+    }
+}
+
+#[interface(name = "org.area.Diagnostics")]
+impl DiagnosticsInterface {
+    /// Replace the active log filter (RUST_LOG syntax)
+    fn set_log_level(&self, filter: &str) -> fdo::Result<()> {
+        self.check(&ShellCommand::SetLogLevel { filter: filter.to_string() })?;
+        diagnostics::set_filter(filter).map_err(|e| fdo::Error::InvalidArgs(format!("{:#}", e)))
+    }
+
+    /// Recent log lines from the in-memory buffer (oldest first)
+    fn dump(&self) -> fdo::Result<Vec<String>> {
+        self.check(&ShellCommand::DebugDump)?;
+        Ok(diagnostics::dump())
+    }
+
+    /// Currently active log filter
+    #[zbus(property)]
+    fn log_level(&self) -> fdo::Result<String> {
+        self.check(&ShellCommand::DebugDump)?;
+        Ok(diagnostics::current_filter())
+    }
+}
+
+/// Export the diagnostics interface and claim the WM bus name
+pub async fn serve(conn: &Connection, access: Arc<Mutex<IpcConfig>>) -> Result<()> {
+    conn.object_server()
+        .at(OBJECT_PATH, DiagnosticsInterface { access })
+        .await
+        .context("Failed to export org.area.Diagnostics")?;
+    conn.request_name(BUS_NAME)
+        .await
+        .with_context(|| format!("Failed to acquire D-Bus name {}", BUS_NAME))?;
+    Ok(())
+}
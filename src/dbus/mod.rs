@@ -4,8 +4,13 @@ use anyhow::{Context, Result};
 use zbus::Connection;
 use std::sync::Arc;
 
+pub mod diagnostics;
+pub mod notification_server;
 pub mod notifications;
+pub mod portal;
 pub mod power;
+pub mod screensaver;
+pub mod window_manager;
 
 pub struct DbusManager {
     conn: Arc<Connection>,
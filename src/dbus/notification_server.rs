@@ -0,0 +1,96 @@
+//! Desktop notification server (org.freedesktop.Notifications)
+//!
+//! Applications' notifications are handed to the main loop, which records
+//! them in the history and, unless Do Not Disturb is on, passes them on to
+//! the shell (`WmEvent::Notification`) to draw and expire.
+
+use anyhow::{Context, Result};
+use area_ipc::Notification;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tracing::debug;
+use zbus::fdo::RequestNameFlags;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::OwnedValue;
+use zbus::{Connection, interface};
+
+const BUS_NAME: &str = "org.freedesktop.Notifications";
+const OBJECT_PATH: &str = "/org/freedesktop/Notifications";
+
+/// `NotificationClosed` reason: closed by a call to `CloseNotification`
+const CLOSED_BY_CALL: u32 = 3;
+
+/// A notification event for the main loop
+#[derive(Debug)]
+pub enum NotificationMessage {
+    Notify(Notification),
+    /// The sender withdrew a notification
+    Close(u32),
+}
+
+struct NotificationServer {
+    next_id: AtomicU32,
+    messages: mpsc::UnboundedSender<NotificationMessage>,
+}
+
+#[interface(name = "org.freedesktop.Notifications")]
+impl NotificationServer {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: String,
+        replaces_id: u32,
+        app_icon: String,
+        summary: String,
+        body: String,
+        actions: Vec<String>,
+        hints: HashMap<String, OwnedValue>,
+        expire_timeout: i32,
+    ) -> u32 {
+        let id = match replaces_id {
+            0 => self.next_id.fetch_add(1, Ordering::Relaxed),
+            id => id,
+        };
+        let urgency = hints.get("urgency").and_then(|value| u8::try_from(value).ok()).unwrap_or(1);
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        debug!("Notification {} from {:?}: {:?}", id, app_name, summary);
+        let notification = Notification { id, app_name, app_icon, summary, body, urgency, actions, expire_timeout, timestamp };
+        let _ = self.messages.send(NotificationMessage::Notify(notification));
+        id
+    }
+
+    async fn close_notification(&self, #[zbus(signal_emitter)] emitter: SignalEmitter<'_>, id: u32) {
+        let _ = self.messages.send(NotificationMessage::Close(id));
+        if let Err(e) = Self::notification_closed(&emitter, id, CLOSED_BY_CALL).await {
+            debug!("Failed to emit NotificationClosed: {}", e);
+        }
+    }
+
+    fn get_capabilities(&self) -> Vec<&str> {
+        vec!["body", "persistence"]
+    }
+
+    /// Name, vendor, version and specification version
+    fn get_server_information(&self) -> (&str, &str, &str, &str) {
+        ("Area", "Area", env!("CARGO_PKG_VERSION"), "1.2")
+    }
+
+    #[zbus(signal)]
+    async fn notification_closed(emitter: &SignalEmitter<'_>, id: u32, reason: u32) -> zbus::Result<()>;
+}
+
+/// Export the server and claim the notification bus name unless another
+/// daemon holds it; notifications are numbered from `first_id`
+pub async fn serve(conn: &Connection, first_id: u32) -> Result<mpsc::UnboundedReceiver<NotificationMessage>> {
+    let (messages, rx) = mpsc::unbounded_channel();
+    conn.object_server()
+        .at(OBJECT_PATH, NotificationServer { next_id: AtomicU32::new(first_id), messages })
+        .await
+        .context("Failed to export org.freedesktop.Notifications")?;
+    conn.request_name_with_flags(BUS_NAME, RequestNameFlags::DoNotQueue.into())
+        .await
+        .with_context(|| format!("Failed to acquire D-Bus name {} (another notification daemon running?)", BUS_NAME))?;
+    Ok(rx)
+}
@@ -0,0 +1,153 @@
+//! XDG desktop portal backend (org.freedesktop.impl.portal.Screenshot)
+//!
+//! xdg-desktop-portal forwards sandboxed apps' screenshot and color picker
+//! requests to the backend named in `area.portal` once the user allowed
+//! them. The screen is read back from the compositor, so it shows what the
+//! user sees; X has no such image itself while windows are redirected.
+//!
+//! Requests are answered right away, without a dialog: `interactive` is
+//! ignored and the color picker samples the pixel under the pointer. That
+//! leaves the frontend's permission check as the only one, so calls from
+//! anyone but xdg-desktop-portal itself are refused.
+//!
+//! ScreenCast is not provided yet and is tracked separately (see the README
+//! roadmap): it needs a PipeWire stream producer, which Area doesn't have.
+//! Until then `area-portals.conf` routes it to no backend, so apps fail over
+//! to capturing through X right away.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tracing::{debug, warn};
+use x11rb::protocol::xproto::ConnectionExt as _;
+use x11rb::rust_connection::RustConnection;
+use zbus::message::Header;
+use zbus::names::BusName;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+use zbus::{Connection, fdo, interface};
+
+use crate::compositor::Compositor;
+use crate::compositor::image::{self, Image};
+
+/// Bus name of the backend (`DBusName` in `area.portal`)
+pub const BUS_NAME: &str = "org.freedesktop.impl.portal.desktop.area";
+
+/// Object path every portal backend is served at
+pub const OBJECT_PATH: &str = "/org/freedesktop/portal/desktop";
+
+/// Bus name of the xdg-desktop-portal frontend, the only caller served
+const FRONTEND_BUS_NAME: &str = "org.freedesktop.portal.Desktop";
+
+/// Portal response codes
+const RESPONSE_SUCCESS: u32 = 0;
+const RESPONSE_FAILED: u32 = 2;
+
+pub struct ScreenshotPortal {
+    compositor: Compositor,
+    conn: Arc<RustConnection>,
+    root: u32,
+}
+
+impl ScreenshotPortal {
+    /// Refuse callers other than the portal frontend
+    async fn check_caller(conn: &Connection, header: &Header<'_>) -> fdo::Result<()> {
+        let sender = header.sender().ok_or_else(|| fdo::Error::AccessDenied("no sender".into()))?;
+        let frontend = BusName::try_from(FRONTEND_BUS_NAME).map_err(zbus::Error::from)?;
+        let owner = fdo::DBusProxy::new(conn).await?.get_name_owner(frontend).await.ok();
+        if owner.is_some_and(|owner| owner.as_str() == sender.as_str()) {
+            return Ok(());
+        }
+        warn!("Portal request from {} refused, only xdg-desktop-portal may call", sender);
+        Err(fdo::Error::AccessDenied(format!("only {} may call the portal backend", FRONTEND_BUS_NAME)))
+    }
+
+    /// The screen as composited
+    async fn capture(&self) -> Result<Image> {
+        self.compositor.capture_screen().await.ok().flatten().context("compositor cannot capture the screen")
+    }
+
+    /// Save a screenshot to the pictures directory; returns its path
+    async fn save(&self) -> Result<std::path::PathBuf> {
+        let image = self.capture().await?;
+        let dir = dirs::picture_dir().or_else(dirs::home_dir).context("no pictures directory")?;
+        let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs();
+        let path = dir.join(format!("Screenshot-{}.png", secs));
+        tokio::fs::write(&path, image::encode_png(&image)?).await.with_context(|| format!("Failed to write {:?}", path))?;
+        Ok(path)
+    }
+
+    /// Color of the screen pixel under the pointer, 0.0-1.0
+    async fn sample_color(&self) -> Result<(f64, f64, f64)> {
+        let pointer = self.conn.query_pointer(self.root)?.reply()?;
+        let image = self.capture().await?;
+        let (x, y) = (pointer.root_x.max(0) as usize, pointer.root_y.max(0) as usize);
+        let offset = (y * image.width as usize + x) * 4;
+        let rgb = image.pixels.get(offset..offset + 3).context("pointer outside the screen")?;
+        Ok((rgb[0] as f64 / 255.0, rgb[1] as f64 / 255.0, rgb[2] as f64 / 255.0))
+    }
+}
+
+/// Portal reply from a result
+fn respond(result: Result<(&str, Value<'_>)>) -> (u32, HashMap<String, OwnedValue>) {
+    let value = result.and_then(|(key, value)| Ok((key, OwnedValue::try_from(value)?)));
+    match value {
+        Ok((key, value)) => (RESPONSE_SUCCESS, HashMap::from([(key.to_string(), value)])),
+        Err(e) => {
+            warn!("Portal request failed: {:#}", e);
+            (RESPONSE_FAILED, HashMap::new())
+        }
+    }
+}
+
+#[interface(name = "org.freedesktop.impl.portal.Screenshot")]
+impl ScreenshotPortal {
+    /// Take a screenshot; results hold its `uri`
+    async fn screenshot(
+        &self,
+        #[zbus(connection)] conn: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        _handle: OwnedObjectPath,
+        app_id: &str,
+        _parent_window: &str,
+        _options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<(u32, HashMap<String, OwnedValue>)> {
+        Self::check_caller(conn, &header).await?;
+        debug!("Portal screenshot for {:?}", app_id);
+        let result = self.save().await.map(|path| ("uri", Value::from(format!("file://{}", path.display()))));
+        Ok(respond(result))
+    }
+
+    /// Pick a color; results hold it as `color` (ddd)
+    async fn pick_color(
+        &self,
+        #[zbus(connection)] conn: &Connection,
+        #[zbus(header)] header: Header<'_>,
+        _handle: OwnedObjectPath,
+        app_id: &str,
+        _parent_window: &str,
+        _options: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<(u32, HashMap<String, OwnedValue>)> {
+        Self::check_caller(conn, &header).await?;
+        debug!("Portal color pick for {:?}", app_id);
+        let result = self.sample_color().await.map(|color| ("color", Value::new(color)));
+        Ok(respond(result))
+    }
+
+    #[zbus(property, name = "version")]
+    fn version(&self) -> u32 {
+        2
+    }
+}
+
+/// Export the screenshot portal and claim the backend bus name
+pub async fn serve(conn: &Connection, compositor: Compositor, x11: Arc<RustConnection>, root: u32) -> Result<()> {
+    conn.object_server()
+        .at(OBJECT_PATH, ScreenshotPortal { compositor, conn: x11, root })
+        .await
+        .context("Failed to export org.freedesktop.impl.portal.Screenshot")?;
+    conn.request_name(BUS_NAME)
+        .await
+        .with_context(|| format!("Failed to acquire D-Bus name {}", BUS_NAME))?;
+    Ok(())
+}
@@ -67,18 +67,12 @@ impl PowerService {
     }
     
     /// Reboot the system
-    /// WHY: Part of PowerService API, planned for use when UI adds Reboot button.
-    /// SPECIFIC PLAN: Task "Phase 2: Advanced Features", Owner: Bizkit
-    #[allow(dead_code)]
     pub async fn reboot(&self) -> Result<()> {
         self.logind.reboot(true).await?;
         Ok(())
     }
     
     /// Check if on battery power
-    /// WHY: Part of PowerService API, planned for use when UI adds Battery indicator.
-    /// SPECIFIC PLAN: Task "Phase 2: Advanced Features", Owner: Bizkit
-    #[allow(dead_code)]
     pub async fn on_battery(&self) -> Result<bool> {
         Ok(self.upower.on_battery().await?)
     }
@@ -0,0 +1,84 @@
+//! Idle inhibition (org.freedesktop.ScreenSaver)
+//!
+//! Video players and browsers call `Inhibit` while playing so the screen is
+//! not blanked and the monitors stay on. Requests are handed to the main
+//! loop, which applies them to the DPMS timeouts; an inhibitor is dropped
+//! when its caller calls `UnInhibit` or leaves the bus.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use anyhow::{Context, Result};
+use tokio::sync::mpsc;
+use tracing::debug;
+use zbus::export::futures_core::Stream;
+use zbus::fdo::RequestNameFlags;
+use zbus::message::Header;
+use zbus::{Connection, interface};
+
+const BUS_NAME: &str = "org.freedesktop.ScreenSaver";
+/// Both paths are in use by applications
+const OBJECT_PATHS: [&str; 2] = ["/org/freedesktop/ScreenSaver", "/ScreenSaver"];
+
+/// An inhibition request for the main loop
+#[derive(Debug)]
+pub enum ScreenSaverMessage {
+    Inhibit { cookie: u32, application: String, reason: String, sender: String },
+    UnInhibit(u32),
+    /// A bus client disconnected (unique name)
+    Vanished(String),
+}
+
+struct ScreenSaver {
+    next_cookie: std::sync::Arc<AtomicU32>,
+    messages: mpsc::UnboundedSender<ScreenSaverMessage>,
+}
+
+#[interface(name = "org.freedesktop.ScreenSaver")]
+impl ScreenSaver {
+    fn inhibit(&self, #[zbus(header)] header: Header<'_>, application_name: String, reason_for_inhibit: String) -> u32 {
+        let cookie = self.next_cookie.fetch_add(1, Ordering::Relaxed);
+        let sender = header.sender().map(|s| s.to_string()).unwrap_or_default();
+        debug!("Inhibit {} from {} ({})", cookie, application_name, sender);
+        let _ = self.messages.send(ScreenSaverMessage::Inhibit {
+            cookie,
+            application: application_name,
+            reason: reason_for_inhibit,
+            sender,
+        });
+        cookie
+    }
+
+    fn un_inhibit(&self, cookie: u32) {
+        let _ = self.messages.send(ScreenSaverMessage::UnInhibit(cookie));
+    }
+}
+
+/// Export the service, claim its bus name unless another daemon holds it
+/// and watch for callers leaving the bus
+pub async fn serve(conn: &Connection) -> Result<mpsc::UnboundedReceiver<ScreenSaverMessage>> {
+    let (messages, rx) = mpsc::unbounded_channel();
+    let next_cookie = std::sync::Arc::new(AtomicU32::new(1));
+    for path in OBJECT_PATHS {
+        let service = ScreenSaver { next_cookie: next_cookie.clone(), messages: messages.clone() };
+        conn.object_server()
+            .at(path, service)
+            .await
+            .with_context(|| format!("Failed to export {} at {}", BUS_NAME, path))?;
+    }
+    conn.request_name_with_flags(BUS_NAME, RequestNameFlags::DoNotQueue.into())
+        .await
+        .with_context(|| format!("Failed to acquire D-Bus name {} (another screen saver running?)", BUS_NAME))?;
+
+    let proxy = zbus::fdo::DBusProxy::new(conn).await?;
+    let changes = proxy.receive_name_owner_changed().await?;
+    tokio::spawn(async move {
+        let mut changes = std::pin::pin!(changes);
+        while let Some(signal) = std::future::poll_fn(|cx| changes.as_mut().poll_next(cx)).await {
+            let Ok(args) = signal.args() else { continue };
+            if args.new_owner().is_none() && args.name().starts_with(':') {
+                let _ = messages.send(ScreenSaverMessage::Vanished(args.name().to_string()));
+            }
+        }
+    });
+    Ok(rx)
+}
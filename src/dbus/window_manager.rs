@@ -0,0 +1,207 @@
+//! Window control over D-Bus (org.area.WindowManager)
+//!
+//! Lets desktop tooling, scripts and applets focus, close and move windows
+//! and switch workspaces without speaking the area-ipc socket protocol.
+//! Methods are forwarded to the main loop as IPC commands. D-Bus callers
+//! can't present the `[ipc] token`, so with a token configured they are
+//! limited to `untrusted_commands` like any socket client without it;
+//! window manager events are re-emitted as the `Event` signal.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use area_ipc::{Reply, ShellCommand, WmEvent};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+use zbus::object_server::SignalEmitter;
+use zbus::{Connection, fdo, interface};
+
+use crate::config::IpcConfig;
+use crate::ipc::IpcRequest;
+
+/// Object path of the window manager interface
+pub const OBJECT_PATH: &str = "/org/area/WindowManager";
+
+pub struct WindowManagerInterface {
+    requests: mpsc::UnboundedSender<IpcRequest>,
+    access: Arc<Mutex<IpcConfig>>,
+}
+
+impl WindowManagerInterface {
+    /// Run a command on the main loop and wait for its reply
+    async fn run(&self, command: ShellCommand) -> fdo::Result<Reply> {
+        if !crate::ipc::untrusted_allowed(&self.access.lock().unwrap(), &command) {
+            warn!("D-Bus client is not allowed to run {:?}", command);
+            return Err(fdo::Error::AccessDenied("command requires the IPC token, list it in [ipc] untrusted_commands to allow it over D-Bus".into()));
+        }
+        let (reply, rx) = oneshot::channel();
+        self.requests
+            .send(IpcRequest { command, reply })
+            .map_err(|_| fdo::Error::Failed("window manager is shutting down".into()))?;
+        match rx.await {
+            Ok(Reply::Error { message }) => Err(fdo::Error::Failed(message)),
+            Ok(reply) => Ok(reply),
+            Err(_) => Err(fdo::Error::Failed("window manager dropped the request".into())),
+        }
+    }
+
+    async fn run_ok(&self, command: ShellCommand) -> fdo::Result<()> {
+        self.run(command).await.map(|_| ())
+    }
+}
+
+#[interface(name = "org.area.WindowManager")]
+impl WindowManagerInterface {
+    /// Focus and raise a window, switching to its workspace if needed
+    async fn focus(&self, id: u32) -> fdo::Result<()> {
+        self.run_ok(ShellCommand::FocusWindow { id }).await
+    }
+
+    /// Ask a window to close (WM_DELETE_WINDOW)
+    async fn close(&self, id: u32) -> fdo::Result<()> {
+        self.run_ok(ShellCommand::CloseWindow { id }).await
+    }
+
+    /// Kill a window's client; `force` also signals its process
+    async fn kill(&self, id: u32, force: bool) -> fdo::Result<()> {
+        self.run_ok(ShellCommand::KillWindow { id, force }).await
+    }
+
+    /// Move a window's frame to root coordinates
+    async fn move_window(&self, id: u32, x: i32, y: i32) -> fdo::Result<()> {
+        self.run_ok(ShellCommand::MoveWindow { id, x, y }).await
+    }
+
+    async fn minimize(&self, id: u32) -> fdo::Result<()> {
+        self.run_ok(ShellCommand::MinimizeWindow { id }).await
+    }
+
+    async fn restore(&self, id: u32) -> fdo::Result<()> {
+        self.run_ok(ShellCommand::RestoreWindow { id }).await
+    }
+
+    async fn maximize(&self, id: u32, maximized: bool) -> fdo::Result<()> {
+        self.run_ok(ShellCommand::MaximizeWindow { id, maximized }).await
+    }
+
+    async fn set_fullscreen(&self, id: u32, fullscreen: bool) -> fdo::Result<()> {
+        self.run_ok(ShellCommand::SetFullscreen { id, fullscreen }).await
+    }
+
+    /// Switch to a workspace (0-based)
+    async fn switch_workspace(&self, workspace: u32) -> fdo::Result<()> {
+        self.run_ok(ShellCommand::SwitchWorkspace { workspace }).await
+    }
+
+    /// Send a window to a workspace (0-based)
+    async fn move_to_workspace(&self, id: u32, workspace: u32) -> fdo::Result<()> {
+        self.run_ok(ShellCommand::MoveWindowToWorkspace { id, workspace }).await
+    }
+
+    /// Managed windows as a JSON array of area-ipc `WindowInfo`
+    async fn get_windows(&self) -> fdo::Result<String> {
+        match self.run(ShellCommand::GetWindows).await? {
+            Reply::Windows { windows } => serde_json::to_string(&windows).map_err(|e| fdo::Error::Failed(e.to_string())),
+            other => Err(fdo::Error::Failed(format!("unexpected reply: {:?}", other))),
+        }
+    }
+
+    /// Extended properties of a window as JSON (area-ipc `WindowDetails`)
+    async fn get_window_info(&self, id: u32) -> fdo::Result<String> {
+        match self.run(ShellCommand::GetWindowInfo { id }).await? {
+            Reply::WindowDetails { window } => serde_json::to_string(&window).map_err(|e| fdo::Error::Failed(e.to_string())),
+            other => Err(fdo::Error::Failed(format!("unexpected reply: {:?}", other))),
+        }
+    }
+
+    /// Ids of windows matching every non-empty criterion, most recently
+    /// focused first (`workspace` < 0: any workspace)
+    async fn find_windows(&self, class: &str, title_regex: &str, workspace: i32) -> fdo::Result<Vec<u32>> {
+        let command = ShellCommand::FindWindows {
+            class: (!class.is_empty()).then(|| class.to_string()),
+            title_regex: (!title_regex.is_empty()).then(|| title_regex.to_string()),
+            workspace: u32::try_from(workspace).ok(),
+        };
+        match self.run(command).await? {
+            Reply::Windows { windows } => Ok(windows.iter().map(|w| w.id).collect()),
+            other => Err(fdo::Error::Failed(format!("unexpected reply: {:?}", other))),
+        }
+    }
+
+    /// Focus a window of `class` (the next one if it has focus), else run `exec`
+    async fn activate_or_launch(&self, class: &str, exec: &str) -> fdo::Result<()> {
+        self.run_ok(ShellCommand::ActivateOrLaunch { class: class.to_string(), exec: exec.to_string() }).await
+    }
+
+    /// Turn a keyboard accessibility feature on or off
+    /// (`sticky_keys`, `slow_keys`, `bounce_keys` or `mouse_keys`)
+    async fn set_accessibility(&self, feature: &str, enabled: bool) -> fdo::Result<()> {
+        let feature = serde_json::from_value(serde_json::Value::from(feature))
+            .map_err(|_| fdo::Error::InvalidArgs(format!("unknown accessibility feature {:?}", feature)))?;
+        self.run_ok(ShellCommand::SetAccessibility { feature, enabled }).await
+    }
+
+    /// Recorded notifications, newest first, as a JSON array of area-ipc
+    /// `Notification` (`limit` 0: all)
+    async fn get_notification_history(&self, limit: u32) -> fdo::Result<String> {
+        let limit = (limit > 0).then_some(limit);
+        match self.run(ShellCommand::GetNotificationHistory { limit }).await? {
+            Reply::NotificationHistory { notifications, .. } => {
+                serde_json::to_string(&notifications).map_err(|e| fdo::Error::Failed(e.to_string()))
+            }
+            other => Err(fdo::Error::Failed(format!("unexpected reply: {:?}", other))),
+        }
+    }
+
+    /// Notifications are only recorded, not shown (critical ones excepted)
+    #[zbus(property)]
+    async fn do_not_disturb(&self) -> fdo::Result<bool> {
+        match self.run(ShellCommand::GetNotificationHistory { limit: Some(0) }).await? {
+            Reply::NotificationHistory { do_not_disturb, .. } => Ok(do_not_disturb),
+            other => Err(fdo::Error::Failed(format!("unexpected reply: {:?}", other))),
+        }
+    }
+
+    #[zbus(property)]
+    async fn set_do_not_disturb(&mut self, enabled: bool) -> fdo::Result<()> {
+        self.run_ok(ShellCommand::SetDoNotDisturb { enabled }).await
+    }
+
+    /// Run any area-ipc command given as JSON, returning the JSON reply
+    async fn command(&self, json: &str) -> fdo::Result<String> {
+        let command: ShellCommand = serde_json::from_str(json).map_err(|e| fdo::Error::InvalidArgs(e.to_string()))?;
+        if matches!(command, ShellCommand::Hello { .. } | ShellCommand::Subscribe | ShellCommand::SubscribePointer) {
+            return Err(fdo::Error::NotSupported("connection commands are socket-only, use the Event signal".into()));
+        }
+        let reply = self.run(command).await?;
+        serde_json::to_string(&reply).map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    /// A window manager event: its area-ipc name (e.g. `window_opened`) and JSON body
+    #[zbus(signal)]
+    async fn event(emitter: &SignalEmitter<'_>, name: &str, json: &str) -> zbus::Result<()>;
+}
+
+/// Export the interface and forward `events` as signals until the IPC server stops
+pub async fn serve(
+    conn: &Connection,
+    requests: mpsc::UnboundedSender<IpcRequest>,
+    access: Arc<Mutex<IpcConfig>>,
+    mut events: mpsc::Receiver<WmEvent>,
+) -> Result<()> {
+    conn.object_server()
+        .at(OBJECT_PATH, WindowManagerInterface { requests, access })
+        .await
+        .context("Failed to export org.area.WindowManager")?;
+    let emitter = SignalEmitter::new(conn, OBJECT_PATH)?.into_owned();
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            let Ok(value) = serde_json::to_value(&event) else { continue };
+            let name = value.get("event").and_then(|n| n.as_str()).unwrap_or_default().to_string();
+            if let Err(e) = WindowManagerInterface::event(&emitter, &name, &value.to_string()).await {
+                debug!("Failed to emit D-Bus event {}: {}", name, e);
+            }
+        }
+    });
+    Ok(())
+}
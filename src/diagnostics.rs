@@ -0,0 +1,131 @@
+//! Diagnostics
+//!
+//! Tracing setup for the whole process: a stderr formatter, a log filter that
+//! can be swapped at runtime (`area-msg debug level <filter>`), and an
+//! in-memory ring buffer of recent log lines (`area-msg debug dump`).
+//!
+//! Log targets follow module paths, so subsystems can be filtered
+//! individually, e.g. `RUST_LOG=area::wm::focus=trace,area::compositor=debug,info`.
+//! Hot paths are wrapped in spans (`handle_event`, `render`), which EnvFilter
+//! can also select: `[render]=trace`.
+
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry};
+
+/// Filter used when `RUST_LOG` is unset
+const DEFAULT_FILTER: &str = "area=debug,info";
+
+/// Number of log lines kept in memory
+const RING_CAPACITY: usize = 2000;
+
+/// Global diagnostics state (set once by `init`)
+static DIAGNOSTICS: OnceLock<Diagnostics> = OnceLock::new();
+
+struct Diagnostics {
+    filter: reload::Handle<EnvFilter, Registry>,
+    filter_text: Mutex<String>,
+    ring: Arc<Mutex<VecDeque<String>>>,
+}
+
+/// Install the global tracing subscriber
+pub fn init() {
+    let filter_text = std::env::var("RUST_LOG").unwrap_or_else(|_| DEFAULT_FILTER.into());
+    let (filter, handle) = reload::Layer::new(EnvFilter::new(&filter_text));
+    let ring = Arc::new(Mutex::new(VecDeque::with_capacity(RING_CAPACITY)));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(RingWriter { ring: ring.clone() }),
+        )
+        .init();
+
+    let _ = DIAGNOSTICS.set(Diagnostics {
+        filter: handle,
+        filter_text: Mutex::new(filter_text),
+        ring,
+    });
+}
+
+/// Replace the active log filter (RUST_LOG syntax)
+pub fn set_filter(filter: &str) -> Result<()> {
+    let diagnostics = DIAGNOSTICS.get().context("Diagnostics not initialized")?;
+    let new_filter = EnvFilter::try_new(filter)
+        .with_context(|| format!("Invalid log filter {:?}", filter))?;
+    diagnostics.filter.reload(new_filter).context("Failed to reload log filter")?;
+    *diagnostics.filter_text.lock().unwrap() = filter.to_string();
+    tracing::info!("Log filter changed to {:?}", filter);
+    Ok(())
+}
+
+/// Currently active log filter
+pub fn current_filter() -> String {
+    DIAGNOSTICS
+        .get()
+        .map(|d| d.filter_text.lock().unwrap().clone())
+        .unwrap_or_default()
+}
+
+/// Snapshot of the in-memory log buffer (oldest first)
+pub fn dump() -> Vec<String> {
+    DIAGNOSTICS
+        .get()
+        .map(|d| d.ring.lock().unwrap().iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// `MakeWriter` that appends formatted log lines to the ring buffer
+#[derive(Clone)]
+struct RingWriter {
+    ring: Arc<Mutex<VecDeque<String>>>,
+}
+
+/// Per-event writer: collects one formatted event and pushes it on drop
+struct RingLine {
+    ring: Arc<Mutex<VecDeque<String>>>,
+    buf: Vec<u8>,
+}
+
+impl<'a> MakeWriter<'a> for RingWriter {
+    type Writer = RingLine;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RingLine {
+            ring: self.ring.clone(),
+            buf: Vec::new(),
+        }
+    }
+}
+
+impl Write for RingLine {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for RingLine {
+    fn drop(&mut self) {
+        if self.buf.is_empty() {
+            return;
+        }
+        let line = String::from_utf8_lossy(&self.buf).trim_end().to_string();
+        if let Ok(mut ring) = self.ring.lock() {
+            if ring.len() == RING_CAPACITY {
+                ring.pop_front();
+            }
+            ring.push_back(line);
+        }
+    }
+}
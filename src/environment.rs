@@ -0,0 +1,82 @@
+//! Session environment for launched programs
+//!
+//! Everything the window manager starts (launcher, run-or-raise, pinned
+//! applications, hooks) gets the same environment: this display and its
+//! authority file, `XDG_CURRENT_DESKTOP=Area`, the session bus and the
+//! locale, plus the variables of the `[environment]` config table, which
+//! win over the built-in ones.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use tracing::debug;
+
+/// Locale variables passed through when set
+const LOCALE_VARS: &[&str] = &[
+    "LANG",
+    "LANGUAGE",
+    "LC_ALL",
+    "LC_CTYPE",
+    "LC_NUMERIC",
+    "LC_TIME",
+    "LC_COLLATE",
+    "LC_MONETARY",
+    "LC_MESSAGES",
+    "LC_PAPER",
+    "LC_NAME",
+    "LC_ADDRESS",
+    "LC_TELEPHONE",
+    "LC_MEASUREMENT",
+    "LC_IDENTIFICATION",
+];
+
+/// Variables set on every launched program
+#[derive(Debug, Clone, Default)]
+pub struct SessionEnvironment {
+    vars: BTreeMap<String, String>,
+}
+
+impl SessionEnvironment {
+    /// Build the environment for `display` with the configured extra variables
+    pub fn new(display: &str, extra: &HashMap<String, String>) -> Self {
+        let mut vars = BTreeMap::new();
+        vars.insert("DISPLAY".to_string(), display.to_string());
+        if let Ok(xauthority) = std::env::var("XAUTHORITY") {
+            vars.insert("XAUTHORITY".to_string(), xauthority);
+        }
+        vars.insert("XDG_CURRENT_DESKTOP".to_string(), "Area".to_string());
+        vars.insert("XDG_SESSION_DESKTOP".to_string(), "area".to_string());
+        vars.insert("XDG_SESSION_TYPE".to_string(), "x11".to_string());
+        if let Some(address) = session_bus_address() {
+            vars.insert("DBUS_SESSION_BUS_ADDRESS".to_string(), address);
+        }
+        for name in LOCALE_VARS {
+            if let Ok(value) = std::env::var(name) {
+                vars.insert(name.to_string(), value);
+            }
+        }
+        vars.extend(extra.iter().map(|(name, value)| (name.clone(), value.clone())));
+        debug!("Session environment: {:?}", vars);
+        Self { vars }
+    }
+
+    /// The display launched programs connect to
+    pub fn display(&self) -> &str {
+        self.vars.get("DISPLAY").map_or("", String::as_str)
+    }
+
+    /// The variables, for `Command::envs`
+    pub fn vars(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.vars.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+/// Our session bus, or the per-user bus of systemd / dbus-broker
+fn session_bus_address() -> Option<String> {
+    if let Ok(address) = std::env::var("DBUS_SESSION_BUS_ADDRESS") {
+        return Some(address);
+    }
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").ok()?;
+    let bus = Path::new(&runtime_dir).join("bus");
+    bus.exists().then(|| format!("unix:path={}", bus.display()))
+}
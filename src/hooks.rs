@@ -0,0 +1,270 @@
+//! Lua hooks for window manager policy
+//!
+//! Runs handlers from a user Lua script when a window opens, focus changes
+//! or the workspace switches, so placement and focus policies can change
+//! without recompiling:
+//!
+//! ```toml
+//! [hooks]
+//! script = "~/.config/area/hooks.lua"
+//! ```
+//!
+//! The script defines its handlers in the global `hooks` table. A handler
+//! gets the event as a table and can run area-ipc commands through
+//! `area.command`, as if sent by a trusted client; it returns the reply.
+//! `area.spawn` starts a program through `sh -c` in the session environment.
+//!
+//! ```lua
+//! function hooks.window_open(event)
+//!   if event.window.class == "Spotify" then
+//!     area.command{ command = "move_window_to_workspace", id = event.window.id, workspace = 4 }
+//!   end
+//! end
+//! ```
+//!
+//! Handlers run one at a time on a thread of their own, so a slow one
+//! doesn't stall the event loop; one still running after `HOOK_TIMEOUT` is
+//! stopped with an error.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use area_ipc::{Reply, ShellCommand};
+use mlua::{Function, HookTriggers, Lua, LuaSerdeExt, Table, VmState};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, warn};
+
+use crate::config::{HookEvent, HooksConfig};
+use crate::environment::SessionEnvironment;
+use crate::ipc::IpcRequest;
+
+/// A handler still running after this is stopped
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Lua instructions between checks of the deadline
+const DEADLINE_CHECK_INSTRUCTIONS: u32 = 10_000;
+
+/// Every event hooks can run on
+const HOOK_EVENTS: [HookEvent; 3] = [HookEvent::WindowOpen, HookEvent::FocusChange, HookEvent::WorkspaceSwitch];
+
+/// The loaded hook script and the thread running its handlers
+pub struct Hooks {
+    /// Events the script has a handler for
+    handlers: Vec<HookEvent>,
+    /// Queue to the handler thread (none without a script)
+    events: Option<std::sync::mpsc::Sender<(HookEvent, serde_json::Value)>>,
+    /// Command channel to the main loop (none without an IPC server)
+    requests: Option<mpsc::UnboundedSender<IpcRequest>>,
+}
+
+impl Hooks {
+    pub fn new(config: HooksConfig, requests: Option<mpsc::UnboundedSender<IpcRequest>>, environment: SessionEnvironment) -> Self {
+        let mut hooks = Self { handlers: Vec::new(), events: None, requests };
+        hooks.set_config(config, environment);
+        hooks
+    }
+
+    /// Load the script again (after a config reload); handlers already
+    /// queued still run on the old one
+    pub fn set_config(&mut self, config: HooksConfig, environment: SessionEnvironment) {
+        self.handlers.clear();
+        self.events = None;
+        let Some(ref script) = config.script else { return };
+        let path = expand_home(script);
+        match load(&path, self.requests.clone(), environment) {
+            Ok((lua, handlers)) => {
+                info!("Loaded hook script {:?} ({} handlers)", path, handlers.len());
+                self.events = spawn_handler_thread(lua);
+                self.handlers = handlers;
+            }
+            Err(e) => warn!("Hook script {:?} not loaded: {:#}", path, e),
+        }
+    }
+
+    /// Whether the script handles `event` (to skip building its payload)
+    pub fn wants(&self, event: HookEvent) -> bool {
+        self.handlers.contains(&event)
+    }
+
+    /// Queue the handler for `event`; `payload` is merged into the table
+    /// it receives
+    pub fn run(&self, event: HookEvent, payload: serde_json::Value) {
+        if !self.wants(event) {
+            return;
+        }
+        if let Some(ref events) = self.events
+            && events.send((event, payload)).is_err()
+        {
+            warn!("Hook thread is gone, {} hook not run", hook_name(event));
+        }
+    }
+}
+
+/// Name of an event's handler in the `hooks` table
+fn hook_name(event: HookEvent) -> String {
+    match serde_json::to_value(event) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => format!("{:?}", event),
+    }
+}
+
+/// Expand a leading `~/` to the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Set up a Lua state with the `area` API and run the script in it;
+/// returns the state and the events it has handlers for
+fn load(
+    path: &std::path::Path,
+    requests: Option<mpsc::UnboundedSender<IpcRequest>>,
+    environment: SessionEnvironment,
+) -> anyhow::Result<(Lua, Vec<HookEvent>)> {
+    let source = std::fs::read_to_string(path).context("failed to read the script")?;
+    let lua = Lua::new();
+    let deadline = Arc::new(Mutex::new(Instant::now() + HOOK_TIMEOUT));
+    let hook_deadline = deadline.clone();
+    lua.set_hook(HookTriggers::new().every_nth_instruction(DEADLINE_CHECK_INSTRUCTIONS), move |_, _| {
+        if Instant::now() > *hook_deadline.lock().unwrap_or_else(PoisonError::into_inner) {
+            return Err(mlua::Error::runtime("hook timed out"));
+        }
+        Ok(VmState::Continue)
+    });
+    lua.set_app_data(deadline);
+
+    let area = lua.create_table()?;
+    area.set("command", lua.create_function(move |lua, command: mlua::Value| {
+        let command: ShellCommand = lua.from_value(command)?;
+        let reply = run_command(command, requests.as_ref()).map_err(mlua::Error::runtime)?;
+        to_lua(lua, &reply)
+    })?)?;
+    area.set("spawn", lua.create_function(move |_, command: String| {
+        std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .envs(environment.vars())
+            .stdin(std::process::Stdio::null())
+            .spawn()
+            .map(drop)
+            .map_err(|e| mlua::Error::runtime(format!("failed to run {}: {}", command, e)))
+    })?)?;
+    lua.globals().set("area", area)?;
+    lua.globals().set("hooks", lua.create_table()?)?;
+
+    lua.load(&source).set_name(format!("@{}", path.display())).exec()?;
+    let table: Table = lua.globals().get("hooks").context("the script replaced the hooks table")?;
+    let mut handlers = Vec::new();
+    for event in HOOK_EVENTS {
+        if table.get::<Option<Function>>(hook_name(event))?.is_some() {
+            handlers.push(event);
+        }
+    }
+    Ok((lua, handlers))
+}
+
+/// Send a command to the main loop and wait for its reply
+fn run_command(command: ShellCommand, requests: Option<&mpsc::UnboundedSender<IpcRequest>>) -> Result<Reply, String> {
+    if matches!(command, ShellCommand::Hello { .. } | ShellCommand::Subscribe | ShellCommand::SubscribePointer) {
+        return Err("hooks cannot run connection commands".into());
+    }
+    let requests = requests.ok_or("IPC is unavailable")?;
+    let (reply, rx) = oneshot::channel();
+    requests.send(IpcRequest { command, reply }).map_err(|_| "the window manager is shutting down")?;
+    let reply = rx.blocking_recv().map_err(|_| "the command was dropped")?;
+    if let Reply::Error { ref message } = reply {
+        debug!("Hook command failed: {}", message);
+    }
+    Ok(reply)
+}
+
+/// JSON to Lua, with null as nil
+fn to_lua(lua: &Lua, value: &impl serde::Serialize) -> mlua::Result<mlua::Value> {
+    let options = mlua::SerializeOptions::new().serialize_none_to_null(false).serialize_unit_to_null(false);
+    lua.to_value_with(value, options)
+}
+
+/// Run queued handlers until the `Hooks` that queues them is replaced
+fn spawn_handler_thread(lua: Lua) -> Option<std::sync::mpsc::Sender<(HookEvent, serde_json::Value)>> {
+    let (tx, rx) = std::sync::mpsc::channel::<(HookEvent, serde_json::Value)>();
+    let spawned = std::thread::Builder::new().name("area-hooks".into()).spawn(move || {
+        for (event, payload) in rx {
+            let name = hook_name(event);
+            if let Err(e) = run_handler(&lua, &name, payload) {
+                warn!("Hook {} failed: {}", name, e);
+            }
+        }
+    });
+    match spawned {
+        Ok(_) => Some(tx),
+        Err(e) => {
+            warn!("Failed to start the hook thread: {}", e);
+            None
+        }
+    }
+}
+
+fn run_handler(lua: &Lua, name: &str, payload: serde_json::Value) -> mlua::Result<()> {
+    let hooks: Table = lua.globals().get("hooks")?;
+    let Some(handler) = hooks.get::<Option<Function>>(name)? else { return Ok(()) };
+    let mut event = serde_json::json!({ "hook": name });
+    if let (Some(event), serde_json::Value::Object(payload)) = (event.as_object_mut(), payload) {
+        event.extend(payload);
+    }
+    if let Some(deadline) = lua.app_data_ref::<Arc<Mutex<Instant>>>() {
+        *deadline.lock().unwrap_or_else(PoisonError::into_inner) = Instant::now() + HOOK_TIMEOUT;
+    }
+    debug!("Running {} hook", name);
+    handler.call::<()>(to_lua(lua, &event)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(name: &str, source: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("area-hooks-{}-{}.lua", name, std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        path
+    }
+
+    #[test]
+    fn handlers_run_commands() {
+        let path = script("commands", r#"
+            function hooks.window_open(event)
+              if event.window.class == "Spotify" and event.previous == nil then
+                local reply = area.command{ command = "move_window_to_workspace", id = event.window.id, workspace = 4 }
+                area.command{ command = "close_window", id = reply.reply == "ok" and 1 or 2 }
+              end
+            end
+        "#);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let hooks = Hooks::new(HooksConfig { script: Some(path.display().to_string()) }, Some(tx), Default::default());
+        std::fs::remove_file(&path).unwrap();
+        assert!(hooks.wants(HookEvent::WindowOpen));
+        assert!(!hooks.wants(HookEvent::FocusChange));
+
+        hooks.run(HookEvent::WindowOpen, serde_json::json!({ "window": { "id": 7, "class": "Spotify" }, "previous": null }));
+        let request = rx.blocking_recv().unwrap();
+        assert!(matches!(request.command, ShellCommand::MoveWindowToWorkspace { id: 7, workspace: 4 }));
+        request.reply.send(Reply::Ok).unwrap();
+        let request = rx.blocking_recv().unwrap();
+        assert!(matches!(request.command, ShellCommand::CloseWindow { id: 1 }));
+    }
+
+    #[test]
+    fn runaway_handlers_are_stopped() {
+        let path = script("runaway", "function hooks.focus_change() while true do end end");
+        let (lua, handlers) = load(&path, None, Default::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(handlers, [HookEvent::FocusChange]);
+        // As if HOOK_TIMEOUT had passed as soon as the handler starts
+        *lua.app_data_ref::<Arc<Mutex<Instant>>>().unwrap().lock().unwrap() = Instant::now();
+        let error = lua.globals().get::<Table>("hooks").unwrap().get::<Function>("focus_change").unwrap().call::<()>(()).unwrap_err();
+        assert!(error.to_string().contains("timed out"), "{}", error);
+    }
+}
@@ -7,7 +7,7 @@ use std::sync::Arc;
 use tracing::{debug, info, warn};
 use x11rb::connection::Connection;
 use x11rb::protocol::xinput::{self, ConnectionExt as XInputExt};
-use x11rb::protocol::xproto::{Atom, PropMode, ConnectionExt as XProtoExt};
+use x11rb::protocol::xproto::{Atom, PropMode};
 use x11rb::rust_connection::RustConnection;
 use crate::config::MouseConfig;
 
@@ -33,26 +33,14 @@ impl InputManager {
             version_reply.major_version, version_reply.minor_version);
         
         // Intern atoms we'll need
-        let float_atom = conn.as_ref().intern_atom(false, b"FLOAT")?
-            .reply()
-            .context("Failed to intern FLOAT atom")?
-            .atom;
+        let float_atom = crate::wm::atom_cache::intern(conn.as_ref(), "FLOAT")?;
         
         // Try to intern libinput atoms (may fail if libinput not available)
-        let accel_speed_atom = conn.as_ref().intern_atom(false, b"libinput Accel Speed")
-            .ok()
-            .and_then(|cookie| cookie.reply().ok())
-            .map(|reply| reply.atom);
+        let accel_speed_atom = crate::wm::atom_cache::intern(conn.as_ref(), "libinput Accel Speed").ok();
         
-        let accel_profile_atom = conn.as_ref().intern_atom(false, b"libinput Accel Profile Enabled")
-            .ok()
-            .and_then(|cookie| cookie.reply().ok())
-            .map(|reply| reply.atom);
+        let accel_profile_atom = crate::wm::atom_cache::intern(conn.as_ref(), "libinput Accel Profile Enabled").ok();
         
-        let left_handed_atom = conn.as_ref().intern_atom(false, b"libinput Left Handed Enabled")
-            .ok()
-            .and_then(|cookie| cookie.reply().ok())
-            .map(|reply| reply.atom);
+        let left_handed_atom = crate::wm::atom_cache::intern(conn.as_ref(), "libinput Left Handed Enabled").ok();
         
         if accel_speed_atom.is_none() {
             warn!("libinput atoms not available - input configuration may not work");
@@ -153,10 +141,7 @@ impl InputManager {
         debug!("Setting libinput Accel Profile to {:?} for device {}", profile_array, device_id);
         
         // Intern INTEGER atom
-        let integer_atom = self.conn.as_ref().intern_atom(false, b"INTEGER")?
-            .reply()
-            .context("Failed to intern INTEGER atom")?
-            .atom;
+        let integer_atom = crate::wm::atom_cache::intern(self.conn.as_ref(), "INTEGER")?;
         
         use x11rb::protocol::xinput::{xi_change_property, XIChangePropertyAux};
         let aux = XIChangePropertyAux::Data8(profile_array.to_vec());
@@ -189,10 +174,7 @@ impl InputManager {
         debug!("Setting libinput Left Handed Enabled to {} for device {}", enabled, device_id);
         
         // Intern INTEGER atom
-        let integer_atom = self.conn.as_ref().intern_atom(false, b"INTEGER")?
-            .reply()
-            .context("Failed to intern INTEGER atom")?
-            .atom;
+        let integer_atom = crate::wm::atom_cache::intern(self.conn.as_ref(), "INTEGER")?;
         
         use x11rb::protocol::xinput::{xi_change_property, XIChangePropertyAux};
         let aux = XIChangePropertyAux::Data8(vec![enabled_byte]);
@@ -0,0 +1,396 @@
+//! IPC Server
+//!
+//! Listens on the area-ipc Unix socket (see `area_ipc::socket_path`) and
+//! forwards each `ShellCommand` to the main event loop, which answers it with
+//! a `Reply`. Any number of clients (shell, pager, applets, `area-msg`
+//! scripts) may be connected; each is served on its own task and command
+//! handling happens on the main loop so it sees consistent WM state.
+//!
+//! Clients that send `Subscribe` get their own bounded event queue, filled
+//! by `emit`. The high-volume `PointerMoved` events only go to clients that
+//! sent `SubscribePointer`, and events behind a capability (see
+//! `area_ipc::version`) only to clients that announced it in their `Hello`.
+//! A client that stops reading loses events once its queue is full and is
+//! disconnected if it falls too far behind, so it never holds up the others.
+//!
+//! Connections from other users are refused (see `IpcConfig`); with a
+//! configured token, clients that did not present it are limited to the
+//! configured read-only commands.
+
+use anyhow::{Context, Result};
+use area_ipc::{FramedMessage, Reply, ShellCommand, Version, WmEvent, WmMessage};
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, trace, warn};
+
+use crate::config::IpcConfig;
+
+/// A command received from a client, with the channel to answer it on
+pub struct IpcRequest {
+    pub command: ShellCommand,
+    pub reply: oneshot::Sender<Reply>,
+}
+
+/// Events queued per subscriber before it starts missing them
+const EVENT_QUEUE: usize = 256;
+
+/// Events a subscriber may miss in a row before it is disconnected
+const MAX_MISSED: u64 = 1024;
+
+/// Pause after a failed accept (e.g. out of file descriptors) before the next one
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Sampling interval of the `PointerMoved` stream
+pub const POINTER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Minimum time between two `WorkspaceLayout` events
+pub const LAYOUT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// An event subscription of one connection
+struct Subscriber {
+    queue: mpsc::Sender<WmEvent>,
+    /// Sent `SubscribePointer`
+    pointer: bool,
+    /// Capabilities negotiated in `Hello`
+    capabilities: Vec<&'static str>,
+    /// Events dropped in a row because the queue was full
+    missed: u64,
+}
+
+impl Subscriber {
+    fn wants(&self, event: &WmEvent) -> bool {
+        if matches!(event, WmEvent::PointerMoved { .. }) && !self.pointer {
+            return false;
+        }
+        event.capability().is_none_or(|c| self.capabilities.contains(&c))
+    }
+}
+
+/// Subscriptions by connection id
+type Subscribers = Arc<Mutex<HashMap<u64, Subscriber>>>;
+
+/// Handle to the listening socket (removes the socket file on drop)
+pub struct IpcServer {
+    path: PathBuf,
+    subscribers: Subscribers,
+    /// Access control, shared with the connection tasks
+    access: Arc<Mutex<IpcConfig>>,
+    /// Request channel to the main loop, for in-process frontends (D-Bus)
+    requests: mpsc::UnboundedSender<IpcRequest>,
+    /// Id of the next connection or in-process subscriber
+    next_id: Arc<AtomicU64>,
+}
+
+impl IpcServer {
+    /// Bind the IPC socket and start accepting clients
+    ///
+    /// Returns the server handle and the stream of incoming requests.
+    pub fn spawn(access: IpcConfig) -> Result<(Self, mpsc::UnboundedReceiver<IpcRequest>)> {
+        let path = area_ipc::socket_path();
+
+        if path.exists() {
+            if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+                anyhow::bail!("IPC socket {:?} is in use by another Area instance", path);
+            }
+            // Stale socket from a previous run
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove stale IPC socket {:?}", path))?;
+        }
+
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("Failed to bind IPC socket {:?}", path))?;
+        // Other allowed users need to open the socket; peer credentials gate them
+        let mode = if access.allowed_uids.is_empty() { 0o600 } else { 0o666 };
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
+        // The socket is ours, so is its owner
+        let owner = std::fs::metadata(&path)?.uid();
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let subscribers: Subscribers = Arc::default();
+        let client_subscribers = subscribers.clone();
+        let access = Arc::new(Mutex::new(access));
+        let client_access = access.clone();
+        let next_id = Arc::new(AtomicU64::new(1));
+        let client_next_id = next_id.clone();
+        let requests = tx.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let peer = match stream.peer_cred() {
+                            Ok(peer) => peer,
+                            Err(e) => {
+                                warn!("IPC peer credentials unavailable, refusing client: {}", e);
+                                continue;
+                            }
+                        };
+                        if peer.uid() != owner && !client_access.lock().unwrap().allowed_uids.contains(&peer.uid()) {
+                            warn!("Refusing IPC client of uid {} (pid {:?})", peer.uid(), peer.pid());
+                            continue;
+                        }
+                        let id = client_next_id.fetch_add(1, Ordering::Relaxed);
+                        debug!("IPC client {} is pid {:?}", id, peer.pid());
+                        tokio::spawn(serve_client(id, stream, tx.clone(), client_subscribers.clone(), client_access.clone()));
+                    }
+                    Err(e) => {
+                        warn!("IPC accept failed: {}", e);
+                        tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                    }
+                }
+            }
+        });
+
+        info!("IPC server listening on {:?}", path);
+        Ok((Self { path, subscribers, access, requests, next_id }, rx))
+    }
+
+    /// Queue an event for every subscribed client that wants it
+    pub fn emit(&self, event: WmEvent) {
+        if let WmEvent::PointerMoved { .. } = event {
+            trace!("IPC event: {:?}", event);
+        } else {
+            debug!("IPC event: {:?}", event);
+        }
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|id, subscriber| {
+            if !subscriber.wants(&event) {
+                return true;
+            }
+            match subscriber.queue.try_send(event.clone()) {
+                Ok(()) => {
+                    subscriber.missed = 0;
+                    true
+                }
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    subscriber.missed += 1;
+                    if subscriber.missed == 1 {
+                        warn!("IPC client {} too slow, dropping events", id);
+                    }
+                    if subscriber.missed >= MAX_MISSED {
+                        // Dropping the queue ends the connection's event stream
+                        warn!("IPC client {} missed {} events in a row, disconnecting", id, subscriber.missed);
+                        return false;
+                    }
+                    true
+                }
+                // Connection already gone
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+            }
+        });
+    }
+
+    /// Channel for submitting commands to the main loop, unchecked (callers
+    /// apply `access` themselves)
+    pub fn requests(&self) -> mpsc::UnboundedSender<IpcRequest> {
+        self.requests.clone()
+    }
+
+    /// Access control settings, kept current across config reloads
+    pub fn access(&self) -> Arc<Mutex<IpcConfig>> {
+        self.access.clone()
+    }
+
+    /// Receive every event except `PointerMoved`, with all capabilities,
+    /// like a connected client (for in-process frontends such as D-Bus)
+    pub fn subscribe(&self) -> mpsc::Receiver<WmEvent> {
+        let (queue, rx) = mpsc::channel(EVENT_QUEUE);
+        let capabilities = area_ipc::CAPABILITIES.to_vec();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.subscribers.lock().unwrap().insert(id, Subscriber { queue, pointer: false, capabilities, missed: 0 });
+        rx
+    }
+
+    /// Apply new access control settings (after a config reload)
+    pub fn set_access(&self, access: IpcConfig) {
+        *self.access.lock().unwrap() = access;
+    }
+
+    /// Number of clients receiving events
+    pub fn subscribers(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+
+    /// Whether any client wants `PointerMoved` events (so the pointer is worth sampling)
+    pub fn wants_pointer(&self) -> bool {
+        self.subscribers.lock().unwrap().values().any(|s| s.pointer)
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Capabilities enabled for a client after its `Hello`
+///
+/// An incompatible client is still answered (so it can report the mismatch)
+/// but treated like one that skipped the handshake.
+fn negotiate(version: &str, requested: &[String]) -> Vec<&'static str> {
+    match Version::parse(version) {
+        Some(version) if version.is_compatible(&area_ipc::PROTOCOL_VERSION) => {
+            debug!("IPC client speaks protocol {}", version);
+            area_ipc::CAPABILITIES.iter().copied().filter(|c| requested.iter().any(|r| r == c)).collect()
+        }
+        _ => {
+            warn!("IPC client speaks protocol {:?}, incompatible with {}", version, area_ipc::PROTOCOL_VERSION);
+            Vec::new()
+        }
+    }
+}
+
+/// Name of a command as sent on the wire (e.g. "get_windows")
+fn command_name(command: &ShellCommand) -> String {
+    serde_json::to_value(command)
+        .ok()
+        .and_then(|value| value.get("command")?.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+/// Whether a client without the token may run `command`
+pub fn untrusted_allowed(access: &IpcConfig, command: &ShellCommand) -> bool {
+    access.token.is_none() || access.untrusted_commands.contains(&command_name(command))
+}
+
+/// Serve one client connection until it disconnects
+///
+/// Frames are read on a separate task so a pending read never blocks (or is
+/// cancelled by) event delivery to a subscribed client.
+async fn serve_client(
+    id: u64,
+    stream: UnixStream,
+    requests: mpsc::UnboundedSender<IpcRequest>,
+    subscribers: Subscribers,
+    access: Arc<Mutex<IpcConfig>>,
+) {
+    debug!("IPC client {} connected", id);
+    let (mut reader, mut writer) = stream.into_split();
+
+    let (command_tx, mut command_rx) = mpsc::channel::<io::Result<ShellCommand>>(16);
+    let read_task = tokio::spawn(async move {
+        loop {
+            let result = FramedMessage::read_async(&mut reader).await;
+            // A frame that didn't decode was still consumed, keep reading;
+            // after a bad length prefix the stream is out of sync
+            let fatal = matches!(&result, Err(e) if e.kind() != io::ErrorKind::InvalidData || FramedMessage::is_framing_error(e));
+            if command_tx.send(result).await.is_err() || fatal {
+                break;
+            }
+        }
+    });
+
+    let mut subscription: Option<mpsc::Receiver<WmEvent>> = None;
+    // Capabilities announced in `Hello` and also supported here
+    let mut capabilities: Vec<&'static str> = Vec::new();
+    // Token presented in `Hello`
+    let mut token: Option<String> = None;
+    loop {
+        let message = tokio::select! {
+            command = command_rx.recv() => {
+                let command = match command {
+                    Some(Ok(command)) => command,
+                    Some(Err(e)) if e.kind() == io::ErrorKind::InvalidData => {
+                        let reply = Reply::error(format!("invalid command: {}", e));
+                        if FramedMessage::write_async(&mut writer, &WmMessage::Reply(reply)).await.is_err() {
+                            break;
+                        }
+                        if FramedMessage::is_framing_error(&e) {
+                            warn!("IPC client {} sent a bad frame ({}), disconnecting", id, e);
+                            break;
+                        }
+                        continue;
+                    }
+                    Some(Err(e)) => {
+                        if e.kind() != io::ErrorKind::UnexpectedEof {
+                            debug!("IPC read failed: {}", e);
+                        }
+                        break;
+                    }
+                    None => break,
+                };
+
+                let received = Instant::now();
+                if let ShellCommand::Hello { token: ref presented, .. } = command {
+                    token = presented.clone();
+                }
+                // Checked on every command, so a reload setting a token applies at once
+                let name = command_name(&command);
+                let (trusted, allowed) = {
+                    let access = access.lock().unwrap();
+                    let trusted = access.token.is_none() || token == access.token;
+                    (trusted, trusted || access.untrusted_commands.contains(&name))
+                };
+                // Hello and Subscribe are per-connection state, no need to involve the main loop
+                let reply = if !allowed {
+                    warn!("IPC client {} is not allowed to run {}", id, name);
+                    Reply::error(format!("{} requires the IPC token", name))
+                } else if let ShellCommand::Hello { version, capabilities: requested, .. } = command {
+                    capabilities = negotiate(&version, &requested);
+                    if let Some(subscriber) = subscribers.lock().unwrap().get_mut(&id) {
+                        subscriber.capabilities = capabilities.clone();
+                    }
+                    Reply::Welcome {
+                        version: area_ipc::PROTOCOL_VERSION.to_string(),
+                        capabilities: area_ipc::CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+                        trusted,
+                    }
+                } else if let ShellCommand::Subscribe | ShellCommand::SubscribePointer = command {
+                    let pointer = matches!(command, ShellCommand::SubscribePointer);
+                    let mut subscribers = subscribers.lock().unwrap();
+                    match subscribers.get_mut(&id) {
+                        Some(subscriber) => subscriber.pointer |= pointer,
+                        None => {
+                            let (queue, rx) = mpsc::channel(EVENT_QUEUE);
+                            subscription = Some(rx);
+                            let capabilities = capabilities.clone();
+                            subscribers.insert(id, Subscriber { queue, pointer, capabilities, missed: 0 });
+                        }
+                    }
+                    Reply::Ok
+                } else {
+                    let (reply_tx, reply_rx) = oneshot::channel();
+                    if requests.send(IpcRequest { command, reply: reply_tx }).is_err() {
+                        break;
+                    }
+                    reply_rx
+                        .await
+                        .unwrap_or_else(|_| Reply::error("window manager is shutting down"))
+                };
+
+                if let Err(e) = FramedMessage::write_async(&mut writer, &WmMessage::Reply(reply)).await {
+                    debug!("IPC write failed: {}", e);
+                    break;
+                }
+                crate::metrics::global().record_ipc_round_trip(received.elapsed());
+                continue;
+            }
+
+            event = async {
+                match subscription.as_mut() {
+                    Some(rx) => rx.recv().await,
+                    None => std::future::pending().await,
+                }
+            } => match event {
+                Some(event) => WmMessage::Event(event),
+                // Dropped by `emit` for falling too far behind
+                None => break,
+            },
+        };
+
+        if let Err(e) = FramedMessage::write_async(&mut writer, &message).await {
+            debug!("IPC write failed: {}", e);
+            break;
+        }
+    }
+
+    read_task.abort();
+    subscribers.lock().unwrap().remove(&id);
+    debug!("IPC client {} disconnected", id);
+}
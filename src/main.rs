@@ -6,42 +6,42 @@
 mod wm;
 mod compositor;
 mod shared;
+mod nested;
 mod shell;
 mod dbus;
 mod x11_async;
 mod config;
 mod input;
+mod replay;
+mod diagnostics;
+mod metrics;
+mod ipc;
+mod hooks;
+mod supervisor;
+mod environment;
+mod audio;
 
 use anyhow::{Context, Result};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use x11rb::connection::Connection;
-use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, ConfigureWindowAux};
+use x11rb::protocol::xproto::{
+    Allow, AtomEnum, ButtonPressEvent, ConnectionExt, ConfigureWindowAux, EventMask, GrabMode, GrabStatus, ModMask, NotifyDetail,
+    NotifyMode, PropMode,
+};
+use x11rb::wrapper::ConnectionExt as _;
 use x11rb::protocol::Event;
 use wm::client::Client;
 use compositor::c_window::CWindow;
 
-// #region agent log
-fn debug_log(location: &str, message: &str, data: serde_json::Value, hypothesis_id: &str) {
-    use std::fs::OpenOptions;
-    use std::io::Write;
-    let log_entry = serde_json::json!({
-        "sessionId": "debug-session",
-        "runId": "run1",
-        "hypothesisId": hypothesis_id,
-        "location": location,
-        "message": message,
-        "data": data,
-        "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()
-    });
-    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("/home/bizkit/GitHub/area/.cursor/debug.log") {
-        let _ = writeln!(file, "{}", log_entry);
-    }
+/// An `EndSession` waiting for windows to close
+struct PendingEndSession {
+    action: area_ipc::SessionAction,
+    /// When to report the windows still open; None once reported
+    deadline: Option<Instant>,
 }
-// #endregion
 
 /// Main application state
 struct AreaApp {
@@ -57,12 +57,21 @@ struct AreaApp {
     /// WM Clients (Window Manager state)
     wm_windows: HashMap<u32, Client>,
     
+    /// Experimental per-output scales (restores the layout when dropped)
+    output_scaling: wm::scaling::OutputScaling,
+    
     /// Window manager state
     wm: wm::WindowManager,
     
+    /// Key bindings grabbed on the root window
+    keyboard: wm::keyboard::KeyboardManager,
+    
     /// Compositor state
     compositor: compositor::Compositor,
     
+    /// Wallpaper published for pseudo-transparent clients
+    root_pixmap: Option<Arc<compositor::wallpaper::RootPixmap>>,
+    
     /// Shell state
     shell: shell::Shell,
     
@@ -91,12 +100,80 @@ struct AreaApp {
     /// Frame windows created by the WM (to prevent recursive management)
     frame_windows: HashSet<u32>,
     
+    /// Mapped override-redirect windows (menus, tooltips) handed to the compositor
+    popups: compositor::popups::Popups,
+    
+    /// When the on-screen display is hidden again
+    osd_deadline: Option<Instant>,
+    /// Drop target shown while a window is dragged onto another monitor
+    drag_preview: Option<shared::Geometry>,
+    
     /// Last titlebar click for double-click detection
     last_titlebar_click: Option<(u32, u32, i16, i16)>, // (window_id, time, x, y)
     
-    /// DISPLAY value to use when spawning child processes
-    /// This ensures child processes connect to the same X server as Area
-    display: String,
+    /// Environment of spawned child processes, so they connect to the same
+    /// X server as Area (see `environment`)
+    environment: environment::SessionEnvironment,
+    
+    /// Event recorder (enabled with --record <file>)
+    recorder: Option<replay::EventRecorder>,
+    /// Running a recording against the mock X server (see `replay`)
+    replaying: bool,
+    
+    /// IPC socket server (area-msg, shell clients)
+    ipc: Option<ipc::IpcServer>,
+    /// Commands received over IPC, answered from the main loop
+    ipc_rx: Option<tokio::sync::mpsc::UnboundedReceiver<ipc::IpcRequest>>,
+    /// Desktop notifications received by the notification server
+    notification_rx: Option<tokio::sync::mpsc::UnboundedReceiver<dbus::notification_server::NotificationMessage>>,
+    /// Idle inhibition requests from the screen saver service
+    screensaver_rx: Option<tokio::sync::mpsc::UnboundedReceiver<dbus::screensaver::ScreenSaverMessage>>,
+    /// Playback stream lists from the audio monitor
+    audio_rx: Option<tokio::sync::mpsc::UnboundedReceiver<Vec<audio::AudioStream>>>,
+    /// Current playback streams
+    audio_streams: Vec<audio::AudioStream>,
+    /// Last reported (playing, muted) of windows with streams
+    window_audio: HashMap<u32, (bool, bool)>,
+    /// Windows whose maximized/fullscreen/shaded/above state changed since the last `WindowStateChanged`
+    window_state_changed: HashSet<u32>,
+    /// Set to leave the main loop (root menu "Exit")
+    exit_requested: bool,
+    /// Session end in progress (see `begin_end_session`)
+    end_session: Option<PendingEndSession>,
+    /// Workspace whose settings profile is applied (None forces re-applying it)
+    profile_workspace: Option<u32>,
+    /// Compositor windows bottom to top, as last sent for occlusion culling
+    compositor_stacking: Vec<u32>,
+    /// Last UPower battery state passed to the compositor's frame pacing
+    on_battery: bool,
+    /// Monitors covered by a fullscreen window, as last sent in `FullscreenChanged`
+    fullscreen_monitors: HashSet<u32>,
+    /// Brightness of the clients dimmed as inactive
+    dimmed: HashMap<u32, f32>,
+    /// Windows streaming an Alt+Tab thumbnail
+    cycle_previews: Vec<u32>,
+    /// Clients unredirected by a `[[compositor.rules]]` entry
+    rule_unredirected: HashSet<u32>,
+    /// Managed windows bottom to top, as last sent in `ClientListStacking`
+    client_list_stacking: Vec<u32>,
+    /// Window and size in increments last sent in `ResizeFeedback`
+    resize_feedback: Option<(u32, u32, u32)>,
+    /// Last `WorkspaceLayout` sent and the subscriber count at that time
+    workspace_layout: Option<(usize, Vec<area_ipc::WorkspaceInfo>)>,
+    /// User scripts run on window manager events
+    hooks: hooks::Hooks,
+    /// Windows managed since hooks last ran
+    opened_windows: Vec<u32>,
+    /// Focused window and current workspace as last seen by hooks
+    hook_focus: Option<u32>,
+    hook_workspace: u32,
+    /// Session components kept running (`[[components]]`)
+    supervisor: supervisor::Supervisor,
+    /// Component state changes from the supervisor
+    component_rx: tokio::sync::mpsc::UnboundedReceiver<area_ipc::ComponentStatus>,
+    /// `WindowClosedSnapshot` events, sent once the compositor wrote the file
+    closed_snapshot_tx: tokio::sync::mpsc::UnboundedSender<area_ipc::WmEvent>,
+    closed_snapshot_rx: tokio::sync::mpsc::UnboundedReceiver<area_ipc::WmEvent>,
 }
 
 impl AreaApp {
@@ -104,15 +181,34 @@ impl AreaApp {
     /// 
     /// # Arguments
     /// * `replace` - If true, attempt to replace existing WM
-    async fn new(replace: bool) -> Result<Self> {
-        // Connect to X11
-        let (conn, screen_num) = x11rb::connect(None)
-            .context("Failed to connect to X server")?;
-        
-        // Store DISPLAY value for spawning child processes
-        // This ensures child processes connect to the same X server as Area
-        let display_value = std::env::var("DISPLAY").unwrap_or_else(|_| ":0".into());
-        info!("Using DISPLAY={} for child processes", display_value);
+    /// * `replay` - Replay this recording against its mock X server instead
+    ///   of connecting to the display; only the WM itself is set up
+    async fn new(replace: bool, replay: Option<&mut replay::EventReplay>) -> Result<Self> {
+        let replaying = replay.is_some();
+        let (conn, screen_num, display_value, config) = match replay {
+            Some(replay) => {
+                let conn = replay.connect()?;
+                let mut config = replay.header.config.clone();
+                // setxkbmap would change the keymap of a real display
+                config.input.keyboard.options.clear();
+                (conn, replay.header.screen, replay.header.display.clone(), config)
+            }
+            None => {
+                // Connect to X11
+                let (conn, screen_num) = x11rb::connect(None)
+                    .context("Failed to connect to X server")?;
+                
+                // Store DISPLAY value for spawning child processes
+                // This ensures child processes connect to the same X server as Area
+                let display_value = std::env::var("DISPLAY").unwrap_or_else(|_| ":0".into());
+                info!("Using DISPLAY={} for child processes", display_value);
+                
+                // Load configuration
+                let config = config::Config::load()
+                    .context("Failed to load configuration")?;
+                (conn, screen_num, display_value, config)
+            }
+        };
         
         let conn = Arc::new(conn);
         let screen = &conn.as_ref().setup().roots[screen_num];
@@ -123,12 +219,22 @@ impl AreaApp {
         info!("Connected to X server, screen {}, root window {}", screen_num, root);
         info!("Screen size: {}x{}", screen_width, screen_height);
         
-        // Load configuration
-        let config = config::Config::load()
-            .context("Failed to load configuration")?;
+        // Optional Prometheus endpoint for long-running profiling
+        if !replaying && let Some(ref listen) = config.metrics.prometheus_listen {
+            match listen.parse() {
+                Ok(addr) => {
+                    if let Err(e) = metrics::spawn_prometheus_endpoint(addr).await {
+                        warn!("Failed to start metrics endpoint on {}: {}", listen, e);
+                    }
+                }
+                Err(e) => warn!("Invalid metrics.prometheus_listen {:?}: {}", listen, e),
+            }
+        }
         
         // Initialize input manager and apply mouse configuration
-        if let Ok(input_manager) = input::InputManager::new(conn.clone()) {
+        if replaying {
+            debug!("Replay: leaving input devices alone");
+        } else if let Ok(input_manager) = input::InputManager::new(conn.clone()) {
             if let Err(e) = input_manager.apply_mouse_config(&config.input.mouse) {
                 warn!("Failed to apply mouse configuration: {}", e);
             }
@@ -142,28 +248,51 @@ impl AreaApp {
         info!("X11 async event stream initialized");
         
         // Initialize window manager
-        let wm = wm::WindowManager::new(&conn, screen_num, root, replace)
+        let mut wm = wm::WindowManager::new(&conn, screen_num, root, replace)
             .context("Failed to initialize window manager")?;
+        wm.apply_config(&conn, &mut HashMap::new(), &config)
+            .context("Failed to set up workspaces")?;
+        
+        let mut keyboard = wm::keyboard::KeyboardManager::new(&conn, root)
+            .context("Failed to set up key bindings")?;
+        if let Err(e) = keyboard.set_chords(&conn, root, &config.keybindings.chords) {
+            warn!("Failed to set up key chords: {}", e);
+        }
         
         // Initialize shell
         let shell = shell::Shell::new(screen_width, screen_height, config.panel.clone());
         
         // Initialize compositor (spawns in separate thread)
-        let compositor = compositor::Compositor::spawn(conn.clone(), screen_num, root)
-            .context("Failed to initialize compositor")?;
-        
-        // Initialize D-Bus (optional, won't fail if D-Bus unavailable)
-        let dbus = match dbus::DbusManager::new().await {
-            Ok(d) => {
-                info!("D-Bus initialized");
-                Some(d)
-            }
+        let compositor = if replaying {
+            compositor::Compositor::detached()
+        } else {
+            compositor::Compositor::spawn(conn.clone(), screen_num, root, config.compositor.deep_color)
+                .context("Failed to initialize compositor")?
+        };
+        let root_pixmap = match compositor::wallpaper::RootPixmap::new(conn.clone(), screen_num) {
+            Ok(root_pixmap) => Some(Arc::new(root_pixmap)),
             Err(e) => {
-                warn!("D-Bus unavailable: {}. Desktop services disabled.", e);
+                warn!("Root pixmap publication unavailable: {}", e);
                 None
             }
         };
         
+        // Initialize D-Bus (optional, won't fail if D-Bus unavailable)
+        let dbus = if replaying {
+            None
+        } else {
+            match dbus::DbusManager::new().await {
+                Ok(d) => {
+                    info!("D-Bus initialized");
+                    Some(d)
+                }
+                Err(e) => {
+                    warn!("D-Bus unavailable: {}. Desktop services disabled.", e);
+                    None
+                }
+            }
+        };
+        
         // Initialize desktop services
         let notifications = if let Some(ref dbus) = dbus {
             match dbus::notifications::NotificationService::new(dbus.connection()).await {
@@ -189,13 +318,86 @@ impl AreaApp {
             None
         };
         
+        // Initialize IPC socket (optional, area-msg won't work without it)
+        let (ipc_server, ipc_rx) = match (!replaying).then(|| ipc::IpcServer::spawn(config.ipc.clone())) {
+            Some(Ok((server, rx))) => (Some(server), Some(rx)),
+            Some(Err(e)) => {
+                warn!("IPC unavailable: {}", e);
+                (None, None)
+            }
+            None => (None, None),
+        };
+        
+        // Without a socket there's no token to present, gate on the config alone
+        let ipc_access = ipc_server
+            .as_ref()
+            .map(|ipc| ipc.access())
+            .unwrap_or_else(|| Arc::new(std::sync::Mutex::new(config.ipc.clone())));
+        if let Some(ref dbus) = dbus
+            && let Err(e) = dbus::diagnostics::serve(dbus.connection(), ipc_access.clone()).await
+        {
+            warn!("D-Bus diagnostics interface unavailable: {}", e);
+        }
+        
+        if let (Some(dbus), Some(ipc)) = (&dbus, &ipc_server)
+            && let Err(e) = dbus::window_manager::serve(dbus.connection(), ipc.requests(), ipc.access(), ipc.subscribe()).await
+        {
+            warn!("D-Bus window manager interface unavailable: {}", e);
+        }
+        
+        if let Some(ref dbus) = dbus
+            && let Err(e) = dbus::portal::serve(dbus.connection(), compositor.clone(), conn.clone(), root).await
+        {
+            warn!("Screenshot portal unavailable: {}", e);
+        }
+        
+        let notification_rx = match &dbus {
+            Some(dbus) if config.notifications.server => {
+                match dbus::notification_server::serve(dbus.connection(), wm.notifications.next_id()).await {
+                    Ok(rx) => Some(rx),
+                    Err(e) => {
+                        warn!("Notification server unavailable: {:#}", e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        
+        let screensaver_rx = match &dbus {
+            Some(dbus) if config.dpms.inhibit_service => {
+                match dbus::screensaver::serve(dbus.connection()).await {
+                    Ok(rx) => Some(rx),
+                    Err(e) => {
+                        warn!("Screen saver inhibit service unavailable: {:#}", e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        
+        let audio_rx = (config.audio.window_indicators && !replaying).then(audio::watch);
+        
+        // Replay must not run scripts or start programs
+        let environment = environment::SessionEnvironment::new(&display_value, &config.environment);
+        let hook_config = if replaying { Default::default() } else { config.hooks.clone() };
+        let hooks = hooks::Hooks::new(hook_config, ipc_server.as_ref().map(|ipc| ipc.requests()), environment.clone());
+        let hook_workspace = wm.workspaces.current_workspace;
+        let components = if replaying { &[][..] } else { &config.components[..] };
+        let (supervisor, component_rx) = supervisor::Supervisor::new(components, &environment);
+        let (closed_snapshot_tx, closed_snapshot_rx) = tokio::sync::mpsc::unbounded_channel();
+        
         let mut app = Self {
             conn: conn.clone(),
             x11_stream,
             root,
             wm_windows: HashMap::new(),
+            output_scaling: wm::scaling::OutputScaling::new(conn.clone(), root),
             wm,
+            keyboard,
             compositor,
+            root_pixmap,
             shell,
             last_frame: Instant::now(),
             screen_width,
@@ -206,8 +408,41 @@ impl AreaApp {
             power,
             reparenting_windows: HashSet::new(),
             frame_windows: HashSet::new(),
+            popups: compositor::popups::Popups::default(),
+            osd_deadline: None,
+            drag_preview: None,
             last_titlebar_click: None,
-            display: display_value.clone(),
+            environment,
+            recorder: None,
+            replaying,
+            ipc: ipc_server,
+            ipc_rx,
+            notification_rx,
+            screensaver_rx,
+            audio_rx,
+            audio_streams: Vec::new(),
+            window_audio: HashMap::new(),
+            window_state_changed: HashSet::new(),
+            exit_requested: false,
+            end_session: None,
+            profile_workspace: None,
+            compositor_stacking: Vec::new(),
+            client_list_stacking: Vec::new(),
+            fullscreen_monitors: HashSet::new(),
+            dimmed: HashMap::new(),
+            cycle_previews: Vec::new(),
+            rule_unredirected: HashSet::new(),
+            resize_feedback: None,
+            workspace_layout: None,
+            on_battery: false,
+            hooks,
+            opened_windows: Vec::new(),
+            hook_focus: None,
+            hook_workspace,
+            supervisor,
+            component_rx,
+            closed_snapshot_tx,
+            closed_snapshot_rx,
         };
         
         // Show startup notification
@@ -220,6 +455,13 @@ impl AreaApp {
         
         // Scan for existing windows
         app.scan_existing_windows()?;
+        // Hooks are for windows opened from now on
+        app.opened_windows.clear();
+        app.apply_output_scales();
+        app.compositor.set_frame_rate(app.config.compositor.frame_rate);
+        app.compositor.set_effects(app.config.compositor.effects.clone());
+        app.apply_work_area();
+        app.apply_workspace_profile();
         
         Ok(app)
     }
@@ -241,10 +483,14 @@ impl AreaApp {
             }
             
             // Get window attributes to check if it's a valid window to manage
-            if let Ok(attrs) = self.conn.as_ref().get_window_attributes(window_id)?.reply() {
-                // Skip override-redirect windows (popups, tooltips, etc.)
+            let attrs = self.conn.as_ref().get_window_attributes(window_id)?.reply();
+            if let Ok(attrs) = attrs {
+                // Override-redirect windows (popups, tooltips, etc.) are only composited
                 if attrs.override_redirect {
                     debug!("Skipping override-redirect window {}", window_id);
+                    if attrs.map_state == x11rb::protocol::xproto::MapState::VIEWABLE {
+                        self.add_popup(window_id)?;
+                    }
                     continue;
                 }
                 
@@ -297,7 +543,8 @@ impl AreaApp {
         info!("Overlay window ID: {}", self.compositor.overlay_window);
         
         // Event buffer for batching events (LeftWM pattern)
-        let mut event_buffer: Vec<Event> = Vec::new();
+        // Each event carries the time it was read, for latency metrics
+        let mut event_buffer: Vec<x11_async::QueuedEvent> = Vec::new();
         let mut needs_render = false; // Will be set to true when events require rendering
         let mut should_exit = false; // Flag to signal clean exit when connection is lost
         
@@ -313,12 +560,32 @@ impl AreaApp {
         let mut perf_log_interval = tokio::time::interval(Duration::from_secs(5));
         perf_log_interval.tick().await;
         
+        // Battery state for frame rate throttling (UPower caches the property)
+        let mut battery_interval = tokio::time::interval(Duration::from_secs(5));
+        battery_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        
+        // Unresponsive window detection (_NET_WM_PING)
+        let mut ping_interval = tokio::time::interval(Duration::from_secs(1));
+        ping_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        
+        // Pointer sampling for IPC PointerMoved subscribers
+        let mut pointer_interval = tokio::time::interval(ipc::POINTER_INTERVAL);
+        pointer_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut last_pointer: Option<(i32, i32)> = None;
+        
+        // Pager mini-map updates for IPC subscribers
+        let mut layout_interval = tokio::time::interval(ipc::LAYOUT_INTERVAL);
+        layout_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        
+        // Alt+Tab thumbnails stop streaming unless requested again
+        let mut cycle_preview_interval = tokio::time::interval(compositor::preview::LIVE_PREVIEW_DURATION / 2);
+        
         // Trigger initial render (compositor handles rendering in its own thread)
         self.compositor.trigger_render();
         
         loop {
             // Check exit flag
-            if should_exit {
+            if should_exit || self.exit_requested {
                 info!("Exiting main loop");
                 return Ok(());
             }
@@ -326,9 +593,8 @@ impl AreaApp {
             // Flush X11 requests at start of loop (LeftWM pattern - batch optimization)
             if let Err(e) = self.x11_stream.flush() {
                 // Check if connection is broken - if so, exit cleanly
-                let error_str = e.to_string();
-                if error_str.contains("Broken pipe") || error_str.contains("Connection reset") {
-                    info!("X11 connection lost, exiting cleanly");
+                if e.policy() == x11_async::ErrorPolicy::Shutdown {
+                    info!("{}, exiting cleanly", e);
                     should_exit = true;
                     continue;
                 }
@@ -341,19 +607,34 @@ impl AreaApp {
                 continue;
             }
             
+            // A pending session end completes once its windows are gone
+            if self.end_session.is_some() && self.session_windows().is_empty() {
+                self.finish_end_session().await;
+                continue;
+            }
+            
+            // A dragged window held against a screen edge flips workspaces
+            let edge_flip_deadline = self.wm.edge_flip_deadline();
+            let chord_deadline = self.keyboard.chord_deadline();
+            let end_session_deadline = self.end_session.as_ref().and_then(|pending| pending.deadline);
+            let popup_deadline = self.popups.next_promotion();
+            let osd_deadline = self.osd_deadline;
+            let raise_deadline = self.wm.focus.raise_deadline();
+            
             tokio::select! {
                 // Wait for X11 events (only when buffer is empty)
                 () = self.x11_stream.wait_readable() => {
                     // Collect all pending events (non-blocking loop)
                     loop {
-                        match self.x11_stream.poll_next_event() {
-                            Ok(Some(event)) => event_buffer.push(event),
+                        match self.x11_stream.poll_next_raw_event() {
+                            Ok(Some((event, raw))) => {
+                                event_buffer.push(x11_async::QueuedEvent { event, raw, received: Instant::now() });
+                            }
                             Ok(None) => break,
                             Err(e) => {
                                 // Check if connection is broken
-                                let error_str = e.to_string();
-                                if error_str.contains("Broken pipe") || error_str.contains("Connection reset") {
-                                    error!("X11 connection lost, exiting cleanly");
+                                if e.policy() == x11_async::ErrorPolicy::Shutdown {
+                                    error!("{}, exiting cleanly", e);
                                     should_exit = true;
                                     break;
                                 }
@@ -365,6 +646,77 @@ impl AreaApp {
                     // Process events in next iteration
                 }
                 
+                // IPC commands (area-msg, shell clients)
+                Some(request) = async {
+                    match self.ipc_rx.as_mut() {
+                        Some(rx) => rx.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    if let Some(ref mut recorder) = self.recorder
+                        && let Err(e) = recorder.record(replay::RecordedInput::Ipc { command: request.command.clone() })
+                    {
+                        warn!("Failed to record IPC command, recording stopped: {}", e);
+                        self.recorder = None;
+                    }
+                    let reply = self.handle_ipc_command(request.command);
+                    self.apply_pending_changes();
+                    let _ = request.reply.send(reply);
+                    needs_render = true;
+                }
+                
+                // Desktop notifications from applications
+                Some(message) = async {
+                    match self.notification_rx.as_mut() {
+                        Some(rx) => rx.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    self.handle_notification(message);
+                }
+                
+                // Applications keeping the screen on
+                Some(message) = async {
+                    match self.screensaver_rx.as_mut() {
+                        Some(rx) => rx.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    if let Err(e) = self.handle_screensaver(message) {
+                        warn!("Failed to update idle inhibition: {:#}", e);
+                    }
+                }
+                
+                // Playback streams changed
+                Some(streams) = async {
+                    match self.audio_rx.as_mut() {
+                        Some(rx) => rx.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    self.audio_streams = streams;
+                    self.update_window_audio();
+                }
+                
+                // setxkbmap finished, the key remaps go on the new keymap
+                result = self.wm.key_remap.options_done() => {
+                    if let Err(e) = self.wm.key_remap.options_set(&self.conn, result) {
+                        warn!("Failed to remap keys: {}", e);
+                    }
+                }
+                
+                // The compositor kept the last frame of a closed window
+                Some(event) = self.closed_snapshot_rx.recv() => {
+                    self.emit_event(event);
+                }
+                
+                // A session component started, crashed or stopped
+                Some(component) = self.component_rx.recv() => {
+                    if self.supervisor.update(&component) {
+                        self.emit_event(area_ipc::WmEvent::ComponentStatusChanged { component });
+                    }
+                }
+                
                 // Render when needed (damage-based, but immediate for cursor)
                 _ = async {
                     if needs_render {
@@ -380,6 +732,60 @@ impl AreaApp {
                     needs_render = false;
                 }
                 
+                // Edge flip while dragging
+                _ = tokio::time::sleep_until(edge_flip_deadline.unwrap_or_else(Instant::now).into()), if edge_flip_deadline.is_some() => {
+                    match self.wm.flip_drag_edge(&self.conn, &mut self.wm_windows) {
+                        Ok(changed) => self.apply_workspace_visibility(changed),
+                        Err(e) => warn!("Edge flip failed: {}", e),
+                    }
+                    needs_render = true;
+                }
+                
+                // Auto-raise of a window focused by the pointer
+                _ = tokio::time::sleep_until(raise_deadline.unwrap_or_else(Instant::now).into()), if raise_deadline.is_some() => {
+                    self.wm.auto_raise(&self.wm_windows);
+                    self.apply_pending_changes();
+                    needs_render = true;
+                }
+                
+                // Throttle the compositor while discharging
+                _ = battery_interval.tick(), if self.power.is_some() => {
+                    self.update_battery_state().await;
+                }
+                
+                // _NET_WM_PING: ping the focused window, detect unresponsive ones
+                _ = ping_interval.tick() => {
+                    self.check_pings();
+                    self.apply_pending_changes();
+                }
+                
+                // Windows that ignored WM_DELETE_WINDOW during a session end
+                _ = tokio::time::sleep_until(end_session_deadline.unwrap_or_else(Instant::now).into()), if end_session_deadline.is_some() => {
+                    self.end_session_timed_out();
+                }
+                
+                // Directly drawn popups that stayed open get composited
+                _ = tokio::time::sleep_until(popup_deadline.unwrap_or_else(Instant::now).into()), if popup_deadline.is_some() => {
+                    for window in self.popups.promote(Instant::now()) {
+                        debug!("Popup {} stayed open, compositing it", window);
+                        self.compositor.redirect_window(window);
+                    }
+                }
+                
+                // The next key of a chord never came
+                _ = tokio::time::sleep_until(chord_deadline.unwrap_or_else(Instant::now).into()), if chord_deadline.is_some() => {
+                    debug!("Chord timed out");
+                    self.keyboard.cancel_chord();
+                    if let Err(e) = self.end_chord() {
+                        warn!("Failed to end chord: {}", e);
+                    }
+                }
+                
+                _ = tokio::time::sleep_until(osd_deadline.unwrap_or_else(Instant::now).into()), if osd_deadline.is_some() => {
+                    self.osd_deadline = None;
+                    self.compositor.set_osd(None);
+                }
+                
                 // Fallback: render at least once per second (for animations, cursor updates, etc.)
                 _ = fallback_render_interval.tick() => {
                     // Only render if there are animations or if we haven't rendered recently
@@ -403,12 +809,26 @@ impl AreaApp {
                     }
                 }
                 
+                // Pointer position stream (only sampled while someone listens)
+                _ = pointer_interval.tick() => {
+                    if let Err(e) = self.sample_pointer(&mut last_pointer) {
+                        debug!("Failed to sample pointer: {}", e);
+                    }
+                }
+                
+                _ = layout_interval.tick() => {
+                    self.publish_workspace_layout();
+                }
+                
+                _ = cycle_preview_interval.tick(), if !self.cycle_previews.is_empty() => {
+                    self.start_cycle_previews();
+                }
+                
                 // Periodic scan for unmanaged windows
                 _ = scan_interval.tick() => {
                     if let Err(e) = self.scan_for_unmanaged_windows() {
                         // Check if connection is broken - if so, exit cleanly
-                        let error_str = e.to_string();
-                        if error_str.contains("Broken pipe") || error_str.contains("Connection reset") {
+                        if x11_async::X11Error::policy_of(&e) == x11_async::ErrorPolicy::Shutdown {
                             info!("X11 connection lost during window scan, exiting cleanly");
                             should_exit = true;
                         } else {
@@ -421,13 +841,34 @@ impl AreaApp {
     }
     
     /// Execute buffered events (LeftWM drain pattern)
-    async fn execute_events(&mut self, event_buffer: &mut Vec<Event>, needs_render: &mut bool) {
+    async fn execute_events(&mut self, event_buffer: &mut Vec<x11_async::QueuedEvent>, needs_render: &mut bool) {
+        // Collapse motion/configure/expose floods, e.g. during a fast drag
+        x11_async::compress_events(event_buffer);
+        
+        // Record what is actually handled, so replay sees the compressed stream
+        if let Some(ref mut recorder) = self.recorder {
+            let recorded = event_buffer.iter().try_for_each(|queued| recorder.record_x11(&queued.raw));
+            if let Err(e) = recorded.and_then(|()| recorder.flush()) {
+                warn!("Failed to record events, recording stopped: {}", e);
+                self.recorder = None;
+            }
+        }
+        
         // Process all buffered events at once (LeftWM drain pattern)
         // Note: We process events sequentially to maintain order and state consistency
-        for event in event_buffer.drain(..) {
-            if let Err(e) = self.handle_event(event).await {
+        for x11_async::QueuedEvent { event, received, .. } in event_buffer.drain(..) {
+            let result = self.handle_event(event).await;
+            self.wm.time.end_event();
+            if let Err(e) = result {
+                if x11_async::X11Error::policy_of(&e) == x11_async::ErrorPolicy::Shutdown {
+                    error!("X11 connection lost while handling an event, exiting");
+                    self.exit_requested = true;
+                    return;
+                }
                 error!("Error handling event: {}", e);
             }
+            self.apply_pending_changes();
+            metrics::global().record_event_latency(received.elapsed());
             // Mark that we need to render (compositor will check damage internally)
             // Note: needs_render is set to true here, but we also check compositor damage
             // The compositor thread handles its own rendering, so we just trigger it
@@ -435,6 +876,264 @@ impl AreaApp {
         }
     }
     
+    /// Apply pending z-order changes from the layer list and announce
+    /// window state changes to IPC subscribers
+    fn apply_pending_changes(&mut self) {
+        if let Err(e) = self.wm.restack_if_needed(&self.conn, &mut self.wm_windows) {
+            warn!("Failed to restack windows: {}", e);
+        }
+        let stacking: Vec<u32> = self.wm.stacking.order().iter()
+            .filter_map(|id| self.wm_windows.get(id))
+            .map(Self::composite_id)
+            .collect();
+        if stacking != self.compositor_stacking {
+            self.compositor.set_stacking(stacking.clone());
+            self.compositor_stacking = stacking;
+        }
+        if self.wm.stacking.order() != self.client_list_stacking.as_slice() {
+            self.client_list_stacking = self.wm.stacking.order().to_vec();
+            self.emit_event(area_ipc::WmEvent::ClientListStacking { ids: self.client_list_stacking.clone() });
+        }
+        self.sync_shortcut_inhibit();
+        self.update_fullscreen_monitors();
+        self.update_pointer_confinement();
+        self.update_dimming();
+        self.update_window_audio();
+        self.run_hooks();
+        for id in std::mem::take(&mut self.window_state_changed) {
+            if let Some(client) = self.wm_windows.get(&id) {
+                let event = area_ipc::WmEvent::WindowStateChanged {
+                    id,
+                    maximized: client.is_maximized(),
+                    fullscreen: client.is_fullscreen(),
+                    shaded: client.is_shaded(),
+                    above: client.flags.contains(crate::wm::client_flags::ClientFlags::ABOVE),
+                };
+                self.emit_event(event);
+            }
+        }
+    }
+    
+    /// Report monitors a fullscreen window starts or stops covering
+    ///
+    /// A fullscreen window covers its monitor while it is above the docks
+    /// (focused, see `stacking::layer_for`) on the current workspace.
+    fn update_fullscreen_monitors(&mut self) {
+        let covered: HashSet<u32> = self.wm_windows.values()
+            .filter(|c| {
+                wm::stacking::layer_for(c) == wm::client_flags::WindowLayer::Fullscreen
+                    && self.wm.workspaces.is_visible(c)
+                    && !c.is_minimized()
+            })
+            .filter_map(|c| {
+                let g = c.geometry;
+                self.wm.monitor_at(g.x + g.width as i32 / 2, g.y + g.height as i32 / 2).map(|m| m as u32)
+            })
+            .collect();
+        if covered == self.fullscreen_monitors {
+            return;
+        }
+        for &monitor in covered.symmetric_difference(&self.fullscreen_monitors) {
+            let active = covered.contains(&monitor);
+            debug!("Fullscreen on monitor {}: {}", monitor, active);
+            self.emit_event(area_ipc::WmEvent::FullscreenChanged { monitor, active });
+        }
+        self.fullscreen_monitors = covered;
+    }
+    
+    /// Hold the pointer on the monitor of the focused window while it is a
+    /// fullscreen game (see `wm::pointer_confine`)
+    fn update_pointer_confinement(&mut self) {
+        let focused = self.wm_windows.values()
+            .find(|c| c.focused() && c.is_fullscreen() && self.wm.workspaces.is_visible(c) && !c.is_minimized())
+            .and_then(|c| {
+                let g = c.geometry;
+                Some((c.window, self.wm.monitor_at(g.x + g.width as i32 / 2, g.y + g.height as i32 / 2)?))
+            });
+        let target = focused.and_then(|(window, monitor)| {
+            Some((window, self.wm_windows.get(&window)?.class_hint.as_ref(), self.wm.monitors.get(monitor)?))
+        });
+        if let Err(e) = self.wm.pointer_confinement.update(self.conn.as_ref(), self.root, target) {
+            warn!("Failed to update pointer confinement: {}", e);
+        }
+    }
+    
+    /// Dim the windows without focus (`compositor.dim_inactive`) and
+    /// restore the ones that got it
+    fn update_dimming(&mut self) {
+        let config = &self.config.compositor.dim_inactive;
+        let brightness = 1.0 - config.amount.clamp(0.0, 1.0);
+        let excluded = |client: &Client| {
+            (config.exclude_fullscreen && client.is_fullscreen())
+                || (config.exclude_above && client.flags.contains(wm::client_flags::ClientFlags::ABOVE))
+                || client.class_hint.as_ref().is_some_and(|hint| {
+                    config.exclude_classes.iter().any(|name| name.eq_ignore_ascii_case(&hint.res_name) || name.eq_ignore_ascii_case(&hint.res_class))
+                })
+        };
+        let dimmed: HashMap<u32, f32> = if config.enabled && brightness < 1.0 {
+            self.wm_windows.values()
+                .filter(|c| !c.focused() && !excluded(c))
+                .map(|c| (c.window, brightness))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        if dimmed == self.dimmed {
+            return;
+        }
+        for client in self.wm_windows.values() {
+            let now = dimmed.get(&client.window).copied().unwrap_or(1.0);
+            if self.dimmed.get(&client.window).copied().unwrap_or(1.0) != now {
+                self.compositor.set_window_brightness(Self::composite_id(client), now);
+            }
+        }
+        self.dimmed = dimmed;
+    }
+    
+    /// Report windows that started or stopped playing sound or were (un)muted
+    fn update_window_audio(&mut self) {
+        if self.audio_rx.is_none() {
+            return;
+        }
+        let states: HashMap<u32, (bool, bool)> = audio::streams_by_window(&self.audio_streams, &self.window_pids())
+            .into_iter()
+            .map(|(window, streams)| {
+                let state = streams.iter().map(|&index| &self.audio_streams[index])
+                    .fold((false, true), |(playing, muted), s| (playing || s.playing, muted && s.muted));
+                (window, state)
+            })
+            .collect();
+        if states == self.window_audio {
+            return;
+        }
+        for (&id, &(playing, muted)) in &states {
+            if self.window_audio.get(&id) != Some(&(playing, muted)) {
+                self.emit_event(area_ipc::WmEvent::WindowAudioState { id, playing, muted });
+            }
+        }
+        for &id in self.window_audio.keys().filter(|id| !states.contains_key(id)) {
+            if self.wm_windows.contains_key(&id) {
+                self.emit_event(area_ipc::WmEvent::WindowAudioState { id, playing: false, muted: false });
+            }
+        }
+        self.window_audio = states;
+    }
+    
+    /// Managed windows with a `_NET_WM_PID`, as (window, pid)
+    fn window_pids(&self) -> Vec<(u32, u32)> {
+        self.wm_windows.values().filter(|c| c.pid != 0).map(|c| (c.window, c.pid)).collect()
+    }
+    
+    /// Mute or unmute every stream a window plays
+    fn mute_window_audio(&self, id: u32, muted: bool) -> Result<()> {
+        if !self.wm_windows.contains_key(&id) {
+            anyhow::bail!("no managed window {}", id);
+        }
+        let indices: Vec<u32> = audio::streams_by_window(&self.audio_streams, &self.window_pids())
+            .remove(&id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|index| self.audio_streams[index].index)
+            .collect();
+        if indices.is_empty() {
+            anyhow::bail!("window {} plays no audio", id);
+        }
+        info!("{} audio of window {}", if muted { "Muting" } else { "Unmuting" }, id);
+        audio::set_muted(indices, muted);
+        Ok(())
+    }
+    
+    /// Run the hooks for windows opened, focus changes and workspace
+    /// switches since the last call
+    fn run_hooks(&mut self) {
+        use config::HookEvent;
+        for id in std::mem::take(&mut self.opened_windows) {
+            if self.hooks.wants(HookEvent::WindowOpen)
+                && let Some(client) = self.wm_windows.get(&id)
+            {
+                self.hooks.run(HookEvent::WindowOpen, serde_json::json!({ "window": self.window_details(client) }));
+            }
+        }
+        let focus = self.wm_windows.values().find(|c| c.focused()).map(|c| c.window);
+        if focus != self.hook_focus {
+            let previous = std::mem::replace(&mut self.hook_focus, focus);
+            if self.hooks.wants(HookEvent::FocusChange) {
+                let window = focus.and_then(|id| self.wm_windows.get(&id)).map(|client| self.window_details(client));
+                self.hooks.run(HookEvent::FocusChange, serde_json::json!({ "window": window, "previous": previous }));
+            }
+        }
+        let workspace = self.wm.workspaces.current_workspace;
+        if workspace != self.hook_workspace {
+            let previous = std::mem::replace(&mut self.hook_workspace, workspace);
+            self.hooks.run(HookEvent::WorkspaceSwitch, serde_json::json!({ "workspace": workspace, "previous": previous }));
+        }
+    }
+    
+    /// Start recording incoming events to `path` (see `replay` module)
+    fn start_recording(&mut self, path: &std::path::Path) -> Result<()> {
+        use x11rb::connection::Connection;
+        let screen = self.conn.setup().roots.iter().position(|screen| screen.root == self.root).unwrap_or_default();
+        self.recorder = Some(replay::EventRecorder::create(path, self.conn.as_ref(), screen, self.environment.display(), &self.config)?);
+        Ok(())
+    }
+
+    /// Replay a recorded event stream through `handle_event`
+    ///
+    /// The app must have been set up with `new(_, Some(replay))`, so it talks
+    /// to the recording's mock X server. The periodic unmanaged-window scan is
+    /// skipped and each entry is followed by a round trip, so the mock has
+    /// applied every request before it sees the next event and WM state
+    /// evolves the same way on every run. With `realtime`, the original
+    /// inter-event delays are reproduced.
+    async fn replay(&mut self, replay: replay::EventReplay, realtime: bool) -> Result<()> {
+        use x11rb::connection::Connection;
+        info!("Replaying {} recorded events{}", replay.entries.len(),
+            if realtime { " in real time" } else { "" });
+
+        let mut last_t_ms = 0;
+        for (index, entry) in replay.entries.iter().enumerate() {
+            if realtime && entry.t_ms > last_t_ms {
+                tokio::time::sleep(Duration::from_millis(entry.t_ms - last_t_ms)).await;
+            }
+            last_t_ms = entry.t_ms;
+
+            match &entry.input {
+                replay::RecordedInput::X11 { raw } => {
+                    let event = match replay.parse_x11(raw) {
+                        Ok(event) => event,
+                        Err(e) => {
+                            warn!("Replay: skipping undecodable event #{}: {}", index, e);
+                            continue;
+                        }
+                    };
+                    debug!("Replay #{} (+{}ms): {:?}", index, entry.t_ms, event);
+                    replay.observe(&event);
+                    let result = self.handle_event(event).await;
+                    self.wm.time.end_event();
+                    if let Err(e) = result {
+                        error!("Replay: error handling event #{}: {}", index, e);
+                    }
+                    self.apply_pending_changes();
+                }
+                replay::RecordedInput::Ipc { command } => {
+                    debug!("Replay #{} (+{}ms): IPC {:?}", index, entry.t_ms, command);
+                    if let area_ipc::Reply::Error { message } = self.handle_ipc_command(command.clone()) {
+                        warn!("Replay: IPC command #{} failed: {}", index, message);
+                    }
+                    self.apply_pending_changes();
+                }
+            }
+
+            self.conn.get_input_focus()?.reply()?;
+            // Errors of requests without replies end up in the event queue
+            while self.conn.poll_for_event()?.is_some() {}
+        }
+
+        self.compositor.trigger_render();
+        info!("Replay finished ({} managed windows)", self.wm_windows.len());
+        Ok(())
+    }
+
     /// Scan for windows that exist but aren't being managed
     fn scan_for_unmanaged_windows(&mut self) -> Result<()> {
         let tree = self.conn.as_ref().query_tree(self.root)?.reply()?;
@@ -486,25 +1185,2241 @@ impl AreaApp {
         Ok(())
     }
     
+    /// Handle a command received over IPC
+    fn handle_ipc_command(&mut self, command: area_ipc::ShellCommand) -> area_ipc::Reply {
+        use area_ipc::{Reply, ShellCommand};
+        
+        debug!("IPC command: {:?}", command);
+        match command {
+            ShellCommand::SetLogLevel { filter } => match diagnostics::set_filter(&filter) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::DebugDump => Reply::DebugDump {
+                filter: diagnostics::current_filter(),
+                lines: diagnostics::dump(),
+            },
+            ShellCommand::DumpState { redact_titles } => Reply::State { state: self.dump_state(redact_titles) },
+            ShellCommand::GetMetrics => Reply::Metrics {
+                metrics: metrics::global().snapshot(),
+            },
+            ShellCommand::SelfCheck { pattern } => {
+                if let Some(enabled) = pattern {
+                    info!("Self-check patterns {}", if enabled { "shown" } else { "hidden" });
+                    self.compositor.set_self_check(enabled);
+                }
+                Reply::SelfCheck { gl: self.compositor.gl_capabilities() }
+            }
+            // Subscriptions are per-connection state kept by the IPC server
+            // Per-connection, answered by the IPC server itself
+            ShellCommand::Hello { .. } | ShellCommand::Subscribe | ShellCommand::SubscribePointer => Reply::Ok,
+            ShellCommand::ClearUrgency { id } => match self.clear_urgency(id) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::GetWindows => Reply::Windows {
+                windows: self.wm_windows.values().map(|client| self.window_info(client)).collect(),
+            },
+            ShellCommand::GetWindowInfo { id } => match self.wm_windows.get(&id) {
+                Some(client) => Reply::WindowDetails { window: self.window_details(client) },
+                None => Reply::error(format!("no managed window {}", id)),
+            },
+            ShellCommand::FindWindows { class, title_regex, workspace } => {
+                match self.find_windows(class.as_deref(), title_regex.as_deref(), workspace) {
+                    Ok(ids) => Reply::Windows {
+                        windows: ids.iter().filter_map(|id| self.wm_windows.get(id)).map(|client| self.window_info(client)).collect(),
+                    },
+                    Err(e) => Reply::error(format!("{:#}", e)),
+                }
+            }
+            ShellCommand::ActivateOrLaunch { class, exec } => match self.activate_or_launch(&class, &exec) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::FocusWindow { id } => match self.focus_client(id) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::CloseWindow { id } => match self.close_client(id) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::MoveWindow { id, x, y } => match self.move_client(id, x, y) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::SwitchWorkspace { workspace } => {
+                if workspace >= self.wm.workspaces.workspace_count {
+                    Reply::error(format!("no workspace {}", workspace))
+                } else {
+                    match self.switch_workspace(workspace) {
+                        Ok(()) => Reply::Ok,
+                        Err(e) => Reply::error(format!("{:#}", e)),
+                    }
+                }
+            }
+            ShellCommand::MoveWindowToWorkspace { id, workspace } => {
+                match self.run_window_menu_action(id, area_ipc::WindowMenuAction::MoveToWorkspace(workspace)) {
+                    Ok(()) => Reply::Ok,
+                    Err(e) => Reply::error(format!("{:#}", e)),
+                }
+            }
+            ShellCommand::MinimizeWindow { id } => match self.minimize_client(id) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::RestoreWindow { id } => match self.restore_client(id) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::MinimizeGroup { leader } => match self.set_group_minimized(leader, true) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::RestoreGroup { leader } => match self.set_group_minimized(leader, false) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::SetPanelStrut { edge, size, start, end, monitor } => {
+                match self.set_panel_strut(edge, size, start, end, monitor) {
+                    Ok(()) => Reply::Ok,
+                    Err(e) => Reply::error(format!("{:#}", e)),
+                }
+            }
+            ShellCommand::SetEdgeTrigger { edge, monitor, enabled } => match self.set_edge_trigger(edge, monitor, enabled) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::SetAccessibility { feature, enabled } => match self.wm.accessx.set_feature(&self.conn, feature, enabled) {
+                // Reported with the ControlsNotify that follows
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::ShowOsd { kind, label, value } => {
+                let label = label.unwrap_or_else(|| kind.default_label().to_string());
+                self.show_osd(kind, label, value);
+                Reply::Ok
+            }
+            ShellCommand::SetDoNotDisturb { enabled } => match self.set_do_not_disturb(enabled) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::GetNotificationHistory { limit } => Reply::NotificationHistory {
+                do_not_disturb: self.wm.notifications.do_not_disturb,
+                notifications: self.wm.notifications.newest(limit),
+            },
+            ShellCommand::SetExpo { enabled } => match self.set_expo(enabled) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::SetDpms { state } => match self.wm.dpms.force(&self.conn, state).and_then(|()| Ok(self.conn.flush()?)) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::RequestActivationToken => Reply::ActivationToken { token: self.wm.activation.issue() },
+            ShellCommand::MuteWindowAudio { id, muted } => match self.mute_window_audio(id, muted) {
+                // Reported with the stream change that follows
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::SetInputRegion { window, rects } => match self.set_input_region(window, &rects) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::MaximizeWindow { id, maximized } => match self.set_client_maximized(id, maximized) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::SetFullscreen { id, fullscreen } => match self.set_client_fullscreen(id, fullscreen) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::InhibitShortcuts { id, inhibit } => match self.set_shortcut_inhibit(id, inhibit) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::KillWindow { id, force } => match self.kill_window(id, force) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::MoveWindowToMonitor { id, direction } => {
+                let direction = match direction {
+                    area_ipc::Direction::Left => wm::keyboard::Direction::Left,
+                    area_ipc::Direction::Right => wm::keyboard::Direction::Right,
+                    area_ipc::Direction::Up => wm::keyboard::Direction::Up,
+                    area_ipc::Direction::Down => wm::keyboard::Direction::Down,
+                };
+                match self.move_client_to_monitor(id, direction) {
+                    Ok(true) => Reply::Ok,
+                    Ok(false) => Reply::error(format!("no monitor {:?} of window {}", direction, id)),
+                    Err(e) => Reply::error(format!("{:#}", e)),
+                }
+            }
+            ShellCommand::RequestLivePreview { id, width, height, fps } => {
+                match self.request_live_preview(id, width, height, fps) {
+                    Ok(path) => Reply::LivePreview { path: path.to_string_lossy().into_owned() },
+                    Err(e) => Reply::error(format!("{:#}", e)),
+                }
+            }
+            ShellCommand::SetWallpaper { path, mode, monitor, workspace } => {
+                match self.set_wallpaper(path, mode, monitor, workspace) {
+                    Ok(()) => Reply::Ok,
+                    Err(e) => Reply::error(format!("{:#}", e)),
+                }
+            }
+            ShellCommand::RunWindowMenuAction { id, action } => match self.run_window_menu_action(id, action) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::RunRootMenuEntry { index } => match self.run_root_menu_entry(index) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::EndSession { action, force } => match self.begin_end_session(action, force) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::CancelEndSession => match self.end_session.take() {
+                Some(_) => {
+                    info!("End of session cancelled");
+                    Reply::Ok
+                }
+                None => Reply::error("no session end in progress"),
+            },
+            ShellCommand::SetTaskbarOrder { ids, slots } => match self.wm.taskbar.set(ids, slots) {
+                Ok(()) => Reply::Ok,
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::PinApp { id, position } => match self.wm.pinned.pin(id, position) {
+                Ok(()) => {
+                    self.emit_event(area_ipc::WmEvent::PinnedAppsChanged { apps: self.pinned_apps() });
+                    Reply::Ok
+                }
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::UnpinApp { id } => match self.wm.pinned.unpin(&id) {
+                Ok(true) => {
+                    self.emit_event(area_ipc::WmEvent::PinnedAppsChanged { apps: self.pinned_apps() });
+                    Reply::Ok
+                }
+                Ok(false) => Reply::error(format!("{} is not pinned", id)),
+                Err(e) => Reply::error(format!("{:#}", e)),
+            },
+            ShellCommand::ListPinned => Reply::Pinned { apps: self.pinned_apps() },
+            ShellCommand::ListComponents => Reply::Components { components: self.supervisor.statuses().to_vec() },
+        }
+    }
+    
+    /// Describe a managed window for IPC clients
+    fn window_info(&self, client: &Client) -> area_ipc::WindowInfo {
+        area_ipc::WindowInfo {
+            id: client.window,
+            title: client.name.clone(),
+            x: client.geometry.x,
+            y: client.geometry.y,
+            width: client.geometry.width,
+            height: client.geometry.height,
+            focused: client.focused(),
+            iconified: client.is_minimized(),
+            urgent: client.is_urgent(),
+            maximized: client.is_maximized(),
+            fullscreen: client.is_fullscreen(),
+            shaded: client.is_shaded(),
+            above: client.flags.contains(crate::wm::client_flags::ClientFlags::ABOVE),
+            group: client.group_leader,
+            shortcuts_inhibited: client.flags.contains(crate::wm::client_flags::ClientFlags::INHIBIT_SHORTCUTS),
+            taskbar_position: self.wm.taskbar.position(client.window),
+            pinned: client.class_hint.as_ref().and_then(|class| self.wm.pinned.app_of(class)).map(str::to_string),
+        }
+    }
+    
+    /// Window manager state plus the frame and compositor bookkeeping of the
+    /// main loop, for `DumpState`
+    fn dump_state(&self, redact_titles: bool) -> serde_json::Value {
+        let mut state = self.wm.dump_state(&self.wm_windows, redact_titles);
+        let sorted = |windows: &HashSet<u32>| {
+            let mut windows: Vec<u32> = windows.iter().copied().collect();
+            windows.sort_unstable();
+            windows
+        };
+        if let Some(state) = state.as_object_mut() {
+            state.insert("frame_windows".into(), sorted(&self.frame_windows).into());
+            state.insert("reparenting_windows".into(), sorted(&self.reparenting_windows).into());
+            state.insert("compositor_stacking".into(), self.compositor_stacking.clone().into());
+            state.insert("client_list_stacking".into(), self.client_list_stacking.clone().into());
+            state.insert("unredirected".into(), sorted(&self.rule_unredirected).into());
+        }
+        state
+    }
+    
+    /// `window_info` plus the properties only `GetWindowInfo` reports
+    ///
+    /// PID, role and icon are read from the client (requests pipelined);
+    /// a window that vanished meanwhile just reports them as unset.
+    fn window_details(&self, client: &Client) -> area_ipc::WindowDetails {
+        use crate::wm::client_flags::ClientFlags;
+        let window = client.window;
+        let atom = |name| wm::atom_cache::intern(self.conn.as_ref(), name).ok();
+        let pid = self.conn.get_property(false, window, self.wm.atoms._net_wm_pid, AtomEnum::CARDINAL, 0, 1);
+        let role = atom("WM_WINDOW_ROLE").map(|atom| self.conn.get_property(false, window, atom, AtomEnum::STRING, 0, 256));
+        let icon = atom("_NET_WM_ICON").map(|atom| self.conn.get_property(false, window, atom, AtomEnum::ANY, 0, 0));
+        let pid = pid.ok().and_then(|c| c.reply().ok()).and_then(|r| r.value32().and_then(|mut v| v.next()));
+        let role = role
+            .and_then(|c| c.ok())
+            .and_then(|c| c.reply().ok())
+            .map(|r| String::from_utf8_lossy(&r.value).trim_end_matches('\0').to_string())
+            .filter(|role| !role.is_empty());
+        let has_icon = icon.and_then(|c| c.ok()).and_then(|c| c.reply().ok()).is_some_and(|r| r.bytes_after > 0)
+            || client.wm_hints.as_ref().is_some_and(|hints| hints.icon_pixmap.is_some());
+        let g = &client.geometry;
+        area_ipc::WindowDetails {
+            info: self.window_info(client),
+            pid,
+            class: client.class_hint.as_ref().map(|hint| hint.res_class.clone()),
+            instance: client.class_hint.as_ref().map(|hint| hint.res_name.clone()),
+            role,
+            window_type: client.type_.name().to_string(),
+            workspace: (!client.is_sticky()).then_some(client.win_workspace),
+            monitor: self.wm.monitor_at(g.x + g.width as i32 / 2, g.y + g.height as i32 / 2).map(|m| m as u32),
+            transient_for: client.transient_for,
+            sticky: client.is_sticky(),
+            modal: client.flags.contains(ClientFlags::STATE_MODAL),
+            skip_taskbar: client.flags.contains(ClientFlags::SKIP_TASKBAR),
+            skip_pager: client.flags.contains(ClientFlags::SKIP_PAGER),
+            has_icon,
+        }
+    }
+    
+    /// A window and, with `minimize_groups`, the rest of its group
+    fn minimize_targets(&self, client_id: u32) -> Vec<u32> {
+        match self.wm_windows.get(&client_id).and_then(|c| c.group_leader) {
+            Some(leader) if self.config.window_manager.behavior.minimize_groups => {
+                wm::transients::group_members(&self.wm_windows, leader)
+            }
+            _ => vec![client_id],
+        }
+    }
+    
+    /// Iconify a managed window (its group with `minimize_groups`)
+    fn minimize_client(&mut self, client_id: u32) -> Result<()> {
+        if !self.wm_windows.contains_key(&client_id) {
+            anyhow::bail!("no managed window {}", client_id);
+        }
+        for id in self.minimize_targets(client_id) {
+            self.minimize_single(id)?;
+        }
+        Ok(())
+    }
+    
+    /// De-iconify a managed window (its group with `minimize_groups`)
+    fn restore_client(&mut self, client_id: u32) -> Result<()> {
+        if !self.wm_windows.contains_key(&client_id) {
+            anyhow::bail!("no managed window {}", client_id);
+        }
+        // The requested window ends up on top
+        let mut targets = self.minimize_targets(client_id);
+        targets.retain(|&id| id != client_id);
+        targets.push(client_id);
+        for id in targets {
+            self.restore_single(id)?;
+        }
+        Ok(())
+    }
+    
+    /// Iconify or de-iconify every window of a group
+    fn set_group_minimized(&mut self, leader: u32, minimized: bool) -> Result<()> {
+        let members = wm::transients::group_members(&self.wm_windows, leader);
+        if members.is_empty() {
+            anyhow::bail!("no managed window in group {}", leader);
+        }
+        for id in members {
+            if minimized {
+                self.minimize_single(id)?;
+            } else {
+                self.restore_single(id)?;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Iconify a managed window and tell IPC subscribers
+    fn minimize_single(&mut self, client_id: u32) -> Result<()> {
+        let Some(client) = self.wm_windows.get(&client_id) else {
+            anyhow::bail!("no managed window {}", client_id);
+        };
+        if client.is_minimized() {
+            return Ok(());
+        }
+        
+        self.wm.minimize_window(&self.conn, &mut self.wm_windows, client_id)?;
+        self.wm.atoms.set_window_state(&self.conn, client_id, &[self.wm.atoms._net_wm_state_hidden], &[])?;
+        self.conn.as_ref().flush()?;
+        
+        self.emit_event(area_ipc::WmEvent::WindowMinimized { id: client_id });
+        Ok(())
+    }
+    
+    /// Map an iconified window again, raise it and tell IPC subscribers
+    fn restore_single(&mut self, client_id: u32) -> Result<()> {
+        let Some(client) = self.wm_windows.get_mut(&client_id) else {
+            anyhow::bail!("no managed window {}", client_id);
+        };
+        if !client.is_minimized() {
+            return Ok(());
+        }
+        
+        client.flags.remove(crate::wm::client_flags::ClientFlags::ICONIFIED);
+        // A window on another workspace shows up when switching there
+        if self.wm.workspaces.is_visible(client) {
+            if let Some(frame) = &client.frame {
+                self.conn.as_ref().map_window(frame.frame)?;
+            } else {
+                self.conn.as_ref().map_window(client_id)?;
+            }
+            client.set_mapped(true);
+        }
+        self.wm.atoms.set_window_state(&self.conn, client_id, &[], &[self.wm.atoms._net_wm_state_hidden])?;
+        self.wm.stacking.raise_window(client_id);
+        self.conn.as_ref().flush()?;
+        
+        self.emit_event(area_ipc::WmEvent::WindowRestored { id: client_id });
+        Ok(())
+    }
+    
+    /// Maximize or unmaximize a managed window
+    fn set_client_maximized(&mut self, client_id: u32, maximized: bool) -> Result<()> {
+        let Some(client) = self.wm_windows.get_mut(&client_id) else {
+            anyhow::bail!("no managed window {}", client_id);
+        };
+        if client.is_maximized() == maximized {
+            return Ok(());
+        }
+        
+        if maximized {
+            self.wm.maximize_window(&self.conn, client)?;
+        } else {
+            self.wm.restore_window(&self.conn, client)?;
+        }
+        self.window_state_changed.insert(client_id);
+        Ok(())
+    }
+    
+    /// Toggle the sticky, above or shaded state of a window from its titlebar button
+    fn toggle_client_state(&mut self, client_id: u32, button: wm::ButtonType) -> Result<()> {
+        let Some(client) = self.wm_windows.get_mut(&client_id) else {
+            anyhow::bail!("no managed window {}", client_id);
+        };
+        match button {
+            wm::ButtonType::Sticky => self.wm.set_sticky(&self.conn, client, !client.is_sticky())?,
+            wm::ButtonType::Above => {
+                let above = client.flags.contains(crate::wm::client_flags::ClientFlags::ABOVE);
+                self.wm.set_above(&self.conn, client, !above)?
+            }
+            wm::ButtonType::Shade => self.wm.set_shaded(&self.conn, client, !client.is_shaded())?,
+            _ => return Ok(()),
+        }
+        self.window_state_changed.insert(client_id);
+        Ok(())
+    }
+    
+    /// Ask the shell to show the window menu of a managed window at a root position
+    fn show_window_menu(&mut self, client_id: u32, x: i16, y: i16) {
+        use area_ipc::{WindowMenuAction, WindowMenuEntry};
+        
+        let Some(client) = self.wm_windows.get(&client_id) else {
+            return;
+        };
+        let entry = |action, label: &str, checked| WindowMenuEntry { action, label: label.to_string(), checked };
+        let mut entries = vec![
+            entry(WindowMenuAction::Move, "Move", false),
+            entry(WindowMenuAction::Resize, "Resize", false),
+            entry(WindowMenuAction::Minimize, "Minimize", false),
+            entry(WindowMenuAction::Maximize, "Maximize", client.is_maximized()),
+            entry(
+                WindowMenuAction::AlwaysOnTop,
+                "Always on Top",
+                client.flags.contains(crate::wm::client_flags::ClientFlags::ABOVE),
+            ),
+        ];
+        for (workspace, name) in self.wm.workspaces.workspace_names.iter().enumerate() {
+            let workspace = workspace as u32;
+            entries.push(entry(
+                WindowMenuAction::MoveToWorkspace(workspace),
+                &format!("Move to {}", name),
+                client.win_workspace == workspace,
+            ));
+        }
+        entries.push(entry(WindowMenuAction::Close, "Close", false));
+        
+        debug!("Window menu requested for window {} at ({}, {})", client_id, x, y);
+        self.emit_event(area_ipc::WmEvent::ShowWindowMenu {
+            id: client_id,
+            x: x as i32,
+            y: y as i32,
+            entries,
+        });
+    }
+    
+    /// Run an entry picked from a window menu
+    fn run_window_menu_action(&mut self, client_id: u32, action: area_ipc::WindowMenuAction) -> Result<()> {
+        use area_ipc::WindowMenuAction;
+        
+        let Some(client) = self.wm_windows.get_mut(&client_id) else {
+            anyhow::bail!("no managed window {}", client_id);
+        };
+        match action {
+            WindowMenuAction::Move | WindowMenuAction::Resize => {
+                // Follows the pointer until the next button release
+                let pointer = self.conn.as_ref().query_pointer(self.root)?.reply()?;
+                if action == WindowMenuAction::Move {
+                    self.wm.start_drag(&self.conn, &self.wm_windows, client_id, pointer.root_x, pointer.root_y)?;
+                } else {
+                    self.wm.start_resize(&self.conn, &self.wm_windows, client_id, pointer.root_x, pointer.root_y)?;
+                }
+            }
+            WindowMenuAction::Minimize => self.minimize_client(client_id)?,
+            WindowMenuAction::Maximize => {
+                let maximized = client.is_maximized();
+                self.set_client_maximized(client_id, !maximized)?;
+            }
+            WindowMenuAction::AlwaysOnTop => self.toggle_client_state(client_id, wm::ButtonType::Above)?,
+            WindowMenuAction::MoveToWorkspace(workspace) => {
+                if let Some(visible) = self.wm.workspaces.move_window_to_workspace(&self.conn, &self.wm.atoms, client, workspace)? {
+                    self.apply_workspace_visibility(vec![(client_id, visible)]);
+                }
+                self.conn.as_ref().flush()?;
+            }
+            WindowMenuAction::Close => self.close_client(client_id)?,
+        }
+        Ok(())
+    }
+    
+    /// Run the configured wheel action for a button 4 (up) or 5 (down) press
+    /// on the desktop or a titlebar
+    fn handle_wheel(&mut self, e: &ButtonPressEvent) -> Result<()> {
+        let behavior = &self.config.window_manager.behavior;
+        let up = e.detail == 4;
+        let state = u16::from(e.state);
+        
+        if e.event == self.root {
+            let modifiers = wm::keyboard::parse_modifiers(&behavior.desktop_wheel_modifiers);
+            if e.child == x11rb::NONE && behavior.desktop_wheel && self.keyboard.modifiers_held(state, modifiers)
+                && let Some(workspace) = self.wm.workspaces.adjacent(!up)
+            {
+                self.switch_workspace(workspace)?;
+            }
+            return Ok(());
+        }
+        
+        let modifiers = wm::keyboard::parse_modifiers(&behavior.titlebar_wheel_modifiers);
+        let action = behavior.titlebar_wheel;
+        let Some(client_id) = self.wm.find_client_from_window(&self.wm_windows, e.event) else {
+            return Ok(());
+        };
+        let Some(client) = self.wm_windows.get_mut(&client_id) else {
+            return Ok(());
+        };
+        let on_titlebar = client.frame.as_ref().is_some_and(|frame| {
+            e.event == frame.titlebar
+                || frame.buttons.iter().any(|b| b.window == e.event)
+                || (e.event == frame.frame && (e.event_y as i32) < client.frame_metrics.titlebar as i32)
+        });
+        if !on_titlebar || !self.keyboard.modifiers_held(state, modifiers) {
+            return Ok(());
+        }
+        
+        match action {
+            config::TitlebarWheelAction::Shade => {
+                if client.is_shaded() == up {
+                    self.wm.set_shaded(&self.conn, client, !up)?;
+                    self.window_state_changed.insert(client_id);
+                }
+            }
+            config::TitlebarWheelAction::Opacity => {
+                // 10% steps, never fully transparent
+                const STEP: f64 = 0.1;
+                const MIN: f64 = 0.1;
+                let current = client.opacity as f64 / u32::MAX as f64;
+                let opacity = if up { current + STEP } else { current - STEP }.clamp(MIN, 1.0);
+                client.opacity = (opacity * u32::MAX as f64).round() as u32;
+                if client.opacity == u32::MAX {
+                    self.conn.as_ref().delete_property(client_id, self.wm.atoms._net_wm_window_opacity)?;
+                } else {
+                    self.conn.as_ref().change_property32(
+                        PropMode::REPLACE,
+                        client_id,
+                        self.wm.atoms._net_wm_window_opacity,
+                        AtomEnum::CARDINAL,
+                        &[client.opacity],
+                    )?;
+                }
+                let composite_id = client.frame.as_ref().map_or(client_id, |frame| frame.frame);
+                self.compositor.set_window_opacity(composite_id, opacity as f32);
+                self.compositor.trigger_render();
+            }
+            config::TitlebarWheelAction::None => {}
+        }
+        Ok(())
+    }
+    
+    /// Ask the shell to show the configured root menu at a root position
+    fn show_root_menu(&self, x: i16, y: i16) {
+        let entries = self.config.root_menu.entries.iter()
+            .map(|entry| area_ipc::RootMenuItem {
+                label: entry.label.clone(),
+                separator: entry.action == config::RootMenuAction::Separator,
+            })
+            .collect();
+        debug!("Root menu requested at ({}, {})", x, y);
+        self.emit_event(area_ipc::WmEvent::ShowRootMenu { x: x as i32, y: y as i32, entries });
+    }
+    
+    /// Run the root menu entry at `index`
+    fn run_root_menu_entry(&mut self, index: u32) -> Result<()> {
+        let Some(entry) = self.config.root_menu.entries.get(index as usize) else {
+            anyhow::bail!("no root menu entry {}", index);
+        };
+        info!("Root menu: {}", entry.label);
+        match entry.action.clone() {
+            config::RootMenuAction::Exec(command) => self.spawn_shell(&command)?,
+            config::RootMenuAction::Workspace(workspace) => self.switch_workspace(workspace)?,
+            config::RootMenuAction::Reload => self.reload_config()?,
+            config::RootMenuAction::Exit => self.exit_requested = true,
+            config::RootMenuAction::Separator => {}
+        }
+        Ok(())
+    }
+    
+    /// Re-read the configuration file and apply what can change at runtime
+    fn reload_config(&mut self) -> Result<()> {
+        if self.replaying {
+            info!("Replay: keeping the recorded configuration");
+            return Ok(());
+        }
+        self.config = config::Config::load()?;
+        let changed = self.wm.apply_config(&self.conn, &mut self.wm_windows, &self.config)?;
+        // Re-apply the workspace profile over the new global settings (reloads the wallpaper)
+        self.profile_workspace = None;
+        self.apply_workspace_visibility(changed);
+        self.apply_output_scales();
+        self.compositor.set_frame_rate(self.config.compositor.frame_rate);
+        self.compositor.set_effects(self.config.compositor.effects.clone());
+        for id in self.wm_windows.keys().copied().collect::<Vec<_>>() {
+            self.apply_compositor_rules(id);
+        }
+        self.environment = environment::SessionEnvironment::new(self.environment.display(), &self.config.environment);
+        self.hooks.set_config(self.config.hooks.clone(), self.environment.clone());
+        for component in self.supervisor.set_config(&self.config.components, &self.environment) {
+            self.emit_event(area_ipc::WmEvent::ComponentStatusChanged { component });
+        }
+        self.keyboard.set_chords(&self.conn, self.root, &self.config.keybindings.chords)?;
+        if let Some(ref ipc) = self.ipc {
+            ipc.set_access(self.config.ipc.clone());
+        }
+        self.conn.as_ref().flush()?;
+        Ok(())
+    }
+    
+    /// Windows that must close before the session can end (docks and desktops go with it)
+    fn session_windows(&self) -> Vec<u32> {
+        self.wm_windows.values()
+            .filter(|client| !matches!(client.type_, crate::wm::client_flags::WindowType::Dock | crate::wm::client_flags::WindowType::Desktop))
+            .map(|client| client.window)
+            .collect()
+    }
+    
+    /// Start ending the session: save window state and ask every window to close
+    ///
+    /// With `force` (or when repeated after `EndSessionBlocked`) the windows
+    /// still open are killed instead. The session actually ends in
+    /// `finish_end_session` once no windows are left.
+    fn begin_end_session(&mut self, action: area_ipc::SessionAction, force: bool) -> Result<()> {
+        if action != area_ipc::SessionAction::Logout && self.power.is_none() {
+            anyhow::bail!("power management (logind) is not available");
+        }
+        let windows = self.session_windows();
+        if force {
+            info!("Ending session ({:?}), killing {} remaining windows", action, windows.len());
+            for window in windows {
+                if let Err(e) = self.conn.as_ref().kill_client(window) {
+                    warn!("Failed to kill window {}: {}", window, e);
+                }
+            }
+        } else {
+            info!("Ending session ({:?}), closing {} windows", action, windows.len());
+            if self.end_session.is_none()
+                && let Err(e) = wm::session::SessionManager::new().save_state(&self.wm_windows)
+            {
+                warn!("Failed to save session state: {:#}", e);
+            }
+            for window in windows {
+                self.wm.close_window(&self.conn, &self.wm_windows, window)?;
+            }
+        }
+        self.conn.as_ref().flush()?;
+        let timeout = Duration::from_millis(self.config.window_manager.behavior.end_session_timeout_ms);
+        self.end_session = Some(PendingEndSession { action, deadline: Some(Instant::now() + timeout) });
+        Ok(())
+    }
+    
+    /// The end-session timeout expired: hand the windows still open to the shell
+    fn end_session_timed_out(&mut self) {
+        let windows = self.session_windows();
+        let Some(ref mut pending) = self.end_session else { return };
+        pending.deadline = None;
+        if windows.is_empty() {
+            return;
+        }
+        info!("{} windows did not close, waiting for the user", windows.len());
+        let action = pending.action;
+        self.emit_event(area_ipc::WmEvent::EndSessionBlocked { action, windows });
+    }
+    
+    /// Pass battery/AC changes on to the compositor's frame pacing
+    async fn update_battery_state(&mut self) {
+        let Some(ref power) = self.power else { return };
+        match power.on_battery().await {
+            Ok(on_battery) if on_battery != self.on_battery => {
+                self.on_battery = on_battery;
+                self.compositor.set_on_battery(on_battery);
+            }
+            Ok(_) => {}
+            Err(e) => debug!("Failed to read battery state: {}", e),
+        }
+    }
+    
+    /// All windows are gone: log out, reboot or power off
+    async fn finish_end_session(&mut self) {
+        let Some(pending) = self.end_session.take() else { return };
+        info!("All windows closed, ending session ({:?})", pending.action);
+        let result = match (pending.action, &self.power) {
+            (area_ipc::SessionAction::Logout, _) => Ok(()),
+            (area_ipc::SessionAction::Reboot, Some(power)) => power.reboot().await,
+            (area_ipc::SessionAction::Shutdown, Some(power)) => power.shutdown().await,
+            (_, None) => Err(anyhow::anyhow!("power management (logind) is not available")),
+        };
+        match result {
+            Ok(()) => self.exit_requested = true,
+            Err(e) => error!("Failed to end session ({:?}): {:#}", pending.action, e),
+        }
+    }
+    
+    /// Move a window to the adjacent monitor; false if there is none that way
+    fn move_client_to_monitor(&mut self, client_id: u32, direction: wm::keyboard::Direction) -> Result<bool> {
+        let Some(client) = self.wm_windows.get_mut(&client_id) else {
+            anyhow::bail!("no managed window {}", client_id);
+        };
+        // The compositor follows the resulting ConfigureNotify
+        self.wm.move_to_monitor(&self.conn, client, direction)
+    }
+    
+    /// Outline where a window dragged onto another monitor will land
+    fn update_drag_preview(&mut self) {
+        let preview = self.wm.drag_crossing().and_then(|(client_id, from, to)| {
+            let client = self.wm_windows.get(&client_id)?;
+            let (geometry, _) = self.wm.drop_geometry(client, from, to, self.output_scale_ratio(from, to));
+            Some(client.frame_metrics.frame_geometry(geometry))
+        });
+        if preview != self.drag_preview {
+            self.drag_preview = preview;
+            self.compositor.set_snap_preview(preview);
+        }
+    }
+    
+    /// Fit a window dropped on another monitor to it: re-maximize it there,
+    /// or adjust its size to the output's scale
+    fn drop_dragged_window(&mut self) -> Result<()> {
+        if self.drag_preview.take().is_some() {
+            self.compositor.set_snap_preview(None);
+        }
+        let Some((client_id, from, to)) = self.wm.drag_crossing() else {
+            return Ok(());
+        };
+        let scale = self.output_scale_ratio(from, to);
+        let Some(client) = self.wm_windows.get_mut(&client_id) else {
+            return Ok(());
+        };
+        self.wm.drop_on_monitor(&self.conn, client, from, to, scale)
+    }
+    
+    /// Factor keeping a window's physical size from monitor `from` to `to`
+    /// (see `config.compositor.output_scale`)
+    fn output_scale_ratio(&self, from: usize, to: usize) -> f64 {
+        let scale = |monitor: usize| {
+            self.wm.monitors.get(monitor).and_then(|m| self.config.compositor.output_scale.get(&m.name)).copied().unwrap_or(1.0)
+        };
+        scale(from) / scale(to)
+    }
+    
+    /// Switch workspaces (e.g. for a pager's `_NET_CURRENT_DESKTOP` request)
+    fn switch_workspace(&mut self, workspace: u32) -> Result<()> {
+        let changed = self.wm.switch_workspace(&self.conn, &mut self.wm_windows, workspace, None)?;
+        self.apply_workspace_visibility(changed);
+        Ok(())
+    }
+    
+    /// Follow windows shown or hidden by a workspace switch in the compositor
+    fn apply_workspace_visibility(&mut self, changed: Vec<(u32, bool)>) {
+        for (client_id, visible) in changed {
+            let Some(client) = self.wm_windows.get(&client_id) else {
+                continue;
+            };
+            let composite_id = match &client.frame {
+                Some(frame) => frame.frame,
+                None => {
+                    // Our own unmap, not a withdrawal
+                    if !visible {
+                        self.reparenting_windows.insert(client_id);
+                    }
+                    client_id
+                }
+            };
+            self.compositor.set_window_viewable(composite_id, visible);
+        }
+        self.apply_workspace_profile();
+    }
+    
+    /// Apply the settings profile of the current workspace after a switch
+    ///
+    /// Gaps and focus mode fall back to the global settings; the wallpaper is
+    /// only reloaded when it differs from the previous workspace's.
+    fn apply_workspace_profile(&mut self) {
+        let workspace = self.wm.workspaces.current_workspace;
+        let previous = self.profile_workspace.replace(workspace);
+        if previous == Some(workspace) {
+            return;
+        }
+        let behavior = &self.config.window_manager.behavior;
+        let profile = self.config.workspace_profiles.get(&workspace.to_string());
+        let gaps = wm::placement::Gaps {
+            inner: profile.and_then(|p| p.window_gaps).unwrap_or(behavior.window_gaps),
+            outer: profile.and_then(|p| p.outer_gaps).unwrap_or(behavior.outer_gaps),
+        };
+        let focus_mode = profile.and_then(|p| p.focus_mode.as_deref()).unwrap_or(&behavior.focus_mode);
+        self.wm.focus_policy = wm::focus::FocusPolicy::parse(focus_mode);
+        if let Err(e) = self.wm.set_gaps(&self.conn, &mut self.wm_windows, gaps) {
+            warn!("Failed to apply gaps: {}", e);
+        }
+        let name = profile.map(|p| p.name.clone().unwrap_or_else(|| workspace.to_string()));
+        debug!("Workspace {} profile: {:?}", workspace, name);
+        if previous.is_some() && self.config.osd.workspace {
+            let label = name.clone().unwrap_or_else(|| format!("Workspace {}", workspace + 1));
+            self.show_osd(area_ipc::OsdKind::Workspace, label, None);
+        }
+        
+        if previous.is_none_or(|previous| self.wallpaper_choices(previous) != self.wallpaper_choices(workspace)) {
+            self.reload_wallpaper();
+        }
+        self.emit_event(area_ipc::WmEvent::WorkspaceProfileChanged { workspace, profile: name });
+    }
+    
+    /// Show the on-screen display on the focused monitor, or have the shell show it
+    fn show_osd(&mut self, kind: area_ipc::OsdKind, label: String, value: Option<f64>) {
+        let osd = self.config.osd;
+        if !osd.enabled {
+            return;
+        }
+        if osd.render == config::OsdRender::Shell {
+            self.emit_event(area_ipc::WmEvent::Osd { kind, label, value });
+            return;
+        }
+        let Some(m) = self.wm.monitors.get(self.focused_monitor()) else { return };
+        self.compositor.set_osd(Some(compositor::osd::OsdContent {
+            text: label,
+            value: value.map(|value| value as f32),
+            monitor: shared::Geometry::new(m.x, m.y, m.width, m.height),
+            position: osd.position,
+        }));
+        self.osd_deadline = Some(Instant::now() + Duration::from_millis(osd.timeout_ms));
+    }
+    
+    /// Monitor of the focused window, else the primary one
+    fn focused_monitor(&self) -> usize {
+        self.wm_windows.values()
+            .find(|c| c.focused())
+            .and_then(|c| self.wm.monitor_at(c.geometry.x, c.geometry.y))
+            .unwrap_or_else(|| self.wm.primary_monitor())
+    }
+    
+    /// Wallpaper of every monitor on a workspace (its profile's unless overridden over IPC)
+    fn wallpaper_choices(&self, workspace: u32) -> Vec<compositor::wallpaper::WallpaperChoice> {
+        let key = workspace.to_string();
+        let profile_spec = self.config.workspace_profiles.get(&key).and_then(|p| p.wallpaper.as_ref());
+        match profile_spec {
+            Some(spec) if !self.config.wallpaper.workspaces.contains_key(&key) => {
+                let mut wallpaper = self.config.wallpaper.clone();
+                wallpaper.workspaces.insert(key, spec.clone());
+                compositor::wallpaper::resolve(&wallpaper, &self.wm.monitors, workspace)
+            }
+            _ => compositor::wallpaper::resolve(&self.config.wallpaper, &self.wm.monitors, workspace),
+        }
+    }
+    
+    /// Run a bound keyboard shortcut
+    fn run_keyboard_action(&mut self, action: wm::keyboard::KeyboardAction) {
+        use wm::keyboard::KeyboardAction;
+        
+        let focused = self.wm_windows.values().find(|c| c.focused()).map(|c| c.window);
+        match action {
+            KeyboardAction::MoveWindowToMonitor(direction) => {
+                if let Some(id) = focused
+                    && let Err(e) = self.move_client_to_monitor(id, direction)
+                {
+                    warn!("Failed to move window {} to the {:?} monitor: {}", id, direction, e);
+                }
+            }
+            KeyboardAction::ShowWindowMenu => {
+                // Below the focused window's top-left corner, where a titlebar click would put it
+                if let Some(client) = focused.and_then(|id| self.wm_windows.get(&id)) {
+                    let (x, y) = (client.geometry.x as i16, client.geometry.y as i16);
+                    self.show_window_menu(client.window, x, y);
+                }
+            }
+            KeyboardAction::TileLeft | KeyboardAction::TileRight => {
+                let position = match action {
+                    KeyboardAction::TileLeft => wm::client_flags::TilePosition::Left,
+                    _ => wm::client_flags::TilePosition::Right,
+                };
+                if let Some(id) = focused
+                    && let Err(e) = self.wm.tile_window(&self.conn, &mut self.wm_windows, id, position)
+                {
+                    warn!("Failed to tile window: {}", e);
+                }
+            }
+            KeyboardAction::AdjustSplit(delta) => {
+                if let Some(id) = focused
+                    && let Err(e) = self.wm.adjust_split(&self.conn, &mut self.wm_windows, id, delta)
+                {
+                    warn!("Failed to move the split: {}", e);
+                }
+            }
+            KeyboardAction::AdjustGaps(delta) => {
+                // Until the next workspace switch or configuration reload
+                let gaps = self.wm.gaps.adjusted(delta);
+                if let Err(e) = self.wm.set_gaps(&self.conn, &mut self.wm_windows, gaps) {
+                    warn!("Failed to change gaps: {}", e);
+                }
+            }
+            KeyboardAction::ToggleShortcutInhibit => {
+                if let Some(client) = focused.and_then(|id| self.wm_windows.get(&id)) {
+                    let inhibit = !client.flags.contains(crate::wm::client_flags::ClientFlags::INHIBIT_SHORTCUTS);
+                    if let Err(e) = self.set_shortcut_inhibit(client.window, inhibit) {
+                        warn!("Failed to toggle shortcut inhibit: {}", e);
+                    }
+                }
+            }
+            KeyboardAction::TogglePointerConfinement => self.wm.pointer_confinement.toggle(),
+            KeyboardAction::CycleWindows { mode, backward } => {
+                if let Err(e) = self.cycle_windows(mode, backward) {
+                    warn!("Window cycling failed: {}", e);
+                }
+            }
+            KeyboardAction::WindowMode => {
+                if let Err(e) = self.enter_window_mode() {
+                    warn!("Window management mode failed: {}", e);
+                }
+            }
+            KeyboardAction::Expo => {
+                if let Err(e) = self.set_expo(!self.wm.expo.active) {
+                    warn!("Workspace overview failed: {}", e);
+                }
+            }
+            KeyboardAction::HintMode => {
+                if let Err(e) = self.enter_hint_mode() {
+                    warn!("Window hint mode failed: {}", e);
+                }
+            }
+            KeyboardAction::SwitchWorkspace(workspace) => {
+                if workspace < self.wm.workspaces.workspace_count
+                    && let Err(e) = self.switch_workspace(workspace)
+                {
+                    warn!("Failed to switch to workspace {}: {}", workspace, e);
+                }
+            }
+            KeyboardAction::MoveToWorkspace(workspace) => {
+                if let Some(id) = focused
+                    && let Err(e) = self.run_window_menu_action(id, area_ipc::WindowMenuAction::MoveToWorkspace(workspace))
+                {
+                    warn!("Failed to move window {} to workspace {}: {}", id, workspace, e);
+                }
+            }
+            KeyboardAction::ToggleDoNotDisturb => {
+                if let Err(e) = self.set_do_not_disturb(!self.wm.notifications.do_not_disturb) {
+                    warn!("Failed to toggle Do Not Disturb: {}", e);
+                }
+            }
+            KeyboardAction::ToggleHud => self.compositor.toggle_hud(),
+            KeyboardAction::ActivateTaskbarSlot(slot) => {
+                if let Err(e) = self.activate_taskbar_slot(slot) {
+                    warn!("Failed to activate taskbar button {}: {}", slot + 1, e);
+                }
+            }
+            other => debug!("Keyboard action {:?} is not bound to anything yet", other),
+        }
+    }
+    
+    /// Start or advance Alt+Tab cycling, focusing the selected window
+    ///
+    /// The keyboard stays grabbed until Alt is released (`finish_cycle`).
+    fn cycle_windows(&mut self, mode: wm::cycle::CycleMode, backward: bool) -> Result<()> {
+        let selected = if self.wm.cycle.active {
+            if backward { self.wm.cycle.cycle_prev() } else { self.wm.cycle.cycle_next() }
+        } else {
+            let workspace = self.wm_windows.values().find(|c| c.focused()).map_or(0, |c| c.win_workspace);
+            let Some(first) = self.wm.cycle.start_cycle(&self.wm_windows, self.wm.stacking.order(), workspace, mode, backward) else {
+                return Ok(());
+            };
+            
+            let grab = self.conn.grab_keyboard(false, self.root, x11rb::CURRENT_TIME, GrabMode::ASYNC, GrabMode::ASYNC)?.reply()?;
+            let alt = u16::from(ModMask::M1);
+            let alt_held = u16::from(self.conn.query_pointer(self.root)?.reply()?.mask) & alt != 0;
+            if grab.status != GrabStatus::SUCCESS || !alt_held {
+                // We won't see Alt being released, switch right away
+                debug!("Keyboard grab for cycling unavailable ({:?}), switching immediately", grab.status);
+                self.wm.cycle.active = true;
+                return self.finish_cycle();
+            }
+            Some(first)
+        };
+        
+        // Minimized windows are only restored once chosen
+        if let Some(id) = selected
+            && self.wm_windows.get(&id).is_some_and(|c| !c.is_minimized())
+        {
+            self.wm.set_focus(&self.conn, &mut self.wm_windows, id)?;
+            self.wm.restack_if_needed(&self.conn, &mut self.wm_windows)?;
+        }
+        if self.cycle_previews.is_empty() {
+            self.cycle_previews = self.wm.cycle.cycle_list.clone();
+            self.start_cycle_previews();
+        }
+        self.publish_cycle();
+        Ok(())
+    }
+    
+    /// Stream (or keep streaming) a thumbnail of every window in the cycle
+    /// list; nothing is captured for windows outside it
+    fn start_cycle_previews(&self) {
+        let behavior = &self.config.window_manager.behavior;
+        if behavior.cycle_preview_fps == 0 {
+            return;
+        }
+        use compositor::preview::{MAX_PREVIEW_FPS, MAX_PREVIEW_SIZE};
+        let size = behavior.cycle_preview_size.clamp(1, MAX_PREVIEW_SIZE) as f64;
+        for client in self.cycle_previews.iter().filter_map(|id| self.wm_windows.get(id)) {
+            let (width, height) = (client.geometry.width.max(1) as f64, client.geometry.height.max(1) as f64);
+            let scale = (size / width.max(height)).min(1.0);
+            self.compositor.start_live_preview(
+                client.window,
+                area_ipc::live_preview_path(client.window),
+                ((width * scale).round() as u32).max(1),
+                ((height * scale).round() as u32).max(1),
+                behavior.cycle_preview_fps.min(MAX_PREVIEW_FPS),
+            );
+        }
+    }
+    
+    /// Tell the shell's switcher the cycle list and selection
+    fn publish_cycle(&self) {
+        let cycle = &self.wm.cycle;
+        let previews = self.config.window_manager.behavior.cycle_preview_fps > 0;
+        let windows = cycle
+            .cycle_list
+            .iter()
+            .filter_map(|id| self.wm_windows.get(id))
+            .map(|client| area_ipc::CycleEntry {
+                id: client.window,
+                title: client.name.clone(),
+                preview: previews.then(|| area_ipc::live_preview_path(client.window).to_string_lossy().into_owned()),
+            })
+            .collect();
+        self.emit_event(area_ipc::WmEvent::CycleChanged { active: cycle.active, windows, selected: cycle.selected() });
+    }
+    
+    /// End Alt+Tab cycling and activate the chosen window
+    fn finish_cycle(&mut self) -> Result<()> {
+        self.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+        let chosen = self.wm.cycle.finish_cycle();
+        for id in std::mem::take(&mut self.cycle_previews) {
+            self.compositor.stop_live_preview(id);
+        }
+        self.publish_cycle();
+        let Some(id) = chosen else {
+            self.conn.flush()?;
+            return Ok(());
+        };
+        debug!("Cycling finished on window {}", id);
+        
+        if self.wm_windows.get(&id).is_some_and(|c| c.is_minimized()) {
+            self.restore_client(id)?;
+        }
+        // The entry stood for its whole group: bring the rest along, below it
+        if self.wm.cycle.group_windows
+            && let Some(leader) = self.wm_windows.get(&id).and_then(|c| c.group_leader)
+        {
+            for member in wm::transients::group_members(&self.wm_windows, leader) {
+                if member != id && self.wm_windows.get(&member).is_some_and(|c| !c.is_minimized()) {
+                    self.wm.stacking.raise_window(member);
+                }
+            }
+        }
+        self.wm.set_focus(&self.conn, &mut self.wm_windows, id)?;
+        self.wm.restack_if_needed(&self.conn, &mut self.wm_windows)?;
+        self.flash_client(id);
+        Ok(())
+    }
+    
+    /// Apply the `[[compositor.rules]]` matching a client's class to its
+    /// compositor window
+    fn apply_compositor_rules(&mut self, client_id: u32) {
+        let Some(client) = self.wm_windows.get(&client_id) else {
+            return;
+        };
+        let outcome = wm::compositor_rules::resolve(&self.config.compositor.rules, client.class_hint.as_ref());
+        let composite_id = Self::composite_id(client);
+        self.compositor.set_window_excluded_effects(composite_id, outcome.excluded);
+        if outcome.unredirect {
+            if self.rule_unredirected.insert(client_id) {
+                debug!("Window {} unredirected by a compositor rule", client_id);
+                self.compositor.unredirect_window(composite_id);
+            }
+        } else if self.rule_unredirected.remove(&client_id)
+            && !self.wm.atoms.check_bypass_compositor(&self.conn, client_id).unwrap_or(false)
+        {
+            self.compositor.redirect_window(composite_id);
+        }
+    }
+    
+    /// Highlight a window focused from the keyboard, to find it on a big screen
+    fn flash_client(&self, client_id: u32) {
+        if let Some(client) = self.wm_windows.get(&client_id) {
+            self.compositor.flash_window(Self::composite_id(client));
+        }
+    }
+    
+    /// Follow a chord after its first key, holding the keyboard until it ends
+    fn begin_chord(&mut self, keycode: u8, state: u16) -> Result<()> {
+        let grab = self.conn.grab_keyboard(false, self.root, x11rb::CURRENT_TIME, GrabMode::ASYNC, GrabMode::ASYNC)?.reply()?;
+        if grab.status != GrabStatus::SUCCESS {
+            anyhow::bail!("keyboard grab unavailable ({:?})", grab.status);
+        }
+        let timeout = Duration::from_millis(self.config.keybindings.chord_timeout_ms);
+        self.keyboard.begin_chord(state, keycode, timeout);
+        self.update_chord_hints();
+        Ok(())
+    }
+    
+    /// Feed a key to the pending chord, running its action once it completes
+    fn chord_key(&mut self, keycode: u8, state: u16) -> Result<()> {
+        match self.keyboard.chord_key(state, keycode) {
+            wm::keyboard::ChordStep::Pending => self.update_chord_hints(),
+            wm::keyboard::ChordStep::Done(action) => {
+                self.end_chord()?;
+                self.run_keyboard_action(action);
+            }
+            wm::keyboard::ChordStep::Cancelled => {
+                debug!("Key {} completes no chord", keycode);
+                self.end_chord()?;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Release the keyboard and hide the chord hints
+    fn end_chord(&mut self) -> Result<()> {
+        if self.config.keybindings.chord_hints {
+            self.compositor.set_key_hints(Vec::new(), None);
+        }
+        self.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+    
+    /// List the ways the pending chord can be completed, bottom left on the focused monitor
+    fn update_chord_hints(&mut self) {
+        if !self.config.keybindings.chord_hints {
+            return;
+        }
+        let Some(m) = self.wm.monitors.get(self.focused_monitor()) else { return };
+        let completions = self.keyboard.chord_completions();
+        // Hint labels are 33 px high
+        let line = 36;
+        let top = m.y + m.height as i32 - 64 - line * completions.len() as i32;
+        let hints = completions
+            .into_iter()
+            .enumerate()
+            .map(|(i, label)| compositor::hints::Hint { x: m.x + 16, y: top + line * i as i32, label })
+            .collect();
+        self.compositor.set_key_hints(hints, None);
+    }
+    
+    /// Enter keyboard window management mode, holding the keyboard until it ends
+    fn enter_window_mode(&mut self) -> Result<()> {
+        let grab = self.conn.grab_keyboard(false, self.root, x11rb::CURRENT_TIME, GrabMode::ASYNC, GrabMode::ASYNC)?.reply()?;
+        if grab.status != GrabStatus::SUCCESS {
+            anyhow::bail!("keyboard grab unavailable ({:?})", grab.status);
+        }
+        info!("Entering window management mode");
+        self.wm.window_mode.active = true;
+        self.update_window_mode_hints();
+        Ok(())
+    }
+    
+    /// Leave window management mode and hide its hints
+    fn exit_window_mode(&mut self) -> Result<()> {
+        info!("Leaving window management mode");
+        self.wm.window_mode.active = false;
+        self.compositor.set_key_hints(Vec::new(), None);
+        self.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+    
+    /// Renumber the windows of the workspace and show the hints again
+    fn update_window_mode_hints(&mut self) {
+        let (windows, workspaces) = (&self.wm_windows, &self.wm.workspaces);
+        self.wm.window_mode.set_targets(self.wm.stacking.order(), |id| {
+            windows.get(&id).is_some_and(|c| workspaces.is_visible(c) && !c.is_minimized())
+        });
+        let focused = self.wm_windows.values().find(|c| c.focused());
+        let legend_at = self.wm.monitors.get(self.focused_monitor()).map_or((16, 16), |m| (m.x + 16, m.y + m.height as i32 - 64));
+        let hints = self.wm.window_mode.hints(&self.wm_windows, legend_at);
+        self.compositor.set_key_hints(hints, focused.map(Client::frame_geometry));
+    }
+    
+    /// Label the visible windows, holding the keyboard until one is picked
+    fn enter_hint_mode(&mut self) -> Result<()> {
+        let (windows, workspaces) = (&self.wm_windows, &self.wm.workspaces);
+        self.wm.hint_mode.start(self.wm.stacking.order(), |id| {
+            windows.get(&id).is_some_and(|c| workspaces.is_visible(c) && !c.is_minimized())
+        });
+        if !self.wm.hint_mode.active {
+            debug!("No windows to pick from");
+            return Ok(());
+        }
+        let grab = self.conn.grab_keyboard(false, self.root, x11rb::CURRENT_TIME, GrabMode::ASYNC, GrabMode::ASYNC)?.reply()?;
+        if grab.status != GrabStatus::SUCCESS {
+            self.wm.hint_mode.stop();
+            anyhow::bail!("keyboard grab unavailable ({:?})", grab.status);
+        }
+        let hints = self.wm.hint_mode.hints(&self.wm_windows);
+        self.compositor.set_key_hints(hints, None);
+        Ok(())
+    }
+    
+    /// Show or hide the overview of all workspaces, holding the pointer and
+    /// keyboard while it is shown
+    fn set_expo(&mut self, enabled: bool) -> Result<()> {
+        if enabled == self.wm.expo.active {
+            return Ok(());
+        }
+        if !enabled {
+            self.wm.expo.stop();
+            self.conn.ungrab_pointer(x11rb::CURRENT_TIME)?;
+            self.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+            self.conn.flush()?;
+            self.compositor.set_expo(None);
+            self.emit_event(area_ipc::WmEvent::ExpoChanged { active: false, workspaces: Vec::new() });
+            return Ok(());
+        }
+        
+        let grab = self.conn.grab_keyboard(false, self.root, x11rb::CURRENT_TIME, GrabMode::ASYNC, GrabMode::ASYNC)?.reply()?;
+        if grab.status != GrabStatus::SUCCESS {
+            anyhow::bail!("keyboard grab unavailable ({:?})", grab.status);
+        }
+        let events = EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION;
+        let grab = self.conn
+            .grab_pointer(false, self.root, events, GrabMode::ASYNC, GrabMode::ASYNC, x11rb::NONE, x11rb::NONE, x11rb::CURRENT_TIME)?
+            .reply()?;
+        if grab.status != GrabStatus::SUCCESS {
+            self.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+            anyhow::bail!("pointer grab unavailable ({:?})", grab.status);
+        }
+        self.layout_expo();
+        Ok(())
+    }
+    
+    /// Lay the overview out again from the current windows and show it
+    fn layout_expo(&mut self) {
+        use wm::client_flags::{ClientFlags, WindowType};
+        
+        let windows = self.wm.stacking.order().iter()
+            .filter_map(|id| self.wm_windows.get(id))
+            .filter(|c| !c.is_minimized() && !c.flags.contains(ClientFlags::SKIP_PAGER))
+            .filter(|c| !matches!(c.type_, WindowType::Desktop | WindowType::Dock))
+            .map(|c| wm::expo::ExpoWindow {
+                client_id: c.window,
+                composite_id: c.frame.as_ref().map_or(c.window, |frame| frame.frame),
+                workspace: (!c.is_sticky()).then_some(c.win_workspace),
+                frame: c.frame_geometry(),
+            })
+            .collect();
+        let area = self.wm.monitors.get(self.focused_monitor())
+            .map(|m| shared::Geometry::new(m.x, m.y, m.width, m.height))
+            .unwrap_or_else(|| shared::Geometry::new(0, 0, self.screen_width as u32, self.screen_height as u32));
+        let workspaces = &self.wm.workspaces;
+        self.wm.expo.start(area, (self.screen_width as u32, self.screen_height as u32), workspaces.workspace_count, workspaces.current_workspace, windows);
+        self.compositor.set_expo(Some(self.wm.expo.scene()));
+        
+        let workspaces = self.wm.expo.cells().iter().enumerate()
+            .map(|(index, cell)| area_ipc::ExpoWorkspace {
+                index: index as u32,
+                name: self.wm.workspaces.workspace_names.get(index).cloned().unwrap_or_else(|| (index + 1).to_string()),
+                rect: area_ipc::Rect { x: cell.x, y: cell.y, width: cell.width, height: cell.height },
+                current: index as u32 == self.wm.workspaces.current_workspace,
+            })
+            .collect();
+        self.emit_event(area_ipc::WmEvent::ExpoChanged { active: true, workspaces });
+    }
+    
+    /// Carry out what an input event did in the overview
+    fn expo_step(&mut self, step: wm::expo::ExpoStep) -> Result<()> {
+        use wm::expo::ExpoStep;
+        
+        match step {
+            ExpoStep::Ignore => {}
+            ExpoStep::Redraw => self.compositor.set_expo(Some(self.wm.expo.scene())),
+            ExpoStep::Cancel => self.set_expo(false)?,
+            ExpoStep::Switch { workspace, focus } => {
+                self.set_expo(false)?;
+                self.switch_workspace(workspace)?;
+                if let Some(id) = focus {
+                    self.focus_client(id)?;
+                    self.wm.restack_if_needed(&self.conn, &mut self.wm_windows)?;
+                }
+            }
+            ExpoStep::MoveWindow { id, workspace } => {
+                self.run_window_menu_action(id, area_ipc::WindowMenuAction::MoveToWorkspace(workspace))?;
+                self.layout_expo();
+            }
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+    
+    /// Focus the window whose label was typed, or leave hint mode on Escape
+    fn hint_mode_key(&mut self, keycode: u8) -> Result<()> {
+        use wm::hint_mode::HintStep;
+        
+        let keysym = wm::keyboard::KeyboardManager::keysym(&self.conn, keycode)?;
+        let selected = match self.wm.hint_mode.key(keysym) {
+            HintStep::Ignore => return Ok(()),
+            HintStep::Cancel => None,
+            HintStep::Select(id) => Some(id),
+        };
+        self.wm.hint_mode.stop();
+        self.compositor.set_key_hints(Vec::new(), None);
+        self.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+        if let Some(id) = selected {
+            self.focus_client(id)?;
+            self.wm.restack_if_needed(&self.conn, &mut self.wm_windows)?;
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+    
+    /// Run the window management mode action of a key
+    fn window_mode_key(&mut self, keycode: u8, state: u16) -> Result<()> {
+        use wm::window_mode::ModeAction;
+        
+        let keysym = wm::keyboard::KeyboardManager::keysym(&self.conn, keycode)?;
+        let shift = state & u16::from(ModMask::SHIFT) != 0;
+        let Some(action) = wm::window_mode::action(keysym, shift) else {
+            return Ok(());
+        };
+        let focused = self.wm_windows.values().find(|c| c.focused()).map(|c| c.window);
+        let step = self.config.keybindings.window_mode;
+        match action {
+            ModeAction::Exit => return self.exit_window_mode(),
+            ModeAction::Move(..) | ModeAction::Resize(..) => {
+                let step = if matches!(action, ModeAction::Move(..)) { step.move_step } else { step.resize_step };
+                if let Some(client) = focused.and_then(|id| self.wm_windows.get_mut(&id))
+                    && let Some(requested) = wm::window_mode::stepped(client.geometry, action, step)
+                {
+                    self.wm.configure_client(&self.conn, client, requested)?;
+                }
+            }
+            ModeAction::Tile(position) => {
+                if let Some(id) = focused {
+                    self.wm.tile_window(&self.conn, &mut self.wm_windows, id, position)?;
+                }
+            }
+            ModeAction::ToggleMaximize => {
+                if let Some(id) = focused {
+                    let maximized = self.wm_windows.get(&id).is_some_and(|c| c.is_maximized());
+                    self.set_client_maximized(id, !maximized)?;
+                }
+            }
+            ModeAction::Minimize => {
+                if let Some(id) = focused {
+                    self.minimize_client(id)?;
+                }
+            }
+            ModeAction::Close => {
+                if let Some(id) = focused {
+                    self.close_client(id)?;
+                }
+            }
+            ModeAction::FocusNext { backward } => {
+                if let Some(id) = self.wm.window_mode.next(focused, backward) {
+                    self.focus_client(id)?;
+                    self.wm.restack_if_needed(&self.conn, &mut self.wm_windows)?;
+                }
+            }
+            ModeAction::Focus(index) => {
+                if let Some(id) = self.wm.window_mode.target(index) {
+                    self.focus_client(id)?;
+                    self.wm.restack_if_needed(&self.conn, &mut self.wm_windows)?;
+                }
+            }
+        }
+        self.conn.flush()?;
+        self.update_window_mode_hints();
+        Ok(())
+    }
+    
+    /// Pass all shortcuts to a window while it has focus, or stop doing so
+    fn set_shortcut_inhibit(&mut self, client_id: u32, inhibit: bool) -> Result<()> {
+        let Some(client) = self.wm_windows.get_mut(&client_id) else {
+            anyhow::bail!("no managed window {}", client_id);
+        };
+        info!("Shortcuts {} for window {}", if inhibit { "inhibited" } else { "restored" }, client_id);
+        client.flags.set(crate::wm::client_flags::ClientFlags::INHIBIT_SHORTCUTS, inhibit);
+        self.sync_shortcut_inhibit();
+        Ok(())
+    }
+    
+    /// Ping the focused window and gray out windows that left a ping unanswered
+    fn check_pings(&mut self) {
+        let timeout_ms = self.config.window_manager.behavior.ping_timeout_ms;
+        if timeout_ms == 0 {
+            return;
+        }
+        if let Some(window) = self.wm_windows.values()
+            .find(|c| c.focused() && c.wm_flags.contains(crate::wm::client_flags::WmFlags::PING))
+            .map(|c| c.window)
+            && self.wm.terminate.ping_due(window, wm::terminate::PING_INTERVAL)
+            && let Err(e) = self.wm.terminate.ping(&self.conn, &self.wm.atoms, window, self.wm.time.now())
+        {
+            debug!("Failed to ping window {}: {}", window, e);
+        }
+        for window in self.wm.terminate.check_timeouts(Duration::from_millis(timeout_ms)) {
+            self.set_window_unresponsive(window, true);
+        }
+    }
+    
+    /// Focus and raise a window, switching to its workspace and
+    /// de-iconifying it first if needed
+    fn focus_client(&mut self, client_id: u32) -> Result<()> {
+        let Some(client) = self.wm_windows.get(&client_id) else {
+            anyhow::bail!("no managed window {}", client_id);
+        };
+        let (minimized, workspace) = (client.is_minimized(), client.win_workspace);
+        if !self.wm.workspaces.is_visible(client) {
+            self.switch_workspace(workspace)?;
+        }
+        if minimized {
+            self.restore_client(client_id)?;
+        }
+        self.wm.set_focus(&self.conn, &mut self.wm_windows, client_id)
+    }
+    
+    /// Managed windows matching every given criterion, most recently focused first
+    fn find_windows(&self, class: Option<&str>, title_regex: Option<&str>, workspace: Option<u32>) -> Result<Vec<u32>> {
+        let title_regex = title_regex
+            .map(regex_automata::meta::Regex::new)
+            .transpose()
+            .context("invalid title regex")?;
+        let mut ids: Vec<u32> = self
+            .wm_windows
+            .values()
+            .filter(|client| {
+                class.is_none_or(|class| {
+                    client.class_hint.as_ref().is_some_and(|hint| {
+                        hint.res_class.eq_ignore_ascii_case(class) || hint.res_name.eq_ignore_ascii_case(class)
+                    })
+                })
+            })
+            .filter(|client| title_regex.as_ref().is_none_or(|re| re.is_match(&client.name)))
+            .filter(|client| workspace.is_none_or(|ws| client.is_sticky() || client.win_workspace == ws))
+            .map(|client| client.window)
+            .collect();
+        ids.sort_by_key(|&id| (self.wm.cycle.recency(id), id));
+        Ok(ids)
+    }
+    
+    /// Focus the most recent window of `class`, cycling through its windows
+    /// on repeated calls, or run `command` if it has none
+    fn activate_or_launch(&mut self, class: &str, command: &str) -> Result<()> {
+        let ids = self.find_windows(Some(class), None, None)?;
+        if self.focus_next_of(&ids)?.is_none() {
+            info!("No {} window, running {}", class, command);
+            self.spawn_shell(command)?;
+        }
+        Ok(())
+    }
+    
+    /// Focus the window after the focused one in `ids` (the first if none
+    /// is focused); returns it, or None if `ids` has no managed window
+    fn focus_next_of(&mut self, ids: &[u32]) -> Result<Option<u32>> {
+        let ids: Vec<u32> = ids.iter().copied().filter(|id| self.wm_windows.contains_key(id)).collect();
+        let focused = ids.iter().position(|id| self.wm_windows.get(id).is_some_and(|c| c.focused()));
+        let Some(&id) = focused.map_or(ids.first(), |i| ids.get((i + 1) % ids.len())) else {
+            return Ok(None);
+        };
+        self.focus_client(id)?;
+        self.flash_client(id);
+        Ok(Some(id))
+    }
+    
+    /// Pinned applications with their open windows, for the shell
+    fn pinned_apps(&self) -> Vec<area_ipc::PinnedApp> {
+        self.wm
+            .pinned
+            .ids()
+            .iter()
+            .map(|id| {
+                let entry = self.wm.pinned.entry(id);
+                area_ipc::PinnedApp {
+                    id: id.clone(),
+                    name: entry.and_then(|entry| entry.name.clone()),
+                    icon: entry.and_then(|entry| entry.icon.clone()),
+                    windows: self.pinned_windows(id),
+                }
+            })
+            .collect()
+    }
+    
+    /// Open windows of a pinned application, most recently focused first
+    fn pinned_windows(&self, id: &str) -> Vec<u32> {
+        let mut ids: Vec<u32> = self
+            .wm_windows
+            .values()
+            .filter(|client| client.class_hint.as_ref().is_some_and(|class| self.wm.pinned.matches(id, class)))
+            .map(|client| client.window)
+            .collect();
+        ids.sort_by_key(|&id| (self.wm.cycle.recency(id), id));
+        ids
+    }
+    
+    /// Super+number: cycle the windows of a taskbar button, or launch its
+    /// pinned application when it has none
+    fn activate_taskbar_slot(&mut self, slot: u32) -> Result<()> {
+        let Some(mut button) = self.wm.taskbar.slot(slot as usize) else {
+            debug!("No taskbar button {}", slot + 1);
+            return Ok(());
+        };
+        if let Some(id) = &button.pinned {
+            if button.windows.is_empty() {
+                button.windows = self.pinned_windows(id);
+            }
+            if button.launch.is_none() {
+                button.launch = self.wm.pinned.entry(id).and_then(|entry| entry.command());
+            }
+        }
+        let window = self.focus_next_of(&button.windows)?;
+        if window.is_none() {
+            let Some(command) = button.launch else {
+                debug!("Taskbar button {} has no window or application", slot + 1);
+                return Ok(());
+            };
+            info!("Taskbar button {} has no window, running {}", slot + 1, command);
+            self.spawn_shell(&command)?;
+        }
+        self.emit_event(area_ipc::WmEvent::TaskbarSlotActivated { slot, window });
+        Ok(())
+    }
+    
+    /// Run a shell command on this display
+    fn spawn_shell(&mut self, command: &str) -> Result<()> {
+        let mut cmd = std::process::Command::new("sh");
+        cmd.arg("-c").arg(command);
+        self.launch_environment(&mut cmd);
+        self.spawn_program(&mut cmd).with_context(|| format!("failed to run {}", command))?;
+        Ok(())
+    }
+    
+    /// Start a program set up by `launch_environment` (skipped during replay)
+    fn spawn_program(&self, cmd: &mut std::process::Command) -> std::io::Result<()> {
+        if self.replaying {
+            info!("Replay: not starting {:?}", cmd.get_program());
+            return Ok(());
+        }
+        cmd.spawn().map(drop)
+    }
+    
+    /// Point a program the user starts at this display and give it an
+    /// activation token, so its first window gets focus
+    fn launch_environment(&mut self, cmd: &mut std::process::Command) {
+        cmd.envs(self.environment.vars());
+        let token = self.wm.activation.issue();
+        cmd.env("DESKTOP_STARTUP_ID", &token).env("XDG_ACTIVATION_TOKEN", token);
+    }
+    
+    /// Decorations for the compositor to paint over a client's frame, if it
+    /// draws them (`decorations.render = "compositor"`)
+    fn compositor_decoration(wm: &wm::WindowManager, client: &Client) -> Option<compositor::decorations::Decoration> {
+        let frame = client.frame.as_ref()?;
+        (wm.decoration.render == config::DecorationRender::Compositor)
+            .then(|| wm::decorations::compositor_decoration(&frame.buttons, &wm.decoration, &wm.colors))
+    }
+    
+    /// Tell the shell the size of a window resized in steps (e.g. "80x24"
+    /// for a terminal) when it changed
+    fn update_resize_feedback(&mut self) {
+        let feedback = self.wm.resizing_window().and_then(|id| {
+            let client = self.wm_windows.get(&id)?;
+            let (columns, rows) = client.size_hints.as_ref()?.size_in_increments(client.geometry.width, client.geometry.height)?;
+            Some((id, columns, rows, client.geometry))
+        });
+        let Some((id, columns, rows, geometry)) = feedback else { return };
+        if self.resize_feedback.replace((id, columns, rows)) == Some((id, columns, rows)) {
+            return;
+        }
+        let (x, y) = (geometry.x + geometry.width as i32 / 2, geometry.y + geometry.height as i32 / 2);
+        self.emit_event(area_ipc::WmEvent::ResizeFeedback { id, columns, rows, x, y });
+    }
+    
+    /// Move a window's client area to a root position, keeping its size
+    fn move_client(&mut self, client_id: u32, x: i32, y: i32) -> Result<()> {
+        let Some(client) = self.wm_windows.get_mut(&client_id) else {
+            anyhow::bail!("no managed window {}", client_id);
+        };
+        let requested = shared::Geometry::new(x, y, client.geometry.width, client.geometry.height);
+        self.wm.configure_client(&self.conn, client, requested)?;
+        self.conn.as_ref().flush()?;
+        Ok(())
+    }
+    
+    /// Close a window at the user's request; an unresponsive one instead
+    /// makes the shell offer to force quit it
+    fn close_client(&mut self, client_id: u32) -> Result<()> {
+        if self.wm.terminate.is_unresponsive(client_id)
+            && let Some(client) = self.wm_windows.get(&client_id)
+        {
+            let title = client.name.clone();
+            let pid = wm::terminate::local_pid(&self.conn, &self.wm.atoms, client_id).unwrap_or(None);
+            info!("Window {} is not responding, asking to force quit", client_id);
+            self.emit_event(area_ipc::WmEvent::ForceQuitRequested { id: client_id, title, pid });
+            return Ok(());
+        }
+        self.wm.close_window(&self.conn, &self.wm_windows, client_id)
+    }
+    
+    /// Kill a managed window's client, with `force` also its local process
+    fn kill_window(&mut self, client_id: u32, force: bool) -> Result<()> {
+        if !self.wm_windows.contains_key(&client_id) {
+            anyhow::bail!("no managed window {}", client_id);
+        }
+        self.wm.terminate.force_kill(&self.conn, &self.wm.atoms, client_id, force)
+    }
+    
+    /// The window the compositor draws for a client: its frame, or the
+    /// client itself when unframed or fullscreen
+    fn composite_id(client: &Client) -> u32 {
+        match client.frame {
+            Some(ref frame) if !client.is_fullscreen() => frame.frame,
+            _ => client.window,
+        }
+    }
+    
+    /// Show a window as unresponsive (desaturated) or normal again
+    fn set_window_unresponsive(&mut self, window: u32, unresponsive: bool) {
+        let Some(client) = self.wm_windows.get(&window) else { return };
+        let composite_id = Self::composite_id(client);
+        self.compositor.set_window_unresponsive(composite_id, unresponsive);
+        self.compositor.trigger_render();
+    }
+    
+    /// Hold the key grabs only while the focused window does not inhibit shortcuts
+    fn sync_shortcut_inhibit(&mut self) {
+        let inhibit = self.wm_windows.values()
+            .any(|c| c.focused() && c.flags.contains(crate::wm::client_flags::ClientFlags::INHIBIT_SHORTCUTS));
+        if let Err(e) = self.keyboard.set_grabs_suspended(&self.conn, self.root, inhibit) {
+            warn!("Failed to update key grabs: {}", e);
+        }
+    }
+    
+    /// Enter or leave fullscreen, moving the window between its frame and the
+    /// bare client window in the compositor
+    fn set_client_fullscreen(&mut self, client_id: u32, fullscreen: bool) -> Result<()> {
+        let Some(client) = self.wm_windows.get_mut(&client_id) else {
+            anyhow::bail!("no managed window {}", client_id);
+        };
+        if client.is_fullscreen() == fullscreen {
+            return Ok(());
+        }
+        
+        self.wm.set_fullscreen(&self.conn, client, fullscreen)?;
+        if fullscreen {
+            // The client covers its frame, render the client window directly
+            if let Some(frame) = &client.frame {
+                self.compositor.remove_window(frame.frame);
+                let c_window = crate::compositor::c_window::CWindow::new(
+                    client_id,  // composite_id = client window
+                    client_id,  // client_id = client window
+                    client.geometry,
+                    0,  // border_width = 0 for fullscreen
+                    true,  // viewable = true (client is mapped)
+                );
+                self.compositor.add_window(c_window);
+            }
+            if self.config.compositor.unredirect_fullscreen {
+                self.compositor.unredirect_window(client_id);
+            }
+        } else {
+            if self.config.compositor.unredirect_fullscreen {
+                self.compositor.redirect_window(client_id);
+            }
+            self.compositor.remove_window(client_id);
+            // The frame got its decorations back in set_fullscreen
+            if let Some(frame) = &client.frame {
+                let mut c_window = crate::compositor::c_window::CWindow::new(
+                    frame.frame,  // composite_id = frame window
+                    client_id,    // client_id = client window
+                    client.frame_geometry(),
+                    client.frame_metrics.border as u16,
+                    true,  // viewable = true (frame is mapped)
+                );
+                c_window.decoration = Self::compositor_decoration(&self.wm, client);
+                self.compositor.add_window(c_window);
+            }
+        }
+        // The compositor window was replaced, carry over the unresponsive look
+        // and dimming
+        if self.wm.terminate.is_unresponsive(client_id) {
+            self.set_window_unresponsive(client_id, true);
+        }
+        if let Some(&brightness) = self.dimmed.get(&client_id) {
+            self.compositor.set_window_brightness(Self::composite_id(&self.wm_windows[&client_id]), brightness);
+        }
+        self.rule_unredirected.remove(&client_id);
+        self.apply_compositor_rules(client_id);
+        self.update_window_shape(client_id)?;
+        self.update_opaque_region(client_id)?;
+        self.window_state_changed.insert(client_id);
+        Ok(())
+    }
+    
+    /// Re-read a client's XShape bounding region and pass it to the
+    /// compositor (and the frame's input region)
+    fn update_window_shape(&mut self, client_id: u32) -> Result<()> {
+        let rects = wm::shape::bounding_rects(self.conn.as_ref(), client_id)?;
+        let Some(client) = self.wm_windows.get_mut(&client_id) else { return Ok(()) };
+        client.flags.set(crate::wm::client_flags::ClientFlags::HAS_SHAPE, rects.is_some());
+        let composite_id = Self::composite_id(client);
+        let shape = match client.frame {
+            Some(ref frame) if composite_id == frame.frame => {
+                let titlebar = client.frame_metrics.titlebar;
+                wm::shape::set_frame_input(self.conn.as_ref(), frame.frame, client_id, titlebar, rects.is_some())?;
+                // The titlebar is drawn whatever the client's shape
+                rects.map(|rects| {
+                    std::iter::once(shared::Geometry::new(0, 0, u16::MAX as u32, titlebar))
+                        .chain(rects.into_iter().map(|r| shared::Geometry::new(r.x, r.y + titlebar as i32, r.width, r.height)))
+                        .collect()
+                })
+            }
+            _ => rects,
+        };
+        self.compositor.set_window_shape(composite_id, shape);
+        Ok(())
+    }
+    
+    /// Re-read a client's _NET_WM_OPAQUE_REGION and pass it to the compositor
+    /// (in frame coordinates for a framed client)
+    fn update_opaque_region(&mut self, client_id: u32) -> Result<()> {
+        let region = self.wm.atoms.get_opaque_region(self.conn.as_ref(), client_id)?;
+        let Some(client) = self.wm_windows.get(&client_id) else { return Ok(()) };
+        let composite_id = Self::composite_id(client);
+        let region = match client.frame {
+            Some(ref frame) if composite_id == frame.frame => {
+                let titlebar = client.frame_metrics.titlebar as i32;
+                region.map(|rects| rects.into_iter().map(|r| shared::Geometry { y: r.y + titlebar, ..r }).collect())
+            }
+            _ => region,
+        };
+        self.compositor.set_window_opaque_region(composite_id, region);
+        Ok(())
+    }
+    
+    /// Emit `PointerMoved` if the pointer moved since the last sample
+    fn sample_pointer(&self, last: &mut Option<(i32, i32)>) -> Result<()> {
+        let Some(ref ipc) = self.ipc else {
+            return Ok(());
+        };
+        if !ipc.wants_pointer() {
+            *last = None;
+            return Ok(());
+        }
+        
+        let pointer = self.conn.as_ref().query_pointer(self.root)?.reply()?;
+        let position = (pointer.root_x as i32, pointer.root_y as i32);
+        if *last == Some(position) {
+            return Ok(());
+        }
+        *last = Some(position);
+        
+        let monitor = self.wm.monitor_at(position.0, position.1)
+            .unwrap_or_else(|| self.wm.primary_monitor()) as u32;
+        ipc.emit(area_ipc::WmEvent::PointerMoved { x: position.0, y: position.1, monitor });
+        Ok(())
+    }
+    
+    /// Send `WorkspaceLayout` if it changed or a client subscribed since the last one
+    fn publish_workspace_layout(&mut self) {
+        let subscribers = self.ipc.as_ref().map_or(0, |ipc| ipc.subscribers());
+        if subscribers == 0 {
+            self.workspace_layout = None;
+            return;
+        }
+        
+        let scale = area_ipc::PAGER_SCALE as i32;
+        let workspaces: Vec<area_ipc::WorkspaceInfo> = (0..self.wm.workspaces.workspace_count)
+            .map(|index| {
+                let windows = self.wm.stacking.order().iter()
+                    .filter_map(|id| self.wm_windows.get(id))
+                    .filter(|c| c.is_sticky() || c.win_workspace == index)
+                    .filter(|c| !c.is_minimized() && !c.flags.contains(wm::client_flags::ClientFlags::SKIP_PAGER))
+                    .filter(|c| !matches!(c.type_, wm::client_flags::WindowType::Desktop | wm::client_flags::WindowType::Dock))
+                    .map(|c| {
+                        let geometry = c.frame_geometry();
+                        area_ipc::PagerWindow {
+                            id: c.window,
+                            rect: area_ipc::Rect {
+                                x: geometry.x / scale,
+                                y: geometry.y / scale,
+                                width: (geometry.width / scale as u32).max(1),
+                                height: (geometry.height / scale as u32).max(1),
+                            },
+                            focused: c.focused(),
+                            urgent: c.is_urgent(),
+                        }
+                    })
+                    .collect();
+                area_ipc::WorkspaceInfo {
+                    index,
+                    name: self.wm.workspaces.workspace_names.get(index as usize).cloned().unwrap_or_else(|| (index + 1).to_string()),
+                    current: index == self.wm.workspaces.current_workspace,
+                    windows,
+                }
+            })
+            .collect();
+        
+        if self.workspace_layout.as_ref().is_some_and(|(count, last)| *count == subscribers && *last == workspaces) {
+            return;
+        }
+        self.emit_event(area_ipc::WmEvent::WorkspaceLayout {
+            width: self.screen_width as u32 / area_ipc::PAGER_SCALE,
+            height: self.screen_height as u32 / area_ipc::PAGER_SCALE,
+            workspaces: workspaces.clone(),
+        });
+        self.workspace_layout = Some((subscribers, workspaces));
+    }
+    
+    /// Publish `_NET_WORKAREA` and refit maximized windows after struts changed
+    fn apply_work_area(&mut self) {
+        if let Err(e) = self.wm.update_workarea(&self.conn) {
+            warn!("Failed to update _NET_WORKAREA: {}", e);
+        }
+        
+        let maximized: Vec<u32> = self.wm_windows.values()
+            .filter(|c| c.is_maximized() && !c.is_fullscreen())
+            .map(|c| c.window)
+            .collect();
+        for id in maximized {
+            if let Some(client) = self.wm_windows.get_mut(&id)
+                && let Err(e) = self.wm.maximize_window(&self.conn, client)
+            {
+                warn!("Failed to refit maximized window {}: {}", id, e);
+            }
+        }
+    }
+    
+    /// Replace a window's XShape input region (clicks elsewhere fall through)
+    fn set_input_region(&self, window: u32, rects: &[area_ipc::Rect]) -> Result<()> {
+        use x11rb::protocol::shape::{ConnectionExt as _, SK, SO};
+        
+        let rectangles: Vec<x11rb::protocol::xproto::Rectangle> = rects
+            .iter()
+            .map(|r| x11rb::protocol::xproto::Rectangle {
+                x: r.x.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+                y: r.y.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+                width: r.width.min(u16::MAX as u32) as u16,
+                height: r.height.min(u16::MAX as u32) as u16,
+            })
+            .collect();
+        self.conn.as_ref()
+            .shape_rectangles(SO::SET, SK::INPUT, x11rb::protocol::xproto::ClipOrdering::UNSORTED, window, 0, 0, &rectangles)?
+            .check()
+            .with_context(|| format!("failed to set input region of window {}", window))?;
+        debug!("Input region of window {} set to {} rectangles", window, rectangles.len());
+        Ok(())
+    }
+    
+    /// Reserve screen space for a shell panel (size 0 releases it)
+    fn set_panel_strut(&mut self, edge: area_ipc::PanelEdge, size: u32, start: u32, end: u32, monitor: u32) -> Result<()> {
+        use wm::strut::PanelStrut;
+        
+        if monitor as usize >= self.wm.monitors.len() {
+            anyhow::bail!("no monitor {}", monitor);
+        }
+        let edge = Self::strut_edge(edge);
+        let panel = (size > 0).then_some(PanelStrut { size, start, end });
+        self.wm.set_panel_strut(monitor, edge, panel);
+        self.apply_work_area();
+        self.conn.as_ref().flush()?;
+        Ok(())
+    }
+    
+    /// Report pointer pressure against a monitor edge (`SetEdgeTrigger`)
+    fn set_edge_trigger(&mut self, edge: area_ipc::PanelEdge, monitor: u32, enabled: bool) -> Result<()> {
+        if monitor as usize >= self.wm.monitors.len() {
+            anyhow::bail!("no monitor {}", monitor);
+        }
+        self.wm.set_edge_trigger(&self.conn, monitor, Self::strut_edge(edge), enabled)?;
+        self.conn.as_ref().flush()?;
+        Ok(())
+    }
+    
+    fn strut_edge(edge: area_ipc::PanelEdge) -> wm::strut::StrutEdge {
+        use wm::strut::StrutEdge;
+        match edge {
+            area_ipc::PanelEdge::Left => StrutEdge::Left,
+            area_ipc::PanelEdge::Right => StrutEdge::Right,
+            area_ipc::PanelEdge::Top => StrutEdge::Top,
+            area_ipc::PanelEdge::Bottom => StrutEdge::Bottom,
+        }
+    }
+    
+    /// Start (or keep alive) streaming a window's live preview; returns the shared-memory file
+    fn request_live_preview(&self, id: u32, width: u32, height: u32, fps: u32) -> Result<std::path::PathBuf> {
+        if !self.wm_windows.contains_key(&id) {
+            anyhow::bail!("no managed window {}", id);
+        }
+        use compositor::preview::{MAX_PREVIEW_FPS, MAX_PREVIEW_SIZE};
+        
+        if !(1..=MAX_PREVIEW_SIZE).contains(&width) || !(1..=MAX_PREVIEW_SIZE).contains(&height) {
+            anyhow::bail!("preview size must be between 1x1 and {0}x{0}", MAX_PREVIEW_SIZE);
+        }
+        let path = area_ipc::live_preview_path(id);
+        self.compositor.start_live_preview(id, path.clone(), width, height, fps.clamp(1, MAX_PREVIEW_FPS));
+        Ok(path)
+    }
+    
+    /// Change the default, per-monitor or per-workspace wallpaper
+    fn set_wallpaper(
+        &mut self,
+        path: String,
+        mode: Option<area_ipc::WallpaperMode>,
+        monitor: Option<u32>,
+        workspace: Option<u32>,
+    ) -> Result<()> {
+        if !std::path::Path::new(&path).is_file() {
+            anyhow::bail!("no such file: {}", path);
+        }
+        let wallpaper = &mut self.config.wallpaper;
+        let spec = config::WallpaperSpec { path, mode };
+        match (workspace, monitor) {
+            (Some(workspace), _) => {
+                wallpaper.workspaces.insert(workspace.to_string(), spec);
+            }
+            (None, Some(monitor)) => {
+                let name = self.wm.monitors.get(monitor as usize)
+                    .with_context(|| format!("no monitor {}", monitor))?
+                    .name
+                    .clone();
+                wallpaper.monitors.insert(name, spec);
+            }
+            (None, None) => {
+                wallpaper.path = Some(spec.path);
+                if let Some(mode) = spec.mode {
+                    wallpaper.mode = mode;
+                }
+            }
+        }
+        self.reload_wallpaper();
+        Ok(())
+    }
+    
+    /// Decode the configured wallpapers off the event loop and hand them to the compositor
+    fn reload_wallpaper(&self) {
+        let choices = self.wallpaper_choices(self.wm.workspaces.current_workspace);
+        let crossfade = Duration::from_millis(self.config.wallpaper.crossfade_ms);
+        let compositor = self.compositor.clone();
+        let root_pixmap = self.root_pixmap.clone();
+        let (width, height) = (self.screen_width, self.screen_height);
+        tokio::task::spawn_blocking(move || match compositor::wallpaper::load_layers(&choices) {
+            Ok(layers) => {
+                // Leave the root pixmap of other wallpaper setters alone unless we have one
+                if let Some(root_pixmap) = root_pixmap.filter(|_| !layers.is_empty())
+                    && let Err(e) = root_pixmap.publish(&layers, width, height)
+                {
+                    warn!("Failed to publish root pixmap: {:#}", e);
+                }
+                compositor.set_wallpaper(layers, crossfade);
+            }
+            Err(e) => warn!("Failed to load wallpaper: {:#}", e),
+        });
+    }
+    
+    /// Pick up a new root size and monitor layout after a RandR change
+    ///
+    /// The setup's screen size is fixed at connect time, so the root geometry
+    /// is queried instead. Rotation needs nothing beyond this: the server
+    /// rotates at scanout and root coordinates (window geometry, pointer
+    /// events) are already in the rotated space.
+    fn handle_screen_change(&mut self) -> Result<()> {
+        let geometry = self.conn.get_geometry(self.root)?.reply()?;
+        let (width, height) = (geometry.width, geometry.height);
+        if width != self.screen_width || height != self.screen_height {
+            info!("Screen size changed: {}x{} -> {}x{}",
+                self.screen_width, self.screen_height, width, height);
+            self.screen_width = width;
+            self.screen_height = height;
+            // Update shell with new screen size
+            self.shell.set_screen_size(width, height);
+            self.compositor.set_screen_size(width, height);
+        }
+        
+        let previous: Vec<_> = self.wm.monitors.iter()
+            .map(|m| (m.x, m.y, m.width, m.height, m.rotation))
+            .collect();
+        let previous_outputs = self.wm.output_areas();
+        if let Err(e) = self.wm.refresh_monitors(&self.conn) {
+            warn!("Failed to refresh monitors: {}", e);
+        }
+        let layout_changed = self.wm.monitors.len() != previous.len()
+            || self.wm.monitors.iter().zip(&previous)
+                .any(|(m, p)| (m.x, m.y, m.width, m.height, m.rotation) != *p);
+        if layout_changed {
+            // A hotplugged output may have a configured scale
+            if self.wm.monitors.len() != previous.len() {
+                self.apply_output_scales();
+            }
+            self.apply_work_area();
+            self.reload_wallpaper();
+            
+            // Windows of a removed output move to the primary one (and back
+            // if it returns); anything else left off-screen comes back into reach
+            if let Err(e) = self.wm.migrate_windows(&self.conn, &mut self.wm_windows, &previous_outputs) {
+                warn!("Failed to migrate windows between monitors: {}", e);
+            }
+            for client in self.wm_windows.values_mut() {
+                if let Err(e) = self.wm.constrain_window(&self.conn, client) {
+                    warn!("Failed to constrain window {}: {}", client.window, e);
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    /// Apply the experimental per-output scales from the config
+    ///
+    /// The resulting RandR change comes back as a screen change event.
+    fn apply_output_scales(&mut self) {
+        if let Err(e) = self.output_scaling.apply(&self.config.compositor.output_scale) {
+            warn!("Failed to apply output scales: {:#}", e);
+        }
+    }
+    
+    /// Send an event to subscribed IPC clients
+    /// Record a notification and pass it on to the shell unless Do Not
+    /// Disturb holds it back (critical ones always go through)
+    fn handle_notification(&mut self, message: dbus::notification_server::NotificationMessage) {
+        use dbus::notification_server::NotificationMessage;
+        match message {
+            NotificationMessage::Notify(notification) => {
+                if let Err(e) = self.wm.notifications.record(notification.clone(), self.config.notifications.history_size) {
+                    warn!("Failed to save notification history: {}", e);
+                }
+                if self.wm.notifications.do_not_disturb && notification.urgency < 2 {
+                    debug!("Do Not Disturb: notification {} only recorded", notification.id);
+                } else {
+                    self.emit_event(area_ipc::WmEvent::Notification { notification });
+                }
+            }
+            NotificationMessage::Close(id) => self.emit_event(area_ipc::WmEvent::NotificationClosed { id }),
+        }
+    }
+    
+    /// Apply an idle inhibitor change to the blank and DPMS timeouts
+    fn handle_screensaver(&mut self, message: dbus::screensaver::ScreenSaverMessage) -> Result<()> {
+        use dbus::screensaver::ScreenSaverMessage;
+        match message {
+            ScreenSaverMessage::Inhibit { cookie, application, reason, sender } => {
+                self.wm.dpms.inhibit(&self.conn, cookie, wm::dpms::Inhibitor { application, reason, sender })?
+            }
+            ScreenSaverMessage::UnInhibit(cookie) => self.wm.dpms.uninhibit(&self.conn, cookie)?,
+            ScreenSaverMessage::Vanished(sender) => self.wm.dpms.client_vanished(&self.conn, &sender)?,
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+    
+    fn set_do_not_disturb(&mut self, enabled: bool) -> Result<()> {
+        if self.wm.notifications.set_do_not_disturb(enabled)? {
+            info!("Do Not Disturb {}", if enabled { "on" } else { "off" });
+            self.emit_event(area_ipc::WmEvent::DoNotDisturbChanged { enabled });
+        }
+        Ok(())
+    }
+    
+    fn emit_event(&self, event: area_ipc::WmEvent) {
+        if let Some(ref ipc) = self.ipc {
+            ipc.emit(event);
+        }
+    }
+    
+    /// Re-read a client's urgency (WM_HINTS + _NET_WM_STATE_DEMANDS_ATTENTION)
+    /// and notify IPC subscribers if it changed
+    fn update_urgency(&mut self, client_id: u32) -> Result<()> {
+        use wm::client_flags::{ClientFlags, WmFlags};
+        
+        let hint_urgent = x11rb::properties::WmHints::get(self.conn.as_ref(), client_id)?
+            .reply()?
+            .is_some_and(|hints| hints.urgent);
+        let demands_attention = self.wm.atoms.get_window_state(&self.conn, client_id)?
+            .contains(&self.wm.atoms._net_wm_state_demands_attention);
+        
+        let Some(client) = self.wm_windows.get_mut(&client_id) else {
+            return Ok(());
+        };
+        let was_urgent = client.is_urgent();
+        client.wm_flags.set(WmFlags::URGENT, hint_urgent);
+        client.flags.set(ClientFlags::DEMANDS_ATTENTION, demands_attention);
+        let urgent = client.is_urgent();
+        
+        let parent = client.transient_for;
+        
+        if urgent != was_urgent {
+            debug!("Window {} urgency changed: {}", client_id, urgent);
+            self.emit_event(area_ipc::WmEvent::WindowUrgencyChanged { id: client_id, urgent });
+        }
+        
+        // A dialog asking for attention brings its minimized parent back
+        if urgent
+            && let Some(parent) = parent
+            && self.wm_windows.get(&parent).is_some_and(|c| c.is_minimized())
+        {
+            debug!("Restoring window {} for its urgent transient {}", parent, client_id);
+            self.restore_client(parent)?;
+        }
+        Ok(())
+    }
+    
+    /// Focus a newly mapped window if it may take focus, otherwise have it
+    /// demand attention
+    fn activate_new_window(&mut self, client_id: u32) -> Result<()> {
+        use wm::client_flags::WindowType;
+        let Some(client) = self.wm_windows.get(&client_id) else { return Ok(()) };
+        if !matches!(client.type_, WindowType::Normal | WindowType::Dialog | WindowType::ModalDialog)
+            || client.is_minimized()
+            || !self.wm.workspaces.is_visible(client)
+        {
+            return Ok(());
+        }
+        if self.may_take_focus(client_id) {
+            self.wm.set_focus(&self.conn, &mut self.wm_windows, client_id)?;
+            self.wm.restack_if_needed(&self.conn, &mut self.wm_windows)?;
+            Ok(())
+        } else {
+            self.demand_attention(client_id)
+        }
+    }
+    
+    /// Whether an application may give a window focus: it shows a fresh
+    /// activation token, the user used it since their last input elsewhere
+    /// (_NET_WM_USER_TIME; 0 asks not to be focused), nothing has focus, or
+    /// the focused window belongs to the same application (its dialogs and
+    /// other windows)
+    fn may_take_focus(&mut self, client_id: u32) -> bool {
+        let behavior = &self.config.window_manager.behavior;
+        if !behavior.focus_stealing_prevention {
+            return true;
+        }
+        let lifetime = Duration::from_millis(behavior.activation_token_timeout_ms);
+        match wm::activation::startup_id(&self.conn, client_id) {
+            Ok(Some(token)) if self.wm.activation.consume(&token, lifetime) => {
+                debug!(target: "area::wm::focus", "Window {} presented activation token {}", client_id, token);
+                return true;
+            }
+            Ok(_) => {}
+            Err(e) => debug!("Failed to read _NET_STARTUP_ID of {}: {}", client_id, e),
+        }
+        match wm::timestamp::user_time(&self.conn, client_id) {
+            Ok(Some(0)) => {
+                debug!(target: "area::wm::focus", "Window {} asks not to be focused", client_id);
+                return false;
+            }
+            Ok(Some(time)) if self.wm.time.since_last_input(time) => {
+                debug!(target: "area::wm::focus", "Window {} was used at {}, after the last input", client_id, time);
+                return true;
+            }
+            Ok(_) => {}
+            Err(e) => debug!("Failed to read _NET_WM_USER_TIME of {}: {}", client_id, e),
+        }
+        let Some(client) = self.wm_windows.get(&client_id) else { return false };
+        let Some(focused) = self.wm_windows.values().find(|c| c.focused()) else { return true };
+        focused.window == client_id
+            || client.transient_for == Some(focused.window)
+            || (client.pid != 0 && client.pid == focused.pid)
+            || (client.group_leader.is_some() && client.group_leader == focused.group_leader)
+    }
+    
+    /// Mark a window that was denied focus as demanding attention
+    fn demand_attention(&mut self, client_id: u32) -> Result<()> {
+        info!("Window {} may not take focus, marking it as demanding attention", client_id);
+        self.wm.atoms.set_window_state(
+            &self.conn,
+            client_id,
+            &[self.wm.atoms._net_wm_state_demands_attention],
+            &[],
+        )?;
+        self.update_urgency(client_id)
+    }
+    
+    /// Drop a window's attention request (clears both the ICCCM hint and the EWMH state)
+    fn clear_urgency(&mut self, client_id: u32) -> Result<()> {
+        use wm::client_flags::{ClientFlags, WmFlags};
+        
+        let Some(client) = self.wm_windows.get_mut(&client_id) else {
+            anyhow::bail!("no managed window {}", client_id);
+        };
+        let was_urgent = client.is_urgent();
+        client.wm_flags.remove(WmFlags::URGENT);
+        client.flags.remove(ClientFlags::DEMANDS_ATTENTION);
+        
+        wm::hints::clear_urgency_hint(&self.conn, client_id)?;
+        self.wm.atoms.set_window_state(
+            &self.conn,
+            client_id,
+            &[],
+            &[self.wm.atoms._net_wm_state_demands_attention],
+        )?;
+        self.conn.as_ref().flush()?;
+        
+        if was_urgent {
+            self.emit_event(area_ipc::WmEvent::WindowUrgencyChanged { id: client_id, urgent: false });
+        }
+        Ok(())
+    }
+    
     /// Handle an X11 event
+    #[tracing::instrument(level = "trace", skip_all, fields(event = event.response_type()))]
     async fn handle_event(&mut self, event: Event) -> Result<()> {
-        // Check for screen size changes (detect via root window geometry)
-        let current_screen = &self.conn.as_ref().setup().roots[0];
-        let current_width = current_screen.width_in_pixels;
-        let current_height = current_screen.height_in_pixels;
-        if current_width != self.screen_width || current_height != self.screen_height {
-            info!("Screen size changed: {}x{} -> {}x{}", 
-                self.screen_width, self.screen_height, current_width, current_height);
-            self.screen_width = current_width;
-            self.screen_height = current_height;
-            // Update shell with new screen size
-            self.shell.set_screen_size(current_width, current_height);
-        }
-        
+        self.wm.time.observe(&event);
         match event {
+            // Resolution, rotation or output layout changed
+            Event::RandrScreenChangeNotify(_) | Event::RandrNotify(_) => {
+                self.handle_screen_change()?;
+            }
             Event::MapRequest(e) => {
                 info!("⭐ MapRequest for window {}", e.window);
+                let is_new = !self.wm_windows.contains_key(&e.window);
                 self.handle_map_request(e.window)?;
+                if is_new && let Err(err) = self.activate_new_window(e.window) {
+                    warn!("Failed to activate new window {}: {}", e.window, err);
+                }
             }
             
             Event::UnmapNotify(e) => {
@@ -513,6 +3428,11 @@ impl AreaApp {
                     return Ok(());
                 }
                 
+                if self.popups.remove(e.window) {
+                    self.compositor.remove_window(e.window);
+                    return Ok(());
+                }
+                
                 // Don't unmanage framed windows on UnmapNotify - they get unmapped during
                 // reparenting and other normal operations. Only unmanage on DestroyNotify.
                 if let Some(client) = self.wm_windows.get(&e.window) {
@@ -558,7 +3478,8 @@ impl AreaApp {
                                     if let Err(err) = self.wm.set_fullscreen(&self.conn, client, true) {
                                         warn!("Failed to set fullscreen for window {} (ConfigureRequest detection): {}", cid, err);
                                     } else {
-                                        // If window has a frame, add client window to compositor (frame is unmapped)
+                                        self.window_state_changed.insert(cid);
+                                        // If window has a frame, add client window to compositor (it covers the frame)
                                         if client.frame.is_some() {
                                             // Add client window to compositor for fullscreen rendering
                                             let client_geom = client.geometry;
@@ -572,7 +3493,7 @@ impl AreaApp {
                                             self.compositor.add_window(c_window);
                                         }
                                         // Coordinate with compositor: unredirect if config allows
-                                        // Use client window directly for fullscreen (frame is hidden)
+                                        // Use client window directly for fullscreen (frame is covered)
                                         if self.config.compositor.unredirect_fullscreen {
                                             self.compositor.unredirect_window(cid);
                                         }
@@ -583,22 +3504,65 @@ impl AreaApp {
                     }
                 }
                 
+                // Managed windows move with their frame and keep their titlebar
+                // reachable; maximized, fullscreen and tiled ones keep the
+                // geometry the WM gave them (see `configure_policy`)
+                let managed = client_id == Some(e.window);
+                if managed && let Some(client) = self.wm_windows.get_mut(&e.window) {
+                    use x11rb::protocol::xproto::ConfigWindow;
+                    let mut requested = client.geometry;
+                    if e.value_mask.contains(ConfigWindow::X) {
+                        requested.x = e.x as i32;
+                    }
+                    if e.value_mask.contains(ConfigWindow::Y) {
+                        requested.y = e.y as i32;
+                    }
+                    if e.value_mask.contains(ConfigWindow::WIDTH) && e.width > 10 {
+                        requested.width = e.width as u32;
+                    }
+                    if e.value_mask.contains(ConfigWindow::HEIGHT) && e.height > 10 {
+                        requested.height = e.height as u32;
+                    }
+                    let granted = self.wm.configure_policy.resolve(client, requested);
+                    if granted != requested {
+                        debug!("ConfigureRequest of window {}: {:?} adjusted to {:?}", e.window, requested, granted);
+                    }
+                    if granted != client.geometry {
+                        self.wm.configure_client(&self.conn, client, granted)?;
+                    }
+                    self.wm.send_configure_notify(&self.conn, client)?;
+                }
+                
                 // Grant the configure request
-                self.conn.as_ref().configure_window(
-                    e.window,
-                    &ConfigureWindowAux::new()
+                let mut aux = if managed {
+                    ConfigureWindowAux::new()
+                } else {
+                    ConfigureWindowAux::new()
                         .x(e.x as i32)
                         .y(e.y as i32)
                         .width(e.width as u32)
                         .height(e.height as u32)
                         .border_width(e.border_width as u32)
-                        .sibling(e.sibling)
-                        .stack_mode(e.stack_mode),
-                )?;
+                };
+                if let Some(cid) = client_id
+                    && e.value_mask.contains(x11rb::protocol::xproto::ConfigWindow::STACK_MODE)
+                {
+                    // Managed windows only move within their own layer
+                    match e.stack_mode {
+                        x11rb::protocol::xproto::StackMode::ABOVE => self.wm.stacking.raise_window(cid),
+                        x11rb::protocol::xproto::StackMode::BELOW => self.wm.stacking.lower_window(cid),
+                        _ => {}
+                    }
+                } else {
+                    aux = aux.sibling(e.sibling).stack_mode(e.stack_mode);
+                }
+                self.conn.as_ref().configure_window(e.window, &aux)?;
                 self.conn.as_ref().flush()?;
                 
                 // Update geometry if window is already managed
-                if let Some(client) = self.wm_windows.get_mut(&e.window) {
+                if managed {
+                    debug!("Configured managed window {} to {:?}", e.window, self.wm_windows[&e.window].geometry);
+                } else if let Some(client) = self.wm_windows.get_mut(&e.window) {
                     if e.width > 10 && e.height > 10 {
                         client.geometry.width = e.width as u32;
                         client.geometry.height = e.height as u32;
@@ -646,6 +3610,9 @@ impl AreaApp {
             }
             
             Event::DestroyNotify(e) => {
+                if self.popups.remove(e.window) {
+                    self.compositor.remove_window(e.window);
+                }
                 if let Err(err) = self.handle_destroy(e.window) {
                     warn!("Error handling DestroyNotify for window {}: {}", e.window, err);
                 }
@@ -653,20 +3620,37 @@ impl AreaApp {
             
             Event::ClientMessage(e) => {
                 // Handle _NET_CLOSE_WINDOW (EWMH close request)
-                if let Ok(net_close_atom) = self.conn.as_ref().intern_atom(false, b"_NET_CLOSE_WINDOW")?.reply() {
-                    if e.type_ == net_close_atom.atom && e.format == 32 {
-                        debug!("ClientMessage: _NET_CLOSE_WINDOW for window {}", e.window);
-                        // Find the client window (could be the window itself or its frame)
-                        let client_id = self.wm.find_client_from_window(&self.wm_windows, e.window);
-                        if let Some(client_id) = client_id {
-                            if let Err(err) = self.wm.close_window(&self.conn, client_id) {
-                                warn!("Failed to close window {} via _NET_CLOSE_WINDOW: {}", client_id, err);
-                            }
-                        } else {
-                            debug!("_NET_CLOSE_WINDOW for unmanaged window {}", e.window);
+                if e.type_ == self.wm.atoms._net_close_window && e.format == 32 {
+                    debug!("ClientMessage: _NET_CLOSE_WINDOW for window {}", e.window);
+                    // Find the client window (could be the window itself or its frame)
+                    let client_id = self.wm.find_client_from_window(&self.wm_windows, e.window);
+                    if let Some(client_id) = client_id {
+                        if let Err(err) = self.close_client(client_id) {
+                            warn!("Failed to close window {} via _NET_CLOSE_WINDOW: {}", client_id, err);
                         }
-                        return Ok(());
+                    } else {
+                        debug!("_NET_CLOSE_WINDOW for unmanaged window {}", e.window);
+                    }
+                    return Ok(());
+                }
+                
+                // Handle _NET_CURRENT_DESKTOP (pager workspace switch)
+                if e.type_ == self.wm.atoms.net_current_desktop && e.format == 32 {
+                    let workspace = e.data.as_data32()[0];
+                    debug!("ClientMessage: _NET_CURRENT_DESKTOP {}", workspace);
+                    if let Err(err) = self.switch_workspace(workspace) {
+                        warn!("Failed to switch to workspace {}: {}", workspace, err);
+                    }
+                    return Ok(());
+                }
+                
+                // Handle _GTK_SHOW_WINDOW_MENU (client-side decorations: device, x_root, y_root)
+                if e.type_ == self.wm.atoms._gtk_show_window_menu && e.format == 32 {
+                    let data32 = e.data.as_data32();
+                    if let Some(client_id) = self.wm.find_client_from_window(&self.wm_windows, e.window) {
+                        self.show_window_menu(client_id, data32[1] as i16, data32[2] as i16);
                     }
+                    return Ok(());
                 }
                 
                 // Handle _NET_WM_STATE (EWMH state change requests)
@@ -719,58 +3703,9 @@ impl AreaApp {
                                 
                                 if should_change {
                                     debug!("Setting fullscreen={} for window {}", !current, client_id);
-                                    if let Some(client) = self.wm_windows.get_mut(&client_id) {
-                                        if let Err(err) = self.wm.set_fullscreen(&self.conn, client, !current) {
-                                            warn!("Failed to set fullscreen for window {}: {}", client_id, err);
-                                        } else {
-                                            debug!("Successfully set fullscreen={} for window {}", !current, client_id);
-                                            state_changed = true;
-                                            // Coordinate with compositor: unredirect/redirect based on fullscreen state
-                                                // Use client window directly for fullscreen (frame is hidden)
-                                            if !current {
-                                                // Entering fullscreen
-                                                // If window has a frame, remove frame from compositor and add client window
-                                                if let Some(frame) = &client.frame {
-                                                    // Remove frame window from compositor (frame is unmapped)
-                                                    self.compositor.remove_window(frame.frame);
-                                                    // Add client window to compositor for fullscreen rendering
-                                                    let client_geom = client.geometry;
-                                                    let c_window = crate::compositor::c_window::CWindow::new(
-                                                        client_id,  // composite_id = client window
-                                                        client_id,  // client_id = client window
-                                                        client_geom,
-                                                        0,  // border_width = 0 for fullscreen
-                                                        true,  // viewable = true (client is mapped)
-                                                    );
-                                                    self.compositor.add_window(c_window);
-                                                }
-                                                // Unredirect if config allows
-                                                if self.config.compositor.unredirect_fullscreen {
-                                                    self.compositor.unredirect_window(client_id);
-                                                }
-                                            } else {
-                                                // Exiting fullscreen - redirect back and remove client window
-                                                if self.config.compositor.unredirect_fullscreen {
-                                                    self.compositor.redirect_window(client_id);
-                                                }
-                                                // Remove client window from compositor
-                                                self.compositor.remove_window(client_id);
-                                                // Re-add frame window to compositor (frame is mapped back in set_fullscreen)
-                                                if let Some(frame) = &client.frame {
-                                                    // Frame window needs to be re-added to compositor
-                                                    // Use the same logic as initial window mapping
-                                                    let frame_geom = client.frame_geometry();
-                                                    let c_window = crate::compositor::c_window::CWindow::new(
-                                                        frame.frame,  // composite_id = frame window
-                                                        client_id,    // client_id = client window
-                                                        frame_geom,
-                                                        2,  // border_width = 2
-                                                        true,  // viewable = true (frame is mapped)
-                                                    );
-                                                    self.compositor.add_window(c_window);
-                                                }
-                                            }
-                                        }
+                                    match self.set_client_fullscreen(client_id, !current) {
+                                        Ok(()) => state_changed = true,
+                                        Err(err) => warn!("Failed to set fullscreen for window {}: {}", client_id, err),
                                     }
                                 }
                             }
@@ -785,20 +3720,9 @@ impl AreaApp {
                                 let should_change = should_apply(current, action);
                                 
                                 if should_change {
-                                    if let Some(client) = self.wm_windows.get_mut(&client_id) {
-                                        if current {
-                                            if let Err(err) = self.wm.restore_window(&self.conn, client) {
-                                                warn!("Failed to restore window {}: {}", client_id, err);
-                                            } else {
-                                                state_changed = true;
-                                            }
-                                        } else {
-                                            if let Err(err) = self.wm.maximize_window(&self.conn, client) {
-                                                warn!("Failed to maximize window {}: {}", client_id, err);
-                                            } else {
-                                                state_changed = true;
-                                            }
-                                        }
+                                    match self.set_client_maximized(client_id, !current) {
+                                        Ok(()) => state_changed = true,
+                                        Err(err) => warn!("Failed to change maximized state of window {}: {}", client_id, err),
                                     }
                                 }
                             }
@@ -811,31 +3735,14 @@ impl AreaApp {
                                 let should_change = should_apply(current, action);
                                 
                                 if should_change {
-                                    if current {
-                                        // Unminimize (restore)
-                                        if let Some(client) = self.wm_windows.get_mut(&client_id) {
-                                            if let Err(err) = self.wm.restore_window(&self.conn, client) {
-                                                warn!("Failed to restore window {}: {}", client_id, err);
-                                            } else {
-                                                // Map the window
-                                                if let Some(frame) = &client.frame {
-                                                    self.conn.as_ref().map_window(frame.frame)?;
-                                                } else {
-                                                    self.conn.as_ref().map_window(client_id)?;
-                                                }
-                                                client.set_mapped(true);
-                                                client.flags.remove(crate::wm::client_flags::ClientFlags::ICONIFIED);
-                                                self.conn.as_ref().flush()?;
-                                                state_changed = true;
-                                            }
-                                        }
+                                    let result = if current {
+                                        self.restore_client(client_id)
                                     } else {
-                                        // Minimize
-                                        if let Err(err) = self.wm.minimize_window(&self.conn, &mut self.wm_windows, client_id) {
-                                            warn!("Failed to minimize window {}: {}", client_id, err);
-                                        } else {
-                                            state_changed = true;
-                                        }
+                                        self.minimize_client(client_id)
+                                    };
+                                    match result {
+                                        Ok(()) => state_changed = true,
+                                        Err(err) => warn!("Failed to change minimized state of window {}: {}", client_id, err),
                                     }
                                 }
                             }
@@ -934,7 +3841,7 @@ impl AreaApp {
                         
                         for (atom, state_name) in property_only_states.iter() {
                             if first_atom == *atom || second_atom == *atom {
-                                if let Some(_client) = self.wm_windows.get_mut(&client_id) {
+                                if let Some(client) = self.wm_windows.get_mut(&client_id) {
                                     // Get current state from property
                                     let mut current = false;
                                     if let Ok(reply) = self.conn.as_ref().get_property(
@@ -964,6 +3871,11 @@ impl AreaApp {
                                             remove_atoms,
                                         )?;
                                         self.conn.as_ref().flush()?;
+                                        if *atom == net_wm_state_shaded {
+                                            self.wm.set_shaded(&self.conn, client, !current)?;
+                                        } else if *atom == net_wm_state_sticky {
+                                            self.wm.set_sticky(&self.conn, client, !current)?;
+                                        }
                                         debug!("Updated {} state for window {} to {}", state_name, client_id, !current);
                                         state_changed = true;
                                     }
@@ -971,7 +3883,11 @@ impl AreaApp {
                             }
                         }
                         
-                        if !state_changed {
+                        if state_changed {
+                            // ABOVE/BELOW/FULLSCREEN may have moved the window to another layer
+                            self.wm.stacking.mark_dirty();
+                            self.window_state_changed.insert(client_id);
+                        } else {
                             debug!("_NET_WM_STATE action {} for window {} resulted in no change", action, client_id);
                         }
                     } else {
@@ -981,71 +3897,77 @@ impl AreaApp {
                 }
                 
                 // Handle _NET_ACTIVE_WINDOW (EWMH focus request)
-                if let Ok(net_active_atom) = self.conn.as_ref().intern_atom(false, b"_NET_ACTIVE_WINDOW")?.reply() {
-                    if e.type_ == net_active_atom.atom && e.format == 32 {
-                        debug!("ClientMessage: _NET_ACTIVE_WINDOW for window {}", e.window);
-                        let data32 = e.data.as_data32();
-                        let _source_indication = data32[0]; // 0=application, 1=pager, 2=wm
-                        let _timestamp = data32[1]; // timestamp or 0
-                        
-                        // Find the client window
-                        let client_id = self.wm.find_client_from_window(&self.wm_windows, e.window);
-                        if let Some(client_id) = client_id {
-                            // Focus the window
-                            if let Err(err) = self.wm.set_focus(&self.conn, &mut self.wm_windows, client_id) {
-                                warn!("Failed to focus window {} via _NET_ACTIVE_WINDOW: {}", client_id, err);
+                if e.type_ == self.wm.atoms.net_active_window && e.format == 32 {
+                    debug!("ClientMessage: _NET_ACTIVE_WINDOW for window {}", e.window);
+                    let data32 = e.data.as_data32();
+                    let source_indication = data32[0]; // 0=legacy, 1=application, 2=pager
+                    let _timestamp = data32[1]; // timestamp or 0
+                    
+                    // Find the client window
+                    let client_id = self.wm.find_client_from_window(&self.wm_windows, e.window);
+                    if let Some(client_id) = client_id {
+                        // Pagers act for the user; applications need a token
+                        if source_indication != 2 && !self.may_take_focus(client_id) {
+                            if let Err(err) = self.demand_attention(client_id) {
+                                warn!("Failed to mark window {} as demanding attention: {}", client_id, err);
                             }
-                        } else {
-                            debug!("_NET_ACTIVE_WINDOW for unmanaged window {}", e.window);
+                            return Ok(());
                         }
-                        return Ok(());
+                        if let Err(err) = self.wm.set_focus(&self.conn, &mut self.wm_windows, client_id) {
+                            warn!("Failed to focus window {} via _NET_ACTIVE_WINDOW: {}", client_id, err);
+                        }
+                    } else {
+                        debug!("_NET_ACTIVE_WINDOW for unmanaged window {}", e.window);
                     }
+                    return Ok(());
                 }
                 
                 // Handle _NET_REQUEST_FRAME_EXTENTS (EWMH frame extents request)
-                if let Ok(net_frame_extents_atom) = self.conn.as_ref().intern_atom(false, b"_NET_REQUEST_FRAME_EXTENTS")?.reply() {
-                    if e.type_ == net_frame_extents_atom.atom {
-                        debug!("ClientMessage: _NET_REQUEST_FRAME_EXTENTS for window {}", e.window);
-                        // Find the client window
-                        let client_id = self.wm.find_client_from_window(&self.wm_windows, e.window);
-                        if let Some(client_id) = client_id {
-                            if let Some(client) = self.wm_windows.get(&client_id) {
-                                // If window has a frame, send frame extents
-                                if client.frame.is_some() {
-                                    // Top: 32 (Titlebar), Left/Right/Bottom: 2 (Border)
-                                    if let Err(err) = self.wm.atoms.update_frame_extents(&self.conn, client_id, 2, 2, 32, 2) {
-                                        warn!("Failed to update frame extents for window {}: {}", client_id, err);
-                                    }
-                                }
-                            }
-                        } else {
-                            // Window not yet managed - use default frame extents
-                            // Top: 32 (Titlebar), Left/Right/Bottom: 2 (Border)
-                            if let Ok(atoms) = crate::wm::ewmh::Atoms::new(self.conn.as_ref()) {
-                                if let Err(err) = atoms.update_frame_extents(&self.conn, e.window, 2, 2, 32, 2) {
-                                    debug!("Failed to set default frame extents for window {}: {}", e.window, err);
+                if e.type_ == self.wm.atoms._net_request_frame_extents {
+                    debug!("ClientMessage: _NET_REQUEST_FRAME_EXTENTS for window {}", e.window);
+                    // Find the client window
+                    let client_id = self.wm.find_client_from_window(&self.wm_windows, e.window);
+                    if let Some(client_id) = client_id {
+                        if let Some(client) = self.wm_windows.get(&client_id) {
+                            // If window has a frame, send frame extents
+                            if client.frame.is_some() {
+                                if let Err(err) = self.wm.atoms.update_frame_extents(&self.conn, client_id, client.frame_metrics.extents()) {
+                                    warn!("Failed to update frame extents for window {}: {}", client_id, err);
                                 }
                             }
                         }
+                    } else {
+                        // Window not yet managed - estimate with the configured decorations
+                        let extents = wm::frame_metrics::FrameMetrics::from_config(&self.wm.decoration).extents();
+                        if let Err(err) = self.wm.atoms.update_frame_extents(&self.conn, e.window, extents) {
+                            debug!("Failed to set default frame extents for window {}: {}", e.window, err);
+                        }
+                    }
+                    return Ok(());
+                }
+                
+                // _NET_WM_PING reply: the client sends our ping back to the root window
+                if e.type_ == self.wm.atoms._wm_protocols && e.format == 32 && e.window == self.root {
+                    let data32 = e.data.as_data32();
+                    if data32[0] == self.wm.atoms._net_wm_ping {
+                        let window = data32[2];
+                        if self.wm.terminate.handle_pong(window, data32[1]) {
+                            self.set_window_unresponsive(window, false);
+                        }
                         return Ok(());
                     }
                 }
                 
                 // Handle WM_DELETE_WINDOW protocol responses
                 // When a window receives WM_DELETE_WINDOW and doesn't respond, we might get a ClientMessage
-                let wm_protocols_atom = self.conn.as_ref().intern_atom(false, b"WM_PROTOCOLS")?.reply();
-                let wm_delete_atom = self.conn.as_ref().intern_atom(false, b"WM_DELETE_WINDOW")?.reply();
-                
-                if let (Ok(wm_protocols), Ok(wm_delete)) = (wm_protocols_atom, wm_delete_atom) {
-                    if e.type_ == wm_protocols.atom {
-                        // as_data32() returns [u32; 5] directly, not Option
-                        let data32 = e.data.as_data32();
-                        if data32[0] == wm_delete.atom {
-                            debug!("ClientMessage: WM_DELETE_WINDOW response for window {}", e.window);
-                            // Window is closing - handle destroy
-                            if let Err(err) = self.handle_destroy(e.window) {
-                                warn!("Error handling destroy for window {}: {}", e.window, err);
-                            }
+                if e.type_ == self.wm.atoms._wm_protocols {
+                    // as_data32() returns [u32; 5] directly, not Option
+                    let data32 = e.data.as_data32();
+                    if data32[0] == self.wm.atoms._wm_delete_window {
+                        debug!("ClientMessage: WM_DELETE_WINDOW response for window {}", e.window);
+                        // Window is closing - handle destroy
+                        if let Err(err) = self.handle_destroy(e.window) {
+                            warn!("Error handling destroy for window {}: {}", e.window, err);
                         }
                     }
                 }
@@ -1083,7 +4005,43 @@ impl AreaApp {
                 }
             }
             
+            Event::EnterNotify(e) => {
+                // Focus follows mouse (workspace profile or global focus_mode)
+                if self.wm.focus_policy != wm::focus::FocusPolicy::ClickToFocus
+                    && e.mode == NotifyMode::NORMAL
+                    && e.detail != NotifyDetail::INFERIOR
+                    && !self.wm.is_dragging()
+                    && !self.wm.cycle.active
+                    && let Some(client_id) = self.wm.find_client_from_window(&self.wm_windows, e.event)
+                    && self.wm_windows.get(&client_id).is_some_and(|c| !c.focused())
+                {
+                    debug!(target: "area::wm::focus", "EnterNotify focuses {}", client_id);
+                    if let Err(err) = self.wm.set_focus_by(&self.conn, &mut self.wm_windows, client_id, wm::focus::FocusCause::Pointer) {
+                        warn!("Failed to focus window {} on enter: {}", client_id, err);
+                    }
+                }
+            }
+            
             Event::ButtonPress(e) => {
+                if self.wm.expo.active {
+                    // The pointer is grabbed while the overview is shown
+                    if e.detail == 1 {
+                        self.wm.expo.press(e.root_x.into(), e.root_y.into());
+                    }
+                    return Ok(());
+                }
+                // Unmodified click inside a client window (synchronous passive
+                // grab): focus it, then let the client have the click
+                if matches!(e.detail, 1..=3)
+                    && self.wm_windows.contains_key(&e.event)
+                    && !(self.wm.drag_modifiers != 0 && self.keyboard.modifiers_held(u16::from(e.state), self.wm.drag_modifiers))
+                {
+                    if let Err(err) = self.wm.click_focus(&self.conn, &mut self.wm_windows, e.event) {
+                        warn!("Failed to focus clicked window {}: {}", e.event, err);
+                    }
+                    self.conn.allow_events(Allow::REPLAY_POINTER, e.time)?;
+                    return Ok(());
+                }
                 // Check if click is on panel (using root coordinates)
                 if self.shell.panel.contains_point(e.root_x, e.root_y) {
                     match self.shell.panel.handle_click(e.root_x, e.root_y, &mut self.shell.logout_dialog) {
@@ -1094,19 +4052,13 @@ impl AreaApp {
                                     info!("Launcher button clicked, launching application launcher");
                                     let launcher_cmd = self.config.keybindings.launcher_command.clone();
                                     let mut cmd = std::process::Command::new(&launcher_cmd);
-                                    cmd.env("DISPLAY", &self.display);
-                                    if let Ok(xauth) = std::env::var("XAUTHORITY") {
-                                        cmd.env("XAUTHORITY", xauth);
-                                    }
-                                    if let Err(err) = cmd.spawn() {
+                                    self.launch_environment(&mut cmd);
+                                    if let Err(err) = self.spawn_program(&mut cmd) {
                                         warn!("Failed to launch {}: {}", launcher_cmd, err);
                                         // Fallback: try launching terminal directly
                                         let mut term_cmd = std::process::Command::new("xfce4-terminal");
-                                        term_cmd.env("DISPLAY", &self.display);
-                                        if let Ok(xauth) = std::env::var("XAUTHORITY") {
-                                            term_cmd.env("XAUTHORITY", xauth);
-                                        }
-                                        if let Err(err) = term_cmd.spawn() {
+                                        self.launch_environment(&mut term_cmd);
+                                        if let Err(err) = self.spawn_program(&mut term_cmd) {
                                             warn!("Failed to launch fallback terminal: {}", err);
                                         }
                                     }
@@ -1126,10 +4078,62 @@ impl AreaApp {
 
                 debug!("ButtonPress on window {} at ({}, {})", e.event, e.event_x, e.event_y);
                 
+                // Wheel over the desktop or a titlebar; never focuses
+                if matches!(e.detail, 4 | 5) {
+                    if let Err(err) = self.handle_wheel(&e) {
+                        warn!("Failed to handle wheel on window {}: {}", e.event, err);
+                    }
+                    return Ok(());
+                }
+                
+                // Modifier+left/right button inside a client window (passive grab): move/resize
+                if matches!(e.detail, 1 | 3)
+                    && self.wm_windows.contains_key(&e.event)
+                    && self.wm.drag_modifiers != 0
+                    && self.keyboard.modifiers_held(u16::from(e.state), self.wm.drag_modifiers)
+                {
+                    if let Err(err) = self.wm.set_focus(&self.conn, &mut self.wm_windows, e.event) {
+                        warn!("Failed to focus window {}: {}", e.event, err);
+                    }
+                    let started = if e.detail == 1 {
+                        self.wm.start_drag(&self.conn, &self.wm_windows, e.event, e.root_x, e.root_y)
+                    } else {
+                        self.wm.start_resize(&self.conn, &self.wm_windows, e.event, e.root_x, e.root_y)
+                    };
+                    if let Err(err) = started {
+                        warn!("Failed to start modifier drag for window {}: {}", e.event, err);
+                    }
+                    return Ok(());
+                }
+                
+                // Left press on the shared edge of two snap-tiled windows (the
+                // gap between them or their frames' borders)
+                if e.detail == 1
+                    && !self.wm_windows.contains_key(&e.event)
+                    && self.wm.find_window_from_button(&self.wm_windows, e.event).is_none_or(|(_, button)| button.is_none())
+                {
+                    match self.wm.start_split_drag(&self.conn, &self.wm_windows, e.root_x, e.root_y) {
+                        Ok(true) => return Ok(()),
+                        Ok(false) => {}
+                        Err(err) => warn!("Failed to start dragging a split: {}", err),
+                    }
+                }
+                
+                // Right-click on the bare desktop
+                if e.event == self.root && e.child == x11rb::NONE && e.detail == 3 && self.config.root_menu.enabled {
+                    self.show_root_menu(e.root_x, e.root_y);
+                    return Ok(());
+                }
+                
                 // Check if click is on shell elements first
-                if let Err(err) = self.shell.handle_click(e.event_x, e.event_y, &self.power).await {
+                if let Err(err) = self.shell.handle_click(e.event_x, e.event_y) {
                     warn!("Error handling shell click: {}", err);
                 }
+                if std::mem::take(&mut self.shell.logout_dialog.logout_requested)
+                    && let Err(err) = self.begin_end_session(area_ipc::SessionAction::Logout, false)
+                {
+                    warn!("Failed to start logout: {:#}", err);
+                }
                 
                 // Find the client window from any window ID (client, frame, titlebar, buttons)
                 let client_id = self.wm.find_client_from_window(&self.wm_windows, e.event);
@@ -1153,8 +4157,7 @@ impl AreaApp {
                                 // Click on frame window - check if coordinates are in titlebar area
                                 // event_x/event_y are relative to the event window (frame)
                                 // Titlebar is at y=0 to y=titlebar_height
-                                let titlebar_height = self.config.window_manager.decorations.titlebar_height as i16;
-                                e.event_y < titlebar_height
+                                (e.event_y as i32) < client.frame_metrics.titlebar as i32
                             } else {
                                 false
                             }
@@ -1187,6 +4190,8 @@ impl AreaApp {
                                 debug!("Double-click on titlebar for window {} - toggling maximize", client_id);
                                 if let Err(err) = self.wm.toggle_maximize(&self.conn, &mut self.wm_windows, client_id) {
                                     warn!("Failed to toggle maximize window {}: {}", client_id, err);
+                                } else {
+                                    self.window_state_changed.insert(client_id);
                                 }
                                 // Reset double-click tracking
                                 self.last_titlebar_click = None;
@@ -1201,21 +4206,33 @@ impl AreaApp {
                                 // Track this click for double-click detection
                                 self.last_titlebar_click = Some((client_id, e.time, e.event_x, e.event_y));
                             }
+                        } else if is_titlebar_click && e.detail == 3 {
+                            self.show_window_menu(client_id, e.root_x, e.root_y);
                         }
                     }
                 }
             }
             
             Event::ButtonRelease(e) => {
+                if self.wm.expo.active {
+                    if e.detail == 1 {
+                        let step = self.wm.expo.release(e.root_x.into(), e.root_y.into());
+                        if let Err(e) = self.expo_step(step) {
+                            warn!("Workspace overview failed: {}", e);
+                        }
+                    }
+                    return Ok(());
+                }
                 // Handle button clicks on release
                 // Check if this is a button window first
                 if let Some((window_id, button_type)) = self.wm.find_window_from_button(&self.wm_windows, e.event) {
-                    if let Some(btn_type) = button_type {
+                    // Other buttons (e.g. a right-click opening the window menu) only end drags
+                    if let Some(btn_type) = button_type.filter(|_| e.detail == 1) {
                         // Handle button click on release
                         match btn_type {
                             wm::ButtonType::Close => {
                                 debug!("Close button clicked for window {}", window_id);
-                                if let Err(err) = self.wm.close_window(&self.conn, window_id) {
+                                if let Err(err) = self.close_client(window_id) {
                                     warn!("Failed to close window {}: {}", window_id, err);
                                 }
                             }
@@ -1223,14 +4240,26 @@ impl AreaApp {
                                 debug!("Maximize button clicked for window {}", window_id);
                                 if let Err(err) = self.wm.toggle_maximize(&self.conn, &mut self.wm_windows, window_id) {
                                     warn!("Failed to toggle maximize window {}: {}", window_id, err);
+                                } else {
+                                    self.window_state_changed.insert(window_id);
                                 }
                             }
                             wm::ButtonType::Minimize => {
                                 debug!("Minimize button clicked for window {}", window_id);
-                                if let Err(err) = self.wm.minimize_window(&self.conn, &mut self.wm_windows, window_id) {
+                                if let Err(err) = self.minimize_client(window_id) {
                                     warn!("Failed to minimize window {}: {}", window_id, err);
                                 }
                             }
+                            wm::ButtonType::Menu => {
+                                debug!("Menu button clicked for window {}", window_id);
+                                self.show_window_menu(window_id, e.root_x, e.root_y);
+                            }
+                            wm::ButtonType::Sticky | wm::ButtonType::Above | wm::ButtonType::Shade => {
+                                debug!("{:?} button clicked for window {}", btn_type, window_id);
+                                if let Err(err) = self.toggle_client_state(window_id, btn_type) {
+                                    warn!("Failed to toggle {:?} for window {}: {}", btn_type, window_id, err);
+                                }
+                            }
                         }
                         // Don't end drag if we handled a button click
                         return Ok(());
@@ -1238,20 +4267,40 @@ impl AreaApp {
                 }
                 
                 // End drag/resize
+                if let Err(err) = self.drop_dragged_window() {
+                    warn!("Failed to fit dropped window to its monitor: {}", err);
+                }
                 if let Err(err) = self.wm.end_drag(&self.conn) {
                     debug!("Error ending drag: {}", err);
                 }
+                if let Some((id, _, _)) = self.resize_feedback.take() {
+                    self.emit_event(area_ipc::WmEvent::ResizeFeedbackEnded { id });
+                }
             }
             
             Event::MotionNotify(e) => {
                 // Update cursor position in compositor
                 self.compositor.update_cursor(e.root_x, e.root_y, true);
                 
+                if self.wm.expo.active {
+                    let step = self.wm.expo.motion(e.root_x.into(), e.root_y.into());
+                    if let Err(e) = self.expo_step(step) {
+                        warn!("Workspace overview failed: {}", e);
+                    }
+                    return Ok(());
+                }
+                
                 // Handle drag - use root coordinates for proper dragging
                 if self.wm.is_dragging() {
                     if let Err(err) = self.wm.update_drag(&self.conn, &mut self.wm_windows, e.root_x, e.root_y) {
                         debug!("Error updating drag: {}", err);
                     }
+                    self.update_resize_feedback();
+                    self.update_drag_preview();
+                } else if self.wm.is_splitting()
+                    && let Err(err) = self.wm.update_split_drag(&self.conn, &mut self.wm_windows, e.root_x, e.root_y)
+                {
+                    debug!("Error moving split: {}", err);
                 }
             }
             
@@ -1332,9 +4381,10 @@ impl AreaApp {
                                 if let Err(err) = self.wm.set_fullscreen(&self.conn, client, true) {
                                     warn!("Failed to set fullscreen for window {} (geometry-based detection): {}", cid, err);
                                 } else {
+                                    self.window_state_changed.insert(cid);
                                     // If window has a frame, remove frame from compositor and add client window
                                     if let Some(frame) = &client.frame {
-                                        // Remove frame window from compositor (frame is unmapped)
+                                        // Remove frame window from compositor (the client covers it)
                                         self.compositor.remove_window(frame.frame);
                                         // Add client window to compositor for fullscreen rendering
                                         let client_geom = client.geometry;
@@ -1348,7 +4398,7 @@ impl AreaApp {
                                         self.compositor.add_window(c_window);
                                     }
                                     // Coordinate with compositor: unredirect if config allows
-                                    // Use client window directly for fullscreen (frame is hidden)
+                                    // Use client window directly for fullscreen (frame is covered)
                                     if self.config.compositor.unredirect_fullscreen {
                                         self.compositor.unredirect_window(cid);
                                     }
@@ -1361,22 +4411,24 @@ impl AreaApp {
                             if let Err(err) = self.wm.set_fullscreen(&self.conn, client, false) {
                                 warn!("Failed to exit fullscreen for window {} (geometry-based detection): {}", cid, err);
                             } else {
+                                self.window_state_changed.insert(cid);
                                 // Coordinate with compositor: redirect back and remove client window
                                 if self.config.compositor.unredirect_fullscreen {
                                     self.compositor.redirect_window(cid);
                                 }
                                 // Remove client window from compositor
                                 self.compositor.remove_window(cid);
-                                // Re-add frame window to compositor (frame is mapped back in set_fullscreen)
+                                // Re-add frame window to compositor (decorated again in set_fullscreen)
                                 if let Some(frame) = &client.frame {
                                     let frame_geom = client.frame_geometry();
-                                    let c_window = crate::compositor::c_window::CWindow::new(
+                                    let mut c_window = crate::compositor::c_window::CWindow::new(
                                         frame.frame,  // composite_id = frame window
                                         cid,          // client_id = client window
                                         frame_geom,
-                                        2,  // border_width = 2
+                                        client.frame_metrics.border as u16,
                                         true,  // viewable = true (frame is mapped)
                                     );
+                                    c_window.decoration = Self::compositor_decoration(&self.wm, client);
                                     self.compositor.add_window(c_window);
                                 }
                             }
@@ -1387,6 +4439,49 @@ impl AreaApp {
             
             Event::KeyPress(e) => {
                 debug!("KeyPress: detail={}, state={:?}", e.detail, e.state);
+                if self.wm.window_mode.active {
+                    // Every key is grabbed while the mode is on
+                    if let Err(e) = self.window_mode_key(e.detail, u16::from(e.state)) {
+                        warn!("Window management mode action failed: {}", e);
+                    }
+                    return Ok(());
+                }
+                if self.wm.expo.active {
+                    // Every key is grabbed while the overview is shown
+                    let step = wm::keyboard::KeyboardManager::keysym(&self.conn, e.detail).map(|keysym| self.wm.expo.key(keysym));
+                    if let Err(e) = step.and_then(|step| self.expo_step(step)) {
+                        warn!("Workspace overview failed: {}", e);
+                    }
+                    return Ok(());
+                }
+                if self.wm.hint_mode.active {
+                    // Every key is grabbed while the labels are shown
+                    if let Err(e) = self.hint_mode_key(e.detail) {
+                        warn!("Window hint mode failed: {}", e);
+                    }
+                    return Ok(());
+                }
+                if self.keyboard.is_chord_pending() {
+                    // Every key is grabbed until the chord ends
+                    if let Err(e) = self.chord_key(e.detail, u16::from(e.state)) {
+                        warn!("Chord failed: {}", e);
+                    }
+                    return Ok(());
+                }
+                if let Some(action) = self.keyboard.handle_key_press(u16::from(e.state), e.detail) {
+                    if action == wm::keyboard::KeyboardAction::Chord {
+                        if let Err(e) = self.begin_chord(e.detail, u16::from(e.state)) {
+                            warn!("Chord unavailable: {}", e);
+                        }
+                    } else {
+                        self.run_keyboard_action(action);
+                    }
+                    return Ok(());
+                }
+                if self.wm.cycle.active {
+                    // Other keys while the cycling grab is held are swallowed
+                    return Ok(());
+                }
                 // Check for launcher key from config
                 // For now, support keycode-based matching (133/134 for SUPER keys)
                 // TODO: Add full keybinding parser for key names like "Super"
@@ -1407,12 +4502,14 @@ impl AreaApp {
                     // Launch launcher command from config
                     info!("Launcher key pressed (keycode {}), launching {}", e.detail, self.config.keybindings.launcher_command);
                     let mut cmd = std::process::Command::new(&self.config.keybindings.launcher_command);
-                    cmd.env("DISPLAY", &self.display);
-                    // Preserve XAUTHORITY if set
-                    if let Ok(xauth) = std::env::var("XAUTHORITY") {
-                        cmd.env("XAUTHORITY", xauth);
-                    }
-                    let _ = cmd.spawn();
+                    self.launch_environment(&mut cmd);
+                    let _ = self.spawn_program(&mut cmd);
+                }
+            }
+            
+            Event::KeyRelease(e) => {
+                if self.wm.cycle.active && self.keyboard.ends_cycle(e.detail) {
+                    self.finish_cycle()?;
                 }
             }
             
@@ -1445,6 +4542,72 @@ impl AreaApp {
                 }
             }
             
+            Event::ShapeNotify(e) => {
+                if e.shape_kind == x11rb::protocol::shape::SK::BOUNDING && self.wm_windows.contains_key(&e.affected_window) {
+                    self.update_window_shape(e.affected_window)?;
+                    self.conn.as_ref().flush()?;
+                }
+            }
+            
+            // Panels stay hidden while a game holds the pointer
+            Event::XinputBarrierHit(_) if self.wm.pointer_confinement.is_active() => {}
+            Event::XinputBarrierHit(e) => {
+                if let Some((monitor, edge)) = self.wm.edge_triggers.hit(self.conn.as_ref(), &e)? {
+                    use wm::strut::StrutEdge;
+                    let edge = match edge {
+                        StrutEdge::Left => area_ipc::PanelEdge::Left,
+                        StrutEdge::Right => area_ipc::PanelEdge::Right,
+                        StrutEdge::Top => area_ipc::PanelEdge::Top,
+                        StrutEdge::Bottom => area_ipc::PanelEdge::Bottom,
+                    };
+                    debug!("Edge {:?} of monitor {} triggered", edge, monitor);
+                    self.emit_event(area_ipc::WmEvent::EdgeTriggered { edge, monitor });
+                    self.conn.as_ref().flush()?;
+                }
+            }
+            
+            Event::XinputBarrierLeave(e) => self.wm.edge_triggers.leave(&e),
+            
+            Event::XinputHierarchy(e) => {
+                if let Err(err) = self.wm.key_remap.hierarchy_changed(&self.conn, &e) {
+                    warn!("Failed to remap keys of a new keyboard: {}", err);
+                }
+            }
+            
+            Event::XkbStateNotify(e) => {
+                if let Some(name) = self.wm.keyboard_layout.state_changed(&self.conn, &e)?
+                    && self.config.osd.keyboard_layout
+                {
+                    self.show_osd(area_ipc::OsdKind::KeyboardLayout, name, None);
+                }
+            }
+            
+            Event::XkbControlsNotify(e) => {
+                if let Some(state) = self.wm.accessx.controls_changed(&e) {
+                    self.emit_event(state.event());
+                }
+            }
+            
+            Event::SyncAlarmNotify(e) => {
+                if let Some((idle, idle_ms)) = self.wm.idle.alarm_notify(&self.conn, &e)? {
+                    self.emit_event(area_ipc::WmEvent::UserIdleStateChanged { idle, idle_ms });
+                }
+            }
+            
+            Event::XfixesSelectionNotify(e) => self.wm.selection_sync.owner_changed(&self.conn, &e)?,
+            Event::SelectionNotify(e) => {
+                self.wm.selection_sync.converted(&self.conn, &e)?;
+                self.wm.clipboard_manager.converted(&self.conn, &e, self.wm.time.now())?;
+            }
+            Event::SelectionRequest(e) => {
+                self.wm.selection_sync.request(&self.conn, &e)?;
+                self.wm.clipboard_manager.request(&self.conn, &e, self.wm.time.now())?;
+            }
+            Event::SelectionClear(e) => {
+                self.wm.selection_sync.cleared(&e);
+                self.wm.clipboard_manager.cleared(&e);
+            }
+            
             Event::XfixesCursorNotify(_e) => {
                 // Cursor shape changed - update cursor image in compositor thread
                 self.compositor.update_cursor_image();
@@ -1452,22 +4615,68 @@ impl AreaApp {
             
             Event::PropertyNotify(e) => {
                 // Check if _NET_WM_STATE changed (for fullscreen detection)
-                // Intern the atom to compare (we can't access wm.atoms directly, but we can intern it)
-                if let Ok(reply) = self.conn.as_ref().intern_atom(false, b"_NET_WM_STATE")?.reply() {
-                    if e.atom == reply.atom {
-                        // Window state changed - check for fullscreen
-                        debug!("PropertyNotify: _NET_WM_STATE changed for window {}", e.window);
-                        
-                        // Use frame ID if managed and framed
-                        let target_id = if let Some(client) = self.wm_windows.get(&e.window) {
-                            client.frame.as_ref().map(|f| f.frame).unwrap_or(e.window)
-                        } else {
-                            e.window
-                        };
-                        self.compositor.update_window_state(target_id);
+                if e.atom == self.wm.atoms.net_wm_state {
+                    // Window state changed - check for fullscreen
+                    debug!("PropertyNotify: _NET_WM_STATE changed for window {}", e.window);
+                    
+                    // Use frame ID if managed and framed
+                    let target_id = if let Some(client) = self.wm_windows.get(&e.window) {
+                        client.frame.as_ref().map(|f| f.frame).unwrap_or(e.window)
+                    } else {
+                        e.window
+                    };
+                    self.compositor.update_window_state(target_id);
+                }
+                
+                // Terminals update their size hints when the font changes
+                if e.atom == u32::from(AtomEnum::WM_NORMAL_HINTS)
+                    && let Some(client) = self.wm_windows.get_mut(&e.window)
+                {
+                    match wm::hints::HintsManager::read_size_hints(&self.conn, &self.wm.atoms, e.window) {
+                        Ok(size_hints) => client.size_hints = size_hints,
+                        Err(err) => debug!("Failed to read size hints for window {}: {}", e.window, err),
+                    }
+                }
+                
+                // Urgency: ICCCM WM_HINTS or EWMH _NET_WM_STATE_DEMANDS_ATTENTION
+                if (e.atom == u32::from(AtomEnum::WM_HINTS) || e.atom == self.wm.atoms.net_wm_state)
+                    && self.wm_windows.contains_key(&e.window)
+                    && let Err(err) = self.update_urgency(e.window)
+                {
+                    debug!("Failed to update urgency for window {}: {}", e.window, err);
+                }
+                
+                // Compositor rules match the class
+                if e.atom == u32::from(AtomEnum::WM_CLASS) && self.wm_windows.contains_key(&e.window) {
+                    let reply = self.conn.as_ref().get_property(false, e.window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)?.reply()?;
+                    if let Some(client) = self.wm_windows.get_mut(&e.window) {
+                        client.class_hint = wm::client::ClassHint::parse(&reply.value);
+                    }
+                    self.apply_compositor_rules(e.window);
+                }
+                
+                // Opaque parts of ARGB windows (occlusion culling, blending)
+                if e.atom == self.wm.atoms._net_wm_opaque_region
+                    && self.wm_windows.contains_key(&e.window)
+                    && let Err(err) = self.update_opaque_region(e.window)
+                {
+                    debug!("Failed to read opaque region of window {}: {}", e.window, err);
+                }
+                
+                // Dock struts
+                if (e.atom == self.wm.atoms._net_wm_strut || e.atom == self.wm.atoms._net_wm_strut_partial)
+                    && self.wm_windows.contains_key(&e.window)
+                {
+                    match self.wm.update_client_strut(&self.conn, e.window) {
+                        Ok(true) => self.apply_work_area(),
+                        Ok(false) => {}
+                        Err(err) => debug!("Failed to read strut of window {}: {}", e.window, err),
                     }
                 }
                 
+                // Confinement hints are re-read on the next update
+                self.wm.pointer_confinement.property_changed(e.window);
+                
                 // Check if _NET_WM_BYPASS_COMPOSITOR changed
                 if e.atom == self.wm.atoms._net_wm_bypass_compositor {
                     if let Some(client) = self.wm_windows.get(&e.window) {
@@ -1571,19 +4780,12 @@ impl AreaApp {
         // Skip if already managed
         if self.wm_windows.contains_key(&window_id) {
             debug!("Window {} already managed, mapping it", window_id);
-            // Map the window if it's not already mapped
+            // If window was minimized, restore it; otherwise just map it
+            if self.wm_windows.get(&window_id).is_some_and(|c| c.is_minimized()) {
+                return self.restore_client(window_id);
+            }
+            self.conn.as_ref().map_window(window_id)?;
             if let Some(client) = self.wm_windows.get_mut(&window_id) {
-                // If window was minimized, restore it
-                if client.is_minimized() {
-                    client.flags.remove(crate::wm::client_flags::ClientFlags::ICONIFIED);
-                    if let Some(frame) = &client.frame {
-                        self.conn.map_window(frame.frame)?;
-                    } else {
-                        self.conn.as_ref().map_window(window_id)?;
-                    }
-                } else {
-                    self.conn.as_ref().map_window(window_id)?;
-                }
                 client.set_mapped(true);
             }
             self.conn.as_ref().flush()?;
@@ -1592,8 +4794,9 @@ impl AreaApp {
         
         // Check if window is override-redirect BEFORE attempting management
         // Override-redirect windows (popups, tooltips) should not be managed by WM
-        let is_override_redirect = match self.conn.as_ref().get_window_attributes(window_id)?.reply() {
-            Ok(attrs) => attrs.override_redirect,
+        // (one reply also tells whether it was mapped before we took over)
+        let (is_override_redirect, was_mapped) = match self.conn.as_ref().get_window_attributes(window_id)?.reply() {
+            Ok(attrs) => (attrs.override_redirect, attrs.map_state != x11rb::protocol::xproto::MapState::UNMAPPED),
             Err(_) => {
                 debug!("Window {} disappeared before we could check attributes", window_id);
                 return Ok(());
@@ -1602,23 +4805,24 @@ impl AreaApp {
         
         if is_override_redirect {
             debug!("Window {} is override-redirect, skipping WM management", window_id);
-            // Still map it so it's visible, but don't manage or composite it
+            // Not managed, but the compositor still has to draw it
+            return self.add_popup(window_id);
+        }
+        
+        // Notifications, tooltips and drag icons that are not override-redirect
+        // are mapped as they are and composited the same way
+        if self.wm.is_unmanaged_type(&self.conn, window_id) {
+            debug!("Window {} is a notification, tooltip or drag icon, not managing it", window_id);
             self.conn.as_ref().map_window(window_id)?;
-            self.conn.as_ref().flush()?;
-            return Ok(());
+            return self.add_popup(window_id);
         }
         
+        // Follow the window's shape (xeyes, conky, splash screens)
+        wm::shape::select_input(self.conn.as_ref(), window_id)?;
+        
         // Create new client with default geometry (will be updated by manage_window)
         let mut client = Client::new(window_id, shared::Geometry::new(0, 0, 100, 100));
-        
-        // Check if window was already mapped before we took over
-        let was_mapped = match self.conn.as_ref().get_window_attributes(window_id)?.reply() {
-            Ok(attrs) => attrs.map_state != x11rb::protocol::xproto::MapState::UNMAPPED,
-            Err(_) => {
-                debug!("Window {} disappeared before management started", window_id);
-                return Ok(());
-            }
-        };
+        client.pid = wm::terminate::local_pid(&self.conn, &self.wm.atoms, window_id).ok().flatten().unwrap_or(0);
         
         // Track this window as being reparented to ignore UnmapNotify/MapNotify events
         // caused by our own reparenting operation
@@ -1628,16 +4832,10 @@ impl AreaApp {
         // This will restore the window's geometry and decorations
         // Note: This will trigger reparent_window, which causes UnmapNotify -> MapNotify
         // We ignore those events because the window is in reparenting_windows
-        let manage_result = self.wm.manage_window(&self.conn, &mut client);
+        let manage_result = self.wm.manage_window(&self.conn, &mut client, &self.wm_windows);
         
-        // #region agent log
-        debug_log("main.rs:1613", "manage_window result", serde_json::json!({
-            "window_id": window_id,
-            "success": manage_result.is_ok(),
-            "has_frame": client.frame.is_some(),
-            "frame_id": client.frame.as_ref().map(|f| f.frame)
-        }), "A");
-        // #endregion
+        debug!("manage_window({}) ok={} frame={:?}",
+            window_id, manage_result.is_ok(), client.frame.as_ref().map(|f| f.frame));
         
         manage_result?;
         
@@ -1645,17 +4843,10 @@ impl AreaApp {
         if let Some(frame) = &client.frame {
             self.frame_windows.insert(frame.frame);
             self.frame_windows.insert(frame.titlebar);
-            self.frame_windows.insert(frame.close_button);
-            self.frame_windows.insert(frame.maximize_button);
-            self.frame_windows.insert(frame.minimize_button);
+            self.frame_windows.extend(frame.buttons.iter().map(|b| b.window));
             
-            // #region agent log
-            debug_log("main.rs:1628", "Frame windows registered", serde_json::json!({
-                "window_id": window_id,
-                "frame": frame.frame,
-                "titlebar": frame.titlebar
-            }), "A");
-            // #endregion
+            debug!("Frame windows registered for {}: frame={} titlebar={}",
+                window_id, frame.frame, frame.titlebar);
         }
         
         // Map the window so it becomes visible
@@ -1677,39 +4868,22 @@ impl AreaApp {
         }
         self.conn.as_ref().flush()?;
         
-        // Raise window to ensure it's visible (bring to front)
-        use x11rb::protocol::xproto::StackMode;
-        if let Some(frame) = &client.frame {
-            self.conn.as_ref().configure_window(
-                frame.frame,
-                &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
-            )?;
-        } else {
-            self.conn.as_ref().configure_window(
-                window_id,
-                &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
-            )?;
-        }
-        self.conn.as_ref().flush()?;
+        // Place window at the top of its layer (restacked after this event)
+        self.wm.stacking.add_window(window_id);
         
         // Let compositor register the window (creates texture, damage tracking)
         // Determine composite target (FRAME or CLIENT)
         let composite_id = client.frame.as_ref().map(|f| f.frame).unwrap_or(client.window);
         
-        // #region agent log
-        debug_log("main.rs:1641", "Adding window to compositor", serde_json::json!({
-            "client_id": window_id,
-            "composite_id": composite_id,
-            "has_frame": client.frame.is_some(),
-            "frame_id": client.frame.as_ref().map(|f| f.frame)
-        }), "D");
-        // #endregion
+        debug!("Adding window {} to compositor (composite_id={}, framed={})",
+            window_id, composite_id, client.frame.is_some());
         
         // Get actual geometry, border width and viewable state from X11
         // We use *actual* X11 geometry because pixmap size matches the real window size
         let (geometry, border_width, viewable) = {
-            let geom_result = self.conn.as_ref().get_geometry(composite_id)?.reply();
-            let attr_result = self.conn.as_ref().get_window_attributes(composite_id)?.reply();
+            let geom_cookie = self.conn.as_ref().get_geometry(composite_id)?;
+            let attr_cookie = self.conn.as_ref().get_window_attributes(composite_id)?;
+            let (geom_result, attr_result) = (geom_cookie.reply(), attr_cookie.reply());
             
             match (geom_result, attr_result) {
                 (Ok(geom), Ok(attr)) => (
@@ -1732,23 +4906,19 @@ impl AreaApp {
         };
 
         // Use actual X11 geometry for the compositor window
-        let c_window = CWindow::new(
+        let mut c_window = CWindow::new(
             composite_id, 
             client.window, 
             geometry, 
             border_width, 
             viewable
         );
+        c_window.decoration = Self::compositor_decoration(&self.wm, &client);
 
         self.compositor.add_window(c_window);
         
-        // #region agent log
-        debug_log("main.rs:1678", "Window added to compositor", serde_json::json!({
-            "composite_id": composite_id,
-            "geometry": {"x": geometry.x, "y": geometry.y, "width": geometry.width, "height": geometry.height},
-            "viewable": viewable
-        }), "D");
-        // #endregion
+        debug!("Window {} added to compositor: {:?} viewable={}",
+            composite_id, geometry, viewable);
         
         // Check for _NET_WM_BYPASS_COMPOSITOR hint before storing window
         // Also check if window should be fullscreen (games often set bypass + fullscreen)
@@ -1793,6 +4963,7 @@ impl AreaApp {
         
         // Store window
         self.wm_windows.insert(window_id, client);
+        self.apply_compositor_rules(window_id);
         
         // Set fullscreen if needed (after insert so we can get_mut)
         if needs_fullscreen {
@@ -1807,10 +4978,40 @@ impl AreaApp {
         // Update _NET_CLIENT_LIST
         self.update_client_list()?;
         
+        // Docks reserve screen space
+        match self.wm.update_client_strut(&self.conn, window_id) {
+            Ok(true) => self.apply_work_area(),
+            Ok(false) => {}
+            Err(err) => debug!("Failed to read strut of window {}: {}", window_id, err),
+        }
+        
+        if let Err(err) = self.update_window_shape(window_id) {
+            debug!("Failed to read shape of window {}: {}", window_id, err);
+        }
+        if let Err(err) = self.update_opaque_region(window_id) {
+            debug!("Failed to read opaque region of window {}: {}", window_id, err);
+        }
+        
+        self.opened_windows.push(window_id);
         debug!("Managed and mapped new window {}", window_id);
         Ok(())
     }
     
+    /// Hand a mapped override-redirect window to the compositor
+    fn add_popup(&mut self, window_id: u32) -> Result<()> {
+        if self.popups.is_tracked(window_id) {
+            return Ok(());
+        }
+        let geom = self.conn.as_ref().get_geometry(window_id)?.reply()?;
+        let geometry = shared::Geometry::new(geom.x as i32, geom.y as i32, geom.width as u32, geom.height as u32);
+        self.compositor.add_window(CWindow::new(window_id, window_id, geometry, geom.border_width, true));
+        if self.popups.mapped(window_id, &self.config.compositor.popups) {
+            // X draws it until it has stayed open long enough to be composited
+            self.compositor.unredirect_window(window_id);
+        }
+        Ok(())
+    }
+    
     /// Update _NET_CLIENT_LIST root property
     fn update_client_list(&mut self) -> Result<()> {
         let client_list: Vec<u32> = self.wm_windows.keys().copied().collect();
@@ -1870,17 +5071,34 @@ impl AreaApp {
             if let Some(frame) = &client.frame {
                 self.frame_windows.remove(&frame.frame);
                 self.frame_windows.remove(&frame.titlebar);
-                self.frame_windows.remove(&frame.close_button);
-                self.frame_windows.remove(&frame.maximize_button);
-                self.frame_windows.remove(&frame.minimize_button);
+                for button in &frame.buttons {
+                    self.frame_windows.remove(&button.window);
+                }
             }
             
             // Let compositor clean up
             let composite_id = client.frame.as_ref().map(|f| f.frame).unwrap_or(window_id);
+            // Keep the last frame of a window that was on screen for the shell
+            let keep = self.config.compositor.close_snapshot_ms;
+            if keep > 0 && !client.is_minimized() && self.wm.workspaces.is_visible(&client) {
+                let path = area_ipc::closed_snapshot_path(window_id);
+                let written = self.compositor.snapshot_closed_window(composite_id, path.clone(), Duration::from_millis(keep));
+                let events = self.closed_snapshot_tx.clone();
+                tokio::spawn(async move {
+                    if written.await.is_ok() {
+                        let _ = events.send(area_ipc::WmEvent::WindowClosedSnapshot { id: window_id, shm: path.to_string_lossy().into_owned() });
+                    }
+                });
+            }
             self.compositor.remove_window(composite_id);
+            self.rule_unredirected.remove(&window_id);
             
             // Let WM clean up (this will reparent window back to root)
             self.wm.unmanage_window(&self.conn, &mut client)?;
+            self.wm.stacking.remove_window(window_id);
+            if self.wm.remove_client_strut(window_id) {
+                self.apply_work_area();
+            }
             
             // Update _NET_CLIENT_LIST
             self.update_client_list()?;
@@ -1897,24 +5115,41 @@ impl AreaApp {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize logging
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "area=debug,info".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    // Initialize logging (filter adjustable at runtime, see diagnostics module)
+    diagnostics::init();
     
     info!("Starting Area Window Manager + Compositor");
     
     // Parse command line arguments
     let args: Vec<String> = std::env::args().collect();
+
+    // Development: --nested [WxH] runs the whole stack inside Xephyr
+    if let Some(options) = nested::NestedOptions::from_args(&args)? {
+        let code = nested::run(options, &args)?;
+        std::process::exit(code);
+    }
+
     let replace = args.iter().any(|arg| arg == "--replace" || arg == "-r");
     
     if replace {
         info!("--replace flag detected: will attempt to replace existing WM");
     }
-    
+
+    // Event recording/replay (debugging): --record <file>, --replay <file> [--replay-realtime];
+    // replay runs against a mock X server, see the `replay` module
+    let arg_value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|i| args.get(i + 1))
+            .map(std::path::PathBuf::from)
+    };
+    let record_path = arg_value("--record");
+    let replay_path = arg_value("--replay");
+    let replay_realtime = args.iter().any(|arg| arg == "--replay-realtime");
+    // Driver triage: --selfcheck shows the compositor's test patterns and
+    // prints its OpenGL setup
+    let selfcheck = args.iter().any(|arg| arg == "--selfcheck");
+
     // Setup signal handlers for graceful shutdown
     let (shutdown_tx, mut shutdown_rx) = tokio::sync::mpsc::channel::<()>(1);
     
@@ -1940,14 +5175,37 @@ async fn main() -> Result<()> {
     }
     
     // Create and run application
-    let app = AreaApp::new(replace).await?;
-    
+    let mut replay = replay_path.map(|path| replay::EventReplay::open(&path)).transpose()?;
+    let mut app = AreaApp::new(replace, replay.as_mut()).await?;
+
+    if let Some(ref path) = record_path {
+        app.start_recording(path)?;
+    }
+
+    if selfcheck {
+        app.compositor.set_self_check(true);
+        let compositor = app.compositor.clone();
+        tokio::spawn(async move {
+            match compositor.wait_gl_capabilities().await {
+                Some(gl) => println!("{}", serde_json::to_string_pretty(&gl).unwrap_or_default()),
+                None => println!("area: no OpenGL, the compositor cannot render"),
+            }
+        });
+    }
+
     // Get compositor handle before moving app into run()
     let compositor_handle = app.compositor.clone();
-    
+
+    let main_loop = async move {
+        match replay {
+            Some(replay) => app.replay(replay, replay_realtime).await,
+            None => app.run().await,
+        }
+    };
+
     // Run app with shutdown handling
     tokio::select! {
-        result = app.run() => {
+        result = main_loop => {
             if let Err(e) = result {
                 error!("Application error: {}", e);
                 return Err(e);
@@ -1964,3 +5222,47 @@ async fn main() -> Result<()> {
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11rb::protocol::xproto::{CREATE_NOTIFY_EVENT, CreateNotifyEvent, MAP_REQUEST_EVENT, MapRequestEvent};
+
+    /// Write a recording of `entries` against the mock's test setup
+    fn recording(name: &str, entries: &[replay::RecordedInput]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("area-replay-{}-{}.jsonl", name, std::process::id()));
+        let mut lines = vec![serde_json::to_string(&replay::mock::tests::header()).unwrap()];
+        for (t_ms, input) in (0..).zip(entries) {
+            lines.push(serde_json::to_string(&replay::RecordedEntry { t_ms, input: input.clone() }).unwrap());
+        }
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn replays_against_the_mock_server() {
+        let window = 0x400001;
+        let create = CreateNotifyEvent {
+            response_type: CREATE_NOTIFY_EVENT,
+            parent: 0x100,
+            window,
+            width: 640,
+            height: 480,
+            ..Default::default()
+        };
+        let map = MapRequestEvent { response_type: MAP_REQUEST_EVENT, parent: 0x100, window, ..Default::default() };
+        let path = recording("map", &[
+            replay::RecordedInput::X11 { raw: <[u8; 32]>::from(&create).to_vec() },
+            replay::RecordedInput::X11 { raw: <[u8; 32]>::from(&map).to_vec() },
+            replay::RecordedInput::Ipc { command: area_ipc::ShellCommand::CloseWindow { id: window } },
+        ]);
+
+        let mut recording = replay::EventReplay::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut app = AreaApp::new(false, Some(&mut recording)).await.unwrap();
+        app.replay(recording, false).await.unwrap();
+
+        let client = app.wm_windows.get(&window).expect("replayed window is managed");
+        assert_eq!((client.geometry.width, client.geometry.height), (640, 480));
+    }
+}
@@ -0,0 +1,208 @@
+//! Performance Metrics
+//!
+//! Process-wide counters and timing samples recorded from the compositor
+//! thread (frame times, damage), the main loop (X event latency) and the IPC
+//! server (round-trip times). Exposed via the `GetMetrics` IPC command and,
+//! when `[metrics] prometheus_listen` is set, a Prometheus text endpoint.
+
+use area_ipc::{MetricsSnapshot, TimingSummary};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, info, warn};
+
+/// Number of recent samples kept per timing series
+const SAMPLE_WINDOW: usize = 1024;
+
+/// Longest a metrics client may take to send its request, and to take the response
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pause after a failed accept (e.g. out of file descriptors) before the next one
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(500);
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Global metrics collector
+pub fn global() -> &'static Metrics {
+    &METRICS
+}
+
+pub struct Metrics {
+    start: Instant,
+    frame_time: Mutex<Timing>,
+    damage_events: AtomicU64,
+    event_latency: Mutex<Timing>,
+    ipc_round_trip: Mutex<Timing>,
+    framebuffer_bits: AtomicU64,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            frame_time: Mutex::new(Timing::default()),
+            damage_events: AtomicU64::new(0),
+            event_latency: Mutex::new(Timing::default()),
+            ipc_round_trip: Mutex::new(Timing::default()),
+            framebuffer_bits: AtomicU64::new(0),
+        }
+    }
+
+    /// Record how long a compositor frame took to render
+    pub fn record_frame(&self, elapsed: Duration) {
+        self.frame_time.lock().unwrap().record(elapsed);
+    }
+
+    /// Count a damage notification
+    pub fn record_damage(&self) {
+        self.damage_events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record the latency of one X event (receipt to handled)
+    pub fn record_event_latency(&self, elapsed: Duration) {
+        self.event_latency.lock().unwrap().record(elapsed);
+    }
+
+    /// Record one IPC request/reply round trip
+    pub fn record_ipc_round_trip(&self, elapsed: Duration) {
+        self.ipc_round_trip.lock().unwrap().record(elapsed);
+    }
+
+    /// Record the bits per channel of the compositor's framebuffer
+    pub fn set_framebuffer_bits(&self, bits: u32) {
+        self.framebuffer_bits.store(u64::from(bits), Ordering::Relaxed);
+    }
+
+    /// Current values of all metrics
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            uptime_ms: self.start.elapsed().as_millis() as u64,
+            frame_time: self.frame_time.lock().unwrap().summary(),
+            damage_events: self.damage_events.load(Ordering::Relaxed),
+            event_latency: self.event_latency.lock().unwrap().summary(),
+            ipc_round_trip: self.ipc_round_trip.lock().unwrap().summary(),
+            framebuffer_bits: self.framebuffer_bits.load(Ordering::Relaxed) as u32,
+        }
+    }
+}
+
+/// Rolling window of timing samples plus session totals
+#[derive(Default)]
+struct Timing {
+    count: u64,
+    total_ms: f64,
+    samples: VecDeque<f64>,
+}
+
+impl Timing {
+    fn record(&mut self, elapsed: Duration) {
+        let ms = elapsed.as_secs_f64() * 1000.0;
+        self.count += 1;
+        self.total_ms += ms;
+        if self.samples.len() == SAMPLE_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(ms);
+    }
+
+    fn summary(&self) -> TimingSummary {
+        if self.samples.is_empty() {
+            return TimingSummary::default();
+        }
+
+        let mut sorted: Vec<f64> = self.samples.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+
+        TimingSummary {
+            count: self.count,
+            total_ms: self.total_ms,
+            avg_ms: self.total_ms / self.count as f64,
+            p50_ms: percentile(0.50),
+            p95_ms: percentile(0.95),
+            p99_ms: percentile(0.99),
+            max_ms: sorted[sorted.len() - 1],
+        }
+    }
+}
+
+/// Render a snapshot in the Prometheus text exposition format
+pub fn prometheus_text(metrics: &MetricsSnapshot) -> String {
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# HELP area_uptime_seconds Time since the window manager started");
+    let _ = writeln!(out, "# TYPE area_uptime_seconds gauge");
+    let _ = writeln!(out, "area_uptime_seconds {}", metrics.uptime_ms as f64 / 1000.0);
+
+    let _ = writeln!(out, "# HELP area_damage_events_total Damage notifications received by the compositor");
+    let _ = writeln!(out, "# TYPE area_damage_events_total counter");
+    let _ = writeln!(out, "area_damage_events_total {}", metrics.damage_events);
+
+    let _ = writeln!(out, "# HELP area_framebuffer_bits_per_channel Color depth of the compositor's framebuffer (0: not composited)");
+    let _ = writeln!(out, "# TYPE area_framebuffer_bits_per_channel gauge");
+    let _ = writeln!(out, "area_framebuffer_bits_per_channel {}", metrics.framebuffer_bits);
+
+    let summaries = [
+        ("area_frame_time_seconds", "Compositor frame render time", &metrics.frame_time),
+        ("area_event_latency_seconds", "X event receipt-to-handled latency", &metrics.event_latency),
+        ("area_ipc_round_trip_seconds", "IPC command round-trip time", &metrics.ipc_round_trip),
+    ];
+    for (name, help, summary) in summaries {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} summary", name);
+        for (quantile, value) in [("0.5", summary.p50_ms), ("0.95", summary.p95_ms), ("0.99", summary.p99_ms)] {
+            let _ = writeln!(out, "{}{{quantile=\"{}\"}} {}", name, quantile, value / 1000.0);
+        }
+        let _ = writeln!(out, "{}_sum {}", name, summary.total_ms / 1000.0);
+        let _ = writeln!(out, "{}_count {}", name, summary.count);
+    }
+
+    out
+}
+
+/// Serve `prometheus_text` over plain HTTP on `addr`
+///
+/// Every request gets the current metrics regardless of path; this is meant
+/// for a local scraper, not as a general web server.
+pub async fn spawn_prometheus_endpoint(addr: SocketAddr) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("Prometheus metrics endpoint listening on http://{}/metrics", addr);
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, peer) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!("Metrics endpoint accept failed: {}", e);
+                    tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                    continue;
+                }
+            };
+            tokio::spawn(async move {
+                // Request line and headers are ignored; read what's there so the
+                // client doesn't see a reset before the response. A client that
+                // sends nothing mustn't hold its connection open forever
+                let mut request = [0u8; 1024];
+                let _ = tokio::time::timeout(CLIENT_TIMEOUT, stream.read(&mut request)).await;
+
+                let body = prometheus_text(&global().snapshot());
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                match tokio::time::timeout(CLIENT_TIMEOUT, stream.write_all(response.as_bytes())).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => debug!("Metrics endpoint write to {} failed: {}", peer, e),
+                    Err(_) => debug!("Metrics endpoint write to {} timed out", peer),
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,217 @@
+//! Nested development session
+//!
+//! `area --nested [WxH]` starts Xephyr on a free display, re-runs this binary
+//! inside it (WM + compositor + shell) and forwards the output of both
+//! processes to the terminal, optionally teeing it to `--nested-log <file>`.
+//! Replaces the manual "Xephyr in one terminal, area in another" workflow.
+
+use anyhow::{bail, Context, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Screen size used when `--nested` is given without a size
+const DEFAULT_SIZE: &str = "1280x800";
+
+/// How long to wait for the Xephyr socket to appear
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Arguments consumed by the nested launcher (not passed to the inner instance)
+const NESTED_FLAGS: [&str; 3] = ["--nested", "--nested-display", "--nested-log"];
+
+/// Options parsed from the command line
+pub struct NestedOptions {
+    /// Xephyr screen size (WxH)
+    pub size: String,
+    /// Display to use, or None to pick the first free one
+    pub display: Option<u32>,
+    /// File receiving a copy of all forwarded output
+    pub log: Option<PathBuf>,
+}
+
+impl NestedOptions {
+    /// Parse `--nested [WxH] [--nested-display :N] [--nested-log <file>]`, None if not nested
+    pub fn from_args(args: &[String]) -> Result<Option<Self>> {
+        let Some(pos) = args.iter().position(|arg| arg == "--nested") else {
+            return Ok(None);
+        };
+        let size = match args.get(pos + 1) {
+            Some(value) if !value.starts_with('-') => {
+                parse_size(value).with_context(|| format!("invalid --nested size {:?}", value))?;
+                value.clone()
+            }
+            _ => DEFAULT_SIZE.to_string(),
+        };
+        let value_of = |flag: &str| args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1));
+        let display = value_of("--nested-display")
+            .map(|value| {
+                value
+                    .trim_start_matches(':')
+                    .parse::<u32>()
+                    .with_context(|| format!("invalid --nested-display {:?}", value))
+            })
+            .transpose()?;
+        Ok(Some(Self {
+            size,
+            display,
+            log: value_of("--nested-log").map(PathBuf::from),
+        }))
+    }
+}
+
+/// Parse a WxH size
+fn parse_size(value: &str) -> Result<(u32, u32)> {
+    let (w, h) = value.split_once('x').context("expected WxH")?;
+    let (w, h) = (w.parse::<u32>()?, h.parse::<u32>()?);
+    if w == 0 || h == 0 {
+        bail!("size must be non-zero");
+    }
+    Ok((w, h))
+}
+
+/// Whether an X server already owns display `n`
+fn display_in_use(n: u32) -> bool {
+    Path::new(&format!("/tmp/.X{}-lock", n)).exists() || Path::new(&format!("/tmp/.X11-unix/X{}", n)).exists()
+}
+
+/// Arguments for the inner instance: ours minus the nested launcher flags and their values
+fn inner_args(args: &[String]) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut iter = args.iter().skip(1).peekable();
+    while let Some(arg) = iter.next() {
+        if NESTED_FLAGS.contains(&arg.as_str()) {
+            // --nested takes an optional size, the others a required value
+            if let Some(next) = iter.peek()
+                && (arg != "--nested" || !next.starts_with('-'))
+            {
+                iter.next();
+            }
+            continue;
+        }
+        out.push(arg.clone());
+    }
+    out
+}
+
+/// Copy lines from a child pipe to stderr (and the log file), with an optional prefix
+fn forward<R: Read + Send + 'static>(
+    pipe: R,
+    prefix: &'static str,
+    log: Option<Arc<Mutex<File>>>,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        for line in BufReader::new(pipe).lines() {
+            let Ok(line) = line else { break };
+            eprintln!("{}{}", prefix, line);
+            if let Some(ref log) = log
+                && let Ok(mut file) = log.lock()
+            {
+                let _ = writeln!(file, "{}{}", prefix, line);
+            }
+        }
+    })
+}
+
+/// Start Xephyr and wait until it accepts connections
+fn start_xephyr(display: u32, size: &str, log: Option<Arc<Mutex<File>>>) -> Result<Child> {
+    let mut xephyr = Command::new("Xephyr")
+        .arg(format!(":{}", display))
+        .args(["-ac", "-br", "-resizeable", "-host-cursor", "-screen", size])
+        .args(["-title", "Area (nested)"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to start Xephyr (is it installed?)")?;
+    if let Some(out) = xephyr.stdout.take() {
+        forward(out, "[Xephyr] ", log.clone());
+    }
+    if let Some(err) = xephyr.stderr.take() {
+        forward(err, "[Xephyr] ", log);
+    }
+
+    let socket = PathBuf::from(format!("/tmp/.X11-unix/X{}", display));
+    let started = Instant::now();
+    while !socket.exists() {
+        if let Some(status) = xephyr.try_wait()? {
+            bail!("Xephyr exited during startup ({})", status);
+        }
+        if started.elapsed() > STARTUP_TIMEOUT {
+            let _ = xephyr.kill();
+            bail!("Xephyr did not create {} within {:?}", socket.display(), STARTUP_TIMEOUT);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    Ok(xephyr)
+}
+
+/// Run the whole stack inside Xephyr and return the inner instance's exit code
+pub fn run(options: NestedOptions, args: &[String]) -> Result<i32> {
+    let display_num = match options.display {
+        Some(n) if display_in_use(n) => bail!("display :{} is already in use", n),
+        Some(n) => n,
+        None => (1..100).find(|&n| !display_in_use(n)).context("no free X display between :1 and :99")?,
+    };
+    let log = options
+        .log
+        .as_ref()
+        .map(|path| File::create(path).with_context(|| format!("failed to create {}", path.display())))
+        .transpose()?
+        .map(|file| Arc::new(Mutex::new(file)));
+
+    info!("Starting Xephyr {} on :{}", options.size, display_num);
+    let mut xephyr = start_xephyr(display_num, &options.size, log.clone())?;
+
+    let exe = std::env::current_exe().context("failed to locate the area binary")?;
+    let inner = inner_args(args);
+    info!("Launching {} {:?} on :{}", exe.display(), inner, display_num);
+    let spawned = Command::new(&exe)
+        .args(&inner)
+        .env("DISPLAY", format!(":{}", display_num))
+        .env_remove("XAUTHORITY")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut area = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = xephyr.kill();
+            return Err(e).context("failed to launch nested area");
+        }
+    };
+
+    let mut forwarders = Vec::new();
+    if let Some(out) = area.stdout.take() {
+        forwarders.push(forward(out, "", log.clone()));
+    }
+    if let Some(err) = area.stderr.take() {
+        forwarders.push(forward(err, "", log));
+    }
+
+    // Whichever exits first ends the session
+    let code = loop {
+        if let Some(status) = area.try_wait()? {
+            info!("Nested area exited ({})", status);
+            let _ = xephyr.kill();
+            break status.code().unwrap_or(1);
+        }
+        if let Some(status) = xephyr.try_wait()? {
+            warn!("Xephyr exited ({}), stopping nested area", status);
+            let _ = area.kill();
+            let _ = area.wait();
+            break status.code().unwrap_or(1);
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    };
+    let _ = xephyr.wait();
+    for handle in forwarders {
+        let _ = handle.join();
+    }
+    Ok(code)
+}
@@ -0,0 +1,840 @@
+//! Mock X server for replay
+//!
+//! Serves a `RustConnection` over a socket pair from a thread, so a recording
+//! replays without a display. The mock keeps a small model of the server:
+//! the window tree with geometry, map state, event masks and properties, the
+//! atoms, keymap and extension opcodes captured at record time, selection
+//! owners, input focus and the pointer position. The model is fed from the
+//! WM's own requests and from the recorded events (`MockServer::observe`).
+//!
+//! The only events the mock generates are PropertyNotify for windows that
+//! select them, stamped with the time of the last replayed event; all others
+//! come from the recording. Everything else is answered the same way on
+//! every run: extension version queries get the version asked for, other
+//! requests with replies fail with `BadImplementation`, and requests without
+//! replies succeed. Client properties aren't part of the recording, so
+//! clients replay with none set.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use anyhow::{Context, Result};
+use tracing::{debug, warn};
+use x11rb::protocol::xproto::{self, EventMask, MapState, Property, Setup, StackMode, WindowClass};
+use x11rb::protocol::{Event, Request, composite, damage, dpms, present, randr, render, shape, sync, xfixes, xinput, xkb};
+use x11rb::rust_connection::{DefaultStream, RustConnection};
+use x11rb::x11_utils::{RequestHeader, Serialize, TryParse};
+
+use super::{RecordedExtension, RecordedExtensions, RecordingHeader};
+
+/// Error codes sent by the mock
+const BAD_VALUE: u8 = 2;
+const BAD_ATOM: u8 = 5;
+const BAD_IMPLEMENTATION: u8 = 17;
+
+/// Atoms every server predefines (PRIMARY to WM_TRANSIENT_FOR)
+const LAST_PREDEFINED_ATOM: u32 = 68;
+
+/// Buttons reported by GetPointerMapping
+const POINTER_BUTTONS: u8 = 5;
+
+/// A window in the mock's model
+#[derive(Debug, Clone, Default)]
+struct MockWindow {
+    parent: u32,
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+    border: u16,
+    mapped: bool,
+    override_redirect: bool,
+    event_mask: EventMask,
+    /// Bottom to top
+    children: Vec<u32>,
+    properties: BTreeMap<u32, MockProperty>,
+}
+
+#[derive(Debug, Clone)]
+struct MockProperty {
+    type_: u32,
+    format: u8,
+    data: Vec<u8>,
+}
+
+/// Server state shared between the serving thread and the replay loop
+#[derive(Debug)]
+struct MockState {
+    root: u32,
+    root_visual: u32,
+    root_depth: u8,
+    extensions: Vec<RecordedExtension>,
+    windows: HashMap<u32, MockWindow>,
+    atoms: BTreeMap<u32, String>,
+    atom_ids: HashMap<String, u32>,
+    selections: HashMap<u32, u32>,
+    focus: (u32, xproto::InputFocus),
+    pointer: (i16, i16),
+    /// Server time, taken from the replayed events
+    time: u32,
+    min_keycode: u8,
+    keysyms_per_keycode: u8,
+    keysyms: Vec<u32>,
+    modifier_keycodes: Vec<u8>,
+}
+
+/// Handle on a running mock server
+pub struct MockServer {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockServer {
+    /// Start a mock server for `header` and connect to it
+    pub fn connect(header: &RecordingHeader) -> Result<(RustConnection, Self)> {
+        let (setup, _) = Setup::try_parse(&header.setup).context("Invalid X setup in event recording")?;
+        let screen = setup.roots.get(header.screen).context("Recorded screen is missing from the X setup")?;
+
+        let mut windows = HashMap::new();
+        windows.insert(screen.root, MockWindow {
+            parent: 0,
+            width: screen.width_in_pixels,
+            height: screen.height_in_pixels,
+            mapped: true,
+            ..Default::default()
+        });
+        let atoms: BTreeMap<u32, String> = header.atoms.iter().cloned().collect();
+        let atom_ids = atoms.iter().map(|(&id, name)| (name.clone(), id)).collect();
+        let state = MockState {
+            root: screen.root,
+            root_visual: screen.root_visual,
+            root_depth: screen.root_depth,
+            extensions: header.extensions.clone(),
+            windows,
+            atoms,
+            atom_ids,
+            selections: HashMap::new(),
+            focus: (screen.root, xproto::InputFocus::POINTER_ROOT),
+            pointer: (0, 0),
+            time: 1,
+            min_keycode: setup.min_keycode,
+            keysyms_per_keycode: header.keyboard.keysyms_per_keycode,
+            keysyms: header.keyboard.keysyms.clone(),
+            modifier_keycodes: header.keyboard.modifier_keycodes.clone(),
+        };
+        let state = Arc::new(Mutex::new(state));
+
+        let (client, server) = UnixStream::pair().context("Failed to create the mock X server socket")?;
+        let setup_bytes = header.setup.clone();
+        let server_state = state.clone();
+        std::thread::Builder::new()
+            .name("area-mock-x11".into())
+            .spawn(move || {
+                if let Err(e) = serve(server, &setup_bytes, &server_state) {
+                    warn!("Mock X server stopped: {}", e);
+                }
+            })
+            .context("Failed to start the mock X server")?;
+
+        let (stream, _) = DefaultStream::from_unix_stream(client)?;
+        let conn = RustConnection::connect_to_stream(stream, header.screen).context("Failed to connect to the mock X server")?;
+        Ok((conn, Self { state }))
+    }
+
+    /// Update the model from a replayed event, before the WM handles it
+    pub fn observe(&self, event: &Event) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match event {
+            Event::CreateNotify(e) => {
+                let window = state.window(e.window);
+                (window.x, window.y, window.width, window.height) = (e.x, e.y, e.width, e.height);
+                window.border = e.border_width;
+                window.override_redirect = e.override_redirect;
+                state.reparent(e.window, e.parent);
+            }
+            Event::DestroyNotify(e) => state.destroy(e.window),
+            Event::MapNotify(e) => state.window(e.window).mapped = true,
+            Event::UnmapNotify(e) => state.window(e.window).mapped = false,
+            Event::ConfigureNotify(e) => {
+                let window = state.window(e.window);
+                (window.x, window.y, window.width, window.height) = (e.x, e.y, e.width, e.height);
+                window.border = e.border_width;
+            }
+            Event::ReparentNotify(e) => {
+                let window = state.window(e.window);
+                (window.x, window.y) = (e.x, e.y);
+                state.reparent(e.window, e.parent);
+            }
+            Event::MotionNotify(e) => (state.pointer, state.time) = ((e.root_x, e.root_y), e.time),
+            Event::ButtonPress(e) | Event::ButtonRelease(e) => (state.pointer, state.time) = ((e.root_x, e.root_y), e.time),
+            Event::EnterNotify(e) | Event::LeaveNotify(e) => (state.pointer, state.time) = ((e.root_x, e.root_y), e.time),
+            Event::KeyPress(e) | Event::KeyRelease(e) => state.time = e.time,
+            Event::PropertyNotify(e) => state.time = e.time,
+            _ => {}
+        }
+    }
+}
+
+impl MockState {
+    /// A window of the model, created as an unmapped 1x1 child of the root
+    /// if the mock hasn't seen it yet
+    fn window(&mut self, id: u32) -> &mut MockWindow {
+        if !self.windows.contains_key(&id) {
+            let root = self.root;
+            self.windows.insert(id, MockWindow { parent: root, width: 1, height: 1, ..Default::default() });
+            if let Some(root) = self.windows.get_mut(&root) {
+                root.children.push(id);
+            }
+        }
+        self.windows.get_mut(&id).expect("window was just inserted")
+    }
+
+    fn reparent(&mut self, id: u32, parent: u32) {
+        let old = self.window(id).parent;
+        if let Some(old) = self.windows.get_mut(&old) {
+            old.children.retain(|&child| child != id);
+        }
+        self.window(id).parent = parent;
+        self.window(parent).children.push(id);
+    }
+
+    fn destroy(&mut self, id: u32) {
+        let Some(window) = self.windows.remove(&id) else { return };
+        if let Some(parent) = self.windows.get_mut(&window.parent) {
+            parent.children.retain(|&child| child != id);
+        }
+        for child in window.children {
+            self.destroy(child);
+        }
+    }
+
+    /// Position of a window's origin relative to the root
+    fn root_origin(&self, mut id: u32) -> (i32, i32) {
+        let (mut x, mut y) = (0, 0);
+        while let Some(window) = self.windows.get(&id) {
+            if id == self.root {
+                break;
+            }
+            x += i32::from(window.x) + i32::from(window.border);
+            y += i32::from(window.y) + i32::from(window.border);
+            id = window.parent;
+        }
+        (x, y)
+    }
+
+    fn intern(&mut self, name: &str, only_if_exists: bool) -> u32 {
+        if let Some(&id) = self.atom_ids.get(name) {
+            return id;
+        }
+        if only_if_exists {
+            return 0;
+        }
+        let id = self.atoms.keys().next_back().map_or(0, |&last| last).max(LAST_PREDEFINED_ATOM) + 1;
+        self.atoms.insert(id, name.to_string());
+        self.atom_ids.insert(name.to_string(), id);
+        id
+    }
+
+    /// PropertyNotify for `id` if it selected them
+    fn property_notify(&self, sequence: u16, id: u32, atom: u32, state: Property) -> Option<Vec<u8>> {
+        let window = self.windows.get(&id)?;
+        if !window.event_mask.contains(EventMask::PROPERTY_CHANGE) {
+            return None;
+        }
+        let event = xproto::PropertyNotifyEvent {
+            response_type: xproto::PROPERTY_NOTIFY_EVENT,
+            sequence,
+            window: id,
+            atom,
+            time: self.time,
+            state,
+        };
+        Some(<[u8; 32]>::from(&event).to_vec())
+    }
+
+    /// Apply a request; returns the reply or error to send, if any
+    fn handle(&mut self, sequence: u16, request: &[u8]) -> Option<Vec<u8>> {
+        let header = RequestHeader {
+            major_opcode: request[0],
+            minor_opcode: request[1],
+            remaining_length: u32::from(u16::from_ne_bytes([request[2], request[3]])) - 1,
+        };
+        let extensions = RecordedExtensions(&self.extensions);
+        let request = match Request::parse(header, &request[4..], &mut Vec::new(), &extensions) {
+            Ok(Request::Unknown(..)) | Err(_) => {
+                debug!("Mock X server: can't parse request {}.{}", header.major_opcode, header.minor_opcode);
+                return Some(error(sequence, BAD_IMPLEMENTATION, 0, header));
+            }
+            Ok(request) => request,
+        };
+
+        match request {
+            Request::CreateWindow(r) => {
+                let window = self.window(r.wid);
+                (window.x, window.y, window.width, window.height, window.border) = (r.x, r.y, r.width, r.height, r.border_width);
+                window.override_redirect = r.value_list.override_redirect.is_some_and(|value| value != 0);
+                window.event_mask = r.value_list.event_mask.unwrap_or_default();
+                self.reparent(r.wid, r.parent);
+            }
+            Request::ChangeWindowAttributes(r) => {
+                let window = self.window(r.window);
+                if let Some(value) = r.value_list.override_redirect {
+                    window.override_redirect = value != 0;
+                }
+                if let Some(mask) = r.value_list.event_mask {
+                    window.event_mask = mask;
+                }
+            }
+            Request::GetWindowAttributes(r) => {
+                let visual = self.root_visual;
+                let window = self.window(r.window);
+                return Some(reply(sequence, xproto::GetWindowAttributesReply {
+                    visual,
+                    class: WindowClass::INPUT_OUTPUT,
+                    win_gravity: xproto::Gravity::NORTH_WEST,
+                    map_is_installed: true,
+                    map_state: if window.mapped { MapState::VIEWABLE } else { MapState::UNMAPPED },
+                    override_redirect: window.override_redirect,
+                    your_event_mask: window.event_mask,
+                    all_event_masks: window.event_mask,
+                    ..Default::default()
+                }));
+            }
+            Request::DestroyWindow(r) => self.destroy(r.window),
+            Request::ReparentWindow(r) => {
+                let window = self.window(r.window);
+                (window.x, window.y) = (r.x, r.y);
+                self.reparent(r.window, r.parent);
+            }
+            Request::MapWindow(r) => self.window(r.window).mapped = true,
+            Request::UnmapWindow(r) => self.window(r.window).mapped = false,
+            Request::ConfigureWindow(r) => self.configure(&r),
+            Request::GetGeometry(r) => {
+                let (root, depth) = (self.root, self.root_depth);
+                let window = self.window(r.drawable);
+                return Some(reply(sequence, xproto::GetGeometryReply {
+                    depth,
+                    root,
+                    x: window.x,
+                    y: window.y,
+                    width: window.width,
+                    height: window.height,
+                    border_width: window.border,
+                    ..Default::default()
+                }));
+            }
+            Request::QueryTree(r) => {
+                let root = self.root;
+                let window = self.window(r.window);
+                return Some(reply(sequence, xproto::QueryTreeReply {
+                    root,
+                    parent: window.parent,
+                    children: window.children.clone(),
+                    ..Default::default()
+                }));
+            }
+            Request::InternAtom(r) => {
+                let atom = self.intern(&String::from_utf8_lossy(&r.name), r.only_if_exists);
+                return Some(reply(sequence, xproto::InternAtomReply { atom, ..Default::default() }));
+            }
+            Request::GetAtomName(r) => {
+                let Some(name) = self.atoms.get(&r.atom) else {
+                    return Some(error(sequence, BAD_ATOM, r.atom, header));
+                };
+                return Some(reply(sequence, xproto::GetAtomNameReply { name: name.clone().into_bytes(), ..Default::default() }));
+            }
+            Request::ChangeProperty(r) => {
+                let Some(data) = r.data.get(..r.data_len as usize * usize::from(r.format / 8)) else {
+                    return Some(error(sequence, BAD_VALUE, r.data_len, header));
+                };
+                let properties = &mut self.window(r.window).properties;
+                match properties.get_mut(&r.property) {
+                    Some(old) if r.mode == xproto::PropMode::PREPEND && old.type_ == r.type_ && old.format == r.format => {
+                        old.data.splice(0..0, data.iter().copied());
+                    }
+                    Some(old) if r.mode == xproto::PropMode::APPEND && old.type_ == r.type_ && old.format == r.format => {
+                        old.data.extend_from_slice(data);
+                    }
+                    _ => {
+                        properties.insert(r.property, MockProperty { type_: r.type_, format: r.format, data: data.to_vec() });
+                    }
+                }
+                return self.property_notify(sequence, r.window, r.property, Property::NEW_VALUE);
+            }
+            Request::DeleteProperty(r) => {
+                self.window(r.window).properties.remove(&r.property)?;
+                return self.property_notify(sequence, r.window, r.property, Property::DELETE);
+            }
+            Request::GetProperty(r) => return Some(self.get_property(sequence, &r, header)),
+            Request::ListProperties(r) => {
+                let atoms = self.window(r.window).properties.keys().copied().collect();
+                return Some(reply(sequence, xproto::ListPropertiesReply { atoms, ..Default::default() }));
+            }
+            Request::SetSelectionOwner(r) => {
+                self.selections.insert(r.selection, r.owner);
+            }
+            Request::GetSelectionOwner(r) => {
+                let owner = self.selections.get(&r.selection).copied().unwrap_or_default();
+                return Some(reply(sequence, xproto::GetSelectionOwnerReply { owner, ..Default::default() }));
+            }
+            Request::GrabPointer(_) => return Some(reply(sequence, xproto::GrabPointerReply::default())),
+            Request::GrabKeyboard(_) => return Some(reply(sequence, xproto::GrabKeyboardReply::default())),
+            Request::QueryPointer(r) => {
+                let (origin_x, origin_y) = self.root_origin(r.window);
+                let (x, y) = self.pointer;
+                return Some(reply(sequence, xproto::QueryPointerReply {
+                    same_screen: true,
+                    root: self.root,
+                    root_x: x,
+                    root_y: y,
+                    win_x: (i32::from(x) - origin_x) as i16,
+                    win_y: (i32::from(y) - origin_y) as i16,
+                    ..Default::default()
+                }));
+            }
+            Request::TranslateCoordinates(r) => {
+                let (src_x, src_y) = self.root_origin(r.src_window);
+                let (dst_x, dst_y) = self.root_origin(r.dst_window);
+                return Some(reply(sequence, xproto::TranslateCoordinatesReply {
+                    same_screen: true,
+                    dst_x: (i32::from(r.src_x) + src_x - dst_x) as i16,
+                    dst_y: (i32::from(r.src_y) + src_y - dst_y) as i16,
+                    ..Default::default()
+                }));
+            }
+            Request::SetInputFocus(r) => self.focus = (r.focus, r.revert_to),
+            Request::GetInputFocus(_) => {
+                let (focus, revert_to) = self.focus;
+                return Some(reply(sequence, xproto::GetInputFocusReply { focus, revert_to, ..Default::default() }));
+            }
+            Request::QueryKeymap(_) => return Some(reply(sequence, xproto::QueryKeymapReply::default())),
+            // TrueColor with 8 bits per channel
+            Request::AllocColor(r) => {
+                let [red, green, blue] = [r.red, r.green, r.blue].map(|channel| channel >> 8);
+                return Some(reply(sequence, xproto::AllocColorReply {
+                    red: red * 0x101,
+                    green: green * 0x101,
+                    blue: blue * 0x101,
+                    pixel: u32::from(red) << 16 | u32::from(green) << 8 | u32::from(blue),
+                    ..Default::default()
+                }));
+            }
+            Request::QueryExtension(r) => {
+                let info = self.extensions.iter().find(|ext| ext.name.as_bytes() == &r.name[..]);
+                return Some(reply(sequence, xproto::QueryExtensionReply {
+                    present: info.is_some(),
+                    major_opcode: info.map_or(0, |ext| ext.major_opcode),
+                    first_event: info.map_or(0, |ext| ext.first_event),
+                    first_error: info.map_or(0, |ext| ext.first_error),
+                    ..Default::default()
+                }));
+            }
+            Request::ListExtensions(_) => {
+                let names = self.extensions.iter().map(|ext| xproto::Str { name: ext.name.clone().into_bytes() }).collect();
+                return Some(reply(sequence, xproto::ListExtensionsReply { names, ..Default::default() }));
+            }
+            Request::GetKeyboardMapping(r) => {
+                let per_keycode = usize::from(self.keysyms_per_keycode);
+                let first = usize::from(r.first_keycode.saturating_sub(self.min_keycode)) * per_keycode;
+                let keysyms = (first..first + usize::from(r.count) * per_keycode)
+                    .map(|index| self.keysyms.get(index).copied().unwrap_or_default())
+                    .collect();
+                return Some(reply(sequence, xproto::GetKeyboardMappingReply {
+                    keysyms_per_keycode: self.keysyms_per_keycode,
+                    keysyms,
+                    ..Default::default()
+                }));
+            }
+            Request::GetPointerMapping(_) => {
+                let map = (1..=POINTER_BUTTONS).collect();
+                return Some(reply(sequence, xproto::GetPointerMappingReply { map, ..Default::default() }));
+            }
+            Request::GetModifierMapping(_) => {
+                let keycodes = self.modifier_keycodes.clone();
+                return Some(reply(sequence, xproto::GetModifierMappingReply { keycodes, ..Default::default() }));
+            }
+            request => return extension_version(sequence, &request).or_else(|| {
+                request.reply_parser().map(|_| {
+                    debug!("Mock X server: no reply for request {}.{}", header.major_opcode, header.minor_opcode);
+                    error(sequence, BAD_IMPLEMENTATION, 0, header)
+                })
+            }),
+        }
+        None
+    }
+
+    fn configure(&mut self, request: &xproto::ConfigureWindowRequest<'_>) {
+        let (id, values) = (request.window, &request.value_list);
+        let window = self.window(id);
+        if let Some(x) = values.x {
+            window.x = x as i16;
+        }
+        if let Some(y) = values.y {
+            window.y = y as i16;
+        }
+        if let Some(width) = values.width {
+            window.width = width as u16;
+        }
+        if let Some(height) = values.height {
+            window.height = height as u16;
+        }
+        if let Some(border) = values.border_width {
+            window.border = border as u16;
+        }
+        let parent = window.parent;
+        let Some(mode) = values.stack_mode else { return };
+        let Some(siblings) = self.windows.get_mut(&parent).map(|parent| &mut parent.children) else { return };
+        siblings.retain(|&child| child != id);
+        let sibling = values.sibling.and_then(|sibling| siblings.iter().position(|&child| child == sibling));
+        let index = match (mode, sibling) {
+            (StackMode::ABOVE, Some(index)) => index + 1,
+            (StackMode::BELOW, Some(index)) => index,
+            (StackMode::BELOW, None) => 0,
+            _ => siblings.len(),
+        };
+        siblings.insert(index, id);
+    }
+
+    fn get_property(&mut self, sequence: u16, request: &xproto::GetPropertyRequest, header: RequestHeader) -> Vec<u8> {
+        let properties = &mut self.window(request.window).properties;
+        let Some(value) = properties.get(&request.property) else {
+            return reply(sequence, xproto::GetPropertyReply::default());
+        };
+        if request.type_ != u32::from(xproto::AtomEnum::ANY) && request.type_ != value.type_ {
+            return reply(sequence, xproto::GetPropertyReply {
+                format: value.format,
+                type_: value.type_,
+                bytes_after: value.data.len() as u32,
+                ..Default::default()
+            });
+        }
+        let offset = request.long_offset as usize * 4;
+        if offset > value.data.len() {
+            return error(sequence, BAD_VALUE, request.long_offset, header);
+        }
+        let end = value.data.len().min(offset.saturating_add(request.long_length as usize * 4));
+        let response = xproto::GetPropertyReply {
+            format: value.format,
+            type_: value.type_,
+            bytes_after: (value.data.len() - end) as u32,
+            value_len: ((end - offset) / usize::from(value.format / 8)) as u32,
+            value: value.data[offset..end].to_vec(),
+            ..Default::default()
+        };
+        let mut bytes = reply(sequence, response);
+        if request.delete && end == value.data.len() {
+            properties.remove(&request.property);
+            bytes.extend(self.property_notify(sequence, request.window, request.property, Property::DELETE).unwrap_or_default());
+        }
+        bytes
+    }
+}
+
+/// Reply to an extension's version handshake with the version the client asked for
+fn extension_version(sequence: u16, request: &Request<'_>) -> Option<Vec<u8>> {
+    Some(match request {
+        Request::ShapeQueryVersion(_) => reply(sequence, shape::QueryVersionReply { major_version: 1, minor_version: 1, ..Default::default() }),
+        Request::CompositeQueryVersion(r) => reply(sequence, composite::QueryVersionReply {
+            major_version: r.client_major_version,
+            minor_version: r.client_minor_version,
+            ..Default::default()
+        }),
+        Request::DamageQueryVersion(r) => reply(sequence, damage::QueryVersionReply {
+            major_version: r.client_major_version,
+            minor_version: r.client_minor_version,
+            ..Default::default()
+        }),
+        Request::XfixesQueryVersion(r) => reply(sequence, xfixes::QueryVersionReply {
+            major_version: r.client_major_version,
+            minor_version: r.client_minor_version,
+            ..Default::default()
+        }),
+        Request::RenderQueryVersion(r) => reply(sequence, render::QueryVersionReply {
+            major_version: r.client_major_version,
+            minor_version: r.client_minor_version,
+            ..Default::default()
+        }),
+        Request::RandrQueryVersion(r) => reply(sequence, randr::QueryVersionReply {
+            major_version: r.major_version,
+            minor_version: r.minor_version,
+            ..Default::default()
+        }),
+        Request::PresentQueryVersion(r) => reply(sequence, present::QueryVersionReply {
+            major_version: r.major_version,
+            minor_version: r.minor_version,
+            ..Default::default()
+        }),
+        Request::XinputXIQueryVersion(r) => reply(sequence, xinput::XIQueryVersionReply {
+            major_version: r.major_version,
+            minor_version: r.minor_version,
+            ..Default::default()
+        }),
+        Request::XkbUseExtension(r) => reply(sequence, xkb::UseExtensionReply {
+            supported: true,
+            server_major: r.wanted_major,
+            server_minor: r.wanted_minor,
+            ..Default::default()
+        }),
+        Request::SyncInitialize(r) => reply(sequence, sync::InitializeReply {
+            major_version: r.desired_major_version,
+            minor_version: r.desired_minor_version,
+            ..Default::default()
+        }),
+        Request::DpmsGetVersion(r) => reply(sequence, dpms::GetVersionReply {
+            server_major_version: r.client_major_version,
+            server_minor_version: r.client_minor_version,
+            ..Default::default()
+        }),
+        _ => return None,
+    })
+}
+
+/// Wire bytes of a reply, with its sequence number and length filled in
+fn reply(sequence: u16, reply: impl Serialize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    reply.serialize_into(&mut bytes);
+    bytes.resize(bytes.len().max(32).next_multiple_of(4), 0);
+    bytes[2..4].copy_from_slice(&sequence.to_ne_bytes());
+    let length = ((bytes.len() - 32) / 4) as u32;
+    bytes[4..8].copy_from_slice(&length.to_ne_bytes());
+    bytes
+}
+
+fn error(sequence: u16, code: u8, value: u32, request: RequestHeader) -> Vec<u8> {
+    let mut error = vec![0, code];
+    error.extend_from_slice(&sequence.to_ne_bytes());
+    error.extend_from_slice(&value.to_ne_bytes());
+    error.extend_from_slice(&u16::from(request.minor_opcode).to_ne_bytes());
+    error.push(request.major_opcode);
+    error.resize(32, 0);
+    error
+}
+
+/// Answer the connection setup, then requests until the client disconnects
+fn serve(mut stream: UnixStream, setup: &[u8], state: &Mutex<MockState>) -> std::io::Result<()> {
+    let mut request = [0; 12];
+    stream.read_exact(&mut request)?;
+    let auth_len = |offset: usize| usize::from(u16::from_ne_bytes([request[offset], request[offset + 1]])).next_multiple_of(4);
+    let mut auth = vec![0; auth_len(6) + auth_len(8)];
+    stream.read_exact(&mut auth)?;
+    stream.write_all(setup)?;
+
+    let mut sequence: u16 = 0;
+    loop {
+        let mut header = [0; 4];
+        match stream.read_exact(&mut header) {
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            result => result?,
+        }
+        let len = usize::from(u16::from_ne_bytes([header[2], header[3]])) * 4;
+        if len < 4 {
+            // BIG-REQUESTS isn't offered, so this can't be a valid request
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "request without a length"));
+        }
+        let mut request = vec![0; len];
+        request[..4].copy_from_slice(&header);
+        stream.read_exact(&mut request[4..])?;
+        sequence = sequence.wrapping_add(1);
+        let response = state.lock().unwrap_or_else(PoisonError::into_inner).handle(sequence, &request);
+        if let Some(response) = response {
+            stream.write_all(&response)?;
+        }
+    }
+}
+
+/// `Setup` as sent by the server, for the recording header
+pub fn setup_bytes(setup: &Setup) -> Vec<u8> {
+    let mut bytes = setup.serialize();
+    // Length of everything after the first 8 bytes, in 4-byte units
+    let length = ((bytes.len() - 8) / 4) as u16;
+    bytes[6..8].copy_from_slice(&length.to_ne_bytes());
+    bytes
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::replay::{FORMAT_VERSION, RecordedKeyboard};
+    use x11rb::connection::{Connection, RequestConnection};
+    use x11rb::protocol::xproto::{
+        AtomEnum, ChangeWindowAttributesAux, ConfigureWindowAux, ConnectionExt, CreateWindowAux, PropMode, Screen,
+        StackMode, WindowClass,
+    };
+    use x11rb::wrapper::ConnectionExt as _;
+
+    pub(crate) fn header() -> RecordingHeader {
+        let screen = Screen {
+            root: 0x100,
+            root_visual: 0x21,
+            root_depth: 24,
+            width_in_pixels: 1920,
+            height_in_pixels: 1080,
+            ..Default::default()
+        };
+        let setup = Setup {
+            status: 1,
+            protocol_major_version: 11,
+            resource_id_base: 0x200000,
+            resource_id_mask: 0x1fffff,
+            maximum_request_length: 0xffff,
+            min_keycode: 8,
+            max_keycode: 255,
+            roots: vec![screen],
+            ..Default::default()
+        };
+        RecordingHeader {
+            version: FORMAT_VERSION,
+            display: ":99".into(),
+            extensions: [("SHAPE", 129, 64, 0), ("XFIXES", 138, 87, 140), ("DAMAGE", 143, 91, 152), ("Composite", 142, 0, 0)]
+                .map(|(name, major_opcode, first_event, first_error)| RecordedExtension {
+                    name: name.into(),
+                    major_opcode,
+                    first_event,
+                    first_error,
+                })
+                .into(),
+            setup: setup_bytes(&setup),
+            screen: 0,
+            atoms: vec![(1, "PRIMARY".into()), (4, "ATOM".into()), (300, "_NET_ACTIVE_WINDOW".into())],
+            keyboard: RecordedKeyboard {
+                keysyms_per_keycode: 2,
+                keysyms: vec![0x61, 0x41, 0x62, 0x42],
+                keycodes_per_modifier: 1,
+                modifier_keycodes: vec![50, 66, 37, 64, 0, 0, 133, 0],
+            },
+            config: Default::default(),
+        }
+    }
+
+    fn connect() -> (RustConnection, MockServer) {
+        MockServer::connect(&header()).unwrap()
+    }
+
+    #[test]
+    fn serves_the_recorded_setup() {
+        let (conn, _server) = connect();
+        assert_eq!(conn.setup().roots[0].root, 0x100);
+        assert_eq!(conn.setup().resource_id_base, 0x200000);
+        assert_eq!(conn.generate_id().unwrap(), 0x200000);
+    }
+
+    #[test]
+    fn interns_recorded_atoms_then_new_ones() {
+        let (conn, _server) = connect();
+        assert_eq!(conn.intern_atom(false, b"_NET_ACTIVE_WINDOW").unwrap().reply().unwrap().atom, 300);
+        assert_eq!(conn.intern_atom(true, b"_AREA_NEW").unwrap().reply().unwrap().atom, 0);
+        assert_eq!(conn.intern_atom(false, b"_AREA_NEW").unwrap().reply().unwrap().atom, 301);
+        assert_eq!(conn.get_atom_name(301).unwrap().reply().unwrap().name, b"_AREA_NEW");
+        assert!(conn.get_atom_name(302).unwrap().reply().is_err());
+    }
+
+    #[test]
+    fn tracks_windows_created_by_the_client() {
+        let (conn, _server) = connect();
+        let frame = conn.generate_id().unwrap();
+        let aux = CreateWindowAux::new().override_redirect(1);
+        conn.create_window(24, frame, 0x100, 10, 20, 300, 200, 2, WindowClass::INPUT_OUTPUT, 0, &aux).unwrap();
+        conn.map_window(frame).unwrap();
+        conn.reparent_window(0x400001, frame, 0, 24).unwrap();
+
+        let geometry = conn.get_geometry(frame).unwrap().reply().unwrap();
+        assert_eq!((geometry.x, geometry.y, geometry.width, geometry.height, geometry.border_width), (10, 20, 300, 200, 2));
+        let attributes = conn.get_window_attributes(frame).unwrap().reply().unwrap();
+        assert!(attributes.override_redirect);
+        assert_eq!(attributes.map_state, x11rb::protocol::xproto::MapState::VIEWABLE);
+        let tree = conn.query_tree(frame).unwrap().reply().unwrap();
+        assert_eq!((tree.parent, tree.children), (0x100, vec![0x400001]));
+
+        let translated = conn.translate_coordinates(0x400001, 0x100, 5, 5).unwrap().reply().unwrap();
+        assert_eq!((translated.dst_x, translated.dst_y), (17, 51));
+
+        conn.change_window_attributes(frame, &ChangeWindowAttributesAux::new().override_redirect(0)).unwrap();
+        conn.configure_window(frame, &ConfigureWindowAux::new().x(50).width(640)).unwrap();
+        let geometry = conn.get_geometry(frame).unwrap().reply().unwrap();
+        assert_eq!((geometry.x, geometry.width), (50, 640));
+        assert!(!conn.get_window_attributes(frame).unwrap().reply().unwrap().override_redirect);
+
+        conn.destroy_window(frame).unwrap();
+        assert!(conn.query_tree(0x100).unwrap().reply().unwrap().children.is_empty());
+    }
+
+    #[test]
+    fn restacks_siblings() {
+        let (conn, _server) = connect();
+        for window in [1, 2, 3] {
+            conn.create_window(24, window, 0x100, 0, 0, 1, 1, 0, WindowClass::INPUT_OUTPUT, 0, &CreateWindowAux::new()).unwrap();
+        }
+        conn.configure_window(1, &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE)).unwrap();
+        conn.configure_window(3, &ConfigureWindowAux::new().sibling(2).stack_mode(StackMode::BELOW)).unwrap();
+        assert_eq!(conn.query_tree(0x100).unwrap().reply().unwrap().children, [3, 2, 1]);
+    }
+
+    #[test]
+    fn properties_round_trip() {
+        let (conn, _server) = connect();
+        conn.change_property32(PropMode::REPLACE, 0x100, 300u32, AtomEnum::WINDOW, &[7]).unwrap();
+        conn.change_property32(PropMode::APPEND, 0x100, 300u32, AtomEnum::WINDOW, &[8, 9]).unwrap();
+        let reply = conn.get_property(false, 0x100, 300u32, AtomEnum::WINDOW, 0, 2).unwrap().reply().unwrap();
+        assert_eq!(reply.value32().unwrap().collect::<Vec<_>>(), [7, 8]);
+        assert_eq!(reply.bytes_after, 4);
+        let wrong_type = conn.get_property(false, 0x100, 300u32, AtomEnum::ATOM, 0, 2).unwrap().reply().unwrap();
+        assert!(wrong_type.value.is_empty());
+        assert_eq!(wrong_type.type_, u32::from(AtomEnum::WINDOW));
+
+        assert_eq!(conn.list_properties(0x100).unwrap().reply().unwrap().atoms, [300]);
+        conn.get_property(true, 0x100, 300u32, AtomEnum::ANY, 0, 16).unwrap().reply().unwrap();
+        let missing = conn.get_property(false, 0x100, 300u32, AtomEnum::ANY, 0, 16).unwrap().reply().unwrap();
+        assert_eq!((missing.type_, missing.format), (0, 0));
+    }
+
+    #[test]
+    fn answers_keyboard_focus_and_selections() {
+        let (conn, _server) = connect();
+        let mapping = conn.get_keyboard_mapping(9, 1).unwrap().reply().unwrap();
+        assert_eq!((mapping.keysyms_per_keycode, mapping.keysyms), (2, vec![0x62, 0x42]));
+        let modifiers = conn.get_modifier_mapping().unwrap().reply().unwrap();
+        assert_eq!(modifiers.keycodes[2], 37);
+
+        conn.set_input_focus(x11rb::protocol::xproto::InputFocus::PARENT, 0x400001u32, 0u32).unwrap();
+        assert_eq!(conn.get_input_focus().unwrap().reply().unwrap().focus, 0x400001);
+        conn.set_selection_owner(0x400001u32, u32::from(AtomEnum::PRIMARY), 0u32).unwrap();
+        assert_eq!(conn.get_selection_owner(u32::from(AtomEnum::PRIMARY)).unwrap().reply().unwrap().owner, 0x400001);
+    }
+
+    #[test]
+    fn reports_the_recorded_extensions() {
+        use x11rb::protocol::{composite::ConnectionExt as _, shape::ConnectionExt as _};
+
+        let (conn, _server) = connect();
+        let shape = conn.extension_information(shape::X11_EXTENSION_NAME).unwrap().unwrap();
+        assert_eq!((shape.major_opcode, shape.first_event), (129, 64));
+        assert!(conn.extension_information(randr::X11_EXTENSION_NAME).unwrap().is_none());
+
+        let version = conn.composite_query_version(0, 4).unwrap().reply().unwrap();
+        assert_eq!((version.major_version, version.minor_version), (0, 4));
+        conn.shape_select_input(0x100, true).unwrap();
+        assert!(conn.get_image(x11rb::protocol::xproto::ImageFormat::Z_PIXMAP, 0x100, 0, 0, 1, 1, !0).unwrap().reply().is_err());
+        assert!(conn.shape_input_selected(0x100).unwrap().reply().is_err());
+    }
+
+    #[test]
+    fn observes_replayed_events() {
+        let (conn, server) = connect();
+        server.observe(&Event::CreateNotify(x11rb::protocol::xproto::CreateNotifyEvent {
+            parent: 0x100,
+            window: 0x400001,
+            x: 5,
+            y: 6,
+            width: 640,
+            height: 480,
+            ..Default::default()
+        }));
+        server.observe(&Event::MapNotify(x11rb::protocol::xproto::MapNotifyEvent { window: 0x400001, ..Default::default() }));
+        let geometry = conn.get_geometry(0x400001).unwrap().reply().unwrap();
+        assert_eq!((geometry.x, geometry.width), (5, 640));
+        assert_eq!(conn.query_tree(0x100).unwrap().reply().unwrap().children, [0x400001]);
+        server.observe(&Event::DestroyNotify(x11rb::protocol::xproto::DestroyNotifyEvent { window: 0x400001, ..Default::default() }));
+        assert!(conn.query_tree(0x100).unwrap().reply().unwrap().children.is_empty());
+    }
+}
@@ -0,0 +1,333 @@
+//! Event Recording and Replay
+//!
+//! Records every X11 event the WM handles (as raw wire bytes, after
+//! `x11_async::compress_events` dropped superseded ones) and IPC command with a
+//! timestamp to a JSON-lines file, and feeds a recording back through `AreaApp::handle_event`
+//! so hard-to-reproduce focus/stacking bugs can be replayed deterministically.
+//!
+//! File layout:
+//! - line 1: `RecordingHeader` (format version, display, extension opcodes)
+//! - line 2..: one `RecordedEntry` per event or IPC command
+//!
+//! Extension opcodes are assigned per X server, so the header stores the ones
+//! that were live while recording and replay decodes events against those
+//! instead of the replay server's.
+//!
+//! Replay doesn't need a display: the WM talks to a mock X server (see
+//! `mock`) started from the header, which also carries the connection setup,
+//! the server's atoms, the keymap and the config in use, so every replay of
+//! a recording runs the same way. The compositor, D-Bus services, IPC socket,
+//! hooks and session components aren't started during replay.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+use tracing::{debug, info};
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::ConnectionExt;
+use x11rb::rust_connection::RustConnection;
+use x11rb::x11_utils::{ExtInfoProvider, ExtensionInformation};
+
+use crate::config::Config;
+
+pub mod mock;
+
+/// Recording format version (bumped on incompatible changes)
+const FORMAT_VERSION: u32 = 2;
+
+/// Atoms looked up per round trip when capturing the server's atoms
+const ATOM_BATCH: u32 = 256;
+
+/// Extensions whose events/errors we may receive (must match Cargo features)
+const RECORDED_EXTENSIONS: &[&str] = &[
+    x11rb::protocol::composite::X11_EXTENSION_NAME,
+    x11rb::protocol::damage::X11_EXTENSION_NAME,
+    x11rb::protocol::xfixes::X11_EXTENSION_NAME,
+    x11rb::protocol::shape::X11_EXTENSION_NAME,
+    x11rb::protocol::randr::X11_EXTENSION_NAME,
+    x11rb::protocol::render::X11_EXTENSION_NAME,
+    x11rb::protocol::present::X11_EXTENSION_NAME,
+    x11rb::protocol::dri3::X11_EXTENSION_NAME,
+    x11rb::protocol::xinput::X11_EXTENSION_NAME,
+    x11rb::protocol::sync::X11_EXTENSION_NAME,
+    x11rb::protocol::xkb::X11_EXTENSION_NAME,
+];
+
+/// Extension opcode assignment captured at record time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedExtension {
+    pub name: String,
+    pub major_opcode: u8,
+    pub first_event: u8,
+    pub first_error: u8,
+}
+
+/// Keyboard and modifier mapping captured at record time
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordedKeyboard {
+    pub keysyms_per_keycode: u8,
+    /// From the setup's `min_keycode` on
+    pub keysyms: Vec<u32>,
+    pub keycodes_per_modifier: u8,
+    pub modifier_keycodes: Vec<u8>,
+}
+
+/// First line of a recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub version: u32,
+    pub display: String,
+    pub extensions: Vec<RecordedExtension>,
+    /// Connection setup as sent by the server
+    pub setup: Vec<u8>,
+    /// Screen the WM manages
+    pub screen: usize,
+    /// The server's atoms when recording started
+    pub atoms: Vec<(u32, String)>,
+    pub keyboard: RecordedKeyboard,
+    pub config: Config,
+}
+
+/// A single recorded input to the event loop
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedInput {
+    /// Raw X11 event bytes as received from the server
+    X11 { raw: Vec<u8> },
+    /// Command received on the IPC socket
+    Ipc { command: area_ipc::ShellCommand },
+}
+
+/// A recorded input with its offset from the start of the recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEntry {
+    /// Milliseconds since recording started
+    pub t_ms: u64,
+    #[serde(flatten)]
+    pub input: RecordedInput,
+}
+
+/// Writes incoming events to a recording file
+pub struct EventRecorder {
+    writer: BufWriter<File>,
+    start: Instant,
+    count: u64,
+}
+
+impl EventRecorder {
+    /// Create a recording at `path`, capturing the extension opcodes, setup,
+    /// atoms and keymap of `conn`
+    pub fn create(path: &Path, conn: &RustConnection, screen: usize, display: &str, config: &Config) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create event recording {:?}", path))?;
+        let mut writer = BufWriter::new(file);
+
+        let mut extensions = Vec::new();
+        for name in RECORDED_EXTENSIONS {
+            if let Ok(Some(info)) = conn.extension_information(name) {
+                extensions.push(RecordedExtension {
+                    name: (*name).to_string(),
+                    major_opcode: info.major_opcode,
+                    first_event: info.first_event,
+                    first_error: info.first_error,
+                });
+            }
+        }
+
+        let header = RecordingHeader {
+            version: FORMAT_VERSION,
+            display: display.to_string(),
+            extensions,
+            setup: mock::setup_bytes(conn.setup()),
+            screen,
+            atoms: server_atoms(conn)?,
+            keyboard: keyboard_mapping(conn)?,
+            config: config.clone(),
+        };
+        serde_json::to_writer(&mut writer, &header)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+
+        info!("Recording events to {:?}", path);
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+            count: 0,
+        })
+    }
+
+    /// Append an input to the recording
+    pub fn record(&mut self, input: RecordedInput) -> Result<()> {
+        let entry = RecordedEntry {
+            t_ms: self.start.elapsed().as_millis() as u64,
+            input,
+        };
+        serde_json::to_writer(&mut self.writer, &entry)?;
+        self.writer.write_all(b"\n")?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Append a raw X11 event to the recording
+    pub fn record_x11(&mut self, raw: &[u8]) -> Result<()> {
+        self.record(RecordedInput::X11 { raw: raw.to_vec() })
+    }
+
+    /// Flush buffered entries to disk (called once per event batch)
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for EventRecorder {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+        debug!("Event recorder closed after {} entries", self.count);
+    }
+}
+
+/// Atoms are handed out in sequence, so look names up from 1 until one is missing
+fn server_atoms(conn: &RustConnection) -> Result<Vec<(u32, String)>> {
+    let mut atoms = Vec::new();
+    for first in (1..).step_by(ATOM_BATCH as usize) {
+        let cookies = (first..first + ATOM_BATCH)
+            .map(|atom| conn.get_atom_name(atom))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut missing = false;
+        for (atom, cookie) in (first..).zip(cookies) {
+            match cookie.reply() {
+                Ok(reply) => atoms.push((atom, String::from_utf8_lossy(&reply.name).into_owned())),
+                Err(_) => missing = true,
+            }
+        }
+        if missing {
+            break;
+        }
+    }
+    Ok(atoms)
+}
+
+fn keyboard_mapping(conn: &RustConnection) -> Result<RecordedKeyboard> {
+    let setup = conn.setup();
+    let count = setup.max_keycode - setup.min_keycode + 1;
+    let keyboard = conn.get_keyboard_mapping(setup.min_keycode, count)?.reply()?;
+    let modifiers = conn.get_modifier_mapping()?.reply()?;
+    Ok(RecordedKeyboard {
+        keysyms_per_keycode: keyboard.keysyms_per_keycode,
+        keysyms: keyboard.keysyms,
+        keycodes_per_modifier: modifiers.keycodes_per_modifier(),
+        modifier_keycodes: modifiers.keycodes,
+    })
+}
+
+/// A recording loaded for replay
+pub struct EventReplay {
+    pub header: RecordingHeader,
+    pub entries: Vec<RecordedEntry>,
+    /// Mock server the replay runs against, once connected
+    server: Option<mock::MockServer>,
+}
+
+impl EventReplay {
+    /// Load a recording from disk
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open event recording {:?}", path))?;
+        let mut lines = BufReader::new(file).lines();
+
+        let header_line = lines.next().context("Event recording is empty")??;
+        let header: RecordingHeader = serde_json::from_str(&header_line)
+            .context("Invalid event recording header")?;
+        if header.version != FORMAT_VERSION {
+            anyhow::bail!(
+                "Unsupported event recording version {} (expected {})",
+                header.version,
+                FORMAT_VERSION
+            );
+        }
+
+        let mut entries = Vec::new();
+        for (index, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: RecordedEntry = serde_json::from_str(&line)
+                .with_context(|| format!("Invalid event recording entry at line {}", index + 2))?;
+            entries.push(entry);
+        }
+
+        info!(
+            "Loaded {} recorded events from {:?} (recorded on {})",
+            entries.len(),
+            path,
+            header.display
+        );
+        Ok(Self { header, entries, server: None })
+    }
+
+    /// Start the mock X server for this recording and connect to it
+    pub fn connect(&mut self) -> Result<RustConnection> {
+        let (conn, server) = mock::MockServer::connect(&self.header)?;
+        self.server = Some(server);
+        Ok(conn)
+    }
+
+    /// Let the mock server see a replayed event before the WM handles it
+    pub fn observe(&self, event: &Event) {
+        if let Some(ref server) = self.server {
+            server.observe(event);
+        }
+    }
+
+    /// Decode a recorded raw X11 event using the recorded extension opcodes
+    pub fn parse_x11(&self, raw: &[u8]) -> Result<Event> {
+        Ok(Event::parse(raw, &RecordedExtensions(&self.header.extensions))?)
+    }
+}
+
+/// `ExtInfoProvider` backed by the opcodes stored in a recording header
+struct RecordedExtensions<'a>(&'a [RecordedExtension]);
+
+impl RecordedExtensions<'_> {
+    fn info(ext: &RecordedExtension) -> (&str, ExtensionInformation) {
+        (
+            ext.name.as_str(),
+            ExtensionInformation {
+                major_opcode: ext.major_opcode,
+                first_event: ext.first_event,
+                first_error: ext.first_error,
+            },
+        )
+    }
+}
+
+impl ExtInfoProvider for RecordedExtensions<'_> {
+    fn get_from_major_opcode(&self, major_opcode: u8) -> Option<(&str, ExtensionInformation)> {
+        self.0
+            .iter()
+            .find(|ext| ext.major_opcode == major_opcode)
+            .map(Self::info)
+    }
+
+    fn get_from_event_code(&self, event_code: u8) -> Option<(&str, ExtensionInformation)> {
+        self.0
+            .iter()
+            .filter(|ext| ext.first_event != 0 && ext.first_event <= event_code)
+            .max_by_key(|ext| ext.first_event)
+            .map(Self::info)
+    }
+
+    fn get_from_error_code(&self, error_code: u8) -> Option<(&str, ExtensionInformation)> {
+        self.0
+            .iter()
+            .filter(|ext| ext.first_error != 0 && ext.first_error <= error_code)
+            .max_by_key(|ext| ext.first_error)
+            .map(Self::info)
+    }
+}
@@ -4,7 +4,7 @@
 //! window manager state and compositor state, eliminating the need for IPC.
 
 /// Window geometry
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct Geometry {
     pub x: i32,
     pub y: i32,
@@ -23,9 +23,7 @@ impl Geometry {
 pub struct WindowFrame {
     pub frame: u32,
     pub titlebar: u32,
-    pub close_button: u32,
-    pub maximize_button: u32,
-    pub minimize_button: u32,
+    pub buttons: Vec<crate::wm::decorations::FrameButton>,
 }
 
 /// Window flags
@@ -15,6 +15,9 @@ pub struct LogoutDialog {
     /// Is dialog visible?
     pub visible: bool,
     
+    /// Logout was confirmed (taken by the main loop)
+    pub logout_requested: bool,
+    
     /// Dialog position (centered)
     dialog_x: f32,
     dialog_y: f32,
@@ -37,6 +40,7 @@ impl LogoutDialog {
         // Will be initialized when shown
         Self {
             visible: false,
+            logout_requested: false,
             dialog_x: 0.0,
             dialog_y: 0.0,
             logout_button_x: 0.0,
@@ -87,7 +91,7 @@ impl LogoutDialog {
     }
     
     /// Handle mouse click
-    pub async fn handle_click(&mut self, x: i16, y: i16, power: &Option<crate::dbus::power::PowerService>) -> Result<bool> {
+    pub fn handle_click(&mut self, x: i16, y: i16) -> Result<bool> {
         if !self.visible {
             return Ok(false);
         }
@@ -105,21 +109,9 @@ impl LogoutDialog {
             BUTTON_HEIGHT,
         ) {
             tracing::info!("Logout button clicked");
-            if let Some(power_svc) = power {
-                // Try to use D-Bus power service to shutdown the machine
-                // In a real desktop, we'd probably want a confirmation or options (Reboot/Suspend)
-                // For this MVP, let's map "Logout" to "Shutdown" if power management is available,
-                // or just "Exit Area" if we want to be safe.
-                
-                // Let's implement real shutdown for "Desktop Integration" proof
-                if let Err(e) = power_svc.shutdown().await {
-                    tracing::error!("Failed to shutdown via D-Bus: {}", e);
-                    // Fallback to exit
-                    std::process::exit(1);
-                }
-            } else {
-                 std::process::exit(0);
-            }
+            // The main loop picks this up and runs the end-session flow
+            self.logout_requested = true;
+            self.hide();
             return Ok(true);
         }
         
@@ -152,11 +144,6 @@ impl LogoutDialog {
         Ok(false)
     }
     
-    /// Perform logout (Unused for now, logic moved to handle_click)
-    fn _perform_logout(&self) -> Result<()> {
-        Ok(())
-    }
-    
     
     /// Render the dialog using the renderer
     pub fn render(&self, renderer: &crate::compositor::renderer::Renderer, screen_width: f32, screen_height: f32) {
@@ -28,10 +28,10 @@ impl Shell {
     }
     
     /// Handle mouse click
-    pub async fn handle_click(&mut self, x: i16, y: i16, power: &Option<crate::dbus::power::PowerService>) -> Result<()> {
+    pub fn handle_click(&mut self, x: i16, y: i16) -> Result<()> {
         // Check if click is on logout dialog first (it's on top)
         if self.logout_dialog.visible {
-            if self.logout_dialog.handle_click(x, y, power).await? {
+            if self.logout_dialog.handle_click(x, y)? {
                 return Ok(());
             }
         }
@@ -0,0 +1,224 @@
+//! Supervision of session components
+//!
+//! Starts the programs of the `[[components]]` config tables (the
+//! area-shell panel, helpers split out of the window manager) with the
+//! session environment, and restarts them with backoff when they crash, so
+//! a shell crash doesn't leave the user without a panel:
+//!
+//! ```toml
+//! [[components]]
+//! name = "shell"
+//! exec = "area-shell"
+//! ```
+//!
+//! Every state change goes to the main loop, which reports it as
+//! `WmEvent::ComponentStatusChanged`. A component that keeps crashing soon
+//! after starting is given up on (`ComponentState::Failed`) until the next
+//! config reload. A component runs in a process group of its own, and the
+//! whole group is killed when the component exits, is stopped, or the
+//! window manager exits, so nothing its shell started is left behind.
+
+use std::collections::HashMap;
+use std::os::unix::process::ExitStatusExt;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use area_ipc::{ComponentState, ComponentStatus};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::config::ComponentConfig;
+use crate::environment::SessionEnvironment;
+
+/// Wait before the first restart; doubles with every quick crash
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A component running this long before exiting starts over at `INITIAL_BACKOFF`
+const STABLE_RUN: Duration = Duration::from_secs(60);
+/// Quick crashes in a row to give up after
+const MAX_QUICK_CRASHES: u32 = 5;
+
+/// Running components and their last reported state
+pub struct Supervisor {
+    /// Config and monitoring task of each component
+    tasks: HashMap<String, (ComponentConfig, JoinHandle<()>)>,
+    /// Last state of each component, in config order
+    statuses: Vec<ComponentStatus>,
+    /// State changes from the monitoring tasks
+    tx: mpsc::UnboundedSender<ComponentStatus>,
+}
+
+impl Supervisor {
+    /// Start the components; their state changes arrive on the receiver
+    pub fn new(components: &[ComponentConfig], environment: &SessionEnvironment) -> (Self, mpsc::UnboundedReceiver<ComponentStatus>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut supervisor = Self { tasks: HashMap::new(), statuses: Vec::new(), tx };
+        supervisor.set_config(components, environment);
+        (supervisor, rx)
+    }
+
+    /// Start added components and stop removed or changed ones (after a
+    /// config reload); returns the states of the stopped ones. Running
+    /// components keep their environment until they restart.
+    pub fn set_config(&mut self, components: &[ComponentConfig], environment: &SessionEnvironment) -> Vec<ComponentStatus> {
+        let mut tasks = HashMap::new();
+        let mut statuses = Vec::new();
+        for component in components {
+            if tasks.contains_key(&component.name) {
+                warn!("Ignoring duplicate component {}", component.name);
+                continue;
+            }
+            let previous = self.statuses.iter().find(|status| status.name == component.name);
+            // Unchanged and still supervised: keep it running
+            if let Some((config, task)) = self.tasks.remove(&component.name) {
+                if config == *component && !task.is_finished() {
+                    tasks.insert(component.name.clone(), (config, task));
+                    statuses.extend(previous.cloned());
+                    continue;
+                }
+                task.abort();
+            }
+            info!("Starting component {} ({})", component.name, component.exec);
+            let task = tokio::spawn(supervise(component.clone(), environment.clone(), self.tx.clone()));
+            tasks.insert(component.name.clone(), (component.clone(), task));
+            statuses.push(ComponentStatus {
+                name: component.name.clone(),
+                state: ComponentState::Running,
+                pid: None,
+                restarts: 0,
+                exit_code: None,
+            });
+        }
+        let mut stopped = Vec::new();
+        for (name, (_, task)) in self.tasks.drain() {
+            info!("Stopping component {}", name);
+            // Dropping the child kills it
+            task.abort();
+            let restarts = self.statuses.iter().find(|status| status.name == name).map_or(0, |status| status.restarts);
+            stopped.push(ComponentStatus { name, state: ComponentState::Stopped, pid: None, restarts, exit_code: None });
+        }
+        self.tasks = tasks;
+        self.statuses = statuses;
+        stopped
+    }
+
+    /// Record a state change from a monitoring task; false if the component
+    /// was removed meanwhile (the change is stale)
+    pub fn update(&mut self, status: &ComponentStatus) -> bool {
+        match self.statuses.iter_mut().find(|s| s.name == status.name) {
+            Some(current) => {
+                *current = status.clone();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Every supervised component's state, in config order
+    pub fn statuses(&self) -> &[ComponentStatus] {
+        &self.statuses
+    }
+}
+
+/// Run a component, restarting it after crashes until it exits cleanly or
+/// crashes too often
+async fn supervise(component: ComponentConfig, environment: SessionEnvironment, tx: mpsc::UnboundedSender<ComponentStatus>) {
+    let mut status = ComponentStatus {
+        name: component.name.clone(),
+        state: ComponentState::Running,
+        pid: None,
+        restarts: 0,
+        exit_code: None,
+    };
+    let mut backoff = INITIAL_BACKOFF;
+    let mut quick_crashes = 0;
+    loop {
+        let started = Instant::now();
+        let reason = match run(&component, &environment, &mut status, &tx).await {
+            Ok(exit) if exit.success() => {
+                info!("Component {} exited", component.name);
+                status.state = ComponentState::Stopped;
+                status.pid = None;
+                status.exit_code = Some(0);
+                let _ = tx.send(status);
+                return;
+            }
+            Ok(exit) => {
+                status.exit_code = exit.code();
+                match exit.signal() {
+                    Some(signal) => format!("killed by signal {}", signal),
+                    None => format!("exited with status {}", exit.code().unwrap_or(-1)),
+                }
+            }
+            Err(e) => {
+                status.exit_code = None;
+                format!("{:#}", e)
+            }
+        };
+        status.pid = None;
+        if !component.restart {
+            warn!("Component {} {}", component.name, reason);
+            status.state = ComponentState::Failed;
+            let _ = tx.send(status);
+            return;
+        }
+        if started.elapsed() >= STABLE_RUN {
+            backoff = INITIAL_BACKOFF;
+            quick_crashes = 0;
+        }
+        quick_crashes += 1;
+        if quick_crashes >= MAX_QUICK_CRASHES {
+            warn!("Component {} {}; giving up after {} restarts", component.name, reason, status.restarts);
+            status.state = ComponentState::Failed;
+            let _ = tx.send(status);
+            return;
+        }
+        warn!("Component {} {}; restarting in {:?}", component.name, reason, backoff);
+        status.state = ComponentState::Restarting;
+        let _ = tx.send(status.clone());
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+        status.restarts += 1;
+    }
+}
+
+/// Start a component and wait for it to exit
+async fn run(
+    component: &ComponentConfig,
+    environment: &SessionEnvironment,
+    status: &mut ComponentStatus,
+    tx: &mpsc::UnboundedSender<ComponentStatus>,
+) -> Result<std::process::ExitStatus> {
+    let mut child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(&component.exec)
+        .envs(environment.vars())
+        .stdin(Stdio::null())
+        .process_group(0)
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("failed to run {}", component.exec))?;
+    let _group = child.id().map(ProcessGroup);
+    debug!("Component {} running as {:?}", component.name, child.id());
+    status.state = ComponentState::Running;
+    status.pid = child.id();
+    status.exit_code = None;
+    let _ = tx.send(status.clone());
+    child.wait().await.context("failed to wait for the component")
+}
+
+/// Terminates a component's process group when dropped: `kill_on_drop`
+/// only reaches the shell, not the programs it started
+struct ProcessGroup(u32);
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        // Fails harmlessly when everyone in the group already exited
+        let _ = std::process::Command::new("kill")
+            .args(["-TERM", "--", &format!("-{}", self.0)])
+            .stderr(Stdio::null())
+            .status();
+    }
+}
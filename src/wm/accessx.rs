@@ -0,0 +1,185 @@
+//! Keyboard accessibility (XKB AccessX)
+//!
+//! Sticky, slow, bounce and mouse keys are X server keyboard controls, so
+//! other tools (xkbset, Shift pressed five times) can change them as well.
+//! The WM applies the configured ones, toggles them on request and watches
+//! ControlsNotify so every change is reported to the shell.
+
+use anyhow::Result;
+use tracing::{debug, info, warn};
+use x11rb::protocol::xkb::{self, BoolCtrl, ConnectionExt as _};
+use x11rb::rust_connection::RustConnection;
+
+use crate::config::AccessibilityConfig;
+
+/// The core keyboard
+const CORE_KBD: xkb::DeviceSpec = 256;
+
+/// Enabled AccessX features
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AccessibilityState {
+    pub sticky_keys: bool,
+    pub slow_keys: bool,
+    pub bounce_keys: bool,
+    pub mouse_keys: bool,
+}
+
+impl AccessibilityState {
+    fn from_controls(controls: BoolCtrl) -> Self {
+        Self {
+            sticky_keys: controls.contains(BoolCtrl::STICKY_KEYS),
+            slow_keys: controls.contains(BoolCtrl::SLOW_KEYS),
+            bounce_keys: controls.contains(BoolCtrl::BOUNCE_KEYS),
+            mouse_keys: controls.contains(BoolCtrl::MOUSE_KEYS),
+        }
+    }
+
+    pub fn event(self) -> area_ipc::WmEvent {
+        area_ipc::WmEvent::AccessibilityChanged {
+            sticky_keys: self.sticky_keys,
+            slow_keys: self.slow_keys,
+            bounce_keys: self.bounce_keys,
+            mouse_keys: self.mouse_keys,
+        }
+    }
+}
+
+/// AccessX controls of the core keyboard
+#[derive(Debug, Default)]
+pub struct AccessX {
+    /// XKB is present and ControlsNotify selected
+    available: bool,
+    /// Last state reported
+    state: AccessibilityState,
+}
+
+impl AccessX {
+    /// Set up XKB and watch the AccessX controls
+    pub fn init(&mut self, conn: &RustConnection) -> Result<()> {
+        let reply = conn.xkb_use_extension(1, 0)?.reply()?;
+        if !reply.supported {
+            warn!("XKB {}.{} unsupported, keyboard accessibility disabled", reply.server_major, reply.server_minor);
+            return Ok(());
+        }
+        // Turning controls on or off changes CONTROLS_ENABLED
+        let controls = xkb::Control::CONTROLS_ENABLED;
+        let details = xkb::SelectEventsAux {
+            controls_notify: Some(xkb::SelectEventsAuxControlsNotify { affect_ctrls: controls, ctrl_details: controls }),
+            ..Default::default()
+        };
+        conn.xkb_select_events(
+            CORE_KBD,
+            xkb::EventType::from(0u16),
+            xkb::EventType::from(0u16),
+            xkb::MapPart::from(0u16),
+            xkb::MapPart::from(0u16),
+            &details,
+        )?;
+        self.state = AccessibilityState::from_controls(conn.xkb_get_controls(CORE_KBD)?.reply()?.enabled_controls);
+        self.available = true;
+        Ok(())
+    }
+
+    /// Apply the features set in the configuration (unset ones are left alone)
+    pub fn apply(&mut self, conn: &RustConnection, config: &AccessibilityConfig) -> Result<()> {
+        if !self.available {
+            return Ok(());
+        }
+        let mut affect = BoolCtrl::from(0u32);
+        let mut enabled = BoolCtrl::from(0u32);
+        for (setting, control) in [
+            (config.sticky_keys, BoolCtrl::STICKY_KEYS),
+            (config.slow_keys, BoolCtrl::SLOW_KEYS),
+            (config.bounce_keys, BoolCtrl::BOUNCE_KEYS),
+            (config.mouse_keys, BoolCtrl::MOUSE_KEYS),
+        ] {
+            if let Some(on) = setting {
+                affect |= control;
+                if on {
+                    enabled |= control;
+                }
+            }
+        }
+        self.set_controls(conn, affect, enabled, config.slow_keys_delay_ms, config.bounce_keys_delay_ms)
+    }
+
+    /// Turn one feature on or off
+    pub fn set_feature(&mut self, conn: &RustConnection, feature: area_ipc::AccessibilityFeature, on: bool) -> Result<()> {
+        if !self.available {
+            anyhow::bail!("XKB is not available");
+        }
+        let control = match feature {
+            area_ipc::AccessibilityFeature::StickyKeys => BoolCtrl::STICKY_KEYS,
+            area_ipc::AccessibilityFeature::SlowKeys => BoolCtrl::SLOW_KEYS,
+            area_ipc::AccessibilityFeature::BounceKeys => BoolCtrl::BOUNCE_KEYS,
+            area_ipc::AccessibilityFeature::MouseKeys => BoolCtrl::MOUSE_KEYS,
+        };
+        info!("{:?} {}", feature, if on { "on" } else { "off" });
+        let enabled = if on { control } else { BoolCtrl::from(0u32) };
+        self.set_controls(conn, control, enabled, None, None)
+    }
+
+    /// Change enabled controls and delays, keeping every other keyboard control
+    fn set_controls(
+        &mut self,
+        conn: &RustConnection,
+        affect: BoolCtrl,
+        enabled: BoolCtrl,
+        slow_keys_delay_ms: Option<u16>,
+        bounce_keys_delay_ms: Option<u16>,
+    ) -> Result<()> {
+        let current = conn.xkb_get_controls(CORE_KBD)?.reply()?;
+        // A control bit in `change_controls` marks its parameters as changed
+        let mut change = xkb::Control::CONTROLS_ENABLED;
+        if slow_keys_delay_ms.is_some() {
+            change |= xkb::Control::from(u32::from(BoolCtrl::SLOW_KEYS));
+        }
+        if bounce_keys_delay_ms.is_some() {
+            change |= xkb::Control::from(u32::from(BoolCtrl::BOUNCE_KEYS));
+        }
+        conn.xkb_set_controls(
+            CORE_KBD,
+            0u16.into(),
+            0u16.into(),
+            0u16.into(),
+            0u16.into(),
+            0u16.into(),
+            0u16.into(),
+            0u16.into(),
+            0u16.into(),
+            current.mouse_keys_dflt_btn,
+            current.groups_wrap,
+            current.access_x_option,
+            affect,
+            enabled,
+            change,
+            current.repeat_delay,
+            current.repeat_interval,
+            slow_keys_delay_ms.unwrap_or(current.slow_keys_delay),
+            bounce_keys_delay_ms.unwrap_or(current.debounce_delay),
+            current.mouse_keys_delay,
+            current.mouse_keys_interval,
+            current.mouse_keys_time_to_max,
+            current.mouse_keys_max_speed,
+            current.mouse_keys_curve,
+            current.access_x_timeout,
+            current.access_x_timeout_mask,
+            current.access_x_timeout_values,
+            current.access_x_timeout_options_mask,
+            current.access_x_timeout_options_values,
+            &current.per_key_repeat,
+        )?;
+        Ok(())
+    }
+
+    /// Account a ControlsNotify; returns the new state if a feature changed
+    pub fn controls_changed(&mut self, event: &xkb::ControlsNotifyEvent) -> Option<AccessibilityState> {
+        let state = AccessibilityState::from_controls(event.enabled_controls);
+        if state == self.state {
+            return None;
+        }
+        debug!("Keyboard accessibility now {:?}", state);
+        self.state = state;
+        Some(state)
+    }
+}
@@ -0,0 +1,50 @@
+//! Activation Token Module
+//!
+//! Focus stealing prevention in the spirit of xdg-activation: a launcher
+//! asks for a token (`RequestActivationToken`) and starts the application
+//! with it in `DESKTOP_STARTUP_ID` (and `XDG_ACTIVATION_TOKEN`). Toolkits
+//! copy that to `_NET_STARTUP_ID` on their windows, so a window showing a
+//! fresh token was started by the user and may take focus; any other window
+//! that tries only gets `_NET_WM_STATE_DEMANDS_ATTENTION`.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use tracing::debug;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _};
+use x11rb::rust_connection::RustConnection;
+
+/// Issued tokens not used yet
+#[derive(Debug, Default)]
+pub struct ActivationTokens {
+    /// Token and when it was issued
+    issued: HashMap<String, Instant>,
+    counter: u32,
+}
+
+impl ActivationTokens {
+    /// A new token, valid once
+    pub fn issue(&mut self) -> String {
+        self.counter = self.counter.wrapping_add(1);
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+        let token = format!("area-{}-{}-{:08x}", std::process::id(), self.counter, nanos);
+        self.issued.insert(token.clone(), Instant::now());
+        debug!("Issued activation token {}", token);
+        token
+    }
+
+    /// Use up a token; true if it was issued less than `lifetime` ago
+    pub fn consume(&mut self, token: &str, lifetime: Duration) -> bool {
+        self.issued.retain(|_, issued| issued.elapsed() < lifetime);
+        self.issued.remove(token).is_some()
+    }
+}
+
+/// A window's `_NET_STARTUP_ID`, if set
+pub fn startup_id(conn: &RustConnection, window: u32) -> Result<Option<String>> {
+    let atom = crate::wm::atom_cache::intern(conn, "_NET_STARTUP_ID")?;
+    let reply = conn.get_property(false, window, atom, AtomEnum::ANY, 0, 256)?.reply()?;
+    let id = String::from_utf8_lossy(&reply.value).trim_end_matches('\0').to_string();
+    Ok((!id.is_empty()).then_some(id))
+}
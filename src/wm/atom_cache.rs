@@ -0,0 +1,150 @@
+//! Process-wide atom cache
+//!
+//! Atoms never change for the lifetime of the X server, so each name is
+//! interned once and shared by every subsystem (WM, compositor, input).
+//! The EWMH/ICCCM set is interned at startup in a single round trip; other
+//! names are interned on first use.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+use anyhow::{Context, Result};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{Atom, ConnectionExt};
+
+static CACHE: LazyLock<Mutex<HashMap<String, Atom>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Atoms interned at startup
+pub const PRELOAD: &[&str] = &[
+    // EWMH root window properties
+    "_NET_SUPPORTED",
+    "_NET_CLIENT_LIST",
+    "_NET_CLIENT_LIST_STACKING",
+    "_NET_NUMBER_OF_DESKTOPS",
+    "_NET_DESKTOP_GEOMETRY",
+    "_NET_DESKTOP_VIEWPORT",
+    "_NET_CURRENT_DESKTOP",
+    "_NET_DESKTOP_NAMES",
+    "_NET_ACTIVE_WINDOW",
+    "_NET_WORKAREA",
+    "_NET_SUPPORTING_WM_CHECK",
+    "_NET_SHOWING_DESKTOP",
+    // EWMH root messages
+    "_NET_CLOSE_WINDOW",
+    "_NET_MOVERESIZE_WINDOW",
+    "_NET_WM_MOVERESIZE",
+    "_NET_REQUEST_FRAME_EXTENTS",
+    // EWMH application window properties
+    "_NET_WM_NAME",
+    "_NET_WM_VISIBLE_NAME",
+    "_NET_WM_ICON_NAME",
+    "_NET_WM_DESKTOP",
+    "_NET_WM_WINDOW_TYPE",
+    "_NET_WM_WINDOW_TYPE_DESKTOP",
+    "_NET_WM_WINDOW_TYPE_DOCK",
+    "_NET_WM_WINDOW_TYPE_TOOLBAR",
+    "_NET_WM_WINDOW_TYPE_MENU",
+    "_NET_WM_WINDOW_TYPE_UTILITY",
+    "_NET_WM_WINDOW_TYPE_SPLASH",
+    "_NET_WM_WINDOW_TYPE_DIALOG",
+    "_NET_WM_WINDOW_TYPE_DROPDOWN_MENU",
+    "_NET_WM_WINDOW_TYPE_POPUP_MENU",
+    "_NET_WM_WINDOW_TYPE_TOOLTIP",
+    "_NET_WM_WINDOW_TYPE_NOTIFICATION",
+    "_NET_WM_WINDOW_TYPE_COMBO",
+    "_NET_WM_WINDOW_TYPE_DND",
+    "_NET_WM_WINDOW_TYPE_NORMAL",
+    "_NET_WM_STATE",
+    "_NET_WM_STATE_MODAL",
+    "_NET_WM_STATE_STICKY",
+    "_NET_WM_STATE_MAXIMIZED_VERT",
+    "_NET_WM_STATE_MAXIMIZED_HORZ",
+    "_NET_WM_STATE_SHADED",
+    "_NET_WM_STATE_SKIP_TASKBAR",
+    "_NET_WM_STATE_SKIP_PAGER",
+    "_NET_WM_STATE_HIDDEN",
+    "_NET_WM_STATE_FULLSCREEN",
+    "_NET_WM_STATE_ABOVE",
+    "_NET_WM_STATE_BELOW",
+    "_NET_WM_STATE_DEMANDS_ATTENTION",
+    "_NET_WM_STATE_FOCUSED",
+    "_NET_WM_ALLOWED_ACTIONS",
+    "_NET_WM_ACTION_MOVE",
+    "_NET_WM_ACTION_RESIZE",
+    "_NET_WM_ACTION_MINIMIZE",
+    "_NET_WM_ACTION_SHADE",
+    "_NET_WM_ACTION_STICK",
+    "_NET_WM_ACTION_MAXIMIZE_HORZ",
+    "_NET_WM_ACTION_MAXIMIZE_VERT",
+    "_NET_WM_ACTION_FULLSCREEN",
+    "_NET_WM_ACTION_CHANGE_DESKTOP",
+    "_NET_WM_ACTION_CLOSE",
+    "_NET_WM_STRUT",
+    "_NET_WM_STRUT_PARTIAL",
+    "_NET_WM_OPAQUE_REGION",
+    "_NET_WM_ICON",
+    "_NET_WM_PID",
+    "_NET_WM_USER_TIME",
+    "_NET_WM_PING",
+    "_NET_WM_SYNC_REQUEST",
+    "_NET_WM_FULLSCREEN_MONITORS",
+    "_NET_WM_BYPASS_COMPOSITOR",
+    "_NET_WM_WINDOW_OPACITY",
+    "_NET_FRAME_EXTENTS",
+    // ICCCM
+    "WM_PROTOCOLS",
+    "WM_DELETE_WINDOW",
+    "WM_TAKE_FOCUS",
+    "WM_STATE",
+    "WM_CLASS",
+    "WM_CHANGE_STATE",
+    "WM_CLIENT_MACHINE",
+    "WM_NORMAL_HINTS",
+    "WM_SIZE_HINTS",
+    "WM_WINDOW_ROLE",
+    // Other conventions
+    "UTF8_STRING",
+    "_MOTIF_WM_HINTS",
+    "_GTK_SHOW_WINDOW_MENU",
+    "GTK_SHOW_WINDOW_MENU",
+    "_XROOTPMAP_ID",
+    "ESETROOT_PMAP_ID",
+];
+
+/// Intern all `names` not cached yet, sending every request before waiting for a reply
+pub fn intern_all<C: Connection>(conn: &C, names: &[&str]) -> Result<()> {
+    let missing: Vec<&str> = {
+        let cache = CACHE.lock().unwrap();
+        names.iter().copied().filter(|name| !cache.contains_key(*name)).collect()
+    };
+    let cookies = missing
+        .iter()
+        .map(|name| conn.intern_atom(false, name.as_bytes()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut interned = Vec::with_capacity(cookies.len());
+    for (name, cookie) in missing.iter().zip(cookies) {
+        let atom = cookie.reply().with_context(|| format!("Failed to intern {}", name))?.atom;
+        interned.push((name.to_string(), atom));
+    }
+    CACHE.lock().unwrap().extend(interned);
+    Ok(())
+}
+
+/// The atom for `name`, interned on first use
+pub fn intern<C: Connection>(conn: &C, name: &str) -> Result<Atom> {
+    if let Some(atom) = lookup(name) {
+        return Ok(atom);
+    }
+    let atom = conn
+        .intern_atom(false, name.as_bytes())?
+        .reply()
+        .with_context(|| format!("Failed to intern {}", name))?
+        .atom;
+    CACHE.lock().unwrap().insert(name.to_string(), atom);
+    Ok(atom)
+}
+
+/// The atom for `name` if it was already interned
+pub fn lookup(name: &str) -> Option<Atom> {
+    CACHE.lock().unwrap().get(name).copied()
+}
@@ -126,8 +126,8 @@ pub struct Client {
     /// Fullscreen monitors [top, bottom, left, right]
     pub fullscreen_monitors: Option<[u32; 4]>,
     
-    /// Frame extents [left, right, top, bottom]
-    pub frame_extents: [i32; 4],
+    /// Decoration sizes (`FrameMetrics::NONE` when undecorated)
+    pub frame_metrics: crate::wm::frame_metrics::FrameMetrics,
     
     /// Tile mode
     pub tile_mode: TilePosition,
@@ -181,28 +181,7 @@ pub struct Client {
     pub mwm_hints: Option<MwmHints>,
 }
 
-/// Size hints (XSizeHints equivalent)
-#[derive(Debug, Clone)]
-pub struct SizeHints {
-    pub flags: u32,
-    pub x: i32,
-    pub y: i32,
-    pub width: u32,
-    pub height: u32,
-    pub min_width: u32,
-    pub min_height: u32,
-    pub max_width: u32,
-    pub max_height: u32,
-    pub width_inc: u32,
-    pub height_inc: u32,
-    pub min_aspect_num: u32,
-    pub min_aspect_den: u32,
-    pub max_aspect_num: u32,
-    pub max_aspect_den: u32,
-    pub base_width: u32,
-    pub base_height: u32,
-    pub win_gravity: u8,
-}
+pub use crate::wm::hints::SizeHints;
 
 /// WM hints (XWMHints equivalent)
 #[derive(Debug, Clone)]
@@ -225,6 +204,14 @@ pub struct ClassHint {
     pub res_class: String,
 }
 
+impl ClassHint {
+    /// Parse a WM_CLASS property value (instance and class, NUL separated)
+    pub fn parse(value: &[u8]) -> Option<Self> {
+        let mut parts = value.split(|&b| b == 0).map(|s| String::from_utf8_lossy(s).into_owned());
+        Some(Self { res_name: parts.next()?, res_class: parts.next()? })
+    }
+}
+
 /// MWM hints (Motif Window Manager hints)
 #[derive(Debug, Clone)]
 pub struct MwmHints {
@@ -276,7 +263,7 @@ impl Client {
             wm_flags: WmFlags::empty(),
             xfwm_flags: XfwmFlags::default(),
             fullscreen_monitors: None,
-            frame_extents: [0; 4],
+            frame_metrics: crate::wm::frame_metrics::FrameMetrics::NONE,
             tile_mode: TilePosition::None,
             opacity: 0xFFFFFFFF, // Opaque
             opacity_applied: 0xFFFFFFFF,
@@ -332,23 +319,20 @@ impl Client {
         self.flags.contains(ClientFlags::STICKY) || self.win_workspace == 0xFFFFFFFF
     }
     
-    /// Calculate frame geometry
+    /// Client size closest to `width`x`height` its WM_NORMAL_HINTS allow
+    pub fn constrain_size(&self, width: u32, height: u32) -> (u32, u32) {
+        match &self.size_hints {
+            Some(hints) => hints.constrain(width, height),
+            None => (width, height),
+        }
+    }
+    
+    /// Calculate frame geometry (the frame window, inside its border)
     pub fn frame_geometry(&self) -> Geometry {
-        if self.is_fullscreen() {
+        if self.is_fullscreen() || self.frame.is_none() {
             return self.geometry;
         }
-        
-        if let Some(_frame) = &self.frame {
-            // Frame extends beyond client by frame extents
-            Geometry {
-                x: self.geometry.x - self.frame_extents[0],
-                y: self.geometry.y - self.frame_extents[2],
-                width: self.geometry.width + (self.frame_extents[0] + self.frame_extents[1]) as u32,
-                height: self.geometry.height + (self.frame_extents[2] + self.frame_extents[3]) as u32,
-            }
-        } else {
-            self.geometry
-        }
+        self.frame_metrics.frame_geometry(self.geometry)
     }
 }
 
@@ -391,6 +375,11 @@ impl Client {
         self.xfwm_flags.contains(XfwmFlags::FOCUS)
     }
     
+    /// Check if the window requests attention (WM_HINTS urgency or DEMANDS_ATTENTION)
+    pub fn is_urgent(&self) -> bool {
+        self.wm_flags.contains(WmFlags::URGENT) || self.flags.contains(ClientFlags::DEMANDS_ATTENTION)
+    }
+    
     /// Get frame window ID (for compatibility)
     pub fn get_frame_window(&self) -> Option<u32> {
         self.frame.as_ref().map(|f| f.frame)
@@ -78,6 +78,7 @@ bitflags! {
         const XSYNC_ENABLED        = 1 << 23;
         const XSYNC_EXT_COUNTER    = 1 << 24;
         const RESTORE_SIZE_POS     = 1 << 25;
+        const INHIBIT_SHORTCUTS    = 1 << 26;
     }
 }
 
@@ -142,20 +143,58 @@ impl WindowType {
             Self::Menu
         } else if atom == atoms._net_wm_window_type_notification {
             Self::Notification
+        } else if atom == atoms._net_wm_window_type_dropdown_menu {
+            Self::DropdownMenu
+        } else if atom == atoms._net_wm_window_type_popup_menu {
+            Self::PopupMenu
+        } else if atom == atoms._net_wm_window_type_tooltip {
+            Self::Tooltip
+        } else if atom == atoms._net_wm_window_type_combo {
+            Self::Combo
+        } else if atom == atoms._net_wm_window_type_dnd {
+            Self::Dnd
         } else {
             Self::Normal
         }
     }
+
+    /// EWMH type name without the `_NET_WM_WINDOW_TYPE_` prefix, lowercase
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Desktop => "desktop",
+            Self::Dock => "dock",
+            Self::Dialog | Self::ModalDialog => "dialog",
+            Self::Toolbar => "toolbar",
+            Self::Menu => "menu",
+            Self::Utility => "utility",
+            Self::Splashscreen => "splash",
+            Self::Notification => "notification",
+            Self::DropdownMenu => "dropdown_menu",
+            Self::PopupMenu => "popup_menu",
+            Self::Tooltip => "tooltip",
+            Self::Combo => "combo",
+            Self::Dnd => "dnd",
+        }
+    }
 }
 
-/// Window layer (for stacking)
+/// Window layer (for stacking), bottom to top
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum WindowLayer {
+    /// _NET_WM_WINDOW_TYPE_DESKTOP
     Desktop = 0,
+    /// _NET_WM_STATE_BELOW
     Below = 1,
     Normal = 2,
-    Above = 3,
-    Fullscreen = 4,
+    /// _NET_WM_WINDOW_TYPE_DOCK (panels)
+    Dock = 3,
+    /// _NET_WM_STATE_ABOVE
+    Above = 4,
+    /// Focused _NET_WM_STATE_FULLSCREEN window
+    Fullscreen = 5,
+    /// Notifications, tooltips, menus, splash screens
+    Overlay = 6,
 }
 
 /// Tile position type
@@ -0,0 +1,336 @@
+//! Clipboard manager (CLIPBOARD_MANAGER selection)
+//!
+//! Applications about to exit while owning CLIPBOARD ask the owner of
+//! CLIPBOARD_MANAGER to save it by converting it to SAVE_TARGETS
+//! (freedesktop.org ClipboardManager specification). The WM's window then
+//! fetches every target the application offers, takes CLIPBOARD over and
+//! serves the saved data, so what was copied can still be pasted.
+//!
+//! Targets arriving incrementally (INCR) or too large for one property are
+//! skipped, as are side-effect targets such as DELETE.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use tracing::{debug, info, warn};
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ClientMessageEvent, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, SelectionClearEvent,
+    SelectionNotifyEvent, SelectionRequestEvent, Timestamp, WindowClass, SELECTION_NOTIFY_EVENT,
+};
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+/// Largest target saved (bytes)
+const MAX_TARGET: u32 = 16 * 1024 * 1024;
+
+/// A save whose application stops answering is given up after this
+const SAVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Atoms used by the protocol
+#[derive(Debug, Default, Clone, Copy)]
+struct Atoms {
+    clipboard: Atom,
+    clipboard_manager: Atom,
+    manager: Atom,
+    save_targets: Atom,
+    targets: Atom,
+    multiple: Atom,
+    timestamp: Atom,
+    delete: Atom,
+    incr: Atom,
+    /// Property of the WM's window receiving converted targets
+    transfer: Atom,
+}
+
+impl Atoms {
+    /// Targets that are not data (or have side effects) and are never saved
+    fn is_meta(&self, target: Atom) -> bool {
+        [self.targets, self.multiple, self.timestamp, self.delete, self.save_targets].contains(&target)
+    }
+}
+
+/// One saved target: its type, format and data
+#[derive(Debug, Clone)]
+struct Saved {
+    target: Atom,
+    type_: Atom,
+    format: u8,
+    data: Vec<u8>,
+}
+
+/// A save in progress
+#[derive(Debug)]
+struct Save {
+    /// Application asking, and the property to answer in
+    requestor: u32,
+    property: Atom,
+    time: Timestamp,
+    started: Instant,
+    /// Targets still to fetch, and the one being fetched (None: TARGETS)
+    queue: Vec<Atom>,
+    current: Option<Atom>,
+    saved: Vec<Saved>,
+}
+
+#[derive(Debug, Default)]
+pub struct ClipboardManager {
+    /// Hidden window owning CLIPBOARD_MANAGER (0 until `init`)
+    window: u32,
+    atoms: Atoms,
+    /// Whether the WM's window owns CLIPBOARD_MANAGER
+    active: bool,
+    save: Option<Save>,
+    /// Contents served while the WM's window owns CLIPBOARD
+    contents: Vec<Saved>,
+    /// When CLIPBOARD was taken over (TIMESTAMP target)
+    owned_since: Option<Timestamp>,
+}
+
+impl ClipboardManager {
+    /// Create the manager window
+    pub fn init(&mut self, conn: &RustConnection, root: u32) -> Result<()> {
+        let intern = |name| crate::wm::atom_cache::intern(conn, name);
+        self.atoms = Atoms {
+            clipboard: intern("CLIPBOARD")?,
+            clipboard_manager: intern("CLIPBOARD_MANAGER")?,
+            manager: intern("MANAGER")?,
+            save_targets: intern("SAVE_TARGETS")?,
+            targets: intern("TARGETS")?,
+            multiple: intern("MULTIPLE")?,
+            timestamp: intern("TIMESTAMP")?,
+            delete: intern("DELETE")?,
+            incr: intern("INCR")?,
+            transfer: intern("_AREA_CLIPBOARD_SAVE")?,
+        };
+        let window = conn.generate_id()?;
+        conn.create_window(
+            0,
+            window,
+            root,
+            -1,
+            -1,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            0,
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+        self.window = window;
+        Ok(())
+    }
+
+    /// Take CLIPBOARD_MANAGER (unless another manager has it) or give it up;
+    /// `now` is the latest server time
+    pub fn set_enabled(&mut self, conn: &RustConnection, root: u32, enabled: bool, now: Timestamp) -> Result<()> {
+        if self.window == 0 || enabled == self.active {
+            return Ok(());
+        }
+        let selection = self.atoms.clipboard_manager;
+        if !enabled {
+            conn.set_selection_owner(x11rb::NONE, selection, now)?;
+            self.active = false;
+            info!("Clipboard manager stopped");
+            return Ok(());
+        }
+        let owner = conn.get_selection_owner(selection)?.reply()?.owner;
+        if owner != x11rb::NONE {
+            info!("Another clipboard manager (window {}) is running", owner);
+            return Ok(());
+        }
+        conn.set_selection_owner(self.window, selection, now)?;
+        if conn.get_selection_owner(selection)?.reply()?.owner != self.window {
+            warn!("Failed to become the clipboard manager");
+            return Ok(());
+        }
+        // ICCCM 2.8: announce the new manager selection owner
+        let announce = ClientMessageEvent::new(32, root, self.atoms.manager, [now, selection, self.window, 0, 0]);
+        conn.send_event(false, root, EventMask::STRUCTURE_NOTIFY, announce)?;
+        self.active = true;
+        info!("Clipboard manager started");
+        Ok(())
+    }
+
+    /// A selection request to the manager window: save the clipboard, or
+    /// serve saved contents
+    pub fn request(&mut self, conn: &RustConnection, event: &SelectionRequestEvent, now: Timestamp) -> Result<()> {
+        if self.window == 0 || event.owner != self.window {
+            return Ok(());
+        }
+        // Obsolete clients leave the property to the owner
+        let property = if event.property == x11rb::NONE { event.target } else { event.property };
+        let a = self.atoms;
+        let served = if event.selection == a.clipboard_manager {
+            if event.target == a.save_targets {
+                return self.start_save(conn, event, property, now);
+            } else if event.target == a.targets {
+                let targets = [a.targets, a.save_targets, a.timestamp];
+                conn.change_property32(PropMode::REPLACE, event.requestor, property, AtomEnum::ATOM, &targets)?;
+                true
+            } else {
+                false
+            }
+        } else if event.selection == a.clipboard && self.owned_since.is_some() {
+            self.serve(conn, event, property)?
+        } else {
+            false
+        };
+        Self::notify(conn, event, if served { property } else { x11rb::NONE })
+    }
+
+    /// Serve a saved target of CLIPBOARD
+    fn serve(&self, conn: &RustConnection, event: &SelectionRequestEvent, property: Atom) -> Result<bool> {
+        let a = self.atoms;
+        if event.target == a.targets {
+            let mut targets = vec![a.targets, a.timestamp];
+            targets.extend(self.contents.iter().map(|saved| saved.target));
+            conn.change_property32(PropMode::REPLACE, event.requestor, property, AtomEnum::ATOM, &targets)?;
+            return Ok(true);
+        }
+        if event.target == a.timestamp {
+            let time = self.owned_since.unwrap_or(x11rb::CURRENT_TIME);
+            conn.change_property32(PropMode::REPLACE, event.requestor, property, AtomEnum::INTEGER, &[time])?;
+            return Ok(true);
+        }
+        let Some(saved) = self.contents.iter().find(|saved| saved.target == event.target) else {
+            return Ok(false);
+        };
+        if saved.data.len() + 64 >= conn.maximum_request_bytes() {
+            debug!("Saved clipboard target {} too large to serve", saved.target);
+            return Ok(false);
+        }
+        let length = saved.data.len() as u32 / (u32::from(saved.format) / 8).max(1);
+        conn.change_property(PropMode::REPLACE, event.requestor, property, saved.type_, saved.format, length, &saved.data)?;
+        Ok(true)
+    }
+
+    /// Begin saving CLIPBOARD for an application about to exit
+    fn start_save(&mut self, conn: &RustConnection, event: &SelectionRequestEvent, property: Atom, now: Timestamp) -> Result<()> {
+        if self.save.as_ref().is_some_and(|save| save.started.elapsed() < SAVE_TIMEOUT) {
+            debug!("Clipboard save already in progress, refusing window {}", event.requestor);
+            return Self::notify(conn, event, x11rb::NONE);
+        }
+        let owner = conn.get_selection_owner(self.atoms.clipboard)?.reply()?.owner;
+        if owner == x11rb::NONE || owner == self.window {
+            // Nothing to save, or already saved
+            return Self::notify(conn, event, property);
+        }
+        // The application may list the targets to save in the property
+        let queue = if event.property != x11rb::NONE {
+            conn.get_property(false, event.requestor, event.property, AtomEnum::ATOM, 0, 1024)?
+                .reply()?
+                .value32()
+                .map(|atoms| atoms.filter(|&target| !self.atoms.is_meta(target)).collect::<Vec<_>>())
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        debug!("Saving the clipboard of window {} for window {}", owner, event.requestor);
+        self.save = Some(Save {
+            requestor: event.requestor,
+            property,
+            time: event.time,
+            started: Instant::now(),
+            current: None,
+            queue,
+            saved: Vec::new(),
+        });
+        if self.save.as_ref().is_some_and(|save| !save.queue.is_empty()) {
+            self.fetch_next(conn, now)
+        } else {
+            conn.convert_selection(self.window, self.atoms.clipboard, self.atoms.targets, self.atoms.transfer, event.time)?;
+            Ok(())
+        }
+    }
+
+    /// The exiting application's answer to a conversion
+    pub fn converted(&mut self, conn: &RustConnection, event: &SelectionNotifyEvent, now: Timestamp) -> Result<()> {
+        if self.window == 0 || event.requestor != self.window || event.selection != self.atoms.clipboard {
+            return Ok(());
+        }
+        let Some(save) = self.save.as_mut() else {
+            return Ok(());
+        };
+        let reply = if event.property == x11rb::NONE {
+            None
+        } else {
+            Some(conn.get_property(true, self.window, self.atoms.transfer, AtomEnum::ANY, 0, MAX_TARGET / 4)?.reply()?)
+        };
+        match (save.current, reply) {
+            (None, Some(reply)) => {
+                let atoms = &self.atoms;
+                save.queue = reply.value32().map(|targets| targets.filter(|&target| !atoms.is_meta(target)).collect()).unwrap_or_default();
+            }
+            (Some(target), Some(reply)) if reply.type_ != self.atoms.incr && reply.bytes_after == 0 => {
+                save.saved.push(Saved { target, type_: reply.type_, format: reply.format, data: reply.value });
+            }
+            (Some(target), _) => debug!("Clipboard target {} not saved", target),
+            (None, None) => debug!("Clipboard owner lists no targets"),
+        }
+        self.fetch_next(conn, now)
+    }
+
+    /// Ask for the next target of a save, or finish it
+    fn fetch_next(&mut self, conn: &RustConnection, now: Timestamp) -> Result<()> {
+        let Some(save) = self.save.as_mut() else {
+            return Ok(());
+        };
+        if let Some(target) = save.queue.pop() {
+            save.current = Some(target);
+            conn.convert_selection(self.window, self.atoms.clipboard, target, self.atoms.transfer, save.time)?;
+            return Ok(());
+        }
+        let Some(save) = self.save.take() else {
+            return Ok(());
+        };
+        let saved = !save.saved.is_empty();
+        if saved {
+            info!("Saved {} clipboard targets", save.saved.len());
+            self.contents = save.saved;
+            conn.set_selection_owner(self.window, self.atoms.clipboard, now)?;
+            self.owned_since = Some(now);
+        }
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: save.time,
+            requestor: save.requestor,
+            selection: self.atoms.clipboard_manager,
+            target: self.atoms.save_targets,
+            property: if saved { save.property } else { x11rb::NONE },
+        };
+        conn.send_event(false, save.requestor, EventMask::NO_EVENT, notify)?;
+        Ok(())
+    }
+
+    /// Another application took CLIPBOARD (or CLIPBOARD_MANAGER) from the
+    /// manager window
+    pub fn cleared(&mut self, event: &SelectionClearEvent) {
+        if self.window == 0 || event.owner != self.window {
+            return;
+        }
+        if event.selection == self.atoms.clipboard {
+            self.owned_since = None;
+            self.contents.clear();
+        } else if event.selection == self.atoms.clipboard_manager {
+            info!("Another clipboard manager took over");
+            self.active = false;
+        }
+    }
+
+    /// Answer a selection request
+    fn notify(conn: &RustConnection, event: &SelectionRequestEvent, property: Atom) -> Result<()> {
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: event.time,
+            requestor: event.requestor,
+            selection: event.selection,
+            target: event.target,
+            property,
+        };
+        conn.send_event(false, event.requestor, EventMask::NO_EVENT, notify)?;
+        Ok(())
+    }
+}
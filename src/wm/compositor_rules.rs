@@ -0,0 +1,64 @@
+//! Per-application compositor rules (`[[compositor.rules]]`)
+//!
+//! Windows are matched by WM_CLASS instance or class, case-insensitively,
+//! with `*` standing for any text (e.g. "*.exe" for Wine programs). Every
+//! matching rule applies: a window loses the effects any of them exclude
+//! and is unredirected if any of them asks for it.
+
+use crate::compositor::effects::EffectKind;
+use crate::config::CompositorRule;
+use crate::wm::client::ClassHint;
+
+/// What the rules say about a window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuleOutcome {
+    /// Effects not drawn for the window
+    pub excluded: EffectKind,
+    /// Let X draw the window directly
+    pub unredirect: bool,
+}
+
+/// Combine the rules matching a window's class
+pub fn resolve(rules: &[CompositorRule], class: Option<&ClassHint>) -> RuleOutcome {
+    let Some(class) = class else {
+        return RuleOutcome::default();
+    };
+    rules
+        .iter()
+        .filter(|rule| matches(&rule.class, &class.res_name) || matches(&rule.class, &class.res_class))
+        .fold(RuleOutcome::default(), |mut outcome, rule| {
+            for (kind, excluded) in [
+                (EffectKind::SHADOW, rule.no_shadow),
+                (EffectKind::ANIMATION, rule.no_animations),
+                (EffectKind::BLUR, rule.no_blur),
+            ] {
+                if excluded {
+                    outcome.excluded |= kind;
+                }
+            }
+            outcome.unredirect |= rule.unredirect;
+            outcome
+        })
+}
+
+/// Case-insensitive match of a pattern where `*` matches any text
+fn matches(pattern: &str, text: &str) -> bool {
+    let (pattern, text) = (pattern.to_lowercase(), text.to_lowercase());
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No wildcard: the whole text
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
@@ -0,0 +1,52 @@
+//! ConfigureRequest policy
+//!
+//! Clients may ask to move and resize themselves at any time. A window the
+//! WM has given a geometry keeps it: fullscreen and tiled windows ignore
+//! the request, maximized ones only take the part along the axis they are
+//! not maximized on. The client is then told its real geometry with a
+//! synthetic ConfigureNotify (ICCCM 4.1.5). Applications that legitimately
+//! place themselves can be exempted by WM_CLASS, except from fullscreen.
+
+use crate::shared::Geometry;
+use crate::wm::client::Client;
+use crate::wm::client_flags::{ClientFlags, TilePosition};
+
+#[derive(Debug, Default)]
+pub struct ConfigurePolicy {
+    /// Lowercase WM_CLASS names (instance or class) exempt from the policy
+    exempt: Vec<String>,
+}
+
+impl ConfigurePolicy {
+    pub fn set_exempt(&mut self, names: &[String]) {
+        self.exempt = names.iter().map(|name| name.to_lowercase()).collect();
+    }
+
+    fn is_exempt(&self, client: &Client) -> bool {
+        client.class_hint.as_ref().is_some_and(|hint| {
+            self.exempt.contains(&hint.res_name.to_lowercase()) || self.exempt.contains(&hint.res_class.to_lowercase())
+        })
+    }
+
+    /// Client geometry a window gets for the one it asked for
+    pub fn resolve(&self, client: &Client, requested: Geometry) -> Geometry {
+        let current = client.geometry;
+        if client.is_fullscreen() {
+            return current;
+        }
+        if self.is_exempt(client) {
+            return requested;
+        }
+        if client.tile_mode != TilePosition::None {
+            return current;
+        }
+        let mut granted = requested;
+        if client.flags.contains(ClientFlags::MAXIMIZED_HORIZ) {
+            (granted.x, granted.width) = (current.x, current.width);
+        }
+        if client.flags.contains(ClientFlags::MAXIMIZED_VERT) {
+            (granted.y, granted.height) = (current.y, current.height);
+        }
+        granted
+    }
+}
@@ -2,20 +2,21 @@
 //!
 //! Window cycling (Alt+Tab) functionality.
 //! This matches xfwm4's cycle system.
+//!
+//! The manager keeps its own most-recently-used list, fed by every focus
+//! change outside a cycle, and only decides which window comes next; the
+//! caller focuses it. While a cycle is running focus changes don't reorder
+//! the MRU list, the final choice is moved to the front when the cycle ends.
 
-use anyhow::Result;
-use tracing::{debug, info};
-use x11rb::connection::Connection;
-use x11rb::protocol::xproto::*;
-use x11rb::rust_connection::RustConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::debug;
 
 use crate::wm::client::Client;
-use crate::wm::display::DisplayInfo;
-use crate::wm::focus::FocusManager;
-use crate::wm::screen::ScreenInfo;
+use crate::wm::client_flags::{ClientFlags, WindowType};
 
 /// Cycle mode
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CycleMode {
     /// Cycle all windows
     All,
@@ -23,21 +24,48 @@ pub enum CycleMode {
     CurrentWorkspace,
     /// Cycle by application group
     Group,
+    /// Cycle windows with the focused window's WM_CLASS (like Alt+` on macOS)
+    SameClass,
+}
+
+/// Order of the cycle list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CycleOrder {
+    /// Most recently focused first
+    #[default]
+    MostRecentlyUsed,
+    /// Topmost first
+    Stacking,
+    /// By workspace number, topmost first within a workspace
+    Workspace,
 }
 
 /// Cycle manager
 pub struct CycleManager {
     /// Current cycle list
     pub cycle_list: Vec<u32>,
-    
+
     /// Current cycle index
     pub cycle_index: usize,
-    
+
     /// Cycle mode
     pub mode: CycleMode,
-    
+
     /// Is cycling active?
     pub active: bool,
+
+    /// Order of the cycle list
+    pub order: CycleOrder,
+
+    /// Include minimized windows
+    pub include_minimized: bool,
+    
+    /// One entry per window group (`WM_HINTS` window_group), its most recent window
+    pub group_windows: bool,
+
+    /// Focused windows, most recent first
+    mru: Vec<u32>,
 }
 
 impl CycleManager {
@@ -48,163 +76,188 @@ impl CycleManager {
             cycle_index: 0,
             mode: CycleMode::All,
             active: false,
+            order: CycleOrder::default(),
+            include_minimized: false,
+            group_windows: false,
+            mru: Vec::new(),
         }
     }
-    
-    /// Start cycling
+
+    /// Record a focus change
+    pub fn note_focus(&mut self, window: u32) {
+        if !self.active {
+            self.mru.retain(|&w| w != window);
+            self.mru.insert(0, window);
+        }
+    }
+
+    /// Focus history, most recent first
+    pub fn mru(&self) -> &[u32] {
+        &self.mru
+    }
+
+    /// Position of a window in the focus history (0: most recent; never focused: last)
+    pub fn recency(&self, window: u32) -> usize {
+        self.mru.iter().position(|&w| w == window).unwrap_or(usize::MAX)
+    }
+
+    /// Drop a window that is no longer managed
+    pub fn forget(&mut self, window: u32) {
+        self.mru.retain(|&w| w != window);
+        if let Some(pos) = self.cycle_list.iter().position(|&w| w == window) {
+            self.cycle_list.remove(pos);
+            if pos < self.cycle_index {
+                self.cycle_index -= 1;
+            }
+            // An emptied list stays active until the caller finishes the cycle
+            self.cycle_index %= self.cycle_list.len().max(1);
+        }
+    }
+
+    /// Start cycling, returning the first window to select
+    ///
+    /// `stacking` is bottom to top; `workspace` is the current workspace.
     pub fn start_cycle(
         &mut self,
-        conn: &RustConnection,
-        display_info: &DisplayInfo,
-        screen_info: &ScreenInfo,
-        focus_manager: &FocusManager,
-        clients: &std::collections::HashMap<u32, Client>,
+        clients: &HashMap<u32, Client>,
+        stacking: &[u32],
+        workspace: u32,
         mode: CycleMode,
-    ) -> Result<()> {
-        debug!("Starting window cycle (mode={:?})", mode);
-        
-        self.mode = mode;
-        self.active = true;
-        
+        backward: bool,
+    ) -> Option<u32> {
+        debug!("Starting window cycle (mode={:?}, order={:?})", mode, self.order);
+
+        let focused = clients.values().find(|c| c.focused()).map(|c| c.window);
+
         // Build cycle list based on mode
-        self.build_cycle_list(focus_manager, clients, mode);
-        
+        self.build_cycle_list(clients, stacking, workspace, focused, mode);
+
         if self.cycle_list.is_empty() {
-            self.active = false;
-            return Ok(());
-        }
-        
-        // Start at first window (or next after current)
-        if let Some(current) = focus_manager.get_focused_window() {
-            if let Some(pos) = self.cycle_list.iter().position(|&w| w == current) {
-                self.cycle_index = (pos + 1) % self.cycle_list.len();
-            } else {
-                self.cycle_index = 0;
-            }
-        } else {
-            self.cycle_index = 0;
+            return None;
         }
-        
+        self.mode = mode;
+        self.active = true;
+
+        // Start next to the focused window, at either end otherwise
+        let len = self.cycle_list.len();
+        self.cycle_index = match (focused.and_then(|f| self.cycle_list.iter().position(|&w| w == f)), backward) {
+            (Some(pos), false) => (pos + 1) % len,
+            (Some(pos), true) => (pos + len - 1) % len,
+            (None, false) => 0,
+            (None, true) => len - 1,
+        };
+
         // Show cycle preview
         // TODO: Implement cycle preview window
-        
-        Ok(())
+
+        self.selected()
     }
-    
+
     /// Cycle to next window
-    pub fn cycle_next(
-        &mut self,
-        conn: &RustConnection,
-        display_info: &DisplayInfo,
-        screen_info: &ScreenInfo,
-        focus_manager: &mut FocusManager,
-        clients: &mut std::collections::HashMap<u32, Client>,
-    ) -> Result<()> {
+    pub fn cycle_next(&mut self) -> Option<u32> {
         if !self.active || self.cycle_list.is_empty() {
-            return Ok(());
+            return None;
         }
-        
+
         self.cycle_index = (self.cycle_index + 1) % self.cycle_list.len();
-        
-        if let Some(&window) = self.cycle_list.get(self.cycle_index) {
-            if let Some(client) = clients.get_mut(&window) {
-                focus_manager.set_focus(
-                    conn,
-                    display_info,
-                    screen_info,
-                    client,
-                    crate::wm::focus::FocusSource::Other,
-                )?;
-            }
-        }
-        
-        Ok(())
+        self.selected()
     }
-    
+
     /// Cycle to previous window
-    pub fn cycle_prev(
-        &mut self,
-        conn: &RustConnection,
-        display_info: &DisplayInfo,
-        screen_info: &ScreenInfo,
-        focus_manager: &mut FocusManager,
-        clients: &mut std::collections::HashMap<u32, Client>,
-    ) -> Result<()> {
+    pub fn cycle_prev(&mut self) -> Option<u32> {
         if !self.active || self.cycle_list.is_empty() {
-            return Ok(());
+            return None;
         }
-        
+
         self.cycle_index = if self.cycle_index == 0 {
             self.cycle_list.len() - 1
         } else {
             self.cycle_index - 1
         };
-        
-        if let Some(&window) = self.cycle_list.get(self.cycle_index) {
-            if let Some(client) = clients.get_mut(&window) {
-                focus_manager.set_focus(
-                    conn,
-                    display_info,
-                    screen_info,
-                    client,
-                    crate::wm::focus::FocusSource::Other,
-                )?;
-            }
-        }
-        
-        Ok(())
+        self.selected()
     }
-    
-    /// Finish cycling
-    pub fn finish_cycle(&mut self) {
+
+    /// Window currently selected
+    pub fn selected(&self) -> Option<u32> {
+        self.cycle_list.get(self.cycle_index).copied()
+    }
+
+    /// Finish cycling, returning the chosen window
+    pub fn finish_cycle(&mut self) -> Option<u32> {
+        let chosen = if self.active { self.selected() } else { None };
         self.active = false;
         self.cycle_list.clear();
         self.cycle_index = 0;
+
+        if let Some(window) = chosen {
+            self.note_focus(window);
+        }
+        chosen
     }
-    
+
     /// Build cycle list
     fn build_cycle_list(
         &mut self,
-        focus_manager: &FocusManager,
-        clients: &std::collections::HashMap<u32, Client>,
+        clients: &HashMap<u32, Client>,
+        stacking: &[u32],
+        workspace: u32,
+        focused: Option<u32>,
         mode: CycleMode,
     ) {
         self.cycle_list.clear();
-        
-        match mode {
-            CycleMode::All => {
-                // All mapped windows
-                for (window, client) in clients.iter() {
-                    if client.mapped() {
-                        self.cycle_list.push(*window);
-                    }
-                }
+
+        let focused = focused.and_then(|f| clients.get(&f));
+        let class_of = |c: &Client| c.class_hint.as_ref().map(|h| h.res_class.clone());
+        let focused_class = focused.and_then(class_of);
+        let focused_group = focused.and_then(|c| c.group_leader);
+
+        for (window, client) in clients.iter() {
+            if !self.is_candidate(client) {
+                continue;
             }
-            CycleMode::CurrentWorkspace => {
-                // Only windows on current workspace
-                // TODO: Filter by workspace
-                for (window, client) in clients.iter() {
-                    if client.mapped() {
-                        self.cycle_list.push(*window);
-                    }
-                }
+            let included = match mode {
+                CycleMode::All => true,
+                CycleMode::CurrentWorkspace => client.is_sticky() || client.win_workspace == workspace,
+                CycleMode::Group => focused_group.is_none() || client.group_leader == focused_group,
+                CycleMode::SameClass => focused_class.is_none() || class_of(client) == focused_class,
+            };
+            if included {
+                self.cycle_list.push(*window);
             }
-            CycleMode::Group => {
-                // Group by application
-                // TODO: Group windows by application
-                for (window, client) in clients.iter() {
-                    if client.mapped() {
-                        self.cycle_list.push(*window);
-                    }
-                }
+        }
+
+        // Topmost first, windows missing from the stacking list last
+        let depth = |w: u32| stacking.iter().rev().position(|&s| s == w).unwrap_or(usize::MAX);
+        match self.order {
+            CycleOrder::MostRecentlyUsed => {
+                // Windows never focused follow, topmost first
+                let mru = &self.mru;
+                self.cycle_list.sort_by_key(|&w| {
+                    (mru.iter().position(|&m| m == w).unwrap_or(usize::MAX), depth(w))
+                });
             }
+            CycleOrder::Stacking => self.cycle_list.sort_by_key(|&w| depth(w)),
+            CycleOrder::Workspace => self.cycle_list.sort_by_key(|&w| (clients[&w].win_workspace, depth(w))),
         }
         
-        // Sort by focus history (most recent first)
-        let history = focus_manager.get_focus_history();
-        self.cycle_list.sort_by_key(|&w| {
-            history.iter().position(|&h| h == w).unwrap_or(usize::MAX)
-        });
+        if self.group_windows {
+            let mut seen = std::collections::HashSet::new();
+            self.cycle_list.retain(|w| seen.insert(clients[w].group_leader.unwrap_or(*w)));
+        }
+    }
+
+    /// Whether a window can be cycled to at all
+    fn is_candidate(&self, client: &Client) -> bool {
+        if matches!(client.type_, WindowType::Desktop | WindowType::Dock)
+            || client.flags.contains(ClientFlags::SKIP_TASKBAR)
+        {
+            return false;
+        }
+        if client.is_minimized() {
+            self.include_minimized
+        } else {
+            client.mapped()
+        }
     }
 }
 
@@ -213,6 +266,3 @@ impl Default for CycleManager {
         Self::new()
     }
 }
-
-
-
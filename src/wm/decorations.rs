@@ -5,25 +5,33 @@ use anyhow::Result;
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::*;
 use x11rb::rust_connection::RustConnection;
+use tracing::{debug, warn};
 
-// #region agent log
-fn debug_log(location: &str, message: &str, data: serde_json::Value, hypothesis_id: &str) {
-    use std::fs::OpenOptions;
-    use std::io::Write;
-    let log_entry = serde_json::json!({
-        "sessionId": "debug-session",
-        "runId": "run1",
-        "hypothesisId": hypothesis_id,
-        "location": location,
-        "message": message,
-        "data": data,
-        "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()
-    });
-    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("/home/bizkit/GitHub/area/.cursor/debug.log") {
-        let _ = writeln!(file, "{}", log_entry);
+/// The screen's 32-bit TrueColor visual and a colormap for it
+///
+/// Frames of ARGB clients use it: a redirected window's pixmap has the
+/// window's depth, so a 24-bit frame would drop the client's alpha.
+#[derive(Debug, Clone, Copy)]
+pub struct ArgbVisual {
+    pub visual: Visualid,
+    pub colormap: Colormap,
+}
+
+impl ArgbVisual {
+    /// Find the screen's ARGB visual and create its colormap, None without one
+    pub fn new(conn: &RustConnection, screen: &Screen) -> Result<Option<Self>> {
+        let visual = screen
+            .allowed_depths
+            .iter()
+            .filter(|depth| depth.depth == 32)
+            .flat_map(|depth| &depth.visuals)
+            .find(|visual| visual.class == VisualClass::TRUE_COLOR);
+        let Some(visual) = visual else { return Ok(None) };
+        let colormap = conn.generate_id()?;
+        conn.create_colormap(ColormapAlloc::NONE, colormap, screen.root, visual.visual_id)?;
+        Ok(Some(Self { visual: visual.visual_id, colormap }))
     }
 }
-// #endregion
 
 /// Represents a window frame with decorations
 #[derive(Debug, Clone)]
@@ -31,11 +39,94 @@ pub struct WindowFrame {
     pub client: Window,
     pub frame: Window,
     pub titlebar: Window,
-    pub close_button: Window,
-    pub maximize_button: Window,
-    pub minimize_button: Window,
+    pub buttons: Vec<FrameButton>,
+}
+
+/// A titlebar button window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameButton {
+    pub kind: ButtonType,
+    pub window: Window,
+    /// Right-aligned group
+    pub from_right: bool,
+    /// Position counted from the titlebar edge it is aligned to
+    pub index: u16,
 }
 
+impl FrameButton {
+    /// X position in a titlebar `width` wide
+    fn x(&self, width: u16, decorations: &crate::config::WindowDecorationConfig) -> i32 {
+        let step = (decorations.button_size + decorations.button_padding) as i32;
+        if self.from_right {
+            width as i32 - (self.index as i32 + 1) * step
+        } else {
+            decorations.button_padding as i32 + self.index as i32 * step
+        }
+    }
+}
+
+/// Decorations for the compositor to draw over a frame (`DecorationRender::Compositor`)
+pub fn compositor_decoration(
+    buttons: &[FrameButton],
+    decorations: &crate::config::WindowDecorationConfig,
+    colors: &crate::config::WindowColors,
+) -> crate::compositor::decorations::Decoration {
+    use crate::compositor::decorations::{Decoration, DecorationButton, rgb};
+    let y = (decorations.titlebar_height - decorations.button_size) as f32 / 2.0;
+    Decoration {
+        titlebar_height: decorations.titlebar_height as f32,
+        titlebar_color: rgb(colors.titlebar),
+        buttons: buttons
+            .iter()
+            .map(|button| DecorationButton {
+                // Same positions as the input windows (see `FrameButton::x`)
+                offset: if button.from_right { -button.x(0, decorations) } else { button.x(0, decorations) } as f32,
+                from_right: button.from_right,
+                y,
+                size: decorations.button_size as f32,
+                color: rgb(button.kind.color(colors)),
+            })
+            .collect(),
+    }
+}
+
+/// Titlebar buttons left and right of the title, e.g. `"menu,sticky|minimize,maximize,close"`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ButtonLayout {
+    pub left: Vec<ButtonType>,
+    pub right: Vec<ButtonType>,
+}
+
+impl ButtonLayout {
+    /// Parse a layout string; unknown button names are skipped with a warning
+    pub fn parse(layout: &str) -> Self {
+        let (left, right) = layout.split_once('|').unwrap_or(("", layout));
+        let parse_side = |side: &str| {
+            side.split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .filter_map(|name| {
+                    let button = ButtonType::from_name(name);
+                    if button.is_none() {
+                        warn!("Unknown titlebar button {:?}", name);
+                    }
+                    button
+                })
+                .collect()
+        };
+        Self { left: parse_side(left), right: parse_side(right) }
+    }
+}
+
+impl Default for ButtonLayout {
+    fn default() -> Self {
+        Self::parse(DEFAULT_BUTTON_LAYOUT)
+    }
+}
+
+/// Button layout used when none is configured
+pub const DEFAULT_BUTTON_LAYOUT: &str = "|minimize,maximize,close";
+
 impl WindowFrame {
     /// Reconstruct a WindowFrame from stored IDs
     pub fn from_state(client: Window, state: &crate::shared::window_state::WindowFrame) -> Self {
@@ -43,9 +134,7 @@ impl WindowFrame {
             client,
             frame: state.frame,
             titlebar: state.titlebar,
-            close_button: state.close_button,
-            maximize_button: state.maximize_button,
-            minimize_button: state.minimize_button,
+            buttons: state.buttons.clone(),
         }
     }
 
@@ -60,16 +149,26 @@ impl WindowFrame {
         height: u16,
         decorations: &crate::config::WindowDecorationConfig,
         colors: &crate::config::WindowColors,
+        layout: &ButtonLayout,
+        argb: Option<ArgbVisual>,
     ) -> Result<Self> {
         let frame = conn.generate_id()?;
         let titlebar = conn.generate_id()?;
-        let close_button = conn.generate_id()?;
-        let maximize_button = conn.generate_id()?;
-        let minimize_button = conn.generate_id()?;
+
+        // Pixels of an ARGB frame need an opaque alpha byte
+        let (frame_depth, visual, opaque) = match argb {
+            Some(argb) => (32, argb.visual, 0xff00_0000),
+            None => (screen.root_depth, 0, 0),
+        };
 
         // Create frame window
+        let aux = CreateWindowAux::new();
+        let aux = match argb {
+            Some(argb) => aux.colormap(argb.colormap),
+            None => aux,
+        };
         conn.create_window(
-            screen.root_depth,
+            frame_depth,
             frame,
             screen.root,
             x,
@@ -78,23 +177,36 @@ impl WindowFrame {
             height + decorations.titlebar_height,
             decorations.border_width, 
             WindowClass::INPUT_OUTPUT,
-            0,
-            &CreateWindowAux::new()
-                .background_pixel(colors.background)
-                .border_pixel(colors.border)
+            visual,
+            &aux
+                .background_pixel(colors.background | opaque)
+                .border_pixel(colors.border | opaque)
                 .event_mask(
                     EventMask::SUBSTRUCTURE_REDIRECT
                         | EventMask::SUBSTRUCTURE_NOTIFY
                         | EventMask::BUTTON_PRESS
                         | EventMask::BUTTON_RELEASE
-                        | EventMask::POINTER_MOTION,
+                        | EventMask::POINTER_MOTION
+                        | EventMask::ENTER_WINDOW,
                 )
                 .override_redirect(1),
         )?;
 
+        // Compositor-drawn decorations only need windows for input
+        let drawn_by_x = decorations.render == crate::config::DecorationRender::X11;
+        let (depth, class) = if drawn_by_x {
+            (frame_depth, WindowClass::INPUT_OUTPUT)
+        } else {
+            (0, WindowClass::INPUT_ONLY)
+        };
+        let fill = |color| {
+            let aux = CreateWindowAux::new().event_mask(EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE);
+            if drawn_by_x { aux.background_pixel(color | opaque) } else { aux }
+        };
+
         // Create titlebar
         conn.create_window(
-            screen.root_depth,
+            depth,
             titlebar,
             frame,
             0,
@@ -102,110 +214,55 @@ impl WindowFrame {
             width,
             decorations.titlebar_height,
             0,
-            WindowClass::INPUT_OUTPUT,
+            class,
             0,
-            &CreateWindowAux::new()
-                .background_pixel(colors.titlebar)
-                .event_mask(EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE),
+            &fill(colors.titlebar),
         )?;
 
-        // Create close button
-        // Use i32 for calculations to avoid underflow on small windows
-        let width_i32 = width as i32;
-        let btn_size = decorations.button_size as i32;
-        let pad = decorations.button_padding as i32;
-
-        let close_x = width_i32 - btn_size - pad;
+        // Create buttons, right-aligned ones counted from the right edge
         let btn_y = (decorations.titlebar_height - decorations.button_size) / 2;
-        conn.create_window(
-            screen.root_depth,
-            close_button,
-            titlebar,
-            close_x as i16,
-            btn_y as i16,
-            decorations.button_size,
-            decorations.button_size,
-            0, // No border for buttons (flat look)
-            WindowClass::INPUT_OUTPUT,
-            0,
-            &CreateWindowAux::new()
-                .background_pixel(colors.close_button)
-                .event_mask(EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE),
-        )?;
-
-        // Create maximize button
-        let max_x = close_x - btn_size - pad;
-        conn.create_window(
-            screen.root_depth,
-            maximize_button,
-            titlebar,
-            max_x as i16,
-            btn_y as i16,
-            decorations.button_size,
-            decorations.button_size,
-            0,
-            WindowClass::INPUT_OUTPUT,
-            0,
-            &CreateWindowAux::new()
-                .background_pixel(colors.maximize_button)
-                .event_mask(EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE),
-        )?;
-
-        // Create minimize button
-        let min_x = max_x - btn_size - pad;
-        conn.create_window(
-            screen.root_depth,
-            minimize_button,
-            titlebar,
-            min_x as i16,
-            btn_y as i16,
-            decorations.button_size,
-            decorations.button_size,
-            0,
-            WindowClass::INPUT_OUTPUT,
-            0,
-            &CreateWindowAux::new()
-                .background_pixel(colors.minimize_button)
-                .event_mask(EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE),
-        )?;
+        let left = layout.left.iter().enumerate().map(|(i, &kind)| (kind, false, i));
+        let right = layout.right.iter().rev().enumerate().map(|(i, &kind)| (kind, true, i));
+        let mut buttons = Vec::new();
+        for (kind, from_right, index) in left.chain(right) {
+            let button = FrameButton { kind, window: conn.generate_id()?, from_right, index: index as u16 };
+            conn.create_window(
+                depth,
+                button.window,
+                titlebar,
+                button.x(width, decorations) as i16,
+                btn_y as i16,
+                decorations.button_size,
+                decorations.button_size,
+                0, // No border for buttons (flat look)
+                class,
+                0,
+                &fill(kind.color(colors)),
+            )?;
+            buttons.push(button);
+        }
 
         // Reparent client into frame
         conn.reparent_window(client, frame, 0, decorations.titlebar_height as i16)?;
         
-        // #region agent log
-        debug_log("decorations.rs:157", "Frame created, about to map", serde_json::json!({
-            "client": client,
-            "frame": frame,
-            "titlebar": titlebar,
-            "width": width,
-            "height": height
-        }), "A");
-        // #endregion
+        debug!("Frame {} created for client {} ({}x{}, titlebar {})", frame, client, width, height, titlebar);
         
         // Map all windows (frame first, then client)
         conn.map_window(frame)?;
-        conn.map_window(close_button)?;
-        conn.map_window(maximize_button)?;
-        conn.map_window(minimize_button)?;
+        for button in &buttons {
+            conn.map_window(button.window)?;
+        }
         conn.map_window(titlebar)?;
         // Map the client window so it's visible
         conn.map_window(client)?;
         
-        // #region agent log
-        debug_log("decorations.rs:170", "Frame windows mapped", serde_json::json!({
-            "client": client,
-            "frame": frame,
-            "all_mapped": true
-        }), "A");
-        // #endregion
+        debug!("Frame {} and client {} mapped", frame, client);
 
         Ok(Self {
             client,
             frame,
             titlebar,
-            close_button,
-            maximize_button,
-            minimize_button,
+            buttons,
         })
     }
 
@@ -213,22 +270,12 @@ impl WindowFrame {
     pub fn contains(&self, window: Window) -> bool {
         window == self.frame
             || window == self.titlebar
-            || window == self.close_button
-            || window == self.maximize_button
-            || window == self.minimize_button
+            || self.buttons.iter().any(|b| b.window == window)
     }
 
     /// Get the button type if window is a button
     pub fn get_button_type(&self, window: Window) -> Option<ButtonType> {
-        if window == self.close_button {
-            Some(ButtonType::Close)
-        } else if window == self.maximize_button {
-            Some(ButtonType::Maximize)
-        } else if window == self.minimize_button {
-            Some(ButtonType::Minimize)
-        } else {
-            None
-        }
+        self.buttons.iter().find(|b| b.window == window).map(|b| b.kind)
     }
 
     /// Resize the frame and client
@@ -251,22 +298,12 @@ impl WindowFrame {
         )?;
 
         // Reposition buttons
-        let close_x = width - decorations.button_size - decorations.button_padding;
-        let max_x = close_x - decorations.button_size - decorations.button_padding;
-        let min_x = max_x - decorations.button_size - decorations.button_padding;
-
-        conn.configure_window(
-            self.close_button,
-            &ConfigureWindowAux::new().x(close_x as i32),
-        )?;
-        conn.configure_window(
-            self.maximize_button,
-            &ConfigureWindowAux::new().x(max_x as i32),
-        )?;
-        conn.configure_window(
-            self.minimize_button,
-            &ConfigureWindowAux::new().x(min_x as i32),
-        )?;
+        for button in &self.buttons {
+            conn.configure_window(
+                button.window,
+                &ConfigureWindowAux::new().x(button.x(width, decorations)),
+            )?;
+        }
 
         Ok(())
     }
@@ -293,4 +330,37 @@ pub enum ButtonType {
     Close,
     Maximize,
     Minimize,
+    /// Open the window menu
+    Menu,
+    /// Toggle showing the window on all workspaces
+    Sticky,
+    /// Toggle always on top
+    Above,
+    /// Roll the window up into its titlebar and back
+    Shade,
+}
+
+impl ButtonType {
+    /// Parse a button name from a layout string
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "close" => Self::Close,
+            "maximize" => Self::Maximize,
+            "minimize" => Self::Minimize,
+            "menu" => Self::Menu,
+            "sticky" => Self::Sticky,
+            "above" => Self::Above,
+            "shade" => Self::Shade,
+            _ => return None,
+        })
+    }
+
+    fn color(self, colors: &crate::config::WindowColors) -> u32 {
+        match self {
+            Self::Close => colors.close_button,
+            Self::Maximize => colors.maximize_button,
+            Self::Minimize => colors.minimize_button,
+            Self::Menu | Self::Sticky | Self::Above | Self::Shade => colors.button,
+        }
+    }
 }
@@ -0,0 +1,129 @@
+//! Display power management (DPMS) and screen blanking
+//!
+//! The X server blanks the screen (core screen saver) and powers the
+//! monitors down (DPMS) after the configured idle times, and wakes them on
+//! any input, so the WM only sets the timeouts. While an application
+//! inhibits idling (org.freedesktop.ScreenSaver, e.g. a video player) the
+//! timeouts are zero. DPMS itself stays enabled so the monitors can still be
+//! switched off on request.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use area_ipc::DpmsState;
+use tracing::{debug, info, warn};
+use x11rb::protocol::dpms::{ConnectionExt as _, DPMSMode};
+use x11rb::protocol::xproto::{Blanking, ConnectionExt as _, Exposures, ScreenSaver};
+use x11rb::rust_connection::RustConnection;
+
+use crate::config::DpmsConfig;
+
+/// An application keeping the screen on
+#[derive(Debug, Clone)]
+pub struct Inhibitor {
+    pub application: String,
+    pub reason: String,
+    /// D-Bus unique name of the caller, to drop the inhibitor when it exits
+    pub sender: String,
+}
+
+/// Blank and DPMS timeouts and idle inhibitors
+#[derive(Debug, Default)]
+pub struct Dpms {
+    /// The server supports DPMS
+    available: bool,
+    config: DpmsConfig,
+    /// Inhibitors by cookie
+    inhibitors: HashMap<u32, Inhibitor>,
+}
+
+impl Dpms {
+    /// Check for the DPMS extension
+    pub fn init(&mut self, conn: &RustConnection) -> Result<()> {
+        let version = conn.dpms_get_version(1, 1)?.reply()?;
+        if !conn.dpms_capable()?.reply()?.capable {
+            warn!("Display is not DPMS capable, only blanking the screen");
+            return Ok(());
+        }
+        debug!("DPMS {}.{}", version.server_major_version, version.server_minor_version);
+        self.available = true;
+        Ok(())
+    }
+
+    /// Apply the configured timeouts
+    pub fn set_config(&mut self, conn: &RustConnection, config: &DpmsConfig) -> Result<()> {
+        self.config = *config;
+        self.apply(conn)
+    }
+
+    /// Set the server's timeouts, or turn them off while inhibited
+    fn apply(&self, conn: &RustConnection) -> Result<()> {
+        let active = self.config.enabled && self.inhibitors.is_empty();
+        let blank = if active { self.config.blank_timeout } else { 0 };
+        conn.set_screen_saver(clamp(blank) as i16, 0, Blanking::PREFERRED, Exposures::DEFAULT)?;
+        if !self.available {
+            return Ok(());
+        }
+        let c = &self.config;
+        let (standby, suspend, off) = if active { (c.standby_timeout, c.suspend_timeout, c.off_timeout) } else { (0, 0, 0) };
+        conn.dpms_set_timeouts(clamp(standby), clamp(suspend), clamp(off))?;
+        conn.dpms_enable()?;
+        debug!("Screen blanking after {}s, standby/suspend/off after {}/{}/{}s (inhibitors: {})", blank, standby, suspend, off, self.inhibitors.len());
+        Ok(())
+    }
+
+    /// Switch the monitors to a power state now; input wakes them again
+    pub fn force(&self, conn: &RustConnection, state: DpmsState) -> Result<()> {
+        if !self.available {
+            anyhow::bail!("display is not DPMS capable");
+        }
+        let mode = match state {
+            DpmsState::On => DPMSMode::ON,
+            DpmsState::Standby => DPMSMode::STANDBY,
+            DpmsState::Suspend => DPMSMode::SUSPEND,
+            DpmsState::Off => DPMSMode::OFF,
+        };
+        info!("Forcing DPMS {:?}", state);
+        conn.dpms_force_level(mode)?;
+        if state == DpmsState::On {
+            // Also unblank and restart the idle timers
+            conn.force_screen_saver(ScreenSaver::RESET)?;
+        }
+        Ok(())
+    }
+
+    /// Keep the screen on until `uninhibit(cookie)`
+    pub fn inhibit(&mut self, conn: &RustConnection, cookie: u32, inhibitor: Inhibitor) -> Result<()> {
+        info!("{} inhibits screen blanking: {}", inhibitor.application, inhibitor.reason);
+        self.inhibitors.insert(cookie, inhibitor);
+        if self.inhibitors.len() == 1 {
+            self.apply(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Drop an inhibitor
+    pub fn uninhibit(&mut self, conn: &RustConnection, cookie: u32) -> Result<()> {
+        if let Some(inhibitor) = self.inhibitors.remove(&cookie) {
+            info!("{} no longer inhibits screen blanking", inhibitor.application);
+            if self.inhibitors.is_empty() {
+                self.apply(conn)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the inhibitors of a D-Bus client that disconnected
+    pub fn client_vanished(&mut self, conn: &RustConnection, sender: &str) -> Result<()> {
+        let cookies: Vec<u32> = self.inhibitors.iter().filter(|(_, i)| i.sender == sender).map(|(&c, _)| c).collect();
+        for cookie in cookies {
+            self.uninhibit(conn, cookie)?;
+        }
+        Ok(())
+    }
+}
+
+/// Seconds as the server's 16-bit timeout
+fn clamp(seconds: u32) -> u16 {
+    seconds.min(i16::MAX as u32) as u16
+}
@@ -0,0 +1,164 @@
+//! Screen-edge pressure (revealing auto-hidden shell panels)
+//!
+//! A shell whose panel auto-hides enables the panel's monitor edge with
+//! `SetEdgeTrigger`. The WM puts an XFixes pointer barrier along that edge;
+//! every push against it arrives as an XInput barrier event with the motion
+//! it blocked. Enough motion into the edge within the configured time
+//! triggers the edge once, until the pointer leaves it.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use anyhow::Result;
+use tracing::{debug, warn};
+use x11rb::connection::Connection;
+use x11rb::protocol::xfixes::{BarrierDirections, ConnectionExt as _};
+use x11rb::protocol::xinput::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::Window;
+
+use crate::config::{EdgePressure, EdgePressureConfig};
+use crate::wm::screen::Monitor;
+use crate::wm::strut::StrutEdge;
+
+/// XI2 device id of all master devices
+const ALL_MASTER_DEVICES: u16 = 1;
+
+/// A barrier along an enabled edge
+#[derive(Debug)]
+struct EdgeBarrier {
+    edge: StrutEdge,
+    monitor: u32,
+    /// Recent pushes: server time (ms) and distance into the edge (px)
+    hits: VecDeque<(u32, f64)>,
+    /// Triggered and not left since
+    triggered: bool,
+}
+
+/// Enabled edges and their barriers
+#[derive(Debug, Default)]
+pub struct EdgeTriggers {
+    enabled: HashSet<(u32, StrutEdge)>,
+    barriers: HashMap<u32, EdgeBarrier>,
+    config: EdgePressureConfig,
+    /// XFixes 5 and XInput 2.3 are present and barrier events selected
+    available: bool,
+}
+
+impl EdgeTriggers {
+    /// Check for pointer barrier support and select barrier events on the root
+    pub fn init<C: Connection>(&mut self, conn: &C, root: Window) -> Result<()> {
+        let fixes = conn.xfixes_query_version(5, 0)?.reply()?;
+        let xi = conn.xinput_xi_query_version(2, 3)?.reply()?;
+        if fixes.major_version < 5 || (xi.major_version, xi.minor_version) < (2, 3) {
+            warn!("Pointer barriers unavailable (XFixes {}, XInput {}.{}), edge triggers disabled",
+                fixes.major_version, xi.major_version, xi.minor_version);
+            return Ok(());
+        }
+        let mask = xinput::EventMask {
+            deviceid: ALL_MASTER_DEVICES,
+            mask: vec![xinput::XIEventMask::BARRIER_HIT | xinput::XIEventMask::BARRIER_LEAVE],
+        };
+        conn.xinput_xi_select_events(root, &[mask])?;
+        self.available = true;
+        Ok(())
+    }
+
+    /// Replace the per-edge settings (after a config reload)
+    pub fn set_config(&mut self, config: EdgePressureConfig) {
+        self.config = config;
+    }
+
+    /// Enable or disable an edge of a monitor; call `rebuild` afterwards
+    pub fn set_enabled(&mut self, monitor: u32, edge: StrutEdge, enabled: bool) {
+        if enabled {
+            self.enabled.insert((monitor, edge));
+        } else {
+            self.enabled.remove(&(monitor, edge));
+        }
+    }
+
+    /// Recreate the barriers of the enabled edges (edges or monitors changed)
+    pub fn rebuild<C: Connection>(&mut self, conn: &C, root: Window, monitors: &[Monitor]) -> Result<()> {
+        for barrier in self.barriers.keys() {
+            conn.xfixes_delete_pointer_barrier(*barrier)?;
+        }
+        self.barriers.clear();
+        if !self.available {
+            return Ok(());
+        }
+        for &(monitor, edge) in &self.enabled {
+            let Some(m) = monitors.get(monitor as usize) else { continue };
+            if !pressure(&self.config, edge).enabled {
+                continue;
+            }
+            let (left, top) = (m.x.max(0) as u16, m.y.max(0) as u16);
+            let (right, bottom) = (left.saturating_add(m.width as u16), top.saturating_add(m.height as u16));
+            // A line along the edge, crossable only from outside the monitor
+            let (x1, y1, x2, y2, directions) = match edge {
+                StrutEdge::Left => (left, top, left, bottom, BarrierDirections::POSITIVE_X),
+                StrutEdge::Right => (right, top, right, bottom, BarrierDirections::NEGATIVE_X),
+                StrutEdge::Top => (left, top, right, top, BarrierDirections::POSITIVE_Y),
+                StrutEdge::Bottom => (left, bottom, right, bottom, BarrierDirections::NEGATIVE_Y),
+            };
+            let barrier = conn.generate_id()?;
+            conn.xfixes_create_pointer_barrier(barrier, root, x1, y1, x2, y2, directions, &[])?;
+            self.barriers.insert(barrier, EdgeBarrier { edge, monitor, hits: VecDeque::new(), triggered: false });
+        }
+        debug!("Edge barriers: {} of {} enabled edges", self.barriers.len(), self.enabled.len());
+        Ok(())
+    }
+
+    /// Account a `BarrierHit`; returns the edge if this push triggered it
+    ///
+    /// The pointer is released through the barrier once it triggers, so it
+    /// can still cross into a neighbouring monitor.
+    pub fn hit<C: Connection>(&mut self, conn: &C, event: &xinput::BarrierHitEvent) -> Result<Option<(u32, StrutEdge)>> {
+        let Some(barrier) = self.barriers.get_mut(&event.barrier) else { return Ok(None) };
+        if barrier.triggered {
+            return Ok(None);
+        }
+        let EdgePressure { threshold, timeout_ms, .. } = *pressure(&self.config, barrier.edge);
+        let (dx, dy) = (fp3232(event.dx), fp3232(event.dy));
+        let push = match barrier.edge {
+            StrutEdge::Left => -dx,
+            StrutEdge::Right => dx,
+            StrutEdge::Top => -dy,
+            StrutEdge::Bottom => dy,
+        };
+        let now = event.time;
+        barrier.hits.push_back((now, push.max(0.0)));
+        while barrier.hits.front().is_some_and(|&(time, _)| now.wrapping_sub(time) as u64 > timeout_ms) {
+            barrier.hits.pop_front();
+        }
+        if barrier.hits.iter().map(|&(_, push)| push).sum::<f64>() < threshold as f64 {
+            return Ok(None);
+        }
+        barrier.triggered = true;
+        barrier.hits.clear();
+        let release = xinput::BarrierReleasePointerInfo { deviceid: event.deviceid, barrier: event.barrier, eventid: event.eventid };
+        conn.xinput_xi_barrier_release_pointer(&[release])?;
+        Ok(Some((barrier.monitor, barrier.edge)))
+    }
+
+    /// Account a `BarrierLeave`: the edge can trigger again
+    pub fn leave(&mut self, event: &xinput::BarrierLeaveEvent) {
+        if let Some(barrier) = self.barriers.get_mut(&event.barrier) {
+            barrier.triggered = false;
+            barrier.hits.clear();
+        }
+    }
+}
+
+/// Settings of one edge
+fn pressure(config: &EdgePressureConfig, edge: StrutEdge) -> &EdgePressure {
+    match edge {
+        StrutEdge::Left => &config.left,
+        StrutEdge::Right => &config.right,
+        StrutEdge::Top => &config.top,
+        StrutEdge::Bottom => &config.bottom,
+    }
+}
+
+/// A 32.32 fixed-point value
+fn fp3232(value: xinput::Fp3232) -> f64 {
+    value.integral as f64 + value.frac as f64 / (1u64 << 32) as f64
+}
@@ -16,6 +16,7 @@ use x11rb::wrapper::ConnectionExt as _;
 pub struct Atoms {
     pub net_supported: Atom,
     pub net_client_list: Atom,
+    pub net_client_list_stacking: Atom,
     pub net_number_of_desktops: Atom,
     pub net_current_desktop: Atom,
     pub net_active_window: Atom,
@@ -49,9 +50,13 @@ pub struct Atoms {
     pub _net_wm_state_above: Atom,
     pub _net_wm_state_below: Atom,
     pub _net_wm_state_demands_attention: Atom,
+    pub _net_wm_state_focused: Atom,
     pub net_frame_extents: Atom,
+    pub net_workarea: Atom,
     pub _net_wm_bypass_compositor: Atom,
     pub _net_close_window: Atom,
+    pub _net_request_frame_extents: Atom,
+    pub _net_wm_ping: Atom,
     pub _net_moveresize_window: Atom,
     pub _net_wm_moveresize: Atom,
     pub _net_wm_fullscreen_monitors: Atom,
@@ -75,6 +80,7 @@ pub struct Atoms {
     // Strut atoms
     pub _net_wm_strut: Atom,
     pub _net_wm_strut_partial: Atom,
+    pub _net_wm_opaque_region: Atom,
     // Standard X11 atoms
     pub _wm_protocols: Atom,
     pub _wm_delete_window: Atom,
@@ -85,19 +91,23 @@ pub struct Atoms {
     pub _utf8_string: Atom,
     // MOTIF WM Hints (for decoration control)
     pub _motif_wm_hints: Atom,
+    // Opacity hint read by compositors
+    pub _net_wm_window_opacity: Atom,
+    // GTK client-side decorations asking for the window menu
+    pub _gtk_show_window_menu: Atom,
 }
 
 impl Atoms {
     /// Intern all required atoms
     pub fn new<C: Connection>(conn: &C) -> Result<Self> {
-        // Helper to intern a single atom
-        let intern = |name: &str| -> Result<Atom> {
-            Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
-        };
+        // One round trip for the whole set, the lookups below hit the cache
+        crate::wm::atom_cache::intern_all(conn, crate::wm::atom_cache::PRELOAD)?;
+        let intern = |name: &str| crate::wm::atom_cache::intern(conn, name);
 
         Ok(Self {
             net_supported: intern("_NET_SUPPORTED")?,
             net_client_list: intern("_NET_CLIENT_LIST")?,
+            net_client_list_stacking: intern("_NET_CLIENT_LIST_STACKING")?,
             net_number_of_desktops: intern("_NET_NUMBER_OF_DESKTOPS")?,
             net_current_desktop: intern("_NET_CURRENT_DESKTOP")?,
             net_active_window: intern("_NET_ACTIVE_WINDOW")?,
@@ -131,9 +141,13 @@ impl Atoms {
             _net_wm_state_above: intern("_NET_WM_STATE_ABOVE")?,
             _net_wm_state_below: intern("_NET_WM_STATE_BELOW")?,
             _net_wm_state_demands_attention: intern("_NET_WM_STATE_DEMANDS_ATTENTION")?,
+            _net_wm_state_focused: intern("_NET_WM_STATE_FOCUSED")?,
             net_frame_extents: intern("_NET_FRAME_EXTENTS")?,
+            net_workarea: intern("_NET_WORKAREA")?,
             _net_wm_bypass_compositor: intern("_NET_WM_BYPASS_COMPOSITOR")?,
             _net_close_window: intern("_NET_CLOSE_WINDOW")?,
+            _net_request_frame_extents: intern("_NET_REQUEST_FRAME_EXTENTS")?,
+            _net_wm_ping: intern("_NET_WM_PING")?,
             _net_moveresize_window: intern("_NET_MOVERESIZE_WINDOW")?,
             _net_wm_moveresize: intern("_NET_WM_MOVERESIZE")?,
             _net_wm_fullscreen_monitors: intern("_NET_WM_FULLSCREEN_MONITORS")?,
@@ -157,6 +171,7 @@ impl Atoms {
             // Strut atoms
             _net_wm_strut: intern("_NET_WM_STRUT")?,
             _net_wm_strut_partial: intern("_NET_WM_STRUT_PARTIAL")?,
+            _net_wm_opaque_region: intern("_NET_WM_OPAQUE_REGION")?,
             // Standard X11 atoms
             _wm_protocols: intern("WM_PROTOCOLS")?,
             _wm_delete_window: intern("WM_DELETE_WINDOW")?,
@@ -166,6 +181,8 @@ impl Atoms {
             _wm_size_hints: intern("WM_SIZE_HINTS")?,
             _utf8_string: intern("UTF8_STRING")?,
             _motif_wm_hints: intern("_MOTIF_WM_HINTS")?,
+            _net_wm_window_opacity: intern("_NET_WM_WINDOW_OPACITY")?,
+            _gtk_show_window_menu: intern("_GTK_SHOW_WINDOW_MENU")?,
         })
     }
 
@@ -178,6 +195,7 @@ impl Atoms {
         let supported = [
             self.net_supported,
             self.net_client_list,
+            self.net_client_list_stacking,
             self.net_number_of_desktops,
             self.net_current_desktop,
             self.net_active_window,
@@ -210,7 +228,11 @@ impl Atoms {
             self._net_wm_state_above,
             self._net_wm_state_below,
             self._net_wm_state_demands_attention,
+            self._net_wm_state_focused,
+            self._net_wm_ping,
             self.net_frame_extents,
+            self._net_request_frame_extents,
+            self.net_workarea,
             self._net_wm_allowed_actions,
             self._net_wm_action_move,
             self._net_wm_action_resize,
@@ -226,8 +248,10 @@ impl Atoms {
             self._net_wm_pid,
             self._net_desktop_viewport,
             self._net_desktop_names,
+            self._gtk_show_window_menu,
             self._net_wm_strut,
             self._net_wm_strut_partial,
+            self._net_wm_opaque_region,
         ];
 
         conn.change_property32(
@@ -277,22 +301,19 @@ impl Atoms {
         Ok(())
     }
 
-    /// Update _NET_FRAME_EXTENTS for a window
+    /// Update _NET_FRAME_EXTENTS ([left, right, top, bottom]) for a window
     pub fn update_frame_extents<C: Connection>(
         &self,
         conn: &C,
         window: Window,
-        left: u32,
-        right: u32,
-        top: u32,
-        bottom: u32,
+        extents: [u32; 4],
     ) -> Result<()> {
         conn.change_property32(
             PropMode::REPLACE,
             window,
             self.net_frame_extents,
             AtomEnum::CARDINAL,
-            &[left, right, top, bottom],
+            &extents,
         )?;
         Ok(())
     }
@@ -370,6 +391,33 @@ impl Atoms {
         Ok(false)
     }
 
+    /// Get the WM_PROTOCOLS a window participates in
+    pub fn get_protocols<C: Connection>(
+        &self,
+        conn: &C,
+        window: Window,
+    ) -> Result<Vec<Atom>> {
+        let reply = conn.get_property(false, window, self._wm_protocols, AtomEnum::ATOM, 0, 1024)?.reply()?;
+        Ok(reply.value32().map(|value32| value32.collect()).unwrap_or_default())
+    }
+
+    /// Send a _NET_WM_PING to a window; the client echoes it back to the root window
+    pub fn send_ping<C: Connection>(
+        &self,
+        conn: &C,
+        window: Window,
+        timestamp: u32,
+    ) -> Result<()> {
+        let event = ClientMessageEvent::new(
+            32,
+            window,
+            self._wm_protocols,
+            [self._net_wm_ping, timestamp, window, 0, 0],
+        );
+        conn.send_event(false, window, EventMask::NO_EVENT, event)?;
+        Ok(())
+    }
+
     /// Send WM_DELETE_WINDOW message to close a window gracefully
     pub fn send_delete_window<C: Connection>(
         &self,
@@ -427,6 +475,47 @@ impl Atoms {
         Ok(vec![])
     }
     
+    /// Get _NET_WM_OPAQUE_REGION of a window: rectangles in window
+    /// coordinates that are fully opaque even though it has an alpha channel
+    pub fn get_opaque_region<C: Connection>(
+        &self,
+        conn: &C,
+        window: Window,
+    ) -> Result<Option<Vec<crate::shared::Geometry>>> {
+        let reply = conn.get_property(false, window, self._net_wm_opaque_region, AtomEnum::CARDINAL, 0, 4096)?.reply()?;
+        let Some(values) = reply.value32() else { return Ok(None) };
+        let values: Vec<u32> = values.collect();
+        Ok(Some(
+            values
+                .chunks_exact(4)
+                .filter(|r| r[2] > 0 && r[3] > 0)
+                .map(|r| crate::shared::Geometry::new(r[0] as i32, r[1] as i32, r[2], r[3]))
+                .collect(),
+        ))
+    }
+    
+    /// Get _NET_WM_STATE property for a window
+    /// Returns a vector of state atoms
+    pub fn get_window_state<C: Connection>(
+        &self,
+        conn: &C,
+        window: Window,
+    ) -> Result<Vec<Atom>> {
+        if let Ok(reply) = conn.get_property(
+            false,
+            window,
+            self.net_wm_state,
+            AtomEnum::ATOM,
+            0,
+            1024,
+        )?.reply()
+            && let Some(value32) = reply.value32()
+        {
+            return Ok(value32.collect());
+        }
+        Ok(vec![])
+    }
+    
     /// Check if a window has _NET_WM_BYPASS_COMPOSITOR set to 1
     /// Returns true if the window requests compositor bypass
     pub fn check_bypass_compositor<C: Connection>(
@@ -0,0 +1,256 @@
+//! Workspace overview ("expo")
+//!
+//! Super+E (or `SetExpo` over IPC) shows every workspace at once as a grid
+//! of zoomed-out desktops on the focused monitor. Clicking a workspace (or
+//! a window on it) switches there; dragging a window onto another
+//! workspace moves it there; the arrow keys, Return, 1-9 and Escape work
+//! from the keyboard. The WM lays the grid out and holds the pointer and
+//! keyboard while it is shown, the compositor draws it (windows of other
+//! workspaces from the snapshot taken when they were hidden), and the shell
+//! may add workspace names on top (`WmEvent::ExpoChanged`).
+
+use crate::compositor::expo::{ExpoCell, ExpoScene};
+use crate::shared::Geometry;
+
+const XK_ESCAPE: u32 = 0xff1b;
+const XK_RETURN: u32 = 0xff0d;
+const XK_LEFT: u32 = 0xff51;
+const XK_UP: u32 = 0xff52;
+const XK_RIGHT: u32 = 0xff53;
+const XK_DOWN: u32 = 0xff54;
+const XK_1: u32 = 0x0031;
+const XK_9: u32 = 0x0039;
+
+/// Space around and between the workspaces (px)
+const SPACING: i32 = 32;
+/// Pointer travel that turns a press on a window into a drag (px)
+const DRAG_THRESHOLD: i32 = 6;
+
+/// A window in the overview
+#[derive(Debug, Clone, Copy)]
+pub struct ExpoWindow {
+    pub client_id: u32,
+    /// Window the compositor draws (the frame of a framed client)
+    pub composite_id: u32,
+    /// Workspace it is on; None for sticky windows, drawn on every one
+    pub workspace: Option<u32>,
+    /// Frame geometry in root coordinates
+    pub frame: Geometry,
+}
+
+/// A window picked up with the pointer
+#[derive(Debug, Clone, Copy)]
+struct Dragged {
+    client_id: u32,
+    /// Where it was pressed and where the pointer is now
+    press: (i32, i32),
+    pointer: (i32, i32),
+    moved: bool,
+}
+
+/// What an input event does in the overview
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpoStep {
+    /// Switch to the workspace (focusing a window on it) and leave
+    Switch { workspace: u32, focus: Option<u32> },
+    /// Move a window to another workspace, staying in the overview
+    MoveWindow { id: u32, workspace: u32 },
+    /// Leave without switching
+    Cancel,
+    /// The picture changed (highlight or a dragged window)
+    Redraw,
+    Ignore,
+}
+
+/// State of the overview
+#[derive(Debug, Default)]
+pub struct Expo {
+    pub active: bool,
+    /// Monitor the grid covers
+    area: Geometry,
+    /// Root coordinates to overview coordinates
+    scale: f64,
+    /// Rectangle of each workspace, by number
+    cells: Vec<Geometry>,
+    /// Windows bottom to top
+    windows: Vec<ExpoWindow>,
+    current: u32,
+    /// Workspace highlighted by the pointer or the arrow keys
+    selected: Option<u32>,
+    dragged: Option<Dragged>,
+}
+
+impl Expo {
+    /// Lay the workspaces out as a grid on `area` (`screen` is the size of
+    /// the root window, which every workspace spans)
+    pub fn start(&mut self, area: Geometry, screen: (u32, u32), count: u32, current: u32, windows: Vec<ExpoWindow>) {
+        let count = count.max(1);
+        let columns = (count as f64).sqrt().ceil() as u32;
+        let rows = count.div_ceil(columns);
+        let slot_width = (area.width as i32 - SPACING * (columns as i32 + 1)).max(1) as f64 / columns as f64;
+        let slot_height = (area.height as i32 - SPACING * (rows as i32 + 1)).max(1) as f64 / rows as f64;
+        let scale = (slot_width / screen.0.max(1) as f64).min(slot_height / screen.1.max(1) as f64);
+        let (width, height) = ((screen.0 as f64 * scale) as u32, (screen.1 as f64 * scale) as u32);
+        // Centre the whole grid on the monitor
+        let grid_width = columns as i32 * width as i32 + (columns as i32 - 1) * SPACING;
+        let grid_height = rows as i32 * height as i32 + (rows as i32 - 1) * SPACING;
+        let (left, top) = (area.x + (area.width as i32 - grid_width) / 2, area.y + (area.height as i32 - grid_height) / 2);
+        self.cells = (0..count)
+            .map(|index| {
+                let (column, row) = ((index % columns) as i32, (index / columns) as i32);
+                Geometry::new(left + column * (width as i32 + SPACING), top + row * (height as i32 + SPACING), width, height)
+            })
+            .collect();
+        self.area = area;
+        self.scale = scale;
+        self.windows = windows;
+        self.current = current.min(count - 1);
+        self.selected.get_or_insert(self.current);
+        self.dragged = None;
+        self.active = true;
+    }
+
+    /// Leave the overview
+    pub fn stop(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Rectangle of each workspace, by number
+    pub fn cells(&self) -> &[Geometry] {
+        &self.cells
+    }
+
+    fn cell_at(&self, x: i32, y: i32) -> Option<u32> {
+        self.cells.iter().position(|c| contains(c, x, y)).map(|index| index as u32)
+    }
+
+    /// A window's rectangle in a workspace's cell
+    fn thumbnail(&self, window: &ExpoWindow, workspace: u32) -> Geometry {
+        let cell = self.cells[workspace as usize];
+        let f = window.frame;
+        Geometry::new(
+            cell.x + (f.x as f64 * self.scale) as i32,
+            cell.y + (f.y as f64 * self.scale) as i32,
+            ((f.width as f64 * self.scale) as u32).max(1),
+            ((f.height as f64 * self.scale) as u32).max(1),
+        )
+    }
+
+    /// Topmost window under a point and the workspace it is shown on
+    fn window_at(&self, x: i32, y: i32) -> Option<(&ExpoWindow, u32)> {
+        let workspace = self.cell_at(x, y)?;
+        self.windows
+            .iter()
+            .rev()
+            .filter(|w| w.workspace.is_none_or(|ws| ws == workspace))
+            .find(|w| contains(&self.thumbnail(w, workspace), x, y))
+            .map(|w| (w, workspace))
+    }
+
+    /// A button went down: pick up the window under it
+    pub fn press(&mut self, x: i32, y: i32) {
+        // Sticky windows are on every workspace already
+        self.dragged = self.window_at(x, y).filter(|(w, _)| w.workspace.is_some()).map(|(w, _)| Dragged {
+            client_id: w.client_id,
+            press: (x, y),
+            pointer: (x, y),
+            moved: false,
+        });
+    }
+
+    /// The pointer moved: highlight the workspace under it, move a dragged window
+    pub fn motion(&mut self, x: i32, y: i32) -> ExpoStep {
+        let mut changed = false;
+        if let Some(dragged) = self.dragged.as_mut() {
+            let (dx, dy) = (x - dragged.press.0, y - dragged.press.1);
+            dragged.moved |= dx.abs() > DRAG_THRESHOLD || dy.abs() > DRAG_THRESHOLD;
+            dragged.pointer = (x, y);
+            changed = dragged.moved;
+        }
+        let selected = self.cell_at(x, y);
+        if selected.is_some() && selected != self.selected {
+            self.selected = selected;
+            changed = true;
+        }
+        if changed { ExpoStep::Redraw } else { ExpoStep::Ignore }
+    }
+
+    /// The button came up: drop a dragged window, or switch to the clicked workspace
+    pub fn release(&mut self, x: i32, y: i32) -> ExpoStep {
+        let target = self.cell_at(x, y);
+        if let Some(dragged) = self.dragged.take()
+            && dragged.moved
+        {
+            let from = self.windows.iter().find(|w| w.client_id == dragged.client_id).and_then(|w| w.workspace);
+            return match target {
+                Some(workspace) if Some(workspace) != from => ExpoStep::MoveWindow { id: dragged.client_id, workspace },
+                // Back to where it was
+                _ => ExpoStep::Redraw,
+            };
+        }
+        match target {
+            Some(workspace) => ExpoStep::Switch { workspace, focus: self.window_at(x, y).map(|(w, _)| w.client_id) },
+            None => ExpoStep::Cancel,
+        }
+    }
+
+    /// What a key does: arrows move the highlight, Return or a digit switches
+    pub fn key(&mut self, keysym: u32) -> ExpoStep {
+        let count = self.cells.len() as u32;
+        let columns = (count as f64).sqrt().ceil() as u32;
+        let selected = self.selected.unwrap_or(self.current);
+        let next = match keysym {
+            XK_ESCAPE => return ExpoStep::Cancel,
+            XK_RETURN => return ExpoStep::Switch { workspace: selected, focus: None },
+            XK_1..=XK_9 if keysym - XK_1 < count => return ExpoStep::Switch { workspace: keysym - XK_1, focus: None },
+            XK_LEFT if !selected.is_multiple_of(columns) => selected - 1,
+            XK_RIGHT if selected % columns + 1 < columns && selected + 1 < count => selected + 1,
+            XK_UP if selected >= columns => selected - columns,
+            XK_DOWN if selected + columns < count => selected + columns,
+            _ => return ExpoStep::Ignore,
+        };
+        self.selected = Some(next);
+        ExpoStep::Redraw
+    }
+
+    /// What the compositor draws
+    pub fn scene(&self) -> ExpoScene {
+        let cells = self
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(index, &rect)| ExpoCell {
+                rect,
+                current: index as u32 == self.current,
+                selected: Some(index as u32) == self.selected,
+            })
+            .collect();
+        let mut windows = Vec::new();
+        let mut on_top = None;
+        for window in &self.windows {
+            let workspaces = match window.workspace {
+                Some(workspace) if (workspace as usize) < self.cells.len() => workspace..workspace + 1,
+                Some(_) => continue,
+                None => 0..self.cells.len() as u32,
+            };
+            for workspace in workspaces {
+                let mut rect = self.thumbnail(window, workspace);
+                match self.dragged.filter(|d| d.moved && d.client_id == window.client_id) {
+                    Some(dragged) => {
+                        rect.x += dragged.pointer.0 - dragged.press.0;
+                        rect.y += dragged.pointer.1 - dragged.press.1;
+                        on_top = Some((window.composite_id, rect));
+                    }
+                    None => windows.push((window.composite_id, rect)),
+                }
+            }
+        }
+        // The dragged window over everything else
+        windows.extend(on_top);
+        ExpoScene { area: self.area, cells, windows }
+    }
+}
+
+fn contains(rect: &Geometry, x: i32, y: i32) -> bool {
+    x >= rect.x && x < rect.x + rect.width as i32 && y >= rect.y && y < rect.y + rect.height as i32
+}
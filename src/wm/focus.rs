@@ -4,7 +4,9 @@
 //! This matches xfwm4's focus management system.
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 use tracing::{debug, info, warn};
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::*;
@@ -28,6 +30,21 @@ pub enum FocusPolicy {
     SloppyFocus,
 }
 
+impl FocusPolicy {
+    /// Parse a `focus_mode` setting; unknown values mean click to focus
+    pub fn parse(text: &str) -> Self {
+        match text {
+            "focus_follows_mouse" => Self::FocusFollowsMouse,
+            "sloppy_focus" => Self::SloppyFocus,
+            "click_to_focus" => Self::ClickToFocus,
+            other => {
+                warn!("Unknown focus mode {:?}, using click_to_focus", other);
+                Self::ClickToFocus
+            }
+        }
+    }
+}
+
 /// Source of focus request (for focus stealing prevention)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FocusSource {
@@ -39,6 +56,28 @@ pub enum FocusSource {
     Other,
 }
 
+/// Clicks that raise a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RaiseOnClick {
+    /// A click anywhere in the window, its contents included
+    #[default]
+    Anywhere,
+    /// Only clicks on the titlebar and frame; clicking inside focuses
+    Titlebar,
+}
+
+/// How a window came to be focused, which decides whether it is raised
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusCause {
+    /// Keyboard, pager, application or decoration click: raised at once
+    Explicit,
+    /// The pointer entered it (focus follows mouse): auto-raise
+    Pointer,
+    /// A click inside its contents
+    Click,
+}
+
 /// Focus manager
 pub struct FocusManager {
     /// Currently focused window
@@ -61,6 +100,18 @@ pub struct FocusManager {
     
     /// Focus stealing delay (milliseconds)
     pub focus_stealing_delay: u32,
+    
+    /// Raise windows focused by the pointer
+    pub auto_raise: bool,
+    
+    /// How long the pointer has to stay before auto-raise (zero: at once)
+    pub raise_delay: Duration,
+    
+    /// Clicks that raise a window
+    pub raise_on_click: RaiseOnClick,
+    
+    /// Window waiting for auto-raise and when
+    pending_raise: Option<(u32, Instant)>,
 }
 
 impl FocusManager {
@@ -74,9 +125,40 @@ impl FocusManager {
             prevent_focus_stealing: true,
             last_user_time: 0,
             focus_stealing_delay: 250, // 250ms default
+            auto_raise: true,
+            raise_delay: Duration::ZERO,
+            raise_on_click: RaiseOnClick::Anywhere,
+            pending_raise: None,
         }
     }
     
+    /// Whether a window just focused is raised now; with an auto-raise
+    /// delay, the raise is scheduled instead (see `raise_deadline`)
+    pub fn raise_now(&mut self, window: u32, cause: FocusCause) -> bool {
+        // Focus moved on: a pending raise is stale
+        self.pending_raise = None;
+        match cause {
+            FocusCause::Explicit => true,
+            FocusCause::Click => self.raise_on_click == RaiseOnClick::Anywhere,
+            FocusCause::Pointer if !self.auto_raise => false,
+            FocusCause::Pointer if self.raise_delay.is_zero() => true,
+            FocusCause::Pointer => {
+                self.pending_raise = Some((window, Instant::now() + self.raise_delay));
+                false
+            }
+        }
+    }
+    
+    /// When the pending auto-raise is due
+    pub fn raise_deadline(&self) -> Option<Instant> {
+        self.pending_raise.map(|(_, deadline)| deadline)
+    }
+    
+    /// Window whose auto-raise is due, if any
+    pub fn take_due_raise(&mut self) -> Option<u32> {
+        self.pending_raise.take_if(|(_, deadline)| *deadline <= Instant::now()).map(|(window, _)| window)
+    }
+    
     /// Set focus on a window
     pub fn set_focus(
         &mut self,
@@ -0,0 +1,45 @@
+//! Decoration sizes and client/frame geometry conversions
+//!
+//! A decorated client is reparented just below the titlebar of its frame
+//! window, and the frame window has an X border. For a client at (x, y) the
+//! frame window therefore sits at (x, y - titlebar) and its outer edge,
+//! border included, at (x - border, y - titlebar - border).
+
+use crate::shared::Geometry;
+
+/// Sizes of a window's decorations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameMetrics {
+    pub titlebar: u32,
+    pub border: u32,
+}
+
+impl FrameMetrics {
+    /// Undecorated (and fullscreen) windows
+    pub const NONE: FrameMetrics = FrameMetrics { titlebar: 0, border: 0 };
+
+    /// Sizes of the frames created with the current decoration settings
+    pub fn from_config(decorations: &crate::config::WindowDecorationConfig) -> Self {
+        Self { titlebar: decorations.titlebar_height as u32, border: decorations.border_width as u32 }
+    }
+
+    /// The frame window (inside its border) around a client
+    pub fn frame_geometry(&self, client: Geometry) -> Geometry {
+        Geometry::new(client.x, client.y - self.titlebar as i32, client.width, client.height + self.titlebar)
+    }
+
+    /// The client whose frame, borders included, fills `outer` (a tile, the work area)
+    pub fn client_geometry(&self, outer: Geometry) -> Geometry {
+        Geometry::new(
+            outer.x + self.border as i32,
+            outer.y + (self.titlebar + self.border) as i32,
+            outer.width.saturating_sub(2 * self.border),
+            outer.height.saturating_sub(self.titlebar + 2 * self.border),
+        )
+    }
+
+    /// `_NET_FRAME_EXTENTS`: left, right, top, bottom
+    pub fn extents(&self) -> [u32; 4] {
+        [self.border, self.border, self.titlebar + self.border, self.border]
+    }
+}
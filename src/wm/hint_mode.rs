@@ -0,0 +1,79 @@
+//! Window hint mode
+//!
+//! Super+F labels every visible window with a letter drawn by the compositor
+//! over its center; typing the letter focuses and raises that window, and
+//! Escape leaves the mode without choosing one. The WM holds a keyboard grab
+//! while the labels are shown.
+
+use std::collections::HashMap;
+
+use crate::compositor::hints::Hint;
+use crate::wm::client::Client;
+
+const XK_ESCAPE: u32 = 0xff1b;
+
+/// What a key does in hint mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HintStep {
+    /// Focus this window and leave the mode
+    Select(u32),
+    /// Leave the mode
+    Cancel,
+    /// Not a label (e.g. a modifier); keep waiting
+    Ignore,
+}
+
+/// Label keys, home row first so the most likely targets are the easiest to type
+const LABELS: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+/// Half the size of a one-letter label (px), to center it on the window
+const LABEL_HALF_SIZE: i32 = 16;
+
+/// State of the mode
+#[derive(Debug, Default)]
+pub struct HintMode {
+    pub active: bool,
+    /// Labelled windows, topmost first
+    targets: Vec<(char, u32)>,
+}
+
+impl HintMode {
+    /// Label the windows (visible ones in `stacking`, bottom to top)
+    pub fn start(&mut self, stacking: &[u32], visible: impl Fn(u32) -> bool) {
+        self.targets = LABELS.chars().zip(stacking.iter().rev().copied().filter(|&id| visible(id))).collect();
+        self.active = !self.targets.is_empty();
+    }
+
+    /// Leave the mode
+    pub fn stop(&mut self) {
+        self.active = false;
+        self.targets.clear();
+    }
+
+    /// What typing `keysym` does (labels match case-insensitively)
+    pub fn key(&self, keysym: u32) -> HintStep {
+        if keysym == XK_ESCAPE {
+            return HintStep::Cancel;
+        }
+        let Some(key) = char::from_u32(keysym).map(|c| c.to_ascii_lowercase()) else { return HintStep::Ignore };
+        match self.targets.iter().find(|&&(label, _)| label == key) {
+            Some(&(_, id)) => HintStep::Select(id),
+            None => HintStep::Ignore,
+        }
+    }
+
+    /// Labels at the centers of the windows
+    pub fn hints(&self, clients: &HashMap<u32, Client>) -> Vec<Hint> {
+        self.targets
+            .iter()
+            .filter_map(|&(label, id)| {
+                let frame = clients.get(&id)?.frame_geometry();
+                Some(Hint {
+                    x: frame.x + frame.width as i32 / 2 - LABEL_HALF_SIZE,
+                    y: frame.y + frame.height as i32 / 2 - LABEL_HALF_SIZE,
+                    label: label.to_ascii_uppercase().to_string(),
+                })
+            })
+            .collect()
+    }
+}
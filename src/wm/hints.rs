@@ -51,6 +51,125 @@ pub struct WmHints {
     pub window_group: Option<u32>,
 }
 
+/// XUrgencyHint flag in `WmHints::flags`
+pub const URGENCY_HINT: u32 = 1 << 8;
+
+/// `SizeHints::flags` bits (ICCCM 4.1.2.3)
+pub const P_MIN_SIZE: u32 = 1 << 4;
+pub const P_MAX_SIZE: u32 = 1 << 5;
+pub const P_RESIZE_INC: u32 = 1 << 6;
+pub const P_ASPECT: u32 = 1 << 7;
+pub const P_BASE_SIZE: u32 = 1 << 8;
+
+impl SizeHints {
+    /// Parse a WM_NORMAL_HINTS property (18 32-bit values)
+    pub fn from_values(values: &[u32]) -> Option<Self> {
+        let values: &[u32; 18] = values.get(..18)?.try_into().ok()?;
+        Some(SizeHints {
+            flags: values[0],
+            x: values[1] as i32,
+            y: values[2] as i32,
+            width: values[3],
+            height: values[4],
+            min_width: values[5],
+            min_height: values[6],
+            max_width: values[7],
+            max_height: values[8],
+            width_inc: values[9],
+            height_inc: values[10],
+            min_aspect_num: values[11],
+            min_aspect_den: values[12],
+            max_aspect_num: values[13],
+            max_aspect_den: values[14],
+            base_width: values[15],
+            base_height: values[16],
+            win_gravity: values[17] as u8,
+        })
+    }
+    
+    fn has(&self, flag: u32) -> bool {
+        self.flags & flag != 0
+    }
+    
+    /// Minimum size (base size if no minimum is given)
+    fn min_size(&self) -> (u32, u32) {
+        if self.has(P_MIN_SIZE) {
+            (self.min_width, self.min_height)
+        } else if self.has(P_BASE_SIZE) {
+            (self.base_width, self.base_height)
+        } else {
+            (0, 0)
+        }
+    }
+    
+    /// Size increments count from (minimum size if no base size is given)
+    fn base_size(&self) -> (u32, u32) {
+        if self.has(P_BASE_SIZE) {
+            (self.base_width, self.base_height)
+        } else if self.has(P_MIN_SIZE) {
+            (self.min_width, self.min_height)
+        } else {
+            (0, 0)
+        }
+    }
+    
+    /// Resize increments, if any is larger than a pixel (terminals)
+    fn increments(&self) -> Option<(u32, u32)> {
+        let (width_inc, height_inc) = (self.width_inc.max(1), self.height_inc.max(1));
+        (self.has(P_RESIZE_INC) && (width_inc > 1 || height_inc > 1)).then_some((width_inc, height_inc))
+    }
+    
+    /// Largest size no bigger than `width`x`height` the hints allow
+    ///
+    /// Sizes shrink to fit the aspect ratio and increments, so the result
+    /// fits the space it was computed for; only the minimum size can make it
+    /// larger.
+    pub fn constrain(&self, width: u32, height: u32) -> (u32, u32) {
+        let (min_width, min_height) = self.min_size();
+        let (min_width, min_height) = (min_width.max(1), min_height.max(1));
+        let (max_width, max_height) = if self.has(P_MAX_SIZE) {
+            (
+                if self.max_width > 0 { self.max_width.max(min_width) } else { u32::MAX },
+                if self.max_height > 0 { self.max_height.max(min_height) } else { u32::MAX },
+            )
+        } else {
+            (u32::MAX, u32::MAX)
+        };
+        let mut width = width.clamp(min_width, max_width);
+        let mut height = height.clamp(min_height, max_height);
+        let (base_width, base_height) = self.base_size();
+        
+        // Aspect ratio of the size beyond the base size: min_num/min_den <= width/height <= max_num/max_den
+        if self.has(P_ASPECT) && self.min_aspect_den > 0 && self.max_aspect_den > 0 {
+            let w = width.saturating_sub(base_width) as u64;
+            let h = height.saturating_sub(base_height) as u64;
+            let (min_num, min_den) = (self.min_aspect_num as u64, self.min_aspect_den as u64);
+            let (max_num, max_den) = (self.max_aspect_num as u64, self.max_aspect_den as u64);
+            if min_num > 0 && w * min_den < h * min_num {
+                // Too tall
+                height = base_height + (w * min_den / min_num) as u32;
+            } else if max_num > 0 && w * max_den > h * max_num {
+                // Too wide
+                width = base_width + (h * max_num / max_den) as u32;
+            }
+        }
+        
+        if let Some((width_inc, height_inc)) = self.increments() {
+            width = base_width + width.saturating_sub(base_width) / width_inc * width_inc;
+            height = base_height + height.saturating_sub(base_height) / height_inc * height_inc;
+        }
+        (width.max(min_width), height.max(min_height))
+    }
+    
+    /// Size in resize increments, e.g. (80, 24) for a terminal; None for
+    /// windows that resize by pixel
+    pub fn size_in_increments(&self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (width_inc, height_inc) = self.increments()?;
+        let (base_width, base_height) = self.base_size();
+        Some((width.saturating_sub(base_width) / width_inc, height.saturating_sub(base_height) / height_inc))
+    }
+}
+
 /// Hints manager
 pub struct HintsManager;
 
@@ -64,35 +183,14 @@ impl HintsManager {
         if let Ok(reply) = conn.get_property(
             false,
             window,
-            atoms._wm_size_hints,
+            atoms._wm_normal_hints,
             atoms._wm_size_hints,
             0,
             18, // XSizeHints has 18 32-bit values
         )?.reply() {
             if let Some(value32) = reply.value32() {
                 let values: Vec<u32> = value32.take(18).collect();
-                if values.len() >= 18 {
-                    return Ok(Some(SizeHints {
-                        flags: values[0],
-                        x: values[1] as i32,
-                        y: values[2] as i32,
-                        width: values[3],
-                        height: values[4],
-                        min_width: values[5],
-                        min_height: values[6],
-                        max_width: values[7],
-                        max_height: values[8],
-                        width_inc: values[9],
-                        height_inc: values[10],
-                        min_aspect_num: values[11],
-                        min_aspect_den: values[12],
-                        max_aspect_num: values[13],
-                        max_aspect_den: values[14],
-                        base_width: values[15],
-                        base_height: values[16],
-                        win_gravity: values[17] as u8,
-                    }));
-                }
+                return Ok(SizeHints::from_values(&values));
             }
         }
         Ok(None)
@@ -139,31 +237,7 @@ impl HintsManager {
         geometry: &Geometry,
     ) -> Geometry {
         let mut new_geom = *geometry;
-        
-        // Apply min/max size constraints
-        if (hints.flags & (1 << 4)) != 0 { // PMinSize
-            new_geom.width = new_geom.width.max(hints.min_width);
-            new_geom.height = new_geom.height.max(hints.min_height);
-        }
-        
-        if (hints.flags & (1 << 5)) != 0 { // PMaxSize
-            new_geom.width = new_geom.width.min(hints.max_width);
-            new_geom.height = new_geom.height.min(hints.max_height);
-        }
-        
-        // Apply size increments
-        if (hints.flags & (1 << 8)) != 0 && hints.width_inc > 0 { // PResizeInc
-            let base = if (hints.flags & (1 << 9)) != 0 { hints.base_width } else { 0 };
-            let diff = new_geom.width.saturating_sub(base);
-            new_geom.width = base + (diff / hints.width_inc) * hints.width_inc;
-        }
-        
-        if (hints.flags & (1 << 8)) != 0 && hints.height_inc > 0 { // PResizeInc
-            let base = if (hints.flags & (1 << 9)) != 0 { hints.base_height } else { 0 };
-            let diff = new_geom.height.saturating_sub(base);
-            new_geom.height = base + (diff / hints.height_inc) * hints.height_inc;
-        }
-        
+        (new_geom.width, new_geom.height) = hints.constrain(geometry.width, geometry.height);
         new_geom
     }
 }
@@ -174,5 +248,34 @@ impl Default for HintsManager {
     }
 }
 
-
-
+/// Clear the XUrgencyHint flag in a window's WM_HINTS (other fields untouched)
+pub fn clear_urgency_hint(
+    conn: &RustConnection,
+    window: u32,
+) -> Result<()> {
+    let reply = conn.get_property(
+        false,
+        window,
+        AtomEnum::WM_HINTS,
+        AtomEnum::WM_HINTS,
+        0,
+        9,
+    )?.reply()?;
+    let Some(value32) = reply.value32() else {
+        return Ok(());
+    };
+    let mut values: Vec<u32> = value32.collect();
+    if let Some(flags) = values.first_mut()
+        && *flags & URGENCY_HINT != 0
+    {
+        *flags &= !URGENCY_HINT;
+        conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_HINTS,
+            AtomEnum::WM_HINTS,
+            &values,
+        )?;
+    }
+    Ok(())
+}
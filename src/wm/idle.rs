@@ -0,0 +1,131 @@
+//! User idle detection (XSync IDLETIME counter)
+//!
+//! The server counts the milliseconds since the last input in its IDLETIME
+//! system counter. One alarm per configured threshold fires when the count
+//! passes it; once the user is idle, another alarm fires when the count
+//! drops back (any input), and the threshold alarms are armed again for the
+//! next idle period. Alarms with a zero delta go inactive after firing, so
+//! each reports once.
+
+use anyhow::{Context, Result};
+use tracing::{debug, info};
+use x11rb::connection::Connection;
+use x11rb::protocol::sync::{
+    Alarm, AlarmNotifyEvent, ChangeAlarmAux, ConnectionExt as _, Counter, CreateAlarmAux, Int64, ALARMSTATE, TESTTYPE, VALUETYPE,
+};
+use x11rb::rust_connection::RustConnection;
+
+/// Alarms on the IDLETIME counter
+#[derive(Debug, Default)]
+pub struct IdleWatcher {
+    /// IDLETIME counter (0: the server has none)
+    counter: Counter,
+    /// Threshold alarms and their idle time (ms), shortest first
+    thresholds: Vec<(Alarm, u64)>,
+    /// Alarm for input after an idle period (0 until first needed)
+    reset: Alarm,
+    /// A threshold has passed since the last input
+    idle: bool,
+}
+
+impl IdleWatcher {
+    /// Find the IDLETIME counter
+    pub fn init(&mut self, conn: &RustConnection) -> Result<()> {
+        let version = conn.sync_initialize(3, 1)?.reply().context("SYNC extension missing")?;
+        let counters = conn.sync_list_system_counters()?.reply()?;
+        let counter = counters
+            .counters
+            .iter()
+            .find(|counter| counter.name == b"IDLETIME")
+            .context("no IDLETIME counter")?;
+        debug!("SYNC {}.{}, IDLETIME counter {}", version.major_version, version.minor_version, counter.counter);
+        self.counter = counter.counter;
+        Ok(())
+    }
+
+    /// Replace the thresholds (seconds without input)
+    pub fn set_thresholds(&mut self, conn: &RustConnection, seconds: &[u32]) -> Result<()> {
+        if self.counter == 0 {
+            return Ok(());
+        }
+        let mut thresholds: Vec<u64> = seconds.iter().filter(|&&s| s > 0).map(|&s| u64::from(s) * 1000).collect();
+        thresholds.sort_unstable();
+        thresholds.dedup();
+        if thresholds.iter().eq(self.thresholds.iter().map(|(_, ms)| ms)) {
+            return Ok(());
+        }
+        for (alarm, _) in self.thresholds.drain(..) {
+            conn.sync_destroy_alarm(alarm)?;
+        }
+        if self.reset != 0 {
+            conn.sync_destroy_alarm(self.reset)?;
+            self.reset = 0;
+        }
+        self.idle = false;
+        for ms in thresholds {
+            let alarm = conn.generate_id()?;
+            let aux = CreateAlarmAux::new()
+                .counter(self.counter)
+                .value_type(VALUETYPE::ABSOLUTE)
+                .value(to_int64(ms))
+                .test_type(TESTTYPE::POSITIVE_COMPARISON)
+                .delta(to_int64(0))
+                .events(1);
+            conn.sync_create_alarm(alarm, &aux)?;
+            self.thresholds.push((alarm, ms));
+        }
+        info!("Idle thresholds: {:?} ms", self.thresholds.iter().map(|(_, ms)| ms).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    /// Handle one of our alarms; returns whether the user is now idle and
+    /// for how long (ms) when that changed or another threshold passed
+    pub fn alarm_notify(&mut self, conn: &RustConnection, event: &AlarmNotifyEvent) -> Result<Option<(bool, u64)>> {
+        if event.state == ALARMSTATE::DESTROYED {
+            return Ok(None);
+        }
+        let idle_ms = from_int64(event.counter_value);
+        if event.alarm == self.reset && self.reset != 0 {
+            if !self.idle {
+                return Ok(None);
+            }
+            self.idle = false;
+            for &(alarm, ms) in &self.thresholds {
+                conn.sync_change_alarm(alarm, &ChangeAlarmAux::new().value(to_int64(ms)))?;
+            }
+            debug!("User active again");
+            return Ok(Some((false, idle_ms)));
+        }
+        if !self.thresholds.iter().any(|(alarm, _)| *alarm == event.alarm) {
+            return Ok(None);
+        }
+        if !self.idle {
+            self.idle = true;
+            // Fires on the next input, when the count drops below its current value
+            let value = to_int64(idle_ms.saturating_sub(1));
+            if self.reset == 0 {
+                self.reset = conn.generate_id()?;
+                let aux = CreateAlarmAux::new()
+                    .counter(self.counter)
+                    .value_type(VALUETYPE::ABSOLUTE)
+                    .value(value)
+                    .test_type(TESTTYPE::NEGATIVE_COMPARISON)
+                    .delta(to_int64(0))
+                    .events(1);
+                conn.sync_create_alarm(self.reset, &aux)?;
+            } else {
+                conn.sync_change_alarm(self.reset, &ChangeAlarmAux::new().value(value))?;
+            }
+        }
+        debug!("User idle for {} ms", idle_ms);
+        Ok(Some((true, idle_ms)))
+    }
+}
+
+fn to_int64(value: u64) -> Int64 {
+    Int64 { hi: (value >> 32) as i32, lo: value as u32 }
+}
+
+fn from_int64(value: Int64) -> u64 {
+    (((value.hi as i64) << 32) | i64::from(value.lo)).max(0) as u64
+}
@@ -0,0 +1,304 @@
+//! Key remapping (`[input.keyboard]`)
+//!
+//! XKB options (`ctrl:nocaps`, `compose:ralt`, ...) are passed to
+//! setxkbmap, which compiles them into the server's keymap. Remap rules
+//! replace the keysym of a key in the core keymap, the way xmodmap does,
+//! and move the key to the modifier its new keysym stands for:
+//!
+//! ```toml
+//! [input.keyboard]
+//! options = ["compose:ralt"]
+//! remap = { caps_lock = "escape", escape = "caps_lock" }
+//! ```
+//!
+//! Rules are resolved against the keymap as it was before any of them, so
+//! two keys can be swapped. A keyboard plugged in later starts with the
+//! server's default keymap, so both are applied again when one appears
+//! (XI2 hierarchy events).
+//!
+//! setxkbmap runs in the background so the event loop doesn't wait for it
+//! to compile the keymap; the rules are applied to the new keymap once it
+//! exits (`options_done`, then `options_set`).
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::sync::oneshot;
+use tracing::{debug, info, warn};
+use x11rb::connection::Connection;
+use x11rb::protocol::xinput::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{ConnectionExt as _, MappingStatus, Window};
+use x11rb::rust_connection::RustConnection;
+
+use crate::config::KeyboardConfig;
+
+/// XI2 device id of all devices
+const ALL_DEVICES: u16 = 0;
+
+/// Keysym of a key name in a remap rule: a printable character or a named key
+fn keysym_for_name(name: &str) -> Option<u32> {
+    let name = name.to_ascii_lowercase();
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next())
+        && c.is_ascii_graphic()
+    {
+        return Some(u32::from(c));
+    }
+    Some(match name.as_str() {
+        "escape" | "esc" => 0xff1b,
+        "caps_lock" | "capslock" => 0xffe5,
+        "control_l" | "ctrl" | "control" => 0xffe3,
+        "control_r" => 0xffe4,
+        "shift_l" | "shift" => 0xffe1,
+        "shift_r" => 0xffe2,
+        "alt_l" | "alt" => 0xffe9,
+        "alt_r" => 0xffea,
+        "super_l" | "super" => 0xffeb,
+        "super_r" => 0xffec,
+        "num_lock" => 0xff7f,
+        "backspace" => 0xff08,
+        "tab" => 0xff09,
+        "return" | "enter" => 0xff0d,
+        "space" => 0x0020,
+        "delete" => 0xffff,
+        "insert" => 0xff63,
+        "home" => 0xff50,
+        "end" => 0xff57,
+        "page_up" => 0xff55,
+        "page_down" => 0xff56,
+        "menu" => 0xff67,
+        "compose" | "multi_key" => 0xff20,
+        "print" => 0xff61,
+        "scroll_lock" => 0xff14,
+        "pause" => 0xff13,
+        _ => return None,
+    })
+}
+
+/// Core modifier (0: Shift ... 7: Mod5) a keysym belongs to
+fn modifier_for(keysym: u32) -> Option<usize> {
+    Some(match keysym {
+        0xffe1 | 0xffe2 => 0,
+        0xffe5 => 1,
+        0xffe3 | 0xffe4 => 2,
+        0xffe9 | 0xffea => 3,
+        0xff7f => 4,
+        0xffeb | 0xffec => 6,
+        _ => return None,
+    })
+}
+
+/// A key the rules changed
+#[derive(Debug)]
+struct RemappedKey {
+    keycode: u8,
+    original: Vec<u32>,
+    remapped: Vec<u32>,
+}
+
+/// Applied XKB options and remap rules
+#[derive(Debug, Default)]
+pub struct KeyRemap {
+    /// Options last passed to setxkbmap (None: never run)
+    options: Option<Vec<String>>,
+    /// Remap rules as keysyms, sorted
+    rules: Vec<(u32, u32)>,
+    /// Keys changed by the rules
+    keys: Vec<RemappedKey>,
+    /// Modifier map before and after the rules, if they changed it
+    modifiers: Option<(Vec<u8>, Vec<u8>)>,
+    /// Outcome of the latest setxkbmap run, until it is taken
+    options_run: Option<oneshot::Receiver<Result<()>>>,
+    /// Held by the running setxkbmap, so runs finish in the order started
+    options_lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl KeyRemap {
+    /// Watch for keyboards being plugged in
+    pub fn init(&mut self, conn: &RustConnection, root: Window) -> Result<()> {
+        conn.xinput_xi_query_version(2, 0)?.reply()?;
+        let mask = xinput::EventMask { deviceid: ALL_DEVICES, mask: vec![xinput::XIEventMask::HIERARCHY] };
+        conn.xinput_xi_select_events(root, &[mask])?;
+        Ok(())
+    }
+
+    /// Apply the options and rules (at startup and after a config reload)
+    pub fn apply(&mut self, conn: &RustConnection, config: &KeyboardConfig) -> Result<()> {
+        let mut rules: Vec<(u32, u32)> = config.remap.iter()
+            .filter_map(|(from, to)| match (keysym_for_name(from), keysym_for_name(to)) {
+                (Some(from), Some(to)) => Some((from, to)),
+                _ => {
+                    warn!("Ignoring key remap {} = {}: unknown key name", from, to);
+                    None
+                }
+            })
+            .collect();
+        rules.sort_unstable();
+        // Leave the keymap alone unless options were configured at some point
+        let options_changed = match &self.options {
+            Some(options) => *options != config.options,
+            None => !config.options.is_empty(),
+        };
+        if options_changed {
+            // The rules go on the new keymap once setxkbmap is done
+            self.options = Some(config.options.clone());
+            self.rules = rules;
+            self.set_xkb_options(&config.options);
+            return Ok(());
+        }
+        if self.options_run.is_some() {
+            self.rules = rules;
+            return Ok(());
+        }
+        if rules == self.rules {
+            return Ok(());
+        }
+        self.restore(conn)?;
+        self.rules = rules;
+        self.remap(conn)
+    }
+
+    /// Wait for the latest setxkbmap run to finish (never returns while
+    /// none is running)
+    pub async fn options_done(&mut self) -> Result<()> {
+        let Some(run) = self.options_run.as_mut() else {
+            return std::future::pending().await;
+        };
+        let result = run.await.unwrap_or_else(|_| Err(anyhow::anyhow!("setxkbmap was not run")));
+        self.options_run = None;
+        result
+    }
+
+    /// Apply the rules after setxkbmap finished with `result`
+    pub fn options_set(&mut self, conn: &RustConnection, result: Result<()>) -> Result<()> {
+        match result {
+            // The keymap is new, the old rules are gone with it
+            Ok(()) => {
+                self.keys.clear();
+                self.modifiers = None;
+            }
+            Err(e) => {
+                warn!("Failed to set XKB options: {:#}", e);
+                self.restore(conn)?;
+            }
+        }
+        self.remap(conn)
+    }
+
+    /// Account an XI2 hierarchy change; a new keyboard gets the options and rules
+    pub fn hierarchy_changed(&mut self, conn: &RustConnection, event: &xinput::HierarchyEvent) -> Result<()> {
+        let added = event.infos.iter().any(|info| {
+            info.type_ == xinput::DeviceType::SLAVE_KEYBOARD
+                && info.flags.intersects(xinput::HierarchyMask::SLAVE_ADDED | xinput::HierarchyMask::SLAVE_ATTACHED)
+        });
+        if !added {
+            return Ok(());
+        }
+        match &self.options {
+            Some(options) if !options.is_empty() => {
+                debug!("Keyboard added, applying XKB options again");
+                let options = options.clone();
+                self.set_xkb_options(&options);
+                Ok(())
+            }
+            // Writing the keys again hands them to the new keyboard too
+            _ => {
+                debug!("Keyboard added, applying {} key remaps again", self.keys.len());
+                self.write(conn, false)
+            }
+        }
+    }
+
+    /// Start replacing the XKB options of the keymap with `options`
+    fn set_xkb_options(&mut self, options: &[String]) {
+        info!("Setting XKB options {:?}", options);
+        // An empty option clears the current ones
+        let mut command = tokio::process::Command::new("setxkbmap");
+        command.args(["-option", ""]);
+        for option in options {
+            command.args(["-option", option]);
+        }
+        let (done, run) = oneshot::channel();
+        let lock = self.options_lock.clone();
+        tokio::spawn(async move {
+            let _running = lock.lock().await;
+            let result = match command.status().await.context("failed to run setxkbmap") {
+                Ok(status) if !status.success() => Err(anyhow::anyhow!("setxkbmap failed ({})", status)),
+                Ok(_) => Ok(()),
+                Err(e) => Err(e),
+            };
+            let _ = done.send(result);
+        });
+        self.options_run = Some(run);
+    }
+
+    /// Resolve the rules against the current keymap and apply them
+    fn remap(&mut self, conn: &RustConnection) -> Result<()> {
+        if self.rules.is_empty() {
+            return Ok(());
+        }
+        let setup = conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let mapping = conn.get_keyboard_mapping(min, max - min + 1)?.reply()?;
+        let width = mapping.keysyms_per_keycode.max(1) as usize;
+        let mut keys = Vec::new();
+        for &(from, to) in &self.rules {
+            for (index, row) in mapping.keysyms.chunks(width).enumerate() {
+                if row.first() != Some(&from) {
+                    continue;
+                }
+                // Other levels and groups follow the first keysym
+                let mut remapped = vec![0; width];
+                remapped[0] = to;
+                keys.push(RemappedKey { keycode: min + index as u8, original: row.to_vec(), remapped });
+            }
+        }
+
+        let modmap = conn.get_modifier_mapping()?.reply()?;
+        let per = modmap.keycodes_per_modifier() as usize;
+        let mut rows: Vec<Vec<u8>> = modmap.keycodes.chunks(per.max(1)).map(|row| row.iter().copied().filter(|&k| k != 0).collect()).collect();
+        rows.resize(8, Vec::new());
+        for key in &keys {
+            for row in rows.iter_mut() {
+                row.retain(|&keycode| keycode != key.keycode);
+            }
+            if let Some(modifier) = modifier_for(key.remapped[0]) {
+                rows[modifier].push(key.keycode);
+            }
+        }
+        let remapped_per = rows.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        let remapped_modmap: Vec<u8> = rows.iter()
+            .flat_map(|row| row.iter().copied().chain(std::iter::repeat(0)).take(remapped_per))
+            .collect();
+        self.modifiers = (remapped_modmap != modmap.keycodes).then_some((modmap.keycodes, remapped_modmap));
+        self.keys = keys;
+        info!("Remapping {} keys", self.keys.len());
+        self.write(conn, false)
+    }
+
+    /// Undo the rules
+    fn restore(&mut self, conn: &RustConnection) -> Result<()> {
+        self.write(conn, true)?;
+        self.keys.clear();
+        self.modifiers = None;
+        Ok(())
+    }
+
+    /// Write the remapped (or original) keys and modifier map
+    fn write(&self, conn: &RustConnection, original: bool) -> Result<()> {
+        for key in &self.keys {
+            let row = if original { &key.original } else { &key.remapped };
+            conn.change_keyboard_mapping(1, key.keycode, row.len() as u8, row)?;
+        }
+        if let Some((before, after)) = &self.modifiers {
+            let keycodes = if original { before } else { after };
+            let status = conn.set_modifier_mapping(keycodes)?.reply()?.status;
+            if status != MappingStatus::SUCCESS {
+                warn!("Failed to change the modifier map: {:?}", status);
+            }
+        }
+        conn.flush()?;
+        Ok(())
+    }
+}
+
@@ -2,17 +2,63 @@
 //!
 //! Keyboard shortcut management, key grabs, and Xfce shortcuts integration.
 //! This matches xfwm4's keyboard system.
+//!
+//! Chords are bindings of several key presses in a row (Super+S, then 1).
+//! Their first press is grabbed like any binding; the WM then holds the
+//! keyboard while `KeyboardManager` follows the remaining presses until a
+//! chord completes, a key continues none, or the timeout passes.
 
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 use tracing::{debug, info, warn};
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::*;
 use x11rb::rust_connection::RustConnection;
 use x11rb::wrapper::ConnectionExt as _;
 
+use crate::wm::cycle::CycleMode;
 use crate::wm::display::DisplayInfo;
-use crate::wm::screen::ScreenInfo;
+
+/// Parse a modifier list like "alt+shift" into a mask
+pub fn parse_modifiers(text: &str) -> u16 {
+    text.split('+')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .fold(0, |mask, name| {
+            mask | match name.to_ascii_lowercase().as_str() {
+                "alt" | "mod1" => u16::from(ModMask::M1),
+                "super" | "mod4" => u16::from(ModMask::M4),
+                "ctrl" | "control" => u16::from(ModMask::CONTROL),
+                "shift" => u16::from(ModMask::SHIFT),
+                _ => {
+                    warn!("Unknown modifier {:?}", name);
+                    0
+                }
+            }
+        })
+}
+
+/// Keysym of a key name: a printable character or one of a few named keys
+fn keysym_for_name(name: &str) -> Option<u32> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next())
+        && c.is_ascii_graphic()
+    {
+        return Some(u32::from(c.to_ascii_lowercase()));
+    }
+    Some(match name {
+        "space" => 0x0020,
+        "tab" => XK_TAB,
+        "return" | "enter" => 0xff0d,
+        "escape" => XK_ESCAPE,
+        "left" => XK_LEFT,
+        "right" => XK_RIGHT,
+        "up" => XK_UP,
+        "down" => XK_DOWN,
+        _ => return None,
+    })
+}
 
 /// Keyboard shortcut action
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -39,14 +85,189 @@ pub enum KeyboardAction {
     MoveToWorkspace(u32),
     /// Show window menu
     ShowWindowMenu,
-    /// Cycle windows
-    CycleWindows,
+    /// Cycle windows (Alt+Tab); finished when Alt is released
+    CycleWindows { mode: CycleMode, backward: bool },
     /// Tile window left
     TileLeft,
     /// Tile window right
     TileRight,
+    /// Move the focused window to the adjacent monitor
+    MoveWindowToMonitor(Direction),
+    /// Pass all shortcuts to the focused window (VMs, remote desktops) or stop doing so
+    ToggleShortcutInhibit,
+    /// Let the pointer leave the monitor of a confined fullscreen game, or
+    /// confine it again
+    TogglePointerConfinement,
+    /// Grow (positive) or shrink the inner and outer gaps by some pixels
+    AdjustGaps(i32),
+    /// Move the shared edge of the focused window's snap group right or
+    /// down (positive) by some percent of the area
+    AdjustSplit(i32),
+    /// Enter (or leave) keyboard window management mode
+    WindowMode,
+    /// Label the visible windows and focus the one whose label is typed
+    HintMode,
+    /// Show (or leave) the overview of all workspaces
+    Expo,
+    /// Turn Do Not Disturb on or off
+    ToggleDoNotDisturb,
+    /// Show or hide the compositor's profiling HUD
+    ToggleHud,
+    /// Focus the windows of the nth taskbar button (0-based) in turn, or
+    /// launch its pinned application when it has none
+    ActivateTaskbarSlot(u32),
+    /// First key of a chord (see `KeyboardManager::begin_chord`)
+    Chord,
+}
+
+impl KeyboardAction {
+    /// Parse a configured chord action, e.g. "tile_left" or "switch_workspace 2"
+    /// (workspaces are 0-based)
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut words = text.split_whitespace();
+        let action = match (words.next()?, words.next()) {
+            ("switch_workspace", Some(n)) => Self::SwitchWorkspace(n.parse().ok()?),
+            ("move_to_workspace", Some(n)) => Self::MoveToWorkspace(n.parse().ok()?),
+            ("move_to_monitor", Some(direction)) => Self::MoveWindowToMonitor(match direction {
+                "left" => Direction::Left,
+                "right" => Direction::Right,
+                "up" => Direction::Up,
+                "down" => Direction::Down,
+                _ => return None,
+            }),
+            ("adjust_split", Some(n)) => Self::AdjustSplit(n.parse().ok()?),
+            ("activate_taskbar_slot", Some(n)) => Self::ActivateTaskbarSlot(n.parse().ok()?),
+            ("tile_left", None) => Self::TileLeft,
+            ("tile_right", None) => Self::TileRight,
+            ("window_menu", None) => Self::ShowWindowMenu,
+            ("window_mode", None) => Self::WindowMode,
+            ("hint_mode", None) => Self::HintMode,
+            ("expo", None) => Self::Expo,
+            ("toggle_shortcut_inhibit", None) => Self::ToggleShortcutInhibit,
+            ("toggle_pointer_confinement", None) => Self::TogglePointerConfinement,
+            ("toggle_do_not_disturb", None) => Self::ToggleDoNotDisturb,
+            ("toggle_hud", None) => Self::ToggleHud,
+            _ => return None,
+        };
+        words.next().is_none().then_some(action)
+    }
+    
+    /// What the action does, for chord hints
+    fn describe(self) -> String {
+        match self {
+            Self::SwitchWorkspace(n) => format!("Workspace {}", n + 1),
+            Self::MoveToWorkspace(n) => format!("Send to workspace {}", n + 1),
+            Self::MoveWindowToMonitor(direction) => format!("Send to {:?} monitor", direction),
+            Self::TileLeft => "Tile left".to_string(),
+            Self::TileRight => "Tile right".to_string(),
+            Self::AdjustSplit(n) => format!("Move split by {}%", n),
+            Self::ActivateTaskbarSlot(n) => format!("Taskbar button {}", n + 1),
+            Self::ShowWindowMenu => "Window menu".to_string(),
+            Self::WindowMode => "Window mode".to_string(),
+            Self::HintMode => "Pick a window".to_string(),
+            Self::Expo => "All workspaces".to_string(),
+            Self::ToggleShortcutInhibit => "Pass shortcuts".to_string(),
+            Self::TogglePointerConfinement => "Pointer confinement".to_string(),
+            Self::ToggleDoNotDisturb => "Do not disturb".to_string(),
+            Self::ToggleHud => "Profiling HUD".to_string(),
+            other => format!("{:?}", other),
+        }
+    }
+}
+
+/// A key press: modifiers and keycode
+type Stroke = (u16, u8);
+
+/// A binding of several key presses in a row
+#[derive(Debug, Clone)]
+struct Chord {
+    strokes: Vec<Stroke>,
+    /// Key names of the strokes, e.g. "super+s", for hints
+    names: Vec<String>,
+    action: KeyboardAction,
+}
+
+/// A chord whose first keys were pressed
+#[derive(Debug)]
+struct PendingChord {
+    strokes: Vec<Stroke>,
+    /// Modifiers of the first key, ignored while still held
+    held: u16,
+    timeout: Duration,
+    deadline: Instant,
+}
+
+/// Result of a key press while a chord is pending
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChordStep {
+    /// More keys to come (or a modifier key, which is ignored)
+    Pending,
+    /// A chord completed
+    Done(KeyboardAction),
+    /// No chord continues with this key
+    Cancelled,
+}
+
+/// Direction on screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
 }
 
+/// Lock modifiers ignored when matching bindings (Caps Lock, Num Lock)
+pub(crate) const LOCK_MASKS: [u16; 4] = [0, 1 << 1, 1 << 4, (1 << 1) | (1 << 4)];
+
+/// Keysyms of the arrow keys
+const XK_LEFT: u32 = 0xff51;
+const XK_UP: u32 = 0xff52;
+const XK_RIGHT: u32 = 0xff53;
+const XK_DOWN: u32 = 0xff54;
+
+/// Keysyms of the window cycling keys
+const XK_TAB: u32 = 0xff09;
+const XK_GRAVE: u32 = 0x0060;
+const XK_ALT_L: u32 = 0xffe9;
+const XK_ALT_R: u32 = 0xffea;
+
+/// Keysym of the shortcut inhibit toggle (Super+Escape) and the pointer
+/// confinement toggle (Super+Shift+Escape)
+const XK_ESCAPE: u32 = 0xff1b;
+
+/// Keysyms of the gap adjustment keys
+const XK_EQUAL: u32 = 0x003d;
+const XK_MINUS: u32 = 0x002d;
+
+/// Keysym of the window management mode key (Super+W)
+const XK_W: u32 = 0x0077;
+
+/// Keysym of the window hint mode key (Super+F)
+const XK_F: u32 = 0x0066;
+
+/// Keysym of the workspace overview key (Super+E)
+const XK_E: u32 = 0x0065;
+
+/// Keysym of the Do Not Disturb toggle (Super+N)
+const XK_N: u32 = 0x006e;
+
+/// Keysym of the profiling HUD toggle (Super+F12)
+const XK_F12: u32 = 0xffc9;
+
+/// Keysyms of the built-in chords: Super+G, then 1-9 goes to a workspace,
+/// Super+S, then 1-9 sends the focused window there (Super+1-9 activate
+/// taskbar buttons)
+const XK_G: u32 = 0x0067;
+const XK_S: u32 = 0x0073;
+const XK_1: u32 = 0x0031;
+
+/// Pixels added or removed per gap adjustment
+const GAP_STEP: i32 = 4;
+
+/// Percent of the area a snap group's split moves per key press
+const SPLIT_STEP: i32 = 5;
+
 /// Key binding
 #[derive(Debug, Clone)]
 pub struct KeyBinding {
@@ -65,6 +286,21 @@ pub struct KeyboardManager {
     
     /// Modifier mapping
     pub mod_map: ModifierMap,
+    
+    /// Keycodes whose release ends window cycling (Alt)
+    cycle_release_keycodes: Vec<u8>,
+    
+    /// Key grabs released for a window that inhibits shortcuts
+    grabs_suspended: bool,
+    
+    /// Multi-key bindings
+    chords: Vec<Chord>,
+    
+    /// Chord being typed
+    pending_chord: Option<PendingChord>,
+    
+    /// Keycodes of modifier keys (pressing them does not end a chord)
+    modifier_keycodes: Vec<u8>,
 }
 
 /// Modifier key mapping
@@ -78,17 +314,24 @@ pub struct ModifierMap {
 
 impl KeyboardManager {
     /// Create a new keyboard manager
-    pub fn new(conn: &RustConnection) -> Result<Self> {
+    pub fn new(conn: &RustConnection, root: Window) -> Result<Self> {
         // Get modifier mapping
         let mod_map = Self::get_modifier_map_internal(conn)?;
         
+        let modifier_keycodes = conn.get_modifier_mapping()?.reply()?.keycodes.into_iter().filter(|&k| k != 0).collect();
+        
         let mut manager = Self {
             bindings: HashMap::new(),
             mod_map,
+            cycle_release_keycodes: Vec::new(),
+            grabs_suspended: false,
+            chords: Vec::new(),
+            pending_chord: None,
+            modifier_keycodes,
         };
         
         // Set up default bindings
-        manager.setup_default_bindings()?;
+        manager.setup_default_bindings(conn, root)?;
         
         Ok(manager)
     }
@@ -112,18 +355,136 @@ impl KeyboardManager {
     }
     
     /// Set up default key bindings
-    fn setup_default_bindings(&mut self) -> Result<()> {
-        // Default xfwm4 bindings would go here
-        // For now, just set up a few common ones
+    fn setup_default_bindings(&mut self, conn: &RustConnection, root: Window) -> Result<()> {
         debug!("Setting up default keyboard bindings");
+        
+        // Super+Shift+Arrow: move the focused window to the next monitor
+        let super_shift = self.mod_map.mod4 | self.mod_map.shift;
+        for (keysym, direction) in [
+            (XK_LEFT, Direction::Left),
+            (XK_RIGHT, Direction::Right),
+            (XK_UP, Direction::Up),
+            (XK_DOWN, Direction::Down),
+        ] {
+            match Self::keycode_for(conn, keysym)? {
+                Some(keycode) => {
+                    self.add_binding(conn, root, super_shift, keycode, KeyboardAction::MoveWindowToMonitor(direction))?
+                }
+                None => warn!("No keycode for keysym 0x{:x}, {:?} binding disabled", keysym, direction),
+            }
+        }
+        
+        // Super+Left/Right tiles the focused window to a screen half
+        for (keysym, action) in [(XK_LEFT, KeyboardAction::TileLeft), (XK_RIGHT, KeyboardAction::TileRight)] {
+            if let Some(keycode) = Self::keycode_for(conn, keysym)? {
+                self.add_binding(conn, root, self.mod_map.mod4, keycode, action)?;
+            }
+        }
+        
+        // Super+Ctrl+Arrow moves the split between snap-tiled windows
+        let super_ctrl = self.mod_map.mod4 | self.mod_map.control;
+        for (keysym, delta) in [(XK_LEFT, -SPLIT_STEP), (XK_UP, -SPLIT_STEP), (XK_RIGHT, SPLIT_STEP), (XK_DOWN, SPLIT_STEP)] {
+            if let Some(keycode) = Self::keycode_for(conn, keysym)? {
+                self.add_binding(conn, root, super_ctrl, keycode, KeyboardAction::AdjustSplit(delta))?;
+            }
+        }
+        
+        // Super+= / Super+- grow and shrink the gaps
+        for (keysym, delta) in [(XK_EQUAL, GAP_STEP), (XK_MINUS, -GAP_STEP)] {
+            if let Some(keycode) = Self::keycode_for(conn, keysym)? {
+                self.add_binding(conn, root, self.mod_map.mod4, keycode, KeyboardAction::AdjustGaps(delta))?;
+            }
+        }
+        
+        // Super+W enters window management mode
+        match Self::keycode_for(conn, XK_W)? {
+            Some(keycode) => self.add_binding(conn, root, self.mod_map.mod4, keycode, KeyboardAction::WindowMode)?,
+            None => warn!("No keycode for W, window management mode disabled"),
+        }
+        
+        // Super+F labels the windows to pick one from the keyboard
+        match Self::keycode_for(conn, XK_F)? {
+            Some(keycode) => self.add_binding(conn, root, self.mod_map.mod4, keycode, KeyboardAction::HintMode)?,
+            None => warn!("No keycode for F, window hint mode disabled"),
+        }
+        
+        // Super+E shows all workspaces
+        match Self::keycode_for(conn, XK_E)? {
+            Some(keycode) => self.add_binding(conn, root, self.mod_map.mod4, keycode, KeyboardAction::Expo)?,
+            None => warn!("No keycode for E, workspace overview disabled"),
+        }
+        
+        // Super+N toggles Do Not Disturb
+        match Self::keycode_for(conn, XK_N)? {
+            Some(keycode) => self.add_binding(conn, root, self.mod_map.mod4, keycode, KeyboardAction::ToggleDoNotDisturb)?,
+            None => warn!("No keycode for N, Do Not Disturb toggle disabled"),
+        }
+        
+        // Super+F12 shows the compositor's profiling HUD
+        match Self::keycode_for(conn, XK_F12)? {
+            Some(keycode) => self.add_binding(conn, root, self.mod_map.mod4, keycode, KeyboardAction::ToggleHud)?,
+            None => warn!("No keycode for F12, profiling HUD toggle disabled"),
+        }
+        
+        // Super+1-9 activate the taskbar buttons
+        for n in 0..9 {
+            if let Some(keycode) = Self::keycode_for(conn, XK_1 + n)? {
+                self.add_binding(conn, root, self.mod_map.mod4, keycode, KeyboardAction::ActivateTaskbarSlot(n))?;
+            }
+        }
+        
+        // Alt+Tab cycles all windows, Alt+` windows of the focused application;
+        // Shift goes backwards
+        let alt = self.mod_map.mod1;
+        for (keysym, mode) in [(XK_TAB, CycleMode::All), (XK_GRAVE, CycleMode::SameClass)] {
+            let Some(keycode) = Self::keycode_for(conn, keysym)? else {
+                warn!("No keycode for keysym 0x{:x}, {:?} cycling disabled", keysym, mode);
+                continue;
+            };
+            for (modifiers, backward) in [(alt, false), (alt | self.mod_map.shift, true)] {
+                self.add_binding(conn, root, modifiers, keycode, KeyboardAction::CycleWindows { mode, backward })?;
+            }
+        }
+        for keysym in [XK_ALT_L, XK_ALT_R] {
+            self.cycle_release_keycodes.extend(Self::keycode_for(conn, keysym)?);
+        }
+        
+        // Super+Escape toggles passing all shortcuts to the focused window
+        // Super+Shift+Escape lets the pointer off a confined game's monitor
+        match Self::keycode_for(conn, XK_ESCAPE)? {
+            Some(keycode) => {
+                self.add_binding(conn, root, self.mod_map.mod4, keycode, KeyboardAction::ToggleShortcutInhibit)?;
+                self.add_binding(conn, root, self.mod_map.mod4 | self.mod_map.shift, keycode, KeyboardAction::TogglePointerConfinement)?;
+            }
+            None => warn!("No keycode for Escape, shortcut inhibit and pointer confinement toggles disabled"),
+        }
         Ok(())
     }
     
+    /// First keycode producing a keysym (unshifted)
+    fn keycode_for(conn: &RustConnection, keysym: u32) -> Result<Option<u8>> {
+        let setup = conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let mapping = conn.get_keyboard_mapping(min, max - min + 1)?.reply()?;
+        let per_keycode = mapping.keysyms_per_keycode.max(1) as usize;
+        Ok(mapping
+            .keysyms
+            .chunks(per_keycode)
+            .position(|syms| syms.first() == Some(&keysym))
+            .map(|index| min + index as u8))
+    }
+    
+    /// Unshifted keysym of a keycode
+    pub fn keysym(conn: &RustConnection, keycode: u8) -> Result<u32> {
+        let mapping = conn.get_keyboard_mapping(keycode, 1)?.reply()?;
+        Ok(mapping.keysyms.first().copied().unwrap_or(0))
+    }
+    
     /// Add a key binding
     pub fn add_binding(
         &mut self,
         conn: &RustConnection,
-        screen_info: &ScreenInfo,
+        root: Window,
         modifiers: u16,
         keycode: u8,
         action: KeyboardAction,
@@ -131,15 +492,17 @@ impl KeyboardManager {
         debug!("Adding key binding: modifiers={:x}, keycode={}, action={:?}", 
             modifiers, keycode, action);
         
-        // Grab key
-        conn.grab_key(
-            true,
-            screen_info.root,
-            ModMask::from(modifiers),
-            keycode,
-            GrabMode::ASYNC,
-            GrabMode::ASYNC,
-        )?;
+        // Grab key, whatever the state of Caps Lock and Num Lock
+        for lock in LOCK_MASKS {
+            conn.grab_key(
+                true,
+                root,
+                ModMask::from(modifiers | lock),
+                keycode,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )?;
+        }
         
         self.bindings.insert((modifiers, keycode), action);
         
@@ -150,29 +513,188 @@ impl KeyboardManager {
     pub fn remove_binding(
         &mut self,
         conn: &RustConnection,
-        screen_info: &ScreenInfo,
+        root: Window,
         modifiers: u16,
         keycode: u8,
     ) -> Result<()> {
         debug!("Removing key binding: modifiers={:x}, keycode={}", modifiers, keycode);
         
         // Ungrab key
-        conn.ungrab_key(keycode, screen_info.root, ModMask::from(modifiers))?;
+        for lock in LOCK_MASKS {
+            conn.ungrab_key(keycode, root, ModMask::from(modifiers | lock))?;
+        }
         
         self.bindings.remove(&(modifiers, keycode));
         
         Ok(())
     }
     
+    /// Release (or restore) the passive grabs of every binding but the
+    /// inhibit and pointer confinement toggles
+    ///
+    /// While suspended, the focused window receives the shortcuts itself.
+    pub fn set_grabs_suspended(&mut self, conn: &RustConnection, root: Window, suspended: bool) -> Result<()> {
+        if self.grabs_suspended == suspended {
+            return Ok(());
+        }
+        info!("{} key grabs", if suspended { "Suspending" } else { "Restoring" });
+        for (&(modifiers, keycode), &action) in &self.bindings {
+            if matches!(action, KeyboardAction::ToggleShortcutInhibit | KeyboardAction::TogglePointerConfinement) {
+                continue;
+            }
+            for lock in LOCK_MASKS {
+                let mask = ModMask::from(modifiers | lock);
+                if suspended {
+                    conn.ungrab_key(keycode, root, mask)?;
+                } else {
+                    conn.grab_key(true, root, mask, keycode, GrabMode::ASYNC, GrabMode::ASYNC)?;
+                }
+            }
+        }
+        self.grabs_suspended = suspended;
+        Ok(())
+    }
+    
     /// Handle key press
     pub fn handle_key_press(
         &self,
         modifiers: u16,
         keycode: u8,
     ) -> Option<KeyboardAction> {
+        let map = &self.mod_map;
+        let modifiers = modifiers & (map.mod1 | map.mod4 | map.control | map.shift);
         self.bindings.get(&(modifiers, keycode)).copied()
     }
     
+    /// Replace the chords with the built-in ones plus `configured`, grabbing their first keys
+    pub fn set_chords(&mut self, conn: &RustConnection, root: Window, configured: &[crate::config::ChordConfig]) -> Result<()> {
+        let leaders: Vec<Stroke> = self.bindings.iter().filter(|&(_, &a)| a == KeyboardAction::Chord).map(|(&k, _)| k).collect();
+        for (modifiers, keycode) in leaders {
+            self.remove_binding(conn, root, modifiers, keycode)?;
+        }
+        self.pending_chord = None;
+        self.chords.clear();
+        
+        for (leader, name, action) in [("super+g", XK_G, KeyboardAction::SwitchWorkspace as fn(u32) -> KeyboardAction), ("super+s", XK_S, KeyboardAction::MoveToWorkspace)] {
+            let Some(leader_keycode) = Self::keycode_for(conn, name)? else { continue };
+            for n in 0..9 {
+                if let Some(keycode) = Self::keycode_for(conn, XK_1 + n)? {
+                    self.chords.push(Chord {
+                        strokes: vec![(self.mod_map.mod4, leader_keycode), (0, keycode)],
+                        names: vec![leader.to_string(), (n + 1).to_string()],
+                        action: action(n),
+                    });
+                }
+            }
+        }
+        for config in configured {
+            match self.parse_chord(conn, config) {
+                Ok(chord) => self.chords.push(chord),
+                Err(e) => warn!("Ignoring chord {:?}: {}", config.keys, e),
+            }
+        }
+        
+        let leaders: HashSet<Stroke> = self.chords.iter().map(|chord| chord.strokes[0]).collect();
+        for (modifiers, keycode) in leaders {
+            match self.bindings.get(&(modifiers, keycode)) {
+                Some(action) => warn!("Chord key {:x}+{} is already bound to {:?}, its chords are disabled", modifiers, keycode, action),
+                None => self.add_binding(conn, root, modifiers, keycode, KeyboardAction::Chord)?,
+            }
+        }
+        debug!("{} chords", self.chords.len());
+        Ok(())
+    }
+    
+    /// Parse a configured chord ("super+a, 1")
+    fn parse_chord(&self, conn: &RustConnection, config: &crate::config::ChordConfig) -> Result<Chord> {
+        let action = KeyboardAction::parse(&config.action)
+            .ok_or_else(|| anyhow::anyhow!("unknown action {:?}", config.action))?;
+        let names: Vec<String> = config.keys.split(',').map(|key| key.trim().to_lowercase()).collect();
+        if names.len() < 2 {
+            anyhow::bail!("a chord needs at least two keys");
+        }
+        let mut strokes = Vec::new();
+        for name in &names {
+            let (modifiers, key) = name.rsplit_once('+').unwrap_or(("", name));
+            let keysym = keysym_for_name(key).ok_or_else(|| anyhow::anyhow!("unknown key {:?}", key))?;
+            let keycode = Self::keycode_for(conn, keysym)?.ok_or_else(|| anyhow::anyhow!("no keycode for {:?}", key))?;
+            strokes.push((parse_modifiers(modifiers), keycode));
+        }
+        Ok(Chord { strokes, names, action })
+    }
+    
+    /// Start following a chord after its first key
+    pub fn begin_chord(&mut self, modifiers: u16, keycode: u8, timeout: Duration) {
+        let modifiers = self.relevant_modifiers(modifiers);
+        self.pending_chord = Some(PendingChord { strokes: vec![(modifiers, keycode)], held: modifiers, timeout, deadline: Instant::now() + timeout });
+    }
+    
+    /// Follow a key pressed while a chord is pending
+    pub fn chord_key(&mut self, modifiers: u16, keycode: u8) -> ChordStep {
+        let modifiers = self.relevant_modifiers(modifiers);
+        let Some(pending) = self.pending_chord.as_mut() else { return ChordStep::Cancelled };
+        if self.modifier_keycodes.contains(&keycode) {
+            return ChordStep::Pending;
+        }
+        // Exactly these modifiers, else the first key's ones still held are ignored
+        for stroke in [(modifiers, keycode), (modifiers & !pending.held, keycode)] {
+            let mut strokes = pending.strokes.clone();
+            strokes.push(stroke);
+            if let Some(chord) = self.chords.iter().find(|chord| chord.strokes == strokes) {
+                let action = chord.action;
+                self.pending_chord = None;
+                return ChordStep::Done(action);
+            }
+            if self.chords.iter().any(|chord| chord.strokes.starts_with(&strokes)) {
+                pending.strokes = strokes;
+                pending.deadline = Instant::now() + pending.timeout;
+                return ChordStep::Pending;
+            }
+        }
+        self.pending_chord = None;
+        ChordStep::Cancelled
+    }
+    
+    /// When the pending chord times out
+    pub fn chord_deadline(&self) -> Option<Instant> {
+        self.pending_chord.as_ref().map(|pending| pending.deadline)
+    }
+    
+    pub fn is_chord_pending(&self) -> bool {
+        self.pending_chord.is_some()
+    }
+    
+    pub fn cancel_chord(&mut self) {
+        self.pending_chord = None;
+    }
+    
+    /// Remaining keys and action of every chord the pending one can still become
+    pub fn chord_completions(&self) -> Vec<String> {
+        let Some(pending) = &self.pending_chord else { return Vec::new() };
+        self.chords
+            .iter()
+            .filter(|chord| chord.strokes.starts_with(&pending.strokes))
+            .map(|chord| format!("{}  {}", chord.names[pending.strokes.len()..].join(", "), chord.action.describe()))
+            .collect()
+    }
+    
+    /// Binding modifiers of an event state (lock keys dropped)
+    fn relevant_modifiers(&self, state: u16) -> u16 {
+        let map = &self.mod_map;
+        state & (map.mod1 | map.mod4 | map.control | map.shift)
+    }
+    
+    /// Whether exactly the `required` modifiers are held in an event state (lock keys ignored)
+    pub fn modifiers_held(&self, state: u16, required: u16) -> bool {
+        let map = &self.mod_map;
+        state & (map.mod1 | map.mod4 | map.control | map.shift) == required
+    }
+    
+    /// Whether releasing this key ends window cycling
+    pub fn ends_cycle(&self, keycode: u8) -> bool {
+        self.cycle_release_keycodes.contains(&keycode)
+    }
+    
     /// Get modifier map
     pub fn get_modifier_map(&self) -> &ModifierMap {
         &self.mod_map
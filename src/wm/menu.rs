@@ -6,7 +6,6 @@
 use anyhow::Result;
 use tracing::{debug, info, warn};
 use x11rb::connection::Connection;
-use x11rb::protocol::xproto::*;
 use x11rb::rust_connection::RustConnection;
 
 use crate::wm::client::Client;
@@ -45,9 +44,7 @@ impl MenuManager {
     /// Create a new menu manager
     pub fn new(conn: &RustConnection, atoms: &Atoms) -> Result<Self> {
         // Intern GTK_SHOW_WINDOW_MENU atom
-        let gtk_show_window_menu = conn.intern_atom(false, b"GTK_SHOW_WINDOW_MENU")?
-            .reply()?
-            .atom;
+        let gtk_show_window_menu = crate::wm::atom_cache::intern(conn, "GTK_SHOW_WINDOW_MENU")?;
         
         Ok(Self {
             gtk_show_window_menu,
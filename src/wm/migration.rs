@@ -0,0 +1,69 @@
+//! Windows of unplugged monitors
+//!
+//! When an output disappears its windows move to the primary monitor,
+//! keeping their workspace and their position relative to the work area.
+//! If an output of the same name comes back within the grace period, the
+//! windows the user hasn't touched since go back where they were.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::shared::Geometry;
+
+/// A window moved off an output that went away
+#[derive(Debug, Clone)]
+pub struct Migrated {
+    /// Name of the output it was on
+    pub output: String,
+    /// Its geometry and restore geometry there
+    pub geometry: Geometry,
+    pub restore: Option<Geometry>,
+    /// The output's work area at the time
+    pub area: Geometry,
+    /// Where it was moved to; a window moved or resized since stays put
+    pub moved_to: Geometry,
+    since: Instant,
+}
+
+/// Windows waiting for their output to come back
+#[derive(Debug, Default)]
+pub struct OutputMigrations {
+    migrated: HashMap<u32, Migrated>,
+    /// How long an output may be gone for its windows to return (zero: never)
+    pub grace: Duration,
+}
+
+impl OutputMigrations {
+    /// Remember where a window was before it was moved off `output`
+    pub fn record(&mut self, window: u32, output: &str, geometry: Geometry, restore: Option<Geometry>, area: Geometry, moved_to: Geometry) {
+        if self.grace.is_zero() {
+            return;
+        }
+        let migrated = Migrated { output: output.to_string(), geometry, restore, area, moved_to, since: Instant::now() };
+        self.migrated.insert(window, migrated);
+    }
+
+    /// Take the windows that left `output` within the grace period
+    pub fn returning(&mut self, output: &str) -> Vec<(u32, Migrated)> {
+        let now = Instant::now();
+        self.migrated.retain(|_, m| now.duration_since(m.since) < self.grace);
+        self.migrated.extract_if(|_, m| m.output == output).collect()
+    }
+
+    /// Forget a window (unmanaged)
+    pub fn forget(&mut self, window: u32) {
+        self.migrated.remove(&window);
+    }
+}
+
+/// Map `geometry` from `src` to the same relative position in `dst`,
+/// shrinking it to fit
+pub fn translate(geometry: Geometry, src: Geometry, dst: Geometry) -> Geometry {
+    let width = geometry.width.min(dst.width);
+    let height = geometry.height.min(dst.height);
+    let fx = (geometry.x - src.x) as f64 / src.width.max(1) as f64;
+    let fy = (geometry.y - src.y) as f64 / src.height.max(1) as f64;
+    let x = (dst.x + (fx * dst.width as f64) as i32).clamp(dst.x, dst.x + (dst.width - width) as i32);
+    let y = (dst.y + (fy * dst.height as f64) as i32).clamp(dst.y, dst.y + (dst.height - height) as i32);
+    Geometry { x, y, width, height }
+}
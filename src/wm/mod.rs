@@ -3,7 +3,9 @@
 //! Handles X11 window management, decorations, and user interactions.
 
 pub mod decorations;
+pub mod frame_metrics;
 pub mod ewmh;
+pub mod atom_cache;
 pub mod client;
 pub mod client_flags;
 pub mod display;
@@ -11,27 +13,51 @@ pub mod screen;
 pub mod events;
 pub mod focus;
 pub mod stacking;
+pub mod strut;
 pub mod workspace;
+pub mod scaling;
+pub mod selection_sync;
+pub mod clipboard_manager;
 pub mod netwm;
 pub mod moveresize;
 pub mod placement;
+pub mod configure_policy;
+pub mod compositor_rules;
+pub mod snap_group;
 pub mod keyboard;
 pub mod settings;
 pub mod transients;
 pub mod hints;
 pub mod menu;
+pub mod migration;
+pub mod edge_pressure;
+pub mod pointer_confine;
+pub mod accessx;
+pub mod key_remap;
+pub mod dpms;
+pub mod idle;
+pub mod timestamp;
+pub mod xkb_layout;
+pub mod shape;
 pub mod icons;
 pub mod cycle;
+pub mod window_mode;
+pub mod hint_mode;
+pub mod expo;
 pub mod session;
 pub mod startup;
+pub mod activation;
 pub mod terminate;
+pub mod taskbar;
+pub mod pinned;
+pub mod notification_history;
 pub mod device;
 pub mod event_filter;
 
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
-use tracing::{debug, info, warn};
+use tracing::{debug, info, trace, warn};
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::*;
 use x11rb::rust_connection::RustConnection;
@@ -39,6 +65,7 @@ use x11rb::wrapper::ConnectionExt as _;
 
 use crate::shared::Geometry;
 use crate::wm::client::Client;
+use crate::wm::frame_metrics::FrameMetrics;
 pub use decorations::ButtonType;
 pub use ewmh::Atoms;
 // Removed dead code module usage
@@ -51,14 +78,110 @@ struct DragState {
     start_y: i16,
     window_start_x: i32,
     window_start_y: i32,
+    /// Last pointer position (root coordinates)
+    last_x: i16,
+    last_y: i16,
+    /// Held at the left (false) or right (true) screen edge since
+    edge: Option<(bool, Instant)>,
+    /// Client size when the drag started, if it resizes instead of moving
+    resize: Option<(u32, u32)>,
+    /// Monitor under the window's centre when the drag started
+    monitor: usize,
 }
 
+/// How far from the opposite edge the pointer lands after an edge flip
+const EDGE_FLIP_WARP: i16 = 32;
+
+/// Smallest client size an interactive resize goes down to
+const MIN_RESIZE_SIZE: u32 = 64;
+
+/// How far from the shared edge of a snap group a press grabs it
+const SPLIT_GRAB: i32 = 6;
+
 
 pub struct WindowManager {
     screen_num: usize,
     root: u32,
     pub atoms: Atoms,
     drag_state: Option<DragState>,
+    /// Window z-order by layer
+    pub stacking: stacking::StackingManager,
+    /// Active outputs (RandR monitors), refreshed on screen changes
+    pub monitors: Vec<screen::Monitor>,
+    /// Struts of managed X clients (docks)
+    client_struts: HashMap<u32, Vec<strut::Reservation>>,
+    /// Panels declared by the shell over IPC, by monitor index and edge
+    panel_struts: HashMap<(u32, strut::StrutEdge), strut::PanelStrut>,
+    /// Alt+Tab state and most-recently-used order
+    pub cycle: cycle::CycleManager,
+    /// Keyboard window management mode (Super+W)
+    pub window_mode: window_mode::WindowMode,
+    /// Window hint mode (Super+F)
+    pub hint_mode: hint_mode::HintMode,
+    /// Virtual desktops
+    pub workspaces: workspace::WorkspaceManager,
+    /// Frame geometry
+    pub decoration: crate::config::WindowDecorationConfig,
+    /// Frame colors
+    pub colors: crate::config::WindowColors,
+    /// Titlebar buttons of new frames
+    pub button_layout: decorations::ButtonLayout,
+    /// Delay before a window dragged against the left/right screen edge
+    /// flips to the adjacent workspace; disabled if unset
+    pub edge_flip: Option<Duration>,
+    /// Modifiers for Alt+drag style move/resize inside client windows (0: disabled)
+    pub drag_modifiers: u16,
+    /// WM_CLASS names exempt from modifier drags
+    drag_exclude: Vec<String>,
+    /// What clients may change of their own geometry (ConfigureRequest)
+    pub configure_policy: configure_policy::ConfigurePolicy,
+    /// Windows tiled side by side that resize together
+    pub snap_groups: snap_group::SnapGroups,
+    /// Snap group (by its first window) whose shared edge is being dragged
+    split_drag: Option<u32>,
+    /// Keeps titlebars reachable
+    pub placement: placement::PlacementManager,
+    /// Space kept free around maximized and tiled windows (current workspace profile)
+    pub gaps: placement::Gaps,
+    /// Whether the pointer entering a window focuses it (current workspace profile)
+    pub focus_policy: focus::FocusPolicy,
+    /// Auto-raise and raise-on-click
+    pub focus: focus::FocusManager,
+    /// _NET_WM_PING tracking of unresponsive windows
+    pub terminate: terminate::TerminateManager,
+    /// User-defined taskbar button order
+    pub taskbar: taskbar::TaskbarOrder,
+    /// Applications pinned to the dock
+    pub pinned: pinned::PinnedApps,
+    /// Received desktop notifications and Do Not Disturb
+    pub notifications: notification_history::NotificationHistory,
+    /// Tokens handed to launchers, which let new windows take focus
+    pub activation: activation::ActivationTokens,
+    /// Windows moved off unplugged monitors, in case they come back
+    pub migrations: migration::OutputMigrations,
+    /// Monitor edges the shell reveals a panel from, and their pointer barriers
+    pub edge_triggers: edge_pressure::EdgeTriggers,
+    /// Pointer barriers around the monitor of a focused fullscreen game
+    pub pointer_confinement: pointer_confine::PointerConfinement,
+    /// Sticky, slow, bounce and mouse keys
+    pub accessx: accessx::AccessX,
+    /// XKB options and key remaps of `[input.keyboard]`
+    pub key_remap: key_remap::KeyRemap,
+    /// Keyboard layout (XKB group) changes
+    pub keyboard_layout: xkb_layout::LayoutWatcher,
+    /// Screen blanking, monitor power saving and idle inhibitors
+    pub dpms: dpms::Dpms,
+    /// User idle time thresholds (XSync alarms)
+    pub idle: idle::IdleWatcher,
+    /// Latest X server time, for focus changes, selections and pings
+    pub time: timestamp::ServerTime,
+    /// Overview of all workspaces
+    pub expo: expo::Expo,
+    /// PRIMARY / CLIPBOARD synchronization
+    pub selection_sync: selection_sync::SelectionSync,
+    pub clipboard_manager: clipboard_manager::ClipboardManager,
+    /// Visual for frames of translucent (depth 32) clients, if the screen has one
+    argb_visual: Option<decorations::ArgbVisual>,
     /// WM owner window (for ICCCM selection)
     /// 
     /// This window owns the WM_S{screen} selection atom and must remain alive
@@ -142,18 +265,20 @@ impl WindowManager {
             WindowClass::INPUT_OUTPUT,
             0,
             &CreateWindowAux::new()
-                .event_mask(EventMask::STRUCTURE_NOTIFY),
+                .event_mask(EventMask::STRUCTURE_NOTIFY | EventMask::PROPERTY_CHANGE),
         )?;
         conn.map_window(wm_owner_window)?;
         conn.flush()?;
         debug!("WM: Created owner window: 0x{:x}", wm_owner_window);
         
-        // Step 4: Acquire WM selection ownership
+        // Step 4: Acquire WM selection ownership (ICCCM 2.8 wants a real timestamp)
         debug!("WM: Acquiring WM selection ownership...");
+        let mut time = timestamp::ServerTime::default();
+        let acquired = time.fetch(conn, wm_owner_window)?;
         conn.set_selection_owner(
             wm_owner_window,
             wm_selection_atom,
-            x11rb::CURRENT_TIME,
+            acquired,
         )?
         .check()
         .context("Failed to set WM selection owner")?;
@@ -295,15 +420,164 @@ impl WindowManager {
         
         info!("Successfully became window manager (keyboard shortcuts enabled)");
         
+        if let Err(e) = screen::select_screen_changes(conn, root) {
+            warn!("RandR screen change notifications unavailable: {}", e);
+        }
+        
+        let mut edge_triggers = edge_pressure::EdgeTriggers::default();
+        if let Err(e) = edge_triggers.init(conn, root) {
+            warn!("Edge triggers unavailable: {}", e);
+        }
+        let mut pointer_confinement = pointer_confine::PointerConfinement::default();
+        if let Err(e) = pointer_confinement.init(conn) {
+            warn!("Pointer confinement unavailable: {}", e);
+        }
+        
+        let mut accessx = accessx::AccessX::default();
+        if let Err(e) = accessx.init(conn) {
+            warn!("Keyboard accessibility unavailable: {}", e);
+        }
+        let mut key_remap = key_remap::KeyRemap::default();
+        if let Err(e) = key_remap.init(conn, root) {
+            warn!("Keyboard hotplug notifications unavailable: {}", e);
+        }
+        let mut keyboard_layout = xkb_layout::LayoutWatcher::default();
+        if let Err(e) = keyboard_layout.init(conn) {
+            warn!("Keyboard layout changes unavailable: {}", e);
+        }
+        let mut dpms = dpms::Dpms::default();
+        if let Err(e) = dpms.init(conn) {
+            warn!("DPMS unavailable: {}", e);
+        }
+        let mut idle = idle::IdleWatcher::default();
+        if let Err(e) = idle.init(conn) {
+            warn!("Idle detection unavailable: {}", e);
+        }
+        let mut selection_sync = selection_sync::SelectionSync::default();
+        if let Err(e) = selection_sync.init(conn, root) {
+            warn!("Selection synchronization unavailable: {}", e);
+        }
+        let mut clipboard_manager = clipboard_manager::ClipboardManager::default();
+        if let Err(e) = clipboard_manager.init(conn, root) {
+            warn!("Clipboard manager unavailable: {}", e);
+        }
+        
+        let argb_visual = decorations::ArgbVisual::new(conn, &conn.setup().roots[screen_num])?;
+        if argb_visual.is_none() {
+            warn!("No 32-bit visual, translucent windows will be framed opaque");
+        }
+        
         Ok(Self {
             screen_num,
             root,
             atoms,
             drag_state: None,
+            stacking: stacking::StackingManager::new(),
+            monitors: screen::query_monitors(conn, root)?,
+            client_struts: HashMap::new(),
+            panel_struts: HashMap::new(),
+            cycle: cycle::CycleManager::new(),
+            window_mode: window_mode::WindowMode::default(),
+            hint_mode: hint_mode::HintMode::default(),
+            workspaces: workspace::WorkspaceManager::new(1),
+            decoration: crate::config::WindowDecorationConfig::default(),
+            colors: crate::config::WindowColors::default(),
+            button_layout: decorations::ButtonLayout::default(),
+            edge_flip: None,
+            drag_modifiers: 0,
+            drag_exclude: Vec::new(),
+            configure_policy: configure_policy::ConfigurePolicy::default(),
+            snap_groups: snap_group::SnapGroups::default(),
+            split_drag: None,
+            placement: placement::PlacementManager::new(placement::PlacementPolicy::Center),
+            gaps: placement::Gaps::default(),
+            focus_policy: focus::FocusPolicy::ClickToFocus,
+            focus: focus::FocusManager::new(),
+            terminate: terminate::TerminateManager::new(),
+            taskbar: taskbar::TaskbarOrder::load(),
+            pinned: pinned::PinnedApps::load(),
+            notifications: notification_history::NotificationHistory::load(),
+            activation: activation::ActivationTokens::default(),
+            migrations: migration::OutputMigrations::default(),
+            edge_triggers,
+            pointer_confinement,
+            accessx,
+            key_remap,
+            keyboard_layout,
+            dpms,
+            idle,
+            time,
+            selection_sync,
+            clipboard_manager,
+            expo: expo::Expo::default(),
+            argb_visual,
             wm_owner_window,
         })
     }
     
+    /// Apply the window manager settings of a (re)loaded configuration
+    ///
+    /// Decorations and drag modifiers only apply to windows managed afterwards. Windows left on
+    /// removed workspaces move to the last one; returns the resulting
+    /// visibility changes (see `switch_workspace`).
+    pub fn apply_config(
+        &mut self,
+        conn: &RustConnection,
+        windows: &mut HashMap<u32, Client>,
+        config: &crate::config::Config,
+    ) -> Result<Vec<(u32, bool)>> {
+        let behavior = &config.window_manager.behavior;
+        self.cycle.order = behavior.cycle_order;
+        self.cycle.include_minimized = behavior.cycle_include_minimized;
+        self.cycle.group_windows = behavior.cycle_group_windows;
+        self.decoration = config.window_manager.decorations.clone();
+        self.colors = config.window_manager.colors.clone();
+        self.button_layout = decorations::ButtonLayout::parse(&config.window_manager.decorations.buttons);
+        self.edge_flip = behavior.edge_flip.then(|| Duration::from_millis(behavior.edge_flip_delay_ms));
+        self.drag_modifiers = keyboard::parse_modifiers(&behavior.drag_modifiers);
+        self.drag_exclude = behavior.drag_exclude.iter().map(|name| name.to_lowercase()).collect();
+        self.configure_policy.set_exempt(&behavior.configure_exempt);
+        self.focus.auto_raise = behavior.raise_on_focus;
+        self.focus.raise_delay = Duration::from_millis(behavior.raise_delay_ms);
+        self.focus.raise_on_click = behavior.raise_on_click;
+        self.placement.min_visible = behavior.titlebar_min_visible;
+        self.placement.policy = behavior.placement;
+        self.placement.smart_max_size = behavior.smart_placement_max_size;
+        self.migrations.grace = Duration::from_millis(behavior.monitor_return_grace_ms);
+        self.edge_triggers.set_config(config.panel.edge_pressure);
+        self.edge_triggers.rebuild(conn, self.root, &self.monitors)?;
+        self.pointer_confinement.set_config(behavior.confine_pointer, &behavior.confine_pointer_classes);
+        self.accessx.apply(conn, &config.input.accessibility)?;
+        if let Err(e) = self.key_remap.apply(conn, &config.input.keyboard) {
+            warn!("Failed to remap keys: {}", e);
+        }
+        self.selection_sync.set_direction(config.clipboard.sync);
+        self.clipboard_manager.set_enabled(conn, self.root, config.clipboard.manager, self.time.now())?;
+        if config.dpms.enabled {
+            self.dpms.set_config(conn, &config.dpms)?;
+        }
+        self.idle.set_thresholds(conn, &config.idle.thresholds)?;
+        
+        let count = behavior.workspace_count.max(1);
+        self.workspaces.set_workspace_count(conn, &self.atoms, self.root, count)?;
+        self.update_workarea(conn)?;
+        for workspace in 0..count {
+            let profile = config.workspace_profiles.get(&workspace.to_string());
+            let policy = profile.and_then(|p| p.workspace_focus).unwrap_or(behavior.workspace_focus);
+            self.workspaces.set_focus_policy(workspace, policy);
+        }
+        let mut changed = Vec::new();
+        for client in windows.values_mut() {
+            if client.win_workspace != workspace::ALL_WORKSPACES
+                && client.win_workspace >= count
+                && let Some(visible) = self.workspaces.move_window_to_workspace(conn, &self.atoms, client, count - 1)?
+            {
+                changed.push((client.window, visible));
+            }
+        }
+        Ok(changed)
+    }
+    
     /// Check if window should be decorated based on class/name patterns
     /// Returns false if window matches a pattern that indicates no decorations
     fn should_decorate_from_patterns<C: Connection>(
@@ -376,11 +650,24 @@ impl WindowManager {
         &mut self,
         conn: &x11rb::rust_connection::RustConnection,
         client: &mut Client,
+        windows: &HashMap<u32, Client>,
     ) -> Result<()> {
+        use crate::wm::client_flags::WindowType;
+        
         debug!("WM: Managing window {}", client.window);
         
+        // Send every request up front and collect the replies afterwards,
+        // one round trip instead of one per property
+        let attrs_cookie = conn.get_window_attributes(client.window)?;
+        let geom_cookie = conn.get_geometry(client.window)?;
+        let normal_hints_cookie = conn.get_property(false, client.window, AtomEnum::WM_NORMAL_HINTS, AtomEnum::WM_SIZE_HINTS, 0, 18)?;
+        let transient_cookie = conn.get_property(false, client.window, AtomEnum::WM_TRANSIENT_FOR, AtomEnum::WINDOW, 0, 1)?;
+        let name_cookie = conn.get_property(false, client.window, AtomEnum::WM_NAME, AtomEnum::STRING, 0, 1024)?;
+        let class_cookie = conn.get_property(false, client.window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)?;
+        let hints_cookie = x11rb::properties::WmHints::get(conn, client.window)?;
+        
         // Get window attributes
-        let attrs = match conn.get_window_attributes(client.window)?.reply() {
+        let attrs = match attrs_cookie.reply() {
             Ok(attrs) => attrs,
             Err(e) => {
                 debug!("WM: Failed to get attributes for window {}, it probably disappeared: {}", client.window, e);
@@ -394,7 +681,7 @@ impl WindowManager {
         }
         
         // Get window geometry
-        let geom = match conn.get_geometry(client.window)?.reply() {
+        let geom = match geom_cookie.reply() {
             Ok(geom) => geom,
             Err(e) => {
                 debug!("WM: Failed to get geometry for window {}, it probably disappeared: {}", client.window, e);
@@ -402,41 +689,27 @@ impl WindowManager {
             }
         };
         
+        // WM_SIZE_HINTS is 18 32-bit values
+        let normal_hints: Option<Vec<u32>> = normal_hints_cookie
+            .reply()
+            .ok()
+            .map(|reply| reply.value32().map(|value| value.take(18).collect()).unwrap_or_default());
+        client.size_hints = normal_hints.as_deref().and_then(hints::SizeHints::from_values);
+        
         // Get window's preferred size from WM_NORMAL_HINTS if available
         let mut width = geom.width as u32;
         let mut height = geom.height as u32;
         
         // If window is 1x1 (uninitialized), try to get size from WM_NORMAL_HINTS
         if width == 1 && height == 1 {
-            if let Ok(reply) = conn.get_property(
-                false,
-                client.window,
-                AtomEnum::WM_NORMAL_HINTS,
-                AtomEnum::WM_SIZE_HINTS,
-                0,
-                18, // WM_SIZE_HINTS is 18 32-bit values
-            )?.reply() {
-                if let Some(value32) = reply.value32() {
-                    let hints: Vec<u32> = value32.take(18).collect();
-                    if hints.len() >= 18 {
-                        // WM_SIZE_HINTS structure:
-                        // flags (u32), pad (u32), min_width (u32), min_height (u32), 
-                        // max_width (u32), max_height (u32), width_inc (u32), height_inc (u32),
-                        // min_aspect (u32), max_aspect (u32), base_width (u32), base_height (u32),
-                        // win_gravity (u32), pad (u32), pad (u32), pad (u32), pad (u32), pad (u32)
-                        // We want base_width/base_height or a reasonable default
-                        let base_width = hints.get(10).copied().unwrap_or(0);
-                        let base_height = hints.get(11).copied().unwrap_or(0);
-                        if base_width > 0 && base_height > 0 {
-                            width = base_width;
-                            height = base_height;
-                        } else {
-                            // Default size if no hints
-                            width = 800;
-                            height = 600;
-                        }
-                    }
-                }
+            if let Some(size_hints) = &client.size_hints {
+                // Base size, else a reasonable default the hints allow
+                let (base_width, base_height) = (size_hints.base_width, size_hints.base_height);
+                (width, height) = if size_hints.flags & hints::P_BASE_SIZE != 0 && base_width > 0 && base_height > 0 {
+                    (base_width, base_height)
+                } else {
+                    size_hints.constrain(800, 600)
+                };
             } else {
                 // No WM_NORMAL_HINTS, use default size
                 width = 800;
@@ -453,38 +726,30 @@ impl WindowManager {
         }
         
         // Center window on screen by default (unless window has a specific position hint)
-        let screen = &conn.setup().roots[self.screen_num];
-        let screen_width = screen.width_in_pixels as i32;
-        let screen_height = screen.height_in_pixels as i32;
+        // (the setup's screen size goes stale after a RandR resize or rotation)
+        let (screen_width, screen_height) = self.root_size();
+        let (screen_width, screen_height) = (screen_width as i32, screen_height as i32);
         
         // Check if window has a position hint (USPosition flag in WM_NORMAL_HINTS)
-        let has_position_hint = if let Ok(reply) = conn.get_property(
-            false,
-            client.window,
-            AtomEnum::WM_NORMAL_HINTS,
-            AtomEnum::WM_SIZE_HINTS,
-            0,
-            18,
-        )?.reply() {
-            if let Some(value32) = reply.value32() {
-                let hints: Vec<u32> = value32.take(18).collect();
-                if hints.len() >= 1 {
-                    // Check USPosition flag (bit 0)
-                    (hints[0] & 0x00000001) != 0
-                } else {
-                    false
-                }
-            } else {
-                false
-            }
-        } else {
-            false
-        };
+        let has_position_hint = normal_hints
+            .as_ref()
+            .and_then(|hints| hints.first())
+            .is_some_and(|flags| flags & 0x00000001 != 0);
+        
+        // Dialogs belong to a managed parent window (WM_TRANSIENT_FOR)
+        client.transient_for = transient_cookie
+            .reply()
+            .ok()
+            .and_then(|reply| reply.value32().and_then(|mut value| value.next()))
+            .filter(|&parent| parent != client.window && windows.contains_key(&parent));
         
         // Center window if it doesn't have a position hint or is at (0,0) or invalid position
         let (x, y) = if has_position_hint && geom.x != 0 && geom.y != 0 {
             // Window has explicit position hint, use it
             (geom.x as i32, geom.y as i32)
+        } else if let Some(parent) = client.transient_for.and_then(|parent| windows.get(&parent)) {
+            // Over its parent
+            transients::center_over(&parent.geometry, width, height)
         } else {
             // Center window on screen
             let center_x = (screen_width - width as i32) / 2;
@@ -500,37 +765,91 @@ impl WindowManager {
         };
         
         // Get window title
-        if let Ok(reply) = conn.get_property(
-            false,
-            client.window,
-            AtomEnum::WM_NAME,
-            AtomEnum::STRING,
-            0,
-            1024,
-        )?.reply() {
-            if let Ok(title) = String::from_utf8(reply.value) {
-                client.name = title;
-            }
+        if let Ok(reply) = name_cookie.reply()
+            && let Ok(title) = String::from_utf8(reply.value)
+        {
+            client.name = title;
+        }
+
+        // Get WM_CLASS (instance and class, NUL separated)
+        if let Ok(reply) = class_cookie.reply() {
+            client.class_hint = crate::wm::client::ClassHint::parse(&reply.value);
         }
         
-        // Create window frame with decorations
-        // Use window's centered position
-        let frame_y = client.geometry.y as i16;
+        // Window group (WM_HINTS window_group)
+        client.group_leader = hints_cookie
+            .reply()
+            .ok()
+            .flatten()
+            .and_then(|hints| hints.window_group);
+        
+        // WM_PROTOCOLS: graceful close and ping support
+        let protocols = self.atoms.get_protocols(conn, client.window).unwrap_or_default();
+        client.wm_flags.set(crate::wm::client_flags::WmFlags::DELETE, protocols.contains(&self.atoms._wm_delete_window));
+        client.wm_flags.set(crate::wm::client_flags::WmFlags::PING, protocols.contains(&self.atoms._net_wm_ping));
+        
+        self.grab_drag_buttons(conn, client)?;
+        self.grab_click_buttons(conn, client)?;
+
+        // New windows open on the current workspace
+        client.win_workspace = self.workspaces.current_workspace;
+        conn.change_property32(
+            PropMode::REPLACE,
+            client.window,
+            self.atoms.net_wm_desktop,
+            AtomEnum::CARDINAL,
+            &[client.win_workspace],
+        )?;
         
         let screen = &conn.setup().roots[self.screen_num];
         
+        // Track property changes (WM_HINTS urgency, _NET_WM_STATE, bypass compositor)
+        // and pointer entry (focus follows mouse)
+        conn.change_window_attributes(
+            client.window,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE | EventMask::ENTER_WINDOW),
+        )?;
+        
+        // Window type and initial ABOVE/BELOW state determine the stacking layer
+        let window_types = self.atoms.get_window_type(conn, client.window).unwrap_or_default();
+        if let Some(&type_atom) = window_types.first() {
+            client.type_atom = type_atom;
+            client.type_ = crate::wm::client_flags::WindowType::from_atom(type_atom, &self.atoms);
+        }
+        let window_state = self.atoms.get_window_state(conn, client.window).unwrap_or_default();
+        if client.type_ == crate::wm::client_flags::WindowType::Dialog
+            && window_state.contains(&self.atoms._net_wm_state_modal)
+        {
+            client.type_ = crate::wm::client_flags::WindowType::ModalDialog;
+        }
+        if window_state.contains(&self.atoms._net_wm_state_modal) {
+            client.flags.insert(crate::wm::client_flags::ClientFlags::STATE_MODAL);
+        }
+        // Docks and the desktop are on every workspace, and in no taskbar or pager
+        if matches!(client.type_, WindowType::Dock | WindowType::Desktop) {
+            client.win_workspace = workspace::ALL_WORKSPACES;
+            conn.change_property32(PropMode::REPLACE, client.window, self.atoms.net_wm_desktop, AtomEnum::CARDINAL, &[client.win_workspace])?;
+            client.flags.insert(crate::wm::client_flags::ClientFlags::SKIP_TASKBAR | crate::wm::client_flags::ClientFlags::SKIP_PAGER);
+            self.atoms.set_window_state(conn, client.window, &[self.atoms._net_wm_state_skip_taskbar, self.atoms._net_wm_state_skip_pager], &[])?;
+        }
+        if window_state.contains(&self.atoms._net_wm_state_above) {
+            client.flags.insert(crate::wm::client_flags::ClientFlags::ABOVE);
+        } else if window_state.contains(&self.atoms._net_wm_state_below) {
+            client.flags.insert(crate::wm::client_flags::ClientFlags::BELOW);
+        }
+        
         // Check if window should be decorated
         // Priority: 1. MOTIF_WM_HINTS, 2. _NET_WM_WINDOW_TYPE, 3. Window class/name patterns
+        // (docks, the desktop and splash screens never are)
         let mut should_decorate = true;
         
-        // First, check MOTIF_WM_HINTS (most authoritative for decoration requests)
-        if let Ok(Some(motif_should_decorate)) = self.atoms.should_decorate_from_motif_hints(conn, client.window) {
+        if matches!(client.type_, WindowType::Dock | WindowType::Desktop | WindowType::Splashscreen) {
+            should_decorate = false;
+        } else if let Ok(Some(motif_should_decorate)) = self.atoms.should_decorate_from_motif_hints(conn, client.window) {
             should_decorate = motif_should_decorate;
             debug!("MOTIF hints for window {}: should_decorate={}", client.window, should_decorate);
         } else {
             // MOTIF hints not present or don't specify - check _NET_WM_WINDOW_TYPE
-            let window_types = self.atoms.get_window_type(conn, client.window).unwrap_or_default();
-            
             for &win_type in &window_types {
                 if win_type == self.atoms._net_wm_window_type_dock ||
                    win_type == self.atoms._net_wm_window_type_tooltip ||
@@ -553,56 +872,126 @@ impl WindowManager {
             }
         }
         
+        // Undecorated windows stay where they are unless that hides them
+        if !should_decorate && client.transient_for.is_none() {
+            client.geometry.x = geom.x as i32;
+            client.geometry.y = geom.y as i32;
+        }
+        // Splash screens go in the middle of the monitor under the pointer
+        if client.type_ == WindowType::Splashscreen && client.transient_for.is_none() {
+            let area = self.work_area(self.pointer_monitor(conn)?);
+            client.geometry.x = area.x + (area.width as i32 - client.geometry.width as i32) / 2;
+            client.geometry.y = area.y + (area.height as i32 - client.geometry.height as i32) / 2;
+        }
+        client.frame_metrics = if should_decorate { FrameMetrics::from_config(&self.decoration) } else { FrameMetrics::NONE };
+        if should_decorate && !(has_position_hint && geom.x != 0 && geom.y != 0) && client.transient_for.is_none() {
+            self.place_new_window(conn, windows, client)?;
+        }
+        if let Some((x, y)) = self.constrained_position(client) {
+            client.geometry.x = x;
+            client.geometry.y = y;
+        }
+        if !should_decorate && (client.geometry.x, client.geometry.y) != (geom.x as i32, geom.y as i32) {
+            conn.configure_window(
+                client.window,
+                &ConfigureWindowAux::new().x(client.geometry.x).y(client.geometry.y),
+            )?;
+        }
+        
         if should_decorate {
-            // Use default decoration config and colors for now
-            // TODO: Store these in WindowManager or pass them in
-            let dec_config = crate::config::WindowDecorationConfig::default();
-            let dec_colors = crate::config::WindowColors::default();
+            // Client content sits below the titlebar
+            let frame_geometry = client.frame_metrics.frame_geometry(client.geometry);
             let dec_frame = decorations::WindowFrame::new(
                 conn,
                 screen,
                 client.window,
-                client.geometry.x as i16,
-                frame_y,
+                frame_geometry.x as i16,
+                frame_geometry.y as i16,
                 client.geometry.width as u16,
                 client.geometry.height as u16,
-                &dec_config,
-                &dec_colors,
+                &self.decoration,
+                &self.colors,
+                &self.button_layout,
+                // ARGB clients keep their alpha only in an ARGB frame
+                self.argb_visual.filter(|_| geom.depth == 32),
             )?;
             
             // Convert to simple WindowFrame for storage
             client.frame = Some(crate::shared::window_state::WindowFrame {
                 frame: dec_frame.frame,
                 titlebar: dec_frame.titlebar,
-                close_button: dec_frame.close_button,
-                maximize_button: dec_frame.maximize_button,
-                minimize_button: dec_frame.minimize_button,
+                buttons: dec_frame.buttons,
             });
-            
-            // Update _NET_FRAME_EXTENTS only if decorated
-            // Top: 32 (Titlebar), Left/Right/Bottom: 2 (Border)
-            let _ = self.atoms.update_frame_extents(conn, client.window, 2, 2, 32, 2);
-        } else {
-            // If not decorated, set _NET_FRAME_EXTENTS to 0
-            let _ = self.atoms.update_frame_extents(conn, client.window, 0, 0, 0, 0);
-            
-            // No panel offset needed - use window's actual position
         }
         
+        // Tell the client about our decorations (all zero if undecorated)
+        let _ = self.atoms.update_frame_extents(conn, client.window, client.frame_metrics.extents());
+        
         client.set_mapped(true);
         
         conn.flush()?;
         
         debug!("WM: Managed window {} ({})", client.window, client.name.as_str());
         
-        // Update _NET_FRAME_EXTENTS so client knows about our decorations
-        // Currently hardcoded based on our hardcoded decoration sizes
-        // Top: 32 (Titlebar), Left/Right/Bottom: 2 (Border)
-        let _ = self.atoms.update_frame_extents(conn, client.window, 2, 2, 32, 2);
-        
         Ok(())
     }
     
+    /// Position a new window that brought no position of its own by the
+    /// placement policy, on the monitor under the pointer ("center" keeps it
+    /// centered on the screen)
+    fn place_new_window(&mut self, conn: &RustConnection, windows: &HashMap<u32, Client>, client: &mut Client) -> Result<()> {
+        use placement::PlacementPolicy;
+        
+        let policy = self.placement.policy;
+        if !matches!(policy, PlacementPolicy::Smart | PlacementPolicy::Cascade) {
+            return Ok(());
+        }
+        let monitor = self.pointer_monitor(conn)?;
+        let workspace = self.workspaces.current_workspace;
+        let area = self.work_area(monitor);
+        let metrics = client.frame_metrics;
+        let size = (client.geometry.width + 2 * metrics.border, client.geometry.height + metrics.titlebar + 2 * metrics.border);
+        // Windows shown on the same workspace and monitor
+        let others: Vec<Geometry> = windows
+            .values()
+            .filter(|c| self.workspaces.is_visible(c) && !c.is_minimized())
+            .map(|c| c.frame_geometry())
+            .filter(|f| self.monitor_at(f.x + f.width as i32 / 2, f.y + f.height as i32 / 2) == Some(monitor))
+            .collect();
+        let (x, y) = match policy {
+            PlacementPolicy::Smart
+                if client.type_ == crate::wm::client_flags::WindowType::Normal
+                    && size.0 as u64 * size.1 as u64 * 100 <= area.width as u64 * area.height as u64 * self.placement.smart_max_size as u64 =>
+            {
+                self.placement.place_smart(area, size, &others)
+            }
+            // Big windows and dialogs without a parent
+            PlacementPolicy::Smart => (area.x + (area.width as i32 - size.0 as i32) / 2, area.y + (area.height as i32 - size.1 as i32) / 2),
+            // Start over in the corner once the cascade's windows are all gone
+            _ => self.placement.place_cascade(workspace, monitor, area, size, metrics.titlebar, others.is_empty()),
+        };
+        let placed = metrics.client_geometry(Geometry::new(x, y, size.0, size.1));
+        (client.geometry.x, client.geometry.y) = (placed.x, placed.y);
+        Ok(())
+    }
+    
+    /// Monitor under the pointer (the primary one if it is off every monitor)
+    fn pointer_monitor(&self, conn: &RustConnection) -> Result<usize> {
+        let pointer = conn.query_pointer(self.root)?.reply()?;
+        Ok(self.monitor_at(pointer.root_x.into(), pointer.root_y.into()).unwrap_or_else(|| self.primary_monitor()))
+    }
+    
+    /// Whether a window's type keeps it unmanaged, only composited like an
+    /// override-redirect popup: notifications, tooltips and drag icons
+    pub fn is_unmanaged_type(&self, conn: &RustConnection, window: u32) -> bool {
+        use crate::wm::client_flags::WindowType;
+        
+        let types = self.atoms.get_window_type(conn, window).unwrap_or_default();
+        types.first().is_some_and(|&type_atom| {
+            matches!(WindowType::from_atom(type_atom, &self.atoms), WindowType::Notification | WindowType::Tooltip | WindowType::Dnd)
+        })
+    }
+    
     /// Unmanage a window (called when window is destroyed)
     pub fn unmanage_window(
         &mut self,
@@ -610,6 +999,9 @@ impl WindowManager {
         client: &mut Client,
     ) -> Result<()> {
         debug!("WM: Unmanaging window {}", client.window);
+        self.cycle.forget(client.window);
+        self.workspaces.forget(client.window);
+        self.snap_groups.forget(client.window);
         
         // Clear drag/resize state if this window was being dragged/resized
         if let Some(ref drag) = self.drag_state {
@@ -617,6 +1009,9 @@ impl WindowManager {
                 self.drag_state = None;
             }
         }
+        self.terminate.forget(client.window);
+        self.taskbar.forget(client.window);
+        self.migrations.forget(client.window);
         
         // Destroy window frame if it exists
         if let Some(frame_state) = &client.frame {
@@ -633,15 +1028,22 @@ impl WindowManager {
     }
     
     /// Close a window gracefully
+    ///
+    /// Windows supporting _NET_WM_PING are pinged as well, so one that
+    /// ignores the request because it hangs shows up as unresponsive.
     pub fn close_window(
         &mut self,
         conn: &RustConnection,
+        windows: &HashMap<u32, Client>,
         window_id: u32,
     ) -> Result<()> {
         info!("Closing window {}", window_id);
         
         // Send WM_DELETE_WINDOW message
         self.atoms.send_delete_window(conn, window_id)?;
+        if windows.get(&window_id).is_some_and(|c| c.wm_flags.contains(crate::wm::client_flags::WmFlags::PING)) {
+            self.terminate.ping(conn, &self.atoms, window_id, self.time.now())?;
+        }
         conn.flush()?;
         
         Ok(())
@@ -679,25 +1081,15 @@ impl WindowManager {
             client.set_restore_geometry(Some(client.geometry));
         }
         
-        // Get screen size
-        let screen = &conn.setup().roots[self.screen_num];
-        let max_width = screen.width_in_pixels as u32;
-        let max_height = screen.height_in_pixels as u32;
-        
-        // Account for decorations (titlebar height + borders)
-        const TITLEBAR_HEIGHT: u32 = 32;
-        const BORDER_WIDTH: u32 = 2;
-        
-        // Final frame outer geometry: (0, 0, max_width, max_height)
-        // Internal size of the frame:
-        let frame_width = max_width - (BORDER_WIDTH * 2);
-        let frame_height = max_height - (BORDER_WIDTH * 2);
-        
-        // Update window geometry (client relative to root)
-        client.geometry.x = BORDER_WIDTH as i32;
-        client.geometry.y = (BORDER_WIDTH + TITLEBAR_HEIGHT) as i32;
-        client.geometry.width = frame_width;
-        client.geometry.height = frame_height - TITLEBAR_HEIGHT;
+        // Fill the work area of the monitor the window is on
+        let center_x = client.geometry.x + client.geometry.width as i32 / 2;
+        let center_y = client.geometry.y + client.geometry.height as i32 / 2;
+        let monitor = self.monitor_at(center_x, center_y).unwrap_or_else(|| self.primary_monitor());
+        let area = self.gaps.shrink(self.work_area(monitor));
+        // The frame's outer edge, borders included, is the work area, less
+        // what the size hints (e.g. a terminal's character cells) leave over
+        client.geometry = client.frame_metrics.client_geometry(area);
+        (client.geometry.width, client.geometry.height) = client.constrain_size(client.geometry.width, client.geometry.height);
         client.flags.insert(crate::wm::client_flags::ClientFlags::MAXIMIZED_VERT);
         client.flags.insert(crate::wm::client_flags::ClientFlags::MAXIMIZED_HORIZ);
         
@@ -705,31 +1097,20 @@ impl WindowManager {
         if let Some(frame_state) = &client.frame {
             let frame = decorations::WindowFrame::from_state(client.window, frame_state);
             
-            // Move frame so its border is flush with screen edge
-            // Frame position: (BORDER_WIDTH, BORDER_WIDTH) to account for borders
-            frame.move_to(conn, BORDER_WIDTH as i16, BORDER_WIDTH as i16)?;
-            // Get decorations config from default for now
-            // TODO: Store decorations config in WindowManager
-            frame.resize(conn, frame_width as u16, frame_height as u16, &crate::config::WindowDecorationConfig {
-                titlebar_height: 32,
-                border_width: 2,
-                button_size: 20,
-                button_padding: 5,
-            })?;
+            // Move frame so its border is flush with the work area edge
+            let frame_geometry = client.frame_metrics.frame_geometry(client.geometry);
+            frame.move_to(conn, frame_geometry.x as i16, frame_geometry.y as i16)?;
+            frame.resize(conn, client.geometry.width as u16, client.geometry.height as u16, &self.decoration)?;
         } else {
             // No frame, resize client directly
             conn.configure_window(
                 client.window,
                 &ConfigureWindowAux::new()
-                    .x(0)
-                    .y(0)
-                    .width(max_width)
-                    .height(max_height),
+                    .x(client.geometry.x)
+                    .y(client.geometry.y)
+                    .width(client.geometry.width)
+                    .height(client.geometry.height),
             )?;
-            client.geometry.x = 0;
-            client.geometry.y = 0;
-            client.geometry.width = max_width;
-            client.geometry.height = max_height;
         }
         
         // Update EWMH state
@@ -744,7 +1125,12 @@ impl WindowManager {
         Ok(())
     }
     
-    /// Set fullscreen state for a window (xfwm4-style: keep frame, hide it)
+    /// Set fullscreen state for a window
+    ///
+    /// The window covers the whole output of its monitor, struts and panels
+    /// included. A framed client stays in its frame (it has to stay viewable
+    /// to be composited): the frame loses its border and takes the output's
+    /// size, and the client covers the titlebar.
     pub fn set_fullscreen(
         &mut self,
         conn: &RustConnection,
@@ -752,6 +1138,7 @@ impl WindowManager {
         fullscreen: bool,
     ) -> Result<()> {
         debug!("Setting fullscreen={} for window {}", fullscreen, client.window);
+        self.stacking.mark_dirty();
         
         if fullscreen {
             // Save geometry before entering fullscreen
@@ -762,49 +1149,29 @@ impl WindowManager {
             // Set fullscreen flag
             client.flags.insert(crate::wm::client_flags::ClientFlags::FULLSCREEN);
             
-            // Get screen dimensions
-            let screen = &conn.setup().roots[self.screen_num];
-            let screen_width = screen.width_in_pixels as u32;
-            let screen_height = screen.height_in_pixels as u32;
-            
-            // Update client geometry to screen size
-            client.geometry.x = 0;
-            client.geometry.y = 0;
-            client.geometry.width = screen_width;
-            client.geometry.height = screen_height;
+            // Output of the monitor the window is on
+            let center_x = client.geometry.x + client.geometry.width as i32 / 2;
+            let center_y = client.geometry.y + client.geometry.height as i32 / 2;
+            let monitor = self.monitor_at(center_x, center_y).unwrap_or_else(|| self.primary_monitor());
+            let output = match self.monitors.get(monitor) {
+                Some(m) => Geometry::new(m.x, m.y, m.width, m.height),
+                None => {
+                    let (width, height) = self.root_size();
+                    Geometry::new(0, 0, width, height)
+                }
+            };
+            client.geometry = output;
             
-            // Configure windows for fullscreen
-            // If window has a frame, unmap it and configure client directly
-            // This ensures decorations are not visible during fullscreen
+            let size = ConfigureWindowAux::new().width(output.width).height(output.height).border_width(0);
             if let Some(frame_state) = &client.frame {
-                let frame = decorations::WindowFrame::from_state(client.window, frame_state);
-                // Unmap the frame window to hide decorations
-                conn.unmap_window(frame.frame)?;
-                // Configure client directly at screen 0,0 with screen dimensions
-                conn.configure_window(
-                    client.window,
-                    &ConfigureWindowAux::new()
-                        .x(0)
-                        .y(0)
-                        .width(screen_width)
-                        .height(screen_height)
-                        .border_width(0),
-                )?;
+                conn.configure_window(frame_state.frame, &size.x(output.x).y(output.y))?;
+                conn.configure_window(client.window, &size.x(0).y(0).stack_mode(StackMode::ABOVE))?;
             } else {
-                // No frame - configure client directly
-                conn.configure_window(
-                    client.window,
-                    &ConfigureWindowAux::new()
-                        .x(0)
-                        .y(0)
-                        .width(screen_width)
-                        .height(screen_height)
-                        .border_width(0),
-                )?;
+                conn.configure_window(client.window, &size.x(output.x).y(output.y))?;
             }
             
             // Set NET_FRAME_EXTENTS to 0,0,0,0 (no decorations visible)
-            self.atoms.update_frame_extents(conn, client.window, 0, 0, 0, 0)?;
+            self.atoms.update_frame_extents(conn, client.window, FrameMetrics::NONE.extents())?;
             
             // Update EWMH state - add FULLSCREEN and ABOVE (always on top)
             // FULLSCREEN windows should always be on top, so set ABOVE state
@@ -823,37 +1190,19 @@ impl WindowManager {
                 
                 // Restore client window geometry
                 if let Some(frame_state) = &client.frame {
-                    // Window has frame - map it back and restore frame position and client position relative to frame
+                    // Window has frame - restore its border, position and size and the client position relative to it
                     let frame = decorations::WindowFrame::from_state(client.window, frame_state);
-                    const TITLEBAR_HEIGHT: i32 = 32;
-                    const BORDER_WIDTH: i32 = 2;
-                    
-            // Frame should be at (x - border, y - titlebar - border)
-            // No panel offset - use actual restore position
-            let frame_x = restore.x - BORDER_WIDTH;
-            let frame_y = restore.y - TITLEBAR_HEIGHT - BORDER_WIDTH;
-                    let frame_width = restore.width + (BORDER_WIDTH * 2) as u32;
-                    let frame_height = restore.height + (TITLEBAR_HEIGHT + BORDER_WIDTH * 2) as u32;
+                    let frame_geometry = client.frame_metrics.frame_geometry(restore);
+                    conn.configure_window(frame.frame, &ConfigureWindowAux::new().border_width(client.frame_metrics.border))?;
+                    frame.move_to(conn, frame_geometry.x as i16, frame_geometry.y as i16)?;
+                    frame.resize(conn, restore.width as u16, restore.height as u16, &self.decoration)?;
                     
-                    frame.move_to(conn, frame_x as i16, frame_y as i16)?;
-                    // Get decorations config - for now use default values
-                    // TODO: Store decorations config in WindowManager or pass it in
-                    frame.resize(conn, frame_width as u16, frame_height as u16, &crate::config::WindowDecorationConfig {
-                        titlebar_height: TITLEBAR_HEIGHT as u16,
-                        border_width: BORDER_WIDTH as u16,
-                        button_size: 20,
-                        button_padding: 5,
-                    })?;
-                    
-                    // Map the frame window back
-                    conn.map_window(frame.frame)?;
-                    
-                    // Client is positioned relative to frame
+                    // Client is positioned relative to frame, below the titlebar
                     conn.configure_window(
                         client.window,
                         &ConfigureWindowAux::new()
-                            .x(BORDER_WIDTH)
-                            .y((TITLEBAR_HEIGHT + BORDER_WIDTH) as i32)
+                            .x(0)
+                            .y(client.frame_metrics.titlebar as i32)
                             .width(restore.width)
                             .height(restore.height),
                     )?;
@@ -874,20 +1223,15 @@ impl WindowManager {
                 warn!("Cannot restore window {} - no saved geometry found", client.window);
             }
             
-            // Restore NET_FRAME_EXTENTS (Top: 32 titlebar, Left/Right/Bottom: 2 border)
-            if client.frame.is_some() {
-                self.atoms.update_frame_extents(conn, client.window, 2, 2, 32, 2)?;
-            } else {
-                self.atoms.update_frame_extents(conn, client.window, 0, 0, 0, 0)?;
-            }
+            // Restore _NET_FRAME_EXTENTS
+            self.atoms.update_frame_extents(conn, client.window, client.frame_metrics.extents())?;
             
-            // Remove EWMH fullscreen and ABOVE state
-            self.atoms.set_window_state(
-                conn,
-                client.window,
-                &[],
-                &[self.atoms._net_wm_state_fullscreen, self.atoms._net_wm_state_above],
-            )?;
+            // Remove EWMH fullscreen state, and ABOVE unless the window was kept above before
+            let mut remove = vec![self.atoms._net_wm_state_fullscreen];
+            if !client.flags.contains(crate::wm::client_flags::ClientFlags::ABOVE) {
+                remove.push(self.atoms._net_wm_state_above);
+            }
+            self.atoms.set_window_state(conn, client.window, &[], &remove)?;
         }
         
         conn.flush()?;
@@ -909,19 +1253,10 @@ impl WindowManager {
             // Restore frame and client window
             if let Some(frame_state) = &client.frame {
                 let frame = decorations::WindowFrame::from_state(client.window, frame_state);
-                // Frame position needs to account for titlebar - client is reparented at (0, TITLEBAR_HEIGHT)
-                // So if client.geometry.y is the client content position, frame should be at y - TITLEBAR_HEIGHT
-                const TITLEBAR_HEIGHT: i32 = 32;
-                let frame_y = client.geometry.y - TITLEBAR_HEIGHT;
-                frame.move_to(conn, client.geometry.x as i16, frame_y as i16)?;
-                // Get decorations config from default for now
-                // TODO: Store decorations config in WindowManager
-                frame.resize(conn, client.geometry.width as u16, client.geometry.height as u16, &crate::config::WindowDecorationConfig {
-                    titlebar_height: 32,
-                    border_width: 2,
-                    button_size: 20,
-                    button_padding: 5,
-                })?;
+                // Client is reparented below the titlebar
+                let frame_geometry = client.frame_metrics.frame_geometry(client.geometry);
+                frame.move_to(conn, frame_geometry.x as i16, frame_geometry.y as i16)?;
+                frame.resize(conn, client.geometry.width as u16, client.geometry.height as u16, &self.decoration)?;
             } else {
                 // No frame, restore client directly
                 conn.configure_window(
@@ -954,6 +1289,218 @@ impl WindowManager {
         Ok(())
     }
     
+    /// Tile a window to a half or quarter of its monitor's work area, or
+    /// restore it if it already is there. A half opposite another tiled
+    /// window joins it in a snap group.
+    pub fn tile_window(
+        &mut self,
+        conn: &RustConnection,
+        windows: &mut HashMap<u32, Client>,
+        window_id: u32,
+        position: crate::wm::client_flags::TilePosition,
+    ) -> Result<()> {
+        use crate::wm::client_flags::TilePosition;
+        if windows.get(&window_id).is_none_or(|client| client.is_fullscreen()) {
+            return Ok(());
+        }
+        self.leave_snap_group(conn, windows, window_id)?;
+        let client = windows.get_mut(&window_id).context("Window not found")?;
+        if client.tile_mode == position {
+            info!("Untiling window {}", client.window);
+            client.tile_mode = TilePosition::None;
+            return self.restore_window(conn, client);
+        }
+        if client.is_maximized() {
+            // Drop the maximized state but keep its restore geometry
+            let restore = client.restore_geometry();
+            self.restore_window(conn, client)?;
+            client.set_restore_geometry(restore);
+        } else if client.tile_mode == TilePosition::None {
+            client.set_restore_geometry(Some(client.geometry));
+        }
+        info!("Tiling window {} to {:?}", client.window, position);
+        client.tile_mode = position;
+        self.apply_tile(conn, client)?;
+        
+        let (opposite, vertical) = match position {
+            TilePosition::Left => (TilePosition::Right, false),
+            TilePosition::Right => (TilePosition::Left, false),
+            TilePosition::Up => (TilePosition::Down, true),
+            TilePosition::Down => (TilePosition::Up, true),
+            _ => return Ok(()),
+        };
+        let monitor = self.tile_monitor(&windows[&window_id]);
+        let partner = windows.values().find(|other| {
+            other.window != window_id
+                && other.tile_mode == opposite
+                && self.workspaces.is_visible(other)
+                && self.snap_groups.group_of(other.window).is_none()
+                && self.tile_monitor(other) == monitor
+        });
+        if let Some(partner) = partner.map(|other| other.window) {
+            debug!("Snap group: {} and {}", window_id, partner);
+            let (first, second) = if matches!(position, TilePosition::Left | TilePosition::Up) { (window_id, partner) } else { (partner, window_id) };
+            self.snap_groups.join(first, second, vertical);
+            self.refit_snap_group(conn, windows, window_id)?;
+        }
+        Ok(())
+    }
+    
+    /// Take a window out of its snap group, giving its partner its half back
+    fn leave_snap_group(&mut self, conn: &RustConnection, windows: &mut HashMap<u32, Client>, window_id: u32) -> Result<()> {
+        if let Some(partner) = self.snap_groups.forget(window_id)
+            && let Some(client) = windows.get_mut(&partner)
+            && client.tile_mode != crate::wm::client_flags::TilePosition::None
+        {
+            self.apply_tile(conn, client)?;
+        }
+        Ok(())
+    }
+    
+    /// Fit both windows of a window's snap group to its split
+    fn refit_snap_group(&self, conn: &RustConnection, windows: &mut HashMap<u32, Client>, window_id: u32) -> Result<()> {
+        let Some(group) = self.snap_groups.group_of(window_id) else {
+            return Ok(());
+        };
+        for id in [group.first, group.second] {
+            if let Some(client) = windows.get_mut(&id) {
+                self.apply_tile(conn, client)?;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Move the shared edge of a window's snap group by `delta` percent of
+    /// the area (towards the right or bottom when positive)
+    pub fn adjust_split(&mut self, conn: &RustConnection, windows: &mut HashMap<u32, Client>, window_id: u32, delta: i32) -> Result<()> {
+        let split = self.snap_groups.split_of(window_id) + delta as f32 / 100.0;
+        if self.snap_groups.set_split(window_id, split).is_some() {
+            self.refit_snap_group(conn, windows, window_id)?;
+        }
+        Ok(())
+    }
+    
+    /// Monitor a tiled window is fitted to: the one under its centre
+    fn tile_monitor(&self, client: &Client) -> usize {
+        let center_x = client.geometry.x + client.geometry.width as i32 / 2;
+        let center_y = client.geometry.y + client.geometry.height as i32 / 2;
+        self.monitor_at(center_x, center_y).unwrap_or_else(|| self.primary_monitor())
+    }
+    
+    /// Start dragging the shared edge of a visible snap group if the pointer
+    /// is on it; returns whether it was
+    pub fn start_split_drag(&mut self, conn: &RustConnection, windows: &HashMap<u32, Client>, x: i16, y: i16) -> Result<bool> {
+        let (x, y) = (x as i32, y as i32);
+        let grabbed = self.snap_groups.iter().find(|group| {
+            let (Some(first), Some(second)) = (windows.get(&group.first), windows.get(&group.second)) else {
+                return false;
+            };
+            if !self.workspaces.is_visible(first) || !self.workspaces.is_visible(second) {
+                return false;
+            }
+            let work_area = self.work_area(self.tile_monitor(first));
+            let area = self.gaps.shrink(work_area);
+            let first = self.gaps.tile(work_area, first.tile_mode, group.split);
+            let second = self.gaps.tile(work_area, second.tile_mode, group.split);
+            if group.vertical {
+                x >= area.x && x < area.x + area.width as i32
+                    && y >= first.y + first.height as i32 - SPLIT_GRAB && y < second.y + SPLIT_GRAB
+            } else {
+                y >= area.y && y < area.y + area.height as i32
+                    && x >= first.x + first.width as i32 - SPLIT_GRAB && x < second.x + SPLIT_GRAB
+            }
+        });
+        let Some(group) = grabbed.copied() else {
+            return Ok(false);
+        };
+        debug!("Dragging the split of {} and {}", group.first, group.second);
+        conn.grab_pointer(
+            false,
+            self.root,
+            EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION,
+            GrabMode::ASYNC,
+            GrabMode::ASYNC,
+            self.root,
+            0u32,
+            x11rb::CURRENT_TIME,
+        )?;
+        conn.flush()?;
+        self.split_drag = Some(group.first);
+        Ok(true)
+    }
+    
+    /// Whether the shared edge of a snap group is being dragged
+    pub fn is_splitting(&self) -> bool {
+        self.split_drag.is_some()
+    }
+    
+    /// Move the dragged shared edge to the pointer
+    pub fn update_split_drag(&mut self, conn: &RustConnection, windows: &mut HashMap<u32, Client>, x: i16, y: i16) -> Result<()> {
+        let Some(first) = self.split_drag else {
+            return Ok(());
+        };
+        let (Some(group), Some(client)) = (self.snap_groups.group_of(first), windows.get(&first)) else {
+            self.split_drag = None;
+            return Ok(());
+        };
+        let area = self.gaps.shrink(self.work_area(self.tile_monitor(client)));
+        let inner = self.gaps.inner as f32;
+        let split = if group.vertical {
+            ((y as i32 - area.y) as f32 - inner / 2.0) / (area.height as f32 - inner).max(1.0)
+        } else {
+            ((x as i32 - area.x) as f32 - inner / 2.0) / (area.width as f32 - inner).max(1.0)
+        };
+        self.snap_groups.set_split(first, split);
+        self.refit_snap_group(conn, windows, first)
+    }
+    
+    /// Fit a tiled window into its tile (after a gap or work area change)
+    fn apply_tile(&self, conn: &RustConnection, client: &mut Client) -> Result<()> {
+        let monitor = self.tile_monitor(client);
+        let tile = self.gaps.tile(self.work_area(monitor), client.tile_mode, self.snap_groups.split_of(client.window));
+        
+        // The tile is the frame's outer rectangle, borders and titlebar included
+        client.geometry = client.frame_metrics.client_geometry(tile);
+        (client.geometry.width, client.geometry.height) = client.constrain_size(
+            client.geometry.width.max(MIN_RESIZE_SIZE),
+            client.geometry.height.max(MIN_RESIZE_SIZE),
+        );
+        if let Some(frame_state) = &client.frame {
+            let frame = decorations::WindowFrame::from_state(client.window, frame_state);
+            let frame_geometry = client.frame_metrics.frame_geometry(client.geometry);
+            frame.move_to(conn, frame_geometry.x as i16, frame_geometry.y as i16)?;
+            frame.resize(conn, client.geometry.width as u16, client.geometry.height as u16, &self.decoration)?;
+        } else {
+            conn.configure_window(
+                client.window,
+                &ConfigureWindowAux::new()
+                    .x(client.geometry.x)
+                    .y(client.geometry.y)
+                    .width(client.geometry.width)
+                    .height(client.geometry.height),
+            )?;
+        }
+        conn.flush()?;
+        Ok(())
+    }
+    
+    /// Change the gaps and refit the maximized and tiled windows of the current workspace
+    pub fn set_gaps(&mut self, conn: &RustConnection, windows: &mut HashMap<u32, Client>, gaps: placement::Gaps) -> Result<()> {
+        debug!("Gaps: inner={} outer={}", gaps.inner, gaps.outer);
+        self.gaps = gaps;
+        for client in windows.values_mut() {
+            if !self.workspaces.is_visible(client) || client.is_fullscreen() {
+                continue;
+            }
+            if client.is_maximized() {
+                self.maximize_window(conn, client)?;
+            } else if client.tile_mode != crate::wm::client_flags::TilePosition::None {
+                self.apply_tile(conn, client)?;
+            }
+        }
+        Ok(())
+    }
+    
     /// Minimize window
     pub fn minimize_window(
         &mut self,
@@ -980,106 +1527,141 @@ impl WindowManager {
         Ok(())
     }
     
-    /// Set focus to a window
+    /// Roll a framed window up into its titlebar, or back down
+    pub fn set_shaded(&mut self, conn: &RustConnection, client: &mut Client, shaded: bool) -> Result<()> {
+        client.flags.set(crate::wm::client_flags::ClientFlags::SHADED, shaded);
+        let (add, remove) = if shaded {
+            (&[self.atoms._net_wm_state_shaded][..], &[][..])
+        } else {
+            (&[][..], &[self.atoms._net_wm_state_shaded][..])
+        };
+        self.atoms.set_window_state(conn, client.window, add, remove)?;
+        
+        // The client stays mapped, the frame just clips it
+        if let Some(frame) = &client.frame {
+            let height = if shaded {
+                client.frame_metrics.titlebar
+            } else {
+                client.frame_metrics.frame_geometry(client.geometry).height
+            };
+            conn.configure_window(frame.frame, &ConfigureWindowAux::new().height(height))?;
+        }
+        conn.flush()?;
+        Ok(())
+    }
+    
+    /// Keep a window above normal windows, or stop doing so
+    pub fn set_above(&mut self, conn: &RustConnection, client: &mut Client, above: bool) -> Result<()> {
+        client.flags.set(crate::wm::client_flags::ClientFlags::ABOVE, above);
+        let (add, remove) = if above {
+            client.flags.remove(crate::wm::client_flags::ClientFlags::BELOW);
+            (&[self.atoms._net_wm_state_above][..], &[self.atoms._net_wm_state_below][..])
+        } else {
+            (&[][..], &[self.atoms._net_wm_state_above][..])
+        };
+        self.atoms.set_window_state(conn, client.window, add, remove)?;
+        self.stacking.mark_dirty();
+        conn.flush()?;
+        Ok(())
+    }
+    
+    /// Show a window on all workspaces, or only on the current one
+    pub fn set_sticky(&mut self, conn: &RustConnection, client: &mut Client, sticky: bool) -> Result<()> {
+        client.flags.set(crate::wm::client_flags::ClientFlags::STICKY, sticky);
+        let (add, remove) = if sticky {
+            (&[self.atoms._net_wm_state_sticky][..], &[][..])
+        } else {
+            (&[][..], &[self.atoms._net_wm_state_sticky][..])
+        };
+        self.atoms.set_window_state(conn, client.window, add, remove)?;
+        
+        // Unstuck windows stay where they are seen
+        let workspace = if sticky { workspace::ALL_WORKSPACES } else { self.workspaces.current_workspace };
+        client.win_workspace = workspace;
+        conn.change_property32(
+            PropMode::REPLACE,
+            client.window,
+            self.atoms.net_wm_desktop,
+            AtomEnum::CARDINAL,
+            &[workspace],
+        )?;
+        conn.flush()?;
+        Ok(())
+    }
+    
+    /// Focus no window (the root window takes keyboard input)
+    pub fn clear_focus(&mut self, conn: &RustConnection, windows: &mut HashMap<u32, Client>) -> Result<()> {
+        for client in windows.values_mut().filter(|c| c.focused()) {
+            client.set_focused(false);
+            self.atoms.set_window_state(conn, client.window, &[], &[self.atoms._net_wm_state_focused])?;
+        }
+        conn.set_input_focus(InputFocus::POINTER_ROOT, self.root, self.time.of_event())?;
+        self.atoms.update_active_window(conn, self.root, None)?;
+        Ok(())
+    }
+    
+    /// Set focus to a window and raise it
     pub fn set_focus(
         &mut self,
         conn: &RustConnection,
         windows: &mut HashMap<u32, Client>,
         window_id: u32,
     ) -> Result<()> {
-        // #region agent log
-        {
-            use std::fs::OpenOptions;
-            use std::io::Write;
-            let log_entry = serde_json::json!({
-                "sessionId": "debug-session",
-                "runId": "run1",
-                "hypothesisId": "B",
-                "location": "wm/mod.rs:960",
-                "message": "set_focus called",
-                "data": {"window_id": window_id, "has_frame": windows.get(&window_id).and_then(|c| c.frame.as_ref().map(|_| true)).unwrap_or(false)},
-                "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()
-            });
-            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("/home/bizkit/GitHub/area/.cursor/debug.log") {
-                let _ = writeln!(file, "{}", log_entry);
+        self.set_focus_by(conn, windows, window_id, focus::FocusCause::Explicit)
+    }
+    
+    /// Set focus to a window, raising it as the raise options say for `cause`
+    pub fn set_focus_by(
+        &mut self,
+        conn: &RustConnection,
+        windows: &mut HashMap<u32, Client>,
+        window_id: u32,
+        cause: focus::FocusCause,
+    ) -> Result<()> {
+        debug!(target: "area::wm::focus", "set_focus({}, {:?})", window_id, cause);
+        
+        // A window with an open modal dialog hands focus to the dialog
+        let window_id = match transients::blocking_modal(windows, window_id) {
+            Some(modal) => {
+                debug!(target: "area::wm::focus", "Window {} is blocked by modal {}", window_id, modal);
+                modal
             }
-        }
-        // #endregion
+            None => window_id,
+        };
         
         // Unfocus previous window
         for client in windows.values_mut() {
             if client.focused() && client.window != window_id {
                 client.set_focused(false);
+                self.atoms.set_window_state(conn, client.window, &[], &[self.atoms._net_wm_state_focused])?;
             }
         }
         
         // Focus new window
         if let Some(client) = windows.get_mut(&window_id) {
             client.set_focused(true);
+            self.cycle.note_focus(window_id);
+            self.workspaces.note_focus(window_id);
             
-            // #region agent log
-            {
-                use std::fs::OpenOptions;
-                use std::io::Write;
-                let log_entry = serde_json::json!({
-                    "sessionId": "debug-session",
-                    "runId": "run1",
-                    "hypothesisId": "B",
-                    "location": "wm/mod.rs:978",
-                    "message": "Calling set_input_focus",
-                    "data": {"window_id": window_id, "has_frame": client.frame.is_some()},
-                    "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()
-                });
-                if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("/home/bizkit/GitHub/area/.cursor/debug.log") {
-                    let _ = writeln!(file, "{}", log_entry);
-                }
-            }
-            // #endregion
+            trace!(target: "area::wm::focus", "set_input_focus({}) framed={}", window_id, client.frame.is_some());
             
             // Set X11 input focus
             let focus_result = conn.set_input_focus(
                 InputFocus::POINTER_ROOT,
                 window_id,
-                x11rb::CURRENT_TIME,
+                self.time.of_event(),
             );
             
-            // #region agent log
-            {
-                use std::fs::OpenOptions;
-                use std::io::Write;
-                let log_entry = serde_json::json!({
-                    "sessionId": "debug-session",
-                    "runId": "run1",
-                    "hypothesisId": "B",
-                    "location": "wm/mod.rs:985",
-                    "message": "set_input_focus result",
-                    "data": {"window_id": window_id, "success": focus_result.is_ok(), "error": focus_result.as_ref().err().map(|e| format!("{:?}", e))},
-                    "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()
-                });
-                if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("/home/bizkit/GitHub/area/.cursor/debug.log") {
-                    let _ = writeln!(file, "{}", log_entry);
-                }
-            }
             focus_result?;
-            // #endregion
             
-            // Raise window to top
-            if let Some(frame) = &client.frame {
-                conn.configure_window(
-                    frame.frame,
-                    &ConfigureWindowAux::new()
-                        .stack_mode(StackMode::ABOVE),
-                )?;
-            } else {
-                conn.configure_window(
-                    window_id,
-                    &ConfigureWindowAux::new()
-                        .stack_mode(StackMode::ABOVE),
-                )?;
+            // Raise window to the top of its layer (applied by restack_if_needed)
+            if self.focus.raise_now(window_id, cause) {
+                self.stacking.raise_window(window_id);
             }
             
             // Update EWMH active window
             self.atoms.update_active_window(conn, self.root, Some(window_id))?;
+            self.atoms.set_window_state(conn, window_id, &[self.atoms._net_wm_state_focused], &[])?;
             
             conn.flush()?;
         }
@@ -1087,6 +1669,540 @@ impl WindowManager {
         Ok(())
     }
     
+    /// A click inside a client window: focus it, raising it unless only
+    /// titlebar clicks raise
+    pub fn click_focus(
+        &mut self,
+        conn: &RustConnection,
+        windows: &mut HashMap<u32, Client>,
+        window_id: u32,
+    ) -> Result<()> {
+        if !windows.get(&window_id).is_some_and(|c| c.focused()) {
+            return self.set_focus_by(conn, windows, window_id, focus::FocusCause::Click);
+        }
+        if self.focus.raise_on_click == focus::RaiseOnClick::Anywhere {
+            self.stacking.raise_window(window_id);
+        }
+        Ok(())
+    }
+    
+    /// Raise the window whose auto-raise delay ran out, if it still has focus
+    pub fn auto_raise(&mut self, windows: &HashMap<u32, Client>) {
+        if let Some(window_id) = self.focus.take_due_raise()
+            && windows.get(&window_id).is_some_and(|c| c.focused())
+        {
+            debug!(target: "area::wm::focus", "Auto-raising {}", window_id);
+            self.stacking.raise_window(window_id);
+        }
+    }
+    
+    /// Apply pending stacking changes (see `StackingManager::restack`)
+    pub fn restack_if_needed(
+        &mut self,
+        conn: &RustConnection,
+        windows: &mut HashMap<u32, Client>,
+    ) -> Result<()> {
+        if self.stacking.is_dirty() {
+            self.stacking.restack(conn, &self.atoms, self.root, windows)?;
+        }
+        Ok(())
+    }
+    
+    /// Re-read the monitor layout from RandR
+    pub fn refresh_monitors(&mut self, conn: &RustConnection) -> Result<()> {
+        self.monitors = screen::query_monitors(conn, self.root)?;
+        for m in &self.monitors {
+            info!("Monitor {}: {}x{}+{}+{}{}{}", m.name, m.width, m.height, m.x, m.y,
+                if m.rotation != 0 { format!(" rotated {}°", m.rotation) } else { String::new() },
+                if m.primary { " (primary)" } else { "" });
+        }
+        // Edge barriers follow the monitor geometry
+        self.edge_triggers.rebuild(conn, self.root, &self.monitors)
+    }
+    
+    /// Enable or disable edge pressure on an edge of a monitor
+    pub fn set_edge_trigger(&mut self, conn: &RustConnection, monitor: u32, edge: strut::StrutEdge, enabled: bool) -> Result<()> {
+        self.edge_triggers.set_enabled(monitor, edge, enabled);
+        self.edge_triggers.rebuild(conn, self.root, &self.monitors)
+    }
+    
+    /// Index of the monitor containing a root coordinate
+    pub fn monitor_at(&self, x: i32, y: i32) -> Option<usize> {
+        self.monitors.iter().position(|m| {
+            x >= m.x && x < m.x + m.width as i32 && y >= m.y && y < m.y + m.height as i32
+        })
+    }
+    
+    /// Index of the primary monitor (first monitor if none is marked primary)
+    pub fn primary_monitor(&self) -> usize {
+        self.monitors.iter().position(|m| m.primary).unwrap_or(0)
+    }
+    
+    /// Nearest monitor lying entirely in `direction` from monitor `from`
+    pub fn monitor_in_direction(&self, from: usize, direction: keyboard::Direction) -> Option<usize> {
+        use keyboard::Direction;
+        
+        let src = self.monitors.get(from)?;
+        let center = |m: &screen::Monitor| (m.x + m.width as i32 / 2, m.y + m.height as i32 / 2);
+        let (cx, cy) = center(src);
+        self.monitors
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| match direction {
+                Direction::Left => m.x + m.width as i32 <= src.x,
+                Direction::Right => m.x >= src.x + src.width as i32,
+                Direction::Up => m.y + m.height as i32 <= src.y,
+                Direction::Down => m.y >= src.y + src.height as i32,
+            })
+            .min_by_key(|(_, m)| {
+                let (x, y) = center(m);
+                (x - cx).pow(2) + (y - cy).pow(2)
+            })
+            .map(|(index, _)| index)
+    }
+    
+    /// Move a window to the adjacent monitor in `direction`
+    ///
+    /// Keeps its position relative to the work area (shrinking it if it
+    /// doesn't fit); a maximized window is re-maximized on the new monitor.
+    /// Returns false if there is no monitor that way.
+    pub fn move_to_monitor(
+        &mut self,
+        conn: &RustConnection,
+        client: &mut Client,
+        direction: keyboard::Direction,
+    ) -> Result<bool> {
+        if client.is_fullscreen() {
+            debug!("Not moving fullscreen window {} between monitors", client.window);
+            return Ok(false);
+        }
+        let center_x = client.geometry.x + client.geometry.width as i32 / 2;
+        let center_y = client.geometry.y + client.geometry.height as i32 / 2;
+        let from = self.monitor_at(center_x, center_y).unwrap_or_else(|| self.primary_monitor());
+        let Some(to) = self.monitor_in_direction(from, direction) else {
+            return Ok(false);
+        };
+        let (src, dst) = (self.work_area(from), self.work_area(to));
+        info!("Moving window {} from monitor {} to {} ({:?})", client.window, from, to, direction);
+        
+        // Same relative offset within the work area, clamped to fit
+        let geometry = migration::translate(client.geometry, src, dst);
+        let restore = client.restore_geometry().map(|restore| migration::translate(restore, src, dst));
+        self.place_client(conn, client, geometry, restore)?;
+        Ok(true)
+    }
+    
+    /// Monitor names with their rectangles and work areas, to compare a
+    /// layout change against (see `migrate_windows`)
+    pub fn output_areas(&self) -> Vec<(String, Geometry, Geometry)> {
+        self.monitors
+            .iter()
+            .enumerate()
+            .map(|(index, m)| (m.name.clone(), Geometry::new(m.x, m.y, m.width, m.height), self.work_area(index)))
+            .collect()
+    }
+    
+    /// Follow monitors unplugged or plugged back in since `previous` (the
+    /// `output_areas` before the layout changed)
+    ///
+    /// Windows of a removed monitor move to the primary one; those of a
+    /// monitor that came back within the grace period return, unless they
+    /// were moved in the meantime. Fullscreen windows span the whole screen
+    /// and panels place themselves, so both are left alone.
+    pub fn migrate_windows(
+        &mut self,
+        conn: &RustConnection,
+        windows: &mut HashMap<u32, Client>,
+        previous: &[(String, Geometry, Geometry)],
+    ) -> Result<()> {
+        use crate::wm::client_flags::WindowType;
+        
+        let current = self.output_areas();
+        let removed: Vec<_> = previous.iter().filter(|(name, ..)| !current.iter().any(|(n, ..)| n == name)).collect();
+        if !removed.is_empty() && !current.is_empty() {
+            let dst = self.work_area(self.primary_monitor());
+            for client in windows.values_mut() {
+                if client.is_fullscreen() || matches!(client.type_, WindowType::Dock | WindowType::Desktop) {
+                    continue;
+                }
+                let center_x = client.geometry.x + client.geometry.width as i32 / 2;
+                let center_y = client.geometry.y + client.geometry.height as i32 / 2;
+                let Some((name, _, area)) = removed.iter().copied().find(|(_, m, _)| {
+                    center_x >= m.x && center_x < m.x + m.width as i32 && center_y >= m.y && center_y < m.y + m.height as i32
+                }) else {
+                    continue;
+                };
+                info!("Moving window {} off removed monitor {}", client.window, name);
+                let (geometry, restore) = (client.geometry, client.restore_geometry());
+                let translated = restore.map(|restore| migration::translate(restore, *area, dst));
+                self.place_client(conn, client, migration::translate(geometry, *area, dst), translated)?;
+                self.migrations.record(client.window, name, geometry, restore, *area, client.geometry);
+            }
+        }
+        
+        for (name, _, area) in current.iter().filter(|(name, ..)| !previous.iter().any(|(n, ..)| n == name)) {
+            for (window, migrated) in self.migrations.returning(name) {
+                let Some(client) = windows.get_mut(&window) else { continue };
+                if client.geometry != migrated.moved_to || client.is_fullscreen() {
+                    continue;
+                }
+                info!("Returning window {} to monitor {}", window, name);
+                let geometry = migration::translate(migrated.geometry, migrated.area, *area);
+                let restore = migrated.restore.map(|restore| migration::translate(restore, migrated.area, *area));
+                self.place_client(conn, client, geometry, restore)?;
+            }
+        }
+        conn.flush()?;
+        Ok(())
+    }
+    
+    /// Move and resize a client; a maximized or tiled one is fitted to the
+    /// monitor under its new centre again, keeping `restore` to restore to
+    fn place_client(&mut self, conn: &RustConnection, client: &mut Client, geometry: Geometry, restore: Option<Geometry>) -> Result<()> {
+        client.geometry = geometry;
+        if client.is_maximized() || client.tile_mode != crate::wm::client_flags::TilePosition::None {
+            if restore.is_some() {
+                client.set_restore_geometry(restore);
+            }
+            // Both pick the monitor under the window's centre
+            return if client.is_maximized() { self.maximize_window(conn, client) } else { self.apply_tile(conn, client) };
+        }
+        
+        if let Some(frame_state) = &client.frame {
+            let frame = decorations::WindowFrame::from_state(client.window, frame_state);
+            let frame_geometry = client.frame_metrics.frame_geometry(client.geometry);
+            frame.move_to(conn, frame_geometry.x as i16, frame_geometry.y as i16)?;
+            frame.resize(conn, client.geometry.width as u16, client.geometry.height as u16, &self.decoration)?;
+        } else {
+            conn.configure_window(
+                client.window,
+                &ConfigureWindowAux::new()
+                    .x(client.geometry.x)
+                    .y(client.geometry.y)
+                    .width(client.geometry.width)
+                    .height(client.geometry.height),
+            )?;
+        }
+        conn.flush()?;
+        Ok(())
+    }
+    
+    /// Size of the area spanned by all monitors
+    fn root_size(&self) -> (u32, u32) {
+        self.monitors.iter().fold((0, 0), |(w, h), m| {
+            ((m.x + m.width as i32).max(w as i32) as u32, (m.y + m.height as i32).max(h as i32) as u32)
+        })
+    }
+    
+    /// Re-read a client's `_NET_WM_STRUT(_PARTIAL)`; returns whether it changed
+    pub fn update_client_strut(&mut self, conn: &RustConnection, window: u32) -> Result<bool> {
+        let (root_width, root_height) = self.root_size();
+        let reservations = strut::read_client_strut(conn, &self.atoms, window, root_width, root_height)?;
+        let changed = if reservations.is_empty() {
+            self.client_struts.remove(&window).is_some()
+        } else {
+            self.client_struts.insert(window, reservations.clone()) != Some(reservations)
+        };
+        if changed {
+            debug!("Strut of window {} changed", window);
+        }
+        Ok(changed)
+    }
+    
+    /// Forget an unmanaged client's strut; returns whether it had one
+    pub fn remove_client_strut(&mut self, window: u32) -> bool {
+        self.client_struts.remove(&window).is_some()
+    }
+    
+    /// Reserve (or with `None`, release) a shell panel on one edge of a monitor
+    pub fn set_panel_strut(&mut self, monitor: u32, edge: strut::StrutEdge, panel: Option<strut::PanelStrut>) {
+        debug!("Panel strut on monitor {} {:?}: {:?}", monitor, edge, panel);
+        match panel {
+            Some(panel) => self.panel_struts.insert((monitor, edge), panel),
+            None => self.panel_struts.remove(&(monitor, edge)),
+        };
+    }
+    
+    /// Usable area of a monitor once client and panel struts are subtracted
+    pub fn work_area(&self, monitor: usize) -> Geometry {
+        let (root_width, root_height) = self.root_size();
+        let Some(target) = self.monitors.get(monitor) else {
+            return Geometry { x: 0, y: 0, width: root_width.max(1), height: root_height.max(1) };
+        };
+        
+        let mut reservations: Vec<strut::Reservation> = self.client_struts.values().flatten().copied().collect();
+        for (&(index, edge), panel) in &self.panel_struts {
+            if let Some(owner) = self.monitors.get(index as usize) {
+                reservations.push(panel.to_reservation(edge, owner, root_width, root_height));
+            }
+        }
+        strut::work_area(target, &reservations, root_width, root_height)
+    }
+    
+    /// Internal state as JSON for debugging (`DumpState`); window titles are
+    /// replaced when `redact_titles` is set
+    pub fn dump_state(&self, clients: &HashMap<u32, Client>, redact_titles: bool) -> serde_json::Value {
+        use serde_json::json;
+        let geometry = |g: &Geometry| json!({ "x": g.x, "y": g.y, "width": g.width, "height": g.height });
+        let mut windows: Vec<&Client> = clients.values().collect();
+        windows.sort_by_key(|client| client.serial);
+        let clients: Vec<serde_json::Value> = windows
+            .iter()
+            .map(|client| {
+                json!({
+                    "window": client.window,
+                    "frame": client.frame.as_ref().map(|frame| json!({
+                        "frame": frame.frame,
+                        "titlebar": frame.titlebar,
+                        "buttons": frame.buttons.iter().map(|button| button.window).collect::<Vec<_>>(),
+                    })),
+                    "title": if redact_titles { "[redacted]".to_string() } else { client.name.clone() },
+                    "class": client.class_hint.as_ref().map(|class| [&class.res_name, &class.res_class]),
+                    "pid": client.pid,
+                    "type": format!("{:?}", client.type_),
+                    "layer": format!("{:?}", client.win_layer),
+                    "workspace": client.win_workspace,
+                    "geometry": geometry(&client.geometry),
+                    "applied_geometry": geometry(&client.applied_geometry),
+                    "saved_geometry": client.saved_geometry.as_ref().map(geometry),
+                    "flags": format!("{:?}", client.flags),
+                    "wm_flags": format!("{:?}", client.wm_flags),
+                    "xfwm_flags": format!("{:?}", client.xfwm_flags),
+                    "tile_mode": format!("{:?}", client.tile_mode),
+                    "opacity": client.opacity,
+                    "transient_for": client.transient_for,
+                    "group_leader": client.group_leader,
+                    "struts": client.struts,
+                })
+            })
+            .collect();
+        let workspaces: Vec<serde_json::Value> = (0..self.workspaces.workspace_count)
+            .map(|index| {
+                json!({
+                    "index": index,
+                    "name": self.workspaces.workspace_names.get(index as usize),
+                    "last_focused": self.workspaces.last_focused(index),
+                    "focus_policy": format!("{:?}", self.workspaces.focus_policy(index)),
+                })
+            })
+            .collect();
+        let reservation = |r: &strut::Reservation| json!({ "edge": format!("{:?}", r.edge), "size": r.size, "start": r.start, "end": r.end });
+        let mut client_struts: Vec<(&u32, &Vec<strut::Reservation>)> = self.client_struts.iter().collect();
+        client_struts.sort_by_key(|(window, _)| **window);
+        let mut panel_struts: Vec<(&(u32, strut::StrutEdge), &strut::PanelStrut)> = self.panel_struts.iter().collect();
+        panel_struts.sort_by_key(|((monitor, edge), _)| (*monitor, format!("{:?}", edge)));
+        json!({
+            "clients": clients,
+            "focus": {
+                "focused": self.focus.focused_window,
+                "history": self.cycle.mru(),
+                "policy": format!("{:?}", self.focus_policy),
+            },
+            "workspaces": {
+                "current": self.workspaces.current_workspace,
+                "count": self.workspaces.workspace_count,
+                "layout": format!("{:?}", self.workspaces.desktop_layout),
+                "workspaces": workspaces,
+            },
+            "stacking": self.stacking.order(),
+            "monitors": self.monitors.iter().enumerate().map(|(index, monitor)| json!({
+                "name": monitor.name,
+                "x": monitor.x,
+                "y": monitor.y,
+                "width": monitor.width,
+                "height": monitor.height,
+                "primary": monitor.primary,
+                "work_area": geometry(&self.work_area(index)),
+            })).collect::<Vec<_>>(),
+            "struts": {
+                "clients": client_struts.iter().map(|(window, reservations)| json!({
+                    "window": window,
+                    "reservations": reservations.iter().map(reservation).collect::<Vec<_>>(),
+                })).collect::<Vec<_>>(),
+                "panels": panel_struts.iter().map(|((monitor, edge), panel)| json!({
+                    "monitor": monitor,
+                    "edge": format!("{:?}", edge),
+                    "size": panel.size,
+                    "start": panel.start,
+                    "end": panel.end,
+                })).collect::<Vec<_>>(),
+            },
+        })
+    }
+    
+    /// Publish the primary monitor's work area as `_NET_WORKAREA`, once per desktop
+    pub fn update_workarea(&self, conn: &RustConnection) -> Result<()> {
+        let area = self.work_area(self.primary_monitor());
+        // One rectangle per desktop, all the same
+        let rect = [area.x as u32, area.y as u32, area.width, area.height];
+        let areas: Vec<u32> = std::iter::repeat_n(rect, self.workspaces.workspace_count as usize).flatten().collect();
+        conn.change_property32(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms.net_workarea,
+            AtomEnum::CARDINAL,
+            &areas,
+        )?;
+        Ok(())
+    }
+    
+    /// Work areas of all monitors
+    pub fn work_areas(&self) -> Vec<Geometry> {
+        (0..self.monitors.len().max(1)).map(|monitor| self.work_area(monitor)).collect()
+    }
+    
+    /// Client position that keeps the titlebar reachable, if the current one doesn't
+    ///
+    /// Fullscreen and maximized windows, panels, the desktop and transient
+    /// popups (menus, tooltips, notifications) are left alone.
+    fn constrained_position(&self, client: &Client) -> Option<(i32, i32)> {
+        use crate::wm::client_flags::WindowType;
+        
+        let constrained_type = matches!(
+            client.type_,
+            WindowType::Normal
+                | WindowType::Dialog
+                | WindowType::ModalDialog
+                | WindowType::Utility
+                | WindowType::Toolbar
+                | WindowType::Menu
+                | WindowType::Splashscreen
+        );
+        if !constrained_type || client.is_fullscreen() || client.is_maximized() {
+            return None;
+        }
+        
+        let titlebar = client.frame_metrics.titlebar;
+        let outer = client.frame_metrics.frame_geometry(client.geometry);
+        let constrained = self.placement.constrain(outer, titlebar, &self.work_areas());
+        (constrained != outer).then_some((constrained.x, constrained.y + titlebar as i32))
+    }
+    
+    /// Move a window back into reach if its titlebar is off-screen or under a
+    /// panel (e.g. after a monitor was unplugged); returns whether it moved
+    pub fn constrain_window(&self, conn: &RustConnection, client: &mut Client) -> Result<bool> {
+        let Some((x, y)) = self.constrained_position(client) else {
+            return Ok(false);
+        };
+        client.geometry.x = x;
+        client.geometry.y = y;
+        match &client.frame {
+            Some(frame) => {
+                let frame_geometry = client.frame_metrics.frame_geometry(client.geometry);
+                conn.configure_window(frame.frame, &ConfigureWindowAux::new().x(frame_geometry.x).y(frame_geometry.y))?
+            }
+            None => conn.configure_window(client.window, &ConfigureWindowAux::new().x(x).y(y))?,
+        };
+        Ok(true)
+    }
+    
+    /// Apply a geometry a managed client asked for (ConfigureRequest)
+    ///
+    /// The position is the client's in root coordinates; framed windows move
+    /// their frame. The titlebar is kept reachable.
+    pub fn configure_client(&self, conn: &RustConnection, client: &mut Client, requested: Geometry) -> Result<()> {
+        client.geometry = requested;
+        if let Some((x, y)) = self.constrained_position(client) {
+            client.geometry.x = x;
+            client.geometry.y = y;
+        }
+        
+        let geometry = client.geometry;
+        if let Some(frame_state) = &client.frame {
+            let frame = decorations::WindowFrame::from_state(client.window, frame_state);
+            let frame_geometry = client.frame_metrics.frame_geometry(geometry);
+            frame.move_to(conn, frame_geometry.x as i16, frame_geometry.y as i16)?;
+            frame.resize(conn, geometry.width as u16, geometry.height as u16, &self.decoration)?;
+        } else {
+            conn.configure_window(
+                client.window,
+                &ConfigureWindowAux::new()
+                    .x(geometry.x)
+                    .y(geometry.y)
+                    .width(geometry.width)
+                    .height(geometry.height),
+            )?;
+        }
+        Ok(())
+    }
+    
+    /// Tell a client its geometry in root coordinates with a synthetic
+    /// ConfigureNotify (ICCCM 4.1.5), e.g. after a refused ConfigureRequest
+    /// or a move of its frame
+    pub fn send_configure_notify(&self, conn: &RustConnection, client: &Client) -> Result<()> {
+        let geometry = client.geometry;
+        let event = ConfigureNotifyEvent {
+            response_type: CONFIGURE_NOTIFY_EVENT,
+            sequence: 0,
+            event: client.window,
+            window: client.window,
+            above_sibling: x11rb::NONE,
+            x: geometry.x as i16,
+            y: geometry.y as i16,
+            width: geometry.width as u16,
+            height: geometry.height as u16,
+            border_width: 0,
+            override_redirect: false,
+        };
+        conn.send_event(false, client.window, EventMask::STRUCTURE_NOTIFY, event)?;
+        Ok(())
+    }
+    
+    /// Passively grab modifier+left/right button on a client window for
+    /// moving and resizing it from anywhere inside
+    fn grab_drag_buttons(&self, conn: &RustConnection, client: &Client) -> Result<()> {
+        if self.drag_modifiers == 0 {
+            return Ok(());
+        }
+        let excluded = client.class_hint.as_ref().is_some_and(|hint| {
+            self.drag_exclude.contains(&hint.res_name.to_lowercase())
+                || self.drag_exclude.contains(&hint.res_class.to_lowercase())
+        });
+        if excluded {
+            debug!("Window {} is excluded from modifier drags", client.window);
+            return Ok(());
+        }
+        
+        // Whatever the state of Caps Lock and Num Lock
+        for button in [ButtonIndex::M1, ButtonIndex::M3] {
+            for lock in keyboard::LOCK_MASKS {
+                conn.grab_button(
+                    false,
+                    client.window,
+                    EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION,
+                    GrabMode::ASYNC,
+                    GrabMode::ASYNC,
+                    x11rb::NONE,
+                    x11rb::NONE,
+                    button,
+                    ModMask::from(self.drag_modifiers | lock),
+                )?;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Synchronously grab unmodified clicks on a client window, so a click
+    /// inside it can focus and raise it before being replayed to the client
+    fn grab_click_buttons(&self, conn: &RustConnection, client: &Client) -> Result<()> {
+        for button in [ButtonIndex::M1, ButtonIndex::M2, ButtonIndex::M3] {
+            for lock in keyboard::LOCK_MASKS {
+                conn.grab_button(
+                    false,
+                    client.window,
+                    EventMask::BUTTON_PRESS,
+                    GrabMode::SYNC,
+                    GrabMode::ASYNC,
+                    x11rb::NONE,
+                    x11rb::NONE,
+                    button,
+                    ModMask::from(lock),
+                )?;
+            }
+        }
+        Ok(())
+    }
+    
     /// Start dragging a window
     pub fn start_drag(
         &mut self,
@@ -1096,24 +2212,7 @@ impl WindowManager {
         start_x: i16,
         start_y: i16,
     ) -> Result<()> {
-        // #region agent log
-        {
-            use std::fs::OpenOptions;
-            use std::io::Write;
-            let log_entry = serde_json::json!({
-                "sessionId": "debug-session",
-                "runId": "run1",
-                "hypothesisId": "C",
-                "location": "wm/mod.rs:1009",
-                "message": "start_drag called",
-                "data": {"window_id": window_id, "start_x": start_x, "start_y": start_y},
-                "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()
-            });
-            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("/home/bizkit/GitHub/area/.cursor/debug.log") {
-                let _ = writeln!(file, "{}", log_entry);
-            }
-        }
-        // #endregion
+        debug!("start_drag({}) at ({}, {})", window_id, start_x, start_y);
         
         let client = windows.get(&window_id)
             .context("Window not found")?;
@@ -1140,6 +2239,10 @@ impl WindowManager {
             warn!("Failed to grab pointer for drag: {:?}", e);
         }
         
+        let center_x = client.geometry.x + client.geometry.width as i32 / 2;
+        let center_y = client.geometry.y + client.geometry.height as i32 / 2;
+        let monitor = self.monitor_at(center_x, center_y).unwrap_or_else(|| self.primary_monitor());
+        
         // Store drag state with root coordinates
         self.drag_state = Some(DragState {
             window_id,
@@ -1147,36 +2250,37 @@ impl WindowManager {
             start_y,
             window_start_x: client.geometry.x,
             window_start_y: client.geometry.y,
+            last_x: start_x,
+            last_y: start_y,
+            edge: None,
+            resize: None,
+            monitor,
         });
         
-        // #region agent log
-        {
-            use std::fs::OpenOptions;
-            use std::io::Write;
-            let log_entry = serde_json::json!({
-                "sessionId": "debug-session",
-                "runId": "run1",
-                "hypothesisId": "C",
-                "location": "wm/mod.rs:1048",
-                "message": "drag_state set",
-                "data": {
-                    "window_id": window_id,
-                    "window_start_x": client.geometry.x,
-                    "window_start_y": client.geometry.y,
-                    "grab_success": grab_success
-                },
-                "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()
-            });
-            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("/home/bizkit/GitHub/area/.cursor/debug.log") {
-                let _ = writeln!(file, "{}", log_entry);
-            }
-        }
-        // #endregion
+        debug!("Drag state set for {}: origin=({}, {}) grab_ok={}",
+            window_id, client.geometry.x, client.geometry.y, grab_success);
         
         conn.flush()?;
         Ok(())
     }
     
+    /// Start resizing a window by its bottom-right corner from the pointer position
+    pub fn start_resize(
+        &mut self,
+        conn: &RustConnection,
+        windows: &HashMap<u32, Client>,
+        window_id: u32,
+        start_x: i16,
+        start_y: i16,
+    ) -> Result<()> {
+        self.start_drag(conn, windows, window_id, start_x, start_y)?;
+        if let Some(drag) = self.drag_state.as_mut() {
+            let geometry = &windows[&window_id].geometry;
+            drag.resize = Some((geometry.width, geometry.height));
+        }
+        Ok(())
+    }
+    
     /// Update drag position
     pub fn update_drag(
         &mut self,
@@ -1185,34 +2289,68 @@ impl WindowManager {
         current_x: i16,
         current_y: i16,
     ) -> Result<()> {
-        // #region agent log
-        {
-            use std::fs::OpenOptions;
-            use std::io::Write;
-            let has_drag = self.drag_state.is_some();
-            let log_entry = serde_json::json!({
-                "sessionId": "debug-session",
-                "runId": "run1",
-                "hypothesisId": "C",
-                "location": "wm/mod.rs:1055",
-                "message": "update_drag called",
-                "data": {"current_x": current_x, "current_y": current_y, "has_drag_state": has_drag},
-                "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()
-            });
-            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("/home/bizkit/GitHub/area/.cursor/debug.log") {
-                let _ = writeln!(file, "{}", log_entry);
-            }
+        trace!("update_drag({}, {}) active={}", current_x, current_y, self.drag_state.is_some());
+        
+        // Note when the pointer starts pressing against an edge with a workspace behind it
+        let (root_width, _) = self.root_size();
+        let at_edge = if current_x <= 0 {
+            Some(false)
+        } else if current_x as u32 >= root_width.saturating_sub(1) {
+            Some(true)
+        } else {
+            None
         }
-        // #endregion
+        .filter(|&forward| self.edge_flip.is_some() && !self.pointer_confinement.is_active() && self.workspaces.adjacent(forward).is_some());
         
-        if let Some(ref drag) = self.drag_state {
+        // Dragged out of its tile, a window leaves its snap group
+        if let Some(window_id) = self.drag_state.as_ref().map(|drag| drag.window_id) {
+            self.leave_snap_group(conn, windows, window_id)?;
+        }
+        
+        if let Some(drag) = self.drag_state.as_mut() {
+            drag.last_x = current_x;
+            drag.last_y = current_y;
+            drag.edge = match (at_edge.filter(|_| drag.resize.is_none()), drag.edge) {
+                (Some(forward), Some((held, since))) if forward == held => Some((held, since)),
+                (Some(forward), _) => Some((forward, Instant::now())),
+                (None, _) => None,
+            };
+            
             let client = windows.get_mut(&drag.window_id)
                 .context("Window not found")?;
+            // Dragged out of its tile, it keeps the tile's size
+            client.tile_mode = crate::wm::client_flags::TilePosition::None;
             
             // Calculate new position
             let delta_x = current_x - drag.start_x;
             let delta_y = current_y - drag.start_y;
             
+            if let Some((start_width, start_height)) = drag.resize {
+                let (width, height) = client.constrain_size(
+                    (start_width as i32 + delta_x as i32).max(MIN_RESIZE_SIZE as i32) as u32,
+                    (start_height as i32 + delta_y as i32).max(MIN_RESIZE_SIZE as i32) as u32,
+                );
+                if (width, height) == (client.geometry.width, client.geometry.height) {
+                    return Ok(());
+                }
+                client.geometry.width = width;
+                client.geometry.height = height;
+                
+                trace!("Resizing dragged window {} to {}x{}", drag.window_id, width, height);
+                
+                if let Some(frame_state) = &client.frame {
+                    let frame = decorations::WindowFrame::from_state(client.window, frame_state);
+                    frame.resize(conn, width as u16, height as u16, &self.decoration)?;
+                } else {
+                    conn.configure_window(
+                        client.window,
+                        &ConfigureWindowAux::new().width(width).height(height),
+                    )?;
+                }
+                conn.flush()?;
+                return Ok(());
+            }
+            
             let new_x = drag.window_start_x + delta_x as i32;
             let new_y = drag.window_start_y + delta_y as i32;
             
@@ -1220,34 +2358,16 @@ impl WindowManager {
             client.geometry.x = new_x;
             client.geometry.y = new_y;
             
-            // #region agent log
-            {
-                use std::fs::OpenOptions;
-                use std::io::Write;
-                let log_entry = serde_json::json!({
-                    "sessionId": "debug-session",
-                    "runId": "run1",
-                    "hypothesisId": "C",
-                    "location": "wm/mod.rs:1075",
-                    "message": "Updating window position",
-                    "data": {"window_id": drag.window_id, "new_x": new_x, "new_y": new_y, "has_frame": client.frame.is_some()},
-                    "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()
-                });
-                if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("/home/bizkit/GitHub/area/.cursor/debug.log") {
-                    let _ = writeln!(file, "{}", log_entry);
-                }
-            }
-            // #endregion
+            trace!("Moving dragged window {} to ({}, {})", drag.window_id, new_x, new_y);
             
             // Move frame (if exists)
             if let Some(frame) = &client.frame {
-                const TITLEBAR_HEIGHT: u32 = 32;
-                // Move frame window
+                let frame_geometry = client.frame_metrics.frame_geometry(client.geometry);
                 conn.configure_window(
                     frame.frame,
                     &ConfigureWindowAux::new()
-                        .x(new_x)
-                        .y(new_y - TITLEBAR_HEIGHT as i32),
+                        .x(frame_geometry.x)
+                        .y(frame_geometry.y),
                 )?;
             } else {
                 // No frame, move client window directly
@@ -1267,10 +2387,11 @@ impl WindowManager {
     
     /// End drag
     pub fn end_drag(&mut self, conn: &RustConnection) -> Result<()> {
-        if self.drag_state.is_some() {
+        if self.drag_state.is_some() || self.split_drag.is_some() {
             conn.ungrab_pointer(x11rb::CURRENT_TIME)?;
             conn.flush()?;
             self.drag_state = None;
+            self.split_drag = None;
         }
         Ok(())
     }
@@ -1280,6 +2401,150 @@ impl WindowManager {
         self.drag_state.is_some()
     }
     
+    /// The window being resized interactively, if any
+    pub fn resizing_window(&self) -> Option<u32> {
+        self.drag_state.as_ref().filter(|drag| drag.resize.is_some()).map(|drag| drag.window_id)
+    }
+    
+    /// While a window is moved onto another monitor: the window, the
+    /// monitor it started on and the one under the pointer
+    pub fn drag_crossing(&self) -> Option<(u32, usize, usize)> {
+        let drag = self.drag_state.as_ref().filter(|drag| drag.resize.is_none())?;
+        let to = self.monitor_at(drag.last_x as i32, drag.last_y as i32)?;
+        (to != drag.monitor).then_some((drag.window_id, drag.monitor, to))
+    }
+    
+    /// Where a window dragged from monitor `from` lands on `to`, with the
+    /// geometry a maximized one restores to
+    ///
+    /// A maximized window fills the new work area. Sizes are multiplied by
+    /// `scale` (the ratio of the outputs' scales) so the window keeps its
+    /// physical size; an unmaximized window keeps the spot under the pointer
+    /// and is kept inside the work area.
+    pub fn drop_geometry(&self, client: &Client, from: usize, to: usize, scale: f64) -> (Geometry, Option<Geometry>) {
+        let (src, dst) = (self.work_area(from), self.work_area(to));
+        let scaled = |g: Geometry| {
+            let width = ((g.width as f64 * scale).round() as u32).clamp(MIN_RESIZE_SIZE, dst.width.max(MIN_RESIZE_SIZE));
+            let height = ((g.height as f64 * scale).round() as u32).clamp(MIN_RESIZE_SIZE, dst.height.max(MIN_RESIZE_SIZE));
+            let (width, height) = client.constrain_size(width, height);
+            Geometry { width, height, ..g }
+        };
+        if client.is_maximized() {
+            let restore = client.restore_geometry().map(|restore| migration::translate(scaled(restore), src, dst));
+            let area = client.frame_metrics.client_geometry(self.gaps.shrink(dst));
+            return (area, restore);
+        }
+        
+        let g = client.geometry;
+        let (pointer_x, pointer_y) = self.drag_state.as_ref().map_or((g.x, g.y), |drag| (drag.last_x as i32, drag.last_y as i32));
+        let Geometry { width, height, .. } = scaled(g);
+        // The grabbed point of the window stays under the pointer
+        let x = pointer_x - ((pointer_x - g.x) as f64 * width as f64 / g.width.max(1) as f64) as i32;
+        let y = pointer_y - ((pointer_y - g.y) as f64 * height as f64 / g.height.max(1) as f64) as i32;
+        let geometry = Geometry::new(x, y, width, height);
+        let frame = client.frame_metrics.frame_geometry(geometry);
+        let dx = frame.x.min(dst.x + dst.width as i32 - frame.width as i32).max(dst.x) - frame.x;
+        let dy = frame.y.min(dst.y + dst.height as i32 - frame.height as i32).max(dst.y) - frame.y;
+        (Geometry::new(x + dx, y + dy, width, height), None)
+    }
+    
+    /// Fit a window dropped on another monitor to it (see `drop_geometry`)
+    pub fn drop_on_monitor(&mut self, conn: &RustConnection, client: &mut Client, from: usize, to: usize, scale: f64) -> Result<()> {
+        let (geometry, restore) = self.drop_geometry(client, from, to, scale);
+        info!("Window {} dropped on monitor {} (from {})", client.window, to, from);
+        // A maximized window is maximized again on the monitor under the
+        // centre of its restore geometry
+        let geometry = restore.filter(|_| client.is_maximized()).unwrap_or(geometry);
+        self.place_client(conn, client, geometry, restore)
+    }
+    
+    /// When the window held against a screen edge should flip workspaces
+    pub fn edge_flip_deadline(&self) -> Option<Instant> {
+        let (_, since) = self.drag_state.as_ref()?.edge?;
+        Some(since + self.edge_flip?)
+    }
+    
+    /// Carry the dragged window to the workspace behind the edge it is held
+    /// against, warping the pointer to the opposite edge
+    ///
+    /// Returns the visibility changes of the switch (see `switch_workspace`).
+    pub fn flip_drag_edge(
+        &mut self,
+        conn: &RustConnection,
+        windows: &mut HashMap<u32, Client>,
+    ) -> Result<Vec<(u32, bool)>> {
+        let Some(drag) = self.drag_state.as_mut() else {
+            return Ok(Vec::new());
+        };
+        let Some((forward, _)) = drag.edge.take() else {
+            return Ok(Vec::new());
+        };
+        let (window_id, y) = (drag.window_id, drag.last_y);
+        let Some(workspace) = self.workspaces.adjacent(forward) else {
+            return Ok(Vec::new());
+        };
+        
+        info!("Edge flip: carrying window {} to workspace {}", window_id, workspace);
+        let changed = self.switch_workspace(conn, windows, workspace, Some(window_id))?;
+        
+        let (root_width, _) = self.root_size();
+        let x = if forward { EDGE_FLIP_WARP } else { root_width as i16 - 1 - EDGE_FLIP_WARP };
+        conn.warp_pointer(x11rb::NONE, self.root, 0, 0, 0, 0, x, y)?;
+        self.update_drag(conn, windows, x, y)?;
+        Ok(changed)
+    }
+    
+    /// Switch to another workspace, optionally taking a window along
+    ///
+    /// Returns the windows shown or hidden (client window, now visible).
+    pub fn switch_workspace(
+        &mut self,
+        conn: &RustConnection,
+        windows: &mut HashMap<u32, Client>,
+        workspace: u32,
+        carry: Option<u32>,
+    ) -> Result<Vec<(u32, bool)>> {
+        if let Some(client) = carry.and_then(|id| windows.get_mut(&id))
+            && !client.is_sticky()
+            && workspace < self.workspaces.workspace_count
+        {
+            // Moved before the switch so it is never hidden
+            client.win_workspace = workspace;
+            conn.change_property32(
+                PropMode::REPLACE,
+                client.window,
+                self.atoms.net_wm_desktop,
+                AtomEnum::CARDINAL,
+                &[workspace],
+            )?;
+        }
+        let previous = self.workspaces.current_workspace;
+        let changed = self.workspaces.switch_workspace(conn, &self.atoms, self.root, workspace, windows)?;
+        if self.workspaces.current_workspace != previous {
+            // A carried window keeps focus, otherwise the workspace's policy decides
+            let target = match carry.filter(|id| windows.contains_key(id)) {
+                Some(id) => Some(id),
+                None => {
+                    let pointer = match self.workspaces.focus_policy(workspace) {
+                        workspace::WorkspaceFocus::UnderPointer => {
+                            let reply = conn.query_pointer(self.root)?.reply()?;
+                            Some((i32::from(reply.root_x), i32::from(reply.root_y)))
+                        }
+                        _ => None,
+                    };
+                    self.workspaces.focus_target(windows, self.stacking.order(), pointer)
+                }
+            };
+            debug!(target: "area::wm::focus", "Workspace {} focuses {:?}", workspace, target);
+            match target {
+                Some(id) => self.set_focus(conn, windows, id)?,
+                None => self.clear_focus(conn, windows)?,
+            }
+        }
+        conn.flush()?;
+        Ok(changed)
+    }
+    
     /// Check if a window ID belongs to a button
     pub fn find_window_from_button(
         &self,
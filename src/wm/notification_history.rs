@@ -0,0 +1,78 @@
+//! Notification History Module
+//!
+//! Every desktop notification received, newest last, and the Do Not Disturb
+//! switch. Kept in `$XDG_STATE_HOME/area/notifications.json` so the shell's
+//! notification center survives a restart, and so does Do Not Disturb.
+
+use anyhow::{Context, Result};
+use area_ipc::Notification;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+/// Recorded notifications and Do Not Disturb
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationHistory {
+    pub do_not_disturb: bool,
+    /// Oldest first
+    entries: Vec<Notification>,
+}
+
+impl NotificationHistory {
+    fn path() -> Option<PathBuf> {
+        Some(dirs::state_dir().or_else(dirs::data_local_dir)?.join("area/notifications.json"))
+    }
+
+    /// Read the saved history (empty if there is none)
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else { return Self::default() };
+        let Ok(data) = std::fs::read_to_string(&path) else { return Self::default() };
+        serde_json::from_str(&data).unwrap_or_else(|e| {
+            warn!("Ignoring invalid notification history {:?}: {}", path, e);
+            Self::default()
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = Self::path().context("no state directory")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string(self)?)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        debug!("Saved {} notifications", self.entries.len());
+        Ok(())
+    }
+
+    /// Id for the first notification of this session, after the recorded ones
+    pub fn next_id(&self) -> u32 {
+        self.entries.iter().map(|n| n.id).max().unwrap_or(0).wrapping_add(1).max(1)
+    }
+
+    /// Record a notification (replacing one with the same id), keeping the
+    /// newest `limit`, and save
+    pub fn record(&mut self, notification: Notification, limit: usize) -> Result<()> {
+        self.entries.retain(|n| n.id != notification.id);
+        self.entries.push(notification);
+        let excess = self.entries.len().saturating_sub(limit);
+        self.entries.drain(..excess);
+        self.save()
+    }
+
+    /// Turn Do Not Disturb on or off and save; false if it already was
+    pub fn set_do_not_disturb(&mut self, enabled: bool) -> Result<bool> {
+        if self.do_not_disturb == enabled {
+            return Ok(false);
+        }
+        self.do_not_disturb = enabled;
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Newest first, at most `limit`
+    pub fn newest(&self, limit: Option<u32>) -> Vec<Notification> {
+        let limit = limit.map_or(usize::MAX, |l| l as usize);
+        self.entries.iter().rev().take(limit).cloned().collect()
+    }
+}
@@ -0,0 +1,167 @@
+//! Pinned Applications Module
+//!
+//! Applications the user pinned to the dock, by desktop-file ID (e.g.
+//! "org.gnome.Terminal.desktop"), in dock order (`PinApp`, `UnpinApp`).
+//! Kept in `$XDG_STATE_HOME/area/pinned.json` so every shell component sees
+//! the same list. Windows belong to a pinned application when their
+//! WM_CLASS matches the desktop file's StartupWMClass or, without one, the
+//! ID itself (case-insensitively, without ".desktop").
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+use crate::wm::client::ClassHint;
+
+/// The parts of a desktop file the dock needs
+#[derive(Debug, Clone, Default)]
+pub struct DesktopEntry {
+    pub name: Option<String>,
+    pub icon: Option<String>,
+    pub exec: Option<String>,
+    pub startup_wm_class: Option<String>,
+}
+
+impl DesktopEntry {
+    /// Find a desktop file by ID in the XDG data directories
+    pub fn find(id: &str) -> Option<Self> {
+        let mut dirs: Vec<PathBuf> = dirs::data_dir().into_iter().collect();
+        let data_dirs = std::env::var("XDG_DATA_DIRS").unwrap_or_else(|_| "/usr/local/share:/usr/share".to_string());
+        dirs.extend(data_dirs.split(':').filter(|dir| !dir.is_empty()).map(PathBuf::from));
+        // "foo-bar.desktop" may also be applications/foo/bar.desktop
+        let names: Vec<String> = std::iter::once(id.to_string()).chain(id.contains('-').then(|| id.replacen('-', "/", 1))).collect();
+        dirs.iter()
+            .flat_map(|dir| names.iter().map(move |name| dir.join("applications").join(name)))
+            .find_map(|path| std::fs::read_to_string(path).ok())
+            .map(|text| Self::parse(&text))
+    }
+
+    /// Read the [Desktop Entry] group (unlocalized keys only)
+    fn parse(text: &str) -> Self {
+        let mut entry = Self::default();
+        let mut in_group = false;
+        for line in text.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_group = line == "[Desktop Entry]";
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=').filter(|_| in_group) else { continue };
+            let value = Some(value.trim().to_string());
+            match key.trim() {
+                "Name" => entry.name = value,
+                "Icon" => entry.icon = value,
+                "Exec" => entry.exec = value,
+                "StartupWMClass" => entry.startup_wm_class = value,
+                _ => {}
+            }
+        }
+        entry
+    }
+
+    /// Exec line with its field codes (%f, %U, ...) removed, for `sh -c`
+    pub fn command(&self) -> Option<String> {
+        let exec = self.exec.as_deref()?;
+        let words: Vec<String> = exec
+            .split_whitespace()
+            .filter(|word| !(word.len() == 2 && word.starts_with('%') && word != &"%%"))
+            .map(|word| word.replace("%%", "%"))
+            .collect();
+        (!words.is_empty()).then(|| words.join(" "))
+    }
+}
+
+/// Pinned applications, in dock order
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PinnedApps {
+    /// Desktop-file IDs
+    ids: Vec<String>,
+    /// Desktop files of the pinned applications that have one
+    #[serde(skip)]
+    entries: HashMap<String, DesktopEntry>,
+}
+
+impl PinnedApps {
+    fn path() -> Option<PathBuf> {
+        Some(dirs::state_dir().or_else(dirs::data_local_dir)?.join("area/pinned.json"))
+    }
+
+    /// Read the saved list (empty if there is none)
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else { return Self::default() };
+        let Ok(data) = std::fs::read_to_string(&path) else { return Self::default() };
+        let mut pinned: Self = serde_json::from_str(&data).unwrap_or_else(|e| {
+            warn!("Ignoring invalid pinned applications {:?}: {}", path, e);
+            Self::default()
+        });
+        for id in pinned.ids.clone() {
+            pinned.read_entry(&id);
+        }
+        pinned
+    }
+
+    fn read_entry(&mut self, id: &str) {
+        match DesktopEntry::find(id) {
+            Some(entry) => {
+                self.entries.insert(id.to_string(), entry);
+            }
+            None => debug!("No desktop file {}", id),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = Self::path().context("no state directory")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string(self)?)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        debug!("Saved {} pinned applications", self.ids.len());
+        Ok(())
+    }
+
+    pub fn ids(&self) -> &[String] {
+        &self.ids
+    }
+
+    /// Pin an application at `position` (last if None); a pinned one moves there
+    pub fn pin(&mut self, id: String, position: Option<u32>) -> Result<()> {
+        self.ids.retain(|pinned| *pinned != id);
+        let index = position.map_or(self.ids.len(), |p| (p as usize).min(self.ids.len()));
+        self.read_entry(&id);
+        self.ids.insert(index, id);
+        self.save()
+    }
+
+    /// Unpin an application; false if it was not pinned
+    pub fn unpin(&mut self, id: &str) -> Result<bool> {
+        let count = self.ids.len();
+        self.ids.retain(|pinned| pinned != id);
+        if self.ids.len() == count {
+            return Ok(false);
+        }
+        self.entries.remove(id);
+        self.save()?;
+        Ok(true)
+    }
+
+    /// The desktop file of a pinned application, if it was found
+    pub fn entry(&self, id: &str) -> Option<&DesktopEntry> {
+        self.entries.get(id)
+    }
+
+    /// Whether a window with this WM_CLASS belongs to the application `id`
+    pub fn matches(&self, id: &str, class: &ClassHint) -> bool {
+        let wm_class = self
+            .entry(id)
+            .and_then(|entry| entry.startup_wm_class.as_deref())
+            .unwrap_or_else(|| id.strip_suffix(".desktop").unwrap_or(id));
+        wm_class.eq_ignore_ascii_case(&class.res_class) || wm_class.eq_ignore_ascii_case(&class.res_name)
+    }
+
+    /// The pinned application a window belongs to
+    pub fn app_of(&self, class: &ClassHint) -> Option<&str> {
+        self.ids.iter().map(String::as_str).find(|id| self.matches(id, class))
+    }
+}
@@ -4,6 +4,8 @@
 //! This matches xfwm4's window placement system.
 
 use anyhow::Result;
+use std::cmp::Reverse;
+use std::collections::HashMap;
 use tracing::{debug, info};
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::*;
@@ -11,15 +13,21 @@ use x11rb::rust_connection::RustConnection;
 
 use crate::shared::Geometry;
 use crate::wm::client::Client;
+use crate::wm::client_flags::TilePosition;
 use crate::wm::screen::ScreenInfo;
 
 /// Placement policy
-#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PlacementPolicy {
     /// Smart placement (avoid overlapping)
+    #[default]
     Smart,
     /// Center placement
     Center,
+    /// Each new window a titlebar height down and right of the previous
+    /// one, per workspace and monitor
+    Cascade,
     /// Mouse placement (at cursor)
     Mouse,
     /// Random placement
@@ -28,6 +36,65 @@ pub enum PlacementPolicy {
     RespectInitialPosition,
 }
 
+/// Titlebar pixels kept on a monitor by default (see `PlacementManager::constrain`)
+pub const DEFAULT_MIN_VISIBLE: u32 = 64;
+
+/// Default for `PlacementManager::smart_max_size` (percent)
+pub const DEFAULT_SMART_MAX_SIZE: u32 = 70;
+
+/// Largest gap reachable with `Gaps::adjusted`
+const MAX_GAP: u32 = 256;
+
+/// Space left around maximized and tiled windows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Gaps {
+    /// Between tiled windows
+    pub inner: u32,
+    /// Between windows and the work area edges
+    pub outer: u32,
+}
+
+impl Gaps {
+    /// Both gaps grown (or shrunk) by `delta` pixels
+    pub fn adjusted(&self, delta: i32) -> Self {
+        let adjust = |gap: u32| gap.saturating_add_signed(delta).min(MAX_GAP);
+        Self { inner: adjust(self.inner), outer: adjust(self.outer) }
+    }
+    
+    /// A work area without its outer margin (never less than half of it)
+    pub fn shrink(&self, area: Geometry) -> Geometry {
+        let gap = self.outer.min(area.width / 4).min(area.height / 4);
+        Geometry::new(area.x + gap as i32, area.y + gap as i32, area.width - gap * 2, area.height - gap * 2)
+    }
+    
+    /// Frame rectangle of a tile (half or quarter) of a work area, the left
+    /// column and top row taking `split` of the width and height
+    pub fn tile(&self, area: Geometry, position: TilePosition, split: f32) -> Geometry {
+        use TilePosition::*;
+        let area = self.shrink(area);
+        let (column, columns) = match position {
+            Left | UpLeft | DownLeft => (0, 2),
+            Right | UpRight | DownRight => (1, 2),
+            _ => (0, 1),
+        };
+        let (row, rows) = match position {
+            Up | UpLeft | UpRight => (0, 2),
+            Down | DownLeft | DownRight => (1, 2),
+            _ => (0, 1),
+        };
+        let inner_x = self.inner.min(area.width / 4) * (columns - 1);
+        let inner_y = self.inner.min(area.height / 4) * (rows - 1);
+        // Offset and length of cell `index` of `count` (1 or 2) along an axis
+        let span = |length: u32, inner: u32, index: u32, count: u32| {
+            let first = if count == 1 { length } else { ((length - inner) as f32 * split) as u32 };
+            if index == 0 { (0, first) } else { (first + inner, length - inner - first) }
+        };
+        let (x, width) = span(area.width, inner_x, column, columns);
+        let (y, height) = span(area.height, inner_y, row, rows);
+        Geometry::new(area.x + x as i32, area.y + y as i32, width, height)
+    }
+}
+
 /// Placement manager
 pub struct PlacementManager {
     /// Current placement policy
@@ -35,6 +102,16 @@ pub struct PlacementManager {
     
     /// Smart placement grid
     pub smart_grid: Vec<(i32, i32)>,
+    
+    /// Titlebar width that must stay inside a work area
+    pub min_visible: u32,
+    
+    /// Largest share of the work area (percent) a window placed by the
+    /// smart policy may cover; bigger ones are centered
+    pub smart_max_size: u32,
+    
+    /// Next cascade position by workspace and monitor
+    cascade: HashMap<(u32, usize), (i32, i32)>,
 }
 
 impl PlacementManager {
@@ -43,9 +120,33 @@ impl PlacementManager {
         Self {
             policy,
             smart_grid: Vec::new(),
+            min_visible: DEFAULT_MIN_VISIBLE,
+            smart_max_size: DEFAULT_SMART_MAX_SIZE,
+            cascade: HashMap::new(),
         }
     }
     
+    /// Next cascade position for a frame of `size` on a work area
+    ///
+    /// The first window goes to the area's top-left corner, each further one
+    /// `step` pixels down and right of the previous one. The cascade starts
+    /// over at the corner when the frame would cross the bottom or right
+    /// edge, or on `restart` (nothing left on that workspace and monitor).
+    pub fn place_cascade(&mut self, workspace: u32, monitor: usize, area: Geometry, size: (u32, u32), step: u32, restart: bool) -> (i32, i32) {
+        let origin = (area.x, area.y);
+        let (x, y) = match self.cascade.get(&(workspace, monitor)) {
+            Some(&next) if !restart => next,
+            _ => origin,
+        };
+        let fits = x >= area.x
+            && y >= area.y
+            && x + size.0 as i32 <= area.x + area.width as i32
+            && y + size.1 as i32 <= area.y + area.height as i32;
+        let (x, y) = if fits { (x, y) } else { origin };
+        self.cascade.insert((workspace, monitor), (x + step as i32, y + step as i32));
+        (x, y)
+    }
+    
     /// Place a window
     pub fn place_window(
         &mut self,
@@ -61,11 +162,16 @@ impl PlacementManager {
         
         match self.policy {
             PlacementPolicy::Smart => {
-                geometry = self.place_smart(screen_info, &geometry, existing_clients)?;
+                let others: Vec<Geometry> = existing_clients.values().filter(|c| c.mapped()).map(|c| c.geometry).collect();
+                (geometry.x, geometry.y) = self.place_smart(*work_area, (geometry.width, geometry.height), &others);
             }
             PlacementPolicy::Center => {
                 geometry = self.place_center(screen_info, &geometry)?;
             }
+            PlacementPolicy::Cascade => {
+                let size = (geometry.width, geometry.height);
+                (geometry.x, geometry.y) = self.place_cascade(0, 0, *work_area, size, client.frame_metrics.titlebar, false);
+            }
             PlacementPolicy::Mouse => {
                 geometry = self.place_mouse(screen_info, &geometry, mouse_x, mouse_y)?;
             }
@@ -81,68 +187,40 @@ impl PlacementManager {
         }
         
         // Constrain to work area
-        geometry.x = geometry.x.max(work_area.x);
-        geometry.y = geometry.y.max(work_area.y);
         geometry.width = geometry.width.min(work_area.width);
         geometry.height = geometry.height.min(work_area.height);
+        let geometry = self.constrain(geometry, 0, std::slice::from_ref(work_area));
         
         client.geometry = geometry;
         
         Ok(geometry)
     }
     
-    /// Smart placement (avoid overlapping windows)
-    fn place_smart(
-        &self,
-        screen_info: &ScreenInfo,
-        geometry: &Geometry,
-        existing_clients: &std::collections::HashMap<u32, Client>,
-    ) -> Result<Geometry> {
-        let work_area = &screen_info.work_area;
-        let mut best_x = work_area.x;
-        let mut best_y = work_area.y;
-        let mut best_score = i32::MAX;
-        
-        // Try positions in a grid
-        let step_x = (geometry.width / 4).max(10) as i32;
-        let step_y = (geometry.height / 4).max(10) as i32;
-        
-        for y in (work_area.y..work_area.y + work_area.height as i32).step_by(step_y as usize) {
-            for x in (work_area.x..work_area.x + work_area.width as i32).step_by(step_x as usize) {
-                let test_geom = Geometry {
-                    x,
-                    y,
-                    width: geometry.width,
-                    height: geometry.height,
-                };
-                
-                // Check for overlaps
-                let mut overlaps = false;
-                for client in existing_clients.values() {
-                    if client.mapped() && self.geometries_overlap(&test_geom, &client.geometry) {
-                        overlaps = true;
-                        break;
-                    }
-                }
-                
-                if !overlaps {
-                    // Score based on distance from top-left (prefer top-left positions)
-                    let score = x + y;
-                    if score < best_score {
-                        best_score = score;
-                        best_x = x;
-                        best_y = y;
-                    }
-                }
-            }
+    /// Smart placement: the position of a frame of `size` on a work area
+    /// overlapping `others` the least
+    ///
+    /// Candidates are the area's edges and the edges of the other windows
+    /// (next to them on either side); of equally good ones the topmost, then
+    /// leftmost wins.
+    pub fn place_smart(&self, area: Geometry, size: (u32, u32), others: &[Geometry]) -> (i32, i32) {
+        let (width, height) = (size.0 as i32, size.1 as i32);
+        let (right, bottom) = (area.x + area.width as i32 - width, area.y + area.height as i32 - height);
+        let mut xs = vec![area.x, right];
+        let mut ys = vec![area.y, bottom];
+        for other in others {
+            xs.extend([other.x + other.width as i32, other.x - width]);
+            ys.extend([other.y + other.height as i32, other.y - height]);
         }
-        
-        Ok(Geometry {
-            x: best_x,
-            y: best_y,
-            width: geometry.width,
-            height: geometry.height,
-        })
+        // Too big for the area: its top-left corner
+        xs.retain(|&x| x >= area.x && x <= right.max(area.x));
+        ys.retain(|&y| y >= area.y && y <= bottom.max(area.y));
+        ys.iter()
+            .flat_map(|&y| xs.iter().map(move |&x| (x, y)))
+            .min_by_key(|&(x, y)| {
+                let frame = Geometry::new(x, y, size.0, size.1);
+                (others.iter().map(|other| overlap_area(&frame, other)).sum::<u64>(), y, x)
+            })
+            .unwrap_or((area.x, area.y))
     }
     
     /// Center placement
@@ -214,15 +292,53 @@ impl PlacementManager {
         })
     }
     
-    /// Check if two geometries overlap
-    fn geometries_overlap(&self, a: &Geometry, b: &Geometry) -> bool {
-        !(a.x + a.width as i32 <= b.x ||
-          b.x + b.width as i32 <= a.x ||
-          a.y + a.height as i32 <= b.y ||
-          b.y + b.height as i32 <= a.y)
+    /// Clamp a window position so its titlebar stays reachable
+    ///
+    /// `frame` is the outer rectangle with the titlebar on top
+    /// (`titlebar_height` 0 for undecorated windows). Afterwards the titlebar
+    /// lies vertically inside a work area, with at least `min_visible` pixels
+    /// of its width inside horizontally. The work area it overlaps most is
+    /// used, otherwise the nearest one; the size is left alone.
+    pub fn constrain(&self, frame: Geometry, titlebar_height: u32, work_areas: &[Geometry]) -> Geometry {
+        let handle = titlebar_height.clamp(1, frame.height.max(1));
+        let strip = Geometry { height: handle, ..frame };
+        let Some(area) = work_areas
+            .iter()
+            .max_by_key(|area| (overlap_area(&strip, area), Reverse(center_distance(&strip, area))))
+        else {
+            return frame;
+        };
+        
+        let visible = self.min_visible.min(frame.width).min(area.width) as i32;
+        let min_x = area.x + visible - frame.width as i32;
+        let max_x = area.x + area.width as i32 - visible;
+        let max_y = (area.y + area.height as i32 - handle as i32).max(area.y);
+        let constrained = Geometry {
+            x: frame.x.clamp(min_x, max_x),
+            y: frame.y.clamp(area.y, max_y),
+            ..frame
+        };
+        if constrained != frame {
+            debug!("Constrained window from ({}, {}) to ({}, {})", frame.x, frame.y, constrained.x, constrained.y);
+        }
+        constrained
     }
 }
 
+/// Area of the intersection of two rectangles
+fn overlap_area(a: &Geometry, b: &Geometry) -> u64 {
+    let width = (a.x + a.width as i32).min(b.x + b.width as i32) - a.x.max(b.x);
+    let height = (a.y + a.height as i32).min(b.y + b.height as i32) - a.y.max(b.y);
+    width.max(0) as u64 * height.max(0) as u64
+}
+
+/// Squared distance between the centers of two rectangles
+fn center_distance(a: &Geometry, b: &Geometry) -> i64 {
+    let dx = (a.x as i64 * 2 + a.width as i64) - (b.x as i64 * 2 + b.width as i64);
+    let dy = (a.y as i64 * 2 + a.height as i64) - (b.y as i64 * 2 + b.height as i64);
+    dx * dx + dy * dy
+}
+
 impl Default for PlacementManager {
     fn default() -> Self {
         Self::new(PlacementPolicy::Smart)
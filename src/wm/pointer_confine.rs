@@ -0,0 +1,179 @@
+//! Pointer confinement for fullscreen games
+//!
+//! Games that turn the mouse into a camera keep warping the pointer back to
+//! the middle of their window, but a fast flick still escapes to the next
+//! monitor between two warps, where a click unfocuses the game. While a
+//! fullscreen window that asks for it has focus, the WM puts XFixes pointer
+//! barriers around its monitor so the pointer cannot leave, and the panel
+//! edge triggers and the edge workspace flip stay quiet.
+//!
+//! A window asks for confinement with `_AREA_CONFINE_POINTER` (CARDINAL, 1;
+//! 0 opts out of the rest), with `_NET_WM_BYPASS_COMPOSITOR` = 1 (which
+//! games set when they go fullscreen), or by its WM_CLASS being listed in
+//! `confine_pointer_classes`. X doesn't tell the WM about pointer grabs of
+//! other clients that stay inside the grabbing window, so hints are all it
+//! goes by. The escape binding (Super+Shift+Escape) lifts the confinement
+//! until the window loses focus or leaves fullscreen.
+
+use anyhow::Result;
+use tracing::{debug, info, warn};
+use x11rb::connection::Connection;
+use x11rb::protocol::xfixes::{BarrierDirections, ConnectionExt as _};
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _, Window};
+
+use crate::wm::client::ClassHint;
+use crate::wm::screen::Monitor;
+
+/// Monitor rectangle the pointer is held in: x, y, width, height
+type Rect = (i32, i32, u32, u32);
+
+/// The confined window and its barriers
+#[derive(Debug, Default)]
+pub struct PointerConfinement {
+    /// Honor the window hints
+    enabled: bool,
+    /// Lowercase WM_CLASS names (instance or class) confined without hints
+    classes: Vec<String>,
+    /// Focused fullscreen window last considered and whether it asks for confinement
+    candidate: Option<(Window, bool)>,
+    /// Window whose confinement the user lifted
+    released: Option<Window>,
+    /// Confined window and its monitor
+    active: Option<(Window, Rect)>,
+    barriers: Vec<u32>,
+    /// XFixes 5 is present
+    available: bool,
+}
+
+impl PointerConfinement {
+    /// Check for pointer barrier support
+    pub fn init<C: Connection>(&mut self, conn: &C) -> Result<()> {
+        let fixes = conn.xfixes_query_version(5, 0)?.reply()?;
+        if fixes.major_version < 5 {
+            warn!("Pointer barriers unavailable (XFixes {}), pointer confinement disabled", fixes.major_version);
+            return Ok(());
+        }
+        self.available = true;
+        Ok(())
+    }
+
+    /// Replace the settings (after a config reload); call `update` afterwards
+    pub fn set_config(&mut self, enabled: bool, classes: &[String]) {
+        self.enabled = enabled;
+        self.classes = classes.iter().map(|name| name.to_lowercase()).collect();
+        self.candidate = None;
+    }
+
+    /// Whether the pointer is held on a monitor (edge actions are suppressed)
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// A property of `window` changed; re-read its hints on the next `update`
+    pub fn property_changed(&mut self, window: Window) {
+        if self.candidate.is_some_and(|(candidate, _)| candidate == window) {
+            self.candidate = None;
+        }
+    }
+
+    /// Confine the pointer to the monitor of `target` (the focused
+    /// fullscreen window) if it asks for it, or lift the confinement
+    pub fn update<C: Connection>(
+        &mut self,
+        conn: &C,
+        root: Window,
+        target: Option<(Window, Option<&ClassHint>, &Monitor)>,
+    ) -> Result<()> {
+        let wanted = match target {
+            Some((window, class_hint, monitor)) if self.available => {
+                let wants = match self.candidate {
+                    Some((candidate, wants)) if candidate == window => wants,
+                    _ => {
+                        if self.released != Some(window) {
+                            self.released = None;
+                        }
+                        let wants = self.wants(conn, window, class_hint)?;
+                        self.candidate = Some((window, wants));
+                        wants
+                    }
+                };
+                (wants && self.released != Some(window)).then_some((window, (monitor.x, monitor.y, monitor.width, monitor.height)))
+            }
+            _ => {
+                self.candidate = None;
+                self.released = None;
+                None
+            }
+        };
+        if wanted == self.active {
+            return Ok(());
+        }
+        self.clear(conn)?;
+        if let Some((window, rect)) = wanted {
+            self.confine(conn, root, rect)?;
+            info!("Confining the pointer to {:?} for window {}", rect, window);
+            self.active = wanted;
+        }
+        Ok(())
+    }
+
+    /// Lift the confinement of the confined window, or restore it for the
+    /// window it was lifted for; call `update` afterwards
+    pub fn toggle(&mut self) {
+        if let Some((window, _)) = self.active {
+            info!("Pointer confinement of window {} lifted", window);
+            self.released = Some(window);
+        } else if self.released.take().is_some() {
+            info!("Pointer confinement restored");
+        }
+    }
+
+    /// Whether `window` asks for confinement
+    fn wants<C: Connection>(&self, conn: &C, window: Window, class_hint: Option<&ClassHint>) -> Result<bool> {
+        if class_hint.is_some_and(|hint| {
+            self.classes.contains(&hint.res_name.to_lowercase()) || self.classes.contains(&hint.res_class.to_lowercase())
+        }) {
+            return Ok(true);
+        }
+        if !self.enabled {
+            return Ok(false);
+        }
+        let cardinal = |name: &str| -> Result<Option<u32>> {
+            let atom = crate::wm::atom_cache::intern(conn, name)?;
+            let reply = conn.get_property(false, window, atom, AtomEnum::CARDINAL, 0, 1)?.reply()?;
+            Ok(reply.value32().and_then(|mut values| values.next()))
+        };
+        Ok(match cardinal("_AREA_CONFINE_POINTER")? {
+            Some(value) => value == 1,
+            None => cardinal("_NET_WM_BYPASS_COMPOSITOR")? == Some(1),
+        })
+    }
+
+    /// Put barriers along the four edges of a monitor, crossable only inwards
+    fn confine<C: Connection>(&mut self, conn: &C, root: Window, (x, y, width, height): Rect) -> Result<()> {
+        let (left, top) = (x.max(0) as u16, y.max(0) as u16);
+        let (right, bottom) = (left.saturating_add(width as u16), top.saturating_add(height as u16));
+        for (x1, y1, x2, y2, directions) in [
+            (left, top, left, bottom, BarrierDirections::POSITIVE_X),
+            (right, top, right, bottom, BarrierDirections::NEGATIVE_X),
+            (left, top, right, top, BarrierDirections::POSITIVE_Y),
+            (left, bottom, right, bottom, BarrierDirections::NEGATIVE_Y),
+        ] {
+            let barrier = conn.generate_id()?;
+            conn.xfixes_create_pointer_barrier(barrier, root, x1, y1, x2, y2, directions, &[])?;
+            self.barriers.push(barrier);
+        }
+        Ok(())
+    }
+
+    /// Remove the barriers
+    fn clear<C: Connection>(&mut self, conn: &C) -> Result<()> {
+        if let Some((window, _)) = self.active.take() {
+            debug!("Releasing the pointer of window {}", window);
+        }
+        for barrier in self.barriers.drain(..) {
+            conn.xfixes_delete_pointer_barrier(barrier)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,311 @@
+//! Output Scaling Module (experimental)
+//!
+//! Fractional scaling for X apps that only render at 1x: a scaled output gets
+//! a smaller logical area in the root window (3840x2160 at 150% becomes
+//! 2560x1440), and its CRTC upscales that area to the panel with a RandR
+//! transform, like `xrandr --scale 0.667x0.667`. Windows, the compositor and
+//! pointer events all stay in logical root coordinates, so neither the WM nor
+//! the compositor has to translate anything; the server scales the final
+//! image on scanout.
+//!
+//! Outputs in a single row (all at y = 0) are repacked left to right so the
+//! shrunken output doesn't leave a gap; other layouts keep their positions.
+//!
+//! The CRTC configurations, transforms and screen size from before the first
+//! change are saved, and restored when the scales are removed from the config
+//! or area exits, so the server isn't left scaled without a window manager.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+use x11rb::connection::Connection;
+use x11rb::protocol::randr::{self, ConnectionExt as _};
+use x11rb::protocol::render::{Fixed, Transform};
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+
+/// A CRTC configuration to set, with its transform
+struct CrtcConfig {
+    crtc: randr::Crtc,
+    x: i16,
+    y: i16,
+    /// Size in the root window (after the transform)
+    width: u16,
+    height: u16,
+    mode: randr::Mode,
+    rotation: randr::Rotation,
+    outputs: Vec<randr::Output>,
+    transform: Transform,
+    filter: Vec<u8>,
+    params: Vec<Fixed>,
+}
+
+/// The outputs as they were before the first scale was applied
+struct SavedLayout {
+    width: u16,
+    height: u16,
+    crtcs: Vec<CrtcConfig>,
+}
+
+/// Applies the configured output scales, and puts the outputs back the way
+/// they were once the scales are removed from the config or area exits
+pub struct OutputScaling {
+    conn: Arc<RustConnection>,
+    root: Window,
+    /// Layout from before area changed anything (none until it does)
+    original: Option<SavedLayout>,
+}
+
+/// 16.16 fixed point
+fn fixed(value: f64) -> Fixed {
+    (value * 65536.0).round() as Fixed
+}
+
+fn scale_transform(scale: f64) -> Transform {
+    let inverse = fixed(1.0 / scale);
+    Transform {
+        matrix11: inverse,
+        matrix12: 0,
+        matrix13: 0,
+        matrix21: 0,
+        matrix22: inverse,
+        matrix23: 0,
+        matrix31: 0,
+        matrix32: 0,
+        matrix33: fixed(1.0),
+    }
+}
+
+/// `Transform` doesn't implement `PartialEq`
+fn same_transform(a: &Transform, b: &Transform) -> bool {
+    let matrix = |t: &Transform| {
+        [t.matrix11, t.matrix12, t.matrix13, t.matrix21, t.matrix22, t.matrix23, t.matrix31, t.matrix32, t.matrix33]
+    };
+    matrix(a) == matrix(b)
+}
+
+impl OutputScaling {
+    pub fn new(conn: Arc<RustConnection>, root: Window) -> Self {
+        Self { conn, root, original: None }
+    }
+
+    /// Apply the configured per-output scales (output name -> factor, e.g. "eDP-1" -> 1.5)
+    ///
+    /// Outputs not listed are reset to 1.0; with no scales at all, the
+    /// layout from before the first change is restored (scales set by other
+    /// tools, like `xrandr --scale`, are left alone). Does nothing when the
+    /// current layout already matches, so it's safe to call on every output
+    /// change.
+    pub fn apply(&mut self, scales: &HashMap<String, f64>) -> Result<()> {
+        if scales.is_empty() {
+            return self.restore();
+        }
+        let conn = &*self.conn;
+        let resources = conn.randr_get_screen_resources_current(self.root)?.reply()?;
+        let modes: HashMap<u32, (u16, u16)> = resources.modes.iter().map(|m| (m.id, (m.width, m.height))).collect();
+
+        let mut layouts = Vec::new();
+        let mut changed = false;
+        for &crtc in &resources.crtcs {
+            let info = conn.randr_get_crtc_info(crtc, resources.config_timestamp)?.reply()?;
+            let (Some(&output), Some(&(mode_width, mode_height))) = (info.outputs.first(), modes.get(&info.mode)) else {
+                continue; // Disabled CRTC
+            };
+            let name = conn.randr_get_output_info(output, resources.config_timestamp)?.reply()?.name;
+            let name = String::from_utf8_lossy(&name).into_owned();
+            let scale = match scales.get(&name) {
+                Some(&scale) if (0.5..=4.0).contains(&scale) => scale,
+                Some(&scale) => {
+                    warn!("Ignoring scale {} for output {} (must be between 0.5 and 4)", scale, name);
+                    1.0
+                }
+                None => 1.0,
+            };
+
+            let current = conn.randr_get_crtc_transform(crtc)?.reply()?.current_transform;
+            if !same_transform(&current, &scale_transform(scale)) {
+                changed = true;
+            }
+
+            let rotated = info.rotation.intersects(randr::Rotation::ROTATE90 | randr::Rotation::ROTATE270);
+            let (mode_width, mode_height) = if rotated { (mode_height, mode_width) } else { (mode_width, mode_height) };
+            let width = (mode_width as f64 / scale).round() as u16;
+            let height = (mode_height as f64 / scale).round() as u16;
+            debug!("Output {}: {}x{} at scale {} -> {}x{}", name, mode_width, mode_height, scale, width, height);
+            let filter: &[u8] = if scale == 1.0 { b"nearest" } else { b"bilinear" };
+            layouts.push(CrtcConfig {
+                crtc,
+                x: info.x,
+                y: info.y,
+                width,
+                height,
+                mode: info.mode,
+                rotation: info.rotation,
+                outputs: info.outputs,
+                transform: scale_transform(scale),
+                filter: filter.to_vec(),
+                params: Vec::new(),
+            });
+        }
+        if !changed || layouts.is_empty() {
+            return Ok(());
+        }
+
+        // Repack a single row of outputs so scaled ones don't leave gaps
+        if layouts.iter().all(|l| l.y == 0) {
+            layouts.sort_by_key(|l| l.x);
+            let mut x = 0i16;
+            for layout in &mut layouts {
+                layout.x = x;
+                x = x.saturating_add(layout.width as i16);
+            }
+        }
+
+        if self.original.is_none() {
+            self.original = Some(self.save(&resources)?);
+        }
+        let (width, height) = extents(&layouts);
+        configure(conn, self.root, resources.config_timestamp, &layouts, width, height)
+            .context("failed to apply output scales")?;
+        info!("Applied output scales, screen now {}x{}", width, height);
+        Ok(())
+    }
+
+    /// Put back the layout from before the first scale was applied
+    ///
+    /// CRTCs that have since been moved to other outputs (by a hotplug) keep
+    /// their new configuration.
+    pub fn restore(&mut self) -> Result<()> {
+        let Some(original) = self.original.take() else { return Ok(()) };
+        let conn = &*self.conn;
+        let resources = conn.randr_get_screen_resources_current(self.root)?.reply()?;
+        let mut restored = Vec::new();
+        let (mut width, mut height) = (original.width, original.height);
+        for crtc in original.crtcs {
+            let info = conn.randr_get_crtc_info(crtc.crtc, resources.config_timestamp)?.reply()?;
+            if info.outputs == crtc.outputs {
+                restored.push(crtc);
+            } else if !info.outputs.is_empty() {
+                debug!("CRTC {} drives other outputs now, not restoring it", crtc.crtc);
+                width = width.max((info.x as i32 + info.width as i32).clamp(1, u16::MAX as i32) as u16);
+                height = height.max((info.y as i32 + info.height as i32).clamp(1, u16::MAX as i32) as u16);
+            }
+        }
+        let (restored_width, restored_height) = extents(&restored);
+        let (width, height) = (width.max(restored_width), height.max(restored_height));
+        configure(conn, self.root, resources.config_timestamp, &restored, width, height)
+            .context("failed to restore the output layout")?;
+        info!("Restored the output layout from before scaling, screen now {}x{}", width, height);
+        Ok(())
+    }
+
+    /// The screen size and every active CRTC with its transform
+    fn save(&self, resources: &randr::GetScreenResourcesCurrentReply) -> Result<SavedLayout> {
+        let conn = &*self.conn;
+        let geometry = conn.get_geometry(self.root)?.reply()?;
+        let mut crtcs = Vec::new();
+        for &crtc in &resources.crtcs {
+            let info = conn.randr_get_crtc_info(crtc, resources.config_timestamp)?.reply()?;
+            if info.outputs.is_empty() {
+                continue;
+            }
+            let transform = conn.randr_get_crtc_transform(crtc)?.reply()?;
+            crtcs.push(CrtcConfig {
+                crtc,
+                x: info.x,
+                y: info.y,
+                width: info.width,
+                height: info.height,
+                mode: info.mode,
+                rotation: info.rotation,
+                outputs: info.outputs,
+                transform: transform.current_transform,
+                filter: transform.current_filter_name,
+                params: transform.current_params,
+            });
+        }
+        Ok(SavedLayout { width: geometry.width, height: geometry.height, crtcs })
+    }
+}
+
+impl Drop for OutputScaling {
+    fn drop(&mut self) {
+        if let Err(e) = self.restore() {
+            warn!("Failed to restore the output layout: {:#}", e);
+        }
+    }
+}
+
+/// Screen size that holds every CRTC in `crtcs`
+fn extents(crtcs: &[CrtcConfig]) -> (u16, u16) {
+    let width = crtcs.iter().map(|c| c.x as i32 + c.width as i32).max().unwrap_or(1);
+    let height = crtcs.iter().map(|c| c.y as i32 + c.height as i32).max().unwrap_or(1);
+    (width.clamp(1, u16::MAX as i32) as u16, height.clamp(1, u16::MAX as i32) as u16)
+}
+
+/// Set `crtcs` and resize the screen to `width` x `height`, under a server grab
+fn configure(
+    conn: &RustConnection,
+    root: Window,
+    config_timestamp: x11rb::protocol::xproto::Timestamp,
+    crtcs: &[CrtcConfig],
+    width: u16,
+    height: u16,
+) -> Result<()> {
+    // Keep the DPI the server reports
+    let screen = &conn.setup().roots.iter().find(|s| s.root == root).context("unknown root window")?;
+    let mm_per_px_x = screen.width_in_millimeters as f64 / screen.width_in_pixels.max(1) as f64;
+    let mm_per_px_y = screen.height_in_millimeters as f64 / screen.height_in_pixels.max(1) as f64;
+    let set_size = |width: u16, height: u16| -> Result<()> {
+        conn.randr_set_screen_size(
+            root,
+            width,
+            height,
+            (width as f64 * mm_per_px_x) as u32,
+            (height as f64 * mm_per_px_y) as u32,
+        )?
+        .check()?;
+        Ok(())
+    };
+    let geometry = conn.get_geometry(root)?.reply()?;
+
+    conn.grab_server()?;
+    let result = (|| -> Result<()> {
+        // Every CRTC must fit in the screen at all times, so grow it first...
+        let (grow_width, grow_height) = (width.max(geometry.width), height.max(geometry.height));
+        if (grow_width, grow_height) != (geometry.width, geometry.height) {
+            set_size(grow_width, grow_height)?;
+        }
+
+        for crtc in crtcs {
+            conn.randr_set_crtc_transform(crtc.crtc, crtc.transform, &crtc.filter, &crtc.params)?
+                .check()?;
+            // The transform is pending until the CRTC is configured again
+            let status = conn.randr_set_crtc_config(
+                crtc.crtc,
+                x11rb::CURRENT_TIME,
+                config_timestamp,
+                crtc.x,
+                crtc.y,
+                crtc.mode,
+                crtc.rotation,
+                &crtc.outputs,
+            )?
+            .reply()?
+            .status;
+            if status != randr::SetConfig::SUCCESS {
+                anyhow::bail!("CRTC {} rejected the new configuration ({:?})", crtc.crtc, status);
+            }
+        }
+
+        // ...and shrink it to the final layout afterwards
+        if (width, height) != (grow_width, grow_height) {
+            set_size(width, height)?;
+        }
+        Ok(())
+    })();
+    conn.ungrab_server()?;
+    conn.flush()?;
+    result
+}
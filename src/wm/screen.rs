@@ -22,6 +22,91 @@ pub struct Monitor {
     pub height: u32,
     pub name: String,
     pub primary: bool,
+    /// CRTC rotation in degrees (0, 90, 180 or 270)
+    ///
+    /// The X server applies it at scanout: the geometry above is already in
+    /// rotated root coordinates, as are pointer events, so the compositor and
+    /// the WM work in root space unchanged. Informational only.
+    pub rotation: u16,
+}
+
+/// Ask for RandR notifications of screen resizes, rotations and output changes
+pub fn select_screen_changes(conn: &RustConnection, root: Window) -> Result<()> {
+    use x11rb::protocol::randr::{ConnectionExt as _, NotifyMask};
+    
+    conn.randr_select_input(root, NotifyMask::SCREEN_CHANGE | NotifyMask::CRTC_CHANGE | NotifyMask::OUTPUT_CHANGE)?
+        .check()?;
+    Ok(())
+}
+
+/// Rotation of the CRTC driving an output, in degrees
+fn output_rotation(conn: &RustConnection, output: u32) -> Result<u16> {
+    use x11rb::protocol::randr::{ConnectionExt as _, Rotation};
+    
+    let crtc = conn.randr_get_output_info(output, x11rb::CURRENT_TIME)?.reply()?.crtc;
+    if crtc == x11rb::NONE {
+        return Ok(0);
+    }
+    let rotation = conn.randr_get_crtc_info(crtc, x11rb::CURRENT_TIME)?.reply()?.rotation;
+    Ok(if rotation.contains(Rotation::ROTATE90) {
+        90
+    } else if rotation.contains(Rotation::ROTATE180) {
+        180
+    } else if rotation.contains(Rotation::ROTATE270) {
+        270
+    } else {
+        0
+    })
+}
+
+/// Query the active outputs via RandR 1.5 `GetMonitors`
+///
+/// Falls back to a single monitor covering the whole root window when RandR
+/// is unavailable or reports no monitors.
+pub fn query_monitors(conn: &RustConnection, root: Window) -> Result<Vec<Monitor>> {
+    use x11rb::protocol::randr::ConnectionExt as _;
+    
+    let reply = conn.randr_get_monitors(root, true)
+        .map_err(x11rb::errors::ReplyError::from)
+        .and_then(|cookie| cookie.reply());
+    let monitors: Vec<Monitor> = match reply {
+        Ok(reply) => reply
+            .monitors
+            .iter()
+            .map(|m| Monitor {
+                x: m.x as i32,
+                y: m.y as i32,
+                width: m.width as u32,
+                height: m.height as u32,
+                name: conn
+                    .get_atom_name(m.name)
+                    .ok()
+                    .and_then(|cookie| cookie.reply().ok())
+                    .map(|reply| String::from_utf8_lossy(&reply.name).into_owned())
+                    .unwrap_or_default(),
+                primary: m.primary,
+                rotation: m.outputs.first().and_then(|&o| output_rotation(conn, o).ok()).unwrap_or(0),
+            })
+            .collect(),
+        Err(e) => {
+            debug!("RandR GetMonitors failed, using root geometry: {}", e);
+            Vec::new()
+        }
+    };
+    if !monitors.is_empty() {
+        return Ok(monitors);
+    }
+    
+    let geometry = conn.get_geometry(root)?.reply()?;
+    Ok(vec![Monitor {
+        x: 0,
+        y: 0,
+        width: geometry.width as u32,
+        height: geometry.height as u32,
+        name: "Default".to_string(),
+        primary: true,
+        rotation: 0,
+    }])
 }
 
 /// Desktop layout (EWMH)
@@ -145,6 +230,7 @@ impl ScreenInfo {
             height: height as u32,
             name: "Default".to_string(),
             primary: true,
+            rotation: 0,
         }];
         
         // Work area starts as full screen (struts will reduce it)
@@ -0,0 +1,224 @@
+//! PRIMARY / CLIPBOARD synchronization
+//!
+//! Optionally makes middle-click paste (PRIMARY) and Ctrl+V (CLIPBOARD)
+//! paste the same text. XFixes reports every new owner of either selection;
+//! the text is then fetched from the source selection and the WM's own
+//! window takes the other one, serving the copy to applications that paste.
+//!
+//! Loop protection: ownership changes to the WM's window are ignored, and a
+//! selection still holding the copy is not taken over again for the same
+//! text. Only text is copied (UTF8_STRING); images and file lists stay in
+//! their own selection, as do transfers too large for a single property
+//! (INCR). The copy is also offered as STRING (Latin-1) when it has no
+//! characters beyond Latin-1.
+
+use anyhow::Result;
+use tracing::debug;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::xfixes::{self, ConnectionExt as _, SelectionEventMask};
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, SelectionClearEvent, SelectionNotifyEvent,
+    SelectionRequestEvent, Timestamp, WindowClass, SELECTION_NOTIFY_EVENT,
+};
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+use crate::config::SelectionSync as Direction;
+
+/// Largest text taken over (bytes)
+const MAX_TEXT: u32 = 16 * 1024 * 1024;
+
+/// Atoms used for the transfer
+#[derive(Debug, Default, Clone, Copy)]
+struct Atoms {
+    primary: Atom,
+    clipboard: Atom,
+    targets: Atom,
+    utf8_string: Atom,
+    text: Atom,
+    incr: Atom,
+    /// Property of the WM's window receiving the converted text
+    transfer: Atom,
+}
+
+#[derive(Debug, Default)]
+pub struct SelectionSync {
+    direction: Direction,
+    /// Hidden window owning the copies (0 until `init`)
+    window: u32,
+    atoms: Atoms,
+    /// Conversion in flight: source selection, selection to take, time
+    pending: Option<(Atom, Atom, Timestamp)>,
+    /// Text last synchronized
+    text: Vec<u8>,
+    /// Selections the WM's window currently owns
+    owned: Vec<Atom>,
+}
+
+impl SelectionSync {
+    /// Create the owner window and watch both selections
+    pub fn init(&mut self, conn: &RustConnection, root: u32) -> Result<()> {
+        conn.xfixes_query_version(5, 0)?.reply()?;
+        let intern = |name| crate::wm::atom_cache::intern(conn, name);
+        self.atoms = Atoms {
+            primary: AtomEnum::PRIMARY.into(),
+            clipboard: intern("CLIPBOARD")?,
+            targets: intern("TARGETS")?,
+            utf8_string: intern("UTF8_STRING")?,
+            text: intern("TEXT")?,
+            incr: intern("INCR")?,
+            transfer: intern("_AREA_SELECTION")?,
+        };
+        let window = conn.generate_id()?;
+        conn.create_window(
+            0,
+            window,
+            root,
+            -1,
+            -1,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            0,
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+        for selection in [self.atoms.primary, self.atoms.clipboard] {
+            conn.xfixes_select_selection_input(window, selection, SelectionEventMask::SET_SELECTION_OWNER)?;
+        }
+        self.window = window;
+        Ok(())
+    }
+
+    /// Change the direction; turning sync off keeps copies already made
+    pub fn set_direction(&mut self, direction: Direction) {
+        if direction != self.direction {
+            debug!("Selection sync: {:?}", direction);
+            self.direction = direction;
+        }
+    }
+
+    /// Selection to copy a selection's new text into, if synchronized
+    fn target(&self, source: Atom) -> Option<Atom> {
+        let Atoms { primary, clipboard, .. } = self.atoms;
+        match self.direction {
+            Direction::PrimaryToClipboard | Direction::Both if source == primary => Some(clipboard),
+            Direction::ClipboardToPrimary | Direction::Both if source == clipboard => Some(primary),
+            _ => None,
+        }
+    }
+
+    /// A selection has a new owner: ask it for its text
+    pub fn owner_changed(&mut self, conn: &RustConnection, event: &xfixes::SelectionNotifyEvent) -> Result<()> {
+        if self.window == 0 || event.owner == self.window || event.owner == x11rb::NONE {
+            return Ok(());
+        }
+        let Some(target) = self.target(event.selection) else {
+            return Ok(());
+        };
+        conn.convert_selection(self.window, event.selection, self.atoms.utf8_string, self.atoms.transfer, event.selection_timestamp)?;
+        self.pending = Some((event.selection, target, event.timestamp));
+        Ok(())
+    }
+
+    /// The source's answer to a conversion: take the other selection
+    pub fn converted(&mut self, conn: &RustConnection, event: &SelectionNotifyEvent) -> Result<()> {
+        if event.requestor != self.window {
+            return Ok(());
+        }
+        let Some((source, target, time)) = self.pending.take_if(|(source, _, _)| *source == event.selection) else {
+            return Ok(());
+        };
+        if event.property == x11rb::NONE {
+            debug!("Selection {} has no text to synchronize", source);
+            return Ok(());
+        }
+        let reply = conn.get_property(true, self.window, self.atoms.transfer, AtomEnum::ANY, 0, MAX_TEXT / 4)?.reply()?;
+        if reply.type_ == self.atoms.incr || reply.bytes_after > 0 {
+            debug!("Selection {} too large to synchronize", source);
+            return Ok(());
+        }
+        if reply.value.is_empty() || (reply.value == self.text && self.owned.contains(&target)) {
+            // The other selection already has it (or the source is a copy
+            // made by another synchronizing tool)
+            return Ok(());
+        }
+        self.text = reply.value;
+        conn.set_selection_owner(self.window, target, time)?;
+        if !self.owned.contains(&target) {
+            self.owned.push(target);
+        }
+        debug!("Synchronized {} bytes from selection {} to {}", self.text.len(), source, target);
+        Ok(())
+    }
+
+    /// Serve the copied text to an application pasting it
+    pub fn request(&self, conn: &RustConnection, event: &SelectionRequestEvent) -> Result<()> {
+        if event.owner != self.window {
+            return Ok(());
+        }
+        // Obsolete clients leave the property to the owner
+        let property = if event.property == x11rb::NONE { event.target } else { event.property };
+        let a = self.atoms;
+        let latin1 = latin1(&self.text);
+        let served = if !self.owned.contains(&event.selection) {
+            false
+        } else if event.target == a.targets {
+            let mut targets = vec![a.targets, a.utf8_string, a.text];
+            if latin1.is_some() {
+                targets.push(AtomEnum::STRING.into());
+            }
+            conn.change_property32(PropMode::REPLACE, event.requestor, property, AtomEnum::ATOM, &targets)?;
+            true
+        } else if event.target == u32::from(AtomEnum::STRING) {
+            match latin1 {
+                Some(text) if text.len() + 64 < conn.maximum_request_bytes() => {
+                    conn.change_property8(PropMode::REPLACE, event.requestor, property, AtomEnum::STRING, &text)?;
+                    true
+                }
+                _ => false,
+            }
+        } else if [a.utf8_string, a.text].contains(&event.target) && self.text.len() + 64 < conn.maximum_request_bytes() {
+            // TEXT lets the owner pick the encoding
+            conn.change_property8(PropMode::REPLACE, event.requestor, property, a.utf8_string, &self.text)?;
+            true
+        } else {
+            false
+        };
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: event.time,
+            requestor: event.requestor,
+            selection: event.selection,
+            target: event.target,
+            property: if served { property } else { x11rb::NONE },
+        };
+        conn.send_event(false, event.requestor, EventMask::NO_EVENT, notify)?;
+        Ok(())
+    }
+
+    /// Another application took a selection from the WM's window
+    pub fn cleared(&mut self, event: &SelectionClearEvent) {
+        if event.owner == self.window {
+            self.owned.retain(|&s| s != event.selection);
+        }
+    }
+}
+
+/// UTF-8 text as Latin-1 (what STRING holds), if every character fits
+fn latin1(text: &[u8]) -> Option<Vec<u8>> {
+    std::str::from_utf8(text).ok()?.chars().map(|c| u8::try_from(c).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_to_latin1_when_representable() {
+        assert_eq!(latin1("café".as_bytes()), Some(b"caf\xe9".to_vec()));
+        assert_eq!(latin1("€5".as_bytes()), None);
+        assert_eq!(latin1(b"\xff"), None);
+    }
+}
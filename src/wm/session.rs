@@ -3,7 +3,7 @@
 //! Xfce session manager integration and state persistence.
 //! This matches xfwm4's session management.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use tracing::{debug, info, warn};
 use x11rb::connection::Connection;
 use x11rb::rust_connection::RustConnection;
@@ -46,14 +46,46 @@ impl SessionManager {
     }
     
     /// Save window state
+    ///
+    /// Writes class, title, geometry, workspace and state of every managed
+    /// window to `save_path` (default `$XDG_STATE_HOME/area/session.json`).
     pub fn save_state(
         &self,
         clients: &std::collections::HashMap<u32, Client>,
     ) -> Result<()> {
-        debug!("Saving window state");
+        let path = match self.save_path {
+            Some(ref path) => std::path::PathBuf::from(path),
+            None => dirs::state_dir()
+                .or_else(dirs::data_local_dir)
+                .context("no state directory")?
+                .join("area/session.json"),
+        };
+        debug!("Saving window state to {:?}", path);
         
-        // TODO: Save window positions, sizes, states to session file
+        let windows: Vec<_> = clients.values()
+            .map(|client| {
+                let class = client.class_hint.as_ref().map(|hint| hint.res_class.as_str()).unwrap_or("");
+                serde_json::json!({
+                    "class": class,
+                    "name": client.name,
+                    "x": client.geometry.x,
+                    "y": client.geometry.y,
+                    "width": client.geometry.width,
+                    "height": client.geometry.height,
+                    "workspace": client.win_workspace,
+                    "maximized": client.is_maximized(),
+                    "minimized": client.is_minimized(),
+                    "sticky": client.is_sticky(),
+                })
+            })
+            .collect();
         
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(&serde_json::json!({ "windows": windows }))?)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        info!("Saved state of {} windows", windows.len());
         Ok(())
     }
     
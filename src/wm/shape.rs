@@ -0,0 +1,48 @@
+//! Shaped windows (XShape)
+//!
+//! Clients like xeyes, conky or splash screens set a bounding region. Their
+//! composite pixmap still covers the whole rectangle, so the compositor
+//! clips them to the region; a framed one also gets its input region
+//! mirrored on the frame so clicks on transparent parts fall through.
+
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::shape::{ConnectionExt as _, SK, SO};
+use x11rb::protocol::xproto::{ClipOrdering, Rectangle, Window};
+
+use crate::shared::Geometry;
+
+/// Get ShapeNotify events for a window
+pub fn select_input<C: Connection>(conn: &C, window: Window) -> Result<()> {
+    conn.shape_select_input(window, true)?;
+    Ok(())
+}
+
+/// A window's bounding region in its own coordinates, None if rectangular
+pub fn bounding_rects<C: Connection>(conn: &C, window: Window) -> Result<Option<Vec<Geometry>>> {
+    if !conn.shape_query_extents(window)?.reply()?.bounding_shaped {
+        return Ok(None);
+    }
+    let reply = conn.shape_get_rectangles(window, SK::BOUNDING)?.reply()?;
+    Ok(Some(
+        reply
+            .rectangles
+            .iter()
+            .map(|r| Geometry::new(r.x as i32, r.y as i32, r.width as u32, r.height as u32))
+            .collect(),
+    ))
+}
+
+/// Give a frame its shaped client's input region below the titlebar, or
+/// the default (rectangular) one back
+pub fn set_frame_input<C: Connection>(conn: &C, frame: Window, client: Window, titlebar: u32, shaped: bool) -> Result<()> {
+    if !shaped {
+        conn.shape_mask(SO::SET, SK::INPUT, frame, 0, 0, x11rb::NONE)?;
+        return Ok(());
+    }
+    conn.shape_combine(SO::SET, SK::INPUT, SK::INPUT, frame, 0, titlebar as i16, client)?;
+    // The titlebar stays clickable; wider than any frame, so resizes keep it whole
+    let titlebar = Rectangle { x: 0, y: 0, width: u16::MAX, height: titlebar as u16 };
+    conn.shape_rectangles(SO::UNION, SK::INPUT, ClipOrdering::UNSORTED, frame, 0, 0, &[titlebar])?;
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! Snap groups
+//!
+//! Two windows tiled to opposite halves of the same monitor share their
+//! edge like a tiling splitter: dragging it, or moving it from the
+//! keyboard, resizes both at once. The group is dissolved when either
+//! window leaves its tile.
+
+/// Smallest and largest share of the split area the first window may take
+const MIN_SPLIT: f32 = 0.1;
+const MAX_SPLIT: f32 = 0.9;
+
+/// Two windows tiled side by side (or one above the other)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapGroup {
+    /// Left (or top) window
+    pub first: u32,
+    /// Right (or bottom) window
+    pub second: u32,
+    /// Stacked top and bottom instead of side by side
+    pub vertical: bool,
+    /// Share of the area the first window takes
+    pub split: f32,
+}
+
+impl SnapGroup {
+    pub fn contains(&self, window: u32) -> bool {
+        self.first == window || self.second == window
+    }
+
+    /// The other window of the group
+    pub fn partner(&self, window: u32) -> u32 {
+        if self.first == window { self.second } else { self.first }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SnapGroups {
+    groups: Vec<SnapGroup>,
+}
+
+impl SnapGroups {
+    /// Pair two tiled windows with an even split
+    pub fn join(&mut self, first: u32, second: u32, vertical: bool) {
+        self.forget(first);
+        self.forget(second);
+        self.groups.push(SnapGroup { first, second, vertical, split: 0.5 });
+    }
+
+    /// Dissolve the group of a window; returns its former partner
+    pub fn forget(&mut self, window: u32) -> Option<u32> {
+        let index = self.groups.iter().position(|group| group.contains(window))?;
+        Some(self.groups.swap_remove(index).partner(window))
+    }
+
+    pub fn group_of(&self, window: u32) -> Option<SnapGroup> {
+        self.groups.iter().find(|group| group.contains(window)).copied()
+    }
+
+    /// Share of its group's area the first window gets (a half when ungrouped)
+    pub fn split_of(&self, window: u32) -> f32 {
+        self.group_of(window).map_or(0.5, |group| group.split)
+    }
+
+    /// Move the split of a window's group; returns the updated group
+    pub fn set_split(&mut self, window: u32, split: f32) -> Option<SnapGroup> {
+        let group = self.groups.iter_mut().find(|group| group.contains(window))?;
+        group.split = split.clamp(MIN_SPLIT, MAX_SPLIT);
+        Some(*group)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SnapGroup> {
+        self.groups.iter()
+    }
+}
@@ -1,25 +1,64 @@
 //! Stacking Module
 //!
-//! Manages window z-order, layers, and stacking operations.
-//! This matches xfwm4's stacking system.
+//! Manages window z-order as a list of layers, like xfwm4's stacking system.
+//!
+//! Every managed window belongs to exactly one `WindowLayer`, derived from its
+//! `_NET_WM_WINDOW_TYPE` and `_NET_WM_STATE` (see `layer_for`). Within a layer
+//! windows keep their relative order (raise/lower move a window to the top or
+//! bottom of its own layer). The X stacking order is recomputed from the layer
+//! list in `restack`, so a raised normal window can never end up above a dock
+//! and a desktop window can never end up above anything. Transient windows
+//! (dialogs) are kept directly above their parent when both share a layer.
 
 use anyhow::Result;
-use tracing::{debug, info, warn};
-use x11rb::connection::Connection;
+use std::collections::HashMap;
+use tracing::debug;
 use x11rb::protocol::xproto::*;
 use x11rb::rust_connection::RustConnection;
 use x11rb::wrapper::ConnectionExt as _;
 
 use crate::wm::client::Client;
-use crate::wm::client_flags::WindowLayer;
-use crate::wm::display::DisplayInfo;
+use crate::wm::client_flags::{ClientFlags, WindowLayer, WindowType};
 use crate::wm::ewmh::Atoms;
-use crate::wm::screen::ScreenInfo;
+
+/// Compute the stacking layer of a client from its type and state
+///
+/// Fullscreen windows only go above docks while focused (EWMH), so switching
+/// to another window brings the panel back.
+pub fn layer_for(client: &Client) -> WindowLayer {
+    match client.type_ {
+        WindowType::Desktop => return WindowLayer::Desktop,
+        WindowType::Notification
+        | WindowType::Tooltip
+        | WindowType::Splashscreen
+        | WindowType::DropdownMenu
+        | WindowType::PopupMenu
+        | WindowType::Combo
+        | WindowType::Dnd => return WindowLayer::Overlay,
+        _ => {}
+    }
+
+    if client.flags.contains(ClientFlags::FULLSCREEN) && client.focused() {
+        WindowLayer::Fullscreen
+    } else if client.flags.contains(ClientFlags::ABOVE) {
+        WindowLayer::Above
+    } else if client.flags.contains(ClientFlags::BELOW) {
+        WindowLayer::Below
+    } else if client.type_ == WindowType::Dock {
+        WindowLayer::Dock
+    } else {
+        WindowLayer::Normal
+    }
+}
 
 /// Stacking manager
 pub struct StackingManager {
-    /// Stacking order (bottom to top)
-    pub stacking_order: Vec<u32>,
+    /// Managed client windows, bottom to top
+    stacking_order: Vec<u32>,
+    /// X windows (frames or clients) in the order last sent to the server
+    applied: Vec<u32>,
+    /// Set when the order or a window's layer may have changed
+    dirty: bool,
 }
 
 impl StackingManager {
@@ -27,182 +66,146 @@ impl StackingManager {
     pub fn new() -> Self {
         Self {
             stacking_order: Vec::new(),
+            applied: Vec::new(),
+            dirty: false,
         }
     }
-    
+
+    /// Add a newly managed window at the top of its layer
+    pub fn add_window(&mut self, window: u32) {
+        if !self.stacking_order.contains(&window) {
+            self.stacking_order.push(window);
+        }
+        self.dirty = true;
+    }
+
+    /// Remove window from stacking order
+    pub fn remove_window(&mut self, window: u32) {
+        self.stacking_order.retain(|&w| w != window);
+        self.dirty = true;
+    }
+
     /// Raise a window to the top of its layer
-    pub fn raise_window(
-        &mut self,
-        conn: &RustConnection,
-        display_info: &DisplayInfo,
-        screen_info: &ScreenInfo,
-        window: u32,
-        clients: &std::collections::HashMap<u32, Client>,
-    ) -> Result<()> {
+    pub fn raise_window(&mut self, window: u32) {
         debug!("Raising window {}", window);
-        
-        // Remove from stacking order
         self.stacking_order.retain(|&w| w != window);
-        
-        // Add to top
         self.stacking_order.push(window);
-        
-        // Apply X11 stacking
-        conn.configure_window(
-            window,
-            &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
-        )?;
-        
-        // Update _NET_CLIENT_LIST_STACKING
-        self.update_client_list_stacking(conn, display_info, screen_info, clients)?;
-        
-        Ok(())
+        self.dirty = true;
     }
-    
+
     /// Lower a window to the bottom of its layer
-    pub fn lower_window(
-        &mut self,
-        conn: &RustConnection,
-        display_info: &DisplayInfo,
-        screen_info: &ScreenInfo,
-        window: u32,
-        clients: &std::collections::HashMap<u32, Client>,
-    ) -> Result<()> {
+    pub fn lower_window(&mut self, window: u32) {
         debug!("Lowering window {}", window);
-        
-        // Remove from stacking order
         self.stacking_order.retain(|&w| w != window);
-        
-        // Add to bottom
         self.stacking_order.insert(0, window);
-        
-        // Apply X11 stacking
-        conn.configure_window(
-            window,
-            &ConfigureWindowAux::new().stack_mode(StackMode::BELOW),
-        )?;
-        
-        // Update _NET_CLIENT_LIST_STACKING
-        self.update_client_list_stacking(conn, display_info, screen_info, clients)?;
-        
-        Ok(())
+        self.dirty = true;
     }
-    
-    /// Set window layer
-    pub fn set_layer(
-        &mut self,
-        conn: &RustConnection,
-        display_info: &DisplayInfo,
-        screen_info: &ScreenInfo,
-        client: &mut Client,
-        layer: WindowLayer,
-        clients: &std::collections::HashMap<u32, Client>,
-    ) -> Result<()> {
-        debug!("Setting layer {:?} for window {}", layer, client.window);
-        
-        let old_layer = client.win_layer;
-        client.win_layer = layer;
-        
-        // Re-stack windows based on layers
-        self.restack_by_layers(conn, display_info, screen_info, clients)?;
-        
-        // Update _NET_CLIENT_LIST_STACKING
-        self.update_client_list_stacking(conn, display_info, screen_info, clients)?;
-        
-        Ok(())
+
+    /// Request a restack after a layer-affecting state change (type, above/below, fullscreen, focus)
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
     }
-    
-    /// Restack windows based on their layers
-    pub fn restack_by_layers(
+
+    /// Whether a restack is pending
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Managed windows, bottom to top
+    pub fn order(&self) -> &[u32] {
+        &self.stacking_order
+    }
+
+    /// Recompute layers and apply the resulting order to the X server
+    ///
+    /// Updates each client's `win_layer`, restacks frames bottom to top if the
+    /// order changed and publishes `_NET_CLIENT_LIST_STACKING`.
+    pub fn restack(
         &mut self,
         conn: &RustConnection,
-        display_info: &DisplayInfo,
-        screen_info: &ScreenInfo,
-        clients: &std::collections::HashMap<u32, Client>,
+        atoms: &Atoms,
+        root: Window,
+        clients: &mut HashMap<u32, Client>,
     ) -> Result<()> {
-        // Group windows by layer
-        let mut by_layer: std::collections::BTreeMap<WindowLayer, Vec<u32>> = 
-            std::collections::BTreeMap::new();
-        
-        for window_id in &self.stacking_order {
-            if let Some(client) = clients.get(window_id) {
-                by_layer
-                    .entry(client.win_layer)
-                    .or_insert_with(Vec::new)
-                    .push(*window_id);
-            }
-        }
-        
-        // Build new stacking order (bottom to top, by layer)
-        let mut new_order = Vec::new();
-        for (_, windows) in by_layer.iter() {
-            new_order.extend(windows.iter().rev()); // Reverse to maintain order within layer
+        self.dirty = false;
+        self.stacking_order.retain(|w| clients.contains_key(w));
+
+        for client in clients.values_mut() {
+            client.win_layer = layer_for(client);
         }
-        
-        self.stacking_order = new_order;
-        
-        // Apply X11 stacking
-        if let Some(&bottom) = self.stacking_order.first() {
-            // Stack all windows relative to bottom window
-            for &window in self.stacking_order.iter().skip(1) {
-                conn.configure_window(
-                    window,
-                    &ConfigureWindowAux::new()
-                        .sibling(bottom)
-                        .stack_mode(StackMode::ABOVE),
-                )?;
+
+        // Stable sort keeps the relative order within each layer
+        self.stacking_order.sort_by_key(|w| clients[w].win_layer);
+        self.keep_transients_above(clients);
+
+        let order: Vec<u32> = self
+            .stacking_order
+            .iter()
+            .map(|w| {
+                let client = &clients[w];
+                client.frame.as_ref().map(|f| f.frame).unwrap_or(client.window)
+            })
+            .collect();
+
+        if order != self.applied {
+            // Bottom window goes below everything, each following window
+            // directly above its predecessor
+            for (i, &window) in order.iter().enumerate() {
+                let aux = if i == 0 {
+                    ConfigureWindowAux::new().stack_mode(StackMode::BELOW)
+                } else {
+                    ConfigureWindowAux::new().sibling(order[i - 1]).stack_mode(StackMode::ABOVE)
+                };
+                conn.configure_window(window, &aux)?;
             }
+            debug!("Restacked {} windows", order.len());
+            self.applied = order;
         }
-        
+
+        self.update_client_list_stacking(conn, atoms, root)?;
         Ok(())
     }
+
+    /// Move transients below their parent to just above it, within a layer
+    fn keep_transients_above(&mut self, clients: &HashMap<u32, Client>) {
+        let order = &mut self.stacking_order;
+        // Bounded, in case of a transient loop
+        let mut moves = order.len() * order.len();
+        let mut i = 0;
+        while i < order.len() {
+            let client = &clients[&order[i]];
+            let parent_index = client.transient_for.and_then(|parent| {
+                order.iter().position(|&w| w == parent)
+                    .filter(|&index| index > i && clients[&parent].win_layer == client.win_layer)
+            });
+            match parent_index {
+                Some(index) if moves > 0 => {
+                    moves -= 1;
+                    // Removal shifts the parent down to `index - 1`
+                    let window = order.remove(i);
+                    order.insert(index, window);
+                }
+                _ => i += 1,
+            }
+        }
+    }
     
-    /// Update _NET_CLIENT_LIST_STACKING root property
+    /// Update _NET_CLIENT_LIST_STACKING root property (bottom to top, per EWMH)
     pub fn update_client_list_stacking(
         &self,
         conn: &RustConnection,
-        display_info: &DisplayInfo,
-        screen_info: &ScreenInfo,
-        clients: &std::collections::HashMap<u32, Client>,
+        atoms: &Atoms,
+        root: Window,
     ) -> Result<()> {
-        // Build list in reverse stacking order (top to bottom)
-        let mut client_list: Vec<u32> = self.stacking_order.iter().rev().copied().collect();
-        
-        // Filter to only include mapped windows
-        client_list.retain(|&w| {
-            clients.get(&w).map(|c| c.mapped()).unwrap_or(false)
-        });
-        
-        // Set root property
         conn.change_property32(
             PropMode::REPLACE,
-            screen_info.root,
-            display_info.atoms.net_client_list,
+            root,
+            atoms.net_client_list_stacking,
             AtomEnum::WINDOW,
-            &client_list,
+            &self.stacking_order,
         )?;
-        
-        debug!("Updated _NET_CLIENT_LIST_STACKING with {} windows", client_list.len());
-        
         Ok(())
     }
-    
-    /// Add window to stacking order
-    pub fn add_window(&mut self, window: u32) {
-        if !self.stacking_order.contains(&window) {
-            self.stacking_order.push(window);
-        }
-    }
-    
-    /// Remove window from stacking order
-    pub fn remove_window(&mut self, window: u32) {
-        self.stacking_order.retain(|&w| w != window);
-    }
-    
-    /// Get stacking order (bottom to top)
-    pub fn get_stacking_order(&self) -> &[u32] {
-        &self.stacking_order
-    }
 }
 
 impl Default for StackingManager {
@@ -210,6 +213,3 @@ impl Default for StackingManager {
         Self::new()
     }
 }
-
-
-
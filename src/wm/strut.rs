@@ -0,0 +1,175 @@
+//! Struts Module
+//!
+//! Screen-edge reservations and the work area they leave free.
+//!
+//! Reservations come from two places: `_NET_WM_STRUT_PARTIAL` / `_NET_WM_STRUT`
+//! on X clients (docks), and panels the shell declares over IPC
+//! (`SetPanelStrut`), which aren't X windows at all. Both are reduced to the
+//! same `Reservation` (EWMH semantics: a thickness measured from the edge of
+//! the root window plus the range it covers along that edge), so maximize and
+//! placement treat them identically.
+
+use anyhow::Result;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+
+use crate::shared::Geometry;
+use crate::wm::ewmh::Atoms;
+use crate::wm::screen::Monitor;
+
+/// Screen edge a strut is attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StrutEdge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// Space reserved along one edge of the root window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reservation {
+    pub edge: StrutEdge,
+    /// Thickness, measured from the root window edge
+    pub size: u32,
+    /// Covered range along the edge (y for left/right, x for top/bottom), inclusive
+    pub start: u32,
+    pub end: u32,
+}
+
+/// A panel declared by the shell, relative to its monitor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PanelStrut {
+    /// Thickness from the monitor edge
+    pub size: u32,
+    /// Covered range along the monitor edge; `end <= start` means the whole edge
+    pub start: u32,
+    pub end: u32,
+}
+
+/// Read a client's strut (`_NET_WM_STRUT_PARTIAL`, falling back to `_NET_WM_STRUT`)
+pub fn read_client_strut(
+    conn: &RustConnection,
+    atoms: &Atoms,
+    window: Window,
+    root_width: u32,
+    root_height: u32,
+) -> Result<Vec<Reservation>> {
+    let partial = conn
+        .get_property(false, window, atoms._net_wm_strut_partial, AtomEnum::CARDINAL, 0, 12)?
+        .reply()?;
+    if let Some(values) = partial.value32() {
+        let values: Vec<u32> = values.collect();
+        if values.len() == 12 {
+            return Ok(from_partial(&values));
+        }
+    }
+
+    let legacy = conn
+        .get_property(false, window, atoms._net_wm_strut, AtomEnum::CARDINAL, 0, 4)?
+        .reply()?;
+    if let Some(values) = legacy.value32() {
+        let values: Vec<u32> = values.collect();
+        if values.len() == 4 {
+            // Legacy struts cover the whole edge
+            let full = [
+                values[0], values[1], values[2], values[3],
+                0, root_height.saturating_sub(1),
+                0, root_height.saturating_sub(1),
+                0, root_width.saturating_sub(1),
+                0, root_width.saturating_sub(1),
+            ];
+            return Ok(from_partial(&full));
+        }
+    }
+
+    Ok(Vec::new())
+}
+
+/// Convert the 12 `_NET_WM_STRUT_PARTIAL` values into reservations
+fn from_partial(values: &[u32]) -> Vec<Reservation> {
+    let edges = [
+        (StrutEdge::Left, values[0], values[4], values[5]),
+        (StrutEdge::Right, values[1], values[6], values[7]),
+        (StrutEdge::Top, values[2], values[8], values[9]),
+        (StrutEdge::Bottom, values[3], values[10], values[11]),
+    ];
+    edges
+        .into_iter()
+        .filter(|&(_, size, _, _)| size > 0)
+        .map(|(edge, size, start, end)| Reservation { edge, size, start, end })
+        .collect()
+}
+
+impl PanelStrut {
+    /// Express the panel as a root-relative reservation, as if it were an X strut
+    pub fn to_reservation(
+        self,
+        edge: StrutEdge,
+        monitor: &Monitor,
+        root_width: u32,
+        root_height: u32,
+    ) -> Reservation {
+        let (origin, length) = match edge {
+            StrutEdge::Left | StrutEdge::Right => (monitor.y.max(0) as u32, monitor.height),
+            StrutEdge::Top | StrutEdge::Bottom => (monitor.x.max(0) as u32, monitor.width),
+        };
+        let (start, end) = if self.end > self.start {
+            (origin + self.start, (origin + self.end.min(length)).saturating_sub(1))
+        } else {
+            (origin, origin + length.saturating_sub(1))
+        };
+
+        let monitor_right = (monitor.x.max(0) as u32) + monitor.width;
+        let monitor_bottom = (monitor.y.max(0) as u32) + monitor.height;
+        let size = match edge {
+            StrutEdge::Left => monitor.x.max(0) as u32 + self.size,
+            StrutEdge::Right => root_width.saturating_sub(monitor_right) + self.size,
+            StrutEdge::Top => monitor.y.max(0) as u32 + self.size,
+            StrutEdge::Bottom => root_height.saturating_sub(monitor_bottom) + self.size,
+        };
+
+        Reservation { edge, size, start, end }
+    }
+}
+
+/// Part of `monitor` not covered by any reservation
+pub fn work_area<'a>(
+    monitor: &Monitor,
+    reservations: impl IntoIterator<Item = &'a Reservation>,
+    root_width: u32,
+    root_height: u32,
+) -> Geometry {
+    let mut left = monitor.x;
+    let mut top = monitor.y;
+    let mut right = monitor.x + monitor.width as i32;
+    let mut bottom = monitor.y + monitor.height as i32;
+
+    // A reservation only applies if its range overlaps the monitor along the edge
+    let overlaps = |start: u32, end: u32, from: i32, to: i32| (start as i32) < to && (end as i32) >= from;
+
+    for r in reservations {
+        match r.edge {
+            StrutEdge::Left if overlaps(r.start, r.end, monitor.y, monitor.y + monitor.height as i32) => {
+                left = left.max(r.size as i32);
+            }
+            StrutEdge::Right if overlaps(r.start, r.end, monitor.y, monitor.y + monitor.height as i32) => {
+                right = right.min(root_width as i32 - r.size as i32);
+            }
+            StrutEdge::Top if overlaps(r.start, r.end, monitor.x, monitor.x + monitor.width as i32) => {
+                top = top.max(r.size as i32);
+            }
+            StrutEdge::Bottom if overlaps(r.start, r.end, monitor.x, monitor.x + monitor.width as i32) => {
+                bottom = bottom.min(root_height as i32 - r.size as i32);
+            }
+            _ => {}
+        }
+    }
+
+    Geometry {
+        x: left,
+        y: top,
+        width: (right - left).max(1) as u32,
+        height: (bottom - top).max(1) as u32,
+    }
+}
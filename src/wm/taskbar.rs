@@ -0,0 +1,87 @@
+//! Taskbar Order Module
+//!
+//! Order of the shell's taskbar buttons as arranged by the user
+//! (`SetTaskbarOrder`). Kept in `$XDG_STATE_HOME/area/taskbar.json` so a
+//! restarted window manager, which re-manages the same windows, hands the
+//! shell the same order. The buttons as shown, pinned launchers included,
+//! are kept too so Super+number activates the same button the user sees.
+
+use anyhow::{Context, Result};
+use area_ipc::TaskbarSlot;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+/// User-defined taskbar order
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TaskbarOrder {
+    /// Client window ids, first button first
+    ids: Vec<u32>,
+    /// Buttons as the shell shows them (empty: one per window of `ids`)
+    #[serde(default)]
+    slots: Vec<TaskbarSlot>,
+}
+
+impl TaskbarOrder {
+    fn path() -> Option<PathBuf> {
+        Some(dirs::state_dir().or_else(dirs::data_local_dir)?.join("area/taskbar.json"))
+    }
+
+    /// Read the saved order (empty if there is none)
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else { return Self::default() };
+        let Ok(data) = std::fs::read_to_string(&path) else { return Self::default() };
+        serde_json::from_str(&data).unwrap_or_else(|e| {
+            warn!("Ignoring invalid taskbar order {:?}: {}", path, e);
+            Self::default()
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = Self::path().context("no state directory")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string(self)?)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        debug!("Saved taskbar order of {} windows", self.ids.len());
+        Ok(())
+    }
+
+    /// Replace the order and buttons and save them
+    pub fn set(&mut self, ids: Vec<u32>, slots: Vec<TaskbarSlot>) -> Result<()> {
+        self.ids = ids;
+        self.slots = slots;
+        self.save()
+    }
+
+    /// The nth button (0-based)
+    pub fn slot(&self, index: usize) -> Option<TaskbarSlot> {
+        if self.slots.is_empty() {
+            let &id = self.ids.get(index)?;
+            return Some(TaskbarSlot { windows: vec![id], ..Default::default() });
+        }
+        self.slots.get(index).cloned()
+    }
+
+    /// Position of a window's button, if the user placed it
+    pub fn position(&self, id: u32) -> Option<u32> {
+        self.ids.iter().position(|&w| w == id).map(|i| i as u32)
+    }
+
+    /// Drop an unmanaged window (its id may be reused by another client)
+    pub fn forget(&mut self, id: u32) {
+        let in_slots = self.slots.iter().any(|slot| slot.windows.contains(&id));
+        if self.position(id).is_some() || in_slots {
+            self.ids.retain(|&w| w != id);
+            for slot in &mut self.slots {
+                slot.windows.retain(|&w| w != id);
+            }
+            // Buttons of closed unpinned windows are gone
+            self.slots.retain(|slot| !slot.windows.is_empty() || slot.launch.is_some() || slot.pinned.is_some());
+            if let Err(e) = self.save() {
+                warn!("Failed to save taskbar order: {}", e);
+            }
+        }
+    }
+}
@@ -1,63 +1,140 @@
 //! Terminate Module
 //!
 //! Force quit dialogs and unresponsive window handling.
-//! This matches xfwm4's termination system.
+//! This matches xfwm4's termination system: windows advertising
+//! _NET_WM_PING are pinged, and a ping left unanswered past the timeout
+//! marks the window unresponsive until it answers again.
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use tracing::{debug, info, warn};
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::*;
 use x11rb::rust_connection::RustConnection;
-use x11rb::wrapper::ConnectionExt as _;
 
-use crate::wm::client::Client;
-use crate::wm::display::DisplayInfo;
 use crate::wm::ewmh::Atoms;
-use crate::wm::screen::ScreenInfo;
+
+/// How often the focused window is pinged
+pub const PING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a process may take to exit after SIGTERM before it gets SIGKILL
+pub const KILL_GRACE: Duration = Duration::from_secs(3);
 
 /// Termination manager
 pub struct TerminateManager {
-    /// Unresponsive windows (window -> timeout)
-    pub unresponsive: std::collections::HashMap<u32, u32>,
+    /// Unresponsive windows (window -> timestamp of the unanswered ping)
+    pub unresponsive: HashMap<u32, u32>,
+    /// Pings awaiting a reply (window -> timestamp sent, when)
+    pending: HashMap<u32, (u32, Instant)>,
+    /// When each window was last pinged
+    last_ping: HashMap<u32, Instant>,
 }
 
 impl TerminateManager {
     /// Create a new termination manager
     pub fn new() -> Self {
         Self {
-            unresponsive: std::collections::HashMap::new(),
+            unresponsive: HashMap::new(),
+            pending: HashMap::new(),
+            last_ping: HashMap::new(),
         }
     }
     
-    /// Show force quit dialog
-    pub fn show_force_quit_dialog(
-        &self,
+    /// Send a _NET_WM_PING unless one is already outstanding; `timestamp`
+    /// is the latest X server time, which the client echoes back
+    pub fn ping(
+        &mut self,
         conn: &RustConnection,
-        display_info: &DisplayInfo,
-        screen_info: &ScreenInfo,
+        atoms: &Atoms,
         window: u32,
+        timestamp: u32,
     ) -> Result<()> {
-        debug!("Showing force quit dialog for window {}", window);
-        
-        // TODO: Show force quit dialog
-        // This would typically use GTK or another UI toolkit
-        
+        if self.pending.contains_key(&window) {
+            return Ok(());
+        }
+        atoms.send_ping(conn, window, timestamp)?;
+        let now = Instant::now();
+        self.pending.insert(window, (timestamp, now));
+        self.last_ping.insert(window, now);
         Ok(())
     }
     
+    /// Whether `window` was not pinged within `interval`
+    pub fn ping_due(&self, window: u32, interval: Duration) -> bool {
+        self.last_ping.get(&window).is_none_or(|last| last.elapsed() >= interval)
+    }
+    
+    /// A client answered a ping; returns true if it was marked unresponsive
+    pub fn handle_pong(&mut self, window: u32, timestamp: u32) -> bool {
+        if self.pending.get(&window).is_some_and(|&(sent, _)| sent == timestamp) {
+            self.pending.remove(&window);
+        }
+        // Any reply, even to an old ping, shows the client is processing events
+        if self.is_unresponsive(window) {
+            info!("Window {} is responding again", window);
+            self.mark_responsive(window);
+            return true;
+        }
+        false
+    }
+    
+    /// Mark windows whose ping went unanswered for `timeout`; returns the newly unresponsive ones
+    pub fn check_timeouts(&mut self, timeout: Duration) -> Vec<u32> {
+        let expired: Vec<(u32, u32)> = self
+            .pending
+            .iter()
+            .filter(|(_, (_, sent))| sent.elapsed() >= timeout)
+            .map(|(&window, &(timestamp, _))| (window, timestamp))
+            .collect();
+        let mut newly = Vec::new();
+        for (window, timestamp) in expired {
+            self.pending.remove(&window);
+            if !self.is_unresponsive(window) {
+                warn!("Window {} did not answer _NET_WM_PING within {:?}", window, timeout);
+                newly.push(window);
+            }
+            self.mark_unresponsive(window, timestamp);
+        }
+        newly
+    }
+    
+    /// Drop all state of a window that is no longer managed
+    pub fn forget(&mut self, window: u32) {
+        self.unresponsive.remove(&window);
+        self.pending.remove(&window);
+        self.last_ping.remove(&window);
+    }
+    
     /// Force kill a window
+    ///
+    /// XKillClient disconnects the client, which ends most X programs. With
+    /// `signal` the process behind the window also gets SIGTERM (SIGKILL if
+    /// it is still alive after `KILL_GRACE`), provided `_NET_WM_PID` and
+    /// WM_CLIENT_MACHINE show it runs on this machine.
     pub fn force_kill(
         &self,
         conn: &RustConnection,
+        atoms: &Atoms,
         window: u32,
+        signal: bool,
     ) -> Result<()> {
-        debug!("Force killing window {}", window);
+        debug!("Force killing window {} (signal={})", window, signal);
+        
+        // Read the PID first, the properties are gone once the client is killed
+        let pid = if signal { local_pid(conn, atoms, window)? } else { None };
         
         // Use XKillClient (via x11rb)
         // Note: x11rb doesn't have XKillClient directly, so we use KillClient request
         conn.kill_client(window)?;
         conn.flush()?;
         
+        if signal {
+            let Some(pid) = pid else {
+                bail!("killed the X client of window {}, but it has no _NET_WM_PID on this machine to signal", window);
+            };
+            terminate_process(pid)?;
+        }
         Ok(())
     }
     
@@ -83,5 +160,72 @@ impl Default for TerminateManager {
     }
 }
 
+/// Process owning a window, if `_NET_WM_PID` is set and WM_CLIENT_MACHINE
+/// names this machine (a PID from another host means nothing here)
+pub fn local_pid(conn: &RustConnection, atoms: &Atoms, window: u32) -> Result<Option<u32>> {
+    let pid = conn
+        .get_property(false, window, atoms._net_wm_pid, AtomEnum::CARDINAL, 0, 1)?
+        .reply()?
+        .value32()
+        .and_then(|mut values| values.next());
+    let machine = conn
+        .get_property(false, window, AtomEnum::WM_CLIENT_MACHINE, AtomEnum::STRING, 0, 256)?
+        .reply()?
+        .value;
+    let machine = String::from_utf8_lossy(&machine).trim_end_matches('\0').to_string();
+    let Some(pid) = pid.filter(|&pid| pid > 1 && pid != std::process::id()) else {
+        return Ok(None);
+    };
+    if !is_local_machine(&machine) {
+        debug!("Window {} belongs to {:?}, not signalling PID {}", window, machine, pid);
+        return Ok(None);
+    }
+    Ok(Some(pid))
+}
 
+/// Whether a WM_CLIENT_MACHINE value names this host (short or fully qualified)
+fn is_local_machine(machine: &str) -> bool {
+    let Ok(hostname) = std::fs::read_to_string("/proc/sys/kernel/hostname") else {
+        return false;
+    };
+    let short = |name: &str| name.split('.').next().unwrap_or_default().to_ascii_lowercase();
+    let hostname = hostname.trim();
+    !machine.is_empty() && (machine.eq_ignore_ascii_case(hostname) || short(machine) == short(hostname))
+}
 
+/// Start time of a process (clock ticks since boot), to detect PID reuse
+fn process_start_time(pid: u32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // Fields after the parenthesized command name; starttime is field 22 overall
+    stat.rsplit_once(')')?.1.split_whitespace().nth(19)?.parse().ok()
+}
+
+/// Send a signal with kill(1)
+fn send_signal(pid: u32, signal: &str) -> Result<()> {
+    let status = std::process::Command::new("kill")
+        .arg(format!("-{}", signal))
+        .arg(pid.to_string())
+        .status()
+        .context("failed to run kill")?;
+    if !status.success() {
+        bail!("kill -{} {} failed ({})", signal, pid, status);
+    }
+    Ok(())
+}
+
+/// SIGTERM a process, then SIGKILL it if the same process is still alive after `KILL_GRACE`
+fn terminate_process(pid: u32) -> Result<()> {
+    let started = process_start_time(pid).with_context(|| format!("process {} is not running", pid))?;
+    info!("Sending SIGTERM to process {}", pid);
+    send_signal(pid, "TERM")?;
+    std::thread::spawn(move || {
+        std::thread::sleep(KILL_GRACE);
+        if process_start_time(pid) == Some(started) {
+            warn!("Process {} ignored SIGTERM, sending SIGKILL", pid);
+            if let Err(e) = send_signal(pid, "KILL") {
+                warn!("{:#}", e);
+            }
+        }
+    });
+    Ok(())
+}
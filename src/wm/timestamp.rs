@@ -0,0 +1,173 @@
+//! X server time
+//!
+//! Requests that take a timestamp (SetInputFocus, SetSelectionOwner,
+//! _NET_WM_PING) should carry the server time of the event that led to
+//! them rather than CURRENT_TIME: the server then ignores a request older
+//! than the last one of its kind, so a focus change decided on a stale
+//! event cannot override a newer one, and selection owners can tell which
+//! claim is newer. `ServerTime` keeps the latest time seen in an event;
+//! the WM's own root property writes come back as PropertyNotify, so it
+//! stays close to the server clock while anything happens. The clock is in
+//! milliseconds and wraps after ~49.7 days, so times compare modulo 2^32.
+//!
+//! A focus change uses the time of the event that caused it. One made
+//! without such an event (IPC, D-Bus, map-time activation) sends
+//! CURRENT_TIME: the latest time seen may predate a focus change a client
+//! made since, and the server would ignore the request.
+
+use anyhow::{Context, Result};
+use tracing::debug;
+use x11rb::connection::Connection;
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+/// Latest X server times seen
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ServerTime {
+    /// Latest time of any event (0: none seen yet)
+    last: u32,
+    /// Latest key or button press (0: none seen yet)
+    last_input: u32,
+    /// Time of the event being handled, if it carries one
+    event: Option<u32>,
+}
+
+impl ServerTime {
+    /// Note the time an event carries
+    pub fn observe(&mut self, event: &Event) {
+        let (time, input) = match event {
+            Event::KeyPress(e) => (e.time, true),
+            Event::ButtonPress(e) => (e.time, true),
+            Event::KeyRelease(e) => (e.time, false),
+            Event::ButtonRelease(e) => (e.time, false),
+            Event::MotionNotify(e) => (e.time, false),
+            Event::EnterNotify(e) => (e.time, false),
+            Event::LeaveNotify(e) => (e.time, false),
+            Event::PropertyNotify(e) => (e.time, false),
+            Event::SelectionClear(e) => (e.time, false),
+            Event::XfixesSelectionNotify(e) => (e.timestamp, false),
+            // Client messages and selection requests carry client-chosen times
+            _ => {
+                self.event = None;
+                return;
+            }
+        };
+        self.event = (time != x11rb::CURRENT_TIME).then_some(time);
+        self.advance(time, input);
+    }
+
+    /// The event passed to `observe` has been handled
+    pub fn end_event(&mut self) {
+        self.event = None;
+    }
+
+    fn advance(&mut self, time: u32, input: bool) {
+        if time == x11rb::CURRENT_TIME {
+            return;
+        }
+        if is_later(time, self.last) {
+            self.last = time;
+        }
+        if input && is_later(time, self.last_input) {
+            self.last_input = time;
+        }
+    }
+
+    /// Timestamp for a request: the latest server time seen (CURRENT_TIME
+    /// until the first event)
+    pub fn now(&self) -> u32 {
+        self.last
+    }
+
+    /// Timestamp for a request made on behalf of the event being handled:
+    /// its time, or CURRENT_TIME outside of an event or for one without a time
+    pub fn of_event(&self) -> u32 {
+        self.event.unwrap_or(x11rb::CURRENT_TIME)
+    }
+
+    /// Whether `time` (e.g. a window's _NET_WM_USER_TIME) is not older than
+    /// the last key or button press
+    pub fn since_last_input(&self, time: u32) -> bool {
+        time == self.last_input || is_later(time, self.last_input)
+    }
+
+    /// Ask the server for its time by touching a property of `window`
+    /// (which must select PropertyChange) and waiting for the notification
+    ///
+    /// Other events read meanwhile are dropped, so this is only for startup,
+    /// before the WM selects events on the root window.
+    pub fn fetch(&mut self, conn: &RustConnection, window: Window) -> Result<u32> {
+        let atom = crate::wm::atom_cache::intern(conn, "_AREA_TIMESTAMP")?;
+        conn.change_property8(PropMode::APPEND, window, atom, AtomEnum::STRING, &[])?;
+        conn.flush()?;
+        loop {
+            let event = conn.wait_for_event().context("failed to read the server time")?;
+            if let Event::PropertyNotify(e) = &event
+                && e.window == window
+                && e.atom == atom
+            {
+                self.advance(e.time, false);
+                debug!("X server time {}", e.time);
+                return Ok(e.time);
+            }
+        }
+    }
+}
+
+/// Whether server time `a` is later than `b` (any time is later than 0)
+pub fn is_later(a: u32, b: u32) -> bool {
+    b == x11rb::CURRENT_TIME || (a.wrapping_sub(b) as i32) > 0
+}
+
+/// A window's _NET_WM_USER_TIME, read from its _NET_WM_USER_TIME_WINDOW
+/// when it has one
+pub fn user_time(conn: &RustConnection, window: Window) -> Result<Option<u32>> {
+    let first = |window: Window, name: &str, type_: AtomEnum| -> Result<Option<u32>> {
+        let atom = crate::wm::atom_cache::intern(conn, name)?;
+        let reply = conn.get_property(false, window, atom, type_, 0, 1)?.reply()?;
+        Ok(reply.value32().and_then(|mut values| values.next()))
+    };
+    let time_window = first(window, "_NET_WM_USER_TIME_WINDOW", AtomEnum::WINDOW)?.unwrap_or(window);
+    first(time_window, "_NET_WM_USER_TIME", AtomEnum::CARDINAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_across_the_wrap() {
+        assert!(is_later(5, u32::MAX - 5));
+        assert!(!is_later(u32::MAX - 5, 5));
+        assert!(is_later(0x8000_0000, 1));
+        assert!(!is_later(0x8000_0001, 1));
+        assert!(!is_later(7, 7));
+        assert!(is_later(1, x11rb::CURRENT_TIME));
+    }
+
+    #[test]
+    fn advances_across_the_wrap() {
+        let mut time = ServerTime::default();
+        time.advance(u32::MAX - 1, true);
+        time.advance(2, false);
+        assert_eq!(time.now(), 2);
+        assert!(time.since_last_input(3));
+        assert!(!time.since_last_input(u32::MAX - 2));
+        time.advance(u32::MAX, false);
+        assert_eq!(time.now(), 2);
+    }
+
+    #[test]
+    fn event_time_only_while_handling_it() {
+        let mut time = ServerTime::default();
+        assert_eq!(time.of_event(), x11rb::CURRENT_TIME);
+        time.observe(&Event::KeyPress(KeyPressEvent { time: 42, ..Default::default() }));
+        assert_eq!(time.of_event(), 42);
+        time.end_event();
+        assert_eq!((time.of_event(), time.now()), (x11rb::CURRENT_TIME, 42));
+        time.observe(&Event::MapRequest(MapRequestEvent::default()));
+        assert_eq!(time.of_event(), x11rb::CURRENT_TIME);
+    }
+}
@@ -2,17 +2,66 @@
 //!
 //! Manages transient windows, modal dialogs, and window groups.
 //! This matches xfwm4's transient window management.
+//!
+//! The live WM keeps `WM_TRANSIENT_FOR` in `Client::transient_for`; the
+//! free functions below answer questions about it over the client map.
 
 use anyhow::Result;
+use std::collections::HashMap;
 use tracing::{debug, info, warn};
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::*;
 use x11rb::rust_connection::RustConnection;
 
+use crate::shared::Geometry;
 use crate::wm::client::Client;
-use crate::wm::client_flags::ClientFlags;
+use crate::wm::client_flags::{ClientFlags, WindowType};
 use crate::wm::screen::ScreenInfo;
 
+/// Position centering a window of the given size over its parent's client area
+pub fn center_over(parent: &Geometry, width: u32, height: u32) -> (i32, i32) {
+    (
+        parent.x + (parent.width as i32 - width as i32) / 2,
+        parent.y + (parent.height as i32 - height as i32) / 2,
+    )
+}
+
+/// Whether a window is a modal dialog
+pub fn is_modal(client: &Client) -> bool {
+    client.type_ == WindowType::ModalDialog || client.flags.contains(ClientFlags::STATE_MODAL)
+}
+
+/// Innermost shown modal dialog blocking a window, if any
+///
+/// A modal dialog of that modal blocks it in turn, so focus goes to the
+/// last one in the chain.
+pub fn blocking_modal(clients: &HashMap<u32, Client>, window: u32) -> Option<u32> {
+    let mut modal = None;
+    let mut blocked = window;
+    // Bounded, in case of a transient loop
+    for _ in 0..clients.len() {
+        let Some(next) = clients
+            .values()
+            .find(|c| c.transient_for == Some(blocked) && is_modal(c) && c.mapped() && !c.is_minimized())
+        else {
+            break;
+        };
+        modal = Some(next.window);
+        blocked = next.window;
+    }
+    modal
+}
+
+/// Windows of a group: the leader (if managed) and every window naming it
+/// in `WM_HINTS` window_group
+pub fn group_members(clients: &HashMap<u32, Client>, leader: u32) -> Vec<u32> {
+    clients
+        .values()
+        .filter(|c| c.window == leader || c.group_leader == Some(leader))
+        .map(|c| c.window)
+        .collect()
+}
+
 /// Transient manager
 pub struct TransientManager {
     /// Transient relationships (child -> parent)
@@ -0,0 +1,140 @@
+//! Keyboard window management mode
+//!
+//! Super+W enters a modal state (like ratpoison's prefix key) where single
+//! keys move, resize, tile and switch windows, so everything the mouse does
+//! to windows is reachable from the keyboard. The WM holds a keyboard grab
+//! while the mode is on, and the compositor numbers the windows of the
+//! workspace, outlines the focused one and shows a legend of the keys.
+
+use std::collections::HashMap;
+
+use crate::compositor::hints::Hint;
+use crate::shared::Geometry;
+use crate::wm::client::Client;
+use crate::wm::client_flags::TilePosition;
+
+/// Keysyms the mode reacts to
+const XK_TAB: u32 = 0xff09;
+const XK_RETURN: u32 = 0xff0d;
+const XK_ESCAPE: u32 = 0xff1b;
+const XK_LEFT: u32 = 0xff51;
+const XK_UP: u32 = 0xff52;
+const XK_RIGHT: u32 = 0xff53;
+const XK_DOWN: u32 = 0xff54;
+const XK_BRACKETLEFT: u32 = 0x005b;
+const XK_BRACKETRIGHT: u32 = 0x005d;
+
+/// Keys of the mode, shown at the bottom of the focused monitor
+pub const LEGEND: &str = "HJKL MOVE  SHIFT+HJKL RESIZE  [ ] TILE  F MAX  N MIN  Q CLOSE  TAB/1-9 FOCUS  ESC DONE";
+
+/// Number of windows that get a digit
+const MAX_TARGETS: usize = 9;
+
+/// Distance of hints from the corner they label (px)
+const HINT_INSET: i32 = 8;
+
+/// What a key does in window management mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeAction {
+    /// Move the focused window by this many steps horizontally and vertically
+    Move(i32, i32),
+    /// Grow (positive) or shrink the focused window by steps
+    Resize(i32, i32),
+    Tile(TilePosition),
+    ToggleMaximize,
+    Minimize,
+    Close,
+    /// Focus the next window in stacking order
+    FocusNext { backward: bool },
+    /// Focus the window with this number (0-based)
+    Focus(usize),
+    /// Leave the mode
+    Exit,
+}
+
+/// The action of a key (by its unshifted keysym)
+pub fn action(keysym: u32, shift: bool) -> Option<ModeAction> {
+    let direction = match keysym {
+        0x0068 | XK_LEFT => Some((-1, 0)),  // h
+        0x006a | XK_DOWN => Some((0, 1)),   // j
+        0x006b | XK_UP => Some((0, -1)),    // k
+        0x006c | XK_RIGHT => Some((1, 0)),  // l
+        _ => None,
+    };
+    if let Some((dx, dy)) = direction {
+        return Some(if shift { ModeAction::Resize(dx, dy) } else { ModeAction::Move(dx, dy) });
+    }
+    Some(match keysym {
+        XK_BRACKETLEFT => ModeAction::Tile(TilePosition::Left),
+        XK_BRACKETRIGHT => ModeAction::Tile(TilePosition::Right),
+        0x0066 => ModeAction::ToggleMaximize, // f
+        0x006e => ModeAction::Minimize,       // n
+        0x0071 => ModeAction::Close,          // q
+        XK_TAB => ModeAction::FocusNext { backward: shift },
+        0x0031..=0x0039 => ModeAction::Focus((keysym - 0x0031) as usize),
+        XK_ESCAPE | XK_RETURN => ModeAction::Exit,
+        _ => return None,
+    })
+}
+
+/// State of the mode
+#[derive(Debug, Default)]
+pub struct WindowMode {
+    pub active: bool,
+    /// Windows of the workspace, topmost first; the first nine are numbered
+    targets: Vec<u32>,
+}
+
+impl WindowMode {
+    /// Renumber the windows (visible ones in `stacking`, bottom to top)
+    pub fn set_targets(&mut self, stacking: &[u32], visible: impl Fn(u32) -> bool) {
+        self.targets = stacking.iter().rev().copied().filter(|&id| visible(id)).collect();
+    }
+
+    /// Window with a number
+    pub fn target(&self, index: usize) -> Option<u32> {
+        self.targets.get(index).filter(|_| index < MAX_TARGETS).copied()
+    }
+
+    /// Window after (or before) `current` in stacking order, wrapping around
+    pub fn next(&self, current: Option<u32>, backward: bool) -> Option<u32> {
+        let len = self.targets.len();
+        let position = current.and_then(|id| self.targets.iter().position(|&t| t == id));
+        let index = match (position, backward) {
+            (Some(i), false) => (i + 1) % len.max(1),
+            (Some(i), true) => (i + len - 1) % len.max(1),
+            (None, _) => 0,
+        };
+        self.targets.get(index).copied()
+    }
+
+    /// Window numbers and the legend (at `legend_at`)
+    pub fn hints(&self, clients: &HashMap<u32, Client>, legend_at: (i32, i32)) -> Vec<Hint> {
+        let mut hints: Vec<Hint> = self.targets.iter().take(MAX_TARGETS).enumerate()
+            .filter_map(|(i, id)| {
+                let frame = clients.get(id)?.frame_geometry();
+                Some(Hint { x: frame.x + HINT_INSET, y: frame.y + HINT_INSET, label: (i + 1).to_string() })
+            })
+            .collect();
+        hints.push(Hint { x: legend_at.0, y: legend_at.1, label: LEGEND.to_string() });
+        hints
+    }
+}
+
+/// Geometry of a window moved or resized by steps of `step` pixels
+pub fn stepped(geometry: Geometry, action: ModeAction, step: u32) -> Option<Geometry> {
+    let step = step as i32;
+    let mut g = geometry;
+    match action {
+        ModeAction::Move(dx, dy) => {
+            g.x += dx * step;
+            g.y += dy * step;
+        }
+        ModeAction::Resize(dw, dh) => {
+            g.width = (g.width as i32 + dw * step).max(step) as u32;
+            g.height = (g.height as i32 + dh * step).max(step) as u32;
+        }
+        _ => return None,
+    }
+    Some(g)
+}
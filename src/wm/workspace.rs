@@ -2,32 +2,60 @@
 //!
 //! Manages virtual desktops/workspaces, workspace switching, and sticky windows.
 //! This matches xfwm4's workspace management system.
+//!
+//! Windows on other workspaces are unmapped (their frames, for framed
+//! windows) and marked unmapped; visibility changes are returned to the
+//! caller, which keeps the compositor in sync.
+//!
+//! Each workspace remembers the window last focused on it and has a policy
+//! for which window gets focus when it is switched to.
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use tracing::{debug, info, warn};
-use x11rb::connection::Connection;
 use x11rb::protocol::xproto::*;
 use x11rb::rust_connection::RustConnection;
 use x11rb::wrapper::ConnectionExt as _;
 
 use crate::wm::client::Client;
-use crate::wm::display::DisplayInfo;
+use crate::wm::client_flags::WindowType;
 use crate::wm::ewmh::Atoms;
-use crate::wm::screen::ScreenInfo;
 
 /// Workspace manager
 pub struct WorkspaceManager {
     /// Current workspace index (0-based)
     pub current_workspace: u32,
-    
+
     /// Number of workspaces
     pub workspace_count: u32,
-    
+
     /// Workspace names
     pub workspace_names: Vec<String>,
-    
+
     /// Desktop layout
     pub desktop_layout: DesktopLayout,
+
+    /// Window focused when each workspace is switched to
+    focus_policies: Vec<WorkspaceFocus>,
+
+    /// Window last focused on each workspace
+    last_focused: Vec<Option<u32>>,
+}
+
+/// Which window gets focus after switching to a workspace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspaceFocus {
+    /// The window focused when the workspace was left, else the topmost
+    #[default]
+    LastFocused,
+    /// The topmost window
+    Topmost,
+    /// The window under the pointer
+    UnderPointer,
+    /// No window (focus goes to the root window)
+    None,
 }
 
 /// Desktop layout (EWMH _NET_DESKTOP_LAYOUT)
@@ -45,138 +73,214 @@ pub const ALL_WORKSPACES: u32 = 0xFFFFFFFF;
 impl WorkspaceManager {
     /// Create a new workspace manager
     pub fn new(workspace_count: u32) -> Self {
+        let workspace_count = workspace_count.max(1);
         let workspace_names = (0..workspace_count)
             .map(|i| format!("Workspace {}", i + 1))
             .collect();
-        
+        let workspaces = workspace_count as usize;
+
         Self {
             current_workspace: 0,
             workspace_count,
             workspace_names,
             desktop_layout: DesktopLayout {
                 orientation: 0, // horizontal
-                columns: 2,
-                rows: 2,
+                columns: workspace_count,
+                rows: 1,
                 starting_corner: 0,
             },
+            focus_policies: vec![WorkspaceFocus::default(); workspaces],
+            last_focused: vec![None; workspaces],
         }
     }
-    
+
     /// Switch to a workspace
+    ///
+    /// Returns the windows whose visibility changed (client window, now visible).
     pub fn switch_workspace(
         &mut self,
         conn: &RustConnection,
-        display_info: &DisplayInfo,
-        screen_info: &ScreenInfo,
+        atoms: &Atoms,
+        root: Window,
         workspace: u32,
         clients: &mut std::collections::HashMap<u32, Client>,
-    ) -> Result<()> {
+    ) -> Result<Vec<(u32, bool)>> {
         if workspace >= self.workspace_count {
             warn!("Invalid workspace index: {} (max: {})", workspace, self.workspace_count - 1);
-            return Ok(());
+            return Ok(Vec::new());
         }
-        
+
         if workspace == self.current_workspace {
             debug!("Already on workspace {}", workspace);
-            return Ok(());
+            return Ok(Vec::new());
         }
-        
+
         info!("Switching from workspace {} to {}", self.current_workspace, workspace);
-        
+
         let old_workspace = self.current_workspace;
         self.current_workspace = workspace;
-        
+
         // Show/hide windows based on workspace
-        self.update_window_visibility(conn, clients, old_workspace, workspace)?;
-        
+        let changed = self.update_window_visibility(conn, clients, old_workspace, workspace)?;
+
         // Update EWMH properties
-        self.update_ewmh_properties(conn, display_info, screen_info)?;
-        
-        Ok(())
+        self.update_ewmh_properties(conn, atoms, root)?;
+
+        Ok(changed)
     }
-    
+
     /// Move window to a workspace
+    ///
+    /// Returns the new visibility if the window was shown or hidden.
     pub fn move_window_to_workspace(
         &mut self,
         conn: &RustConnection,
-        display_info: &DisplayInfo,
-        screen_info: &ScreenInfo,
+        atoms: &Atoms,
         client: &mut Client,
         workspace: u32,
-    ) -> Result<()> {
+    ) -> Result<Option<bool>> {
         if workspace != ALL_WORKSPACES && workspace >= self.workspace_count {
             warn!("Invalid workspace index: {} (max: {})", workspace, self.workspace_count - 1);
-            return Ok(());
+            return Ok(None);
         }
-        
+
         debug!("Moving window {} to workspace {}", client.window, workspace);
-        
+
+        let was_visible = self.is_visible(client);
         client.win_workspace = workspace;
-        
-        // Update visibility if not on current workspace
-        if workspace != ALL_WORKSPACES && workspace != self.current_workspace {
-            // Hide window
-            if let Some(frame) = &client.frame {
-                conn.unmap_window(frame.frame)?;
-            } else {
-                conn.unmap_window(client.window)?;
-            }
-        } else {
-            // Show window
-            if let Some(frame) = &client.frame {
-                conn.map_window(frame.frame)?;
-            } else {
-                conn.map_window(client.window)?;
-            }
+        let visible = self.is_visible(client);
+
+        // Update visibility if it changed
+        if visible != was_visible && !client.is_minimized() {
+            set_window_visible(conn, client, visible)?;
         }
-        
+
         // Update _NET_WM_DESKTOP
         conn.change_property32(
             PropMode::REPLACE,
             client.window,
-            display_info.atoms.net_wm_desktop,
+            atoms.net_wm_desktop,
             AtomEnum::CARDINAL,
             &[workspace],
         )?;
-        
-        Ok(())
+
+        Ok((visible != was_visible && !client.is_minimized()).then_some(visible))
     }
-    
+
     /// Set workspace count
     pub fn set_workspace_count(
         &mut self,
         conn: &RustConnection,
-        display_info: &DisplayInfo,
-        screen_info: &ScreenInfo,
+        atoms: &Atoms,
+        root: Window,
         count: u32,
     ) -> Result<()> {
         if count == 0 {
             warn!("Cannot set workspace count to 0");
             return Ok(());
         }
-        
+
         info!("Setting workspace count to {}", count);
-        
+
         // Adjust current workspace if needed
         if self.current_workspace >= count {
             self.current_workspace = count - 1;
         }
-        
+
         // Update workspace names
         while self.workspace_names.len() < count as usize {
             let idx = self.workspace_names.len();
             self.workspace_names.push(format!("Workspace {}", idx + 1));
         }
         self.workspace_names.truncate(count as usize);
-        
+        self.focus_policies.resize(count as usize, WorkspaceFocus::default());
+        self.last_focused.resize(count as usize, None);
+
         self.workspace_count = count;
-        
+        self.desktop_layout.columns = count;
+
         // Update EWMH properties
-        self.update_ewmh_properties(conn, display_info, screen_info)?;
-        
+        self.update_ewmh_properties(conn, atoms, root)?;
+
         Ok(())
     }
-    
+
+    /// Set which window a workspace focuses when switched to
+    pub fn set_focus_policy(&mut self, workspace: u32, policy: WorkspaceFocus) {
+        if let Some(slot) = self.focus_policies.get_mut(workspace as usize) {
+            *slot = policy;
+        }
+    }
+
+    pub fn focus_policy(&self, workspace: u32) -> WorkspaceFocus {
+        self.focus_policies.get(workspace as usize).copied().unwrap_or_default()
+    }
+
+    /// Record a window focused on the current workspace
+    pub fn note_focus(&mut self, window: u32) {
+        if let Some(slot) = self.last_focused.get_mut(self.current_workspace as usize) {
+            *slot = Some(window);
+        }
+    }
+
+    /// Window last focused on a workspace
+    pub fn last_focused(&self, workspace: u32) -> Option<u32> {
+        self.last_focused.get(workspace as usize).copied().flatten()
+    }
+
+    /// Drop a window that is no longer managed
+    pub fn forget(&mut self, window: u32) {
+        for slot in &mut self.last_focused {
+            if *slot == Some(window) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Window to focus on the current workspace after a switch, following
+    /// its policy (`stacking` bottom to top, `pointer` for `UnderPointer`)
+    pub fn focus_target(&self, clients: &HashMap<u32, Client>, stacking: &[u32], pointer: Option<(i32, i32)>) -> Option<u32> {
+        let focusable = |id: &u32| {
+            clients.get(id).is_some_and(|c| {
+                self.is_visible(c) && !c.is_minimized() && !matches!(c.type_, WindowType::Desktop | WindowType::Dock)
+            })
+        };
+        let mut topmost = stacking.iter().rev().filter(|id| focusable(id));
+        match self.focus_policy(self.current_workspace) {
+            WorkspaceFocus::LastFocused => self.last_focused
+                .get(self.current_workspace as usize)
+                .copied()
+                .flatten()
+                .filter(focusable)
+                .or_else(|| topmost.next().copied()),
+            WorkspaceFocus::Topmost => topmost.next().copied(),
+            WorkspaceFocus::UnderPointer => {
+                let (x, y) = pointer?;
+                topmost
+                    .find(|id| {
+                        let g = clients[id].frame_geometry();
+                        x >= g.x && x < g.x + g.width as i32 && y >= g.y && y < g.y + g.height as i32
+                    })
+                    .copied()
+            }
+            WorkspaceFocus::None => None,
+        }
+    }
+
+    /// Whether a window belongs on the current workspace
+    pub fn is_visible(&self, client: &Client) -> bool {
+        client.is_sticky() || client.win_workspace == self.current_workspace
+    }
+
+    /// Workspace next to the current one in a row (no wrapping)
+    pub fn adjacent(&self, forward: bool) -> Option<u32> {
+        if forward {
+            (self.current_workspace + 1 < self.workspace_count).then_some(self.current_workspace + 1)
+        } else {
+            self.current_workspace.checked_sub(1)
+        }
+    }
+
     /// Update window visibility based on workspace
     fn update_window_visibility(
         &self,
@@ -184,94 +288,98 @@ impl WorkspaceManager {
         clients: &mut std::collections::HashMap<u32, Client>,
         old_workspace: u32,
         new_workspace: u32,
-    ) -> Result<()> {
+    ) -> Result<Vec<(u32, bool)>> {
+        let mut changed = Vec::new();
         for client in clients.values_mut() {
             let ws = client.win_workspace;
-            
-            // Sticky windows (ALL_WORKSPACES) are always visible
-            if ws == ALL_WORKSPACES {
+
+            // Sticky windows are always visible, minimized ones stay hidden
+            if client.is_sticky() || client.is_minimized() {
                 continue;
             }
-            
+
             // Hide windows from old workspace
             if ws == old_workspace {
-                if let Some(frame) = &client.frame {
-                    conn.unmap_window(frame.frame)?;
-                } else {
-                    conn.unmap_window(client.window)?;
-                }
+                set_window_visible(conn, client, false)?;
+                changed.push((client.window, false));
             }
-            
+
             // Show windows for new workspace
             if ws == new_workspace {
-                if let Some(frame) = &client.frame {
-                    conn.map_window(frame.frame)?;
-                } else {
-                    conn.map_window(client.window)?;
-                }
+                set_window_visible(conn, client, true)?;
+                changed.push((client.window, true));
             }
         }
-        
-        Ok(())
+
+        Ok(changed)
     }
-    
+
     /// Update EWMH workspace properties
-    fn update_ewmh_properties(
+    pub fn update_ewmh_properties(
         &self,
         conn: &RustConnection,
-        display_info: &DisplayInfo,
-        screen_info: &ScreenInfo,
+        atoms: &Atoms,
+        root: Window,
     ) -> Result<()> {
         // Update _NET_NUMBER_OF_DESKTOPS
         conn.change_property32(
             PropMode::REPLACE,
-            screen_info.root,
-            display_info.atoms.net_number_of_desktops,
+            root,
+            atoms.net_number_of_desktops,
             AtomEnum::CARDINAL,
             &[self.workspace_count],
         )?;
-        
+
         // Update _NET_CURRENT_DESKTOP
         conn.change_property32(
             PropMode::REPLACE,
-            screen_info.root,
-            display_info.atoms.net_current_desktop,
+            root,
+            atoms.net_current_desktop,
             AtomEnum::CARDINAL,
             &[self.current_workspace],
         )?;
-        
+
         // Update _NET_DESKTOP_NAMES
         let names: Vec<u8> = self.workspace_names
             .iter()
             .flat_map(|name| name.as_bytes().iter().copied().chain(std::iter::once(0)))
             .collect();
-        
+
         conn.change_property8(
             PropMode::REPLACE,
-            screen_info.root,
-            display_info.atoms._net_desktop_names,
-            display_info.atoms._utf8_string,
+            root,
+            atoms._net_desktop_names,
+            atoms._utf8_string,
             &names,
         )?;
-        
+
         Ok(())
     }
-    
+
     /// Get current workspace
     pub fn get_current_workspace(&self) -> u32 {
         self.current_workspace
     }
-    
+
     /// Get workspace count
     pub fn get_workspace_count(&self) -> u32 {
         self.workspace_count
     }
-    
+
     /// Check if window is sticky (on all workspaces)
     pub fn is_sticky(&self, client: &Client) -> bool {
         client.win_workspace == ALL_WORKSPACES
     }
 }
 
-
-
+/// Map or unmap a window (its frame, if framed) for a workspace change
+fn set_window_visible(conn: &RustConnection, client: &mut Client, visible: bool) -> Result<()> {
+    let window = client.frame.as_ref().map(|f| f.frame).unwrap_or(client.window);
+    if visible {
+        conn.map_window(window)?;
+    } else {
+        conn.unmap_window(window)?;
+    }
+    client.set_mapped(visible);
+    Ok(())
+}
@@ -0,0 +1,62 @@
+//! Keyboard layout changes (XKB group switches)
+//!
+//! Each configured layout is an XKB group; switching layouts (e.g. with
+//! `setxkbmap -option grp:alt_shift_toggle`) changes the locked group of the
+//! core keyboard, which XKB reports as a StateNotify.
+
+use anyhow::Result;
+use tracing::debug;
+use x11rb::protocol::xkb::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::ConnectionExt as _;
+use x11rb::rust_connection::RustConnection;
+
+/// The core keyboard
+const CORE_KBD: xkb::DeviceSpec = 256;
+
+/// Active group of the core keyboard
+#[derive(Debug, Default)]
+pub struct LayoutWatcher {
+    group: u8,
+}
+
+impl LayoutWatcher {
+    /// Watch group changes of the core keyboard
+    pub fn init(&mut self, conn: &RustConnection) -> Result<()> {
+        if !conn.xkb_use_extension(1, 0)?.reply()?.supported {
+            anyhow::bail!("XKB unsupported");
+        }
+        let details = xkb::SelectEventsAux {
+            state_notify: Some(xkb::SelectEventsAuxStateNotify {
+                affect_state: xkb::StatePart::GROUP_STATE,
+                state_details: xkb::StatePart::GROUP_STATE,
+            }),
+            ..Default::default()
+        };
+        conn.xkb_select_events(
+            CORE_KBD,
+            xkb::EventType::from(0u16),
+            xkb::EventType::from(0u16),
+            xkb::MapPart::from(0u16),
+            xkb::MapPart::from(0u16),
+            &details,
+        )?;
+        self.group = u8::from(conn.xkb_get_state(CORE_KBD)?.reply()?.group);
+        Ok(())
+    }
+
+    /// Account a StateNotify; returns the new layout's name if the group changed
+    pub fn state_changed(&mut self, conn: &RustConnection, event: &xkb::StateNotifyEvent) -> Result<Option<String>> {
+        let group = u8::from(event.group);
+        if group == self.group {
+            return Ok(None);
+        }
+        self.group = group;
+        let names = conn.xkb_get_names(CORE_KBD, xkb::NameDetail::GROUP_NAMES)?.reply()?;
+        let name = match names.value_list.groups.as_ref().and_then(|groups| groups.get(group as usize)) {
+            Some(&atom) if atom != x11rb::NONE => String::from_utf8_lossy(&conn.get_atom_name(atom)?.reply()?.name).into_owned(),
+            _ => format!("Layout {}", group + 1),
+        };
+        debug!("Keyboard layout now {:?} (group {})", name, group);
+        Ok(Some(name))
+    }
+}
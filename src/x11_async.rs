@@ -1,15 +1,133 @@
 //! X11 Async Event Stream
 //!
 //! Provides non-blocking async X11 event polling using mio, following LeftWM's proven architecture.
+//! Failures are classified into `X11Error`s so the main loop can tell a lost
+//! connection (shut down) from a failed request (carry on).
+//! Buffered events are compressed before dispatch, see `compress_events`.
 
+use std::collections::HashSet;
+use std::fmt;
 use std::os::unix::io::AsRawFd;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use anyhow::{Context, Result};
 use tokio::sync::{Notify, oneshot};
+use x11rb::errors::{ConnectionError, ParseError, ReplyError, ReplyOrIdError};
 use x11rb::rust_connection::RustConnection;
 use x11rb::protocol::Event;
 
+/// An X11 failure, classified by what the main loop should do about it
+#[derive(Debug)]
+pub enum X11Error {
+    /// The server connection is gone (server exited, socket closed)
+    ConnectionLost(ConnectionError),
+    /// An extension is missing or sent data that cannot be parsed
+    Extension(ConnectionError),
+    /// A single request failed with an X error (e.g. BadWindow for a window
+    /// destroyed meanwhile)
+    Request(x11rb::x11_utils::X11Error),
+    /// Any other connection-level failure (request too long, out of ids, ...)
+    Other(String),
+}
+
+/// How the main loop reacts to an `X11Error`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Log and keep running
+    Continue,
+    /// Leave the main loop; nothing can be sent to or received from the server anymore
+    Shutdown,
+}
+
+impl X11Error {
+    /// What to do about this error
+    pub fn policy(&self) -> ErrorPolicy {
+        match self {
+            X11Error::ConnectionLost(_) => ErrorPolicy::Shutdown,
+            X11Error::Extension(_) | X11Error::Request(_) | X11Error::Other(_) => ErrorPolicy::Continue,
+        }
+    }
+
+    /// Classify an error from code using anyhow, by the first X11 error in its chain
+    pub fn classify(err: &anyhow::Error) -> Option<X11Error> {
+        err.chain().find_map(|cause| {
+            if let Some(e) = cause.downcast_ref::<ConnectionError>() {
+                Some(X11Error::from(clone_connection_error(e)))
+            } else if let Some(e) = cause.downcast_ref::<ReplyError>() {
+                Some(match e {
+                    ReplyError::ConnectionError(e) => X11Error::from(clone_connection_error(e)),
+                    ReplyError::X11Error(e) => X11Error::Request(e.clone()),
+                })
+            } else if let Some(e) = cause.downcast_ref::<ReplyOrIdError>() {
+                Some(match e {
+                    ReplyOrIdError::ConnectionError(e) => X11Error::from(clone_connection_error(e)),
+                    ReplyOrIdError::X11Error(e) => X11Error::Request(e.clone()),
+                    ReplyOrIdError::IdsExhausted => X11Error::Other(e.to_string()),
+                })
+            } else {
+                cause.downcast_ref::<ParseError>().map(|e| X11Error::from(*e))
+            }
+        })
+    }
+
+    /// Policy for an anyhow error; errors from outside X11 never stop the loop
+    pub fn policy_of(err: &anyhow::Error) -> ErrorPolicy {
+        Self::classify(err).map_or(ErrorPolicy::Continue, |e| e.policy())
+    }
+}
+
+/// `ConnectionError` is not `Clone` (it may hold an `io::Error`)
+fn clone_connection_error(err: &ConnectionError) -> ConnectionError {
+    match err {
+        ConnectionError::IoError(e) => ConnectionError::IoError(std::io::Error::new(e.kind(), e.to_string())),
+        ConnectionError::ParseError(e) => ConnectionError::ParseError(*e),
+        ConnectionError::UnsupportedExtension => ConnectionError::UnsupportedExtension,
+        ConnectionError::MaximumRequestLengthExceeded => ConnectionError::MaximumRequestLengthExceeded,
+        ConnectionError::FdPassingFailed => ConnectionError::FdPassingFailed,
+        ConnectionError::InsufficientMemory => ConnectionError::InsufficientMemory,
+        _ => ConnectionError::UnknownError,
+    }
+}
+
+impl From<ConnectionError> for X11Error {
+    fn from(err: ConnectionError) -> Self {
+        match err {
+            // x11rb gives up on the connection after any I/O error
+            ConnectionError::IoError(_) => X11Error::ConnectionLost(err),
+            ConnectionError::UnsupportedExtension | ConnectionError::ParseError(_) => X11Error::Extension(err),
+            _ => X11Error::Other(err.to_string()),
+        }
+    }
+}
+
+impl From<ParseError> for X11Error {
+    fn from(err: ParseError) -> Self {
+        X11Error::Extension(ConnectionError::ParseError(err))
+    }
+}
+
+impl From<ReplyError> for X11Error {
+    fn from(err: ReplyError) -> Self {
+        match err {
+            ReplyError::ConnectionError(e) => e.into(),
+            ReplyError::X11Error(e) => X11Error::Request(e),
+        }
+    }
+}
+
+impl fmt::Display for X11Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            X11Error::ConnectionLost(e) => write!(f, "X11 connection lost: {}", e),
+            X11Error::Extension(e) => write!(f, "X11 extension error: {}", e),
+            X11Error::Request(e) => write!(f, "X11 request failed: {:?} (major {}, minor {})", e.error_kind, e.major_opcode, e.minor_opcode),
+            X11Error::Other(e) => write!(f, "X11 error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for X11Error {}
+
 /// X11 event stream with async polling support
 ///
 /// Uses mio in a background thread to poll the X11 file descriptor and notify
@@ -74,13 +192,21 @@ impl X11EventStream {
     
     /// Non-blocking: poll for events (drains internal buffer)
     ///
-    /// Returns `Some(event)` if an event is available, `None` if the buffer is empty.
+    /// Returns `Some((event, raw))` if an event is available, `None` if the buffer is empty.
+    /// The raw wire bytes are kept alongside the parsed event so they can be
+    /// written to an event recording (see `replay`).
     /// This is non-blocking and should be called in a loop to drain all pending events.
-    pub fn poll_next_event(&self) -> Result<Option<Event>> {
-        use x11rb::connection::Connection;
-        Ok(self.conn.as_ref().poll_for_event()?)
+    pub fn poll_next_raw_event(&self) -> Result<Option<(Event, Vec<u8>)>, X11Error> {
+        use x11rb::connection::{Connection, RequestConnection};
+        match self.conn.as_ref().poll_for_raw_event()? {
+            Some(raw) => {
+                let event = self.conn.as_ref().parse_event(&raw)?;
+                Ok(Some((event, raw)))
+            }
+            None => Ok(None),
+        }
     }
-    
+
     /// Async wait for X11 FD to become readable
     ///
     /// Returns when the background mio thread detects that the X11 file descriptor
@@ -94,10 +220,144 @@ impl X11EventStream {
     ///
     /// Flushes all pending X11 requests to the server. Should be called at the
     /// start of each event loop iteration to batch requests (LeftWM pattern).
-    pub fn flush(&self) -> Result<()> {
+    pub fn flush(&self) -> Result<(), X11Error> {
         use x11rb::connection::Connection;
         self.conn.as_ref().flush()?;
         Ok(())
     }
 }
 
+
+/// An event waiting in the main loop's buffer
+pub struct QueuedEvent {
+    pub event: Event,
+    /// Wire bytes, written to the event recording once the event is handled
+    pub raw: Vec<u8>,
+    pub received: Instant,
+}
+
+/// What a compressible event is about; a later event with the same key
+/// carries everything an earlier one would have told
+#[derive(PartialEq, Eq, Hash)]
+enum CompressKey {
+    /// Pointer motion in a window with a given button/modifier state
+    Motion(u32, u16),
+    /// New geometry of a window, as seen by the window it was reported to
+    Configure(u32, u32),
+    /// Window contents need repainting
+    Expose(u32),
+}
+
+impl CompressKey {
+    /// `None` for events that must all be handled; such events also end a
+    /// run, so nothing is collapsed across e.g. a button release or an unmap
+    fn of(event: &Event) -> Option<Self> {
+        match event {
+            Event::MotionNotify(e) => Some(Self::Motion(e.event, u16::from(e.state))),
+            Event::ConfigureNotify(e) => Some(Self::Configure(e.event, e.window)),
+            Event::Expose(e) => Some(Self::Expose(e.window)),
+            _ => None,
+        }
+    }
+}
+
+/// Drop events superseded by a later one in the same run of motion,
+/// configure, expose and damage events
+///
+/// During a fast drag the server queues dozens of MotionNotify and
+/// ConfigureNotify events between two loop iterations; only the last of
+/// each matters. Returns the number of events dropped.
+pub fn compress_events(events: &mut Vec<QueuedEvent>) -> usize {
+    let mut seen = HashSet::new();
+    let mut keep = vec![true; events.len()];
+    for (i, queued) in events.iter().enumerate().rev() {
+        let event = &queued.event;
+        match CompressKey::of(event) {
+            Some(key) => keep[i] = seen.insert(key),
+            None if matches!(event, Event::DamageNotify(_)) => {}
+            None => seen.clear(),
+        }
+    }
+    let before = events.len();
+    let mut keep = keep.into_iter();
+    events.retain(|_| keep.next().unwrap_or(true));
+    before - events.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11rb::protocol::damage::NotifyEvent as DamageNotifyEvent;
+    use x11rb::protocol::xproto::{ButtonReleaseEvent, ConfigureNotifyEvent, ExposeEvent, MotionNotifyEvent};
+
+    fn queue(events: Vec<Event>) -> Vec<QueuedEvent> {
+        let received = Instant::now();
+        events.into_iter().map(|event| QueuedEvent { event, raw: Vec::new(), received }).collect()
+    }
+
+    /// Sequence numbers of the events left after compression
+    fn compressed(events: Vec<Event>) -> Vec<u16> {
+        let mut events = queue(events);
+        compress_events(&mut events);
+        events.iter().map(|queued| queued.event.wire_sequence_number().unwrap_or_default()).collect()
+    }
+
+    fn motion(sequence: u16, window: u32) -> Event {
+        Event::MotionNotify(MotionNotifyEvent { sequence, event: window, ..Default::default() })
+    }
+
+    fn configure(sequence: u16, event: u32, window: u32) -> Event {
+        Event::ConfigureNotify(ConfigureNotifyEvent { sequence, event, window, ..Default::default() })
+    }
+
+    fn expose(sequence: u16, window: u32) -> Event {
+        Event::Expose(ExposeEvent { sequence, window, ..Default::default() })
+    }
+
+    #[test]
+    fn keeps_last_of_each_key() {
+        let events = vec![motion(1, 10), configure(2, 10, 10), motion(3, 10), expose(4, 10), configure(5, 10, 10), expose(6, 10)];
+        assert_eq!(compressed(events), [3, 5, 6]);
+    }
+
+    #[test]
+    fn keys_are_per_event_and_window() {
+        let events = vec![
+            motion(1, 10),
+            motion(2, 20),
+            configure(3, 1, 10),
+            configure(4, 10, 10),
+            expose(5, 10),
+            expose(6, 20),
+        ];
+        assert_eq!(compressed(events), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn motion_state_is_part_of_the_key() {
+        let pressed = Event::MotionNotify(MotionNotifyEvent { sequence: 2, event: 10, state: 0x100u16.into(), ..Default::default() });
+        assert_eq!(compressed(vec![motion(1, 10), pressed, motion(3, 10)]), [2, 3]);
+    }
+
+    #[test]
+    fn other_events_end_a_run() {
+        let release = Event::ButtonRelease(ButtonReleaseEvent { sequence: 2, event: 10, ..Default::default() });
+        let events = vec![motion(1, 10), release, motion(3, 10), motion(4, 10)];
+        assert_eq!(compressed(events), [1, 2, 4]);
+    }
+
+    #[test]
+    fn damage_does_not_end_a_run() {
+        let damage = Event::DamageNotify(DamageNotifyEvent { sequence: 2, drawable: 10, ..Default::default() });
+        let again = Event::DamageNotify(DamageNotifyEvent { sequence: 4, drawable: 10, ..Default::default() });
+        let events = vec![expose(1, 10), damage, expose(3, 10), again];
+        assert_eq!(compressed(events), [2, 3, 4]);
+    }
+
+    #[test]
+    fn reports_dropped_count() {
+        let mut events = queue(vec![motion(1, 10), motion(2, 10), motion(3, 10)]);
+        assert_eq!(compress_events(&mut events), 2);
+        assert_eq!(events.len(), 1);
+    }
+}